@@ -14,6 +14,7 @@
 
 use std::cell::{RefCell, RefMut};
 use std::io;
+use std::time::Duration;
 
 use futures::Stream;
 use tokio_core::reactor::Core;
@@ -23,10 +24,10 @@ use client::{ClientHandle, BasicClientHandle, ClientConnection, ClientFuture};
 use client::SecureClientHandle;
 use error::*;
 use rr::{domain, DNSClass, IntoRecordSet, RecordType, Record};
-use rr::dnssec::Signer;
+use rr::dnssec::{Signer, TSigner};
 #[cfg(any(feature = "openssl", feature = "ring"))]
 use rr::dnssec::TrustAnchor;
-use op::Message;
+use op::{AnyRecords, Message, UpdateBuilder};
 
 /// Client trait which implements basic DNS Client operations.
 ///
@@ -71,6 +72,20 @@ pub trait Client<C: ClientHandle> {
         ))
     }
 
+    /// A query of type ANY, for all of the records of the name by their type, see
+    ///  `ClientHandle::query_any`, this should be used with a `TcpClientConnection`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the label to lookup
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    fn query_any(&self, name: &domain::Name, query_class: DNSClass) -> ClientResult<AnyRecords> {
+        self.get_io_loop().run(self.get_client_handle().query_any(
+            name.clone(),
+            query_class,
+        ))
+    }
+
     /// Sends a NOTIFY message to the remote system
     ///
     /// # Arguments
@@ -377,6 +392,16 @@ pub trait Client<C: ClientHandle> {
             dns_class,
         ))
     }
+
+    /// Sends the update, of any number of prerequisites and changes to a zone, which fails with
+    ///  `ClientErrorKind::Update` if the server did not apply it
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - the update, see `UpdateBuilder`
+    fn update(&self, update: &UpdateBuilder) -> ClientResult<Message> {
+        self.get_io_loop().run(self.get_client_handle().update(update))
+    }
 }
 
 /// The Client is abstracted over either trust_dns::tcp::TcpClientConnection or
@@ -430,6 +455,35 @@ impl SyncClient {
             io_loop: RefCell::new(io_loop),
         }
     }
+
+    /// Creates a new DNS client with the specified connection type and a TSIG signer.
+    ///
+    /// Updates and zone transfers are signed with the key shared with the server, and their
+    ///  responses must be signed by the server with the same key.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_connection` - the client_connection to use for all communication
+    /// * `tsigner` - the shared key to sign with
+    pub fn with_tsigner<CC: ClientConnection>(client_connection: CC, tsigner: TSigner) -> SyncClient
+    where
+        <CC as ClientConnection>::MessageStream: Stream<Item = Vec<u8>, Error = io::Error> + 'static,
+    {
+        let (io_loop, stream, stream_handle) = client_connection.unwrap();
+
+        let client = ClientFuture::with_tsigner(
+            stream,
+            stream_handle,
+            &io_loop.handle(),
+            Duration::from_secs(5),
+            tsigner,
+        );
+
+        SyncClient {
+            client_handle: RefCell::new(client),
+            io_loop: RefCell::new(io_loop),
+        }
+    }
 }
 
 impl Client<BasicClientHandle> for SyncClient {
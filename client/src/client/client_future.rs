@@ -9,18 +9,18 @@ use std::marker::PhantomData;
 use std::io;
 use std::time::Duration;
 
-use futures::Future;
+use futures::{future, Future};
 use futures::stream::Stream;
 use rand;
 use tokio_core::reactor::Handle;
-use trust_dns_proto::{BasicDnsHandle, DnsStreamHandle, DnsHandle, DnsFuture};
+use trust_dns_proto::{BasicDnsHandle, DnsStreamHandle, DnsHandle, DnsFuture, RetryPolicy};
 
 use client::ClientStreamHandle;
 use error::*;
-use op::{Message, MessageType, OpCode, Query, UpdateMessage};
-use rr::{domain, DNSClass, IntoRecordSet, RData, Record, RecordType};
-use rr::dnssec::Signer;
-use rr::rdata::NULL;
+use op::{AnyRecords, Message, MessageType, OpCode, Query, UpdateBuilder, UpdateError};
+use rr::{domain, DNSClass, IntoRecordSet, Record, RecordType};
+use rr::dnssec::{Signer, TSigner};
+use serialize::binary::NameCompression;
 
 /// A DNS Client implemented over futures-rs.
 ///
@@ -76,12 +76,83 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> ClientFuture<S> {
         timeout_duration: Duration,
         finalizer: Option<Signer>,
     ) -> BasicClientHandle {
-        let dns_future_handle = DnsFuture::with_timeout(
+        Self::with_name_compression(
             stream,
             stream_handle,
             loop_handle,
             timeout_duration,
             finalizer,
+            NameCompression::All,
+        )
+    }
+
+    /// Spawns a new ClientFuture Stream, which compresses only the names of the requests allowed
+    ///  by `name_compression`, for servers, or middleboxes on the way, which fail to decode
+    ///  pointers.
+    ///
+    /// See `with_timeout` for the other arguments.
+    pub fn with_name_compression(
+        stream: Box<Future<Item = S, Error = io::Error>>,
+        stream_handle: Box<DnsStreamHandle>,
+        loop_handle: &Handle,
+        timeout_duration: Duration,
+        finalizer: Option<Signer>,
+        name_compression: NameCompression,
+    ) -> BasicClientHandle {
+        Self::with_retry_policy(
+            stream,
+            stream_handle,
+            loop_handle,
+            RetryPolicy::new(timeout_duration),
+            finalizer,
+            name_compression,
+        )
+    }
+
+    /// Spawns a new ClientFuture Stream, which retransmits the requests which are not answered
+    ///  as the `retry_policy` allows, each time with a new query id. This is for datagram
+    ///  streams, e.g. `UdpClientStream`, over which requests and responses may be lost.
+    ///
+    /// See `with_name_compression` for the other arguments.
+    pub fn with_retry_policy(
+        stream: Box<Future<Item = S, Error = io::Error>>,
+        stream_handle: Box<DnsStreamHandle>,
+        loop_handle: &Handle,
+        retry_policy: RetryPolicy,
+        finalizer: Option<Signer>,
+        name_compression: NameCompression,
+    ) -> BasicClientHandle {
+        let dns_future_handle = DnsFuture::with_retry_policy(
+            stream,
+            stream_handle,
+            loop_handle,
+            retry_policy,
+            finalizer,
+            name_compression,
+        );
+
+        BasicClientHandle { message_sender: dns_future_handle }
+    }
+
+    /// Spawns a new ClientFuture Stream, which signs updates and zone transfers with the shared
+    ///  key of the `tsigner`, TSIG, and only accepts the responses to them signed by the server
+    ///  with the same key.
+    ///
+    /// See `with_timeout` for the other arguments.
+    pub fn with_tsigner(
+        stream: Box<Future<Item = S, Error = io::Error>>,
+        stream_handle: Box<DnsStreamHandle>,
+        loop_handle: &Handle,
+        timeout_duration: Duration,
+        tsigner: TSigner,
+    ) -> BasicClientHandle {
+        let dns_future_handle = DnsFuture::with_retry_policy(
+            stream,
+            stream_handle,
+            loop_handle,
+            RetryPolicy::new(timeout_duration),
+            Some(tsigner),
+            NameCompression::All,
         );
 
         BasicClientHandle { message_sender: dns_future_handle }
@@ -172,6 +243,26 @@ pub trait ClientHandle: Clone + DnsHandle<Error = ClientError> {
         self.lookup(query)
     }
 
+    /// A query of type ANY, for all of the records of the name by their type
+    ///
+    /// Most servers answer ANY over UDP minimally, per RFC 8482, see `AnyRecords::is_minimal`,
+    ///  this should be used with a client over TCP, e.g. of a `TcpClientStream`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the label to lookup
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    fn query_any(
+        &mut self,
+        name: domain::Name,
+        query_class: DNSClass,
+    ) -> Box<Future<Item = AnyRecords, Error = ClientError>> {
+        Box::new(
+            self.query(name, query_class, RecordType::ANY)
+                .map(AnyRecords::from),
+        )
+    }
+
 
 
     /// Sends a NOTIFY message to the remote system
@@ -276,7 +367,9 @@ pub trait ClientHandle: Clone + DnsHandle<Error = ClientError> {
 
         // add the notify message, see https://tools.ietf.org/html/rfc1996, section 3.7
         if let Some(rrset) = rrset {
-            message.add_answers(rrset.into_record_set());
+            if let Err(e) = message.answers_mut().insert_all(rrset.into_record_set()) {
+                return Box::new(future::err(e.into()));
+            }
         }
 
         Box::new(self.send(message).map_err(Into::into))
@@ -323,38 +416,14 @@ pub trait ClientHandle: Clone + DnsHandle<Error = ClientError> {
     where
         R: IntoRecordSet,
     {
-        // TODO: assert non-empty rrset?
         let rrset = rrset.into_record_set();
-        assert!(zone_origin.zone_of(rrset.name()));
-
-        // for updates, the query section is used for the zone
-        let mut zone: Query = Query::new();
-        zone.set_name(zone_origin)
-            .set_query_class(rrset.dns_class())
-            .set_query_type(RecordType::SOA);
-
-        // build the message
-        let mut message: Message = Message::new();
-        message
-            .set_id(rand::random())
-            .set_message_type(MessageType::Query)
-            .set_op_code(OpCode::Update)
-            .set_recursion_desired(false);
-        message.add_zone(zone);
+        let mut update = UpdateBuilder::new(zone_origin);
+        update
+            .set_dns_class(rrset.dns_class())
+            .rrset_not_exists(rrset.name().clone(), rrset.record_type())
+            .add(rrset);
 
-        let mut prerequisite = Record::with(rrset.name().clone(), rrset.record_type(), 0);
-        prerequisite.set_dns_class(DNSClass::NONE);
-        message.add_pre_requisite(prerequisite);
-        message.add_updates(rrset);
-
-        // Extended dns
-        {
-            let edns = message.edns_mut();
-            edns.set_max_payload(1500);
-            edns.set_version(0);
-        }
-
-        Box::new(self.send(message).map_err(Into::into))
+        send_update(self, &update)
     }
 
     /// Appends a record to an existing rrset, optionally require the rrset to exis (atomicity
@@ -401,39 +470,14 @@ pub trait ClientHandle: Clone + DnsHandle<Error = ClientError> {
         R: IntoRecordSet,
     {
         let rrset = rrset.into_record_set();
-        assert!(zone_origin.zone_of(rrset.name()));
-
-        // for updates, the query section is used for the zone
-        let mut zone: Query = Query::new();
-        zone.set_name(zone_origin)
-            .set_query_class(rrset.dns_class())
-            .set_query_type(RecordType::SOA);
-
-        // build the message
-        let mut message: Message = Message::new();
-        message
-            .set_id(rand::random())
-            .set_message_type(MessageType::Query)
-            .set_op_code(OpCode::Update)
-            .set_recursion_desired(false);
-        message.add_zone(zone);
-
+        let mut update = UpdateBuilder::new(zone_origin);
+        update.set_dns_class(rrset.dns_class());
         if must_exist {
-            let mut prerequisite = Record::with(rrset.name().clone(), rrset.record_type(), 0);
-            prerequisite.set_dns_class(DNSClass::ANY);
-            message.add_pre_requisite(prerequisite);
-        }
-
-        message.add_updates(rrset);
-
-        // Extended dns
-        {
-            let edns = message.edns_mut();
-            edns.set_max_payload(1500);
-            edns.set_version(0);
+            update.rrset_exists(rrset.name().clone(), rrset.record_type());
         }
+        update.add(rrset);
 
-        Box::new(self.send(message).map_err(Into::into))
+        send_update(self, &update)
     }
 
     /// Compares and if it matches, swaps it for the new value (atomicity depends on the server)
@@ -490,48 +534,15 @@ pub trait ClientHandle: Clone + DnsHandle<Error = ClientError> {
         let current = current.into_record_set();
         let new = new.into_record_set();
 
-        assert!(zone_origin.zone_of(current.name()));
-        assert!(zone_origin.zone_of(new.name()));
-
-        // for updates, the query section is used for the zone
-        let mut zone: Query = Query::new();
-        zone.set_name(zone_origin)
-            .set_query_class(new.dns_class())
-            .set_query_type(RecordType::SOA);
-
-        // build the message
-        let mut message: Message = Message::new();
-        message
-            .set_id(rand::random())
-            .set_message_type(MessageType::Query)
-            .set_op_code(OpCode::Update)
-            .set_recursion_desired(false);
-        message.add_zone(zone);
-
-        // make sure the record is what is expected
-        let mut prerequisite = current.clone();
-        prerequisite.set_ttl(0);
-        message.add_pre_requisites(prerequisite);
-
-        // add the delete for the old record
-        let mut delete = current;
-        // the class must be none for delete
-        delete.set_dns_class(DNSClass::NONE);
-        // the TTL should be 0
-        delete.set_ttl(0);
-        message.add_updates(delete);
-
-        // insert the new record...
-        message.add_updates(new);
-
-        // Extended dns
-        {
-            let edns = message.edns_mut();
-            edns.set_max_payload(1500);
-            edns.set_version(0);
-        }
+        // make sure the record is what is expected, then replace it
+        let mut update = UpdateBuilder::new(zone_origin);
+        update
+            .set_dns_class(new.dns_class())
+            .rrset_exists_with(current.clone())
+            .delete_records(current)
+            .add(new);
 
-        Box::new(self.send(message).map_err(Into::into))
+        send_update(self, &update)
     }
 
     /// Deletes a record (by rdata) from an rrset, optionally require the rrset to exist.
@@ -578,38 +589,11 @@ pub trait ClientHandle: Clone + DnsHandle<Error = ClientError> {
     where
         R: IntoRecordSet,
     {
-        let mut rrset = rrset.into_record_set();
-        assert!(zone_origin.zone_of(rrset.name()));
-
-        // for updates, the query section is used for the zone
-        let mut zone: Query = Query::new();
-        zone.set_name(zone_origin)
-            .set_query_class(rrset.dns_class())
-            .set_query_type(RecordType::SOA);
-
-        // build the message
-        let mut message: Message = Message::new();
-        message
-            .set_id(rand::random())
-            .set_message_type(MessageType::Query)
-            .set_op_code(OpCode::Update)
-            .set_recursion_desired(false);
-        message.add_zone(zone);
-
-        // the class must be none for delete
-        rrset.set_dns_class(DNSClass::NONE);
-        // the TTL shoudl be 0
-        rrset.set_ttl(0);
-        message.add_updates(rrset);
-
-        // Extended dns
-        {
-            let edns = message.edns_mut();
-            edns.set_max_payload(1500);
-            edns.set_version(0);
-        }
+        let rrset = rrset.into_record_set();
+        let mut update = UpdateBuilder::new(zone_origin);
+        update.set_dns_class(rrset.dns_class()).delete_records(rrset);
 
-        Box::new(self.send(message).map_err(Into::into))
+        send_update(self, &update)
     }
 
     /// Deletes an entire rrset, optionally require the rrset to exist.
@@ -648,42 +632,16 @@ pub trait ClientHandle: Clone + DnsHandle<Error = ClientError> {
     /// the rrset does not exist and must_exist is false, then the RRSet will be deleted.
     fn delete_rrset(
         &mut self,
-        mut record: Record,
+        record: Record,
         zone_origin: domain::Name,
     ) -> Box<Future<Item = Message, Error = ClientError>> {
-        assert!(zone_origin.zone_of(record.name()));
-
-        // for updates, the query section is used for the zone
-        let mut zone: Query = Query::new();
-        zone.set_name(zone_origin)
-            .set_query_class(record.dns_class())
-            .set_query_type(RecordType::SOA);
-
-        // build the message
-        let mut message: Message = Message::new();
-        message
-            .set_id(rand::random())
-            .set_message_type(MessageType::Query)
-            .set_op_code(OpCode::Update)
-            .set_recursion_desired(false);
-        message.add_zone(zone);
-
-        // the class must be none for an rrset delete
-        record.set_dns_class(DNSClass::ANY);
-        // the TTL shoudl be 0
-        record.set_ttl(0);
-        // the rdata must be null to delete all rrsets
-        record.set_rdata(RData::NULL(NULL::new()));
-        message.add_update(record);
-
-        // Extended dns
-        {
-            let edns = message.edns_mut();
-            edns.set_max_payload(1500);
-            edns.set_version(0);
-        }
+        let mut update = UpdateBuilder::new(zone_origin);
+        update.set_dns_class(record.dns_class()).delete_rrset(
+            record.name().clone(),
+            record.rr_type(),
+        );
 
-        Box::new(self.send(message).map_err(Into::into))
+        send_update(self, &update)
     }
 
     /// Deletes all records at the specified name
@@ -716,40 +674,42 @@ pub trait ClientHandle: Clone + DnsHandle<Error = ClientError> {
         zone_origin: domain::Name,
         dns_class: DNSClass,
     ) -> Box<Future<Item = Message, Error = ClientError>> {
-        assert!(zone_origin.zone_of(&name_of_records));
-
-        // for updates, the query section is used for the zone
-        let mut zone: Query = Query::new();
-        zone.set_name(zone_origin)
-            .set_query_class(dns_class)
-            .set_query_type(RecordType::SOA);
-
-        // build the message
-        let mut message: Message = Message::new();
-        message
-            .set_id(rand::random())
-            .set_message_type(MessageType::Query)
-            .set_op_code(OpCode::Update)
-            .set_recursion_desired(false);
-        message.add_zone(zone);
-
-        // the TTL shoudl be 0
-        // the rdata must be null to delete all rrsets
-        // the record type must be any
-        let mut record = Record::with(name_of_records, RecordType::ANY, 0);
-
-        // the class must be none for an rrset delete
-        record.set_dns_class(DNSClass::ANY);
+        let mut update = UpdateBuilder::new(zone_origin);
+        update.set_dns_class(dns_class).delete_all(name_of_records);
 
-        message.add_update(record);
+        send_update(self, &update)
+    }
 
-        // Extended dns
-        {
-            let edns = message.edns_mut();
-            edns.set_max_payload(1500);
-            edns.set_version(0);
-        }
+    /// Sends the update, of any number of prerequisites and changes to a zone, which fails with
+    ///  `ClientErrorKind::Update` if the server did not apply it
+    ///
+    /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - the update, see `UpdateBuilder`
+    ///
+    /// The update must go to a zone authority (i.e. the server used in the ClientConnection).
+    fn update(
+        &mut self,
+        update: &UpdateBuilder,
+    ) -> Box<Future<Item = Message, Error = ClientError>> {
+        Box::new(send_update(self, update).and_then(|response| {
+            match UpdateError::from_response_code(response.response_code()) {
+                Some(error) => Err(ClientErrorKind::Update(error).into()),
+                None => Ok(response),
+            }
+        }))
+    }
+}
 
-        Box::new(self.send(message).map_err(Into::into))
+/// Sends the update, the response is returned whatever its response code
+fn send_update<H: ClientHandle>(
+    handle: &mut H,
+    update: &UpdateBuilder,
+) -> Box<Future<Item = Message, Error = ClientError>> {
+    match update.build() {
+        Ok(message) => Box::new(handle.send(message).map_err(Into::into)),
+        Err(e) => Box::new(future::err(e)),
     }
 }
@@ -24,6 +24,8 @@ mod rc_future;
 mod retry_client_handle;
 #[cfg(any(feature = "openssl", feature = "ring"))]
 mod secure_client_handle;
+mod tcp_fallback_client_handle;
+mod zone_transfer;
 
 #[allow(deprecated)]
 pub use self::client::{Client, SyncClient};
@@ -36,6 +38,9 @@ pub use self::memoize_client_handle::MemoizeClientHandle;
 pub use self::retry_client_handle::RetryClientHandle;
 #[cfg(any(feature = "openssl", feature = "ring"))]
 pub use self::secure_client_handle::SecureClientHandle;
+pub use self::tcp_fallback_client_handle::TcpFallbackClientHandle;
+pub use self::zone_transfer::{IncrementalChange, IncrementalTransfer, ZoneDiff, ZoneTransfer};
+pub use trust_dns_proto::RetryPolicy;
 
 /// This is an alias for [`trust_dns_proto::StreamHandle`]
 #[deprecated(note = "use [`trust_dns_proto::StreamHandle`] instead")]
@@ -12,18 +12,19 @@ use std::collections::HashSet;
 use std::mem;
 use std::rc::Rc;
 
+use data_encoding::base32hex;
 use futures::*;
 use trust_dns_proto::DnsHandle;
 use trust_dns_proto::error::{ProtoErrorKind, ProtoResult};
 
 use client::ClientHandle;
 use error::*;
-use op::{Message, OpCode, Query};
+use op::{Message, OpCode, Query, ResponseCode};
 use rr::{domain, DNSClass, RData, Record, RecordType};
 #[cfg(any(feature = "openssl", feature = "ring"))]
 use rr::dnssec::Verifier;
-use rr::dnssec::{Algorithm, SupportedAlgorithms, TrustAnchor};
-use rr::rdata::{DNSKEY, SIG};
+use rr::dnssec::{Algorithm, Nsec3HashAlgorithm, SupportedAlgorithms, TrustAnchor};
+use rr::rdata::{DNSKEY, NSEC3, SIG};
 use rr::rdata::opt::EdnsOption;
 
 #[derive(Debug)]
@@ -111,9 +112,15 @@ where
 
         // dnssec only matters on queries.
         if let OpCode::Query = message.op_code() {
-            // This will panic on no queries, that is a very odd type of request, isn't it?
             // TODO: there should only be one
-            let query = message.queries().first().cloned().unwrap();
+            let query = match message.queries().first().cloned() {
+                Some(query) => query,
+                None => {
+                    return Box::new(failed(
+                        ClientErrorKind::Message("no query to validate the response of").into(),
+                    ))
+                }
+            };
             let client: SecureClientHandle<H> = self.clone_with_context();
 
             // TODO: cache response of the server about understood algorithms
@@ -158,16 +165,30 @@ where
                     })
                     .and_then(move |verified_message| {
                         // at this point all of the message is verified.
-                        //  This is where NSEC (and possibly NSEC3) validation occurs
-                        // As of now, only NSEC is supported.
+                        //  This is where NSEC and NSEC3 validation occurs
                         if verified_message.answers().is_empty() {
                             let nsecs = verified_message
                                 .name_servers()
                                 .iter()
                                 .filter(|rr| rr.rr_type() == RecordType::NSEC)
                                 .collect::<Vec<_>>();
+                            let nsec3s = verified_message
+                                .name_servers()
+                                .iter()
+                                .filter(|rr| rr.rr_type() == RecordType::NSEC3)
+                                .collect::<Vec<_>>();
 
-                            if !verify_nsec(&query, nsecs) {
+                            // a zone is signed with one or the other, RFC 5155 section 7.2
+                            if !nsec3s.is_empty() {
+                                let response_code = verified_message.response_code();
+                                if !verify_nsec3(&query, response_code, nsec3s) {
+                                    return Err(
+                                        ClientErrorKind::Message(
+                                            "could not validate nxdomain with NSEC3",
+                                        ).into(),
+                                    );
+                                }
+                            } else if !verify_nsec(&query, nsecs) {
                                 // TODO change this to remove the NSECs, like we do for the others?
                                 return Err(
                                     ClientErrorKind::Message(
@@ -357,34 +378,19 @@ impl Future for VerifyRrsetsFuture {
                     .take_answers()
                     .into_iter()
                     .chain(message_result.take_additionals().into_iter())
-                    .filter(|record| {
-                        self.verified_rrsets.contains(&(
-                            record.name().clone(),
-                            record.rr_type(),
-                        ))
-                    })
+                    .filter(|record| is_verified(&self.verified_rrsets, record))
                     .collect::<Vec<Record>>();
 
                 let name_servers = message_result
                     .take_name_servers()
                     .into_iter()
-                    .filter(|record| {
-                        self.verified_rrsets.contains(&(
-                            record.name().clone(),
-                            record.rr_type(),
-                        ))
-                    })
+                    .filter(|record| is_verified(&self.verified_rrsets, record))
                     .collect::<Vec<Record>>();
 
                 let additionals = message_result
                     .take_additionals()
                     .into_iter()
-                    .filter(|record| {
-                        self.verified_rrsets.contains(&(
-                            record.name().clone(),
-                            record.rr_type(),
-                        ))
-                    })
+                    .filter(|record| is_verified(&self.verified_rrsets, record))
                     .collect::<Vec<Record>>();
 
                 // add the filtered records back to the message
@@ -865,3 +871,443 @@ fn verify_nsec(query: &Query, nsecs: Vec<&Record>) -> bool {
     // if we got here, then there are no matching NSEC records, no validation
     false
 }
+
+/// Verifies NSEC3 records, the denial of existence of RFC 5155
+///
+/// ```text
+/// RFC 5155                         NSEC3                        March 2008
+///
+/// 8.4.  Validating Name Error Responses
+///
+///    A validator MUST verify that there is a closest encloser proof for
+///    QNAME present in the response and that there is an NSEC3 RR that
+///    covers the wildcard at the closest encloser (i.e., the name formed by
+///    prepending the asterisk label to the closest encloser).
+///
+/// 8.5.  Validating No Data Responses, QTYPE is not DS
+///
+///    The validator MUST verify that an NSEC3 RR that matches QNAME is
+///    present and that both the QTYPE and the CNAME type are not set in its
+///    Type Bit Maps field.
+///
+/// 8.6.  Validating No Data Responses, QTYPE is DS
+///
+///    If there is an NSEC3 RR that matches QNAME present in the response,
+///    then that NSEC3 RR MUST NOT have the bits corresponding to DS and
+///    CNAME set in its Type Bit Maps field.
+///
+///    If there is no such NSEC3 RR, then the validator MUST verify that a
+///    closest provable encloser proof for QNAME is present in the response,
+///    and that the NSEC3 RR that covers the "next closer" name has the Opt-
+///    Out bit set.
+///
+/// 8.7.  Validating Wildcard No Data Responses
+///
+///    The validator MUST verify a closest encloser proof for QNAME and MUST
+///    find an NSEC3 RR present in the response that matches the wildcard
+///    name generated by prepending the asterisk label to the closest
+///    encloser.  Furthermore, the bits corresponding to both QTYPE and
+///    CNAME MUST NOT be set in the wildcard matching NSEC3 RR.
+/// ```
+fn verify_nsec3(query: &Query, response_code: ResponseCode, nsec3s: Vec<&Record>) -> bool {
+    let proof = match Nsec3Proof::new(nsec3s) {
+        Some(proof) => proof,
+        None => return false,
+    };
+
+    let name = query.name();
+    let query_type = query.query_type();
+    if !proof.zone.zone_of(name) {
+        return false;
+    }
+
+    match response_code {
+        ResponseCode::NXDomain => {
+            match proof.closest_encloser_proof(name) {
+                Some((closest_encloser, _)) => {
+                    proof.covering(&wildcard(&closest_encloser)).is_some()
+                }
+                None => false,
+            }
+        }
+        ResponseCode::NoError => {
+            if let Some(nsec3) = proof.matching(name) {
+                return !has_type(nsec3, query_type);
+            }
+
+            match proof.closest_encloser_proof(name) {
+                // an unsigned delegation in an Opt-Out span, which has no DS
+                Some((_, next_closer)) if query_type == RecordType::DS && next_closer.opt_out() => {
+                    true
+                }
+                Some((closest_encloser, _)) => {
+                    proof.matching(&wildcard(&closest_encloser)).map_or(false, |nsec3| {
+                        !has_type(nsec3, query_type)
+                    })
+                }
+                None => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// The NSEC3 records of a response, by their hashed owner names, which all share the zone and
+///  the hash parameters of the first of them
+struct Nsec3Proof<'a> {
+    zone: domain::Name,
+    hash_algorithm: Nsec3HashAlgorithm,
+    salt: &'a [u8],
+    iterations: u16,
+    nsec3s: Vec<(Vec<u8>, &'a NSEC3)>,
+}
+
+impl<'a> Nsec3Proof<'a> {
+    /// Returns None if there are no usable NSEC3 records
+    fn new(records: Vec<&'a Record>) -> Option<Self> {
+        let mut proof: Option<Nsec3Proof<'a>> = None;
+
+        for record in records {
+            let nsec3 = match *record.rdata() {
+                RData::NSEC3(ref nsec3) => nsec3,
+                _ => continue,
+            };
+            let hashed_owner = match hashed_owner(record.name()) {
+                Some(hashed_owner) => hashed_owner,
+                None => continue,
+            };
+
+            match proof {
+                Some(ref mut proof) => {
+                    // the others are ignored, RFC 5155 section 8.2
+                    if proof.zone == record.name().base_name() &&
+                        proof.hash_algorithm == nsec3.hash_algorithm() &&
+                        proof.salt == nsec3.salt() &&
+                        proof.iterations == nsec3.iterations()
+                    {
+                        proof.nsec3s.push((hashed_owner, nsec3));
+                    }
+                    continue;
+                }
+                None => (),
+            }
+
+            proof = Some(Nsec3Proof {
+                zone: record.name().base_name(),
+                hash_algorithm: nsec3.hash_algorithm(),
+                salt: nsec3.salt(),
+                iterations: nsec3.iterations(),
+                nsec3s: vec![(hashed_owner, nsec3)],
+            });
+        }
+
+        proof
+    }
+
+    fn hash(&self, name: &domain::Name) -> Option<Vec<u8>> {
+        self.hash_algorithm
+            .hash(self.salt, name, self.iterations)
+            .ok()
+            .map(|digest| digest.as_ref().to_vec())
+    }
+
+    /// The NSEC3 whose owner is the hash of the name, proving the name exists
+    fn matching(&self, name: &domain::Name) -> Option<&'a NSEC3> {
+        let hash = match self.hash(name) {
+            Some(hash) => hash,
+            None => return None,
+        };
+
+        self.nsec3s
+            .iter()
+            .find(|&&(ref hashed_owner, _)| *hashed_owner == hash)
+            .map(|&(_, nsec3)| nsec3)
+    }
+
+    /// The NSEC3 whose span of hashes includes the hash of the name, proving the name does not
+    ///  exist
+    fn covering(&self, name: &domain::Name) -> Option<&'a NSEC3> {
+        let hash = match self.hash(name) {
+            Some(hash) => hash,
+            None => return None,
+        };
+
+        self.nsec3s
+            .iter()
+            .find(|&&(ref hashed_owner, nsec3)| {
+                let next = nsec3.next_hashed_owner_name();
+                if hashed_owner.as_slice() < next {
+                    hashed_owner.as_slice() < hash.as_slice() && hash.as_slice() < next
+                } else {
+                    // the last NSEC3 of the zone wraps around to the first
+                    hashed_owner.as_slice() < hash.as_slice() || hash.as_slice() < next
+                }
+            })
+            .map(|&(_, nsec3)| nsec3)
+    }
+
+    /// The closest encloser proof of RFC 5155 section 8.3, the closest encloser and the NSEC3
+    ///  covering the next closer name, the name one label longer than the closest encloser
+    fn closest_encloser_proof(&self, name: &domain::Name) -> Option<(domain::Name, &'a NSEC3)> {
+        let zone_labels = self.zone.num_labels() as usize;
+        let name_labels = name.num_labels() as usize;
+
+        for labels in (zone_labels..name_labels).rev() {
+            let closest_encloser = name.trim_to(labels);
+            let nsec3 = match self.matching(&closest_encloser) {
+                Some(nsec3) => nsec3,
+                None => continue,
+            };
+
+            // the names below a delegation or a DNAME are not in this zone
+            let types = nsec3.type_bit_maps();
+            if types.contains(&RecordType::DNAME) ||
+                (types.contains(&RecordType::NS) && !types.contains(&RecordType::SOA))
+            {
+                return None;
+            }
+
+            return self.covering(&name.trim_to(labels + 1)).map(
+                |next_closer| (closest_encloser, next_closer),
+            );
+        }
+
+        None
+    }
+}
+
+/// The binary hash from the first label of the NSEC3 owner name
+fn hashed_owner(name: &domain::Name) -> Option<Vec<u8>> {
+    if name.num_labels() == 0 {
+        return None;
+    }
+
+    base32hex::decode(name[0].to_uppercase().as_bytes()).ok()
+}
+
+/// The wildcard at the closest encloser, `*.closest_encloser`
+fn wildcard(closest_encloser: &domain::Name) -> domain::Name {
+    closest_encloser.prepend_label(Rc::new("*".to_string()))
+}
+
+/// Returns true if the type, or a CNAME, which would have been returned instead, is at the NSEC3
+///  owner
+fn has_type(nsec3: &NSEC3, query_type: RecordType) -> bool {
+    nsec3.type_bit_maps().contains(&query_type) ||
+        nsec3.type_bit_maps().contains(&RecordType::CNAME)
+}
+
+/// Returns true if the record's rrset was verified, RRSIGs are kept with the rrset they cover,
+///  so that the signatures which validated the records are available to the caller
+fn is_verified(verified_rrsets: &HashSet<(domain::Name, RecordType)>, record: &Record) -> bool {
+    match *record.rdata() {
+        RData::SIG(ref rrsig) if record.rr_type() == RecordType::RRSIG => {
+            verified_rrsets.contains(&(record.name().clone(), rrsig.type_covered()))
+        }
+        _ => verified_rrsets.contains(&(record.name().clone(), record.rr_type())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rr::Name;
+
+    use super::*;
+
+    /// The hash parameters of the example zone of RFC 5155 appendix A
+    const SALT: [u8; 4] = [0xAA, 0xBB, 0xCC, 0xDD];
+    const ITERATIONS: u16 = 12;
+
+    fn name(name: &str) -> Name {
+        Name::parse(name, None).unwrap()
+    }
+
+    fn hash(name: &Name) -> Vec<u8> {
+        Nsec3HashAlgorithm::SHA1
+            .hash(&SALT, name, ITERATIONS)
+            .unwrap()
+            .as_ref()
+            .to_vec()
+    }
+
+    /// The NSEC3 chain of the names of the example zone, each with the types at the name
+    fn nsec3_chain(names: Vec<(&str, Vec<RecordType>)>, opt_out: bool) -> Vec<Record> {
+        let zone = name("example.");
+        let mut hashed = names
+            .into_iter()
+            .map(|(n, types)| (hash(&name(n)), types))
+            .collect::<Vec<_>>();
+        hashed.sort_by(|a, b| a.0.cmp(&b.0));
+
+        (0..hashed.len())
+            .map(|i| {
+                let (ref hashed_owner, ref types) = hashed[i];
+                let next = hashed[(i + 1) % hashed.len()].0.clone();
+                let owner = base32hex::encode(hashed_owner).to_lowercase();
+
+                Record::from_rdata(
+                    Name::parse(&owner, Some(&zone)).unwrap(),
+                    3600,
+                    RecordType::NSEC3,
+                    RData::NSEC3(NSEC3::new(
+                        Nsec3HashAlgorithm::SHA1,
+                        opt_out,
+                        ITERATIONS,
+                        SALT.to_vec(),
+                        next,
+                        types.clone(),
+                    )),
+                )
+            })
+            .collect()
+    }
+
+    fn example_chain(opt_out: bool) -> Vec<Record> {
+        nsec3_chain(
+            vec![
+                ("example.", vec![RecordType::SOA, RecordType::NS, RecordType::DNSKEY]),
+                ("a.example.", vec![RecordType::NS, RecordType::DS]),
+                ("ai.example.", vec![RecordType::A, RecordType::HINFO, RecordType::AAAA]),
+                ("ns1.example.", vec![RecordType::A]),
+                ("ns2.example.", vec![RecordType::A]),
+                ("w.example.", vec![]),
+                ("*.w.example.", vec![RecordType::MX]),
+                ("x.w.example.", vec![RecordType::MX]),
+                ("y.w.example.", vec![]),
+                ("x.y.w.example.", vec![RecordType::MX]),
+                ("xx.example.", vec![RecordType::A, RecordType::HINFO, RecordType::AAAA]),
+            ],
+            opt_out,
+        )
+    }
+
+    fn verify(query_name: &str, query_type: RecordType, response_code: ResponseCode) -> bool {
+        verify_with(query_name, query_type, response_code, &example_chain(false))
+    }
+
+    fn verify_with(
+        query_name: &str,
+        query_type: RecordType,
+        response_code: ResponseCode,
+        chain: &[Record],
+    ) -> bool {
+        let query = Query::query(name(query_name), query_type);
+        verify_nsec3(&query, response_code, chain.iter().collect())
+    }
+
+    #[test]
+    fn test_nsec3_hashed_owner() {
+        let chain = example_chain(false);
+        let proof = Nsec3Proof::new(chain.iter().collect()).unwrap();
+
+        assert_eq!(proof.zone, name("example."));
+        assert_eq!(proof.nsec3s.len(), 11);
+        assert!(proof.matching(&name("x.w.example.")).is_some());
+        assert!(proof.covering(&name("x.w.example.")).is_none());
+        assert!(proof.matching(&name("c.x.w.example.")).is_none());
+        assert!(proof.covering(&name("c.x.w.example.")).is_some());
+    }
+
+    #[test]
+    fn test_nsec3_name_error() {
+        // RFC 5155 appendix B.1, the closest encloser is x.w.example.
+        assert!(verify("a.c.x.w.example.", RecordType::A, ResponseCode::NXDomain));
+        assert!(verify("b.example.", RecordType::A, ResponseCode::NXDomain));
+
+        // the name exists
+        assert!(!verify("ns1.example.", RecordType::A, ResponseCode::NXDomain));
+        // the wildcard at the closest encloser would have been the answer
+        assert!(!verify("b.w.example.", RecordType::A, ResponseCode::NXDomain));
+        // not of this zone
+        assert!(!verify("www.example.com.", RecordType::A, ResponseCode::NXDomain));
+    }
+
+    #[test]
+    fn test_nsec3_no_data() {
+        // RFC 5155 appendix B.2
+        assert!(verify("ns1.example.", RecordType::MX, ResponseCode::NoError));
+        // RFC 5155 appendix B.2.1, the empty non-terminal
+        assert!(verify("y.w.example.", RecordType::A, ResponseCode::NoError));
+
+        // the type exists
+        assert!(!verify("ns1.example.", RecordType::A, ResponseCode::NoError));
+        // a name error is not no data
+        assert!(!verify("a.c.x.w.example.", RecordType::A, ResponseCode::NoError));
+    }
+
+    #[test]
+    fn test_nsec3_wildcard_no_data() {
+        // RFC 5155 appendix B.4, expanded from *.w.example.
+        assert!(verify("a.z.w.example.", RecordType::AAAA, ResponseCode::NoError));
+        assert!(!verify("a.z.w.example.", RecordType::MX, ResponseCode::NoError));
+    }
+
+    #[test]
+    fn test_nsec3_ds_no_data() {
+        // the delegation is signed, and has a DS
+        assert!(!verify("a.example.", RecordType::DS, ResponseCode::NoError));
+        // the DS of a name which matches, without a DS
+        assert!(verify("ns1.example.", RecordType::DS, ResponseCode::NoError));
+    }
+
+    #[test]
+    fn test_nsec3_opt_out() {
+        // RFC 5155 appendix B.3, c.example. is an unsigned delegation in the Opt-Out span
+        let opt_out = example_chain(true);
+        assert!(verify_with("c.example.", RecordType::DS, ResponseCode::NoError, &opt_out));
+        // only the absence of the DS is proven by Opt-Out
+        assert!(!verify_with("c.example.", RecordType::A, ResponseCode::NoError, &opt_out));
+
+        assert!(!verify("c.example.", RecordType::DS, ResponseCode::NoError));
+    }
+
+    #[test]
+    fn test_nsec3_mismatched_parameters() {
+        let chain = example_chain(false);
+        let other = Record::from_rdata(
+            chain[0].name().clone(),
+            3600,
+            RecordType::NSEC3,
+            RData::NSEC3(NSEC3::new(
+                Nsec3HashAlgorithm::SHA1,
+                false,
+                ITERATIONS + 1,
+                SALT.to_vec(),
+                vec![],
+                vec![],
+            )),
+        );
+
+        // the first NSEC3 has the parameters, those with others are ignored
+        let mut appended = chain.clone();
+        appended.push(other.clone());
+        assert!(verify_with("b.example.", RecordType::A, ResponseCode::NXDomain, &appended));
+
+        let mut prepended = vec![other];
+        prepended.extend(chain);
+        assert!(!verify_with("b.example.", RecordType::A, ResponseCode::NXDomain, &prepended));
+    }
+
+    #[derive(Clone)]
+    struct UnreachableClient;
+
+    impl DnsHandle for UnreachableClient {
+        type Error = ClientError;
+
+        fn send(&mut self, _: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            Box::new(failed(ClientErrorKind::Message("unreachable").into()))
+        }
+    }
+
+    impl ClientHandle for UnreachableClient {
+        fn is_verifying_dnssec(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_query_without_question() {
+        let mut client = SecureClientHandle::new(UnreachableClient);
+        assert!(client.send(Message::new()).wait().is_err());
+    }
+}
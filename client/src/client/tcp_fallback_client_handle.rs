@@ -0,0 +1,146 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use futures::{future, Future};
+
+use client::ClientHandle;
+use error::*;
+use op::Message;
+use trust_dns_proto::DnsHandle;
+
+/// Retries the queries whose responses are truncated over a stream, RFC 1035 section 4.2.1
+///
+/// The client is generally over UDP, and the stream client over TCP to the same name server,
+///  e.g. `ClientFuture::new(TcpClientStream::new(..))`. Only the truncated responses are retried,
+///  all other queries are sent over the client.
+#[derive(Clone)]
+#[must_use = "queries can only be sent through a ClientHandle"]
+pub struct TcpFallbackClientHandle<H: ClientHandle, S: ClientHandle> {
+    client: H,
+    stream_client: S,
+}
+
+impl<H, S> TcpFallbackClientHandle<H, S>
+where
+    H: ClientHandle,
+    S: ClientHandle,
+{
+    /// Creates a new Client handler for retrying truncated responses over a stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - handle to the datagram client connection
+    /// * `stream_client` - handle to the stream client connection for the retries
+    pub fn new(client: H, stream_client: S) -> TcpFallbackClientHandle<H, S> {
+        TcpFallbackClientHandle {
+            client: client,
+            stream_client: stream_client,
+        }
+    }
+}
+
+impl<H, S> DnsHandle for TcpFallbackClientHandle<H, S>
+where
+    H: ClientHandle + 'static,
+    S: ClientHandle + 'static,
+{
+    type Error = ClientError;
+
+    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = Self::Error>> {
+        // the message is kept for the retry, the response may not have the queries
+        let retry = message.clone();
+        let mut stream_client = self.stream_client.clone();
+
+        Box::new(self.client.send(message).and_then(move |response| {
+            if response.truncated() {
+                debug!("truncated response, retrying over the stream");
+                future::Either::A(stream_client.send(retry))
+            } else {
+                future::Either::B(future::ok(response))
+            }
+        }))
+    }
+}
+
+impl<H, S> ClientHandle for TcpFallbackClientHandle<H, S>
+where
+    H: ClientHandle + 'static,
+    S: ClientHandle + 'static,
+{
+    fn is_verifying_dnssec(&self) -> bool {
+        self.client.is_verifying_dnssec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use client::*;
+    use error::*;
+    use op::*;
+    use futures::*;
+    use trust_dns_proto::DnsHandle;
+
+    #[derive(Clone)]
+    struct TestClient {
+        truncated: bool,
+        id: u16,
+    }
+
+    impl DnsHandle for TestClient {
+        type Error = ClientError;
+
+        fn send(&mut self, _: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            let mut message = Message::new();
+            message.set_id(self.id);
+            message.set_truncated(self.truncated);
+            Box::new(finished(message))
+        }
+    }
+
+    impl ClientHandle for TestClient {
+        fn is_verifying_dnssec(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut client = TcpFallbackClientHandle::new(
+            TestClient {
+                truncated: true,
+                id: 1,
+            },
+            TestClient {
+                truncated: false,
+                id: 2,
+            },
+        );
+        let result = client.send(Message::new()).wait().ok().expect(
+            "should have succeeded",
+        );
+        assert_eq!(result.id(), 2);
+        assert!(!result.truncated());
+    }
+
+    #[test]
+    fn test_not_truncated() {
+        let mut client = TcpFallbackClientHandle::new(
+            TestClient {
+                truncated: false,
+                id: 1,
+            },
+            TestClient {
+                truncated: false,
+                id: 2,
+            },
+        );
+        let result = client.send(Message::new()).wait().ok().expect(
+            "should have succeeded",
+        );
+        assert_eq!(result.id(), 1);
+    }
+}
@@ -0,0 +1,895 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Zone transfers, full ones, AXFR, RFC 5936, and incremental ones, IXFR, RFC 1995
+
+use std::collections::VecDeque;
+use std::io;
+use std::iter;
+use std::mem;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll, Stream};
+use rand;
+use tokio_core::net::TcpStream as TokioTcpStream;
+use tokio_core::reactor::{Handle, Timeout};
+use trust_dns_proto::DnsStreamHandle;
+
+use error::*;
+use op::{Message, MessageType, OpCode, Query, ResponseCode};
+use rr::{DNSClass, Name, RData, Record, RecordType};
+use rr::rdata::SOA;
+use tcp::TcpClientStream;
+
+/// Returns the serial of the record, if it is the SOA of the zone
+fn zone_serial(zone: &Name, record: &Record) -> Option<u32> {
+    match *record.rdata() {
+        RData::SOA(ref soa) if record.name() == zone => Some(soa.serial()),
+        _ => None,
+    }
+}
+
+/// Returns the serial of the record, 0 if it is not an SOA
+fn soa_serial(record: &Record) -> u32 {
+    match *record.rdata() {
+        RData::SOA(ref soa) => soa.serial(),
+        _ => 0,
+    }
+}
+
+/// Builds the request of a transfer of the zone, the SOA of the version of the zone which the
+///  client has goes in the authority section of an IXFR
+fn transfer_request(id: u16, zone: &Name, query_type: RecordType, soa: Option<Record>) -> Message {
+    let mut query = Query::query(zone.clone(), query_type);
+    query.set_query_class(DNSClass::IN);
+
+    let mut message = Message::new();
+    message
+        .set_id(id)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(false);
+    message.add_query(query);
+    if let Some(soa) = soa {
+        message.add_name_server(soa);
+    }
+    message
+}
+
+/// Checks the id and the response code of a response to the request with the id
+fn check_response(response: &Message, id: u16) -> ClientResult<()> {
+    if response.id() != id {
+        return Err(ClientErrorKind::IncorrectMessageId(response.id(), id).into());
+    }
+    if response.response_code() != ResponseCode::NoError {
+        return Err(ClientErrorKind::ErrorResponse(response.response_code()).into());
+    }
+    Ok(())
+}
+
+enum TransferState<S> {
+    /// Waiting for the connection, the request is sent once it is made
+    Connecting(Box<Future<Item = S, Error = io::Error>>, Box<DnsStreamHandle>),
+    /// The request is sent, the records arrive over any number of responses
+    Receiving(S, Box<DnsStreamHandle>),
+    /// The transfer failed, the error is returned from the next poll
+    Failed(ClientError),
+    /// All the records were received, or the transfer failed
+    Complete,
+}
+
+/// The connection over which a zone is transferred, it fails if nothing is received for the
+///  duration of the timeout
+struct TransferConnection<S> {
+    state: TransferState<S>,
+    timeout_duration: Duration,
+    timeout: Option<Timeout>,
+}
+
+impl<S: Stream<Item = Vec<u8>, Error = io::Error>> TransferConnection<S> {
+    fn new(
+        stream: Box<Future<Item = S, Error = io::Error>>,
+        stream_handle: Box<DnsStreamHandle>,
+        loop_handle: &Handle,
+        timeout_duration: Duration,
+    ) -> Self {
+        let (state, timeout) = match Timeout::new(timeout_duration, loop_handle) {
+            Ok(timeout) => (TransferState::Connecting(stream, stream_handle), Some(timeout)),
+            Err(e) => (TransferState::Failed(e.into()), None),
+        };
+
+        TransferConnection {
+            state,
+            timeout_duration,
+            timeout,
+        }
+    }
+
+    /// Polls for the next response, None once the transfer is complete
+    ///
+    /// The request is built and sent once the connection is made.
+    fn poll_response<F: FnOnce() -> Message>(
+        &mut self,
+        request: F,
+    ) -> Poll<Option<Message>, ClientError> {
+        // any failure completes the transfer
+        match mem::replace(&mut self.state, TransferState::Complete) {
+            TransferState::Connecting(mut stream, mut stream_handle) => {
+                match try!(stream.poll()) {
+                    Async::Ready(stream) => {
+                        let request = try!(request().to_vec());
+                        try!(stream_handle.send(request));
+                        self.reset_timeout();
+                        self.state = TransferState::Receiving(stream, stream_handle);
+                    }
+                    Async::NotReady => {
+                        try!(self.poll_timeout());
+                        self.state = TransferState::Connecting(stream, stream_handle);
+                        return Ok(Async::NotReady);
+                    }
+                }
+            }
+            state => self.state = state,
+        }
+
+        match mem::replace(&mut self.state, TransferState::Complete) {
+            TransferState::Receiving(mut stream, stream_handle) => {
+                match try!(stream.poll()) {
+                    Async::Ready(Some(buffer)) => {
+                        let response = try!(Message::from_vec(&buffer));
+                        self.reset_timeout();
+                        self.state = TransferState::Receiving(stream, stream_handle);
+                        Ok(Async::Ready(Some(response)))
+                    }
+                    Async::Ready(None) => {
+                        Err(
+                            ClientErrorKind::Message(
+                                "the connection closed before the zone transfer completed",
+                            ).into(),
+                        )
+                    }
+                    Async::NotReady => {
+                        try!(self.poll_timeout());
+                        self.state = TransferState::Receiving(stream, stream_handle);
+                        Ok(Async::NotReady)
+                    }
+                }
+            }
+            TransferState::Connecting(..) => unreachable!("the connection is made above"),
+            TransferState::Failed(error) => Err(error),
+            TransferState::Complete => Ok(Async::Ready(None)),
+        }
+    }
+
+    /// Completes the transfer, after the last response or a failure
+    fn complete(&mut self) {
+        self.state = TransferState::Complete;
+    }
+
+    /// Restarts the timeout of the transfer, after something was received
+    fn reset_timeout(&mut self) {
+        if let Some(ref mut timeout) = self.timeout {
+            timeout.reset(Instant::now() + self.timeout_duration);
+        }
+    }
+
+    fn poll_timeout(&mut self) -> ClientResult<()> {
+        match self.timeout {
+            Some(ref mut timeout) => {
+                match try!(timeout.poll()) {
+                    Async::Ready(()) => Err(ClientErrorKind::Timeout.into()),
+                    Async::NotReady => Ok(()),
+                }
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// A full transfer of a zone, AXFR, a stream of all the records of the zone
+///
+/// The records arrive over any number of responses on the connection, they are yielded as they
+///  arrive. The transfer starts with the SOA of the zone, which is yielded first, and ends with
+///  the same SOA, which is not yielded again. The stream fails if the first record is not the
+///  SOA, if the serial of the closing SOA differs, i.e. the zone changed during the transfer, or
+///  if the connection closes before the closing SOA. Records outside of the zone are dropped.
+///
+/// The request is not signed, see `ClientFuture::with_tsigner` for signed queries.
+#[must_use = "streams do nothing unless polled"]
+pub struct ZoneTransfer<S: Stream<Item = Vec<u8>, Error = io::Error>> {
+    connection: TransferConnection<S>,
+    zone: Name,
+    id: u16,
+    // the serial of the opening SOA, None until it is received
+    serial: Option<u32>,
+    records: VecDeque<Record>,
+}
+
+impl ZoneTransfer<TcpClientStream<TokioTcpStream>> {
+    /// Transfers the zone from the name server, over a new TCP connection
+    ///
+    /// The transfer fails if nothing is received for 5 seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the name server, an authority of the zone
+    /// * `zone` - the origin of the zone to transfer
+    /// * `loop_handle` - reference to the tokio_core::Core for future based IO
+    pub fn axfr(name_server: SocketAddr, zone: Name, loop_handle: &Handle) -> Self {
+        let timeout = Duration::from_secs(5);
+        let (stream, stream_handle) =
+            TcpClientStream::<TokioTcpStream>::with_timeout(name_server, loop_handle, timeout);
+        Self::with_timeout(stream, stream_handle, loop_handle, timeout, zone)
+    }
+}
+
+impl<S: Stream<Item = Vec<u8>, Error = io::Error>> ZoneTransfer<S> {
+    /// Transfers the zone over the connection, which must be a stream, e.g. TCP
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - the connection to the name server, see `TcpClientStream`
+    /// * `stream_handle` - the sender of the request over the connection
+    /// * `loop_handle` - reference to the tokio_core::Core for future based IO
+    /// * `timeout_duration` - the transfer fails if nothing is received for this long
+    /// * `zone` - the origin of the zone to transfer
+    pub fn with_timeout(
+        stream: Box<Future<Item = S, Error = io::Error>>,
+        stream_handle: Box<DnsStreamHandle>,
+        loop_handle: &Handle,
+        timeout_duration: Duration,
+        zone: Name,
+    ) -> Self {
+        ZoneTransfer {
+            connection: TransferConnection::new(
+                stream,
+                stream_handle,
+                loop_handle,
+                timeout_duration,
+            ),
+            zone,
+            id: rand::random(),
+            serial: None,
+            records: VecDeque::new(),
+        }
+    }
+
+    /// The origin of the zone which is transferred
+    pub fn zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// Checks the response and queues its records, returns true once the closing SOA is received
+    fn receive(&mut self, mut response: Message) -> ClientResult<bool> {
+        try!(check_response(&response, self.id));
+
+        for record in response.take_answers() {
+            match (self.serial, zone_serial(&self.zone, &record)) {
+                (None, Some(serial)) => self.serial = Some(serial),
+                (None, None) => {
+                    return Err(
+                        ClientErrorKind::Message("zone transfer did not start with the SOA")
+                            .into(),
+                    )
+                }
+                (Some(serial), Some(closing)) => {
+                    if serial != closing {
+                        return Err(
+                            ClientErrorKind::Msg(format!(
+                                "the serial of {} changed during the zone transfer: {} to {}",
+                                self.zone,
+                                serial,
+                                closing
+                            )).into(),
+                        );
+                    }
+                    return Ok(true);
+                }
+                (Some(_), None) => {
+                    if !self.zone.zone_of(record.name()) {
+                        debug!("dropping {} from the transfer of {}", record.name(), self.zone);
+                        continue;
+                    }
+                }
+            }
+
+            self.records.push_back(record);
+        }
+
+        Ok(false)
+    }
+}
+
+impl<S: Stream<Item = Vec<u8>, Error = io::Error>> Stream for ZoneTransfer<S> {
+    type Item = Record;
+    type Error = ClientError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(record) = self.records.pop_front() {
+                return Ok(Async::Ready(Some(record)));
+            }
+
+            let response = {
+                let (id, zone) = (self.id, &self.zone);
+                let request = || transfer_request(id, zone, RecordType::AXFR, None);
+                match try!(self.connection.poll_response(request)) {
+                    Async::Ready(Some(response)) => response,
+                    Async::Ready(None) => return Ok(Async::Ready(None)),
+                    Async::NotReady => return Ok(Async::NotReady),
+                }
+            };
+
+            match self.receive(response) {
+                Ok(true) => self.connection.complete(),
+                Ok(false) => (),
+                Err(error) => {
+                    self.connection.complete();
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+/// The changes from one version of a zone to the next, a step of an incremental zone transfer
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZoneDiff {
+    old_soa: Record,
+    deleted: Vec<Record>,
+    new_soa: Record,
+    added: Vec<Record>,
+}
+
+impl ZoneDiff {
+    /// Creates the changes between the versions of the zone with the old and the new SOA
+    ///
+    /// # Arguments
+    ///
+    /// * `old_soa` - the SOA of the version before the change
+    /// * `deleted` - the records deleted by the change, other than the SOA
+    /// * `new_soa` - the SOA of the version after the change
+    /// * `added` - the records added by the change, other than the SOA
+    pub fn new(old_soa: Record, deleted: Vec<Record>, new_soa: Record, added: Vec<Record>) -> Self {
+        ZoneDiff {
+            old_soa,
+            deleted,
+            new_soa,
+            added,
+        }
+    }
+
+    /// The SOA of the version before the change
+    pub fn old_soa(&self) -> &Record {
+        &self.old_soa
+    }
+
+    /// The records deleted by the change, other than the SOA
+    pub fn deleted(&self) -> &[Record] {
+        &self.deleted
+    }
+
+    /// The SOA of the version after the change
+    pub fn new_soa(&self) -> &Record {
+        &self.new_soa
+    }
+
+    /// The records added by the change, other than the SOA
+    pub fn added(&self) -> &[Record] {
+        &self.added
+    }
+
+    /// The serial of the version before the change
+    pub fn from_serial(&self) -> u32 {
+        soa_serial(&self.old_soa)
+    }
+
+    /// The serial of the version after the change
+    pub fn to_serial(&self) -> u32 {
+        soa_serial(&self.new_soa)
+    }
+
+    /// The records in the order of an incremental zone transfer, the old SOA, the deleted
+    ///  records, the new SOA and the added records
+    pub fn records(&self) -> Vec<&Record> {
+        iter::once(&self.old_soa)
+            .chain(self.deleted.iter())
+            .chain(iter::once(&self.new_soa))
+            .chain(self.added.iter())
+            .collect()
+    }
+}
+
+/// A change to a zone received in an incremental zone transfer, see `IncrementalTransfer`
+#[derive(Clone, Debug, PartialEq)]
+pub enum IncrementalChange {
+    /// The changes from one version of the zone to the next
+    Diff(ZoneDiff),
+    /// All the records of the zone, starting with its SOA, sent by a name server which does not
+    ///  have the changes, they replace the records of the zone
+    Zone(Vec<Record>),
+}
+
+enum IncrementalState {
+    /// Waiting for the SOA of the version of the name server
+    Start,
+    /// Only the SOA of the version of the name server was received, the next record tells apart
+    ///  the changes from the whole zone
+    Started(u32, Record),
+    /// Receiving the records deleted from the version of the old SOA
+    Deleting(u32, Record, Vec<Record>),
+    /// Receiving the records added in the version of the new SOA
+    Adding(u32, ZoneDiff),
+    /// Receiving the whole zone, until its SOA is repeated
+    Zone(u32, Vec<Record>),
+}
+
+/// An incremental transfer of a zone, IXFR, a stream of the changes to the zone since the
+///  version which the client has, RFC 1995
+///
+/// The changes from each version of the zone to the next are yielded as they arrive, in the order
+///  of the versions from the serial of the client to that of the name server. Nothing is
+///  yielded if the zone did not change. A name server which does not have the changes sends the
+///  whole zone instead, which is yielded once all of it is received. The stream fails like that
+///  of a `ZoneTransfer`, and if the changes do not follow on from each other.
+///
+/// The request is not signed, see `ClientFuture::with_tsigner` for signed queries.
+#[must_use = "streams do nothing unless polled"]
+pub struct IncrementalTransfer<S: Stream<Item = Vec<u8>, Error = io::Error>> {
+    connection: TransferConnection<S>,
+    zone: Name,
+    serial: u32,
+    id: u16,
+    state: IncrementalState,
+    changes: VecDeque<IncrementalChange>,
+}
+
+impl IncrementalTransfer<TcpClientStream<TokioTcpStream>> {
+    /// Transfers the changes to the zone from the name server, over a new TCP connection
+    ///
+    /// The transfer fails if nothing is received for 5 seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the name server, an authority of the zone
+    /// * `zone` - the origin of the zone to transfer
+    /// * `serial` - the serial of the version of the zone which the client has
+    /// * `loop_handle` - reference to the tokio_core::Core for future based IO
+    pub fn ixfr(name_server: SocketAddr, zone: Name, serial: u32, loop_handle: &Handle) -> Self {
+        let timeout = Duration::from_secs(5);
+        let (stream, stream_handle) =
+            TcpClientStream::<TokioTcpStream>::with_timeout(name_server, loop_handle, timeout);
+        Self::with_timeout(stream, stream_handle, loop_handle, timeout, zone, serial)
+    }
+}
+
+impl<S: Stream<Item = Vec<u8>, Error = io::Error>> IncrementalTransfer<S> {
+    /// Transfers the changes to the zone over the connection, which must be a stream, e.g. TCP
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - the connection to the name server, see `TcpClientStream`
+    /// * `stream_handle` - the sender of the request over the connection
+    /// * `loop_handle` - reference to the tokio_core::Core for future based IO
+    /// * `timeout_duration` - the transfer fails if nothing is received for this long
+    /// * `zone` - the origin of the zone to transfer
+    /// * `serial` - the serial of the version of the zone which the client has
+    pub fn with_timeout(
+        stream: Box<Future<Item = S, Error = io::Error>>,
+        stream_handle: Box<DnsStreamHandle>,
+        loop_handle: &Handle,
+        timeout_duration: Duration,
+        zone: Name,
+        serial: u32,
+    ) -> Self {
+        IncrementalTransfer {
+            connection: TransferConnection::new(
+                stream,
+                stream_handle,
+                loop_handle,
+                timeout_duration,
+            ),
+            zone,
+            serial,
+            id: rand::random(),
+            state: IncrementalState::Start,
+            changes: VecDeque::new(),
+        }
+    }
+
+    /// The origin of the zone which is transferred
+    pub fn zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// The serial of the version of the zone from which the changes are transferred
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// The SOA of the version of the zone which the client has, only its serial is of use to the
+    ///  name server
+    fn client_soa(&self) -> Record {
+        let mut soa = Record::with(self.zone.clone(), RecordType::SOA, 0);
+        soa.set_rdata(RData::SOA(
+            SOA::new(Name::root(), Name::root(), self.serial, 0, 0, 0, 0),
+        ));
+        soa
+    }
+
+    /// Checks the response and queues the changes it completes, returns true once the transfer
+    ///  is complete
+    fn receive(&mut self, mut response: Message) -> ClientResult<bool> {
+        try!(check_response(&response, self.id));
+
+        for record in response.take_answers() {
+            let serial = zone_serial(&self.zone, &record);
+            if serial.is_none() && !self.zone.zone_of(record.name()) {
+                debug!("dropping {} from the transfer of {}", record.name(), self.zone);
+                continue;
+            }
+
+            self.state = match (mem::replace(&mut self.state, IncrementalState::Start), serial) {
+                (IncrementalState::Start, Some(serial)) => {
+                    IncrementalState::Started(serial, record)
+                }
+                (IncrementalState::Start, None) => {
+                    return Err(
+                        ClientErrorKind::Message(
+                            "incremental zone transfer did not start with the SOA",
+                        ).into(),
+                    )
+                }
+                (IncrementalState::Started(current, soa), serial) => {
+                    if serial == Some(current) {
+                        // a zone of only its SOA
+                        self.changes.push_back(IncrementalChange::Zone(vec![soa]));
+                        return Ok(true);
+                    }
+
+                    // the SOA of the version of the client opens the changes from it
+                    if serial == Some(self.serial) {
+                        IncrementalState::Deleting(current, record, vec![])
+                    } else {
+                        IncrementalState::Zone(current, vec![soa, record])
+                    }
+                }
+                (IncrementalState::Deleting(current, old_soa, deleted), Some(_)) => {
+                    let diff = ZoneDiff::new(old_soa, deleted, record, vec![]);
+                    IncrementalState::Adding(current, diff)
+                }
+                (IncrementalState::Deleting(current, old_soa, mut deleted), None) => {
+                    deleted.push(record);
+                    IncrementalState::Deleting(current, old_soa, deleted)
+                }
+                (IncrementalState::Adding(current, diff), Some(serial)) => {
+                    let to_serial = diff.to_serial();
+                    self.changes.push_back(IncrementalChange::Diff(diff));
+
+                    // the SOA of the version of the name server closes the changes
+                    if to_serial == current && serial == current {
+                        return Ok(true);
+                    }
+                    if serial != to_serial {
+                        return Err(
+                            ClientErrorKind::Msg(format!(
+                                "the changes to {} do not follow on from each other: {} to {}",
+                                self.zone,
+                                to_serial,
+                                serial
+                            )).into(),
+                        );
+                    }
+                    IncrementalState::Deleting(current, record, vec![])
+                }
+                (IncrementalState::Adding(current, mut diff), None) => {
+                    diff.added.push(record);
+                    IncrementalState::Adding(current, diff)
+                }
+                (IncrementalState::Zone(current, records), Some(serial)) => {
+                    if serial != current {
+                        return Err(
+                            ClientErrorKind::Msg(format!(
+                                "the serial of {} changed during the zone transfer: {} to {}",
+                                self.zone,
+                                current,
+                                serial
+                            )).into(),
+                        );
+                    }
+                    self.changes.push_back(IncrementalChange::Zone(records));
+                    return Ok(true);
+                }
+                (IncrementalState::Zone(current, mut records), None) => {
+                    records.push(record);
+                    IncrementalState::Zone(current, records)
+                }
+            };
+        }
+
+        // a response of only the SOA, which is not after the version of the client, RFC 1995
+        //  section 2, the zone did not change
+        if let IncrementalState::Started(current, _) = self.state {
+            if (current.wrapping_sub(self.serial) as i32) <= 0 {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+impl<S: Stream<Item = Vec<u8>, Error = io::Error>> Stream for IncrementalTransfer<S> {
+    type Item = IncrementalChange;
+    type Error = ClientError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(change) = self.changes.pop_front() {
+                return Ok(Async::Ready(Some(change)));
+            }
+
+            let response = {
+                let (id, zone, soa) = (self.id, &self.zone, self.client_soa());
+                let request = || transfer_request(id, zone, RecordType::IXFR, Some(soa));
+                match try!(self.connection.poll_response(request)) {
+                    Async::Ready(Some(response)) => response,
+                    Async::Ready(None) => return Ok(Async::Ready(None)),
+                    Async::NotReady => return Ok(Async::NotReady),
+                }
+            };
+
+            match self.receive(response) {
+                Ok(true) => self.connection.complete(),
+                Ok(false) => (),
+                Err(error) => {
+                    self.connection.complete();
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    use futures::{future, stream};
+    use tokio_core::reactor::Core;
+    use trust_dns_proto::error::ProtoResult;
+
+    use rr::rdata::SOA;
+    use super::*;
+
+    struct RecordingHandle(Arc<Mutex<Vec<Vec<u8>>>>);
+
+    impl DnsStreamHandle for RecordingHandle {
+        fn send(&mut self, buffer: Vec<u8>) -> ProtoResult<()> {
+            self.0.lock().unwrap().push(buffer);
+            Ok(())
+        }
+    }
+
+    fn zone() -> Name {
+        Name::from_str("example.com.").unwrap()
+    }
+
+    fn soa(serial: u32) -> Record {
+        let mut record = Record::with(zone(), RecordType::SOA, 3600);
+        record.set_rdata(RData::SOA(SOA::new(
+            Name::from_str("ns.example.com.").unwrap(),
+            Name::from_str("hostmaster.example.com.").unwrap(),
+            serial,
+            3600,
+            600,
+            86400,
+            300,
+        )));
+        record
+    }
+
+    fn a(name: &str) -> Record {
+        let mut record = Record::with(Name::from_str(name).unwrap(), RecordType::A, 3600);
+        record.set_rdata(RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+        record
+    }
+
+    fn response(id: u16, answers: Vec<Record>) -> Vec<u8> {
+        let mut message = Message::new();
+        message.set_id(id).set_message_type(MessageType::Response);
+        message.add_answers(answers);
+        message.to_vec().unwrap()
+    }
+
+    /// Transfers the zone from the responses, returns the records and the requests sent
+    fn transfer(responses: Vec<Vec<u8>>) -> (ClientResult<Vec<Record>>, Vec<Vec<u8>>) {
+        let mut io_loop = Core::new().unwrap();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let connection = stream::iter_ok::<_, io::Error>(responses);
+        let mut transfer = ZoneTransfer::with_timeout(
+            Box::new(future::ok(connection)),
+            Box::new(RecordingHandle(sent.clone())),
+            &io_loop.handle(),
+            Duration::from_secs(5),
+            zone(),
+        );
+        transfer.id = 42;
+
+        let records = io_loop.run(transfer.collect());
+        let sent = sent.lock().unwrap().clone();
+        (records, sent)
+    }
+
+    /// Transfers the changes since the serial from the responses, returns them and the requests
+    ///  sent
+    fn incremental(
+        responses: Vec<Vec<u8>>,
+        serial: u32,
+    ) -> (ClientResult<Vec<IncrementalChange>>, Vec<Vec<u8>>) {
+        let mut io_loop = Core::new().unwrap();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let connection = stream::iter_ok::<_, io::Error>(responses);
+        let mut transfer = IncrementalTransfer::with_timeout(
+            Box::new(future::ok(connection)),
+            Box::new(RecordingHandle(sent.clone())),
+            &io_loop.handle(),
+            Duration::from_secs(5),
+            zone(),
+            serial,
+        );
+        transfer.id = 42;
+
+        let changes = io_loop.run(transfer.collect());
+        let sent = sent.lock().unwrap().clone();
+        (changes, sent)
+    }
+
+    #[test]
+    fn test_transfer_over_several_responses() {
+        let (records, sent) = transfer(vec![
+            response(42, vec![soa(7), a("www.example.com.")]),
+            response(42, vec![a("mail.example.com."), a("www.example.net.")]),
+            response(42, vec![soa(7)]),
+        ]);
+
+        assert_eq!(
+            records.expect("transfer failed"),
+            vec![soa(7), a("www.example.com."), a("mail.example.com.")]
+        );
+
+        assert_eq!(sent.len(), 1);
+        let request = Message::from_vec(&sent[0]).unwrap();
+        assert_eq!(request.id(), 42);
+        assert_eq!(request.queries()[0].query_type(), RecordType::AXFR);
+        assert_eq!(request.queries()[0].name(), &zone());
+    }
+
+    #[test]
+    fn test_transfer_without_opening_soa() {
+        let (records, _) = transfer(vec![response(42, vec![a("www.example.com."), soa(7)])]);
+        assert!(records.is_err());
+    }
+
+    #[test]
+    fn test_transfer_serial_changed() {
+        let (records, _) = transfer(vec![
+            response(42, vec![soa(7), a("www.example.com.")]),
+            response(42, vec![soa(8)]),
+        ]);
+        assert!(records.is_err());
+    }
+
+    #[test]
+    fn test_transfer_closed_early() {
+        let (records, _) = transfer(vec![response(42, vec![soa(7), a("www.example.com.")])]);
+        assert!(records.is_err());
+    }
+
+    #[test]
+    fn test_transfer_other_id() {
+        let (records, _) = transfer(vec![response(43, vec![soa(7), soa(7)])]);
+        match *records.unwrap_err().kind() {
+            ClientErrorKind::IncorrectMessageId(43, 42) => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_transfer_refused() {
+        let mut message = Message::new();
+        message
+            .set_id(42)
+            .set_message_type(MessageType::Response)
+            .set_response_code(ResponseCode::NotAuth);
+
+        let (records, _) = transfer(vec![message.to_vec().unwrap()]);
+        match *records.unwrap_err().kind() {
+            ClientErrorKind::ErrorResponse(ResponseCode::NotAuth) => (),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_incremental_transfer() {
+        let (changes, sent) = incremental(
+            vec![
+                response(
+                    42,
+                    vec![soa(9), soa(7), a("www.example.com."), soa(8), a("mail.example.com.")],
+                ),
+                response(42, vec![soa(8), soa(9), a("ftp.example.com."), a("www.example.net.")]),
+                response(42, vec![soa(9)]),
+            ],
+            7,
+        );
+
+        assert_eq!(
+            changes.expect("transfer failed"),
+            vec![
+                IncrementalChange::Diff(ZoneDiff::new(
+                    soa(7),
+                    vec![a("www.example.com.")],
+                    soa(8),
+                    vec![a("mail.example.com.")],
+                )),
+                IncrementalChange::Diff(
+                    ZoneDiff::new(soa(8), vec![], soa(9), vec![a("ftp.example.com.")]),
+                ),
+            ]
+        );
+
+        assert_eq!(sent.len(), 1);
+        let request = Message::from_vec(&sent[0]).unwrap();
+        assert_eq!(request.id(), 42);
+        assert_eq!(request.queries()[0].query_type(), RecordType::IXFR);
+        assert_eq!(request.queries()[0].name(), &zone());
+        assert_eq!(request.name_servers().len(), 1);
+        match *request.name_servers()[0].rdata() {
+            RData::SOA(ref soa) => assert_eq!(soa.serial(), 7),
+            ref rdata => panic!("expected SOA: {:?}", rdata),
+        }
+    }
+
+    #[test]
+    fn test_incremental_transfer_up_to_date() {
+        let (changes, _) = incremental(vec![response(42, vec![soa(7)])], 7);
+        assert_eq!(changes.expect("transfer failed"), vec![]);
+    }
+
+    #[test]
+    fn test_incremental_transfer_of_zone() {
+        let (changes, _) = incremental(
+            vec![
+                response(42, vec![soa(9), a("www.example.com.")]),
+                response(42, vec![a("mail.example.com."), soa(9)]),
+            ],
+            7,
+        );
+
+        assert_eq!(
+            changes.expect("transfer failed"),
+            vec![
+                IncrementalChange::Zone(
+                    vec![soa(9), a("www.example.com."), a("mail.example.com.")],
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_incremental_transfer_gap() {
+        let (changes, _) = incremental(
+            vec![response(42, vec![soa(9), soa(7), soa(8), soa(6), soa(9), soa(9)])],
+            7,
+        );
+        assert!(changes.is_err());
+    }
+}
@@ -23,7 +23,7 @@ use openssl::error::ErrorStack as SslErrorStack;
 #[cfg(not(feature = "openssl"))]
 use error::dnssec_error::not_openssl::SslErrorStack;
 
-use op::ResponseCode;
+use op::{ResponseCode, UpdateError};
 use rr::{Name, Record};
 use error::{DnsSecError, DnsSecErrorKind};
 
@@ -98,6 +98,11 @@ error_chain! {
         display("response was an error: {}", response_code.to_str())
       }
 
+      Update(error: UpdateError) {
+        description("update was not applied")
+        display("update was not applied: {}", error)
+      }
+
       // TODO: add record to which this applies
       NoRRSIG {
         description("no rrsig was recieved")
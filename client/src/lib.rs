@@ -205,7 +205,6 @@
 
 
 extern crate chrono;
-#[cfg(test)]
 extern crate data_encoding;
 #[macro_use]
 extern crate error_chain;
@@ -220,7 +219,6 @@ extern crate openssl;
 extern crate rand;
 #[cfg(feature = "ring")]
 extern crate ring;
-extern crate rustc_serialize;
 extern crate tokio_io;
 extern crate tokio_core;
 #[cfg(feature = "tokio-tls")]
@@ -236,6 +234,7 @@ pub mod error;
 pub mod logger;
 pub mod op;
 pub mod rr;
+pub mod service_discovery;
 pub mod tcp;
 pub mod udp;
 pub mod serialize;
@@ -0,0 +1,152 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! the records of a name from the response to a query of type ANY
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Keys;
+
+use op::{Message, ResponseCode};
+use rr::{RData, Record, RecordType};
+
+/// The records of the answer to a query of type ANY, by their type
+///
+/// [RFC 8482](https://tools.ietf.org/html/rfc8482) allows servers to answer ANY with only a
+///  subset of the records, most answer over UDP with a synthesized HINFO, see `is_minimal`.
+///  Many still answer with all of the records over TCP, so tools which enumerate the records of
+///  a name should query over TCP. A server may also answer with one or some of the RRsets,
+///  RFC 8482 section 4.1, which can not be told apart from a name with only those RRsets.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnyRecords {
+    response_code: ResponseCode,
+    records: BTreeMap<RecordType, Vec<Record>>,
+    minimal: bool,
+}
+
+impl AnyRecords {
+    /// The response code of the response, e.g. `NXDomain` if the name does not exist
+    pub fn response_code(&self) -> ResponseCode {
+        self.response_code
+    }
+
+    /// Returns true if the server answered with the HINFO of RFC 8482 section 4.2, rather than
+    ///  the records of the name; there are then no records
+    pub fn is_minimal(&self) -> bool {
+        self.minimal
+    }
+
+    /// The records of the type, empty if there are none
+    pub fn get(&self, record_type: RecordType) -> &[Record] {
+        self.records
+            .get(&record_type)
+            .map(|records| &records[..])
+            .unwrap_or(&[])
+    }
+
+    /// The types of the records of the name, in order
+    pub fn record_types(&self) -> Keys<RecordType, Vec<Record>> {
+        self.records.keys()
+    }
+
+    /// All of the records by their type
+    pub fn as_map(&self) -> &BTreeMap<RecordType, Vec<Record>> {
+        &self.records
+    }
+
+    /// Returns the records by their type
+    pub fn into_map(self) -> BTreeMap<RecordType, Vec<Record>> {
+        self.records
+    }
+}
+
+impl From<Message> for AnyRecords {
+    fn from(mut message: Message) -> Self {
+        let answers = message.take_answers();
+        let minimal = answers.len() == 1 &&
+            match *answers[0].rdata() {
+                RData::HINFO(ref hinfo) => hinfo.is_rfc8482(),
+                _ => false,
+            };
+
+        let mut records = BTreeMap::<RecordType, Vec<Record>>::new();
+        if !minimal {
+            for record in answers {
+                records
+                    .entry(record.rr_type())
+                    .or_insert_with(Vec::new)
+                    .push(record);
+            }
+        }
+
+        AnyRecords {
+            response_code: message.response_code(),
+            records,
+            minimal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use rr::Name;
+    use rr::rdata::{HINFO, TXT};
+
+    use super::*;
+
+    fn record(rdata: RData) -> Record {
+        Record::from_rdata(
+            Name::parse("example.com.", None).unwrap(),
+            3600,
+            rdata.to_record_type(),
+            rdata,
+        )
+    }
+
+    #[test]
+    fn test_any_records() {
+        let mut message = Message::new();
+        message.insert_answers(vec![
+            record(RData::TXT(TXT::new(vec!["v=spf1 -all".to_string()]))),
+            record(RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+            record(RData::A(Ipv4Addr::new(192, 0, 2, 2))),
+        ]);
+
+        let any = AnyRecords::from(message);
+        assert!(!any.is_minimal());
+        assert_eq!(any.response_code(), ResponseCode::NoError);
+        assert_eq!(
+            any.record_types().cloned().collect::<Vec<_>>(),
+            vec![RecordType::A, RecordType::TXT]
+        );
+        assert_eq!(any.get(RecordType::A).len(), 2);
+        assert!(any.get(RecordType::MX).is_empty());
+    }
+
+    #[test]
+    fn test_rfc8482() {
+        let mut message = Message::new();
+        message.insert_answers(vec![
+            record(RData::HINFO(HINFO::new("RFC8482".to_string(), "".to_string()))),
+        ]);
+
+        let any = AnyRecords::from(message);
+        assert!(any.is_minimal());
+        assert!(any.as_map().is_empty());
+
+        // a real HINFO is a record of the name
+        let mut message = Message::new();
+        message.insert_answers(vec![
+            record(RData::HINFO(HINFO::new("PDP-11/70".to_string(), "UNIX".to_string()))),
+        ]);
+
+        let any = AnyRecords::from(message);
+        assert!(!any.is_minimal());
+        assert_eq!(any.get(RecordType::HINFO).len(), 1);
+    }
+}
@@ -8,8 +8,12 @@
 //! Operations to send with a `Client` or server, e.g. `Query`, `Message`, or `UpdateMessage` can
 //! be used to gether to either query or update resource records sets.
 
+mod any_records;
+mod update_builder;
 mod update_message;
 
+pub use self::any_records::AnyRecords;
+pub use self::update_builder::{UpdateBuilder, UpdateError};
 pub use self::update_message::UpdateMessage;
 pub use trust_dns_proto::op::{Edns, Header, Message, MessageFinalizer, MessageType, OpCode, Query,
-                              ResponseCode};
+                              QueryBuilder, ResponseCode};
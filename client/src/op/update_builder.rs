@@ -0,0 +1,383 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Composing dynamic updates, RFC 2136
+
+use std::fmt;
+
+use rand;
+
+use error::*;
+use rr::{DNSClass, IntoRecordSet, Name, RData, Record, RecordType};
+use rr::rdata::NULL;
+use super::{Message, MessageType, OpCode, Query, ResponseCode, UpdateMessage};
+
+/// Builds a dynamic update, [RFC 2136](https://tools.ietf.org/html/rfc2136), of a zone, with any
+///  number of prerequisites and changes, which the server applies all or none of.
+///
+/// ```
+/// use std::net::Ipv4Addr;
+/// use std::str::FromStr;
+///
+/// use trust_dns::op::UpdateBuilder;
+/// use trust_dns::rr::{Name, RData, Record, RecordType};
+///
+/// let zone = Name::from_str("example.com.").unwrap();
+/// let www = Name::from_str("www.example.com.").unwrap();
+///
+/// let mut record = Record::with(www.clone(), RecordType::A, 3600);
+/// record.set_rdata(RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+///
+/// // replace the addresses of www, only if there is no CNAME at the name
+/// let mut builder = UpdateBuilder::new(zone);
+/// builder
+///     .rrset_not_exists(www.clone(), RecordType::CNAME)
+///     .delete_rrset(www.clone(), RecordType::A)
+///     .add(record);
+///
+/// let update = builder.build().unwrap();
+/// assert_eq!(update.answers().len(), 1);
+/// assert_eq!(update.name_servers().len(), 2);
+///
+/// // the names must be in the zone
+/// builder.delete_all(Name::from_str("www.example.net.").unwrap());
+/// assert!(builder.build().is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct UpdateBuilder {
+    zone_origin: Name,
+    dns_class: DNSClass,
+    prerequisites: Vec<Record>,
+    updates: Vec<Record>,
+}
+
+impl UpdateBuilder {
+    /// A builder of an update of the zone, in the IN class, with nothing to change yet
+    ///
+    /// # Arguments
+    ///
+    /// * `zone_origin` - the zone name to update, i.e. SOA name
+    pub fn new(zone_origin: Name) -> Self {
+        UpdateBuilder {
+            zone_origin: zone_origin,
+            dns_class: DNSClass::IN,
+            prerequisites: Vec::new(),
+            updates: Vec::new(),
+        }
+    }
+
+    /// Specify the class of the zone, almost always IN
+    pub fn set_dns_class(&mut self, dns_class: DNSClass) -> &mut Self {
+        self.dns_class = dns_class;
+        self
+    }
+
+    /// Requires the RRset of the name and type to exist, whatever its records, RFC 2136 section
+    ///  2.4.1, otherwise the update fails with NXRRSet
+    pub fn rrset_exists(&mut self, name: Name, record_type: RecordType) -> &mut Self {
+        let mut prerequisite = Record::with(name, record_type, 0);
+        prerequisite.set_dns_class(DNSClass::ANY);
+        self.prerequisites.push(prerequisite);
+        self
+    }
+
+    /// Requires the RRset to exist with exactly these records, RFC 2136 section 2.4.2, otherwise
+    ///  the update fails with NXRRSet, the TTLs are ignored
+    pub fn rrset_exists_with<R: IntoRecordSet>(&mut self, rrset: R) -> &mut Self {
+        let mut rrset = rrset.into_record_set();
+        rrset.set_ttl(0);
+        rrset.set_dns_class(self.dns_class);
+        self.prerequisites.extend(rrset);
+        self
+    }
+
+    /// Requires that there is no RRset of the name and type, RFC 2136 section 2.4.3, otherwise
+    ///  the update fails with YXRRSet
+    pub fn rrset_not_exists(&mut self, name: Name, record_type: RecordType) -> &mut Self {
+        let mut prerequisite = Record::with(name, record_type, 0);
+        prerequisite.set_dns_class(DNSClass::NONE);
+        self.prerequisites.push(prerequisite);
+        self
+    }
+
+    /// Requires the name to have at least one record, of any type, RFC 2136 section 2.4.4,
+    ///  otherwise the update fails with NXDomain
+    pub fn name_in_use(&mut self, name: Name) -> &mut Self {
+        let mut prerequisite = Record::with(name, RecordType::ANY, 0);
+        prerequisite.set_dns_class(DNSClass::ANY);
+        self.prerequisites.push(prerequisite);
+        self
+    }
+
+    /// Requires the name to have no records, of any type, RFC 2136 section 2.4.5, otherwise the
+    ///  update fails with YXDomain
+    pub fn name_not_in_use(&mut self, name: Name) -> &mut Self {
+        let mut prerequisite = Record::with(name, RecordType::ANY, 0);
+        prerequisite.set_dns_class(DNSClass::NONE);
+        self.prerequisites.push(prerequisite);
+        self
+    }
+
+    /// Adds the records to their RRset, RFC 2136 section 2.5.1, records which already exist are
+    ///  ignored by the server
+    pub fn add<R: IntoRecordSet>(&mut self, rrset: R) -> &mut Self {
+        let mut rrset = rrset.into_record_set();
+        rrset.set_dns_class(self.dns_class);
+        self.updates.extend(rrset);
+        self
+    }
+
+    /// Deletes the RRset of the name and type, RFC 2136 section 2.5.2
+    pub fn delete_rrset(&mut self, name: Name, record_type: RecordType) -> &mut Self {
+        let mut record = Record::with(name, record_type, 0);
+        record.set_dns_class(DNSClass::ANY);
+        record.set_rdata(RData::NULL(NULL::new()));
+        self.updates.push(record);
+        self
+    }
+
+    /// Deletes all the RRsets of the name, RFC 2136 section 2.5.3
+    pub fn delete_all(&mut self, name: Name) -> &mut Self {
+        let mut record = Record::with(name, RecordType::ANY, 0);
+        record.set_dns_class(DNSClass::ANY);
+        self.updates.push(record);
+        self
+    }
+
+    /// Deletes the records, by their name, type and rdata, from their RRset, RFC 2136 section
+    ///  2.5.4, records which do not exist are ignored by the server
+    pub fn delete_records<R: IntoRecordSet>(&mut self, rrset: R) -> &mut Self {
+        let mut rrset = rrset.into_record_set();
+        rrset.set_dns_class(DNSClass::NONE);
+        rrset.set_ttl(0);
+        self.updates.extend(rrset);
+        self
+    }
+
+    /// Returns the update message, with a random id, to send to an authority of the zone
+    ///
+    /// # Errors
+    ///
+    /// If any of the names of the prerequisites or changes is not in the zone, which the server
+    ///  would refuse with NotZone
+    pub fn build(&self) -> ClientResult<Message> {
+        if let Some(record) = self.prerequisites.iter().chain(self.updates.iter()).find(
+            |record| !self.zone_origin.zone_of(record.name()),
+        )
+        {
+            return Err(
+                ClientErrorKind::Msg(format!(
+                    "{} is not in the zone of the update: {}",
+                    record.name(),
+                    self.zone_origin
+                )).into(),
+            );
+        }
+
+        // for updates, the query section is used for the zone
+        let mut zone: Query = Query::new();
+        zone.set_name(self.zone_origin.clone())
+            .set_query_class(self.dns_class)
+            .set_query_type(RecordType::SOA);
+
+        let mut message: Message = Message::new();
+        message
+            .set_id(rand::random())
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Update)
+            .set_recursion_desired(false);
+        message.add_zone(zone);
+        message.add_pre_requisites(self.prerequisites.clone());
+        message.add_updates(self.updates.clone());
+
+        // Extended dns
+        {
+            let edns = message.edns_mut();
+            edns.set_max_payload(1500);
+            edns.set_version(0);
+        }
+
+        Ok(message)
+    }
+}
+
+/// The reasons for which a server does not apply an update, by the response code of the
+///  response, RFC 2136 section 2.2
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpdateError {
+    /// A name which must not be in use is, YXDomain
+    NameInUse,
+    /// A name which must be in use is not, NXDomain
+    NameNotInUse,
+    /// An RRset which must not exist does, YXRRSet
+    RRSetExists,
+    /// An RRset which must exist does not, or not with the required records, NXRRSet
+    RRSetNotExists,
+    /// The server is not authoritative for the zone, or the update was not authenticated,
+    ///  NotAuth
+    NotAuth,
+    /// A name of the update is not in the zone, NotZone
+    NotZone,
+    /// The server does not allow the update, Refused
+    Refused,
+    /// Any other failure, e.g. ServFail
+    Other(ResponseCode),
+}
+
+impl UpdateError {
+    /// Returns the failure of the response code, None for NoError
+    pub fn from_response_code(response_code: ResponseCode) -> Option<Self> {
+        match response_code {
+            ResponseCode::NoError => None,
+            ResponseCode::YXDomain => Some(UpdateError::NameInUse),
+            ResponseCode::NXDomain => Some(UpdateError::NameNotInUse),
+            ResponseCode::YXRRSet => Some(UpdateError::RRSetExists),
+            ResponseCode::NXRRSet => Some(UpdateError::RRSetNotExists),
+            ResponseCode::NotAuth => Some(UpdateError::NotAuth),
+            ResponseCode::NotZone => Some(UpdateError::NotZone),
+            ResponseCode::Refused => Some(UpdateError::Refused),
+            response_code => Some(UpdateError::Other(response_code)),
+        }
+    }
+
+    /// The response code of the failure
+    pub fn response_code(&self) -> ResponseCode {
+        match *self {
+            UpdateError::NameInUse => ResponseCode::YXDomain,
+            UpdateError::NameNotInUse => ResponseCode::NXDomain,
+            UpdateError::RRSetExists => ResponseCode::YXRRSet,
+            UpdateError::RRSetNotExists => ResponseCode::NXRRSet,
+            UpdateError::NotAuth => ResponseCode::NotAuth,
+            UpdateError::NotZone => ResponseCode::NotZone,
+            UpdateError::Refused => ResponseCode::Refused,
+            UpdateError::Other(response_code) => response_code,
+        }
+    }
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UpdateError::NameInUse => write!(f, "a name which must not be in use is"),
+            UpdateError::NameNotInUse => write!(f, "a name which must be in use is not"),
+            UpdateError::RRSetExists => write!(f, "an rrset which must not exist does"),
+            UpdateError::RRSetNotExists => write!(f, "an rrset which must exist does not"),
+            UpdateError::NotAuth => write!(f, "not authorized"),
+            UpdateError::NotZone => write!(f, "a name is not in the zone"),
+            UpdateError::Refused => write!(f, "refused"),
+            UpdateError::Other(response_code) => write!(f, "{}", response_code.to_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn a_record(name: &str, ttl: u32) -> Record {
+        let mut record = Record::with(Name::from_str(name).unwrap(), RecordType::A, ttl);
+        record.set_rdata(RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+        record
+    }
+
+    #[test]
+    fn test_prerequisites() {
+        let www = Name::from_str("www.example.com.").unwrap();
+        let message = UpdateBuilder::new(Name::from_str("example.com.").unwrap())
+            .rrset_exists(www.clone(), RecordType::A)
+            .rrset_exists_with(a_record("www.example.com.", 3600))
+            .rrset_not_exists(www.clone(), RecordType::CNAME)
+            .name_in_use(www.clone())
+            .name_not_in_use(Name::from_str("new.example.com.").unwrap())
+            .build()
+            .unwrap();
+
+        assert_eq!(message.op_code(), OpCode::Update);
+        assert_eq!(message.zones()[0].query_type(), RecordType::SOA);
+
+        let prerequisites = message.prerequisites();
+        let classes_and_types = prerequisites
+            .iter()
+            .map(|record| (record.dns_class(), record.rr_type(), record.ttl()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            classes_and_types,
+            vec![
+                (DNSClass::ANY, RecordType::A, 0),
+                (DNSClass::IN, RecordType::A, 0),
+                (DNSClass::NONE, RecordType::CNAME, 0),
+                (DNSClass::ANY, RecordType::ANY, 0),
+                (DNSClass::NONE, RecordType::ANY, 0),
+            ]
+        );
+        assert_eq!(prerequisites[1].rdata(), &RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+    }
+
+    #[test]
+    fn test_updates() {
+        let www = Name::from_str("www.example.com.").unwrap();
+        let message = UpdateBuilder::new(Name::from_str("example.com.").unwrap())
+            .add(a_record("www.example.com.", 3600))
+            .delete_rrset(www.clone(), RecordType::AAAA)
+            .delete_all(Name::from_str("old.example.com.").unwrap())
+            .delete_records(a_record("www.example.com.", 3600))
+            .build()
+            .unwrap();
+
+        let classes_and_types = message
+            .updates()
+            .iter()
+            .map(|record| (record.dns_class(), record.rr_type(), record.ttl()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            classes_and_types,
+            vec![
+                (DNSClass::IN, RecordType::A, 3600),
+                (DNSClass::ANY, RecordType::AAAA, 0),
+                (DNSClass::ANY, RecordType::ANY, 0),
+                (DNSClass::NONE, RecordType::A, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_in_zone() {
+        let mut builder = UpdateBuilder::new(Name::from_str("example.com.").unwrap());
+        builder.add(a_record("www.example.com.", 3600));
+        assert!(builder.build().is_ok());
+
+        builder.rrset_exists(Name::from_str("example.net.").unwrap(), RecordType::A);
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn test_update_error() {
+        assert_eq!(UpdateError::from_response_code(ResponseCode::NoError), None);
+        for response_code in &[
+            ResponseCode::YXDomain,
+            ResponseCode::NXDomain,
+            ResponseCode::YXRRSet,
+            ResponseCode::NXRRSet,
+            ResponseCode::NotAuth,
+            ResponseCode::NotZone,
+            ResponseCode::Refused,
+            ResponseCode::ServFail,
+        ]
+        {
+            let error = UpdateError::from_response_code(*response_code).unwrap();
+            assert_eq!(error.response_code(), *response_code);
+        }
+
+        assert_eq!(
+            UpdateError::from_response_code(ResponseCode::NXRRSet),
+            Some(UpdateError::RRSetNotExists)
+        );
+    }
+}
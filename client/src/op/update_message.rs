@@ -83,6 +83,10 @@ pub trait UpdateMessage: Debug {
 
 /// to reduce errors in using the Message struct as an Update, this will do the call throughs
 ///   to properly do that.
+///
+/// The prerequisite and update sections follow RFC 2136 rather than the rules of the answer and
+///  authority sections of a response, so the records are added unchecked.
+#[allow(deprecated)]
 impl UpdateMessage for Message {
     fn id(&self) -> u16 {
         self.id()
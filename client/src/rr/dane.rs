@@ -0,0 +1,363 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DANE, RFC 6698 and RFC 7671, verification of a presented certificate chain against TLSA records
+//!
+//! The TLSA records must have been validated with DNSSec, e.g. looked up through the
+//!  `SecureClientHandle`, those which are not secure must not be passed to `verify`. The chain is
+//!  that presented by the server, DER encoded, the end entity certificate first. This does not
+//!  validate the chain itself, the verdict says what the TLS stack must still check.
+
+use trust_dns_proto::error::{ProtoError, ProtoErrorKind, ProtoResult};
+
+use rr::dnssec::DigestType;
+use rr::rdata::TLSA;
+use rr::rdata::tlsa::{CertUsage, Matching, Selector};
+
+/// The outcome of the verification of a certificate chain against a TLSA RRset
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Verdict {
+    /// A TLSA record matched the certificate at `chain_index` of the chain
+    ///
+    /// For `PkixTa` and `PkixEe` the chain must still pass PKIX validation, and for `PkixTa` and
+    ///  `DaneTa` the chain must chain up to the matched certificate. For `DaneEe` nothing else
+    ///  is checked, not even the name or the expiry of the certificate, RFC 7671 section 5.1.
+    Matched {
+        /// the usage of the TLSA record which matched
+        usage: CertUsage,
+        /// the index of the matched certificate in the chain, 0 is the end entity
+        chain_index: usize,
+    },
+    /// None of the TLSA records are usable, e.g. there are none, or of unassigned usage, selector
+    ///  or matching type; the connection proceeds as if there were no TLSA records, with PKIX
+    ///  validation, RFC 6698 section 4.1
+    Unusable,
+    /// There are usable TLSA records, but none matched, the connection must be aborted
+    Mismatch,
+}
+
+impl Verdict {
+    /// Returns true if the chain must still pass PKIX validation against the trust anchors of
+    ///  the TLS stack, for those usages and when no record was usable
+    pub fn pkix_required(&self) -> bool {
+        match *self {
+            Verdict::Matched { usage: CertUsage::PkixTa, .. } |
+            Verdict::Matched { usage: CertUsage::PkixEe, .. } |
+            Verdict::Unusable => true,
+            Verdict::Matched { .. } |
+            Verdict::Mismatch => false,
+        }
+    }
+
+    /// Returns true if the connection may proceed, subject to `pkix_required`
+    pub fn is_ok(&self) -> bool {
+        *self != Verdict::Mismatch
+    }
+}
+
+/// Verifies the presented certificate chain against the TLSA records of the service
+///
+/// # Arguments
+///
+/// * `tlsas` - the DNSSec validated TLSA RRset of the service, e.g. `_443._tcp.www.example.com.`
+/// * `chain` - the DER encoded certificates presented by the server, the end entity first
+///
+/// # Return
+///
+/// The verdict for the first record of the RRset which matches, records for the end entity are
+///  matched against the first certificate, those for a CA against the rest of the chain.
+pub fn verify<'a, I, C>(tlsas: I, chain: &[C]) -> Verdict
+where
+    I: IntoIterator<Item = &'a TLSA>,
+    C: AsRef<[u8]>,
+{
+    let mut usable = false;
+    for tlsa in tlsas {
+        let candidates = match tlsa.cert_usage() {
+            CertUsage::PkixEe | CertUsage::DaneEe => 0..chain.len().min(1),
+            CertUsage::PkixTa | CertUsage::DaneTa => chain.len().min(1)..chain.len(),
+            CertUsage::Unassigned(_) | CertUsage::Private => continue,
+        };
+
+        let digest_type = match tlsa.matching() {
+            Matching::Raw => None,
+            Matching::Sha256 => Some(DigestType::SHA256),
+            Matching::Sha512 => Some(DigestType::SHA512),
+            Matching::Unassigned(_) | Matching::Private => continue,
+        };
+
+        match tlsa.selector() {
+            Selector::Full | Selector::Spki => (),
+            Selector::Unassigned(_) | Selector::Private => continue,
+        }
+
+        usable = true;
+        for chain_index in candidates {
+            if matches(tlsa, digest_type, chain[chain_index].as_ref()) {
+                return Verdict::Matched {
+                    usage: tlsa.cert_usage(),
+                    chain_index,
+                };
+            }
+        }
+    }
+
+    if usable {
+        Verdict::Mismatch
+    } else {
+        Verdict::Unusable
+    }
+}
+
+/// true if the selected content of the certificate matches the association data of the TLSA
+fn matches(tlsa: &TLSA, digest_type: Option<DigestType>, cert: &[u8]) -> bool {
+    let selected = match tlsa.selector() {
+        Selector::Spki => {
+            match subject_public_key_info(cert) {
+                Ok(spki) => spki,
+                Err(e) => {
+                    debug!("no SubjectPublicKeyInfo in the certificate: {}", e);
+                    return false;
+                }
+            }
+        }
+        _ => cert,
+    };
+
+    match digest_type {
+        None => selected == tlsa.cert_data(),
+        Some(digest_type) => {
+            match digest_type.hash(selected) {
+                Ok(digest) => digest.as_ref() == tlsa.cert_data(),
+                Err(e) => {
+                    warn!("could not hash the certificate: {}", e);
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// Returns the DER encoded SubjectPublicKeyInfo of the DER encoded X.509 certificate
+///
+/// ```text
+/// Certificate  ::=  SEQUENCE  {
+///      tbsCertificate       TBSCertificate,
+///      ... }
+///
+/// TBSCertificate  ::=  SEQUENCE  {
+///      version         [0]  EXPLICIT Version DEFAULT v1,
+///      serialNumber         CertificateSerialNumber,
+///      signature            AlgorithmIdentifier,
+///      issuer               Name,
+///      validity             Validity,
+///      subject              Name,
+///      subjectPublicKeyInfo SubjectPublicKeyInfo,
+///      ... }
+/// ```
+fn subject_public_key_info(cert: &[u8]) -> ProtoResult<&[u8]> {
+    const SEQUENCE: u8 = 0x30;
+    const INTEGER: u8 = 0x02;
+    const VERSION: u8 = 0xA0;
+
+    let (_, certificate, _) = try!(read_tlv(cert, SEQUENCE));
+    let (_, tbs_certificate, _) = try!(read_tlv(certificate, SEQUENCE));
+
+    // the version is optional, v1 certificates start with the serial number
+    let mut rest = tbs_certificate;
+    if rest.first() == Some(&VERSION) {
+        rest = try!(read_tlv(rest, VERSION)).2;
+    }
+    rest = try!(read_tlv(rest, INTEGER)).2;
+
+    // signature, issuer, validity and subject
+    for _ in 0..4 {
+        rest = try!(read_tlv(rest, SEQUENCE)).2;
+    }
+
+    read_tlv(rest, SEQUENCE).map(|(spki, _, _)| spki)
+}
+
+/// Reads a DER TLV of the tag, returning the whole TLV, its value and what follows it
+fn read_tlv(input: &[u8], tag: u8) -> ProtoResult<(&[u8], &[u8], &[u8])> {
+    let truncated = || ProtoError::from(ProtoErrorKind::Message("truncated DER"));
+
+    if *try!(input.first().ok_or_else(&truncated)) != tag {
+        return Err(ProtoErrorKind::Msg(format!("expected DER tag: {:X}", tag)).into());
+    }
+
+    let first = *try!(input.get(1).ok_or_else(&truncated)) as usize;
+    let (header_len, len) = if first < 0x80 {
+        (2, first)
+    } else {
+        // the indefinite form, 0x80, is not DER, nor are lengths beyond those of a certificate
+        let octets = first & 0x7F;
+        if octets == 0 || octets > 4 {
+            return Err(ProtoErrorKind::Message("unsupported DER length").into());
+        }
+
+        let len_bytes = try!(input.get(2..2 + octets).ok_or_else(&truncated));
+        let len = len_bytes.iter().fold(0usize, |len, b| (len << 8) | *b as usize);
+        (2 + octets, len)
+    };
+
+    let end = try!(header_len.checked_add(len).ok_or_else(&truncated));
+    if end > input.len() {
+        return Err(truncated());
+    }
+
+    Ok((&input[..end], &input[header_len..end], &input[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+        let mut tlv = vec![tag];
+        if value.len() < 0x80 {
+            tlv.push(value.len() as u8);
+        } else {
+            tlv.push(0x82);
+            tlv.push((value.len() >> 8) as u8);
+            tlv.push(value.len() as u8);
+        }
+        tlv.extend_from_slice(value);
+        tlv
+    }
+
+    fn spki(key: u8) -> Vec<u8> {
+        // an algorithm identifier and a bit string of a key long enough for the long length form
+        let algorithm = tlv(0x30, &tlv(0x06, &[0x2B, 0x65, 0x70]));
+        let key = tlv(0x03, &[key; 200]);
+        tlv(0x30, &[algorithm, key].concat())
+    }
+
+    /// The structure of a certificate, just enough to find the SubjectPublicKeyInfo
+    fn cert(serial: u8, key: u8) -> Vec<u8> {
+        let name = tlv(0x30, &tlv(0x31, &[]));
+        let tbs = tlv(
+            0x30,
+            &[
+                tlv(0xA0, &tlv(0x02, &[2])),
+                tlv(0x02, &[serial]),
+                tlv(0x30, &tlv(0x06, &[0x2B, 0x65, 0x70])),
+                name.clone(),
+                tlv(0x30, &[]),
+                name,
+                spki(key),
+            ].concat(),
+        );
+
+        tlv(
+            0x30,
+            &[
+                tbs,
+                tlv(0x30, &tlv(0x06, &[0x2B, 0x65, 0x70])),
+                tlv(0x03, &[0; 65]),
+            ].concat(),
+        )
+    }
+
+    fn sha256(data: &[u8]) -> Vec<u8> {
+        DigestType::SHA256.hash(data).unwrap().as_ref().to_vec()
+    }
+
+    #[test]
+    fn test_subject_public_key_info() {
+        assert_eq!(subject_public_key_info(&cert(1, 7)).unwrap(), &spki(7)[..]);
+        assert!(subject_public_key_info(&spki(7)).is_err());
+        assert!(subject_public_key_info(&cert(1, 7)[..100]).is_err());
+        assert!(subject_public_key_info(&[]).is_err());
+    }
+
+    #[test]
+    fn test_dane_ee() {
+        let chain = vec![cert(1, 1), cert(2, 2)];
+        let tlsa = TLSA::new(
+            CertUsage::DaneEe,
+            Selector::Spki,
+            Matching::Sha256,
+            sha256(&spki(1)),
+        );
+
+        let verdict = verify(&[tlsa], &chain);
+        assert_eq!(
+            verdict,
+            Verdict::Matched {
+                usage: CertUsage::DaneEe,
+                chain_index: 0,
+            }
+        );
+        assert!(verdict.is_ok());
+        assert!(!verdict.pkix_required());
+
+        // the key of the CA does not match an end entity record
+        let tlsa = TLSA::new(
+            CertUsage::DaneEe,
+            Selector::Spki,
+            Matching::Sha256,
+            sha256(&spki(2)),
+        );
+        assert_eq!(verify(&[tlsa], &chain), Verdict::Mismatch);
+    }
+
+    #[test]
+    fn test_pkix_ta() {
+        let chain = vec![cert(1, 1), cert(2, 2), cert(3, 3)];
+        let tlsa = TLSA::new(CertUsage::PkixTa, Selector::Full, Matching::Raw, cert(3, 3));
+
+        let verdict = verify(&[tlsa], &chain);
+        assert_eq!(
+            verdict,
+            Verdict::Matched {
+                usage: CertUsage::PkixTa,
+                chain_index: 2,
+            }
+        );
+        assert!(verdict.pkix_required());
+
+        // the end entity is not a CA
+        let tlsa = TLSA::new(CertUsage::DaneTa, Selector::Full, Matching::Raw, cert(1, 1));
+        assert_eq!(verify(&[tlsa], &chain), Verdict::Mismatch);
+    }
+
+    #[test]
+    fn test_unusable() {
+        let chain = vec![cert(1, 1)];
+        let unassigned = TLSA::new(
+            CertUsage::Unassigned(4),
+            Selector::Full,
+            Matching::Raw,
+            cert(1, 1),
+        );
+        let private = TLSA::new(
+            CertUsage::DaneEe,
+            Selector::Full,
+            Matching::Private,
+            cert(1, 1),
+        );
+
+        let verdict = verify(&[unassigned.clone(), private], &chain);
+        assert_eq!(verdict, Verdict::Unusable);
+        assert!(verdict.is_ok());
+        assert!(verdict.pkix_required());
+        assert_eq!(verify(&[], &chain), Verdict::Unusable);
+
+        // a usable record which does not match fails the connection
+        let mismatch = TLSA::new(
+            CertUsage::DaneEe,
+            Selector::Full,
+            Matching::Sha512,
+            vec![0; 64],
+        );
+        let verdict = verify(&[unassigned, mismatch.clone()], &chain);
+        assert_eq!(verdict, Verdict::Mismatch);
+        assert!(!verdict.is_ok());
+        assert_eq!(verify(&[mismatch], &[] as &[Vec<u8>]), Verdict::Mismatch);
+    }
+}
@@ -123,6 +123,9 @@ impl KeyFormat {
                     }
                 }
             }
+            e @ Algorithm::ED448 => {
+                return Err(format!("unsupported Algorithm: {:?}", e).into())
+            }
             #[cfg(not(all(feature = "openssl", feature = "ring")))]
             e @ _ => {
                 return Err(
@@ -164,6 +167,9 @@ impl KeyFormat {
             Algorithm::ECDSAP384SHA384 => KeyPair::generate(algorithm)?,
             #[cfg(feature = "ring")]
             Algorithm::ED25519 => return KeyPair::generate_pkcs8(algorithm),
+            e @ Algorithm::ED448 => {
+                return Err(format!("unsupported Algorithm: {:?}", e).into())
+            }
             #[cfg(not(all(feature = "openssl", feature = "ring")))]
             e @ _ => {
                 return Err(
@@ -300,7 +300,12 @@ impl KeyPair {
         digest_type: DigestType,
     ) -> DnsSecResult<DS> {
         self.to_dnskey(algorithm)
-            .and_then(|dnskey| self.key_tag().map(|key_tag| (key_tag, dnskey)))
+            .and_then(|dnskey| {
+                dnskey
+                    .calculate_key_tag()
+                    .map(|key_tag| (key_tag, dnskey))
+                    .map_err(Into::into)
+            })
             .and_then(|(key_tag, dnskey)| {
                 dnskey
                     .to_digest(name, digest_type)
@@ -448,6 +453,9 @@ impl KeyPair {
                     ).into(),
                 )
             }
+            Algorithm::ED448 => Err(
+                DnsSecErrorKind::Message("ED448 is not supported by ring or openssl").into(),
+            ),
             #[cfg(not(all(feature = "openssl", feature = "ring")))]
             _ => Err(
                 DnsSecErrorKind::Message("openssl nor ring feature(s) not enabled").into(),
@@ -487,6 +495,9 @@ impl KeyPair {
                     .map_err(|e| e.into())
                     .map(|pkcs8_bytes| pkcs8_bytes.to_vec())
             }
+            Algorithm::ED448 => Err(
+                DnsSecErrorKind::Message("ED448 is not supported by ring or openssl").into(),
+            ),
             #[cfg(not(all(feature = "openssl", feature = "ring")))]
             _ => Err(
                 DnsSecErrorKind::Message("openssl nor ring feature(s) not enabled").into(),
@@ -529,6 +540,33 @@ mod tests {
         hash_test(Algorithm::ED25519, KeyFormat::Pkcs8);
     }
 
+    #[cfg(feature = "ring")]
+    #[test]
+    fn test_ds_key_tag() {
+        use rr::Name;
+
+        let algorithm = Algorithm::ED25519;
+        let key_format = KeyFormat::Pkcs8;
+        let key = key_format
+            .decode_key(
+                &key_format.generate_and_encode(algorithm, None).unwrap(),
+                None,
+                algorithm,
+            )
+            .unwrap();
+
+        // the key tag is over the entire DNSKEY rdata, not only the public key
+        let dnskey = key.to_dnskey(algorithm).unwrap();
+        let ds = key.to_ds(&Name::root(), algorithm, DigestType::SHA256)
+            .unwrap();
+        assert_eq!(ds.key_tag(), dnskey.calculate_key_tag().unwrap());
+    }
+
+    #[test]
+    fn test_ed448_unsupported() {
+        assert!(KeyPair::generate(Algorithm::ED448).is_err());
+    }
+
     fn public_key_test(algorithm: Algorithm, key_format: KeyFormat) {
         let key = key_format
             .decode_key(
@@ -20,6 +20,7 @@
 mod key_format;
 mod keypair;
 mod signer;
+mod tsigner;
 
 use trust_dns_proto::rr::dnssec;
 
@@ -37,6 +38,7 @@ pub use self::dnssec::SupportedAlgorithms;
 pub use self::dnssec::TrustAnchor;
 pub use self::dnssec::tbs;
 pub use self::dnssec::TBS;
+pub use self::tsigner::{time_other, TSigner, TsigAlgorithm, DEFAULT_FUDGE};
 pub use self::dnssec::Verifier;
 
 pub use error::DnsSecError;
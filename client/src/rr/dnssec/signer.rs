@@ -30,7 +30,7 @@ use rr::RData;
 #[cfg(any(feature = "openssl", feature = "ring"))]
 use rr::dnssec::KeyPair;
 #[cfg(any(feature = "openssl", feature = "ring"))]
-use rr::dnssec::Algorithm;
+use rr::dnssec::{Algorithm, DnsSecResult};
 #[cfg(any(feature = "openssl", feature = "ring"))]
 use rr::rdata::SIG;
 #[cfg(any(feature = "openssl", feature = "ring"))]
@@ -284,14 +284,15 @@ impl Signer {
         }
     }
 
-    /// Version of Signer for verifying RRSIGs and SIG0 records.
+    /// Version of Signer for signing messages with SIG(0), e.g. for authenticating dynamic updates,
+    ///  see `ClientFuture::new` and `SyncClient::with_signer`.
     ///
     /// # Arguments
     ///
     /// * `key_rdata` - the KEY and public key material
     /// * `key` - the private key for signing, unless validating, where just the public key is necessary
-    /// * `signer_name` - name in the zone to which this DNSKEY is bound
-    /// * `is_zone_update_auth` - this key may be used for updating the zone
+    /// * `signer_name` - name in the zone at which the KEY is published, the server looks the key
+    ///                   up by this name to verify the signature
     pub fn sig0(key_rdata: KEY, key: KeyPair, signer_name: Name) -> Self {
         let algorithm = key_rdata.algorithm();
 
@@ -305,6 +306,40 @@ impl Signer {
         }
     }
 
+    /// Version of Signer for SIG(0) with the KEY of the key pair, see `sig0`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the private key for signing
+    /// * `algorithm` - the algorithm of the key
+    /// * `signer_name` - name in the zone at which the KEY is published, see `sig0_key_record`
+    pub fn sig0_from_key_pair(
+        key: KeyPair,
+        algorithm: Algorithm,
+        signer_name: Name,
+    ) -> DnsSecResult<Self> {
+        let key_rdata = try!(key.to_sig0key(algorithm));
+        Ok(Self::sig0(key_rdata, key, signer_name))
+    }
+
+    /// The KEY record to publish in the zone, at the signer name, for the server to verify the
+    ///  SIG(0)s of this Signer, e.g. added with an update signed by a key already in the zone.
+    ///
+    /// # Return
+    ///
+    /// None if this is not a SIG(0) Signer, i.e. its key is a DNSKEY
+    pub fn sig0_key_record(&self, ttl: u32) -> Option<Record> {
+        match self.key_rdata {
+            RData::KEY(_) => Some(Record::from_rdata(
+                self.signer_name.clone(),
+                ttl,
+                RecordType::KEY,
+                self.key_rdata.clone(),
+            )),
+            _ => None,
+        }
+    }
+
     /// Version of Signer for signing RRSIGs and SIG0 records.
     #[deprecated(note = "use SIG0 or DNSSec constructors")]
     pub fn new(
@@ -638,6 +673,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sig0_from_key_pair() {
+        let signer_name = Name::parse("update.example.com.", None).unwrap();
+        let rsa = Rsa::generate(2048).unwrap();
+        let key = KeyPair::from_rsa(rsa).unwrap();
+        let signer =
+            Signer::sig0_from_key_pair(key, Algorithm::RSASHA256, signer_name.clone()).unwrap();
+
+        let record = signer.sig0_key_record(3600).unwrap();
+        assert_eq!(record.name(), &signer_name);
+        assert_eq!(record.rr_type(), RecordType::KEY);
+        assert_eq!(record.ttl(), 3600);
+        let sig0key = match *record.rdata() {
+            RData::KEY(ref key) => key.clone(),
+            _ => panic!("not a KEY"),
+        };
+
+        let mut update: Message = Message::new();
+        let mut zone: Query = Query::new();
+        zone.set_name(Name::parse("example.com.", None).unwrap());
+        zone.set_query_type(RecordType::SOA);
+        update.add_query(zone);
+        update.finalize(&signer, 0).expect("should have signed");
+
+        if let RData::SIG(ref sig) = *update.sig0()[0].rdata() {
+            assert_eq!(sig.signer_name(), &signer_name);
+            assert!(sig0key.verify_message(&update, sig.sig(), sig).is_ok());
+        } else {
+            panic!("not a SIG");
+        }
+
+        // a DNSSec signer has no KEY to publish
+        let rsa = Rsa::generate(2048).unwrap();
+        let key = KeyPair::from_rsa(rsa).unwrap();
+        let dnskey = key.to_dnskey(Algorithm::RSASHA256).unwrap();
+        let signer = Signer::dnssec(dnskey, key, signer_name, Duration::zero());
+        assert!(signer.sig0_key_record(3600).is_none());
+    }
+
     #[test]
     #[allow(deprecated)]
     fn test_sign_and_verify_rrset() {
@@ -0,0 +1,804 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! signing and verification of messages with a shared secret, TSIG, RFC 8945
+
+#[cfg(all(not(feature = "ring"), feature = "openssl"))]
+use openssl::pkey::PKey;
+#[cfg(all(not(feature = "ring"), feature = "openssl"))]
+use openssl::sign::Signer;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+#[cfg(feature = "ring")]
+use ring::hmac;
+use trust_dns_proto::error::{ProtoErrorKind, ProtoResult};
+
+use op::{Message, MessageFinalizer};
+use rr::{DNSClass, Name, RData, Record, RecordType};
+use rr::dnssec::DigestType;
+use rr::rdata::TSIG;
+use rr::rdata::tsig::{BADKEY, BADSIG, BADTIME};
+use serialize::binary::{BinEncoder, BinSerializable, EncodeMode};
+
+/// The recommended fudge, RFC 8945 section 10
+pub const DEFAULT_FUDGE: u16 = 300;
+
+/// The MAC algorithms of TSIG, by the names used in the TSIG record, RFC 8945 section 6
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TsigAlgorithm {
+    /// `hmac-sha1.`, not recommended
+    HmacSha1,
+    /// `hmac-sha256.`, mandatory to implement
+    HmacSha256,
+    /// `hmac-sha384.`
+    HmacSha384,
+    /// `hmac-sha512.`
+    HmacSha512,
+}
+
+impl TsigAlgorithm {
+    /// Returns the algorithm of the name, e.g. `hmac-sha256` with or without the final dot, the
+    ///  case is ignored
+    pub fn from_str(name: &str) -> ProtoResult<Self> {
+        match name.trim_right_matches('.').to_lowercase().as_str() {
+            "hmac-sha1" => Ok(TsigAlgorithm::HmacSha1),
+            "hmac-sha256" => Ok(TsigAlgorithm::HmacSha256),
+            "hmac-sha384" => Ok(TsigAlgorithm::HmacSha384),
+            "hmac-sha512" => Ok(TsigAlgorithm::HmacSha512),
+            _ => Err(ProtoErrorKind::Msg(format!("unsupported TSIG algorithm: {}", name)).into()),
+        }
+    }
+
+    /// Returns the algorithm of the algorithm name of a TSIG record
+    pub fn from_name(name: &Name) -> ProtoResult<Self> {
+        Self::from_str(&name.to_string())
+    }
+
+    /// The name of the algorithm in the TSIG record
+    pub fn to_name(&self) -> Name {
+        let name = match *self {
+            TsigAlgorithm::HmacSha1 => "hmac-sha1.",
+            TsigAlgorithm::HmacSha256 => "hmac-sha256.",
+            TsigAlgorithm::HmacSha384 => "hmac-sha384.",
+            TsigAlgorithm::HmacSha512 => "hmac-sha512.",
+        };
+
+        Name::parse(name, None).expect("the algorithm names are valid")
+    }
+
+    /// The hash of the HMAC
+    pub fn digest_type(&self) -> DigestType {
+        match *self {
+            TsigAlgorithm::HmacSha1 => DigestType::SHA1,
+            TsigAlgorithm::HmacSha256 => DigestType::SHA256,
+            TsigAlgorithm::HmacSha384 => DigestType::SHA384,
+            TsigAlgorithm::HmacSha512 => DigestType::SHA512,
+        }
+    }
+}
+
+/// Signs messages with a key shared by the client and the server, and verifies the messages
+///  signed by the other side, TSIG, RFC 8945
+///
+/// Used as the finalizer of a client, see `ClientFuture::with_tsigner`, it signs updates and zone
+///  transfers, and only accepts the responses to them which carry a valid TSIG. A server verifies
+///  the requests and signs the responses with the key of the request, see
+///  `Catalog::add_tsig_key`.
+///
+/// A server which rejects a request as signed outside of the fudge, BADTIME, responds with its
+///  time. The signer then signs with the time of the server from then on, shared by its clones,
+///  and the client sends the request once more, RFC 8945 section 5.2.3.
+#[derive(Clone)]
+pub struct TSigner {
+    key_name: Name,
+    algorithm: TsigAlgorithm,
+    key: Vec<u8>,
+    fudge: u16,
+    // seconds the clock of the server is ahead of ours
+    time_offset: Arc<AtomicIsize>,
+}
+
+impl TSigner {
+    /// Creates a new TSigner
+    ///
+    /// # Arguments
+    ///
+    /// * `key_name` - the name of the key, which the client and server agree on, e.g.
+    ///                `update-key.example.com.`
+    /// * `algorithm` - the MAC algorithm of the key
+    /// * `key` - the shared secret, e.g. the decoded base64 secret of a BIND key file
+    /// * `fudge` - seconds of allowed difference between the clocks of the client and server,
+    ///             see `DEFAULT_FUDGE`
+    pub fn new(key_name: Name, algorithm: TsigAlgorithm, key: Vec<u8>, fudge: u16) -> Self {
+        TSigner {
+            key_name: key_name,
+            algorithm: algorithm,
+            key: key,
+            fudge: fudge,
+            time_offset: Arc::new(AtomicIsize::new(0)),
+        }
+    }
+
+    /// The name of the key
+    pub fn key_name(&self) -> &Name {
+        &self.key_name
+    }
+
+    /// The MAC algorithm of the key
+    pub fn algorithm(&self) -> TsigAlgorithm {
+        self.algorithm
+    }
+
+    /// Seconds of allowed difference between the clocks of the client and server
+    pub fn fudge(&self) -> u16 {
+        self.fudge
+    }
+
+    /// Seconds the clock of the server is ahead of ours, learned from its last BADTIME response,
+    ///  which are added to the current time when signing requests and verifying responses
+    pub fn time_offset(&self) -> i64 {
+        self.time_offset.load(Ordering::Relaxed) as i64
+    }
+
+    /// The current time, as the server sees it
+    fn server_time(&self, current_time: u32) -> u64 {
+        let server_time = i64::from(current_time) + self.time_offset();
+        if server_time < 0 { 0 } else { server_time as u64 }
+    }
+
+    /// Computes the MAC of the data with the key
+    #[cfg(feature = "ring")]
+    pub fn mac(&self, data: &[u8]) -> ProtoResult<Vec<u8>> {
+        let digest = try!(self.algorithm.digest_type().to_ring_digest_alg());
+        let key = hmac::SigningKey::new(digest, &self.key);
+        Ok(hmac::sign(&key, data).as_ref().to_vec())
+    }
+
+    /// Computes the MAC of the data with the key
+    #[cfg(all(not(feature = "ring"), feature = "openssl"))]
+    pub fn mac(&self, data: &[u8]) -> ProtoResult<Vec<u8>> {
+        let digest = try!(self.algorithm.digest_type().to_openssl_digest());
+        PKey::hmac(&self.key)
+            .and_then(|key| {
+                let mut signer = try!(Signer::new(digest, &key));
+                try!(signer.update(data));
+                signer.finish()
+            })
+            .map_err(|e| ProtoErrorKind::Msg(format!("hmac error: {}", e)).into())
+    }
+
+    /// This will always error, enable openssl or ring at compile time
+    #[cfg(not(any(feature = "openssl", feature = "ring")))]
+    pub fn mac(&self, _: &[u8]) -> ProtoResult<Vec<u8>> {
+        Err(
+            ProtoErrorKind::Message("the ring or openssl feature must be enabled for TSIG").into(),
+        )
+    }
+
+    /// Signs the message, returning the TSIG record to append to it, see `Message::add_sig0`
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the message to sign, its id is the original id of the TSIG
+    /// * `request_mac` - for a response, the MAC of the TSIG of the request
+    /// * `time_signed` - seconds since the epoch
+    /// * `error` - for a response, the TSIG error of the request, e.g. `BADTIME`, or 0
+    /// * `other` - for a `BADTIME` error, the time of the server, see `time_other`
+    pub fn sign_message(
+        &self,
+        message: &Message,
+        request_mac: Option<&[u8]>,
+        time_signed: u64,
+        error: u16,
+        other: Vec<u8>,
+    ) -> ProtoResult<Record> {
+        let pre_tsig = TSIG::new(
+            self.algorithm.to_name(),
+            time_signed,
+            self.fudge,
+            Vec::new(),
+            message.id(),
+            error,
+            other,
+        );
+
+        self.sign_with(message, pre_tsig, |message_bytes, tsig| {
+            self.signed_data(message_bytes, request_mac, tsig)
+        })
+    }
+
+    /// Signs a message after the first of a response of several messages, e.g. a zone transfer,
+    ///  returning the TSIG record to append to it, RFC 8945 section 5.3.1
+    ///
+    /// The MAC covers the MAC of the message before, rather than that of the request, and of the
+    ///  TSIG only its timers.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the message to sign, its id is the original id of the TSIG
+    /// * `prior_mac` - the MAC of the TSIG of the message before
+    /// * `time_signed` - seconds since the epoch
+    pub fn sign_message_continued(
+        &self,
+        message: &Message,
+        prior_mac: &[u8],
+        time_signed: u64,
+    ) -> ProtoResult<Record> {
+        let pre_tsig = TSIG::new(
+            self.algorithm.to_name(),
+            time_signed,
+            self.fudge,
+            Vec::new(),
+            message.id(),
+            0,
+            Vec::new(),
+        );
+
+        self.sign_with(message, pre_tsig, |message_bytes, tsig| {
+            continued_data(message_bytes, prior_mac, tsig)
+        })
+    }
+
+    /// Signs the message, with the MAC of the data which `signed_data` returns for the encoded
+    ///  message and the TSIG without its MAC
+    fn sign_with<F>(&self, message: &Message, pre_tsig: TSIG, signed_data: F) -> ProtoResult<Record>
+    where
+        F: FnOnce(&[u8], &TSIG) -> ProtoResult<Vec<u8>>,
+    {
+        let mut message_bytes = Vec::with_capacity(512);
+        {
+            // the additional count does not include the TSIG
+            let mut encoder = BinEncoder::with_mode(&mut message_bytes, EncodeMode::Signing);
+            try!(message.emit(&mut encoder));
+        }
+
+        let mac = try!(self.mac(&try!(signed_data(&message_bytes, &pre_tsig))));
+
+        let tsig = TSIG::new(
+            pre_tsig.algorithm().clone(),
+            pre_tsig.time_signed(),
+            pre_tsig.fudge(),
+            mac,
+            pre_tsig.original_id(),
+            pre_tsig.error(),
+            pre_tsig.other().to_vec(),
+        );
+
+        let mut record = Record::from_rdata(
+            self.key_name.clone(),
+            0,
+            RecordType::TSIG,
+            RData::TSIG(tsig),
+        );
+        record.set_dns_class(DNSClass::ANY);
+        Ok(record)
+    }
+
+    /// Verifies the TSIG of the received message, RFC 8945 section 5.2
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the message as it was decoded, see `Message::signed_bytes`
+    /// * `request_mac` - for a response, the MAC of the TSIG of the request
+    /// * `current_time` - seconds since the epoch
+    ///
+    /// # Return
+    ///
+    /// The verified TSIG, or an error of `ProtoErrorKind::Tsig` with the TSIG error to respond
+    ///  with, `BADKEY` if the message was signed with another key, `BADSIG` if the MAC does not
+    ///  match, and `BADTIME` if the MAC matches but the time signed is not within the fudge.
+    pub fn verify_message<'m>(
+        &self,
+        message: &'m Message,
+        request_mac: Option<&[u8]>,
+        current_time: u64,
+    ) -> ProtoResult<&'m TSIG> {
+        let tsig = try!(self.verify_mac(message, request_mac));
+        if !tsig.is_timely(current_time) {
+            return Err(ProtoErrorKind::Tsig(BADTIME).into());
+        }
+
+        Ok(tsig)
+    }
+
+    /// Verifies the TSIG of a received message after the first of a response of several
+    ///  messages, e.g. a zone transfer, see `sign_message_continued`
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the message as it was decoded, see `Message::signed_bytes`
+    /// * `prior_mac` - the MAC of the TSIG of the message before
+    /// * `current_time` - seconds since the epoch
+    pub fn verify_message_continued<'m>(
+        &self,
+        message: &'m Message,
+        prior_mac: &[u8],
+        current_time: u64,
+    ) -> ProtoResult<&'m TSIG> {
+        let tsig = try!(self.verify_mac_with(message, |message_bytes, tsig| {
+            continued_data(message_bytes, prior_mac, tsig)
+        }));
+        if !tsig.is_timely(current_time) {
+            return Err(ProtoErrorKind::Tsig(BADTIME).into());
+        }
+
+        Ok(tsig)
+    }
+
+    /// Verifies the key and MAC of the TSIG of the message, but not its time
+    fn verify_mac<'m>(
+        &self,
+        message: &'m Message,
+        request_mac: Option<&[u8]>,
+    ) -> ProtoResult<&'m TSIG> {
+        self.verify_mac_with(message, |message_bytes, tsig| {
+            self.signed_data(message_bytes, request_mac, tsig)
+        })
+    }
+
+    /// Verifies the key of the TSIG of the message, and its MAC of the data which `signed_data`
+    ///  returns for the message as it was signed and the TSIG
+    fn verify_mac_with<'m, F>(&self, message: &'m Message, signed_data: F) -> ProtoResult<&'m TSIG>
+    where
+        F: FnOnce(&[u8], &TSIG) -> ProtoResult<Vec<u8>>,
+    {
+        let record = try!(message.tsig().ok_or_else(|| {
+            ProtoErrorKind::Message("the message is not signed with a TSIG")
+        }));
+        let tsig = match *record.rdata() {
+            RData::TSIG(ref tsig) => tsig,
+            _ => return Err(ProtoErrorKind::Message("the TSIG record has no TSIG data").into()),
+        };
+
+        if record.name() != &self.key_name ||
+            TsigAlgorithm::from_name(tsig.algorithm()).ok() != Some(self.algorithm)
+        {
+            return Err(ProtoErrorKind::Tsig(BADKEY).into());
+        }
+
+        let mut message_bytes = try!(message.signed_bytes().ok_or_else(|| {
+            ProtoErrorKind::Message("the message was not received with a TSIG")
+        })).to_vec();
+        if message_bytes.len() < 12 {
+            return Err(ProtoErrorKind::Message("the signed message has no header").into());
+        }
+
+        // the MAC covers the original id, and the additional count without the TSIG
+        let additional_count = (u16::from(message_bytes[10]) << 8 | u16::from(message_bytes[11]))
+            .saturating_sub(1);
+        message_bytes[0] = (tsig.original_id() >> 8) as u8;
+        message_bytes[1] = tsig.original_id() as u8;
+        message_bytes[10] = (additional_count >> 8) as u8;
+        message_bytes[11] = additional_count as u8;
+
+        let mac = try!(self.mac(&try!(signed_data(&message_bytes, tsig))));
+        if !constant_time_eq(&mac, tsig.mac()) {
+            return Err(ProtoErrorKind::Tsig(BADSIG).into());
+        }
+
+        Ok(tsig)
+    }
+
+    /// The data which the MAC covers, RFC 8945 section 4.3
+    fn signed_data(
+        &self,
+        message_bytes: &[u8],
+        request_mac: Option<&[u8]>,
+        tsig: &TSIG,
+    ) -> ProtoResult<Vec<u8>> {
+        let mut data = Vec::with_capacity(message_bytes.len() + 128);
+        {
+            let mut encoder = BinEncoder::new(&mut data);
+            if let Some(request_mac) = request_mac {
+                try!(encoder.emit_u16(request_mac.len() as u16));
+                try!(encoder.emit_vec(request_mac));
+            }
+            try!(encoder.emit_vec(message_bytes));
+            try!(tsig.emit_variables(&mut encoder, &self.key_name));
+        }
+
+        Ok(data)
+    }
+}
+
+/// The data which the MAC of a message after the first of a response of several messages covers,
+///  RFC 8945 section 5.3.1
+fn continued_data(message_bytes: &[u8], prior_mac: &[u8], tsig: &TSIG) -> ProtoResult<Vec<u8>> {
+    let mut data = Vec::with_capacity(message_bytes.len() + prior_mac.len() + 16);
+    {
+        let mut encoder = BinEncoder::new(&mut data);
+        try!(encoder.emit_u16(prior_mac.len() as u16));
+        try!(encoder.emit_vec(prior_mac));
+        try!(encoder.emit_vec(message_bytes));
+        try!(tsig.emit_timers(&mut encoder));
+    }
+
+    Ok(data)
+}
+
+/// The time of the server, for the other data of a `BADTIME` error, RFC 8945 section 5.2.3
+pub fn time_other(current_time: u64) -> Vec<u8> {
+    (0..6).rev().map(|i| (current_time >> (i * 8)) as u8).collect()
+}
+
+/// The time of the server from the other data of a `BADTIME` error, see `time_other`
+pub fn time_from_other(other: &[u8]) -> Option<u64> {
+    if other.len() != 6 {
+        return None;
+    }
+
+    Some(other.iter().fold(0, |time, byte| time << 8 | u64::from(*byte)))
+}
+
+/// Compares the MACs in time independent of where they differ
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len() &&
+        left.iter().zip(right).fold(0, |diff, (l, r)| diff | (l ^ r)) == 0
+}
+
+impl MessageFinalizer for TSigner {
+    fn finalize_message(&self, message: &Message, current_time: u32) -> ProtoResult<Vec<Record>> {
+        debug!("signing message with TSIG key: {}", self.key_name);
+        let time_signed = self.server_time(current_time);
+        let tsig = try!(self.sign_message(message, None, time_signed, 0, Vec::new()));
+        Ok(vec![tsig])
+    }
+
+    fn verify_response(
+        &self,
+        request_signatures: &[Record],
+        response: &Message,
+        current_time: u32,
+    ) -> ProtoResult<()> {
+        let request_mac = request_signatures.iter().rev().filter_map(|record| {
+            match *record.rdata() {
+                RData::TSIG(ref tsig) => Some(tsig.mac()),
+                _ => None,
+            }
+        }).next();
+
+        // the server answers a request which it could not verify with an unsigned error, which
+        //  is passed on as the NotAuth response it is, RFC 8945 section 5.3.2
+        if let Some(&RData::TSIG(ref tsig)) = response.tsig().map(|record| record.rdata()) {
+            if tsig.mac().is_empty() && (tsig.error() == BADKEY || tsig.error() == BADSIG) {
+                warn!("request was not verified by the server, TSIG error: {}", tsig.error());
+                return Ok(());
+            }
+        }
+
+        let tsig = try!(self.verify_mac(response, request_mac));
+
+        // the request was signed outside of the fudge, the response carries the time of the
+        //  server to sign it again with, RFC 8945 section 5.2.3
+        if tsig.error() == BADTIME {
+            let server_time = try!(time_from_other(tsig.other()).ok_or_else(|| {
+                ProtoErrorKind::Message("the BADTIME response carries no time of the server")
+            }));
+            let skew = server_time as i64 - i64::from(current_time);
+            warn!(
+                "clock differs from the server by {} seconds, beyond the fudge of {} seconds",
+                skew,
+                self.fudge
+            );
+            self.time_offset.store(skew as isize, Ordering::Relaxed);
+            return Err(ProtoErrorKind::TsigClockSkew(skew).into());
+        }
+
+        if !tsig.is_timely(self.server_time(current_time)) {
+            return Err(ProtoErrorKind::Tsig(BADTIME).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use op::{Message, MessageType, Query};
+    use rr::rdata::tsig::{BADKEY, BADSIG, BADTIME};
+
+    use super::*;
+
+    fn tsigner() -> TSigner {
+        TSigner::new(
+            Name::parse("update-key.example.com.", None).unwrap(),
+            TsigAlgorithm::HmacSha256,
+            b"a secret shared by client and server".to_vec(),
+            DEFAULT_FUDGE,
+        )
+    }
+
+    /// Encodes and decodes the message, as it would be sent
+    fn transmit(message: &Message) -> Message {
+        Message::from_vec(&message.to_vec().unwrap()).unwrap()
+    }
+
+    fn tsig_error(result: ProtoResult<&TSIG>) -> u16 {
+        match *result.unwrap_err().kind() {
+            ProtoErrorKind::Tsig(error) => error,
+            ref kind => panic!("expected a TSIG error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_algorithm_names() {
+        for algorithm in &[
+            TsigAlgorithm::HmacSha1,
+            TsigAlgorithm::HmacSha256,
+            TsigAlgorithm::HmacSha384,
+            TsigAlgorithm::HmacSha512,
+        ]
+        {
+            assert_eq!(TsigAlgorithm::from_name(&algorithm.to_name()).unwrap(), *algorithm);
+        }
+
+        assert_eq!(TsigAlgorithm::from_str("HMAC-SHA256").unwrap(), TsigAlgorithm::HmacSha256);
+        assert!(TsigAlgorithm::from_str("hmac-md5.sig-alg.reg.int").is_err());
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231 test case 2
+        let signer = TSigner::new(
+            Name::root(),
+            TsigAlgorithm::HmacSha256,
+            b"Jefe".to_vec(),
+            DEFAULT_FUDGE,
+        );
+        let mac = signer.mac(b"what do ya want for nothing?").unwrap();
+        assert_eq!(
+            mac,
+            vec![
+                0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24,
+                0x26, 0x08, 0x95, 0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27,
+                0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9, 0x64, 0xec, 0x38, 0x43,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify_request_and_response() {
+        let signer = tsigner();
+
+        let mut request = Message::new();
+        request.set_id(1234);
+        request.add_query(Query::query(
+            Name::parse("example.com.", None).unwrap(),
+            RecordType::AXFR,
+        ));
+        request.finalize(&signer, 1000).unwrap();
+        assert!(request.tsig().is_some());
+
+        let received = transmit(&request);
+        let request_tsig = signer.verify_message(&received, None, 1000).unwrap().clone();
+
+        let mut response = Message::new();
+        response.set_id(received.id());
+        response.set_message_type(MessageType::Response);
+        response.add_queries(received.queries().to_vec());
+        let tsig = signer
+            .sign_message(&response, Some(request_tsig.mac()), 1001, 0, vec![])
+            .unwrap();
+        response.add_sig0(tsig);
+
+        let response = transmit(&response);
+        assert!(
+            signer
+                .verify_response(request.sig0(), &response, 1002)
+                .is_ok()
+        );
+
+        // the response covers the MAC of the request, it can not be replayed to another request
+        assert_eq!(
+            tsig_error(signer.verify_message(&response, Some(&[0; 32]), 1002)),
+            BADSIG
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify_continued() {
+        let signer = tsigner();
+        let name = Name::parse("example.com.", None).unwrap();
+
+        let mut first = Message::new();
+        first.set_id(1234);
+        first.set_message_type(MessageType::Response);
+        first.add_query(Query::query(name.clone(), RecordType::AXFR));
+        let first_tsig = signer
+            .sign_message(&first, Some(&[1; 32]), 1000, 0, vec![])
+            .unwrap();
+        first.add_sig0(first_tsig);
+        let first = transmit(&first);
+        let first_mac = signer
+            .verify_message(&first, Some(&[1; 32]), 1000)
+            .unwrap()
+            .mac()
+            .to_vec();
+
+        // the next message covers the MAC of the first, not that of the request
+        let mut next = Message::new();
+        next.set_id(1234);
+        next.set_message_type(MessageType::Response);
+        next.add_answer(Record::from_rdata(
+            name,
+            3600,
+            RecordType::A,
+            RData::A(Ipv4Addr::new(93, 184, 216, 34)),
+        ));
+        let next_tsig = signer.sign_message_continued(&next, &first_mac, 1001).unwrap();
+        next.add_sig0(next_tsig);
+        let next = transmit(&next);
+
+        assert!(signer.verify_message_continued(&next, &first_mac, 1001).is_ok());
+        assert_eq!(
+            tsig_error(signer.verify_message_continued(&next, &[1; 32], 1001)),
+            BADSIG
+        );
+        assert_eq!(
+            tsig_error(signer.verify_message(&next, Some(&first_mac), 1001)),
+            BADSIG
+        );
+    }
+
+    #[test]
+    fn test_verify_errors() {
+        let signer = tsigner();
+
+        let mut request = Message::new();
+        request.add_query(Query::query(
+            Name::parse("example.com.", None).unwrap(),
+            RecordType::AXFR,
+        ));
+        request.finalize(&signer, 1000).unwrap();
+
+        // a changed id is covered by the original id
+        let mut forwarded = request.clone();
+        forwarded.set_id(4321);
+        assert!(signer.verify_message(&transmit(&forwarded), None, 1000).is_ok());
+
+        // outside of the fudge
+        let received = transmit(&request);
+        assert_eq!(tsig_error(signer.verify_message(&received, None, 1301)), BADTIME);
+        assert_eq!(tsig_error(signer.verify_message(&received, None, 699)), BADTIME);
+
+        // another key of the same name
+        let other = TSigner::new(
+            signer.key_name().clone(),
+            TsigAlgorithm::HmacSha256,
+            b"another secret".to_vec(),
+            DEFAULT_FUDGE,
+        );
+        assert_eq!(tsig_error(other.verify_message(&received, None, 1000)), BADSIG);
+
+        // another key
+        let other = TSigner::new(
+            Name::parse("other-key.", None).unwrap(),
+            TsigAlgorithm::HmacSha256,
+            b"a secret shared by client and server".to_vec(),
+            DEFAULT_FUDGE,
+        );
+        assert_eq!(tsig_error(other.verify_message(&received, None, 1000)), BADKEY);
+
+        // a changed message
+        let mut bytes = request.to_vec().unwrap();
+        bytes[2] ^= 0x01;
+        let changed = Message::from_vec(&bytes).unwrap();
+        assert_eq!(tsig_error(signer.verify_message(&changed, None, 1000)), BADSIG);
+    }
+
+    #[test]
+    fn test_unsigned_response() {
+        let signer = tsigner();
+
+        let mut request = Message::new();
+        request.add_query(Query::query(
+            Name::parse("example.com.", None).unwrap(),
+            RecordType::AXFR,
+        ));
+        request.finalize(&signer, 1000).unwrap();
+
+        // a response without a TSIG is not accepted
+        let mut response = Message::new();
+        response.set_message_type(MessageType::Response);
+        assert!(
+            signer
+                .verify_response(request.sig0(), &transmit(&response), 1000)
+                .is_err()
+        );
+
+        // unless it is the error of a request the server could not verify
+        let tsig = TSIG::new(
+            TsigAlgorithm::HmacSha256.to_name(),
+            1000,
+            DEFAULT_FUDGE,
+            vec![],
+            0,
+            BADKEY,
+            vec![],
+        );
+        response.add_sig0(Record::from_rdata(
+            signer.key_name().clone(),
+            0,
+            RecordType::TSIG,
+            RData::TSIG(tsig),
+        ));
+        assert!(
+            signer
+                .verify_response(request.sig0(), &transmit(&response), 1000)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_time_other() {
+        assert_eq!(time_other(0x0102_0304_0506), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(time_from_other(&[1, 2, 3, 4, 5, 6]), Some(0x0102_0304_0506));
+        assert_eq!(time_from_other(&[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn test_badtime_response() {
+        let signer = tsigner();
+        let shared = signer.clone();
+
+        let mut request = Message::new();
+        request.add_query(Query::query(
+            Name::parse("example.com.", None).unwrap(),
+            RecordType::AXFR,
+        ));
+        request.finalize(&signer, 1000).unwrap();
+        let request_mac = match *request.tsig().unwrap().rdata() {
+            RData::TSIG(ref tsig) => tsig.mac().to_vec(),
+            _ => panic!("expected a TSIG"),
+        };
+
+        // the server is an hour ahead
+        let mut response = Message::new();
+        response.set_message_type(MessageType::Response);
+        let tsig = signer
+            .sign_message(&response, Some(&request_mac), 4600, BADTIME, time_other(4600))
+            .unwrap();
+        response.add_sig0(tsig);
+
+        match *signer
+            .verify_response(request.sig0(), &transmit(&response), 1000)
+            .unwrap_err()
+            .kind() {
+            ProtoErrorKind::TsigClockSkew(skew) => assert_eq!(skew, 3600),
+            ref kind => panic!("expected the clock skew: {:?}", kind),
+        }
+        assert_eq!(shared.time_offset(), 3600);
+
+        // the request is signed again with the time of the server
+        let mut request = Message::new();
+        request.add_query(Query::query(
+            Name::parse("example.com.", None).unwrap(),
+            RecordType::AXFR,
+        ));
+        request.finalize(&shared, 1001).unwrap();
+        let received = transmit(&request);
+        assert_eq!(signer.verify_message(&received, None, 4601).unwrap().time_signed(), 4601);
+
+        // and its response, signed at the time of the server, is accepted
+        let request_tsig = signer.verify_message(&received, None, 4601).unwrap().clone();
+        let mut response = Message::new();
+        response.set_message_type(MessageType::Response);
+        let tsig = signer
+            .sign_message(&response, Some(request_tsig.mac()), 4602, 0, vec![])
+            .unwrap();
+        response.add_sig0(tsig);
+        assert!(
+            signer
+                .verify_response(request.sig0(), &transmit(&response), 1002)
+                .is_ok()
+        );
+    }
+}
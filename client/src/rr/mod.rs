@@ -16,6 +16,8 @@
 
 //! Resource record related components, e.g. `Name` aka label, `Record`, `RData`, ...
 
+#[cfg(any(feature = "openssl", feature = "ring"))]
+pub mod dane;
 pub mod dnssec;
 
 use trust_dns_proto::rr;
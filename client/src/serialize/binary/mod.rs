@@ -22,3 +22,6 @@ pub use self::binary::BinSerializable;
 pub use self::binary::BinDecoder;
 pub use self::binary::BinEncoder;
 pub use self::binary::EncodeMode;
+pub use self::binary::NameCompression;
+pub use self::binary::DecodeMode;
+pub use self::binary::DecodeWarning;
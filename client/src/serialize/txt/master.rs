@@ -13,13 +13,26 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
 
 use error::*;
 use rr::{Name, IntoRecordSet, RecordType, Record, DNSClass, RData, RrKey, RecordSet};
 use serialize::txt::master_lex::{Lexer, Token};
 use serialize::txt::parse_rdata::RDataParser;
 
+/// The most files which may be included within each other, which also stops an `$INCLUDE` of the
+///  including file
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// The most records which may be added by one `$GENERATE`
+const MAX_GENERATE_RECORDS: u32 = 65_536;
+
+/// The widest a value of a `$GENERATE` modifier may be padded to
+const MAX_GENERATE_WIDTH: usize = 255;
+
 /// ```text
 /// 5. MASTER FILES
 ///
@@ -120,12 +133,25 @@ use serialize::txt::parse_rdata::RDataParser;
 /// ;               Semicolon is used to start a comment; the remainder of
 ///                 the line is ignored.
 /// ```
-pub struct Parser;
+///
+/// Besides the entries above, `$TTL <time>` sets the default TTL, RFC 2308 section 4, and
+///  `$GENERATE <range> <lhs> [<ttl>] [<class>] <type> <rhs>` adds a record for each value of the
+///  range, as BIND does. The files of `$INCLUDE` entries are read relative
+///  to the directory given to `Parser::with_include_dir`.
+pub struct Parser {
+    include_dir: Option<PathBuf>,
+}
 
 impl Parser {
     /// Returns a new Zone file parser
     pub fn new() -> Self {
-        Parser
+        Parser { include_dir: None }
+    }
+
+    /// Returns a new Zone file parser, which reads the files of `$INCLUDE` entries with relative
+    ///  paths from the directory, otherwise they are relative to the working directory
+    pub fn with_include_dir(include_dir: PathBuf) -> Self {
+        Parser { include_dir: Some(include_dir) }
     }
 
     /// Parse a file from the Lexer
@@ -138,14 +164,45 @@ impl Parser {
         lexer: Lexer,
         origin: Option<Name>,
     ) -> ParseResult<(Name, BTreeMap<RrKey, RecordSet>)> {
-        let mut lexer = lexer;
         let mut records: BTreeMap<RrKey, RecordSet> = BTreeMap::new();
+        let mut ttl: Option<u32> = None;
+        let mut class: Option<DNSClass> = None;
+
+        let origin = try!(self.parse_entries(
+            lexer,
+            origin,
+            &mut ttl,
+            &mut class,
+            &mut records,
+            0,
+        ));
+
+        //
+        // build the Authority and return.
+        let origin = try!(origin.ok_or(ParseError::from(
+            ParseErrorKind::Message("$ORIGIN was not specified"),
+        )));
+        Ok((origin, records))
+    }
+
+    /// Parses the entries of a file, or of an included file, into the records, returns the origin
+    ///  at the end of the file
+    ///
+    /// The default TTL and class carry over from an included file, its origin does not.
+    fn parse_entries(
+        &self,
+        lexer: Lexer,
+        origin: Option<Name>,
+        ttl: &mut Option<u32>,
+        class: &mut Option<DNSClass>,
+        records: &mut BTreeMap<RrKey, RecordSet>,
+        include_depth: usize,
+    ) -> ParseResult<Option<Name>> {
+        let mut lexer = Entries::new(lexer);
 
         let mut origin: Option<Name> = origin;
         let mut current_name: Option<Name> = None;
         let mut rtype: Option<RecordType> = None;
-        let mut ttl: Option<u32> = None;
-        let mut class: Option<DNSClass> = None;
         let mut state = State::StartLine;
         let mut tokens: Vec<Token> = Vec::new();
 
@@ -157,10 +214,11 @@ impl Parser {
                     tokens.clear();
 
                     match t {
-                        // if Dollar, then $INCLUDE or $ORIGIN
-                        Token::Include => unimplemented!(),
+                        // if Dollar, then $INCLUDE, $ORIGIN, $TTL or $GENERATE
+                        Token::Include => State::Include,
                         Token::Origin => State::Origin,
                         Token::Ttl => State::Ttl,
+                        Token::Generate => State::Generate,
 
                         // if CharData, then Name then ttl_class_type
                         Token::CharData(ref data) => {
@@ -183,7 +241,7 @@ impl Parser {
                 State::Ttl => {
                     match t {
                         Token::CharData(ref data) => {
-                            ttl = Some(try!(Self::parse_time(data)));
+                            *ttl = Some(try!(Self::parse_time(data)));
                             State::StartLine
                         }
                         _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
@@ -192,14 +250,52 @@ impl Parser {
                 State::Origin => {
                     match t {
                         Token::CharData(ref data) => {
-                            // TODO an origin was specified, should this be legal? definitely confusing...
-                            origin = Some(try!(Name::parse(data, None)));
+                            // a relative origin is relative to the current one
+                            origin = Some(try!(Name::parse(data, origin.as_ref())));
+                            State::StartLine
+                        }
+                        _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+                    }
+                }
+                State::Include => {
+                    match t {
+                        Token::EOL => {
+                            try!(self.include(
+                                &tokens,
+                                origin.as_ref(),
+                                ttl,
+                                class,
+                                records,
+                                include_depth,
+                            ));
                             State::StartLine
                         }
+                        Token::CharData(..) => {
+                            tokens.push(t);
+                            State::Include
+                        }
+                        _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+                    }
+                }
+                State::Generate => {
+                    match t {
+                        Token::EOL => {
+                            try!(Self::generate(
+                                &tokens,
+                                origin.as_ref(),
+                                *ttl,
+                                *class,
+                                records,
+                            ));
+                            State::StartLine
+                        }
+                        Token::CharData(..) => {
+                            tokens.push(t);
+                            State::Generate
+                        }
                         _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
                     }
                 }
-                State::Include => unimplemented!(),
                 State::TtlClassType => {
                     match t {
                         // if number, TTL
@@ -209,13 +305,13 @@ impl Parser {
                             // if it's a number it's a ttl
                             let result: ParseResult<u32> = Self::parse_time(data);
                             if result.is_ok() {
-                                ttl = result.ok();
+                                *ttl = result.ok();
                                 State::TtlClassType // hm, should this go to just ClassType?
                             } else {
                                 // if can parse DNSClass, then class
                                 let result = DNSClass::from_str(data);
                                 if result.is_ok() {
-                                    class = result.ok();
+                                    *class = result.ok();
                                     State::TtlClassType
                                 } else {
 
@@ -271,7 +367,7 @@ impl Parser {
                                         // TODO, this looks wrong, get_expire() should be get_minimum(), right?
                                         record.set_ttl(soa.expire() as u32); // the spec seems a little inaccurate with u32 and i32
                                         if ttl.is_none() {
-                                            ttl = Some(soa.minimum());
+                                            *ttl = Some(soa.minimum());
                                         } // TODO: should this only set it if it's not set?
                                     } else {
                                         assert!(
@@ -294,30 +390,7 @@ impl Parser {
                             record.set_rdata(rdata);
 
                             // add to the map
-                            let key = RrKey::new(record.name(), record.rr_type());
-
-                            match rtype.unwrap() {
-                                RecordType::SOA => {
-                                    let set = record.into_record_set();
-                                    if records.insert(key, set).is_some() {
-                                        return Err(
-                                            ParseErrorKind::Message(
-                                                "SOA is already \
-                                                                            specified",
-                                            ).into(),
-                                        );
-                                    }
-                                }
-                                _ => {
-                                    // add a Vec if it's not there, then add the record to the list
-                                    let set = records.entry(key).or_insert(RecordSet::new(
-                                        record.name(),
-                                        record.rr_type(),
-                                        0,
-                                    ));
-                                    set.insert(record, 0);
-                                }
-                            }
+                            try!(Self::add_record(records, record));
 
                             State::StartLine
                         }
@@ -330,12 +403,271 @@ impl Parser {
             }
         }
 
-        //
-        // build the Authority and return.
-        let origin = try!(origin.ok_or(ParseError::from(
-            ParseErrorKind::Message("$ORIGIN was not specified"),
+        Ok(origin)
+    }
+
+    /// Adds a record to the map, there may only be one SOA
+    fn add_record(records: &mut BTreeMap<RrKey, RecordSet>, record: Record) -> ParseResult<()> {
+        let key = RrKey::new(record.name(), record.rr_type());
+
+        match record.rr_type() {
+            RecordType::SOA => {
+                let set = record.into_record_set();
+                if records.insert(key, set).is_some() {
+                    return Err(ParseErrorKind::Message("SOA is already specified").into());
+                }
+            }
+            _ => {
+                // add a Vec if it's not there, then add the record to the list
+                let set = records.entry(key).or_insert(RecordSet::new(
+                    record.name(),
+                    record.rr_type(),
+                    0,
+                ));
+                set.insert(record, 0);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses the file of an `$INCLUDE <file-name> [<domain-name>]` entry into the records
+    ///
+    /// The included file starts at the given origin, relative to the current one, or at the
+    ///  current one, and does not change the origin of the including file, RFC 1035 section 5.1.
+    fn include(
+        &self,
+        tokens: &[Token],
+        origin: Option<&Name>,
+        ttl: &mut Option<u32>,
+        class: &mut Option<DNSClass>,
+        records: &mut BTreeMap<RrKey, RecordSet>,
+        include_depth: usize,
+    ) -> ParseResult<()> {
+        if include_depth >= MAX_INCLUDE_DEPTH {
+            return Err(
+                ParseErrorKind::Msg(format!(
+                    "$INCLUDE is nested more than {} files deep",
+                    MAX_INCLUDE_DEPTH
+                )).into(),
+            );
+        }
+
+        let (file_name, include_origin) = match (tokens.len(), tokens.get(0), tokens.get(1)) {
+            (1, Some(&Token::CharData(ref file_name)), _) => (file_name, origin.cloned()),
+            (2, Some(&Token::CharData(ref file_name)), Some(&Token::CharData(ref domain_name))) => {
+                (file_name, Some(try!(Name::parse(domain_name, origin))))
+            }
+            _ => {
+                return Err(
+                    ParseErrorKind::Message("expected $INCLUDE <file-name> [<domain-name>]").into(),
+                )
+            }
+        };
+
+        let path = match self.include_dir {
+            Some(ref include_dir) => include_dir.join(file_name),
+            None => PathBuf::from(file_name),
+        };
+        let mut buf = String::new();
+        try!(File::open(&path).and_then(|mut file| file.read_to_string(&mut buf)).map_err(
+            |e| {
+                ParseError::from(ParseErrorKind::Msg(
+                    format!("could not read $INCLUDE file {}: {}", path.display(), e),
+                ))
+            },
+        ));
+
+        try!(self.parse_entries(
+            Lexer::new(&buf),
+            include_origin,
+            ttl,
+            class,
+            records,
+            include_depth + 1,
+        ));
+        Ok(())
+    }
+
+    /// Adds the records of a `$GENERATE <range> <lhs> [<ttl>] [<class>] <type> <rhs>` entry, as
+    ///  BIND does
+    ///
+    /// The range is `<start>-<stop>[/<step>]`, a record is added for each value in it, at most
+    ///  65536 of them. In the owner name, `<lhs>`, and the RDATA, `<rhs>`, each `$` is replaced by
+    ///  the value, and each `${<offset>[,<width>[,<base>]]}` by the value plus the offset, padded
+    ///  with zeros to the width, at most 255, in the base `d`, `o`, `x` or `X`. `\$` is a `$`. The
+    ///  TTL and class default to those of the zone, as for other records.
+    fn generate(
+        tokens: &[Token],
+        origin: Option<&Name>,
+        ttl: Option<u32>,
+        class: Option<DNSClass>,
+        records: &mut BTreeMap<RrKey, RecordSet>,
+    ) -> ParseResult<()> {
+        let mut strings = Vec::with_capacity(tokens.len());
+        for t in tokens {
+            match *t {
+                Token::CharData(ref s) => strings.push(s.as_str()),
+                _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
+            }
+        }
+        let mut token = strings.into_iter();
+        let mut next = |field: &str| {
+            token.next().ok_or_else(|| {
+                ParseError::from(ParseErrorKind::MissingToken(format!("$GENERATE {}", field)))
+            })
+        };
+
+        let (start, stop, step) = try!(Self::parse_range(try!(next("range"))));
+        let lhs = try!(next("lhs"));
+
+        let mut ttl = ttl;
+        let mut class = class;
+        let rtype;
+        loop {
+            let data = try!(next("type"));
+            if let Ok(value) = Self::parse_time(data) {
+                ttl = Some(value);
+            } else if let Ok(value) = DNSClass::from_str(data) {
+                class = Some(value);
+            } else {
+                rtype = try!(RecordType::from_str(data));
+                break;
+            }
+        }
+        let rhs = try!(next("rhs"));
+        if let Ok(extra) = next("end") {
+            return Err(ParseErrorKind::UnexpectedToken(Token::CharData(extra.to_string())).into());
+        }
+
+        let ttl = try!(ttl.ok_or(ParseError::from(
+            ParseErrorKind::Message("record ttl not specified"),
         )));
-        Ok((origin, records))
+        let class = try!(class.ok_or(ParseError::from(
+            ParseErrorKind::Message("record class not specified"),
+        )));
+
+        let mut value = start;
+        while value <= stop {
+            let name = try!(Name::parse(&try!(Self::substitute(lhs, value)), origin));
+            let rdata = try!(RData::parse(
+                rtype,
+                &vec![Token::CharData(try!(Self::substitute(rhs, value)))],
+                origin,
+            ));
+
+            let mut record = Record::new();
+            record
+                .set_name(name)
+                .set_rr_type(rtype)
+                .set_dns_class(class)
+                .set_ttl(ttl)
+                .set_rdata(rdata);
+            try!(Self::add_record(records, record));
+
+            value = match value.checked_add(step) {
+                Some(value) => value,
+                None => break,
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Parses the range of a `$GENERATE`, `<start>-<stop>[/<step>]`
+    fn parse_range(range: &str) -> ParseResult<(u32, u32, u32)> {
+        let invalid = || -> ParseError {
+            ParseErrorKind::Msg(format!("invalid $GENERATE range: {}", range)).into()
+        };
+
+        let (start_stop, step) = match range.find('/') {
+            Some(index) => (&range[..index], try!(range[index + 1..].parse())),
+            None => (range, 1),
+        };
+        let (start, stop) = match start_stop.find('-') {
+            Some(index) => (
+                try!(start_stop[..index].parse()),
+                try!(start_stop[index + 1..].parse()),
+            ),
+            None => return Err(invalid()),
+        };
+
+        if start > stop || step == 0 {
+            return Err(invalid());
+        }
+        if (stop - start) / step >= MAX_GENERATE_RECORDS {
+            return Err(
+                ParseErrorKind::Msg(format!(
+                    "$GENERATE range adds more than {} records: {}",
+                    MAX_GENERATE_RECORDS,
+                    range
+                )).into(),
+            );
+        }
+        Ok((start, stop, step))
+    }
+
+    /// Replaces the `$`s of a `$GENERATE` template with the value, see `generate`
+    ///
+    /// Other escapes are kept, they are decoded with the name or the RDATA.
+    fn substitute(template: &str, value: u32) -> ParseResult<String> {
+        let mut substituted = String::with_capacity(template.len());
+        let mut chars = template.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => {
+                    match chars.next() {
+                        Some('$') => substituted.push('$'),
+                        Some(escaped) => {
+                            substituted.push('\\');
+                            substituted.push(escaped);
+                        }
+                        None => substituted.push('\\'),
+                    }
+                }
+                '$' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    let modifier: String = chars.by_ref().take_while(|ch| *ch != '}').collect();
+                    substituted.push_str(&try!(Self::modify(&modifier, value)));
+                }
+                '$' => substituted.push_str(&value.to_string()),
+                ch => substituted.push(ch),
+            }
+        }
+
+        Ok(substituted)
+    }
+
+    /// Formats the value of a `$GENERATE` with a modifier, `<offset>[,<width>[,<base>]]`
+    fn modify(modifier: &str, value: u32) -> ParseResult<String> {
+        let invalid = || -> ParseError {
+            ParseErrorKind::Msg(format!("invalid $GENERATE modifier: {{{}}}", modifier)).into()
+        };
+
+        let mut parts = modifier.split(',');
+        let offset: i64 = try!(parts.next().unwrap_or("0").parse().map_err(|_| invalid()));
+        let width: usize = match parts.next() {
+            Some(width) => try!(width.parse().map_err(|_| invalid())),
+            None => 0,
+        };
+        let base = parts.next().unwrap_or("d");
+        if parts.next().is_some() || width > MAX_GENERATE_WIDTH {
+            return Err(invalid());
+        }
+
+        let value = value as i64 + offset;
+        if value < 0 {
+            return Err(invalid());
+        }
+
+        match base {
+            "d" => Ok(format!("{:01$}", value, width)),
+            "o" => Ok(format!("{:01$o}", value, width)),
+            "x" => Ok(format!("{:01$x}", value, width)),
+            "X" => Ok(format!("{:01$X}", value, width)),
+            _ => Err(invalid()),
+        }
     }
 
     /// parses the string following the rules from:
@@ -411,12 +743,53 @@ impl Parser {
     }
 }
 
-#[allow(unused)]
 enum State {
     StartLine, // start of line, @, $<WORD>, Name, Blank
     TtlClassType, // [<TTL>] [<class>] <type>,
     Ttl, // $TTL <time>
     Record,
-    Include, // $INCLUDE <filename>
+    Include, // $INCLUDE <filename> [<domain-name>]
     Origin,
+    Generate, // $GENERATE <range> <lhs> [<ttl>] [<class>] <type> <rhs>
+}
+
+/// The tokens of the entries of a file
+///
+/// The items of lists are returned as CharData, so that parentheses may be put around any items
+///  of an entry, and the last entry is ended with an EOL, even if the file does not end with one.
+struct Entries<'a> {
+    lexer: Lexer<'a>,
+    pending: VecDeque<Token>,
+    is_done: bool,
+}
+
+impl<'a> Entries<'a> {
+    fn new(lexer: Lexer<'a>) -> Self {
+        Entries {
+            lexer: lexer,
+            pending: VecDeque::new(),
+            is_done: false,
+        }
+    }
+
+    fn next_token(&mut self) -> ParseResult<Option<Token>> {
+        if let Some(token) = self.pending.pop_front() {
+            return Ok(Some(token));
+        }
+        if self.is_done {
+            return Ok(None);
+        }
+
+        match try!(self.lexer.next_token()) {
+            Some(Token::List(list)) => {
+                self.pending.extend(list.into_iter().map(Token::CharData));
+                self.next_token()
+            }
+            Some(token) => Ok(Some(token)),
+            None => {
+                self.is_done = true;
+                Ok(Some(Token::EOL))
+            }
+        }
+    }
 }
@@ -7,10 +7,12 @@
 
 use std::iter::Peekable;
 use std::str::Chars;
-use std::char;
 
 use error::*;
 
+/// The most steps taken to lex a single token, long enough for the RDATA of a record in a list
+const MAX_TOKEN_STEPS: usize = 1 << 20;
+
 /// A Lexer for Zone files
 pub struct Lexer<'a> {
     txt: Peekable<Chars<'a>>,
@@ -31,9 +33,10 @@ impl<'a> Lexer<'a> {
         let mut char_data_vec: Option<Vec<String>> = None;
         let mut char_data: Option<String> = None;
 
-        'out: for i in 0..4096 {
+        'out: for i in 0..MAX_TOKEN_STEPS {
             // max chars in a single lex, helps with issues in the lexer...
-            assert!(i < 4095); // keeps the bounds of the loop defined (nothing lasts forever)
+            // keeps the bounds of the loop defined (nothing lasts forever)
+            assert!(i < MAX_TOKEN_STEPS - 1);
 
             // This is to get around mutibility rules such that we can peek at the iter without moving next...
             let ch: Option<char> = self.peek();
@@ -80,7 +83,7 @@ impl<'a> Lexer<'a> {
                         Some('"') => {
                             self.txt.next();
                             char_data = Some(String::new());
-                            self.state = State::Quote;
+                            self.state = State::Quote { is_list: false };
                         }
                         Some(';') => self.state = State::Comment { is_list: false },
                         Some(ch) if ch.is_whitespace() => {
@@ -119,16 +122,23 @@ impl<'a> Lexer<'a> {
                         }
                     }
                 }
-                State::Quote => {
+                State::Quote { is_list } => {
                     match ch {
                         // end and gobble the '"'
                         Some('"') => {
-                            self.state = State::RestOfLine;
                             self.txt.next();
-                            return Ok(Some(Token::CharData(char_data.take().unwrap_or("".into()))));
+                            if is_list {
+                                try!(Self::push_to_list(&mut char_data_vec, &mut char_data));
+                                self.state = State::List;
+                            } else {
+                                self.state = State::RestOfLine;
+                                return Ok(Some(
+                                    Token::CharData(char_data.take().unwrap_or("".into())),
+                                ));
+                            }
                         }
                         Some('\\') => {
-                            try!(Self::push_to_str(&mut char_data, try!(self.escape_seq())));
+                            try!(self.escape_seq(&mut char_data));
                         }
                         Some(ch) => {
                             self.txt.next();
@@ -154,18 +164,21 @@ impl<'a> Lexer<'a> {
                                 ),
                             )));
 
-                            if "INCLUDE" == dollar {
+                            if dollar.is_empty() {
+                                // not a control entry, but data starting with a '$', e.g. the
+                                //  templates of $GENERATE
+                                char_data = Some("$".to_string());
+                                self.state = State::CharData { is_list: false };
+                            } else if "GENERATE" == dollar {
+                                return Ok(Some(Token::Generate));
+                            } else if "INCLUDE" == dollar {
                                 return Ok(Some(Token::Include));
                             } else if "ORIGIN" == dollar {
                                 return Ok(Some(Token::Origin));
                             } else if "TTL" == dollar {
                                 return Ok(Some(Token::Ttl));
                             } else {
-                                return Err(
-                                    LexerErrorKind::UnrecognizedDollar(
-                                        char_data.take().unwrap_or("".into()),
-                                    ).into(),
-                                );
+                                return Err(LexerErrorKind::UnrecognizedDollar(dollar).into());
                             }
                         }
                     }
@@ -184,6 +197,11 @@ impl<'a> Lexer<'a> {
                                 .ok_or(LexerErrorKind::IllegalState("char_data_vec is None").into())
                                 .map(|v| Some(Token::List(v)));
                         }
+                        Some('"') => {
+                            self.txt.next();
+                            char_data = Some(String::new());
+                            self.state = State::Quote { is_list: true };
+                        }
                         Some(ch) if ch.is_whitespace() => {
                             self.txt.next();
                         }
@@ -202,20 +220,7 @@ impl<'a> Lexer<'a> {
                         }
                         Some(ch) if ch.is_whitespace() || ch == ')' || ch == ';' => {
                             if is_list {
-                                try!(
-                                    char_data_vec
-                                        .as_mut()
-                                        .ok_or(LexerError::from(
-                                            LexerErrorKind::IllegalState("char_data_vec is None"),
-                                        ))
-                                        .and_then(|v| {
-                                            Ok(v.push(try!(
-                                                char_data.take().ok_or(LexerErrorKind::IllegalState(
-                                                    "char_data is None",
-                                                ))
-                                            )))
-                                        })
-                                );
+                                try!(Self::push_to_list(&mut char_data_vec, &mut char_data));
                                 self.state = State::List;
                             } else {
                                 self.state = State::RestOfLine;
@@ -228,8 +233,10 @@ impl<'a> Lexer<'a> {
                                 return opt;
                             }
                         }
-                        // TODO: this next one can be removed, but will keep unescaping for quoted strings
-                        //Some('\\') => { try!(Self::push_to_str(&mut char_data, try!(self.escape_seq()))); },
+                        // escaped whitespace, parentheses or semicolons are part of the data
+                        Some('\\') => {
+                            try!(self.escape_seq(&mut char_data));
+                        }
                         Some(ch) if !ch.is_control() && !ch.is_whitespace() => {
                             self.txt.next();
                             try!(Self::push_to_str(&mut char_data, ch));
@@ -282,60 +289,51 @@ impl<'a> Lexer<'a> {
             .and_then(|s| Ok(s.push(ch)))
     }
 
-    fn escape_seq(&mut self) -> LexerResult<char> {
-        // escaped character, let's decode it.
+    fn push_to_list(
+        collect: &mut Option<Vec<String>>,
+        char_data: &mut Option<String>,
+    ) -> LexerResult<()> {
+        let data = try!(char_data.take().ok_or(LexerError::from(
+            LexerErrorKind::IllegalState("char_data is None"),
+        )));
+        collect
+            .as_mut()
+            .ok_or(LexerErrorKind::IllegalState("char_data_vec is None").into())
+            .map(|v| v.push(data))
+    }
+
+    /// Appends the escape sequence at the current position, `\X` or `\DDD`, as it is written, so
+    ///  that an escaped `.` in a name can be told from a separator, the names and character
+    ///  strings which the data is parsed into decode it
+    fn escape_seq(&mut self, char_data: &mut Option<String>) -> LexerResult<()> {
         self.txt.next(); // consume the escape
+        try!(Self::push_to_str(char_data, '\\'));
         let ch = try!(self.peek().ok_or(LexerError::from(LexerErrorKind::EOF)));
 
-        if !ch.is_control() {
-            if ch.is_numeric() {
-                // in this case it's an excaped octal: \DDD
-                let d1: u32 = try!(try!(
-                    self.txt
-                        .next()
-                        .ok_or(LexerError::from(LexerErrorKind::EOF))
-                        .map(|c| {
-                            c.to_digit(10).ok_or(LexerError::from(
-                                LexerErrorKind::IllegalCharacter(c),
-                            ))
-                        })
-                )); // gobble
-                let d2: u32 = try!(try!(
-                    self.txt
-                        .next()
-                        .ok_or(LexerError::from(LexerErrorKind::EOF))
-                        .map(|c| {
-                            c.to_digit(10).ok_or(LexerError::from(
-                                LexerErrorKind::IllegalCharacter(c),
-                            ))
-                        })
-                )); // gobble
-                let d3: u32 = try!(try!(
-                    self.txt
-                        .next()
-                        .ok_or(LexerError::from(LexerErrorKind::EOF))
-                        .map(|c| {
-                            c.to_digit(10).ok_or(LexerError::from(
-                                LexerErrorKind::IllegalCharacter(c),
-                            ))
-                        })
-                )); // gobble
-
-                let val: u32 = (d1 << 16) + (d2 << 8) + d3;
-                let ch: char = try!(char::from_u32(val).ok_or(LexerError::from(
-                    LexerErrorKind::UnrecognizedOctet(val),
-                )));
-
-                return Ok(ch);
-            } else {
-                // this is an excaped char: \X
-                self.txt.next(); // gobble the char
-                return Ok(ch);
-            }
-        } else {
+        if ch.is_control() {
             return Err(LexerErrorKind::IllegalCharacter(ch).into());
         }
+        if !ch.is_digit(10) {
+            // this is an escaped char: \X
+            self.txt.next(); // gobble the char
+            return Self::push_to_str(char_data, ch);
+        }
+
+        // in this case it's the decimal value of an octet: \DDD
+        let mut octet: u32 = 0;
+        for _ in 0..3 {
+            let digit = try!(self.txt.next().ok_or(LexerError::from(LexerErrorKind::EOF)));
+            octet = octet * 10 +
+                try!(digit.to_digit(10).ok_or(LexerError::from(
+                    LexerErrorKind::IllegalCharacter(digit),
+                )));
+            try!(Self::push_to_str(char_data, digit));
+        }
 
+        if octet > u8::max_value() as u32 {
+            return Err(LexerErrorKind::UnrecognizedOctet(octet).into());
+        }
+        Ok(())
     }
 
     fn peek(&mut self) -> Option<char> {
@@ -354,7 +352,7 @@ pub enum State {
     //  Name,              // CharData + '.' + CharData
     Comment { is_list: bool }, // ;.*
     At, // @
-    Quote, // ".*"
+    Quote { is_list: bool }, // ".*"
     Dollar, // $
     EOL, // \n or \r\n
     EOF,
@@ -368,9 +366,13 @@ pub enum Token {
     /// (..) TODO, this is probably wrong, List maybe should just skip line endings
     List(Vec<String>),
     /// [a-zA-Z, non-control utf8, ., -, 0-9]+, ".*"
+    ///
+    /// Escapes, `\X` and `\DDD`, are kept as they are written, also within quotes
     CharData(String),
     /// @
     At,
+    /// $GENERATE
+    Generate,
     /// $INCLUDE
     Include,
     /// $ORIGIN
@@ -483,24 +485,29 @@ mod lex_test {
         );
         assert_eq!(
             Lexer::new("\"some \\A\"").next_token().unwrap().unwrap(),
-            Token::CharData("some A".to_string())
+            Token::CharData("some \\A".to_string())
         );
         assert_eq!(
             Lexer::new("\"a\\Aa\"").next_token().unwrap().unwrap(),
-            Token::CharData("aAa".to_string())
+            Token::CharData("a\\Aa".to_string())
         );
         assert_eq!(
             Lexer::new("\"a\\$\"").next_token().unwrap().unwrap(),
-            Token::CharData("a$".to_string())
+            Token::CharData("a\\$".to_string())
         );
         assert_eq!(
             Lexer::new("\"a\\077\"").next_token().unwrap().unwrap(),
-            Token::CharData("a\u{707}".to_string())
+            Token::CharData("a\\077".to_string())
+        );
+        assert_eq!(
+            Lexer::new("\"a\\\"b\"").next_token().unwrap().unwrap(),
+            Token::CharData("a\\\"b".to_string())
         );
 
         assert!(Lexer::new("\"a\\\"").next_token().is_err());
         assert!(Lexer::new("\"a\\0\"").next_token().is_err());
         assert!(Lexer::new("\"a\\07\"").next_token().is_err());
+        assert!(Lexer::new("\"a\\256\"").next_token().is_err());
 
         let mut lexer = Lexer::new("\"multi\nline\ntext\"");
 
@@ -522,6 +529,27 @@ mod lex_test {
         assert!(Lexer::new("\"multi").next_token().is_err());
     }
 
+    #[test]
+    fn escaped_delimiters() {
+        let mut lexer = Lexer::new("a\\ b\\;c\\(\\) d\\255 e");
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("a\\ b\\;c\\(\\)".to_string())
+        );
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("d\\255".to_string())
+        );
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("e".to_string())
+        );
+        assert_eq!(next_token(&mut lexer), None);
+
+        assert!(Lexer::new("a\\256").next_token().is_err());
+        assert!(Lexer::new("a\\2").next_token().is_err());
+    }
+
     #[test]
     fn unicode() {
         assert_eq!(
@@ -565,6 +593,11 @@ mod lex_test {
             Token::Origin
         );
         assert_eq!(next_token(&mut Lexer::new("$TTL")).unwrap(), Token::Ttl);
+        assert_eq!(
+            next_token(&mut Lexer::new("$GENERATE")).unwrap(),
+            Token::Generate
+        );
+        assert!(Lexer::new("$UNKNOWN").next_token().is_err());
         assert_eq!(next_token(&mut Lexer::new("\n")), Some(Token::EOL));
         assert_eq!(next_token(&mut Lexer::new("\r\n")), Some(Token::EOL));
     }
@@ -607,6 +640,55 @@ mod lex_test {
             Token::List(vec!["abc".to_string()])
         );
         assert_eq!(next_token(&mut lexer), None);
+
+        let mut lexer = Lexer::new("( \"quoted ) ;\"\n\"\" abc\"def\" )");
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::List(vec![
+                "quoted ) ;".to_string(),
+                "".to_string(),
+                "abc\"def\"".to_string(),
+            ])
+        );
+        assert_eq!(next_token(&mut lexer), None);
+    }
+
+    #[test]
+    fn generate() {
+        let mut lexer = Lexer::new("$GENERATE 1-10/2 host-$ A 10.0.${1,3,d}.$\n");
+        assert_eq!(next_token(&mut lexer).unwrap(), Token::Generate);
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("1-10/2".to_string())
+        );
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("host-$".to_string())
+        );
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("A".to_string())
+        );
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("10.0.${1,3,d}.$".to_string())
+        );
+        assert_eq!(next_token(&mut lexer).unwrap(), Token::EOL);
+
+        let mut lexer = Lexer::new("$ PTR ${0,2,x}");
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("$".to_string())
+        );
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("PTR".to_string())
+        );
+        assert_eq!(
+            next_token(&mut lexer).unwrap(),
+            Token::CharData("${0,2,x}".to_string())
+        );
+        assert_eq!(next_token(&mut lexer), None);
     }
 
     #[test]
@@ -0,0 +1,381 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Writer of the master file text form, the inverse of the `Parser`
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use error::*;
+use rr::{Name, RData, Record, RecordType};
+use serialize::binary::{BinEncoder, NameCompression};
+
+/// Writes the records as a master file, RFC 1035 section 5, which the `Parser` reads back
+///
+/// The file starts with `$ORIGIN`, and with `$TTL` set to the most common TTL as the default for
+///  records added to it. Each record is written with its TTL, as the `Parser` takes the TTL of a
+///  record as the default for the records after it. The SOA of the origin comes first, then the
+///  records in the canonical order of their names, RFC 4034 section 6.1, and types, each RRSIG
+///  after the records it covers, so the same records are always written the same way. Owner
+///  names within the origin are relative to it, the names in the RDATA are fully qualified, and
+///  the labels and character strings are escaped where needed. The RDATA of the types without a
+///  text form in the `Parser` is written in the generic form, RFC 3597 section 5.
+///
+/// # Arguments
+///
+/// * `origin` - the origin of the zone, owner names within it are written relative to it
+/// * `records` - the records to write, in any order
+pub fn write_zone(origin: &Name, records: &[Record]) -> Result<String, EncodeError> {
+    let mut records = records.iter().collect::<Vec<_>>();
+    records.sort_by(|a, b| zone_cmp(origin, a, b));
+
+    let mut zone = String::new();
+    writeln!(zone, "$ORIGIN {}", escape_name(origin)).expect("write to String failed");
+
+    if let Some(ttl) = most_common_ttl(&records) {
+        writeln!(zone, "$TTL {}", ttl).expect("write to String failed");
+    }
+
+    for record in records {
+        let owner = if record.name() == origin {
+            "@".to_string()
+        } else if origin.zone_of(record.name()) {
+            let relative = record.name().iter().len() - origin.iter().len();
+            escape_labels(record.name().iter().take(relative))
+        } else {
+            escape_name(record.name())
+        };
+
+        write!(
+            zone,
+            "{} {} {} {} ",
+            owner,
+            record.ttl(),
+            record.dns_class(),
+            record.rr_type()
+        ).expect("write to String failed");
+        try!(write_rdata(&mut zone, record.rdata()));
+        zone.push('\n');
+    }
+
+    Ok(zone)
+}
+
+/// The order of the records in the file, the SOA of the origin first, then by name and type, with
+///  each RRSIG right after the type it covers
+fn zone_cmp(origin: &Name, a: &Record, b: &Record) -> Ordering {
+    let is_origin_soa = |record: &Record| {
+        record.rr_type() == RecordType::SOA && record.name() == origin
+    };
+    let covered_type = |record: &Record| match *record.rdata() {
+        RData::SIG(ref sig) => (sig.type_covered(), true),
+        _ => (record.rr_type(), false),
+    };
+
+    is_origin_soa(b)
+        .cmp(&is_origin_soa(a))
+        .then_with(|| a.name().cmp(b.name()))
+        .then_with(|| covered_type(a).cmp(&covered_type(b)))
+        .then_with(|| a.canonical_cmp(b))
+}
+
+/// Returns the TTL of the most records, the lowest of those with as many, None without records
+fn most_common_ttl(records: &[&Record]) -> Option<u32> {
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for record in records {
+        *counts.entry(record.ttl()).or_insert(0) += 1;
+    }
+
+    counts
+        .into_iter()
+        .max_by(|&(ttl_a, count_a), &(ttl_b, count_b)| {
+            count_a.cmp(&count_b).then(ttl_b.cmp(&ttl_a))
+        })
+        .map(|(ttl, _)| ttl)
+}
+
+/// Writes the RDATA in the text form which the `Parser` reads for its type
+fn write_rdata(zone: &mut String, rdata: &RData) -> Result<(), EncodeError> {
+    let written = match *rdata {
+        RData::A(ref address) => write!(zone, "{}", address),
+        RData::AAAA(ref address) => write!(zone, "{}", address),
+        RData::CNAME(ref name) |
+        RData::NS(ref name) |
+        RData::PTR(ref name) => write!(zone, "{}", escape_name(name)),
+        RData::HINFO(ref hinfo) => {
+            write!(
+                zone,
+                "{} {}",
+                escape_string(hinfo.cpu()),
+                escape_string(hinfo.os())
+            )
+        }
+        RData::MX(ref mx) => {
+            write!(zone, "{} {}", mx.preference(), escape_name(mx.exchange()))
+        }
+        RData::NAPTR(ref naptr) => {
+            write!(
+                zone,
+                "{} {} {} {} {} {}",
+                naptr.order(),
+                naptr.preference(),
+                escape_string(naptr.flags()),
+                escape_string(naptr.services()),
+                escape_string(naptr.regexp()),
+                escape_name(naptr.replacement())
+            )
+        }
+        RData::SOA(ref soa) => {
+            write!(
+                zone,
+                "{} {} {} {} {} {} {}",
+                escape_name(soa.mname()),
+                escape_name(soa.rname()),
+                soa.serial(),
+                soa.refresh(),
+                soa.retry(),
+                soa.expire(),
+                soa.minimum()
+            )
+        }
+        RData::SRV(ref srv) => {
+            write!(
+                zone,
+                "{} {} {} {}",
+                srv.priority(),
+                srv.weight(),
+                srv.port(),
+                escape_name(srv.target())
+            )
+        }
+        RData::TXT(ref txt) => {
+            let strings = txt.txt_data()
+                .iter()
+                .map(|string| escape_string(string))
+                .collect::<Vec<_>>();
+            write!(zone, "{}", strings.join(" "))
+        }
+        RData::URI(ref uri) => {
+            write!(
+                zone,
+                "{} {} {}",
+                uri.priority(),
+                uri.weight(),
+                escape_string(uri.target())
+            )
+        }
+        RData::Unknown { rdata: ref bytes, .. } => write_generic(zone, bytes),
+        _ => {
+            let mut bytes = Vec::new();
+            {
+                let mut encoder = BinEncoder::new(&mut bytes);
+                encoder.set_name_compression(NameCompression::Disabled);
+                try!(rdata.emit(&mut encoder));
+            }
+            write_generic(zone, &bytes)
+        }
+    };
+
+    written.expect("write to String failed");
+    Ok(())
+}
+
+/// Writes the RDATA in the generic form, RFC 3597 section 5, `\# <length> <hex>`
+fn write_generic(zone: &mut String, bytes: &[u8]) -> ::std::fmt::Result {
+    try!(write!(zone, "\\# {}", bytes.len()));
+    if !bytes.is_empty() {
+        zone.push(' ');
+        for byte in bytes {
+            try!(write!(zone, "{:02X}", byte));
+        }
+    }
+    Ok(())
+}
+
+/// The fully qualified name with its labels escaped
+fn escape_name(name: &Name) -> String {
+    if name.is_root() {
+        return ".".to_string();
+    }
+
+    let mut escaped = escape_labels(name.iter());
+    if name.is_fqdn() {
+        escaped.push('.');
+    }
+    escaped
+}
+
+/// The labels, escaped and joined by dots, without a final dot
+fn escape_labels<'a, I: Iterator<Item = &'a str>>(labels: I) -> String {
+    labels
+        .map(|label| {
+            let mut escaped = String::with_capacity(label.len());
+            for ch in label.chars() {
+                match ch {
+                    '.' | '\\' | '"' | ';' | '(' | ')' | '@' | '$' => {
+                        escaped.push('\\');
+                        escaped.push(ch);
+                    }
+                    ch if ch.is_control() || ch.is_whitespace() => escape_octet(&mut escaped, ch),
+                    ch => escaped.push(ch),
+                }
+            }
+            escaped
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// The character string in quotes, with the quotes, backslashes and control characters escaped
+fn escape_string(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len() + 2);
+    escaped.push('"');
+    for ch in string.chars() {
+        match ch {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            ch if ch.is_control() => escape_octet(&mut escaped, ch),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Writes the character as `\DDD`, the decimal value of each of its octets
+fn escape_octet(escaped: &mut String, ch: char) {
+    let mut buf = [0; 4];
+    for octet in ch.encode_utf8(&mut buf).bytes() {
+        write!(escaped, "\\{:03}", octet).expect("write to String failed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use rr::rdata::{MX, SOA, TXT};
+    use serialize::txt::{Lexer, Parser};
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        let name = Name::from_labels(vec!["a.b", "semi;colon", "example", "com"]);
+        assert_eq!(escape_name(&name), "a\\.b.semi\\;colon.example.com.");
+        assert_eq!(escape_name(&Name::root()), ".");
+
+        assert_eq!(
+            escape_string("with \"quotes\" and \\ tab\t"),
+            "\"with \\\"quotes\\\" and \\\\ tab\\009\""
+        );
+    }
+
+    #[test]
+    fn test_write_zone() {
+        let origin = Name::from_str("example.com.").unwrap();
+        let www = Name::from_str("www.example.com.").unwrap();
+        let soa = Record::from_rdata(
+            origin.clone(),
+            3600,
+            RecordType::SOA,
+            RData::SOA(SOA::new(
+                Name::from_str("ns.example.com.").unwrap(),
+                Name::from_str("hostmaster.example.com.").unwrap(),
+                2017101601,
+                7200,
+                600,
+                3600000,
+                60,
+            )),
+        );
+        let records = vec![
+            Record::from_rdata(
+                www.clone(),
+                60,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(10, 0, 0, 2)),
+            ),
+            Record::from_rdata(
+                www.clone(),
+                60,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(10, 0, 0, 1)),
+            ),
+            Record::from_rdata(
+                www.clone(),
+                60,
+                RecordType::TXT,
+                RData::TXT(TXT::new(vec!["v=1; a \"b\"".to_string()])),
+            ),
+            Record::from_rdata(
+                origin.clone(),
+                300,
+                RecordType::MX,
+                RData::MX(MX::new(10, Name::from_str("mail.example.net.").unwrap())),
+            ),
+            Record::from_rdata(
+                origin.clone(),
+                60,
+                RecordType::Unknown(65534),
+                RData::Unknown {
+                    code: 65534,
+                    rdata: vec![0xbe, 0xef],
+                },
+            ),
+            soa.clone(),
+        ];
+
+        let zone = write_zone(&origin, &records).unwrap();
+        assert_eq!(
+            zone,
+            "$ORIGIN example.com.
+$TTL 60
+@ 3600 IN SOA ns.example.com. hostmaster.example.com. 2017101601 7200 600 3600000 60
+@ 300 IN MX 10 mail.example.net.
+@ 60 IN TYPE65534 \\# 2 BEEF
+www 60 IN A 10.0.0.1
+www 60 IN A 10.0.0.2
+www 60 IN TXT \"v=1; a \\\"b\\\"\"
+"
+        );
+
+        // the same records in any order are written the same
+        let mut reversed = records.clone();
+        reversed.reverse();
+        assert_eq!(write_zone(&origin, &reversed).unwrap(), zone);
+
+        // and read back as they were
+        let (parsed_origin, rrsets) = Parser::new().parse(Lexer::new(&zone), None).unwrap();
+        assert_eq!(parsed_origin, origin);
+        let mut parsed = rrsets
+            .values()
+            .flat_map(|rrset| rrset.iter().cloned())
+            .filter(|record| record.rr_type() != RecordType::SOA)
+            .collect::<Vec<_>>();
+        let mut expected = records
+            .into_iter()
+            .filter(|record| record.rr_type() != RecordType::SOA)
+            .collect::<Vec<_>>();
+        parsed.sort_by(|a, b| a.canonical_cmp(b));
+        expected.sort_by(|a, b| a.canonical_cmp(b));
+        assert_eq!(parsed, expected);
+        assert!(parsed.iter().zip(&expected).all(|(a, b)| a.ttl() == b.ttl()));
+    }
+}
@@ -18,9 +18,11 @@
 
 mod master_lex;
 mod master;
+mod master_writer;
 mod parse_rdata;
 mod rdata_parsers;
 
 pub use self::master::Parser;
+pub use self::master_writer::write_zone;
 pub use self::master_lex::Lexer;
 pub use self::master_lex::Token;
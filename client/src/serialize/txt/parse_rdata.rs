@@ -36,29 +36,83 @@ impl RDataParser for RData {
         tokens: &Vec<Token>,
         origin: Option<&Name>,
     ) -> ParseResult<Self> {
+        // any type may be in the generic form, RFC 3597 section 5
+        if unknown::is_generic(tokens) {
+            return unknown::parse(record_type, tokens);
+        }
+
         let rdata = match record_type {
             RecordType::A => RData::A(a::parse(tokens)?),
             RecordType::AAAA => RData::AAAA(aaaa::parse(tokens)?),
-            RecordType::ANY => panic!("parsing ANY doesn't make sense"), // valid panic, never should happen
-            RecordType::AXFR => panic!("parsing AXFR doesn't make sense"), // valid panic, never should happen
+            RecordType::ANY => {
+                return Err(ParseErrorKind::Message("parsing ANY doesn't make sense").into())
+            }
+            RecordType::AXFR => {
+                return Err(ParseErrorKind::Message("parsing AXFR doesn't make sense").into())
+            }
+            RecordType::CDNSKEY => RData::CDNSKEY(cdnskey::parse(tokens)?),
+            RecordType::CDS => RData::CDS(cds::parse(tokens)?),
             RecordType::CNAME => RData::CNAME(name::parse(tokens, origin)?),
-            RecordType::KEY => panic!("KEY should be dynamically generated"), // valid panic, never should happen
-            RecordType::DNSKEY => panic!("DNSKEY should be dynamically generated"), // valid panic, never should happen
-            RecordType::DS => panic!("DS should be dynamically generated"), // valid panic, never should happen
-            RecordType::IXFR => panic!("parsing IXFR doesn't make sense"), // valid panic, never should happen
+            RecordType::CSYNC => RData::CSYNC(csync::parse(tokens)?),
+            RecordType::KEY => {
+                return Err(ParseErrorKind::Message("KEY should be dynamically generated").into())
+            }
+            RecordType::DNSKEY => {
+                return Err(ParseErrorKind::Message("DNSKEY should be dynamically generated").into())
+            }
+            RecordType::DS => {
+                return Err(ParseErrorKind::Message("DS should be dynamically generated").into())
+            }
+            RecordType::HINFO => RData::HINFO(hinfo::parse(tokens)?),
+            RecordType::HTTPS => RData::HTTPS(svcb::parse(tokens, origin)?),
+            RecordType::IXFR => {
+                return Err(ParseErrorKind::Message("parsing IXFR doesn't make sense").into())
+            }
             RecordType::MX => RData::MX(mx::parse(tokens, origin)?),
+            RecordType::NAPTR => RData::NAPTR(naptr::parse(tokens, origin)?),
             RecordType::NULL => RData::NULL(null::parse(tokens)?),
             RecordType::NS => RData::NS(name::parse(tokens, origin)?),
-            RecordType::NSEC => panic!("NSEC should be dynamically generated"), // valid panic, never should happen
-            RecordType::NSEC3 => panic!("NSEC3 should be dynamically generated"), // valid panic, never should happen
-            RecordType::NSEC3PARAM => panic!("NSEC3PARAM should be dynamically generated"), // valid panic, never should happen
-            RecordType::OPT => panic!("parsing OPT doesn't make sense"), // valid panic, never should happen
+            RecordType::NSEC => {
+                return Err(ParseErrorKind::Message("NSEC should be dynamically generated").into())
+            }
+            RecordType::NSEC3 => {
+                return Err(ParseErrorKind::Message("NSEC3 should be dynamically generated").into())
+            }
+            RecordType::NSEC3PARAM => {
+                return Err(
+                    ParseErrorKind::Message("NSEC3PARAM should be dynamically generated").into(),
+                )
+            }
+            RecordType::OPENPGPKEY => RData::OPENPGPKEY(openpgpkey::parse(tokens)?),
+            RecordType::OPT => {
+                return Err(ParseErrorKind::Message("parsing OPT doesn't make sense").into())
+            }
             RecordType::PTR => RData::PTR(name::parse(tokens, origin)?),
-            RecordType::RRSIG => panic!("RRSIG should be dynamically generated"), // valid panic, never should happen
-            RecordType::SIG => panic!("parsing SIG doesn't make sense"), // valid panic, never should happen
+            RecordType::RRSIG => {
+                return Err(ParseErrorKind::Message("RRSIG should be dynamically generated").into())
+            }
+            RecordType::SIG => {
+                return Err(ParseErrorKind::Message("parsing SIG doesn't make sense").into())
+            }
+            RecordType::SMIMEA => RData::SMIMEA(tlsa::parse(tokens)?),
             RecordType::SOA => RData::SOA(soa::parse(tokens, origin)?),
             RecordType::SRV => RData::SRV(srv::parse(tokens, origin)?),
+            RecordType::SSHFP => RData::SSHFP(sshfp::parse(tokens)?),
+            RecordType::SVCB => RData::SVCB(svcb::parse(tokens, origin)?),
+            RecordType::TLSA => RData::TLSA(tlsa::parse(tokens)?),
+            RecordType::TSIG => {
+                return Err(ParseErrorKind::Message("parsing TSIG doesn't make sense").into())
+            }
             RecordType::TXT => RData::TXT(txt::parse(tokens)?),
+            RecordType::URI => RData::URI(uri::parse(tokens)?),
+            RecordType::Unknown(..) => {
+                return Err(
+                    ParseErrorKind::Message("unknown types must be in the \\# generic form").into(),
+                )
+            }
+            RecordType::ZONEMD => {
+                return Err(ParseErrorKind::Message("ZONEMD should be dynamically generated").into())
+            }
         };
 
         Ok(rdata)
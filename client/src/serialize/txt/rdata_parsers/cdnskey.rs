@@ -0,0 +1,92 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for CDNSKEY text form
+
+use std::str::FromStr;
+
+use data_encoding::base64;
+
+use serialize::txt::*;
+use error::*;
+use rr::dnssec::Algorithm;
+use rr::rdata::{CDNSKEY, DNSKEY};
+
+use super::to_strings;
+
+/// Parse the RData from a set of Tokens
+///
+/// The public key may be split by whitespace, `0 3 0 AA==` is the request to delete the DS
+///  RRset.
+pub fn parse(tokens: &Vec<Token>) -> ParseResult<CDNSKEY> {
+    let strings = try!(to_strings(tokens));
+    let mut token = strings.iter();
+
+    let flags: u16 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("flags".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+    let protocol: u8 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("protocol".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+    let algorithm: &str = try!(token.next().ok_or(ParseError::from(
+        ParseErrorKind::MissingToken("algorithm".to_string()),
+    )));
+
+    let public_key: String = token.map(|s| *s).collect();
+    if public_key.is_empty() {
+        return Err(ParseErrorKind::MissingToken("public key".to_string()).into());
+    }
+    let public_key = try!(base64::decode(public_key.as_bytes()).map_err(|e| {
+        ParseError::from(ParseErrorKind::Msg(format!("invalid public key: {:?}", e)))
+    }));
+
+    // protocol is defined to only be '3' right now
+    if protocol != 3 {
+        return Err(ParseErrorKind::Msg(format!("protocol must be 3: {}", protocol)).into());
+    }
+
+    if flags == 0 && algorithm == "0" && public_key == [0] {
+        return Ok(CDNSKEY::Delete);
+    }
+
+    let algorithm = match algorithm.parse::<u8>() {
+        Ok(algorithm) => try!(Algorithm::from_u8(algorithm)),
+        Err(_) => try!(Algorithm::from_str(algorithm)),
+    };
+
+    // as with the wire format, the reserved flags are ignored
+    let zone_key = flags & 0b0000_0001_0000_0000 == 0b0000_0001_0000_0000;
+    let secure_entry_point = flags & 0b0000_0000_0000_0001 == 0b0000_0000_0000_0001;
+    let revoke = flags & 0b0000_0000_1000_0000 == 0b0000_0000_1000_0000;
+
+    Ok(CDNSKEY::DNSKEY(DNSKEY::new(
+        zone_key,
+        secure_entry_point,
+        revoke,
+        algorithm,
+        public_key,
+    )))
+}
@@ -0,0 +1,76 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for CDS text form
+
+use std::str::FromStr;
+
+use data_encoding::hex;
+
+use serialize::txt::*;
+use error::*;
+use rr::dnssec::{Algorithm, DigestType};
+use rr::rdata::{CDS, DS};
+
+use super::to_strings;
+
+/// Parse the RData from a set of Tokens
+///
+/// The digest may be split by whitespace, `0 0 0 00` is the request to delete the DS RRset.
+pub fn parse(tokens: &Vec<Token>) -> ParseResult<CDS> {
+    let strings = try!(to_strings(tokens));
+    let mut token = strings.iter();
+
+    let key_tag: u16 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("key tag".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+    let algorithm: &str = try!(token.next().ok_or(ParseError::from(
+        ParseErrorKind::MissingToken("algorithm".to_string()),
+    )));
+    let digest_type: u8 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("digest type".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+
+    let digest: String = token.map(|s| s.to_uppercase()).collect();
+    if digest.is_empty() {
+        return Err(ParseErrorKind::MissingToken("digest".to_string()).into());
+    }
+    let digest = try!(hex::decode(digest.as_bytes()).map_err(|e| {
+        ParseError::from(ParseErrorKind::Msg(format!("invalid digest: {:?}", e)))
+    }));
+
+    if key_tag == 0 && algorithm == "0" && digest_type == 0 && digest == [0] {
+        return Ok(CDS::Delete);
+    }
+
+    let algorithm = match algorithm.parse::<u8>() {
+        Ok(algorithm) => try!(Algorithm::from_u8(algorithm)),
+        Err(_) => try!(Algorithm::from_str(algorithm)),
+    };
+    let digest_type = try!(DigestType::from_u8(digest_type));
+
+    Ok(CDS::DS(DS::new(key_tag, algorithm, digest_type, digest)))
+}
@@ -0,0 +1,66 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for CSYNC text form
+
+use serialize::txt::*;
+use error::*;
+use rr::RecordType;
+use rr::rdata::CSYNC;
+
+use super::to_strings;
+
+/// The immediate flag
+const IMMEDIATE: u16 = 0b0000_0000_0000_0001;
+/// The soaminimum flag
+const SOA_MINIMUM: u16 = 0b0000_0000_0000_0010;
+
+/// Parse the RData from a set of Tokens
+///
+/// e.g. `66 3 A NS AAAA`, the serial, the flags and the types to be copied to the parent
+pub fn parse(tokens: &Vec<Token>) -> ParseResult<CSYNC> {
+    let strings = try!(to_strings(tokens));
+    let mut token = strings.iter();
+
+    let soa_serial: u32 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("soa serial".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+    let flags: u16 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("flags".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+
+    let mut type_bit_maps = Vec::new();
+    for s in token {
+        type_bit_maps.push(try!(RecordType::from_str(s)));
+    }
+
+    Ok(CSYNC::new(
+        soa_serial,
+        flags & IMMEDIATE == IMMEDIATE,
+        flags & SOA_MINIMUM == SOA_MINIMUM,
+        type_bit_maps,
+    ))
+}
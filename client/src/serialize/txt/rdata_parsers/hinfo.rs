@@ -0,0 +1,43 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for HINFO text form
+
+use serialize::txt::*;
+use error::*;
+use rr::rdata::HINFO;
+
+use super::unescape;
+
+/// Parse the RData from a set of Tokens
+///
+/// e.g. `"PDP-11/70" "UNIX"`, the CPU and the OS
+pub fn parse(tokens: &Vec<Token>) -> ParseResult<HINFO> {
+    let mut token = tokens.iter();
+
+    let mut next = |field: &str| -> ParseResult<String> {
+        match token.next() {
+            Some(&Token::CharData(ref data)) => unescape(data),
+            Some(t) => Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
+            None => Err(ParseErrorKind::MissingToken(field.to_string()).into()),
+        }
+    };
+
+    let cpu = try!(next("cpu"));
+    let os = try!(next("os"));
+
+    Ok(HINFO::new(cpu, os))
+}
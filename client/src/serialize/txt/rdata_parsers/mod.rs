@@ -21,9 +21,77 @@
 // each of these module's has the parser for that rdata embedded, to keep the file sizes down...
 pub mod a;
 pub mod aaaa;
+pub mod cdnskey;
+pub mod cds;
+pub mod csync;
+pub mod hinfo;
 pub mod mx;
 pub mod name;
+pub mod naptr;
 pub mod null;
+pub mod openpgpkey;
 pub mod soa;
 pub mod srv;
+pub mod sshfp;
+pub mod svcb;
+pub mod tlsa;
 pub mod txt;
+pub mod unknown;
+pub mod uri;
+
+use serialize::txt::Token;
+use error::*;
+
+/// The strings of the tokens, with those of any list, the `( ... )` which rdata spanning lines is
+///  wrapped in, in place
+fn to_strings(tokens: &Vec<Token>) -> ParseResult<Vec<&str>> {
+    let mut strings = Vec::with_capacity(tokens.len());
+    for t in tokens {
+        match *t {
+            Token::CharData(ref s) => strings.push(s.as_str()),
+            Token::List(ref list) => strings.extend(list.iter().map(|s| s.as_str())),
+            _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
+        }
+    }
+
+    Ok(strings)
+}
+
+/// Decodes the escapes of a character string, `\X` is the character X, and `\DDD` the octet of
+///  the decimal value DDD, RFC 1035 section 5.1, the lexer keeps them as they are written
+fn unescape(char_data: &str) -> ParseResult<String> {
+    let mut unescaped = String::with_capacity(char_data.len());
+    let mut chars = char_data.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unescaped.push(ch);
+            continue;
+        }
+
+        let escaped = try!(chars.next().ok_or(ParseError::from(
+            ParseErrorKind::Message("escape at the end of a character string"),
+        )));
+        let first = match escaped.to_digit(10) {
+            Some(first) => first,
+            None => {
+                unescaped.push(escaped);
+                continue;
+            }
+        };
+
+        let mut octet = first;
+        for _ in 0..2 {
+            let digit = try!(chars.next().and_then(|ch| ch.to_digit(10)).ok_or(
+                ParseError::from(ParseErrorKind::Message("an escaped octet must have three digits")),
+            ));
+            octet = octet * 10 + digit;
+        }
+        if octet > u8::max_value() as u32 {
+            return Err(ParseErrorKind::Msg(format!("illegal escaped octet: {}", octet)).into());
+        }
+        unescaped.push(octet as u8 as char);
+    }
+
+    Ok(unescaped)
+}
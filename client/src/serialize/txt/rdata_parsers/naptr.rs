@@ -0,0 +1,64 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for NAPTR text form
+
+use std::slice::Iter;
+
+use serialize::txt::*;
+use error::*;
+use rr::domain::Name;
+use rr::rdata::NAPTR;
+use rr::rdata::naptr::Substitution;
+
+use super::{to_strings, unescape};
+
+/// Parse the RData from a set of Tokens
+///
+/// e.g. `100 10 "u" "E2U+sip" "!^.*$!sip:info@example.com!" .`, the order, preference, flags,
+///  services, regexp and replacement; the regexp is checked to be a valid substitution expression
+pub fn parse(tokens: &Vec<Token>, origin: Option<&Name>) -> ParseResult<NAPTR> {
+    let strings = try!(to_strings(tokens));
+    let mut token = strings.iter();
+
+    let order: u16 = try!(try!(next(&mut token, "order")).parse());
+    let preference: u16 = try!(try!(next(&mut token, "preference")).parse());
+    let flags = try!(unescape(try!(next(&mut token, "flags"))));
+    let services = try!(unescape(try!(next(&mut token, "services"))));
+    let regexp = try!(unescape(try!(next(&mut token, "regexp"))));
+    let replacement = try!(Name::parse(try!(next(&mut token, "replacement")), origin));
+
+    if !regexp.is_empty() {
+        try!(Substitution::parse(&regexp));
+    }
+
+    Ok(NAPTR::new(
+        order,
+        preference,
+        flags,
+        services,
+        regexp,
+        replacement,
+    ))
+}
+
+/// The next token, which is the given field
+fn next<'a>(token: &mut Iter<&'a str>, field: &str) -> ParseResult<&'a str> {
+    token
+        .next()
+        .cloned()
+        .ok_or_else(|| ParseErrorKind::MissingToken(field.to_string()).into())
+}
@@ -0,0 +1,42 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for OPENPGPKEY text form
+
+use data_encoding::base64;
+
+use serialize::txt::*;
+use error::*;
+use rr::rdata::OPENPGPKEY;
+
+use super::to_strings;
+
+/// Parse the RData from a set of Tokens
+///
+/// The base64 of the public key, which may be split by whitespace
+pub fn parse(tokens: &Vec<Token>) -> ParseResult<OPENPGPKEY> {
+    let strings = try!(to_strings(tokens));
+
+    let public_key: String = strings.iter().map(|s| *s).collect();
+    if public_key.is_empty() {
+        return Err(ParseErrorKind::MissingToken("public key".to_string()).into());
+    }
+    let public_key = try!(base64::decode(public_key.as_bytes()).map_err(|e| {
+        ParseError::from(ParseErrorKind::Msg(format!("invalid public key: {:?}", e)))
+    }));
+
+    Ok(OPENPGPKEY::new(public_key))
+}
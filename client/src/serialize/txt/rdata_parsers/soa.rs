@@ -21,78 +21,30 @@ use error::*;
 use rr::domain::Name;
 use rr::rdata::SOA;
 
+use super::to_strings;
+
 /// Parse the RData from a set of Tokens
+///
+/// e.g. `ns hostmaster ( 2017101601 7200 600 3600000 60 )`, the names of the primary and of the
+///  mailbox of the administrator, then the serial, refresh, retry, expire and minimum, which may
+///  all be on one line
 pub fn parse(tokens: &Vec<Token>, origin: Option<&Name>) -> ParseResult<SOA> {
-    let mut token = tokens.iter();
+    let strings = try!(to_strings(tokens));
+    let mut token = strings.iter();
 
-    let mname: Name = try!(
-        token
-            .next()
-            .ok_or(ParseErrorKind::MissingToken("mname".to_string()).into())
-            .and_then(|t| if let &Token::CharData(ref s) = t {
-                Name::parse(s, origin).map_err(ParseError::from)
-            } else {
-                Err(ParseErrorKind::UnexpectedToken(t.clone()).into())
-            })
-    );
-    let rname: Name = try!(
-        token
-            .next()
-            .ok_or(ParseErrorKind::MissingToken("rname".to_string()).into())
-            .and_then(|t| if let &Token::CharData(ref s) = t {
-                Name::parse(s, origin).map_err(ParseError::from)
-            } else {
-                Err(ParseErrorKind::UnexpectedToken(t.clone()).into())
-            })
-    );
-    let mut list = try!(
-        token
-            .next()
-            .ok_or(ParseError::from(
-                ParseErrorKind::MissingToken("List".to_string()),
-            ))
-            .and_then(|t| if let &Token::List(ref v) = t {
-                Ok(v)
-            } else {
-                Err(ParseErrorKind::UnexpectedToken(t.clone()).into())
-            })
-    ).iter();
+    let mut next = |field: &str| -> ParseResult<&str> {
+        token.next().cloned().ok_or_else(|| {
+            ParseErrorKind::MissingToken(field.to_string()).into()
+        })
+    };
 
-    let serial: u32 = try!(
-        list.next()
-            .ok_or(ParseError::from(
-                ParseErrorKind::MissingToken("serial".to_string()),
-            ))
-            .and_then(|s| Ok(try!(s.parse())))
-    );
-    let refresh: i32 = try!(
-        list.next()
-            .ok_or(ParseError::from(
-                ParseErrorKind::MissingToken("refresh".to_string()),
-            ))
-            .and_then(|s| Ok(try!(s.parse())))
-    );
-    let retry: i32 = try!(
-        list.next()
-            .ok_or(ParseError::from(
-                ParseErrorKind::MissingToken("retry".to_string()),
-            ))
-            .and_then(|s| Ok(try!(s.parse())))
-    );
-    let expire: i32 = try!(
-        list.next()
-            .ok_or(ParseError::from(
-                ParseErrorKind::MissingToken("expire".to_string()),
-            ))
-            .and_then(|s| Ok(try!(s.parse())))
-    );
-    let minimum: u32 = try!(
-        list.next()
-            .ok_or(ParseError::from(
-                ParseErrorKind::MissingToken("minimum".to_string()),
-            ))
-            .and_then(|s| Ok(try!(s.parse())))
-    );
+    let mname: Name = try!(Name::parse(try!(next("mname")), origin));
+    let rname: Name = try!(Name::parse(try!(next("rname")), origin));
+    let serial: u32 = try!(try!(next("serial")).parse());
+    let refresh: i32 = try!(try!(next("refresh")).parse());
+    let retry: i32 = try!(try!(next("retry")).parse());
+    let expire: i32 = try!(try!(next("expire")).parse());
+    let minimum: u32 = try!(try!(next("minimum")).parse());
 
     Ok(SOA::new(
         mname,
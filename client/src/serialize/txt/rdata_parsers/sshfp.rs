@@ -0,0 +1,65 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for SSHFP text form
+
+use data_encoding::hex;
+
+use serialize::txt::*;
+use error::*;
+use rr::rdata::SSHFP;
+use rr::rdata::sshfp::{Algorithm, FingerprintType};
+
+use super::to_strings;
+
+/// Parse the RData from a set of Tokens
+///
+/// e.g. `4 2 <hex>`, the algorithm and the fingerprint type, the hex may be split by whitespace
+pub fn parse(tokens: &Vec<Token>) -> ParseResult<SSHFP> {
+    let strings = try!(to_strings(tokens));
+    let mut token = strings.iter();
+
+    let algorithm: u8 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("algorithm".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+    let fingerprint_type: u8 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("fingerprint type".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+
+    let fingerprint: String = token.map(|s| s.to_uppercase()).collect();
+    if fingerprint.is_empty() {
+        return Err(ParseErrorKind::MissingToken("fingerprint".to_string()).into());
+    }
+    let fingerprint = try!(hex::decode(fingerprint.as_bytes()).map_err(|e| {
+        ParseError::from(ParseErrorKind::Msg(format!("invalid fingerprint: {:?}", e)))
+    }));
+
+    Ok(SSHFP::new(
+        Algorithm::from(algorithm),
+        FingerprintType::from(fingerprint_type),
+        fingerprint,
+    ))
+}
@@ -0,0 +1,130 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for SVCB and HTTPS text form
+
+use data_encoding::base64;
+
+use serialize::txt::*;
+use error::*;
+use rr::domain::Name;
+use rr::rdata::SVCB;
+use rr::rdata::svcb::{SvcParam, SvcParamKey};
+
+use super::to_strings;
+
+/// Parse the RData from a set of Tokens
+///
+/// e.g. `1 . alpn=h3,h2 port=8443 ipv4hint=192.0.2.1 ech=<base64>`, the priority, the target and
+///  the params as `key=value`, or only the key for `no-default-alpn`. Values may be quoted, the
+///  escapes of RFC 9460 appendix A are not supported.
+pub fn parse(tokens: &Vec<Token>, origin: Option<&Name>) -> ParseResult<SVCB> {
+    let strings = try!(to_strings(tokens));
+    let mut token = strings.iter();
+
+    let svc_priority: u16 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("svc priority".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+    let target_name: Name = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("target name".to_string()),
+            ))
+            .and_then(|s| Name::parse(s, origin).map_err(ParseError::from))
+    );
+
+    let mut svc_params: Vec<SvcParam> = Vec::new();
+    for s in token {
+        let param = try!(parse_param(s));
+        if svc_params.iter().any(|p| p.key() == param.key()) {
+            return Err(
+                ParseErrorKind::Msg(format!("duplicate SvcParamKey: {}", param.key())).into(),
+            );
+        }
+        svc_params.push(param);
+    }
+
+    Ok(SVCB::new(svc_priority, target_name, svc_params))
+}
+
+/// Parses a `key=value` param
+fn parse_param(param: &str) -> ParseResult<SvcParam> {
+    let (key, value) = match param.find('=') {
+        Some(idx) => (&param[..idx], Some(unquote(&param[idx + 1..]))),
+        None => (param, None),
+    };
+    let key = try!(SvcParamKey::from_str(key));
+
+    let invalid = || ParseError::from(ParseErrorKind::Msg(format!("invalid SvcParam: {}", param)));
+
+    if key == SvcParamKey::NoDefaultAlpn {
+        return match value {
+            None => Ok(SvcParam::NoDefaultAlpn),
+            Some(_) => Err(invalid()),
+        };
+    }
+
+    let value = try!(value.ok_or_else(&invalid));
+    let param = match key {
+        SvcParamKey::Mandatory => SvcParam::Mandatory(try!(
+            value
+                .split(',')
+                .map(|k| SvcParamKey::from_str(k).map_err(ParseError::from))
+                .collect::<ParseResult<Vec<_>>>()
+        )),
+        SvcParamKey::Alpn => {
+            if value.split(',').any(str::is_empty) {
+                return Err(invalid());
+            }
+            SvcParam::Alpn(value.split(',').map(str::to_string).collect())
+        }
+        SvcParamKey::NoDefaultAlpn => SvcParam::NoDefaultAlpn,
+        SvcParamKey::Port => SvcParam::Port(try!(value.parse())),
+        SvcParamKey::Ipv4Hint => SvcParam::Ipv4Hint(try!(
+            value
+                .split(',')
+                .map(|a| a.parse().map_err(ParseError::from))
+                .collect::<ParseResult<Vec<_>>>()
+        )),
+        SvcParamKey::Ech => SvcParam::Ech(try!(base64::decode(value.as_bytes()).map_err(|e| {
+            ParseError::from(ParseErrorKind::Msg(format!("invalid ech: {:?}", e)))
+        }))),
+        SvcParamKey::Ipv6Hint => SvcParam::Ipv6Hint(try!(
+            value
+                .split(',')
+                .map(|a| a.parse().map_err(ParseError::from))
+                .collect::<ParseResult<Vec<_>>>()
+        )),
+        SvcParamKey::Key(key) => SvcParam::Unknown(key, value.as_bytes().to_vec()),
+    };
+
+    Ok(param)
+}
+
+/// Removes the quotes around a value, e.g. `alpn="h2,h3"`
+fn unquote(value: &str) -> &str {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
@@ -0,0 +1,78 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for TLSA text form
+
+use data_encoding::hex;
+
+use serialize::txt::*;
+use error::*;
+use rr::rdata::TLSA;
+use rr::rdata::tlsa::{CertUsage, Matching, Selector};
+
+use super::to_strings;
+
+/// Parse the RData from a set of Tokens
+///
+/// e.g. `3 1 1 <hex>`, the usage, selector and matching type, the hex may be split by whitespace
+pub fn parse(tokens: &Vec<Token>) -> ParseResult<TLSA> {
+    let strings = try!(to_strings(tokens));
+    let mut token = strings.iter();
+
+    let cert_usage: u8 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("cert usage".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+    let selector: u8 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("selector".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+    let matching: u8 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("matching type".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+
+    let cert_data: String = token.map(|s| s.to_uppercase()).collect();
+    if cert_data.is_empty() {
+        return Err(
+            ParseErrorKind::MissingToken("certificate association data".to_string()).into(),
+        );
+    }
+    let cert_data = try!(hex::decode(cert_data.as_bytes()).map_err(|e| {
+        ParseError::from(ParseErrorKind::Msg(
+            format!("invalid certificate association data: {:?}", e),
+        ))
+    }));
+
+    Ok(TLSA::new(
+        CertUsage::from(cert_usage),
+        Selector::from(selector),
+        Matching::from(matching),
+        cert_data,
+    ))
+}
@@ -20,14 +20,13 @@ use serialize::txt::*;
 use error::*;
 use rr::rdata::TXT;
 
+use super::{to_strings, unescape};
+
 /// Parse the RData from a set of Tokens
 pub fn parse(tokens: &Vec<Token>) -> ParseResult<TXT> {
     let mut txt_data: Vec<String> = Vec::with_capacity(tokens.len());
-    for t in tokens {
-        match *t {
-            Token::CharData(ref txt) => txt_data.push(txt.clone()),
-            _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
-        }
+    for txt in try!(to_strings(tokens)) {
+        txt_data.push(try!(unescape(txt)));
     }
 
     Ok(TXT::new(txt_data))
@@ -0,0 +1,83 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for the generic text form of RDATA, RFC 3597
+
+use data_encoding::hex;
+
+use serialize::binary::BinDecoder;
+use serialize::txt::*;
+use error::*;
+use rr::{RData, RecordType};
+
+use super::to_strings;
+
+/// The token which starts the generic form
+pub const GENERIC: &'static str = "\\#";
+
+/// Returns true if the tokens are in the generic form
+pub fn is_generic(tokens: &Vec<Token>) -> bool {
+    match tokens.first() {
+        Some(&Token::CharData(ref s)) => s == GENERIC,
+        _ => false,
+    }
+}
+
+/// Parse the RData from a set of Tokens
+///
+/// e.g. `\# 4 0A000001`, the length of the RDATA and the RDATA in hex, the hex may be split by
+///  whitespace. The RDATA of a known type is decoded as that type, RFC 3597 section 5.
+pub fn parse(record_type: RecordType, tokens: &Vec<Token>) -> ParseResult<RData> {
+    let strings = try!(to_strings(tokens));
+    let mut token = strings.iter();
+
+    if token.next() != Some(&GENERIC) {
+        return Err(ParseErrorKind::MissingToken(GENERIC.to_string()).into());
+    }
+
+    let length: u16 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("length".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+
+    let data: String = token.map(|s| s.to_uppercase()).collect();
+    let data = try!(hex::decode(data.as_bytes()).map_err(|e| {
+        ParseError::from(ParseErrorKind::Msg(format!("invalid rdata: {:?}", e)))
+    }));
+    if data.len() != length as usize {
+        return Err(
+            ParseErrorKind::Msg(format!(
+                "rdata length {} does not match the {} bytes of data",
+                length,
+                data.len()
+            )).into(),
+        );
+    }
+
+    if let RecordType::Unknown(code) = record_type {
+        return Ok(RData::Unknown {
+            code: code,
+            rdata: data,
+        });
+    }
+
+    let mut decoder = BinDecoder::new(&data);
+    Ok(try!(RData::read(&mut decoder, record_type, length)))
+}
@@ -0,0 +1,56 @@
+/*
+ * Copyright (C) 2017 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parser for URI text form
+
+use serialize::txt::*;
+use error::*;
+use rr::rdata::URI;
+
+use super::{to_strings, unescape};
+
+/// Parse the RData from a set of Tokens
+///
+/// e.g. `10 1 "ftp://ftp1.example.com/public"`, the priority, weight and the quoted target
+pub fn parse(tokens: &Vec<Token>) -> ParseResult<URI> {
+    let strings = try!(to_strings(tokens));
+    let mut token = strings.iter();
+
+    let priority: u16 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("priority".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+    let weight: u16 = try!(
+        token
+            .next()
+            .ok_or(ParseError::from(
+                ParseErrorKind::MissingToken("weight".to_string()),
+            ))
+            .and_then(|s| Ok(try!(s.parse())))
+    );
+    let target = try!(unescape(try!(token.next().ok_or(ParseError::from(
+        ParseErrorKind::MissingToken("target".to_string()),
+    )))));
+    if target.is_empty() {
+        return Err(ParseErrorKind::Msg("URI target must not be empty".to_string()).into());
+    }
+
+    Ok(URI::new(priority, weight, target))
+}
@@ -0,0 +1,529 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS based service discovery, DNS-SD, [RFC 6763](https://tools.ietf.org/html/rfc6763)
+//!
+//! Services are browsed by the PTR records under `_service._proto.domain`, each of which names
+//!  an instance of the service. The instances are then resolved to the SRV and TXT records which
+//!  describe where and how to connect to them.
+//!
+//! ```rust,no_run
+//! # extern crate futures;
+//! # extern crate tokio_core;
+//! # extern crate trust_dns;
+//! use std::str::FromStr;
+//!
+//! use futures::Future;
+//! use tokio_core::reactor::Core;
+//!
+//! use trust_dns::client::ClientFuture;
+//! use trust_dns::rr::Name;
+//! use trust_dns::service_discovery::ServiceDiscovery;
+//! use trust_dns::udp::UdpClientStream;
+//!
+//! # fn main() {
+//! let mut io_loop = Core::new().unwrap();
+//! let address = "8.8.8.8:53".parse().unwrap();
+//! let (stream, sender) = UdpClientStream::new(address, &io_loop.handle());
+//! let client = ClientFuture::new(stream, sender, &io_loop.handle(), None);
+//!
+//! let mut discovery = ServiceDiscovery::new(client, Name::from_str("example.com.").unwrap());
+//! let instances = io_loop.run(discovery.browse("_http._tcp")).unwrap();
+//!
+//! for instance in instances {
+//!     let service = io_loop.run(discovery.resolve(instance)).unwrap();
+//!     println!("{} at {:?}", service.instance_label(), service.srvs());
+//! }
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use futures::{future, Async, Future, Poll, Stream};
+use tokio_core::reactor::{Handle, Interval};
+
+use client::ClientHandle;
+#[cfg(feature = "mdns")]
+use client::{BasicClientHandle, ClientFuture};
+use error::*;
+use op::Message;
+use rr::{DNSClass, Name, RData, RecordType};
+use rr::rdata::SRV;
+#[cfg(feature = "mdns")]
+use trust_dns_proto::multicast::{MdnsClientStream, MdnsQueryType};
+
+/// The name, below the domain, at which the service types are enumerated, RFC 6763 section 9
+const SERVICES_NAME: &'static str = "_services._dns-sd._udp";
+
+/// A resolved instance of a service, see `ServiceDiscovery::resolve`
+#[derive(Clone, Debug)]
+pub struct ServiceInfo {
+    instance: Name,
+    srvs: Vec<SRV>,
+    txt: HashMap<String, Option<String>>,
+    addresses: Vec<IpAddr>,
+}
+
+impl ServiceInfo {
+    /// Builds the service from the responses to the SRV and TXT queries for the instance
+    fn from_responses(
+        instance: Name,
+        srv_response: &Message,
+        txt_response: &Message,
+    ) -> ClientResult<Self> {
+        let mut srvs = srv_response
+            .answers()
+            .iter()
+            .filter(|r| r.name() == &instance)
+            .filter_map(|r| if let RData::SRV(ref srv) = *r.rdata() {
+                Some(srv.clone())
+            } else {
+                None
+            })
+            .collect::<Vec<_>>();
+
+        if srvs.is_empty() {
+            return Err(
+                ClientErrorKind::Msg(format!("no SRV for service instance: {}", instance)).into(),
+            );
+        }
+
+        // lowest priority first, then the heaviest weight, RFC 2782
+        srvs.sort_by(|a, b| {
+            a.priority().cmp(&b.priority()).then(b.weight().cmp(&a.weight()))
+        });
+
+        // servers generally include the addresses of the targets, RFC 6763 section 12.2
+        let addresses = srv_response
+            .answers()
+            .iter()
+            .chain(srv_response.additionals().iter())
+            .filter(|r| srvs.iter().any(|srv| srv.target() == r.name()))
+            .filter_map(|r| match *r.rdata() {
+                RData::A(ip) => Some(IpAddr::V4(ip)),
+                RData::AAAA(ip) => Some(IpAddr::V6(ip)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let txt_data = txt_response
+            .answers()
+            .iter()
+            .filter(|r| r.name() == &instance)
+            .filter_map(|r| if let RData::TXT(ref txt) = *r.rdata() {
+                Some(txt.txt_data().to_vec())
+            } else {
+                None
+            })
+            .next()
+            .unwrap_or_default();
+
+        Ok(ServiceInfo {
+            instance: instance,
+            srvs: srvs,
+            txt: parse_txt(&txt_data),
+            addresses: addresses,
+        })
+    }
+
+    /// The full name of the instance, e.g. `My Printer._ipp._tcp.local.`
+    pub fn instance(&self) -> &Name {
+        &self.instance
+    }
+
+    /// The user visible name of the instance, i.e. the first label, e.g. `My Printer`
+    pub fn instance_label(&self) -> &str {
+        &self.instance[0]
+    }
+
+    /// The hosts and ports on which the instance is available, in the order in which they should
+    ///  be tried
+    pub fn srvs(&self) -> &[SRV] {
+        &self.srvs
+    }
+
+    /// The key/value pairs of the TXT record, keys are lowercase, and boolean attributes, those
+    ///  without an `=`, have no value, RFC 6763 section 6.4
+    pub fn txt(&self) -> &HashMap<String, Option<String>> {
+        &self.txt
+    }
+
+    /// The value for the key in the TXT record, None if the key is not present or has no value
+    pub fn txt_value(&self, key: &str) -> Option<&str> {
+        self.txt
+            .get(&key.to_lowercase())
+            .and_then(|value| value.as_ref())
+            .map(|value| value.as_str())
+    }
+
+    /// Addresses of the SRV targets, if the server included them with the SRV records
+    pub fn addresses(&self) -> &[IpAddr] {
+        &self.addresses
+    }
+}
+
+/// Parses the strings of a DNS-SD TXT record, RFC 6763 section 6
+fn parse_txt(strings: &[String]) -> HashMap<String, Option<String>> {
+    let mut txt = HashMap::new();
+
+    for string in strings {
+        let (key, value) = match string.find('=') {
+            Some(index) => (&string[..index], Some(string[index + 1..].to_string())),
+            None => (&string[..], None),
+        };
+
+        // strings without a key are ignored, and only the first occurrence of a key counts
+        if key.is_empty() {
+            continue;
+        }
+
+        txt.entry(key.to_lowercase()).or_insert(value);
+    }
+
+    txt
+}
+
+/// A change in the instances of a service, see `ServiceDiscovery::watch`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServiceEvent {
+    /// A new instance of the service was found
+    Discovered(Name),
+    /// The instance is no longer available
+    Removed(Name),
+}
+
+/// Discovers services in a domain, e.g. `local.` over mDNS, or a domain on a unicast DNS server
+#[derive(Clone)]
+pub struct ServiceDiscovery<H: ClientHandle> {
+    client: H,
+    domain: Name,
+}
+
+impl<H: ClientHandle + 'static> ServiceDiscovery<H> {
+    /// Creates a new ServiceDiscovery for the domain
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - the client over which to send the queries
+    /// * `domain` - the domain in which to look for services, e.g. `local.`
+    pub fn new(client: H, domain: Name) -> Self {
+        ServiceDiscovery {
+            client: client,
+            domain: domain,
+        }
+    }
+
+    /// The domain in which services are discovered
+    pub fn domain(&self) -> &Name {
+        &self.domain
+    }
+
+    /// Lists the types of services which are advertised in the domain, e.g. `_http._tcp.local.`
+    pub fn service_types(&mut self) -> Box<Future<Item = Vec<Name>, Error = ClientError>> {
+        match self.service_name(SERVICES_NAME) {
+            Ok(name) => self.ptrs(name),
+            Err(e) => Box::new(future::err(e)),
+        }
+    }
+
+    /// Lists the instances of the service
+    ///
+    /// # Arguments
+    ///
+    /// * `service_type` - the service and protocol, e.g. `_http._tcp`
+    pub fn browse(
+        &mut self,
+        service_type: &str,
+    ) -> Box<Future<Item = Vec<Name>, Error = ClientError>> {
+        match self.service_name(service_type) {
+            Ok(name) => self.ptrs(name),
+            Err(e) => Box::new(future::err(e)),
+        }
+    }
+
+    /// Looks up the SRV and TXT records for the instance of a service
+    ///
+    /// # Arguments
+    ///
+    /// * `instance` - the instance, as returned from `browse`
+    pub fn resolve(
+        &mut self,
+        instance: Name,
+    ) -> Box<Future<Item = ServiceInfo, Error = ClientError>> {
+        let srv = self.client.query(
+            instance.clone(),
+            DNSClass::IN,
+            RecordType::SRV,
+        );
+        let txt = self.client.query(
+            instance.clone(),
+            DNSClass::IN,
+            RecordType::TXT,
+        );
+
+        Box::new(srv.join(txt).and_then(move |(srv, txt)| {
+            ServiceInfo::from_responses(instance, &srv, &txt)
+        }))
+    }
+
+    /// Returns a stream of the instances of the service as they are discovered and removed
+    ///
+    /// The service is browsed immediately, and then again on every interval, instances which are
+    ///  no longer returned are reported as removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_type` - the service and protocol, e.g. `_http._tcp`
+    /// * `interval` - the time between each browse
+    /// * `loop_handle` - handle to the IO loop
+    pub fn watch(
+        &self,
+        service_type: &str,
+        interval: Duration,
+        loop_handle: &Handle,
+    ) -> ClientResult<ServiceWatch<H>> {
+        let mut discovery = self.clone();
+        let browse = discovery.browse(service_type);
+
+        Ok(ServiceWatch {
+            discovery: discovery,
+            service_type: service_type.to_string(),
+            interval: try!(Interval::new(interval, loop_handle)),
+            browse: Some(browse),
+            instances: HashSet::new(),
+            events: VecDeque::new(),
+        })
+    }
+
+    fn service_name(&self, service_type: &str) -> ClientResult<Name> {
+        Ok(try!(Name::parse(service_type, None)).append_domain(&self.domain))
+    }
+
+    fn ptrs(&mut self, name: Name) -> Box<Future<Item = Vec<Name>, Error = ClientError>> {
+        Box::new(
+            self.client
+                .query(name.clone(), DNSClass::IN, RecordType::PTR)
+                .map(move |response| ptr_targets(&name, &response)),
+        )
+    }
+}
+
+#[cfg(feature = "mdns")]
+impl ServiceDiscovery<BasicClientHandle> {
+    /// Discovers services on the local link, in the `local.` domain, with one-shot mDNS queries
+    ///
+    /// Only the first responder to each query is heard, on a link with many instances of a
+    ///  service, use a `MdnsQueryType::Continuous` stream and the client directly.
+    pub fn mdns(loop_handle: &Handle) -> Self {
+        let (stream, sender) =
+            MdnsClientStream::new_ipv4(MdnsQueryType::OneShot, vec![], loop_handle);
+        let client = ClientFuture::new(stream, sender, loop_handle, None);
+
+        Self::new(client, Name::from_labels(vec!["local"]).append_domain(&Name::root()))
+    }
+}
+
+/// The targets of the PTR records for the name, records with a TTL of zero are mDNS goodbyes,
+///  i.e. the instance is being withdrawn, RFC 6762 section 10.1
+fn ptr_targets(name: &Name, response: &Message) -> Vec<Name> {
+    let mut targets = response
+        .answers()
+        .iter()
+        .filter(|r| r.name() == name && r.ttl() > 0)
+        .filter_map(|r| if let RData::PTR(ref target) = *r.rdata() {
+            Some(target.clone())
+        } else {
+            None
+        })
+        .collect::<Vec<_>>();
+
+    targets.sort();
+    targets.dedup();
+    targets
+}
+
+/// A stream of `ServiceEvent`s, see `ServiceDiscovery::watch`
+#[must_use = "streams do nothing unless polled"]
+pub struct ServiceWatch<H: ClientHandle> {
+    discovery: ServiceDiscovery<H>,
+    service_type: String,
+    interval: Interval,
+    browse: Option<Box<Future<Item = Vec<Name>, Error = ClientError>>>,
+    instances: HashSet<Name>,
+    events: VecDeque<ServiceEvent>,
+}
+
+impl<H: ClientHandle> ServiceWatch<H> {
+    /// Queues the differences between the known instances and the latest browse
+    fn update(&mut self, instances: Vec<Name>) {
+        let instances = instances.into_iter().collect::<HashSet<_>>();
+
+        for removed in self.instances.difference(&instances) {
+            self.events.push_back(ServiceEvent::Removed(removed.clone()));
+        }
+        for discovered in instances.difference(&self.instances) {
+            self.events.push_back(
+                ServiceEvent::Discovered(discovered.clone()),
+            );
+        }
+
+        self.instances = instances;
+    }
+}
+
+impl<H: ClientHandle + 'static> Stream for ServiceWatch<H> {
+    type Item = ServiceEvent;
+    type Error = ClientError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Async::Ready(Some(event)));
+            }
+
+            if self.browse.is_none() {
+                match try!(self.interval.poll()) {
+                    Async::Ready(Some(())) => (),
+                    Async::Ready(None) => return Ok(Async::Ready(None)),
+                    Async::NotReady => return Ok(Async::NotReady),
+                }
+
+                self.browse = Some(self.discovery.browse(&self.service_type));
+            }
+
+            let result = match self.browse.as_mut().expect("browse was just set").poll() {
+                Ok(Async::Ready(instances)) => Ok(instances),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => Err(e),
+            };
+            self.browse = None;
+
+            match result {
+                Ok(instances) => self.update(instances),
+                // a failed browse says nothing about the instances, wait for the next one
+                Err(e) => warn!("browse of {} failed: {}", self.service_type, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use rr::Record;
+    use rr::rdata::TXT;
+    use super::*;
+
+    #[test]
+    fn test_parse_txt() {
+        let txt = parse_txt(&[
+            "txtvers=1".to_string(),
+            "".to_string(),
+            "PaperSize=A4".to_string(),
+            "papersize=Letter".to_string(),
+            "Color".to_string(),
+            "note=".to_string(),
+            "=nokey".to_string(),
+        ]);
+
+        assert_eq!(txt.len(), 4);
+        assert_eq!(txt["txtvers"], Some("1".to_string()));
+        assert_eq!(txt["papersize"], Some("A4".to_string()));
+        assert_eq!(txt["color"], None);
+        assert_eq!(txt["note"], Some("".to_string()));
+    }
+
+    #[test]
+    fn test_ptr_targets() {
+        let service = Name::from_str("_ipp._tcp.local.").unwrap();
+        let first = Name::from_str("first._ipp._tcp.local.").unwrap();
+        let gone = Name::from_str("gone._ipp._tcp.local.").unwrap();
+
+        let mut response = Message::new();
+        response
+            .answers_mut()
+            .insert(Record::from_rdata(
+                service.clone(),
+                120,
+                RecordType::PTR,
+                RData::PTR(first.clone()),
+            ))
+            .unwrap()
+            .insert(Record::from_rdata(
+                service.clone(),
+                0,
+                RecordType::PTR,
+                RData::PTR(gone),
+            ))
+            .unwrap();
+
+        assert_eq!(ptr_targets(&service, &response), vec![first]);
+    }
+
+    #[test]
+    fn test_service_info() {
+        let instance = Name::from_str("My\\ Printer._ipp._tcp.local.").unwrap();
+        let host = Name::from_str("printer.local.").unwrap();
+        let backup = Name::from_str("backup.local.").unwrap();
+
+        let mut srv_response = Message::new();
+        srv_response
+            .answers_mut()
+            .insert(Record::from_rdata(
+                instance.clone(),
+                120,
+                RecordType::SRV,
+                RData::SRV(SRV::new(10, 0, 631, backup.clone())),
+            ))
+            .unwrap()
+            .insert(Record::from_rdata(
+                instance.clone(),
+                120,
+                RecordType::SRV,
+                RData::SRV(SRV::new(0, 0, 631, host.clone())),
+            ))
+            .unwrap();
+        srv_response
+            .additionals_mut()
+            .insert(Record::from_rdata(
+                host.clone(),
+                120,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(169, 254, 0, 1)),
+            ))
+            .unwrap();
+
+        let mut txt_response = Message::new();
+        txt_response
+            .answers_mut()
+            .insert(Record::from_rdata(
+                instance.clone(),
+                120,
+                RecordType::TXT,
+                RData::TXT(TXT::new(vec!["rp=printers/1".to_string()])),
+            ))
+            .unwrap();
+
+        let service = ServiceInfo::from_responses(instance.clone(), &srv_response, &txt_response)
+            .unwrap();
+
+        assert_eq!(service.instance(), &instance);
+        assert_eq!(service.srvs()[0].target(), &host);
+        assert_eq!(service.srvs()[1].target(), &backup);
+        assert_eq!(service.addresses(), &[IpAddr::V4(Ipv4Addr::new(169, 254, 0, 1))]);
+        assert_eq!(service.txt_value("RP"), Some("printers/1"));
+
+        // an instance without an SRV can't be connected to
+        assert!(
+            ServiceInfo::from_responses(instance, &Message::new(), &txt_response).is_err()
+        );
+    }
+}
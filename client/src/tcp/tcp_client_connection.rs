@@ -18,13 +18,15 @@ use std::io;
 use std::net::SocketAddr;
 use std::time::Duration;
 
-use futures::Future;
+use futures::{Future, Stream};
 use tokio_core::net::TcpStream;
 use tokio_core::reactor::Core;
 use trust_dns_proto::DnsStreamHandle;
 
 use error::*;
-use client::{ClientConnection, ClientStreamHandle};
+use client::{ClientConnection, ClientStreamHandle, IncrementalChange, IncrementalTransfer,
+             ZoneTransfer};
+use rr::{Name, Record};
 use tcp::TcpClientStream;
 
 /// Tcp client connection
@@ -34,6 +36,7 @@ pub struct TcpClientConnection {
     io_loop: Core,
     tcp_client_stream: Box<Future<Item = TcpClientStream<TcpStream>, Error = io::Error>>,
     client_stream_handle: Box<ClientStreamHandle>,
+    timeout: Duration,
 }
 
 impl TcpClientConnection {
@@ -68,8 +71,54 @@ impl TcpClientConnection {
             io_loop: io_loop,
             tcp_client_stream: tcp_client_stream,
             client_stream_handle: handle,
+            timeout: timeout,
         })
     }
+
+    /// Transfers the zone from the name server over this connection, AXFR, see `ZoneTransfer`
+    ///
+    /// The records are returned once all of them are received, starting with the SOA of the zone.
+    ///  `ZoneTransfer::axfr` yields them as they arrive.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the origin of the zone to transfer
+    pub fn axfr(self, zone: Name) -> ClientResult<Vec<Record>> {
+        let mut io_loop = self.io_loop;
+        let transfer = ZoneTransfer::with_timeout(
+            self.tcp_client_stream,
+            self.client_stream_handle,
+            &io_loop.handle(),
+            self.timeout,
+            zone,
+        );
+
+        io_loop.run(transfer.collect())
+    }
+
+    /// Transfers the changes to the zone since the serial from the name server over this
+    ///  connection, IXFR, see `IncrementalTransfer`
+    ///
+    /// The changes are returned once all of them are received, in the order of the versions of
+    ///  the zone, empty if the zone did not change.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the origin of the zone to transfer
+    /// * `serial` - the serial of the version of the zone which the client has
+    pub fn ixfr(self, zone: Name, serial: u32) -> ClientResult<Vec<IncrementalChange>> {
+        let mut io_loop = self.io_loop;
+        let transfer = IncrementalTransfer::with_timeout(
+            self.tcp_client_stream,
+            self.client_stream_handle,
+            &io_loop.handle(),
+            self.timeout,
+            zone,
+            serial,
+        );
+
+        io_loop.run(transfer.collect())
+    }
 }
 
 impl ClientConnection for TcpClientConnection {
@@ -0,0 +1,498 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! HTTP/2 client stream for sending DNS messages to a DNS over HTTPS endpoint
+
+use std::io;
+use std::mem;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use data_encoding::base64url;
+use futures::{future, Async, Future, Poll, Stream};
+use futures::sync::mpsc::{unbounded, UnboundedReceiver};
+use h2;
+use h2::RecvStream;
+use h2::client::{ResponseFuture, SendRequest};
+use http::{self, Method, Request, Uri};
+use rustls::{Certificate, ClientConfig};
+use tokio_core::net::TcpStream as TokioTcpStream;
+use tokio_core::reactor::Handle;
+use tokio_rustls::ClientConfigExt;
+
+use trust_dns_proto::{BufDnsStreamHandle, DnsStreamHandle};
+
+use {DEFAULT_DNS_QUERY_PATH, MIME_APPLICATION_DNS};
+
+/// The ALPN protocol id for HTTP/2
+const ALPN_H2: &'static str = "h2";
+
+/// The variable of the URI template which is expanded to the query for GET requests
+const DNS_VARIABLE: &'static str = "{?dns}";
+
+/// The largest DNS message which will be accepted in a response
+const MAX_MESSAGE_LEN: usize = 65_535;
+
+fn h2_error(e: h2::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, format!("h2 error: {}", e))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RequestMethod {
+    Get,
+    Post,
+}
+
+/// The endpoint for the DNS over HTTPS requests
+///
+/// [RFC 8484](https://tools.ietf.org/html/rfc8484#section-4.1)
+///
+/// ```text
+/// 4.1.  The HTTP Request
+///
+///    A DNS API client encodes a single DNS query into an HTTP request
+///    using either the HTTP GET or POST method and the other requirements
+///    of this section.  The DNS API server defines the URI used by the
+///    request through the use of a URI Template.
+/// ```
+///
+/// Only the `{?dns}` variable is supported, templates which end with it are sent as GET requests,
+///  all others as POST requests to the URL as is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct UrlTemplate {
+    url: String,
+    method: RequestMethod,
+}
+
+impl UrlTemplate {
+    fn parse(template: &str) -> io::Result<Self> {
+        let (url, method) = if template.ends_with(DNS_VARIABLE) {
+            (
+                &template[..template.len() - DNS_VARIABLE.len()],
+                RequestMethod::Get,
+            )
+        } else {
+            (template, RequestMethod::Post)
+        };
+
+        if !url.starts_with("https://") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("DNS over HTTPS requires an https URL: {}", template),
+            ));
+        }
+
+        if url.contains('{') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("only the {} template variable is supported: {}", DNS_VARIABLE, template),
+            ));
+        }
+
+        try!(url.parse::<Uri>().map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("bad URL template: {}: {}", template, e),
+            )
+        }));
+
+        Ok(UrlTemplate {
+            url: url.to_string(),
+            method: method,
+        })
+    }
+
+    /// The common default, POST requests to `/dns-query` on the named server
+    fn with_dns_name(dns_name: &str) -> Self {
+        UrlTemplate {
+            url: format!("https://{}{}", dns_name, DEFAULT_DNS_QUERY_PATH),
+            method: RequestMethod::Post,
+        }
+    }
+
+    fn request(&self, message: &[u8]) -> Result<Request<()>, http::Error> {
+        let mut builder = Request::builder();
+
+        match self.method {
+            RequestMethod::Get => {
+                // the query is base64url encoded without padding, RFC 8484 section 4.1
+                let encoded = base64url::encode(message);
+                let separator = if self.url.contains('?') { '&' } else { '?' };
+                let url = format!(
+                    "{}{}dns={}",
+                    self.url,
+                    separator,
+                    encoded.trim_right_matches('=')
+                );
+
+                builder.method(Method::GET).uri(url.as_str());
+            }
+            RequestMethod::Post => {
+                builder
+                    .method(Method::POST)
+                    .uri(self.url.as_str())
+                    .header("content-type", MIME_APPLICATION_DNS)
+                    .header("content-length", message.len().to_string().as_str());
+            }
+        }
+
+        builder.header("accept", MIME_APPLICATION_DNS);
+        builder.body(())
+    }
+}
+
+/// The response to a single request, resolves to the DNS message in the body
+#[must_use = "futures do nothing unless polled"]
+struct HttpsResponse {
+    response: Option<ResponseFuture>,
+    body: Option<RecvStream>,
+    message: Vec<u8>,
+}
+
+impl HttpsResponse {
+    fn new(response: ResponseFuture) -> Self {
+        HttpsResponse {
+            response: Some(response),
+            body: None,
+            message: Vec::new(),
+        }
+    }
+}
+
+impl Future for HttpsResponse {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Some(mut response) = self.response.take() {
+            let response = match try!(response.poll().map_err(h2_error)) {
+                Async::Ready(response) => response,
+                Async::NotReady => {
+                    self.response = Some(response);
+                    return Ok(Async::NotReady);
+                }
+            };
+
+            if !response.status().is_success() {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("http error: {}", response.status()),
+                ));
+            }
+
+            let (_, body) = response.into_parts();
+            self.body = Some(body);
+        }
+
+        let body = match self.body {
+            Some(ref mut body) => body,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "response polled after completion",
+                ))
+            }
+        };
+
+        loop {
+            match try_ready!(body.poll().map_err(h2_error)) {
+                Some(chunk) => {
+                    if self.message.len() + chunk.len() > MAX_MESSAGE_LEN {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "response is larger than a DNS message",
+                        ));
+                    }
+
+                    self.message.extend_from_slice(&chunk);
+
+                    // allow the server to continue sending
+                    try!(
+                        body.release_capacity()
+                            .release_capacity(chunk.len())
+                            .map_err(h2_error)
+                    );
+                }
+                None => return Ok(Async::Ready(mem::replace(&mut self.message, Vec::new()))),
+            }
+        }
+    }
+}
+
+/// A DNS over HTTPS client stream, [RFC 8484](https://tools.ietf.org/html/rfc8484)
+///
+/// Each message sent to the stream is sent as a separate request over a single HTTP/2 connection,
+///  responses are returned in the order they complete. Use with `trust_dns::client::DnsFuture`
+///  impls, which match responses to queries by the message id.
+#[must_use = "futures do nothing unless polled"]
+pub struct HttpsClientStream {
+    name_server: SocketAddr,
+    template: UrlTemplate,
+    h2: SendRequest<Bytes>,
+    outbound_messages: UnboundedReceiver<(Vec<u8>, SocketAddr)>,
+    pending: Option<Vec<u8>>,
+    in_flight: Vec<HttpsResponse>,
+    is_shutdown: bool,
+}
+
+impl HttpsClientStream {
+    fn send(&mut self, message: Vec<u8>) -> io::Result<()> {
+        let request = try!(self.template.request(&message).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("bad request: {}", e),
+            )
+        }));
+
+        let is_post = self.template.method == RequestMethod::Post;
+        let (response, mut send_stream) = try!(
+            self.h2
+                .send_request(request, !is_post)
+                .map_err(h2_error)
+        );
+
+        if is_post {
+            try!(
+                send_stream
+                    .send_data(Bytes::from(message), true)
+                    .map_err(h2_error)
+            );
+        }
+
+        self.in_flight.push(HttpsResponse::new(response));
+        Ok(())
+    }
+}
+
+impl Stream for HttpsClientStream {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        // send the queued messages, for as long as the connection will accept new streams
+        while !self.is_shutdown {
+            if self.pending.is_none() {
+                match self.outbound_messages.poll() {
+                    Ok(Async::Ready(Some((message, _)))) => self.pending = Some(message),
+                    // the DnsStreamHandle was dropped, nothing more will be sent
+                    Ok(Async::Ready(None)) |
+                    Err(_) => {
+                        self.is_shutdown = true;
+                        break;
+                    }
+                    Ok(Async::NotReady) => break,
+                }
+            }
+
+            match try!(self.h2.poll_ready().map_err(h2_error)) {
+                Async::Ready(()) => {
+                    let message = self.pending.take().expect("pending message missing");
+                    try!(self.send(message));
+                }
+                Async::NotReady => break,
+            }
+        }
+
+        // a failed request only fails that query, the connection may still be used
+        let mut idx = 0;
+        while idx < self.in_flight.len() {
+            match self.in_flight[idx].poll() {
+                Ok(Async::Ready(message)) => {
+                    self.in_flight.swap_remove(idx);
+                    return Ok(Async::Ready(Some(message)));
+                }
+                Ok(Async::NotReady) => idx += 1,
+                Err(e) => {
+                    warn!("request to {} failed: {}", self.name_server, e);
+                    self.in_flight.swap_remove(idx);
+                }
+            }
+        }
+
+        if self.is_shutdown && self.in_flight.is_empty() {
+            Ok(Async::Ready(None))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+/// Builder for `HttpsClientStream`
+pub struct HttpsClientStreamBuilder {
+    client_config: ClientConfig,
+    ca_chain: Vec<Certificate>,
+    url_template: Option<String>,
+}
+
+impl HttpsClientStreamBuilder {
+    /// Constructs a new builder, no roots are trusted by default
+    pub fn new() -> HttpsClientStreamBuilder {
+        Self::with_client_config(ClientConfig::new())
+    }
+
+    /// Creates a builder from an existing configuration, e.g. one which already trusts a set of
+    ///  root certificates
+    pub fn with_client_config(client_config: ClientConfig) -> HttpsClientStreamBuilder {
+        HttpsClientStreamBuilder {
+            client_config: client_config,
+            ca_chain: vec![],
+            url_template: None,
+        }
+    }
+
+    /// Add a custom trusted peer certificate or certificate auhtority.
+    pub fn add_ca(&mut self, ca: Certificate) {
+        self.ca_chain.push(ca);
+    }
+
+    /// The URI template of the endpoint, e.g. `https://cloudflare-dns.com/dns-query{?dns}`
+    ///
+    /// Templates ending in `{?dns}` are sent as GET requests, otherwise the URL is used as is with
+    ///  POST requests. By default POST requests are sent to `https://{dns_name}/dns-query`.
+    pub fn set_url_template(&mut self, url_template: String) {
+        self.url_template = Some(url_template);
+    }
+
+    /// Creates a new HttpsClientStream to the specified name_server
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - IP and Port for the remote DNS over HTTPS server
+    /// * `dns_name` - The name to validate the certificate of the server against, sent as SNI
+    /// * `loop_handle` - The reactor Core handle
+    pub fn build(
+        self,
+        name_server: SocketAddr,
+        dns_name: String,
+        loop_handle: &Handle,
+    ) -> (Box<Future<Item = HttpsClientStream, Error = io::Error>>, Box<DnsStreamHandle>) {
+        let (message_sender, outbound_messages) = unbounded();
+        let sender = Box::new(BufDnsStreamHandle::new(name_server, message_sender));
+
+        let template = match self.url_template {
+            Some(ref url_template) => UrlTemplate::parse(url_template),
+            None => Ok(UrlTemplate::with_dns_name(&dns_name)),
+        };
+        let template = match template {
+            Ok(template) => template,
+            Err(e) => return (Box::new(future::err(e)), sender),
+        };
+
+        let mut client_config = self.client_config;
+        for ca in &self.ca_chain {
+            if let Err(e) = client_config.root_store.add(ca) {
+                return (
+                    Box::new(future::err(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        format!("tls error: {:?}", e),
+                    ))),
+                    sender,
+                );
+            }
+        }
+
+        // HTTP/2 must be negotiated during the TLS handshake, RFC 8484 section 5.2
+        client_config.set_protocols(&[ALPN_H2.to_string()]);
+        let client_config = Arc::new(client_config);
+
+        let tcp = TokioTcpStream::connect(&name_server, loop_handle);
+        let loop_handle = loop_handle.clone();
+
+        let stream: Box<Future<Item = HttpsClientStream, Error = io::Error>> = Box::new(
+            tcp.and_then(move |tcp_stream| {
+                client_config
+                    .connect_async(&dns_name, tcp_stream)
+                    .map_err(|e| {
+                        io::Error::new(
+                            io::ErrorKind::ConnectionRefused,
+                            format!("tls error: {}", e),
+                        )
+                    })
+            }).and_then(|tls_stream| h2::client::handshake(tls_stream).map_err(h2_error))
+                .map(move |(h2, connection)| {
+                    // the connection drives all the IO for the requests
+                    loop_handle.spawn(connection.map_err(move |e| {
+                        warn!("h2 connection to {} failed: {}", name_server, e)
+                    }));
+
+                    debug!("h2 connection established to: {}", name_server);
+                    HttpsClientStream {
+                        name_server: name_server,
+                        template: template,
+                        h2: h2,
+                        outbound_messages: outbound_messages,
+                        pending: None,
+                        in_flight: vec![],
+                        is_shutdown: false,
+                    }
+                }),
+        );
+
+        (stream, sender)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template() {
+        assert_eq!(
+            UrlTemplate::parse("https://cloudflare-dns.com/dns-query").unwrap(),
+            UrlTemplate {
+                url: "https://cloudflare-dns.com/dns-query".to_string(),
+                method: RequestMethod::Post,
+            }
+        );
+        assert_eq!(
+            UrlTemplate::parse("https://cloudflare-dns.com/dns-query{?dns}").unwrap(),
+            UrlTemplate {
+                url: "https://cloudflare-dns.com/dns-query".to_string(),
+                method: RequestMethod::Get,
+            }
+        );
+
+        assert!(UrlTemplate::parse("http://cloudflare-dns.com/dns-query").is_err());
+        assert!(UrlTemplate::parse("https://cloudflare-dns.com/{name}{?dns}").is_err());
+    }
+
+    #[test]
+    fn test_get_request() {
+        let template = UrlTemplate::parse("https://dns.example.com/dns-query{?dns}").unwrap();
+
+        // from RFC 8484 section 4.1.1, the query for www.example.com A
+        let message = [
+            0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x77,
+            0x77, 0x77, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d,
+            0x00, 0x00, 0x01, 0x00, 0x01,
+        ];
+        let request = template.request(&message).unwrap();
+
+        assert_eq!(request.method(), &Method::GET);
+        assert_eq!(
+            request.uri().to_string(),
+            "https://dns.example.com/dns-query?dns=AAABAAABAAAAAAAAA3d3dwdleGFtcGxlA2NvbQAAAQAB"
+        );
+        assert_eq!(request.headers()["accept"], MIME_APPLICATION_DNS);
+    }
+
+    #[test]
+    fn test_post_request() {
+        let template = UrlTemplate::with_dns_name("cloudflare-dns.com");
+        let request = template.request(&[0, 1, 2, 3]).unwrap();
+
+        assert_eq!(request.method(), &Method::POST);
+        assert_eq!(
+            request.uri().to_string(),
+            "https://cloudflare-dns.com/dns-query"
+        );
+        assert_eq!(request.headers()["content-type"], MIME_APPLICATION_DNS);
+        assert_eq!(request.headers()["content-length"], "4");
+    }
+}
@@ -0,0 +1,33 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS over HTTPS, [RFC 8484](https://tools.ietf.org/html/rfc8484), related components
+
+#![deny(missing_docs)]
+
+extern crate bytes;
+extern crate data_encoding;
+#[macro_use]
+extern crate futures;
+extern crate h2;
+extern crate http;
+#[macro_use]
+extern crate log;
+extern crate rustls;
+extern crate tokio_core;
+extern crate tokio_rustls;
+extern crate trust_dns_proto;
+
+pub mod https_client_stream;
+
+pub use self::https_client_stream::{HttpsClientStream, HttpsClientStreamBuilder};
+
+/// The media type of DNS messages in the body of requests and responses
+pub const MIME_APPLICATION_DNS: &'static str = "application/dns-message";
+
+/// The default path of the endpoint, as used by most public DNS over HTTPS servers
+pub const DEFAULT_DNS_QUERY_PATH: &'static str = "/dns-query";
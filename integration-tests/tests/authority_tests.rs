@@ -1,3 +1,4 @@
+extern crate chrono;
 extern crate rusqlite;
 extern crate trust_dns;
 extern crate trust_dns_server;
@@ -5,14 +6,19 @@ extern crate trust_dns_integration;
 
 use std::collections::BTreeMap;
 use std::net::*;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use chrono::Duration;
 use rusqlite::*;
 
 use trust_dns::rr::*;
 use trust_dns::rr::dnssec::*;
 use trust_dns::rr::rdata::*;
 use trust_dns::op::*;
+use trust_dns::serialize::txt::{Lexer, Parser};
 use trust_dns_server::authority::*;
+use trust_dns_server::error::{PersistenceErrorKind, PersistenceResult};
 
 use trust_dns_integration::authority::{create_example, create_secure_example};
 
@@ -946,6 +952,337 @@ fn test_update() {
     assert_eq!(serial + 6, authority.serial());
 }
 
+/// Records the changes each hook call is made with, as the name, type and whether the RRset
+///  exists before and after
+#[derive(Default)]
+struct ChangeRecorder(Mutex<Vec<(u32, Vec<(Name, RecordType, bool, bool)>)>>);
+
+impl ChangeHook for ChangeRecorder {
+    fn on_records_changed(&self, _origin: &Name, serial: u32, changes: &[RRsetChange]) {
+        let changes = changes
+            .iter()
+            .map(|change| {
+                (
+                    change.name().clone(),
+                    change.record_type(),
+                    change.before().is_some(),
+                    change.after().is_some(),
+                )
+            })
+            .collect();
+        self.0.lock().unwrap().push((serial, changes));
+    }
+}
+
+#[test]
+fn test_change_hook() {
+    let mut authority: Authority = create_example();
+    let recorder = Arc::new(ChangeRecorder::default());
+    authority.add_change_hook(recorder.clone());
+    let serial = authority.serial();
+
+    let new_name = Name::from_labels(vec!["new", "example", "com"]);
+    let mut record = Record::with(new_name.clone(), RecordType::A, 86400);
+    record.set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)));
+    assert!(authority.update_records(&[record.clone()], true).unwrap());
+
+    // nothing changed, the hooks are not called
+    assert!(!authority.update_records(&[record.clone()], true).unwrap());
+
+    let mut delete = Record::with(new_name.clone(), RecordType::A, 0);
+    delete.set_dns_class(DNSClass::ANY).set_rdata(RData::NULL(NULL::new()));
+    assert!(authority.update_records(&[delete], true).unwrap());
+
+    let origin = authority.origin().clone();
+    assert_eq!(
+        *recorder.0.lock().unwrap(),
+        vec![
+            (
+                serial + 1,
+                vec![
+                    (origin.clone(), RecordType::SOA, true, true),
+                    (new_name.clone(), RecordType::A, false, true),
+                ],
+            ),
+            (
+                serial + 2,
+                vec![
+                    (origin.clone(), RecordType::SOA, true, true),
+                    (new_name.clone(), RecordType::A, true, false),
+                ],
+            ),
+        ]
+    );
+}
+
+/// Keeps the records in memory, and the keys written to, as a store of a database might
+///
+/// Once `failing` is set, the writes fail, as those to a full disk might.
+struct WriteLog {
+    records: BTreeMap<RrKey, RecordSet>,
+    writes: Arc<Mutex<Vec<(RrKey, bool)>>>,
+    failing: Arc<AtomicBool>,
+    begun: Option<BTreeMap<RrKey, RecordSet>>,
+}
+
+impl WriteLog {
+    fn write(&self, key: &RrKey, is_upsert: bool) -> PersistenceResult<()> {
+        if self.failing.load(Ordering::Relaxed) {
+            return Err(PersistenceErrorKind::Msg("disk full".to_string()).into());
+        }
+        self.writes.lock().unwrap().push((key.clone(), is_upsert));
+        Ok(())
+    }
+}
+
+impl ZoneStore for WriteLog {
+    fn get_rrset(&self, key: &RrKey) -> Option<&RecordSet> {
+        self.records.get(key)
+    }
+
+    fn upsert(&mut self, key: RrKey, rrset: RecordSet) -> PersistenceResult<Option<RecordSet>> {
+        try!(self.write(&key, true));
+        Ok(self.records.insert(key, rrset))
+    }
+
+    fn delete(&mut self, key: &RrKey) -> PersistenceResult<Option<RecordSet>> {
+        try!(self.write(key, false));
+        Ok(self.records.remove(key))
+    }
+
+    fn begin(&mut self) -> PersistenceResult<()> {
+        self.begun = Some(self.records.clone());
+        Ok(())
+    }
+
+    fn commit(&mut self) -> PersistenceResult<()> {
+        self.begun = None;
+        Ok(())
+    }
+
+    fn rollback(&mut self) {
+        if let Some(records) = self.begun.take() {
+            self.records = records;
+        }
+    }
+
+    fn iter<'a>(&'a self) -> Box<Iterator<Item = (&'a RrKey, &'a RecordSet)> + 'a> {
+        Box::new(self.records.iter())
+    }
+
+    fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    fn clear(&mut self) {
+        self.records.clear()
+    }
+}
+
+#[test]
+fn test_zone_store() {
+    let example = create_example();
+    let writes = Arc::new(Mutex::new(vec![]));
+    let failing = Arc::new(AtomicBool::new(false));
+    let store = WriteLog {
+        records: example.records().to_map(),
+        writes: writes.clone(),
+        failing: failing.clone(),
+        begun: None,
+    };
+    let mut authority = Authority::with_store(
+        example.origin().clone(),
+        Box::new(store),
+        ZoneType::Master,
+        true,
+        false,
+    );
+
+    // the zone is served from the store
+    let www = Name::parse("www.example.com.", None).unwrap();
+    assert_eq!(
+        authority.lookup(&www, RecordType::A, false, SupportedAlgorithms::new()),
+        example.lookup(&www, RecordType::A, false, SupportedAlgorithms::new())
+    );
+    assert_eq!(authority.records().len(), example.records().len());
+    assert!(writes.lock().unwrap().is_empty());
+
+    // and each change is written to it, the SOA with its serial
+    let new_name = Name::from_labels(vec!["new", "example", "com"]);
+    let mut record = Record::with(new_name.clone(), RecordType::A, 86400);
+    record.set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)));
+    assert!(authority.update_records(&[record], true).unwrap());
+
+    let new_a = RrKey::new(&new_name, RecordType::A);
+    let soa = RrKey::new(authority.origin(), RecordType::SOA);
+    assert_eq!(
+        *writes.lock().unwrap(),
+        vec![(new_a.clone(), true), (soa, true)]
+    );
+    assert!(authority.records().get_rrset(&new_a).is_some());
+
+    writes.lock().unwrap().clear();
+    let mut delete = Record::with(new_name.clone(), RecordType::A, 0);
+    delete.set_dns_class(DNSClass::ANY).set_rdata(RData::NULL(NULL::new()));
+    assert!(authority.update_records(&[delete], true).unwrap());
+    assert_eq!(writes.lock().unwrap()[0], (new_a.clone(), false));
+    assert!(authority.records().get_rrset(&new_a).is_none());
+
+    // an update which can not be written is answered with ServFail, and leaves the zone as it was
+    let serial = authority.serial();
+    let mut record = Record::with(new_name.clone(), RecordType::A, 86400);
+    record.set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)));
+    failing.store(true, Ordering::Relaxed);
+    assert_eq!(
+        authority.update_records(&[record], true),
+        Err(ResponseCode::ServFail)
+    );
+    assert!(authority.records().get_rrset(&new_a).is_none());
+    assert_eq!(authority.serial(), serial);
+}
+
+#[test]
+fn test_sqlite_store() {
+    let path = std::env::temp_dir().join("trust-dns-test_sqlite_store.sqlite");
+    let _ = std::fs::remove_file(&path);
+
+    let example = create_example();
+    let new_name = Name::from_labels(vec!["new", "example", "com"]);
+    let new_a = RrKey::new(&new_name, RecordType::A);
+    let serial = {
+        let mut store = SqliteStore::from_file(&path).expect("open store");
+        assert!(store.is_empty());
+        for (key, rrset) in example.records().to_map() {
+            store.upsert(key, rrset).unwrap();
+        }
+
+        let mut authority = Authority::with_store(
+            example.origin().clone(),
+            Box::new(store),
+            ZoneType::Master,
+            true,
+            false,
+        );
+        let mut record = Record::with(new_name.clone(), RecordType::A, 86400);
+        record.set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)));
+        assert!(authority.update_records(&[record], true).unwrap());
+
+        let www = Name::parse("www.example.com.", None).unwrap();
+        let mut delete = Record::with(www, RecordType::A, 0);
+        delete.set_dns_class(DNSClass::ANY).set_rdata(RData::NULL(NULL::new()));
+        assert!(authority.update_records(&[delete], true).unwrap());
+
+        assert_eq!(authority.serial(), example.serial() + 2);
+        authority.serial()
+    };
+
+    // the zone is replayed from the journal as it was left, at the same serial, and the journal is
+    //  compacted to its RRsets
+    let store = SqliteStore::from_file(&path).expect("reopen store");
+    assert_eq!(store.entries(), store.len());
+    assert_eq!(store.len(), example.records().len());
+
+    let authority = Authority::with_store(
+        example.origin().clone(),
+        Box::new(store),
+        ZoneType::Master,
+        true,
+        false,
+    );
+    assert_eq!(authority.serial(), serial);
+    assert!(authority.records().get_rrset(&new_a).is_some());
+    let www = Name::parse("www.example.com.", None).unwrap();
+    assert!(
+        authority
+            .lookup(&www, RecordType::A, false, SupportedAlgorithms::new())
+            .is_empty()
+    );
+    assert_eq!(
+        authority.lookup(&www, RecordType::AAAA, false, SupportedAlgorithms::new()),
+        example.lookup(&www, RecordType::AAAA, false, SupportedAlgorithms::new())
+    );
+    drop(authority);
+
+    // the changes of a transaction which is rolled back are neither kept nor journaled
+    {
+        let mut store = SqliteStore::from_file(&path).expect("reopen store");
+        let entries = store.entries();
+        store.begin().unwrap();
+        assert!(store.delete(&new_a).unwrap().is_some());
+        assert!(store.get_rrset(&new_a).is_none());
+        store.rollback();
+        assert!(store.get_rrset(&new_a).is_some());
+        assert_eq!(store.entries(), entries);
+    }
+    let store = SqliteStore::from_file(&path).expect("reopen store");
+    assert!(store.get_rrset(&new_a).is_some());
+    drop(store);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_ixfr_history() {
+    let mut authority: Authority = create_example();
+    authority.set_ixfr_history_size(2);
+    let serial = authority.serial();
+
+    let new_name = Name::from_labels(vec!["new", "example", "com"]);
+    let mut record = Record::with(new_name.clone(), RecordType::A, 86400);
+    record.set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)));
+    assert!(authority.update_records(&[record.clone()], true).unwrap());
+
+    let mut other = record.clone();
+    other.set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 25)));
+    let mut delete = record.clone();
+    delete.set_dns_class(DNSClass::NONE).set_ttl(0);
+    assert!(authority.update_records(&[delete, other.clone()], true).unwrap());
+
+    let changes = authority.changes_since(serial).expect("changes not kept");
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].from_serial(), serial);
+    assert_eq!(changes[0].to_serial(), serial + 1);
+    assert!(changes[0].deleted().is_empty());
+    assert_eq!(changes[0].added(), &[record.clone()]);
+    assert_eq!(changes[1].deleted(), &[record.clone()]);
+    assert_eq!(changes[1].added(), &[other.clone()]);
+
+    // the SOA, the changes each from the old to the new SOA, and the SOA again
+    let ixfr = authority.ixfr(serial, false, SupportedAlgorithms::new());
+    let serials = ixfr
+        .iter()
+        .filter_map(|record| match *record.rdata() {
+            RData::SOA(ref soa) => Some(soa.serial()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(ixfr.len(), 9);
+    assert_eq!(
+        serials,
+        vec![serial + 2, serial, serial + 1, serial + 1, serial + 2, serial + 2]
+    );
+
+    // an up to date client is only sent the SOA
+    assert_eq!(
+        authority.ixfr(serial + 2, false, SupportedAlgorithms::new()),
+        vec![authority.soa().unwrap()]
+    );
+
+    // only the latest changes are kept, older versions are sent the whole zone
+    assert!(authority.update_records(&[record.clone()], true).unwrap());
+    assert!(authority.changes_since(serial).is_none());
+    assert_eq!(authority.changes_since(serial + 1).unwrap().len(), 2);
+
+    let ixfr = authority.ixfr(serial, false, SupportedAlgorithms::new());
+    let mut query = Query::new();
+    query.set_name(authority.origin().clone());
+    query.set_query_type(RecordType::AXFR);
+    assert_eq!(
+        ixfr,
+        authority.search(&query, false, SupportedAlgorithms::new())
+    );
+}
+
 #[test]
 fn test_zone_signing() {
     let authority: Authority = create_secure_example();
@@ -998,6 +1335,77 @@ fn test_get_nsec() {
     }
 }
 
+#[test]
+fn test_get_nsec3() {
+    let mut authority = create_example();
+    let origin = authority.origin().clone();
+
+    // b.example.com. is an empty non-terminal
+    let mut record = Record::with(
+        Name::from_labels(vec!["a", "b", "example", "com"]),
+        RecordType::A,
+        86400,
+    );
+    record.set_rdata(RData::A(Ipv4Addr::new(10, 0, 0, 1)));
+    authority.upsert(record, 0);
+
+    let key = KeyPair::generate(Algorithm::ECDSAP256SHA256).unwrap();
+    let dnskey = key.to_dnskey(Algorithm::ECDSAP256SHA256).unwrap();
+    let signer = Signer::dnssec(dnskey, key, origin.clone(), Duration::weeks(1));
+    authority.add_secure_key(signer).unwrap();
+    authority.set_denial(Denial::Nsec3 {
+        iterations: 1,
+        salt: vec![0xAA, 0xBB],
+        opt_out: false,
+    });
+    authority.secure_zone().unwrap();
+
+    // example.com., www.example.com., b.example.com. and a.b.example.com.
+    let records = authority.lookup(&origin, RecordType::AXFR, false, SupportedAlgorithms::new());
+    let count = |record_type: RecordType| records.iter().filter(|r| r.rr_type() == record_type).count();
+    assert_eq!(count(RecordType::NSEC), 0);
+    assert_eq!(count(RecordType::NSEC3PARAM), 1);
+    assert_eq!(count(RecordType::NSEC3), 4);
+
+    let types = |records: &[&Record]| -> Vec<Vec<RecordType>> {
+        records
+            .iter()
+            .filter_map(|record| match *record.rdata() {
+                RData::NSEC3(ref nsec3) => Some(nsec3.type_bit_maps().to_vec()),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // NoData is proven by the NSEC3 of the name
+    let www = Name::from_labels(vec!["www", "example", "com"]);
+    let results = authority.get_denial_records(&www, true, false, SupportedAlgorithms::new());
+    assert_eq!(results.len(), 1);
+    let www_types = types(&results).remove(0);
+    assert!(www_types.contains(&RecordType::A));
+    assert!(!www_types.contains(&RecordType::MX));
+
+    // the empty non-terminal has an NSEC3 without types
+    let ent = Name::from_labels(vec!["b", "example", "com"]);
+    assert!(authority.contains_name(&ent));
+    let results = authority.get_denial_records(&ent, true, false, SupportedAlgorithms::new());
+    assert_eq!(types(&results), vec![Vec::<RecordType>::new()]);
+
+    // NXDomain is proven by the closest encloser, with the NSEC3 records covering the next closer
+    //  name and the wildcard
+    let nx = Name::from_labels(vec!["nx", "zzz", "example", "com"]);
+    assert!(!authority.contains_name(&nx));
+    let results = authority.get_denial_records(&nx, false, true, SupportedAlgorithms::all());
+    assert!(results.iter().all(|r| {
+        r.rr_type() == RecordType::NSEC3 || r.rr_type() == RecordType::RRSIG
+    }));
+    assert!(results.iter().any(|r| r.rr_type() == RecordType::RRSIG));
+    assert!(types(&results).iter().any(
+        |bit_maps| bit_maps.contains(&RecordType::SOA),
+    ));
+    assert!(types(&results).len() <= 3);
+}
+
 #[test]
 fn test_journal() {
     // test that this message can be inserted
@@ -1094,7 +1502,7 @@ fn test_recovery() {
     assert_eq!(recovered_authority.soa(), authority.soa());
     assert!(recovered_authority.records().iter().all(
         |(rr_key, rr_set)| {
-            let other_rr_set = authority.records().get(rr_key).expect(&format!(
+            let other_rr_set = authority.records().get_rrset(rr_key).expect(&format!(
                 "key doesn't exist: {:?}",
                 rr_key
             ));
@@ -1106,7 +1514,7 @@ fn test_recovery() {
     ));
 
     assert!(authority.records().iter().all(|(rr_key, rr_set)| {
-        let other_rr_set = recovered_authority.records().get(rr_key).expect(&format!(
+        let other_rr_set = recovered_authority.records().get_rrset(rr_key).expect(&format!(
             "key doesn't exist: {:?}",
             rr_key
         ));
@@ -1116,3 +1524,135 @@ fn test_recovery() {
         })
     }));
 }
+
+#[test]
+fn test_replace_records() {
+    let conn = Connection::open_in_memory().expect("could not create in memory DB");
+    let mut journal = Journal::new(conn).unwrap();
+    journal.schema_up().unwrap();
+
+    let example = create_example();
+    let origin = example.origin().clone();
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let mut records = example
+        .records()
+        .iter()
+        .flat_map(|(_, rrset)| rrset.iter().cloned())
+        .collect::<Vec<_>>();
+    records.push(Record::from_rdata(
+        www.clone(),
+        86400,
+        RecordType::RRSIG,
+        RData::SIG(SIG::new(
+            RecordType::A,
+            Algorithm::RSASHA256,
+            3,
+            86400,
+            1442283296,
+            1440443544,
+            54108,
+            origin.clone(),
+            vec![1, 2, 3, 4],
+        )),
+    ));
+
+    // a secondary which has not been transferred yet
+    let mut authority = Authority::new(
+        origin.clone(),
+        BTreeMap::new(),
+        ZoneType::Slave,
+        false,
+        false,
+    );
+    authority.set_journal(journal);
+    assert_eq!(authority.replace_records(records.clone()), 2015082403);
+
+    // the signature is served with the records it covers
+    let www_a = RrKey::new(&www, RecordType::A);
+    assert_eq!(authority.records().len(), example.records().len());
+    assert_eq!(authority.records().get_rrset(&www_a).unwrap().rrsigs().len(), 1);
+
+    // and the zone is recovered from the journal as a whole, even after it was replaced again
+    assert_eq!(authority.replace_records(records), 2015082403);
+    let mut recovered = Authority::new(
+        origin.clone(),
+        BTreeMap::new(),
+        ZoneType::Slave,
+        false,
+        false,
+    );
+    recovered.recover_with_journal(authority.journal().unwrap()).expect("recovery");
+    assert_eq!(recovered.serial(), 2015082403);
+    assert_eq!(recovered.records().len(), authority.records().len());
+    assert_eq!(recovered.records().get_rrset(&www_a).unwrap().rrsigs().len(), 1);
+}
+
+#[test]
+fn test_advance_serial() {
+    let mut authority = create_example();
+    assert_eq!(authority.serial(), 2015082403);
+
+    // a later serial is kept
+    assert_eq!(authority.advance_serial(2015082402), 2015082403);
+    assert_eq!(authority.serial(), 2015082403);
+
+    assert_eq!(authority.advance_serial(2015082403), 2015082404);
+    assert_eq!(authority.serial(), 2015082404);
+
+    // serial numbers wrap, RFC 1982
+    assert_eq!(authority.advance_serial(2999999999), 3000000000);
+    assert_eq!(authority.advance_serial(u32::max_value()), 0);
+    assert_eq!(authority.serial(), 0);
+    assert_eq!(authority.advance_serial(u32::max_value() - 1), 0);
+}
+
+#[test]
+fn test_to_zone_file() {
+    let example = create_example();
+    let zone = example.to_zone_file().unwrap();
+    assert!(zone.starts_with("$ORIGIN example.com.\n"));
+    assert_eq!(example.to_zone_file().unwrap(), zone);
+
+    // the zone is read back with the same records
+    let (origin, records) = Parser::new().parse(Lexer::new(&zone), None).unwrap();
+    assert_eq!(&origin, example.origin());
+    assert_eq!(
+        records.keys().collect::<Vec<_>>(),
+        example
+            .records()
+            .iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>()
+    );
+    for (key, rrset) in example.records().iter() {
+        let mut expected = rrset.iter().map(|r| r.rdata()).collect::<Vec<_>>();
+        let mut parsed = records[key].iter().map(|r| r.rdata()).collect::<Vec<_>>();
+        expected.sort();
+        parsed.sort();
+        assert_eq!(parsed, expected, "{:?}", key);
+    }
+
+    // the DNSSEC records are only written when asked for
+    let secure = create_secure_example();
+    let count = |zone: &str, record_type: &str| {
+        zone.lines()
+            .filter(|line| line.split_whitespace().nth(3) == Some(record_type))
+            .count()
+    };
+    let zone = secure.to_zone_file().unwrap();
+    assert_eq!(count(&zone, "RRSIG"), 0);
+    assert_eq!(count(&zone, "NSEC"), 0);
+    assert_eq!(count(&zone, "DNSKEY"), 0);
+
+    let zone = secure.to_zone_file_with_dnssec().unwrap();
+    let rrsigs = secure
+        .records()
+        .iter()
+        .map(|(_, rrset)| rrset.rrsigs().len())
+        .sum::<usize>();
+    assert!(rrsigs > 0);
+    assert_eq!(count(&zone, "RRSIG"), rrsigs);
+    assert!(count(&zone, "NSEC") > 0);
+    assert_eq!(count(&zone, "DNSKEY"), 1);
+    assert!(Parser::new().parse(Lexer::new(&zone), None).is_ok());
+}
@@ -1,15 +1,22 @@
+extern crate chrono;
+extern crate futures;
+extern crate tokio_core;
 extern crate trust_dns;
+extern crate trust_dns_proto;
 extern crate trust_dns_server;
 extern crate trust_dns_integration;
 
 use std::net::*;
 use std::collections::*;
+use std::sync::{Arc, Mutex};
 
 use trust_dns::op::*;
 use trust_dns::rr::*;
 use trust_dns::rr::rdata::*;
+use trust_dns::rr::rdata::opt::{ClientSubnet, Cookie, EdnsCode, EdnsOption, ExtendedErrorCode};
 
 use trust_dns_server::authority::*;
+use trust_dns_server::server::{Request, RequestHandler};
 
 use trust_dns_integration::authority::create_example;
 
@@ -232,6 +239,49 @@ fn test_catalog_nx_soa() {
     );
 }
 
+#[test]
+fn test_catalog_nodata() {
+    let mut example = create_example();
+    let origin = example.origin().clone();
+
+    // b.example.com. is an empty non-terminal
+    let mut record = Record::with(
+        Name::parse("a.b.example.com.", None).unwrap(),
+        RecordType::A,
+        86400,
+    );
+    record.set_rdata(RData::A(Ipv4Addr::new(10, 0, 0, 1)));
+    example.upsert(record, 0);
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let lookup = |name: &str, record_type: RecordType| {
+        let mut question: Message = Message::new();
+        let mut query: Query = Query::new();
+        query.set_name(Name::parse(name, None).unwrap());
+        query.set_query_type(record_type);
+        question.add_query(query);
+        catalog.lookup(&question)
+    };
+
+    // the name exists, the type does not
+    for &(name, record_type) in &[
+        ("www.example.com.", RecordType::MX),
+        ("b.example.com.", RecordType::A),
+    ]
+    {
+        let result = lookup(name, record_type);
+        assert_eq!(result.response_code(), ResponseCode::NoError);
+        assert!(result.answers().is_empty());
+        assert_eq!(result.name_servers().len(), 1);
+        assert_eq!(result.name_servers()[0].rr_type(), RecordType::SOA);
+    }
+
+    let result = lookup("c.b.example.com.", RecordType::A);
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+}
+
 #[test]
 fn test_axfr() {
     let test = create_test();
@@ -368,3 +418,1766 @@ fn test_axfr() {
 
     assert_eq!(expected_set, answers);
 }
+
+#[test]
+fn test_catalog_response_cache() {
+    let test = create_test();
+    let origin = test.origin().clone();
+    let www_name = Name::parse("www.test.com.", None).unwrap();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.set_response_cache_size(16);
+    assert_eq!(catalog.response_cache_size(), 16);
+    catalog.upsert(origin.clone(), test);
+
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(www_name.clone());
+    question.add_query(query);
+
+    // the second lookup is answered from the cache, and must be identical
+    let first: Message = catalog.lookup(&question);
+    let second: Message = catalog.lookup(&question);
+    assert_eq!(first.response_code(), ResponseCode::NoError);
+    assert_eq!(first.answers(), second.answers());
+    assert_eq!(first.name_servers(), second.name_servers());
+    assert_eq!(
+        second.answers().first().unwrap().rdata(),
+        &RData::A(Ipv4Addr::new(94, 184, 216, 34))
+    );
+
+    // replacing the zone, even with the same serial, must invalidate the cached answer
+    let mut replacement = create_test();
+    replacement.upsert(
+        Record::new()
+            .set_name(www_name.clone())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(10, 0, 0, 1)))
+            .clone(),
+        0,
+    );
+    catalog.upsert(origin.clone(), replacement);
+
+    let result: Message = catalog.lookup(&question);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.answers().iter().any(|r| {
+        r.rdata() == &RData::A(Ipv4Addr::new(10, 0, 0, 1))
+    }));
+}
+
+#[test]
+fn test_catalog_replace() {
+    let test = create_test();
+    let origin = test.origin().clone();
+    let www_name = Name::parse("www.test.com.", None).unwrap();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.set_response_cache_size(16);
+    catalog.upsert(origin.clone(), test);
+    assert_eq!(catalog.serial(&origin), Some(2015082403));
+
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(www_name.clone());
+    question.add_query(query);
+    catalog.lookup(&question);
+
+    // a reloaded zone replaces the one being served, without a mutable catalog
+    let mut replacement = create_test();
+    replacement.upsert(
+        Record::new()
+            .set_name(www_name.clone())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(10, 0, 0, 1)))
+            .clone(),
+        0,
+    );
+    assert_eq!(replacement.advance_serial(2015082403), 2015082404);
+
+    let catalog = &catalog;
+    assert!(catalog.replace(&origin, replacement));
+    assert_eq!(catalog.serial(&origin), Some(2015082404));
+
+    let result: Message = catalog.lookup(&question);
+    assert!(result.answers().iter().any(|r| {
+        r.rdata() == &RData::A(Ipv4Addr::new(10, 0, 0, 1))
+    }));
+
+    // only zones which are being served can be replaced
+    let unknown = Name::parse("example.net.", None).unwrap();
+    assert!(!catalog.replace(&unknown, create_test()));
+    assert_eq!(catalog.serial(&unknown), None);
+}
+
+/// Returns the serials of the SOA records of an answer
+fn soa_serials(records: &[Record]) -> Vec<u32> {
+    records
+        .iter()
+        .filter_map(|record| match *record.rdata() {
+            RData::SOA(ref soa) => Some(soa.serial()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_catalog_ixfr() {
+    let test = create_test();
+    let origin = test.origin().clone();
+    let new_name = Name::parse("new.test.com.", None).unwrap();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.set_response_cache_size(16);
+    catalog.upsert(origin.clone(), test);
+
+    // the changes of the replaced versions are kept
+    let mut replacement = create_test();
+    replacement.upsert(
+        Record::new()
+            .set_name(new_name.clone())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(10, 0, 0, 1)))
+            .clone(),
+        0,
+    );
+    replacement.advance_serial(2015082403);
+    assert!(catalog.replace(&origin, replacement));
+
+    let ixfr = |serial: u32| {
+        let mut soa = Record::with(origin.clone(), RecordType::SOA, 0);
+        soa.set_rdata(RData::SOA(SOA::new(
+            Name::root(),
+            Name::root(),
+            serial,
+            0,
+            0,
+            0,
+            0,
+        )));
+
+        let mut question: Message = Message::new();
+        question.add_query(Query::query(origin.clone(), RecordType::IXFR));
+        question.add_name_server(soa);
+        catalog.lookup(&question)
+    };
+
+    let result = ixfr(2015082403);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(
+        soa_serials(result.answers()),
+        vec![2015082404, 2015082403, 2015082404, 2015082404]
+    );
+    assert_eq!(result.answers().len(), 5);
+    assert_eq!(result.answers()[3].name(), &new_name);
+
+    // an up to date client, and a client of an unknown version which is sent the whole zone
+    assert_eq!(ixfr(2015082404).answers().len(), 1);
+    let result = ixfr(2015082400);
+    assert_eq!(soa_serials(result.answers()), vec![2015082404, 2015082404]);
+    assert!(result.answers().len() > 5);
+
+    // the SOA of the client is required
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(origin.clone(), RecordType::IXFR));
+    assert_eq!(
+        catalog.lookup(&question).response_code(),
+        ResponseCode::FormErr
+    );
+}
+
+#[test]
+fn test_catalog_notify() {
+    let test = create_test();
+    let origin = test.origin().clone();
+    let example = create_example();
+    let example_origin = example.origin().clone();
+    let secondary = Authority::new(
+        example_origin.clone(),
+        example.records().to_map(),
+        ZoneType::Slave,
+        false,
+        false,
+    );
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+    catalog.upsert(example_origin.clone(), secondary);
+    catalog.set_primary(example_origin.clone(), "10.0.0.1:53".parse().unwrap());
+
+    let primary = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let notify = |name: &Name, record_type: RecordType, serial: Option<u32>| {
+        let mut notify: Message = Message::new();
+        notify
+            .set_id(7)
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Notify);
+        notify.add_query(Query::query(name.clone(), record_type));
+        if let Some(serial) = serial {
+            notify.add_answer(Record::from_rdata(
+                name.clone(),
+                3600,
+                RecordType::SOA,
+                RData::SOA(SOA::new(
+                    Name::parse("sns.dns.icann.org.", None).unwrap(),
+                    Name::parse("noc.dns.icann.org.", None).unwrap(),
+                    serial,
+                    7200,
+                    3600,
+                    1209600,
+                    3600,
+                )),
+            ));
+        }
+        notify
+    };
+
+    // the response echoes the NOTIFY
+    let response = catalog.handle_notify(
+        &notify(&example_origin, RecordType::SOA, Some(2015082403)),
+        primary,
+    );
+    assert_eq!(response.id(), 7);
+    assert_eq!(response.message_type(), MessageType::Response);
+    assert_eq!(response.op_code(), OpCode::Notify);
+    assert_eq!(response.queries()[0].name(), &example_origin);
+
+    // the serial is not newer, the zone is not refreshed
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(
+        catalog.zone_stats()[&example_origin].transfer_status(),
+        &TransferStatus::None
+    );
+
+    // only from the primary, of a secondary zone, which is being served
+    let other = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+    let unknown = Name::parse("example.net.", None).unwrap();
+    let cases = [
+        (example_origin.clone(), RecordType::SOA, other, ResponseCode::Refused),
+        (origin.clone(), RecordType::SOA, primary, ResponseCode::Refused),
+        (unknown, RecordType::SOA, primary, ResponseCode::NotAuth),
+        (example_origin.clone(), RecordType::A, primary, ResponseCode::FormErr),
+    ];
+    for &(ref name, record_type, src, response_code) in &cases {
+        let response = catalog.handle_notify(&notify(name, record_type, None), src);
+        assert_eq!(response.response_code(), response_code);
+    }
+}
+
+#[test]
+fn test_catalog_closest_zone() {
+    let test = create_test();
+    let origin = test.origin().clone();
+
+    let sub_origin = Name::parse("sub.test.com.", None).unwrap();
+    let mut sub = Authority::new(
+        sub_origin.clone(),
+        BTreeMap::new(),
+        ZoneType::Master,
+        false,
+        false,
+    );
+    sub.upsert(
+        Record::from_rdata(
+            sub_origin.clone(),
+            3600,
+            RecordType::SOA,
+            RData::SOA(SOA::new(
+                Name::parse("sns.dns.icann.org.", None).unwrap(),
+                Name::parse("noc.dns.icann.org.", None).unwrap(),
+                2017101601,
+                7200,
+                3600,
+                1209600,
+                3600,
+            )),
+        ),
+        0,
+    );
+    sub.upsert(
+        Record::from_rdata(
+            Name::parse("www.sub.test.com.", None).unwrap(),
+            3600,
+            RecordType::A,
+            RData::A(Ipv4Addr::new(10, 0, 0, 2)),
+        ),
+        0,
+    );
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+    catalog.upsert(sub_origin.clone(), sub);
+
+    let lookup = |name: &str| {
+        let mut question: Message = Message::new();
+        let mut query: Query = Query::new();
+        query.set_name(Name::parse(name, None).unwrap());
+        question.add_query(query);
+        catalog.lookup(&question)
+    };
+
+    // the longest matching origin wins, regardless of case
+    for name in vec!["www.sub.test.com.", "WWW.Sub.Test.COM."] {
+        let result = lookup(name);
+        assert_eq!(result.response_code(), ResponseCode::NoError);
+        assert_eq!(
+            result.answers().first().unwrap().rdata(),
+            &RData::A(Ipv4Addr::new(10, 0, 0, 2))
+        );
+    }
+
+    let result = lookup("www.test.com.");
+    assert_eq!(
+        result.answers().first().unwrap().rdata(),
+        &RData::A(Ipv4Addr::new(94, 184, 216, 34))
+    );
+
+    let result = lookup("nothing.sub.test.com.");
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+    assert_eq!(
+        result.name_servers().first().unwrap().name(),
+        &sub_origin
+    );
+
+    // no zone at all
+    let result = lookup("www.test.org.");
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+    assert!(result.answers().is_empty());
+}
+
+#[test]
+fn test_catalog_failed_zone() {
+    let test = create_test();
+    let origin = test.origin().clone();
+    let failed_origin = Name::parse("sub.test.com.", None).unwrap();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+    catalog.mark_failed(failed_origin.clone(), "bad zone file".to_string());
+    assert_eq!(catalog.failed_zones().len(), 1);
+
+    // names in the failed zone are not answered from the parent zone
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(Name::parse("www.sub.test.com.", None).unwrap());
+    question.add_query(query);
+
+    let result: Message = catalog.lookup(&question);
+    assert_eq!(result.response_code(), ResponseCode::ServFail);
+    assert!(result.answers().is_empty());
+
+    // the rest of the parent zone is still served
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(Name::parse("www.test.com.", None).unwrap());
+    question.add_query(query);
+
+    let result: Message = catalog.lookup(&question);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(!result.answers().is_empty());
+
+    // a zone which failed to reload continues to serve the previous version
+    catalog.mark_failed(origin.clone(), "bad zone file".to_string());
+    let result: Message = catalog.lookup(&question);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+
+    catalog.upsert(origin.clone(), create_test());
+    assert!(!catalog.failed_zones().contains_key(&origin));
+}
+
+#[test]
+fn test_catalog_reload_failed() {
+    let test = create_test();
+    let origin = test.origin().clone();
+    let serial = test.serial();
+
+    // the catalog is shared by the server, the failure is recorded through it
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+    let catalog = Arc::new(catalog);
+    catalog.mark_failed(origin.clone(), "bad zone file".to_string());
+    catalog.mark_failed(origin.clone(), "still a bad zone file".to_string());
+
+    assert_eq!(catalog.zone_stats()[&origin].load_failures(), 2);
+    let health = catalog.health();
+    assert_eq!(health.len(), 1);
+    assert_eq!(health[0].status(), ZoneHealthStatus::ReloadFailed);
+    assert_eq!(health[0].serial(), Some(serial));
+    assert_eq!(health[0].error(), Some("still a bad zone file"));
+    assert!(health[0].is_critical());
+    assert!(health[0].to_string().starts_with(
+        &format!("test.com. ReloadFailed serial {} refreshed ", serial),
+    ));
+    assert!(health[0].to_string().ends_with(
+        ", reload failed: still a bad zone file",
+    ));
+
+    // the previous version is still served
+    let mut question: Message = Message::new();
+    question.add_query(Query::query(
+        Name::parse("www.test.com.", None).unwrap(),
+        RecordType::A,
+    ));
+    assert_eq!(catalog.lookup(&question).response_code(), ResponseCode::NoError);
+
+    // until a version replaces it, the count of failures is kept
+    assert!(catalog.replace(&origin, create_test()));
+    assert!(catalog.failed_zones().is_empty());
+    let health = catalog.health();
+    assert_eq!(health[0].status(), ZoneHealthStatus::Healthy);
+    assert_eq!(health[0].error(), None);
+    assert_eq!(catalog.zone_stats()[&origin].load_failures(), 2);
+}
+
+#[test]
+fn test_catalog_zone_stats_and_health() {
+    let test = create_test();
+    let origin = test.origin().clone();
+
+    // a secondary which has already passed its expire interval
+    let secondary_origin = Name::parse("secondary.com.", None).unwrap();
+    let mut secondary = Authority::new(
+        secondary_origin.clone(),
+        BTreeMap::new(),
+        ZoneType::Slave,
+        false,
+        false,
+    );
+    secondary.upsert(
+        Record::new()
+            .set_name(secondary_origin.clone())
+            .set_ttl(3600)
+            .set_rr_type(RecordType::SOA)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::SOA(SOA::new(
+                Name::parse("sns.dns.icann.org.", None).unwrap(),
+                Name::parse("noc.dns.icann.org.", None).unwrap(),
+                2017100101,
+                0,
+                0,
+                0,
+                3600,
+            )))
+            .clone(),
+        0,
+    );
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+    catalog.upsert(secondary_origin.clone(), secondary);
+    catalog.mark_failed(
+        Name::parse("failed.com.", None).unwrap(),
+        "bad zone file".to_string(),
+    );
+
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(Name::parse("www.test.com.", None).unwrap());
+    question.add_query(query);
+    catalog.lookup(&question);
+
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(Name::parse("nx.test.com.", None).unwrap());
+    question.add_query(query);
+    catalog.lookup(&question);
+
+    catalog.record_transfer(&secondary_origin, TransferStatus::Failed("refused".to_string()));
+
+    let zone_stats = catalog.zone_stats();
+    let stats = &zone_stats[&origin];
+    assert_eq!(stats.queries(), 2);
+    assert_eq!(stats.response_code_count(ResponseCode::NoError), 1);
+    assert_eq!(stats.response_code_count(ResponseCode::NXDomain), 1);
+    assert_eq!(
+        zone_stats[&secondary_origin].transfer_status(),
+        &TransferStatus::Failed("refused".to_string())
+    );
+
+    let health = catalog.health();
+    assert_eq!(health.len(), 3);
+    let status_of = |name: &str| {
+        let name = Name::parse(name, None).unwrap();
+        health
+            .iter()
+            .find(|h| h.zone() == &name)
+            .map(|h| h.status())
+            .unwrap()
+    };
+    assert_eq!(status_of("test.com."), ZoneHealthStatus::Healthy);
+    assert_eq!(status_of("secondary.com."), ZoneHealthStatus::Expired);
+    assert_eq!(status_of("failed.com."), ZoneHealthStatus::Failed);
+    assert_eq!(health.iter().filter(|h| h.is_critical()).count(), 2);
+}
+
+#[test]
+fn test_catalog_health_query() {
+    let test = create_test();
+    let origin = test.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+    catalog.mark_failed(
+        Name::parse("failed.com.", None).unwrap(),
+        "bad zone file".to_string(),
+    );
+    let serial = catalog.serial(&origin).unwrap();
+
+    let health_name = Name::parse("health.server.", None).unwrap();
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(health_name.clone());
+    query.set_query_type(RecordType::TXT);
+    query.set_query_class(DNSClass::CH);
+    question.add_query(query);
+
+    // not answered unless enabled, the names of the zones are not given away
+    let result: Message = catalog.lookup(&question);
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+    assert!(result.answers().is_empty());
+
+    catalog.set_health_query(true);
+    let result: Message = catalog.lookup(&question);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.header().authoritative());
+
+    let txt_data = result
+        .answers()
+        .iter()
+        .map(|record| {
+            assert_eq!(record.name(), &health_name);
+            assert_eq!(record.dns_class(), DNSClass::CH);
+            match *record.rdata() {
+                RData::TXT(ref txt) => txt.txt_data().to_vec(),
+                ref rdata => panic!("expected TXT: {:?}", rdata),
+            }
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(txt_data.len(), 2);
+    assert_eq!(
+        txt_data[0],
+        vec![
+            "failed.com.".to_string(),
+            "Failed".to_string(),
+            "load failed: bad zone file".to_string(),
+        ]
+    );
+    assert_eq!(txt_data[1][0], "test.com.");
+    assert_eq!(txt_data[1][1], "Healthy");
+    assert_eq!(txt_data[1][2], format!("serial {}", serial));
+    assert!(txt_data[1][3].starts_with("refreshed "));
+
+    // the same in the text of the control channel
+    let health = catalog.health();
+    let test_health = health.iter().find(|h| h.zone() == &origin).unwrap();
+    assert!(test_health.to_string().starts_with(
+        &format!("test.com. Healthy serial {} refreshed ", serial),
+    ));
+
+    // the health query is only for the CHAOS class
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(health_name.clone());
+    query.set_query_type(RecordType::TXT);
+    question.add_query(query);
+    let result: Message = catalog.lookup(&question);
+    assert_eq!(result.response_code(), ResponseCode::NXDomain);
+    assert!(result.answers().is_empty());
+}
+
+#[test]
+fn test_catalog_query_count() {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    // no queries
+    let question: Message = Message::new();
+    let result: Message = catalog.lookup(&question);
+    assert_eq!(result.response_code(), ResponseCode::FormErr);
+    assert!(result.answers().is_empty());
+
+    // more than one query, even for names which exist
+    let mut question: Message = Message::new();
+    let mut query: Query = Query::new();
+    query.set_name(origin.clone());
+    question.add_query(query.clone());
+    query.set_query_type(RecordType::SOA);
+    question.add_query(query);
+
+    let result: Message = catalog.lookup(&question);
+    assert_eq!(result.response_code(), ResponseCode::FormErr);
+    assert!(result.answers().is_empty());
+    assert_eq!(result.queries().len(), 2);
+}
+
+#[test]
+fn test_catalog_forward_update() {
+    use std::io::{Read, Write};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use futures::Future;
+    use tokio_core::reactor::{Core, Timeout};
+    use trust_dns::rr::dnssec::{TSigner, TsigAlgorithm};
+
+    let secondary_origin = Name::parse("secondary.com.", None).unwrap();
+    let mut secondary = Authority::new(
+        secondary_origin.clone(),
+        BTreeMap::new(),
+        ZoneType::Slave,
+        false,
+        false,
+    );
+    secondary.upsert(
+        Record::new()
+            .set_name(secondary_origin.clone())
+            .set_ttl(3600)
+            .set_rr_type(RecordType::SOA)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::SOA(SOA::new(
+                Name::parse("sns.dns.icann.org.", None).unwrap(),
+                Name::parse("noc.dns.icann.org.", None).unwrap(),
+                2017100101,
+                7200,
+                3600,
+                1209600,
+                3600,
+            )))
+            .clone(),
+        0,
+    );
+
+    // a primary which answers a single update over TCP
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let primary = listener.local_addr().unwrap();
+    let primary_thread = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len).unwrap();
+        let mut buffer = vec![0u8; ((len[0] as usize) << 8) | len[1] as usize];
+        stream.read_exact(&mut buffer).unwrap();
+        let update = Message::from_vec(&buffer).unwrap();
+
+        let mut response = Message::new();
+        response.set_id(update.id());
+        response.set_op_code(OpCode::Update);
+        response.set_message_type(MessageType::Response);
+        response.set_response_code(ResponseCode::NoError);
+        let buffer = response.to_vec().unwrap();
+        stream
+            .write_all(&[(buffer.len() >> 8) as u8, buffer.len() as u8])
+            .unwrap();
+        stream.write_all(&buffer).unwrap();
+
+        update
+    });
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(secondary_origin.clone(), secondary);
+    catalog.set_primary(secondary_origin.clone(), primary);
+
+    let mut update: Message = Message::new();
+    update.set_id(1234);
+    update.set_op_code(OpCode::Update);
+    let mut zone = Query::new();
+    zone.set_name(secondary_origin.clone());
+    zone.set_query_type(RecordType::SOA);
+    update.add_zone(zone);
+    update.add_update(
+        Record::new()
+            .set_name(Name::parse("new.secondary.com.", None).unwrap())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)))
+            .clone(),
+    );
+
+    // unsigned updates are not forwarded
+    let result = catalog.update(&update);
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+    assert_eq!(result.id(), 1234);
+
+    // the signature is only verified by the primary, the EDNS carries the extended errors
+    update.set_edns(Edns::new());
+    let tsigner = TSigner::new(
+        Name::parse("update-key.secondary.com.", None).unwrap(),
+        TsigAlgorithm::HmacSha256,
+        b"a secret shared by requestor and primary".to_vec(),
+        300,
+    );
+    update.finalize(&tsigner, 0).unwrap();
+
+    let result = catalog.update(&update);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.id(), 1234);
+
+    // the primary received the update as sent to the secondary
+    let forwarded = primary_thread.join().unwrap();
+    assert_eq!(forwarded.id(), 1234);
+    assert_eq!(forwarded.zones()[0].name(), &secondary_origin);
+    assert_eq!(forwarded.updates(), update.updates());
+
+    // no primary listening
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let unreachable = listener.local_addr().unwrap();
+    drop(listener);
+    catalog.set_primary(secondary_origin.clone(), unreachable);
+
+    let result = catalog.update(&update);
+    assert_eq!(result.response_code(), ResponseCode::ServFail);
+    assert_eq!(result.id(), 1234);
+
+    // the server waits on the primary on its IO loop, a primary which only answers once a timer
+    //  on the loop has fired shows that the loop is not blocked
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let primary = listener.local_addr().unwrap();
+    let (timer_fired, fired) = mpsc::channel();
+    let primary_thread = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len).unwrap();
+        let mut buffer = vec![0u8; ((len[0] as usize) << 8) | len[1] as usize];
+        stream.read_exact(&mut buffer).unwrap();
+        let update = Message::from_vec(&buffer).unwrap();
+        let was_fired = fired.recv_timeout(Duration::from_secs(5)).is_ok();
+
+        let mut response = Message::new();
+        response.set_id(update.id());
+        response.set_op_code(OpCode::Update);
+        response.set_message_type(MessageType::Response);
+        response.set_response_code(ResponseCode::Refused);
+        let buffer = response.to_vec().unwrap();
+        stream
+            .write_all(&[(buffer.len() >> 8) as u8, buffer.len() as u8])
+            .unwrap();
+        stream.write_all(&buffer).unwrap();
+
+        was_fired
+    });
+    catalog.set_primary(secondary_origin.clone(), primary);
+
+    let mut core = Core::new().unwrap();
+    let handle = core.handle();
+    let request = Request {
+        message: update.clone(),
+        src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+    };
+    let forwarded = catalog.forward_request(&request, &handle).expect(
+        "update to a secondary is forwarded",
+    );
+    let timer = Timeout::new(Duration::from_millis(10), &handle)
+        .unwrap()
+        .map(move |_| timer_fired.send(()).unwrap());
+    let (result, _) = core.run(forwarded.join(timer)).unwrap();
+
+    // the primary's response is returned as it is
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+    assert_eq!(result.id(), 1234);
+    assert!(primary_thread.join().unwrap());
+
+    // the updates past the 32 being forwarded at once are answered with ServFail, a forward is
+    //  in use from the moment its future is returned
+    let forwards = (0..32)
+        .map(|_| catalog.forward_request(&request, &handle).unwrap())
+        .collect::<Vec<_>>();
+    let result = core.run(catalog.forward_request(&request, &handle).unwrap()).unwrap();
+    assert_eq!(result.response_code(), ResponseCode::ServFail);
+    assert_eq!(result.id(), 1234);
+    assert_eq!(
+        result.edns().unwrap().extended_error().unwrap().info_code(),
+        ExtendedErrorCode::Other
+    );
+
+    // and released once they are dropped
+    drop(forwards);
+    let result = core.run(catalog.forward_request(&request, &handle).unwrap()).unwrap();
+    assert_eq!(result.response_code(), ResponseCode::ServFail);
+    assert_eq!(
+        result.edns().unwrap().extended_error().unwrap().info_code(),
+        ExtendedErrorCode::NetworkError
+    );
+
+    // queries are answered by the catalog itself
+    let mut query = update.clone();
+    query.set_op_code(OpCode::Query);
+    let request = Request {
+        message: query,
+        src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+    };
+    assert!(catalog.forward_request(&request, &handle).is_none());
+}
+
+#[test]
+fn test_catalog_response_policy() {
+    let mut test = create_test();
+    let origin = test.origin().clone();
+    for txt in vec!["v=spf1 -all", "google-site-verification=abc123"] {
+        test.upsert(
+            Record::from_rdata(
+                origin.clone(),
+                3600,
+                RecordType::TXT,
+                RData::TXT(TXT::new(vec![txt.to_string()])),
+            ),
+            0,
+        );
+    }
+    test.upsert(
+        Record::from_rdata(
+            Name::parse("verify.test.com.", None).unwrap(),
+            3600,
+            RecordType::TXT,
+            RData::TXT(TXT::new(vec!["google-site-verification=def456".to_string()])),
+        ),
+        0,
+    );
+
+    let mut policy = ResponsePolicy::new();
+    policy.set_max_ttl(Some(300));
+    policy.set_refuse_any(true);
+    policy.hide_txt("^google-site-verification=").unwrap();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+    catalog.set_response_policy(origin.clone(), policy);
+
+    let lookup = |name: &str, query_type: RecordType| {
+        let mut question: Message = Message::new();
+        let mut query: Query = Query::new();
+        query.set_name(Name::parse(name, None).unwrap()).set_query_type(query_type);
+        question.add_query(query);
+        catalog.lookup(&question)
+    };
+
+    // the TTLs are clamped, in the answers and the authority section
+    let result = lookup("www.test.com.", RecordType::A);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().first().unwrap().ttl(), 300);
+    assert!(result.name_servers().iter().all(|ns| ns.ttl() == 300));
+
+    // only the matching TXT records are hidden
+    let result = lookup("test.com.", RecordType::TXT);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), 1);
+    assert_eq!(
+        result.answers().first().unwrap().rdata(),
+        &RData::TXT(TXT::new(vec!["v=spf1 -all".to_string()]))
+    );
+
+    // a name with only hidden records still exists
+    let result = lookup("verify.test.com.", RecordType::TXT);
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.answers().is_empty());
+    assert_eq!(
+        result.name_servers().first().unwrap().rr_type(),
+        RecordType::SOA
+    );
+
+    let result = lookup("test.com.", RecordType::ANY);
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+    assert!(result.answers().is_empty());
+}
+
+#[test]
+fn test_catalog_add_remove_zone() {
+    let test = create_test();
+    let origin = test.origin().clone();
+    let example = create_example();
+    let example_origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+
+    let lookup = |catalog: &Catalog, name: &str| {
+        let mut question: Message = Message::new();
+        let mut query: Query = Query::new();
+        query.set_name(Name::parse(name, None).unwrap());
+        question.add_query(query);
+        catalog.lookup(&question)
+    };
+
+    assert_eq!(
+        lookup(&catalog, "www.example.com.").response_code(),
+        ResponseCode::NXDomain
+    );
+
+    // zones are added and removed through a shared reference, as the server holds the catalog
+    assert!(catalog.add_zone(example_origin.clone(), create_example()));
+    assert!(!catalog.add_zone(example_origin.clone(), example));
+    let mut names = catalog.zone_names();
+    names.sort();
+    assert_eq!(names, vec![example_origin.clone(), origin.clone()]);
+
+    let result = lookup(&catalog, "www.example.com.");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(!result.answers().is_empty());
+
+    assert!(catalog.remove_zone(&example_origin));
+    assert!(!catalog.remove_zone(&example_origin));
+    assert_eq!(catalog.zone_names(), vec![origin.clone()]);
+    assert_eq!(
+        lookup(&catalog, "www.example.com.").response_code(),
+        ResponseCode::NXDomain
+    );
+
+    // the other zones are unaffected
+    let result = lookup(&catalog, "www.test.com.");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+}
+
+#[test]
+fn test_catalog_client_subnet() {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let request = |option: EdnsOption| {
+        let mut query: Query = Query::new();
+        query.set_name(Name::parse("www.example.com.", None).unwrap());
+
+        let mut message: Message = Message::new();
+        message.add_query(query);
+        message.edns_mut().set_option(option);
+
+        Request {
+            message: message,
+            src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+        }
+    };
+
+    // the subnet is echoed with a scope of 0, the answer is the same for all clients
+    let subnet = ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)), 24, 0);
+    let response = catalog.handle_request(&request(EdnsOption::Subnet(subnet)));
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.answers().is_empty());
+    assert_eq!(
+        response.edns().and_then(|edns| edns.client_subnet()),
+        Some(&subnet)
+    );
+
+    // the address is longer than the source prefix
+    let malformed = EdnsOption::Unknown(8, vec![0, 1, 24, 0, 192, 0, 2, 1]);
+    let response = catalog.handle_request(&request(malformed));
+    assert_eq!(response.response_code(), ResponseCode::FormErr);
+    assert_eq!(response.message_type(), MessageType::Response);
+    assert_eq!(response.queries().len(), 1);
+    assert_eq!(
+        response.edns().and_then(|edns| edns.option(&EdnsCode::Subnet)),
+        None
+    );
+}
+
+#[test]
+fn test_catalog_cpu_intensive() {
+    use chrono::Duration;
+    use trust_dns::rr::dnssec::{Algorithm, KeyPair, Signer, TSigner, TsigAlgorithm};
+
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut nsec3 = create_test();
+    let nsec3_origin = nsec3.origin().clone();
+    let key = KeyPair::generate(Algorithm::ECDSAP256SHA256).unwrap();
+    let dnskey = key.to_dnskey(Algorithm::ECDSAP256SHA256).unwrap();
+    let signer = Signer::dnssec(dnskey, key, nsec3_origin.clone(), Duration::weeks(1));
+    nsec3.add_secure_key(signer).unwrap();
+    nsec3.set_denial(Denial::Nsec3 {
+        iterations: 0,
+        salt: vec![],
+        opt_out: false,
+    });
+    nsec3.secure_zone().unwrap();
+    assert!(nsec3.is_nsec3());
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+    catalog.upsert(nsec3_origin.clone(), nsec3);
+
+    let request = |name: &Name, query_type: RecordType, dnssec_ok: bool| {
+        let mut query: Query = Query::new();
+        query.set_name(name.clone()).set_query_type(query_type);
+
+        let mut message: Message = Message::new();
+        message.add_query(query);
+        if dnssec_ok {
+            message.edns_mut().set_dnssec_ok(true);
+        }
+
+        Request {
+            message: message,
+            src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+        }
+    };
+
+    // the messages of a transfer are only read from the zone, see `response_stream`
+    assert!(!catalog.is_cpu_intensive(&request(&origin, RecordType::A, false)));
+    assert!(!catalog.is_cpu_intensive(&request(&origin, RecordType::AXFR, false)));
+
+    // the denial of existence in zones with an NSEC3 chain hashes names
+    assert!(!catalog.is_cpu_intensive(&request(&origin, RecordType::A, true)));
+    assert!(!catalog.is_cpu_intensive(&request(&nsec3_origin, RecordType::A, false)));
+    assert!(catalog.is_cpu_intensive(&request(&nsec3_origin, RecordType::A, true)));
+
+    // TSIG signed requests are verified
+    let tsigner = TSigner::new(
+        Name::parse("key.example.com.", None).unwrap(),
+        TsigAlgorithm::HmacSha256,
+        b"a secret shared by client and server".to_vec(),
+        300,
+    );
+    let mut signed = request(&origin, RecordType::A, false);
+    signed.message.finalize(&tsigner, 0).unwrap();
+    let signed = Request {
+        message: Message::from_vec(&signed.message.to_vec().unwrap()).unwrap(),
+        src: signed.src,
+    };
+    assert!(catalog.is_cpu_intensive(&signed));
+
+    // and so is each message of their transfers signed
+    let mut signed = request(&origin, RecordType::AXFR, false);
+    signed.message.finalize(&tsigner, 0).unwrap();
+    let signed = Request {
+        message: Message::from_vec(&signed.message.to_vec().unwrap()).unwrap(),
+        src: signed.src,
+    };
+    assert!(catalog.is_cpu_intensive(&signed));
+}
+
+#[test]
+fn test_catalog_cookies() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use trust_dns::rr::dnssec::{TSigner, TsigAlgorithm};
+
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let request = |cookie: Cookie, ip: Ipv4Addr| {
+        let mut query: Query = Query::new();
+        query.set_name(Name::parse("www.example.com.", None).unwrap());
+
+        let mut message: Message = Message::new();
+        message.add_query(query);
+        message.edns_mut().set_option(EdnsOption::Cookie(cookie));
+
+        Request {
+            message: message,
+            src: SocketAddr::new(IpAddr::V4(ip), 53),
+        }
+    };
+    let client = [1, 2, 3, 4, 5, 6, 7, 8];
+    let localhost = Ipv4Addr::new(127, 0, 0, 1);
+
+    // the first query only has a client cookie, it is answered along with a server cookie
+    let response = catalog.handle_request(&request(Cookie::new(client, None), localhost));
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.answers().is_empty());
+    let cookie = response.edns().and_then(|edns| edns.cookie()).cloned().unwrap();
+    assert_eq!(cookie.client(), &client);
+    assert_eq!(cookie.server().map(|server| server.len()), Some(8));
+
+    // the same client gets the same server cookie
+    let response = catalog.handle_request(&request(cookie.clone(), localhost));
+    assert_eq!(response.edns().and_then(|edns| edns.cookie()), Some(&cookie));
+
+    catalog.set_require_cookies(true);
+    let response = catalog.handle_request(&request(cookie.clone(), localhost));
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.answers().is_empty());
+
+    // the server cookie is bound to the address of the client, the response echoes the question
+    //  for the client to match it to the request, RFC 7873 section 5.3
+    let elsewhere = request(cookie.clone(), Ipv4Addr::new(192, 0, 2, 1));
+    let response = catalog.handle_request(&elsewhere);
+    assert_eq!(response.response_code(), ResponseCode::BADCOOKIE);
+    assert_eq!(response.message_type(), MessageType::Response);
+    assert_eq!(response.op_code(), OpCode::Query);
+    assert_eq!(response.queries(), elsewhere.message.queries());
+    assert!(response.answers().is_empty());
+    let fresh = response.edns().and_then(|edns| edns.cookie()).cloned().unwrap();
+    assert_eq!(fresh.client(), &client);
+    assert!(fresh.server().is_some());
+    assert!(fresh != cookie);
+
+    let response = catalog.handle_request(&request(Cookie::new(client, None), localhost));
+    assert_eq!(response.response_code(), ResponseCode::BADCOOKIE);
+
+    // a response to a signed request is signed, as are the other responses
+    let tsigner = TSigner::new(
+        Name::parse("key.example.com.", None).unwrap(),
+        TsigAlgorithm::HmacSha256,
+        b"a secret shared by client and server".to_vec(),
+        300,
+    );
+    catalog.add_tsig_key(tsigner.clone());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    let mut sent = request(Cookie::new(client, None), localhost).message;
+    sent.finalize(&tsigner, now).unwrap();
+    let signed = Request {
+        message: Message::from_vec(&sent.to_vec().unwrap()).unwrap(),
+        src: SocketAddr::new(IpAddr::V4(localhost), 53),
+    };
+    let response = catalog.handle_request(&signed);
+    let response = Message::from_vec(&response.to_vec().unwrap()).unwrap();
+    assert_eq!(response.response_code(), ResponseCode::BADCOOKIE);
+    assert_eq!(response.queries(), sent.queries());
+    assert!(tsigner.verify_response(sent.sig0(), &response, now).is_ok());
+
+    // a malformed cookie is a FormErr, which also echoes the question
+    let mut malformed = request(Cookie::new(client, None), localhost);
+    malformed.message.edns_mut().set_option(EdnsOption::Unknown(10, vec![1, 2, 3]));
+    let response = catalog.handle_request(&malformed);
+    assert_eq!(response.response_code(), ResponseCode::FormErr);
+    assert_eq!(response.message_type(), MessageType::Response);
+    assert_eq!(response.queries(), malformed.message.queries());
+}
+
+#[test]
+fn test_catalog_extended_error() {
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    let mut policy = ResponsePolicy::new();
+    policy.set_refuse_any(true);
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+    catalog.set_response_policy(origin.clone(), policy);
+
+    let lookup = |name: &str, query_type: RecordType, edns: bool| {
+        let mut question: Message = Message::new();
+        let mut query: Query = Query::new();
+        query.set_name(Name::parse(name, None).unwrap()).set_query_type(query_type);
+        question.add_query(query);
+        if edns {
+            question.set_edns(Edns::new());
+        }
+        catalog.lookup(&question)
+    };
+    let info_code = |response: &Message| {
+        response
+            .edns()
+            .and_then(|edns| edns.extended_error())
+            .map(|extended_error| extended_error.info_code())
+    };
+
+    let response = lookup("www.example.com.", RecordType::ANY, true);
+    assert_eq!(response.response_code(), ResponseCode::Refused);
+    assert_eq!(info_code(&response), Some(ExtendedErrorCode::NotSupported));
+
+    let response = lookup("www.example.net.", RecordType::A, true);
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    assert_eq!(info_code(&response), Some(ExtendedErrorCode::NotAuthoritative));
+
+    // the option is only sent to clients which sent EDNS
+    let response = lookup("www.example.net.", RecordType::A, false);
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    assert!(response.edns().is_none());
+}
+
+#[test]
+fn test_catalog_secondary() {
+    // the example zone, and the test zone with an expire interval which has already passed
+    let secondary = |authority: Authority, expire: Option<i32>| {
+        let origin = authority.origin().clone();
+        let records = authority
+            .records()
+            .iter()
+            .flat_map(|(_, rrset)| rrset.iter().cloned())
+            .map(|mut record| {
+                let soa = match *record.rdata() {
+                    RData::SOA(ref soa) => Some(SOA::new(
+                        soa.mname().clone(),
+                        soa.rname().clone(),
+                        soa.serial(),
+                        soa.refresh(),
+                        soa.retry(),
+                        expire.unwrap_or(soa.expire()),
+                        soa.minimum(),
+                    )),
+                    _ => None,
+                };
+                if let Some(soa) = soa {
+                    record.set_rdata(RData::SOA(soa));
+                }
+                record
+            })
+            .collect::<Vec<_>>();
+
+        let mut secondary =
+            Authority::new(origin.clone(), BTreeMap::new(), ZoneType::Slave, false, false);
+        secondary.replace_records(records);
+        (origin, secondary)
+    };
+    let (fresh, transferred) = secondary(create_example(), None);
+    let (expired, expiring) = secondary(create_test(), Some(0));
+    let untransferred = Name::parse("example.net.", None).unwrap();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(
+        untransferred.clone(),
+        Authority::new(
+            untransferred.clone(),
+            BTreeMap::new(),
+            ZoneType::Slave,
+            false,
+            false,
+        ),
+    );
+    catalog.upsert(expired.clone(), expiring);
+    catalog.upsert(fresh.clone(), transferred);
+
+    let lookup = |name: &Name| {
+        let mut question: Message = Message::new();
+        let mut query: Query = Query::new();
+        query.set_name(name.clone()).set_query_type(RecordType::SOA);
+        question.add_query(query);
+        question.set_edns(Edns::new());
+        catalog.lookup(&question)
+    };
+    let info_code = |response: &Message| {
+        response
+            .edns()
+            .and_then(|edns| edns.extended_error())
+            .map(|extended_error| extended_error.info_code())
+    };
+
+    // neither a zone which was never transferred, nor one which expired, is served
+    for name in &[untransferred.clone(), expired.clone()] {
+        let response = lookup(name);
+        assert_eq!(response.response_code(), ResponseCode::ServFail);
+        assert_eq!(info_code(&response), Some(ExtendedErrorCode::NotReady));
+    }
+
+    let response = lookup(&fresh);
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response.answers().len(), 1);
+
+    // only the zone without an SOA is due, the others are within their refresh interval
+    assert_eq!(catalog.refresh_secondaries(), vec![untransferred]);
+}
+
+#[test]
+fn test_catalog_tsig() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use trust_dns::rr::dnssec::{TSigner, TsigAlgorithm};
+    use trust_dns::rr::rdata::tsig::{BADKEY, BADSIG, BADTIME};
+    use trust_dns_proto::error::ProtoErrorKind;
+
+    let key_name = Name::parse("update-key.example.com.", None).unwrap();
+    let tsigner = TSigner::new(
+        key_name.clone(),
+        TsigAlgorithm::HmacSha256,
+        b"a secret shared by client and server".to_vec(),
+        300,
+    );
+
+    let mut example = create_example();
+    let origin = example.origin().clone();
+    example.set_allow_update(true);
+    example.add_update_tsig_key(key_name.clone());
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+    catalog.add_tsig_key(tsigner.clone());
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    let signed_request = |message: &Message, signer: &TSigner, time: u32| {
+        let mut message = message.clone();
+        message.finalize(signer, time).unwrap();
+        let request = Request {
+            message: Message::from_vec(&message.to_vec().unwrap()).unwrap(),
+            src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+        };
+        (message, request)
+    };
+    let tsig_of = |response: &Message| match *response.tsig().unwrap().rdata() {
+        RData::TSIG(ref tsig) => tsig.clone(),
+        _ => panic!("not a TSIG"),
+    };
+
+    let mut query: Message = Message::new();
+    query.set_id(1234);
+    query.add_query(Query::query(origin.clone(), RecordType::AXFR));
+
+    // the response is signed with the key of the request
+    let (sent, request) = signed_request(&query, &tsigner, now);
+    let response = catalog.handle_request(&request);
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(!response.answers().is_empty());
+    let response = Message::from_vec(&response.to_vec().unwrap()).unwrap();
+    assert!(tsigner.verify_response(sent.sig0(), &response, now).is_ok());
+
+    // an unknown key
+    let unknown = TSigner::new(
+        Name::parse("other-key.", None).unwrap(),
+        TsigAlgorithm::HmacSha256,
+        b"a secret shared by client and server".to_vec(),
+        300,
+    );
+    let (_, request) = signed_request(&query, &unknown, now);
+    let response = catalog.handle_request(&request);
+    assert_eq!(response.response_code(), ResponseCode::NotAuth);
+    assert!(response.answers().is_empty());
+    assert_eq!(tsig_of(&response).error(), BADKEY);
+    assert!(tsig_of(&response).mac().is_empty());
+
+    // a bad MAC
+    let forged = TSigner::new(
+        key_name.clone(),
+        TsigAlgorithm::HmacSha256,
+        b"a guessed secret".to_vec(),
+        300,
+    );
+    let (_, request) = signed_request(&query, &forged, now);
+    let response = catalog.handle_request(&request);
+    assert_eq!(response.response_code(), ResponseCode::NotAuth);
+    assert_eq!(tsig_of(&response).error(), BADSIG);
+
+    // a client with a wrong clock gets the time of the server in a signed response
+    let skewed = TSigner::new(
+        key_name.clone(),
+        TsigAlgorithm::HmacSha256,
+        b"a secret shared by client and server".to_vec(),
+        300,
+    );
+    let (sent, request) = signed_request(&query, &skewed, now - 3600);
+    let response = catalog.handle_request(&request);
+    assert_eq!(response.response_code(), ResponseCode::NotAuth);
+    assert_eq!(tsig_of(&response).error(), BADTIME);
+    assert_eq!(tsig_of(&response).other().len(), 6);
+    let response = Message::from_vec(&response.to_vec().unwrap()).unwrap();
+    match *skewed
+        .verify_response(sent.sig0(), &response, now - 3600)
+        .unwrap_err()
+        .kind() {
+        ProtoErrorKind::TsigClockSkew(skew) => assert!(skew >= 3600 && skew < 3602),
+        ref kind => panic!("expected the clock skew: {:?}", kind),
+    }
+
+    // which it signs again with
+    let (_, request) = signed_request(&query, &skewed, now - 3600);
+    let response = catalog.handle_request(&request);
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+
+    // updates are authorized by the key
+    let mut update: Message = Message::new();
+    update.set_id(4321);
+    update.set_op_code(OpCode::Update);
+    update.add_zone(Query::query(origin.clone(), RecordType::SOA));
+    update.add_update(
+        Record::new()
+            .set_name(Name::parse("new.example.com.", None).unwrap())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)))
+            .clone(),
+    );
+
+    let (_, request) = signed_request(&update, &tsigner, now);
+    let response = catalog.handle_request(&request);
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert!(response.tsig().is_some());
+
+    // an unsigned update is refused
+    let request = Request {
+        message: update.clone(),
+        src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+    };
+    let response = catalog.handle_request(&request);
+    assert_eq!(response.response_code(), ResponseCode::Refused);
+}
+
+/// Records the zone, serial and number of changed RRsets of each call
+#[derive(Default)]
+struct ChangeRecorder(Mutex<Vec<(Name, u32, usize)>>);
+
+impl ChangeHook for ChangeRecorder {
+    fn on_records_changed(&self, origin: &Name, serial: u32, changes: &[RRsetChange]) {
+        self.0.lock().unwrap().push((origin.clone(), serial, changes.len()));
+    }
+}
+
+#[test]
+fn test_catalog_change_hook() {
+    let test = create_test();
+    let origin = test.origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+
+    // the hook applies to the zones already served and those added later
+    let recorder = Arc::new(ChangeRecorder::default());
+    catalog.add_change_hook(recorder.clone());
+    let example = create_example();
+    let example_origin = example.origin().clone();
+    assert!(catalog.add_zone(example_origin.clone(), example));
+
+    // a replaced zone reports the difference, the hook carries over to the replacement
+    let mut replacement = create_test();
+    replacement.upsert(
+        Record::new()
+            .set_name(Name::parse("new.test.com.", None).unwrap())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(10, 0, 0, 1)))
+            .clone(),
+        0,
+    );
+    replacement.advance_serial(2015082403);
+    assert!(catalog.replace(&origin, replacement));
+
+    // the same records again is not a change
+    let mut replacement = create_test();
+    replacement.upsert(
+        Record::new()
+            .set_name(Name::parse("new.test.com.", None).unwrap())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(10, 0, 0, 1)))
+            .clone(),
+        0,
+    );
+    replacement.advance_serial(2015082403);
+    assert!(catalog.replace(&origin, replacement));
+
+    // a replacement of the added zone
+    let mut replacement = create_example();
+    replacement.advance_serial(2015082403);
+    assert!(catalog.replace(&example_origin, replacement));
+
+    let calls = recorder.0.lock().unwrap();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0], (origin, 2015082404, 2));
+    assert_eq!(calls[1].0, example_origin);
+    assert_eq!(calls[1].2, 1);
+}
+
+/// Returns a reverse zone with only its SOA, for the PTRs generated from a forward zone
+fn create_reverse(origin: &Name, serial: u32) -> Authority {
+    let mut reverse = Authority::new(
+        origin.clone(),
+        BTreeMap::new(),
+        ZoneType::Master,
+        false,
+        false,
+    );
+    reverse.upsert(
+        Record::new()
+            .set_name(origin.clone())
+            .set_ttl(3600)
+            .set_rr_type(RecordType::SOA)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::SOA(SOA::new(
+                Name::parse("sns.dns.icann.org.", None).unwrap(),
+                Name::parse("noc.dns.icann.org.", None).unwrap(),
+                serial,
+                7200,
+                3600,
+                1209600,
+                3600,
+            )))
+            .clone(),
+        0,
+    );
+    reverse
+}
+
+#[test]
+fn test_catalog_reverse_zone() {
+    let test = create_test();
+    let origin = test.origin().clone();
+    let reverse_origin = Name::parse("216.184.94.in-addr.arpa.", None).unwrap();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), test);
+    catalog.upsert(reverse_origin.clone(), create_reverse(&reverse_origin, 1));
+    catalog.set_reverse_zone(reverse_origin.clone(), vec![origin.clone()]);
+    assert_eq!(catalog.serial(&reverse_origin), Some(2));
+
+    let ptrs = |catalog: &Catalog, address: Ipv4Addr| -> Vec<RData> {
+        let mut question: Message = Message::new();
+        let mut query: Query = Query::new();
+        query.set_name(Name::from(address)).set_query_type(
+            RecordType::PTR,
+        );
+        question.add_query(query);
+
+        let mut ptrs = catalog
+            .lookup(&question)
+            .answers()
+            .iter()
+            .map(|record| record.rdata().clone())
+            .collect::<Vec<_>>();
+        ptrs.sort();
+        ptrs
+    };
+
+    // the PTRs of the A records of the forward zone, its AAAA records are not in the reverse zone
+    let address = Ipv4Addr::new(94, 184, 216, 34);
+    let mut expected = vec![
+        RData::PTR(origin.clone()),
+        RData::PTR(Name::parse("www.test.com.", None).unwrap()),
+    ];
+    expected.sort();
+    assert_eq!(ptrs(&catalog, address), expected);
+
+    // a replaced forward zone is followed by the reverse zone
+    let new_name = Name::parse("new.test.com.", None).unwrap();
+    let new_address = Ipv4Addr::new(94, 184, 216, 35);
+    let mut replacement = create_test();
+    replacement.upsert(
+        Record::new()
+            .set_name(new_name.clone())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(new_address))
+            .clone(),
+        0,
+    );
+    replacement.advance_serial(2015082403);
+    assert!(catalog.replace(&origin, replacement));
+    assert_eq!(ptrs(&catalog, new_address), vec![RData::PTR(new_name.clone())]);
+    assert_eq!(catalog.serial(&reverse_origin), Some(3));
+
+    // the PTRs of the records which are gone are deleted
+    let mut replacement = create_test();
+    replacement.advance_serial(2015082404);
+    assert!(catalog.replace(&origin, replacement));
+    assert!(ptrs(&catalog, new_address).is_empty());
+    assert_eq!(ptrs(&catalog, address).len(), 2);
+    assert_eq!(catalog.serial(&reverse_origin), Some(4));
+
+    // a reloaded reverse zone gets the PTRs again
+    assert!(catalog.replace(&reverse_origin, create_reverse(&reverse_origin, 10)));
+    assert_eq!(ptrs(&catalog, address).len(), 2);
+    assert_eq!(catalog.serial(&reverse_origin), Some(11));
+
+    // and without the forward zone there are none
+    assert!(catalog.remove_zone(&origin));
+    assert!(ptrs(&catalog, address).is_empty());
+}
+
+/// The example zone, with a thousand more hosts, and the request for its transfer
+fn large_zone_transfer() -> (Catalog, Message) {
+    let mut example = create_example();
+    let origin = example.origin().clone();
+    let serial = example.serial();
+    for i in 0..1000 {
+        let name = Name::parse(&format!("host-{}", i), Some(&origin)).unwrap();
+        example.upsert(
+            Record::new()
+                .set_name(name)
+                .set_ttl(86400)
+                .set_rr_type(RecordType::A)
+                .set_dns_class(DNSClass::IN)
+                .set_rdata(RData::A(Ipv4Addr::new(10, 0, (i / 256) as u8, i as u8)))
+                .clone(),
+            serial,
+        );
+    }
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let mut query: Message = Message::new();
+    query.set_id(1234);
+    query.add_query(Query::query(origin, RecordType::AXFR));
+
+    (catalog, query)
+}
+
+#[test]
+fn test_axfr_stream() {
+    let (catalog, query) = large_zone_transfer();
+    let request = Request {
+        message: query,
+        src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+    };
+
+    let mut messages: Vec<Message> = Vec::new();
+    catalog
+        .handle_request_stream(&request, &mut |message| {
+            messages.push(message);
+            Ok(())
+        })
+        .unwrap();
+    assert!(messages.len() > 1);
+
+    // only the first message has the question
+    for (i, message) in messages.iter().enumerate() {
+        assert_eq!(message.id(), 1234);
+        assert_eq!(message.response_code(), ResponseCode::NoError);
+        assert!(message.authoritative());
+        assert_eq!(message.queries().len(), if i == 0 { 1 } else { 0 });
+        assert!(message.to_vec().unwrap().len() < u16::max_value() as usize);
+    }
+
+    // the records are those of the transfer in one message, from SOA to SOA
+    let streamed: Vec<Record> = messages
+        .iter()
+        .flat_map(|message| message.answers().iter().cloned())
+        .collect();
+    let response = catalog.handle_request(&request);
+    assert_eq!(streamed, response.answers().to_vec());
+    assert_eq!(streamed.first().unwrap().rr_type(), RecordType::SOA);
+    assert_eq!(streamed.last().unwrap().rr_type(), RecordType::SOA);
+    assert!(streamed.len() > 1000);
+
+    // other queries are answered in one message
+    let mut query: Message = Message::new();
+    query.add_query(Query::query(
+        Name::parse("host-1.example.com.", None).unwrap(),
+        RecordType::A,
+    ));
+    let request = Request {
+        message: query,
+        src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+    };
+    let mut count = 0;
+    catalog
+        .handle_request_stream(&request, &mut |message| {
+            assert_eq!(message.answers().len(), 1);
+            count += 1;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn test_axfr_stream_tsig() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use futures::Stream;
+    use trust_dns::rr::dnssec::{TSigner, TsigAlgorithm};
+
+    let tsigner = TSigner::new(
+        Name::parse("transfer-key.example.com.", None).unwrap(),
+        TsigAlgorithm::HmacSha256,
+        b"a secret shared by client and server".to_vec(),
+        300,
+    );
+    let (mut catalog, query) = large_zone_transfer();
+    catalog.add_tsig_key(tsigner.clone());
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    let mut sent = query.clone();
+    sent.finalize(&tsigner, now).unwrap();
+    let request = Request {
+        message: Message::from_vec(&sent.to_vec().unwrap()).unwrap(),
+        src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+    };
+
+    let mut messages: Vec<Message> = Vec::new();
+    catalog
+        .handle_request_stream(&request, &mut |message| {
+            messages.push(Message::from_vec(&message.to_vec().unwrap()).unwrap());
+            Ok(())
+        })
+        .unwrap();
+    assert!(messages.len() > 1);
+
+    // the first message covers the MAC of the request, each one after it the MAC before it
+    assert!(tsigner.verify_response(sent.sig0(), &messages[0], now).is_ok());
+    let mut prior_mac = match *messages[0].tsig().unwrap().rdata() {
+        RData::TSIG(ref tsig) => tsig.mac().to_vec(),
+        _ => panic!("not a TSIG"),
+    };
+    for message in &messages[1..] {
+        let mac = tsigner
+            .verify_message_continued(message, &prior_mac, now as u64)
+            .unwrap()
+            .mac()
+            .to_vec();
+        prior_mac = mac;
+    }
+
+    // a transfer with an unknown key is answered by the stream too, with the one NotAuth
+    let other = TSigner::new(
+        Name::parse("other-key.example.com.", None).unwrap(),
+        TsigAlgorithm::HmacSha256,
+        b"a secret the server does not know".to_vec(),
+        300,
+    );
+    let mut sent = query.clone();
+    sent.finalize(&other, now).unwrap();
+    let request = Request {
+        message: Message::from_vec(&sent.to_vec().unwrap()).unwrap(),
+        src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+    };
+    let messages: Vec<Message> = catalog
+        .response_stream(&request)
+        .expect("the transfer is streamed")
+        .wait()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].response_code(), ResponseCode::NotAuth);
+}
+
+#[test]
+fn test_axfr_stream_zone_changed() {
+    use futures::Stream;
+
+    let (catalog, query) = large_zone_transfer();
+    let origin = query.queries()[0].name().clone();
+    let src = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53);
+    let request = Request {
+        message: query,
+        src: src,
+    };
+
+    // each message is read from the zone as it is taken
+    let mut messages = catalog
+        .response_stream(&request)
+        .expect("the transfer is streamed")
+        .wait();
+    let first = messages.next().unwrap().unwrap();
+    assert_eq!(first.response_code(), ResponseCode::NoError);
+    assert_eq!(first.queries().len(), 1);
+
+    // the rest of the transfer would be of another version of the zone
+    let mut replacement = create_example();
+    let serial = replacement.serial();
+    replacement.advance_serial(serial);
+    assert!(catalog.replace(&origin, replacement));
+
+    let failed = messages.next().unwrap().unwrap();
+    assert_eq!(failed.response_code(), ResponseCode::ServFail);
+    assert!(messages.next().is_none());
+
+    // other queries are not streamed
+    let mut query: Message = Message::new();
+    query.add_query(Query::query(origin, RecordType::SOA));
+    let request = Request {
+        message: query,
+        src: src,
+    };
+    assert!(catalog.response_stream(&request).is_none());
+}
@@ -4,6 +4,7 @@ extern crate log;
 extern crate openssl;
 extern crate tokio_core;
 extern crate trust_dns;
+extern crate trust_dns_proto;
 extern crate trust_dns_server;
 extern crate trust_dns_integration;
 
@@ -16,12 +17,15 @@ use openssl::rsa::Rsa;
 use tokio_core::reactor::Core;
 
 use trust_dns::client::{ClientFuture, BasicClientHandle, ClientHandle};
-use trust_dns::op::ResponseCode;
+use trust_dns::error::ClientErrorKind;
+use trust_dns::op::{Message, MessageType, Query, ResponseCode};
 use trust_dns::rr::domain;
 use trust_dns::rr::{DNSClass, IntoRecordSet, RData, Record, RecordType, RecordSet};
 use trust_dns::rr::dnssec::{Algorithm, KeyPair, Signer};
 use trust_dns::udp::UdpClientStream;
 use trust_dns::tcp::TcpClientStream;
+use trust_dns_proto::DnsHandle;
+use trust_dns_proto::error::ProtoErrorKind;
 use trust_dns_server::authority::Catalog;
 
 use trust_dns_integration::{NeverReturnsClientStream, TestClientStream};
@@ -41,6 +45,34 @@ fn test_query_nonet() {
     io_loop.run(test_query(&mut client)).unwrap();
 }
 
+#[test]
+fn test_query_count() {
+    let authority = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(authority.origin().clone(), authority);
+
+    let mut io_loop = Core::new().unwrap();
+    let (stream, sender) = TestClientStream::new(catalog);
+    let mut client = ClientFuture::new(stream, sender, &io_loop.handle(), None);
+
+    let name = domain::Name::from_labels(vec!["www", "example", "com"]);
+    let mut message = Message::new();
+    message
+        .add_query(Query::query(name.clone(), RecordType::A))
+        .add_query(Query::query(name, RecordType::AAAA));
+
+    let error = io_loop.run(client.send(message)).expect_err(
+        "multiple queries should be refused",
+    );
+    match *error.kind() {
+        ClientErrorKind::Proto(ProtoErrorKind::QueryCount(2)) => (),
+        ref kind => panic!("unexpected error: {:?}", kind),
+    }
+
+    // the client continues to work
+    io_loop.run(test_query(&mut client)).unwrap();
+}
+
 #[test]
 #[ignore]
 fn test_query_udp_ipv4() {
@@ -965,3 +997,71 @@ fn test_timeout_query_tcp() {
     );
     test_timeout_query(client, io_loop);
 }
+
+#[test]
+fn test_query_tcp_connection_closed() {
+    use std::io::{Read, Write};
+    use std::time::Instant;
+
+    fn read_query(stream: &mut TcpStream) -> Message {
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len).unwrap();
+        let mut buffer = vec![0u8; ((len[0] as usize) << 8) | len[1] as usize];
+        stream.read_exact(&mut buffer).unwrap();
+        Message::from_vec(&buffer).unwrap()
+    }
+
+    // a name server which closes the connection once the query arrived, and answers the query on
+    //  the next connection
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        read_query(&mut stream);
+        drop(stream);
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let query = read_query(&mut stream);
+        let mut response = Message::new();
+        response
+            .set_id(query.id())
+            .set_message_type(MessageType::Response)
+            .set_response_code(ResponseCode::NXDomain);
+        response.add_queries(query.queries().into_iter().cloned());
+        let buffer = response.to_vec().unwrap();
+        stream
+            .write_all(&[(buffer.len() >> 8) as u8, buffer.len() as u8])
+            .unwrap();
+        stream.write_all(&buffer).unwrap();
+    });
+
+    let mut io_loop = Core::new().unwrap();
+    let (stream, sender) = TcpClientStream::new(addr, &io_loop.handle());
+    let mut client = ClientFuture::with_timeout(
+        stream,
+        sender,
+        &io_loop.handle(),
+        std::time::Duration::from_secs(10),
+        None,
+    );
+    let name = domain::Name::from_labels(vec!["www", "example", "com"]);
+
+    // the query lost with the connection fails at once, rather than once it timed out
+    let started = Instant::now();
+    let err = io_loop
+        .run(client.query(name.clone(), DNSClass::IN, RecordType::A))
+        .unwrap_err();
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    let error_str = format!("{}", err);
+    assert!(
+        error_str.contains("tcp connection lost"),
+        format!("actual error: {}", error_str)
+    );
+
+    // and the next query is sent over a new connection
+    let response = io_loop
+        .run(client.query(name, DNSClass::IN, RecordType::A))
+        .expect("query failed");
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    server.join().unwrap();
+}
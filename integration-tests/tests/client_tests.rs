@@ -18,6 +18,7 @@ use tokio_core::reactor::Core;
 
 #[allow(deprecated)]
 use trust_dns::client::{Client, ClientConnection, SecureSyncClient, SyncClient};
+use trust_dns::error::ClientErrorKind;
 use trust_dns::op::*;
 use trust_dns::rr::{DNSClass, Record, RecordType, domain, RData};
 use trust_dns::rr::dnssec::{Algorithm, KeyPair, Signer, TrustAnchor};
@@ -779,3 +780,38 @@ fn test_delete_all() {
     assert_eq!(result.response_code(), ResponseCode::NXDomain);
     assert_eq!(result.answers().len(), 0);
 }
+
+#[test]
+fn test_update_builder() {
+    let catalog = Catalog::new();
+    let (client, origin) = create_sig0_ready_client(catalog);
+
+    let name = domain::Name::from_labels(vec!["builder", "example", "com"]);
+    let mut record = Record::with(
+        name.clone(),
+        RecordType::A,
+        Duration::minutes(5).num_seconds() as u32,
+    );
+    record.set_rdata(RData::A(Ipv4Addr::new(100, 10, 100, 10)));
+
+    let mut update = UpdateBuilder::new(origin.clone());
+    update.name_not_in_use(name.clone()).add(record.clone());
+
+    let result = client.update(&update).expect("update failed");
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    let result = client
+        .query(record.name(), record.dns_class(), record.rr_type())
+        .expect("query failed");
+    assert_eq!(result.answers(), &[record.clone()]);
+
+    // the prerequisite no longer holds, so the same update is refused
+    match client.update(&update) {
+        Err(error) => {
+            match *error.kind() {
+                ClientErrorKind::Update(UpdateError::NameInUse) => (),
+                ref kind => panic!("unexpected error: {:?}", kind),
+            }
+        }
+        Ok(response) => panic!("expected an error: {:?}", response),
+    }
+}
@@ -6,11 +6,12 @@ extern crate trust_dns_resolver;
 
 use std::net::*;
 use std::str::FromStr;
+use std::time::Duration;
 
 use tokio_core::reactor::{Core, Handle};
 
 use trust_dns::error::{ClientErrorKind, ClientResult};
-use trust_dns::op::{Query, Message};
+use trust_dns::op::{Query, Message, ResponseCode};
 use trust_dns::rr::domain;
 use trust_dns::rr::RecordType;
 use trust_dns_proto::DnsHandle;
@@ -29,17 +30,64 @@ impl ConnectionProvider for MockConnProvider {
     }
 }
 
+/// Connections which answer with the record of the TCP NameServer, for the retries of truncated
+///  responses
+#[derive(Clone)]
+struct TcpConnProvider {}
+
+impl ConnectionProvider for TcpConnProvider {
+    type ConnHandle = MockClientHandle;
+
+    fn new_connection(config: &NameServerConfig, _: &ResolverOpts, _: &Handle) -> Self::ConnHandle {
+        assert_eq!(config.protocol, Protocol::Tcp);
+
+        let query = Query::query(
+            domain::Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        );
+        let tcp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 2));
+        MockClientHandle::mock(vec![message(query, vec![tcp_record], vec![], vec![])])
+    }
+}
+
 type MockedNameServer = NameServer<MockClientHandle, MockConnProvider>;
 type MockedNameServerPool = NameServerPool<MockClientHandle, MockConnProvider>;
 
 #[cfg(test)]
 fn mock_nameserver(messages: Vec<ClientResult<Message>>, reactor: &Handle) -> MockedNameServer {
+    mock_nameserver_on(0, messages, reactor)
+}
+
+/// NameServers are distinguished by their configuration, so each in a pool needs its own port
+#[cfg(test)]
+fn mock_nameserver_on(
+    port: u16,
+    messages: Vec<ClientResult<Message>>,
+    reactor: &Handle,
+) -> MockedNameServer {
+    mock_nameserver_with(Protocol::Udp, port, messages, reactor)
+}
+
+#[cfg(test)]
+fn mock_nameserver_with(
+    protocol: Protocol,
+    port: u16,
+    messages: Vec<ClientResult<Message>>,
+    reactor: &Handle,
+) -> MockedNameServer {
     let client = MockClientHandle::mock(messages);
 
     NameServer::from_conn(
         NameServerConfig {
-            socket_addr: SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 0),
-            protocol: Protocol::Udp,
+            socket_addr: SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), port),
+            protocol,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            // truncated responses are retried by the pool in these tests, not the NameServer
+            transport_strategy: TransportStrategy::UdpOnly,
         },
         ResolverOpts::default(),
         client,
@@ -55,6 +103,22 @@ fn mock_nameserver_pool(
     NameServerPool::from_nameservers(&ResolverOpts::default(), udp, tcp)
 }
 
+#[cfg(test)]
+fn racing_nameserver_pool(
+    udp: Vec<MockedNameServer>,
+    tcp: Vec<MockedNameServer>,
+) -> MockedNameServerPool {
+    let options = ResolverOpts {
+        transport_race: Some(TransportRace {
+            preferred: Protocol::Tcp,
+            head_start: Duration::from_secs(5),
+        }),
+        ..ResolverOpts::default()
+    };
+
+    NameServerPool::from_nameservers(&options, udp, tcp)
+}
+
 #[test]
 fn test_datagram() {
     let query = Query::query(
@@ -116,6 +180,60 @@ fn test_datagram_stream_upgrade() {
     assert_eq!(response.answers()[0], tcp_record);
 }
 
+#[test]
+fn test_datagram_tcp_fallback() {
+    // lookup to UDP should return truncated message
+    // then the same NameServer is asked over TCP
+
+    let query = Query::query(
+        domain::Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    );
+
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 1));
+    let tcp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 2));
+
+    let mut udp_message = message(query.clone(), vec![udp_record], vec![], vec![]);
+    udp_message.as_mut().unwrap().set_truncated(true);
+
+    let mut reactor = Core::new().unwrap();
+    let config = NameServerConfig {
+        socket_addr: SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 0),
+        protocol: Protocol::Udp,
+        tls_dns_name: None,
+        https_template: None,
+        edns_options: vec![],
+        name_compression: NameCompression::All,
+        padding_block_size: None,
+        transport_strategy: TransportStrategy::UdpThenTcp,
+    };
+    let mut name_server = NameServer::<_, TcpConnProvider>::from_conn(
+        config.clone(),
+        ResolverOpts::default(),
+        MockClientHandle::mock(vec![udp_message.clone()]),
+        &reactor.handle(),
+    );
+
+    let request = message(query.clone(), vec![], vec![], vec![]).unwrap();
+    let response = reactor.run(name_server.send(request.clone())).unwrap();
+    assert!(!response.truncated());
+    assert_eq!(response.answers()[0], tcp_record);
+
+    // without the fallback the truncated response is returned
+    let mut name_server = NameServer::<_, TcpConnProvider>::from_conn(
+        NameServerConfig {
+            transport_strategy: TransportStrategy::UdpOnly,
+            ..config
+        },
+        ResolverOpts::default(),
+        MockClientHandle::mock(vec![udp_message]),
+        &reactor.handle(),
+    );
+
+    let response = reactor.run(name_server.send(request)).unwrap();
+    assert!(response.truncated());
+}
+
 #[test]
 fn test_datagram_fails_to_stream() {
     // lookup to UDP should fail
@@ -144,4 +262,202 @@ fn test_datagram_fails_to_stream() {
 
     let response = reactor.run(future).unwrap();
     assert_eq!(response.answers()[0], tcp_record);
-}
\ No newline at end of file
+}
+#[test]
+fn test_datagram_fails_over() {
+    // lookup to the first UDP server fails
+    // then lookup on the next UDP server, rather than TCP
+
+    let query = Query::query(
+        domain::Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    );
+
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 1));
+    let tcp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 2));
+
+    let failed_message = Err(ClientErrorKind::Msg(format!("Forced Testing Error")).into());
+    let udp_message = message(query.clone(), vec![udp_record.clone()], vec![], vec![]);
+    let tcp_message = message(query.clone(), vec![tcp_record], vec![], vec![]);
+
+    let mut reactor = Core::new().unwrap();
+
+    let failed_nameserver = mock_nameserver_on(1, vec![failed_message], &reactor.handle());
+    let udp_nameserver = mock_nameserver_on(2, vec![udp_message], &reactor.handle());
+    let tcp_nameserver = mock_nameserver_on(3, vec![tcp_message], &reactor.handle());
+
+    let mut pool = mock_nameserver_pool(
+        vec![failed_nameserver.clone(), udp_nameserver.clone()],
+        vec![tcp_nameserver],
+    );
+
+    let request = message(query, vec![], vec![], vec![]).unwrap();
+    let future = pool.send(request);
+
+    let response = reactor.run(future).unwrap();
+    assert_eq!(response.answers()[0], udp_record);
+
+    // the failed server is now the least preferred
+    assert!(udp_nameserver > failed_nameserver);
+}
+
+#[test]
+fn test_servfail_fails_over() {
+    let query = Query::query(
+        domain::Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    );
+
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 1));
+
+    let mut servfail_message = message(query.clone(), vec![], vec![], vec![]);
+    servfail_message.as_mut().unwrap().set_response_code(
+        ResponseCode::ServFail,
+    );
+    let udp_message = message(query.clone(), vec![udp_record.clone()], vec![], vec![]);
+
+    let mut reactor = Core::new().unwrap();
+
+    let servfail_nameserver = mock_nameserver_on(1, vec![servfail_message], &reactor.handle());
+    let udp_nameserver = mock_nameserver_on(2, vec![udp_message], &reactor.handle());
+
+    let mut pool = mock_nameserver_pool(
+        vec![servfail_nameserver.clone(), udp_nameserver.clone()],
+        vec![],
+    );
+
+    let request = message(query, vec![], vec![], vec![]).unwrap();
+    let future = pool.send(request);
+
+    let response = reactor.run(future).unwrap();
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response.answers()[0], udp_record);
+
+    // the server which answered SERVFAIL is quarantined
+    assert!(udp_nameserver > servfail_nameserver);
+}
+
+#[test]
+fn test_all_servfail() {
+    // if every server answers SERVFAIL, that is the answer
+
+    let query = Query::query(
+        domain::Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    );
+
+    let mut servfail_message = message(query.clone(), vec![], vec![], vec![]);
+    servfail_message.as_mut().unwrap().set_response_code(
+        ResponseCode::ServFail,
+    );
+
+    let mut reactor = Core::new().unwrap();
+
+    let servfail_nameserver = mock_nameserver(vec![servfail_message], &reactor.handle());
+    let mut pool = mock_nameserver_pool(vec![servfail_nameserver], vec![]);
+
+    let request = message(query, vec![], vec![], vec![]).unwrap();
+    let future = pool.send(request);
+
+    let response = reactor.run(future).unwrap();
+    assert_eq!(response.response_code(), ResponseCode::ServFail);
+}
+
+#[test]
+fn test_race_preferred_wins() {
+    // the preferred TCP server answers within its head start, UDP is never queried
+
+    let query = Query::query(
+        domain::Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    );
+
+    let tcp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 2));
+    let tcp_message = message(query.clone(), vec![tcp_record.clone()], vec![], vec![]);
+
+    let mut reactor = Core::new().unwrap();
+
+    let udp_nameserver = mock_nameserver_with(Protocol::Udp, 1, vec![error()], &reactor.handle());
+    let tcp_nameserver =
+        mock_nameserver_with(Protocol::Tcp, 2, vec![tcp_message], &reactor.handle());
+
+    let mut pool = racing_nameserver_pool(vec![udp_nameserver], vec![tcp_nameserver]);
+
+    let request = message(query, vec![], vec![], vec![]).unwrap();
+    let future = pool.send(request);
+
+    let response = reactor.run(future).unwrap();
+    assert_eq!(response.answers()[0], tcp_record);
+}
+
+#[test]
+fn test_race_preferred_fails() {
+    // the preferred TCP server fails, UDP is queried without waiting for the head start
+
+    let query = Query::query(
+        domain::Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    );
+
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 1));
+    let udp_message = message(query.clone(), vec![udp_record.clone()], vec![], vec![]);
+
+    let mut reactor = Core::new().unwrap();
+
+    let udp_nameserver =
+        mock_nameserver_with(Protocol::Udp, 1, vec![udp_message], &reactor.handle());
+    let tcp_nameserver = mock_nameserver_with(Protocol::Tcp, 2, vec![error()], &reactor.handle());
+
+    let mut pool = racing_nameserver_pool(vec![udp_nameserver], vec![tcp_nameserver]);
+
+    let request = message(query, vec![], vec![], vec![]).unwrap();
+    let future = pool.send(request);
+
+    let response = reactor.run(future).unwrap();
+    assert_eq!(response.answers()[0], udp_record);
+}
+
+#[test]
+fn test_race_all_fail() {
+    let query = Query::query(
+        domain::Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    );
+
+    let mut reactor = Core::new().unwrap();
+
+    let udp_nameserver = mock_nameserver_with(Protocol::Udp, 1, vec![error()], &reactor.handle());
+    let tcp_nameserver = mock_nameserver_with(Protocol::Tcp, 2, vec![error()], &reactor.handle());
+
+    let mut pool = racing_nameserver_pool(vec![udp_nameserver], vec![tcp_nameserver]);
+
+    let request = message(query, vec![], vec![], vec![]).unwrap();
+    let future = pool.send(request);
+
+    assert!(reactor.run(future).is_err());
+}
+
+#[test]
+fn test_denied_query_types() {
+    let name = domain::Name::from_str("www.example.com.").unwrap();
+    let query = Query::query(name.clone(), RecordType::A);
+    let record = v4_record(name.clone(), Ipv4Addr::new(127, 0, 0, 1));
+    let udp_message = message(query.clone(), vec![record.clone()], vec![], vec![]);
+
+    let mut reactor = Core::new().unwrap();
+
+    let udp_nameserver = mock_nameserver(vec![udp_message], &reactor.handle());
+    let options = ResolverOpts {
+        denied_query_types: &[RecordType::ANY],
+        ..ResolverOpts::default()
+    };
+    let mut pool = NameServerPool::from_nameservers(&options, vec![udp_nameserver], vec![]);
+
+    // the ANY query is refused without being sent, the response is left for the A query
+    let any = message(Query::query(name, RecordType::ANY), vec![], vec![], vec![]).unwrap();
+    assert!(reactor.run(pool.send(any)).is_err());
+
+    let request = message(query, vec![], vec![], vec![]).unwrap();
+    let response = reactor.run(pool.send(request)).unwrap();
+    assert_eq!(response.answers()[0], record);
+}
@@ -5,9 +5,10 @@ extern crate trust_dns_openssl;
 extern crate trust_dns_server;
 extern crate trust_dns_integration;
 
-use std::io;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket, TcpListener};
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 use std::time::Duration;
@@ -181,6 +182,333 @@ fn test_server_www_tls() {
     server_thread.join().unwrap();
 }
 
+#[test]
+fn test_server_notify_refresh() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+
+    let ipaddr = tcp_listener.local_addr().unwrap();
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server_continue2 = server_continue.clone();
+
+    // the primary has a version of the zone with a new record
+    let server_thread = thread::Builder::new()
+        .name("test_server:notify:primary".to_string())
+        .spawn(move || {
+            let catalog = new_catalog();
+            let mut replacement = create_example();
+            replacement.upsert(
+                Record::from_rdata(
+                    Name::parse("new.example.com.", None).unwrap(),
+                    86400,
+                    RecordType::A,
+                    RData::A(Ipv4Addr::new(10, 0, 0, 2)),
+                ),
+                0,
+            );
+            replacement.advance_serial(2015082403);
+            let origin = replacement.origin().clone();
+            assert!(catalog.replace(&origin, replacement));
+
+            let mut server = ServerFuture::new(catalog).expect("new tcp server failed");
+            server
+                .register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+
+            while server_continue2.load(Ordering::Relaxed) {
+                server.tokio_core().turn(Some(Duration::from_millis(10)));
+            }
+        })
+        .unwrap();
+
+    // the secondary has the version before
+    let example = create_example();
+    let origin = example.origin().clone();
+    let secondary = Authority::new(
+        origin.clone(),
+        example.records().to_map(),
+        ZoneType::Slave,
+        false,
+        false,
+    );
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), secondary);
+    catalog.set_primary(origin.clone(), ipaddr);
+
+    let mut notify = Message::new();
+    notify
+        .set_id(1)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Notify);
+    notify.add_query(Query::query(origin.clone(), RecordType::SOA));
+    let response = catalog.handle_notify(&notify, ipaddr.ip());
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+
+    // the zone is refreshed in the background
+    let mut transfer_status = TransferStatus::None;
+    for _ in 0..100 {
+        transfer_status = catalog.zone_stats()[&origin].transfer_status().clone();
+        if transfer_status != TransferStatus::None {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    server_continue.store(false, Ordering::Relaxed);
+    server_thread.join().unwrap();
+
+    assert_eq!(transfer_status, TransferStatus::Succeeded(2015082404));
+    assert_eq!(catalog.serial(&origin), Some(2015082404));
+
+    let mut question = Message::new();
+    question.add_query(Query::query(
+        Name::parse("new.example.com.", None).unwrap(),
+        RecordType::A,
+    ));
+    assert_eq!(catalog.lookup(&question).answers().len(), 1);
+}
+
+#[test]
+fn test_server_secondary_transfer() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+
+    let ipaddr = tcp_listener.local_addr().unwrap();
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server_continue2 = server_continue.clone();
+
+    let server_thread = thread::Builder::new()
+        .name("test_server:secondary:primary".to_string())
+        .spawn(move || {
+            let mut server = ServerFuture::new(new_catalog()).expect("new tcp server failed");
+            server
+                .register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+
+            while server_continue2.load(Ordering::Relaxed) {
+                server.tokio_core().turn(Some(Duration::from_millis(10)));
+            }
+        })
+        .unwrap();
+
+    // the secondary starts without any records
+    let origin = Name::parse("example.com.", None).unwrap();
+    let secondary = Authority::new(
+        origin.clone(),
+        BTreeMap::new(),
+        ZoneType::Slave,
+        false,
+        false,
+    );
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), secondary);
+    catalog.set_primary(origin.clone(), ipaddr);
+
+    let mut question = Message::new();
+    question.add_query(Query::query(
+        Name::parse("www.example.com.", None).unwrap(),
+        RecordType::A,
+    ));
+    assert_eq!(
+        catalog.lookup(&question).response_code(),
+        ResponseCode::ServFail
+    );
+
+    // the zone is transferred in the background
+    assert_eq!(catalog.refresh_secondaries(), vec![origin.clone()]);
+    let mut transfer_status = TransferStatus::None;
+    for _ in 0..100 {
+        transfer_status = catalog.zone_stats()[&origin].transfer_status().clone();
+        if transfer_status != TransferStatus::None {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    server_continue.store(false, Ordering::Relaxed);
+    server_thread.join().unwrap();
+
+    assert_eq!(transfer_status, TransferStatus::Succeeded(2015082403));
+    assert_eq!(catalog.lookup(&question).answers().len(), 1);
+
+    // and is not due again until its refresh interval passed
+    assert!(catalog.refresh_secondaries().is_empty());
+}
+
+#[test]
+fn test_server_axfr_alongside_udp() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+
+    let udp_addr = udp_socket.local_addr().unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server_continue2 = server_continue.clone();
+    let (yields_sender, yields_receiver) = mpsc::channel();
+
+    // one IO loop serves both the transfer and the queries
+    let server_thread = thread::Builder::new()
+        .name("test_server:axfr:server".to_string())
+        .spawn(move || {
+            let mut server = ServerFuture::new(large_catalog()).expect("new server failed");
+            server.set_poll_budget(2);
+            yields_sender.send(server.budget_yields()).unwrap();
+            server.register_socket(udp_socket);
+            server
+                .register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+
+            while server_continue2.load(Ordering::Relaxed) {
+                server.tokio_core().turn(Some(Duration::from_millis(10)));
+            }
+        })
+        .unwrap();
+    let budget_yields = yields_receiver.recv().unwrap();
+
+    // the transfer is requested, but not read yet
+    let mut axfr = Message::new();
+    axfr.set_id(4321);
+    axfr.add_query(Query::query(
+        Name::parse("example.com.", None).unwrap(),
+        RecordType::AXFR,
+    ));
+    let request = axfr.to_vec().unwrap();
+    let mut tcp_stream = std::net::TcpStream::connect(tcp_addr).unwrap();
+    tcp_stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    tcp_stream
+        .write_all(&[(request.len() >> 8) as u8, request.len() as u8])
+        .unwrap();
+    tcp_stream.write_all(&request).unwrap();
+
+    // the queries over UDP are answered while the transfer is under way
+    for _ in 0..4 {
+        client_thread_www(lazy_udp_client(udp_addr));
+    }
+
+    // the whole zone is transferred, from SOA to SOA
+    let messages = read_transfer(&mut tcp_stream, 4321);
+    let records: Vec<Record> = messages
+        .iter()
+        .flat_map(|message| message.answers().iter().cloned())
+        .collect();
+    assert!(messages.len() > 2);
+    assert!(records.len() > 5000);
+    assert_eq!(records.first().unwrap().rr_type(), RecordType::SOA);
+    assert_eq!(records.last().unwrap().rr_type(), RecordType::SOA);
+
+    // the transfer gave up the IO loop between its messages
+    assert!(budget_yields.load(Ordering::Relaxed) > 0);
+
+    server_continue.store(false, Ordering::Relaxed);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_server_tsig_axfr_on_worker_pool() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use trust_dns::rr::dnssec::{TSigner, TsigAlgorithm};
+
+    let tsigner = TSigner::new(
+        Name::parse("transfer-key.example.com.", None).unwrap(),
+        TsigAlgorithm::HmacSha256,
+        b"a secret shared by client and server".to_vec(),
+        300,
+    );
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    let server_continue = Arc::new(AtomicBool::new(true));
+    let server_continue2 = server_continue.clone();
+    let server_tsigner = tsigner.clone();
+
+    // the request is verified, and each message signed, by the one worker
+    let server_thread = thread::Builder::new()
+        .name("test_server:tsig_axfr:server".to_string())
+        .spawn(move || {
+            let mut catalog = large_catalog();
+            catalog.add_tsig_key(server_tsigner);
+            let mut server =
+                ServerFuture::with_worker_pool(catalog, 1, 1).expect("new server failed");
+            server
+                .register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+
+            while server_continue2.load(Ordering::Relaxed) {
+                server.tokio_core().turn(Some(Duration::from_millis(10)));
+            }
+        })
+        .unwrap();
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    let mut axfr = Message::new();
+    axfr.set_id(4322);
+    axfr.add_query(Query::query(
+        Name::parse("example.com.", None).unwrap(),
+        RecordType::AXFR,
+    ));
+    axfr.finalize(&tsigner, now).unwrap();
+    let request = axfr.to_vec().unwrap();
+    let mut tcp_stream = std::net::TcpStream::connect(tcp_addr).unwrap();
+    tcp_stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    tcp_stream
+        .write_all(&[(request.len() >> 8) as u8, request.len() as u8])
+        .unwrap();
+    tcp_stream.write_all(&request).unwrap();
+
+    let messages = read_transfer(&mut tcp_stream, 4322);
+    assert!(messages.len() > 2);
+
+    // the first message covers the MAC of the request, each one after it the MAC before it
+    assert!(tsigner.verify_response(axfr.sig0(), &messages[0], now).is_ok());
+    let mut prior_mac = match *messages[0].tsig().unwrap().rdata() {
+        RData::TSIG(ref tsig) => tsig.mac().to_vec(),
+        _ => panic!("not a TSIG"),
+    };
+    for message in &messages[1..] {
+        let mac = tsigner
+            .verify_message_continued(message, &prior_mac, now as u64)
+            .unwrap()
+            .mac()
+            .to_vec();
+        prior_mac = mac;
+    }
+
+    server_continue.store(false, Ordering::Relaxed);
+    server_thread.join().unwrap();
+}
+
+/// Reads the messages of a zone transfer from the connection, until the closing SOA
+fn read_transfer(tcp_stream: &mut std::net::TcpStream, id: u16) -> Vec<Message> {
+    let mut messages: Vec<Message> = Vec::new();
+    let mut soas = 0;
+    while soas < 2 {
+        let mut len = [0u8; 2];
+        tcp_stream.read_exact(&mut len).unwrap();
+        let mut buffer = vec![0u8; (len[0] as usize) << 8 | len[1] as usize];
+        tcp_stream.read_exact(&mut buffer).unwrap();
+
+        let message = Message::from_vec(&buffer).unwrap();
+        assert_eq!(message.id(), id);
+        assert_eq!(message.response_code(), ResponseCode::NoError);
+        soas += message
+            .answers()
+            .iter()
+            .filter(|record| record.rr_type() == RecordType::SOA)
+            .count();
+        messages.push(message);
+    }
+    messages
+}
+
 fn lazy_udp_client(ipaddr: SocketAddr) -> UdpClientConnection {
     UdpClientConnection::new(ipaddr).unwrap()
 }
@@ -255,6 +583,29 @@ fn new_catalog() -> Catalog {
     catalog
 }
 
+/// The example zone, with enough hosts that its transfer takes many messages
+fn large_catalog() -> Catalog {
+    let mut example = create_example();
+    let origin = example.origin().clone();
+    let serial = example.serial();
+    for i in 0..5000u32 {
+        let name = Name::parse(&format!("host-{}", i), Some(&origin)).unwrap();
+        example.upsert(
+            Record::from_rdata(
+                name,
+                86400,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(10, 0, (i / 256) as u8, i as u8)),
+            ),
+            serial,
+        );
+    }
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin, example);
+    catalog
+}
+
 fn server_thread_udp(udp_socket: UdpSocket, server_continue: Arc<AtomicBool>) {
     let catalog = new_catalog();
 
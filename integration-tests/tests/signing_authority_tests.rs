@@ -0,0 +1,231 @@
+extern crate chrono;
+extern crate trust_dns;
+extern crate trust_dns_server;
+extern crate trust_dns_integration;
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use chrono::{Duration, Utc};
+
+use trust_dns::op::*;
+use trust_dns::rr::*;
+use trust_dns::rr::dnssec::*;
+use trust_dns_server::authority::{Authority, Catalog, Denial, SigningAuthority, ZoneSigner};
+use trust_dns_server::authority::zone_signer::verify_zone;
+use trust_dns_server::server::{Request, RequestHandler};
+
+use trust_dns_integration::authority::create_example;
+
+fn signer(origin: &Name) -> Signer {
+    let key = KeyPair::generate(Algorithm::ECDSAP256SHA256).unwrap();
+    let dnskey = key.to_dnskey(Algorithm::ECDSAP256SHA256).unwrap();
+    Signer::dnssec(dnskey, key, origin.clone(), Duration::weeks(1))
+}
+
+/// The example zone, signed with a zone signing key and a key signing key
+fn create_signed_example(denial: Denial) -> SigningAuthority {
+    let example = create_example();
+    let origin = example.origin().clone();
+    let zone_signer = ZoneSigner::with_key_signers(
+        origin.clone(),
+        vec![signer(&origin)],
+        vec![signer(&origin)],
+        denial,
+    );
+
+    SigningAuthority::new(example, zone_signer).unwrap()
+}
+
+/// All the records of the zone, with their signatures
+fn zone_records(authority: &Authority) -> Vec<Record> {
+    authority
+        .records()
+        .iter()
+        .flat_map(|(_, rrset)| rrset.iter().chain(rrset.rrsigs()))
+        .cloned()
+        .collect()
+}
+
+fn now() -> u32 {
+    Utc::now().timestamp() as u32
+}
+
+fn count(records: &[Record], record_type: RecordType) -> usize {
+    records
+        .iter()
+        .filter(|record| record.rr_type() == record_type)
+        .count()
+}
+
+/// The key tags of the signatures of the RRset
+fn key_tags(records: &[Record], name: &Name, record_type: RecordType) -> Vec<u16> {
+    records
+        .iter()
+        .filter(|record| record.name() == name)
+        .filter_map(|record| match *record.rdata() {
+            RData::SIG(ref sig) if sig.type_covered() == record_type => Some(sig.key_tag()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_sign_on_load() {
+    let serial = create_example().serial();
+    let signed = create_signed_example(Denial::Nsec);
+    let records = zone_records(&signed);
+
+    assert!(verify_zone(signed.origin(), &records, now()).is_empty());
+    assert_eq!(signed.serial(), serial + 1);
+    assert_eq!(count(&records, RecordType::DNSKEY), 2);
+
+    // the DNSKEY RRset is signed by the key signing key, everything else by the zone signing key
+    let zsk = signed.signer().signers()[0].calculate_key_tag().unwrap();
+    let ksk = signed.signer().key_signers()[0].calculate_key_tag().unwrap();
+    assert_eq!(
+        key_tags(&records, signed.origin(), RecordType::DNSKEY),
+        vec![ksk]
+    );
+    assert_eq!(key_tags(&records, signed.origin(), RecordType::SOA), vec![zsk]);
+
+    // the DS records are those of the key signing key
+    let ds_records = signed.ds_records(DigestType::SHA256, 3600).unwrap();
+    assert_eq!(ds_records.len(), 1);
+    match *ds_records[0].rdata() {
+        RData::DS(ref ds) => assert_eq!(ds.key_tag(), ksk),
+        ref rdata => panic!("expected DS: {:?}", rdata), // valid panic, in test
+    }
+}
+
+#[test]
+fn test_sign_nsec3() {
+    let denial = Denial::Nsec3 {
+        iterations: 1,
+        salt: vec![0xAA, 0xBB],
+        opt_out: false,
+    };
+    let signed = create_signed_example(denial);
+    let records = zone_records(&signed);
+
+    assert!(verify_zone(signed.origin(), &records, now()).is_empty());
+    assert_eq!(count(&records, RecordType::NSEC), 0);
+    assert_eq!(count(&records, RecordType::NSEC3PARAM), 1);
+    assert!(count(&records, RecordType::NSEC3) > 0);
+}
+
+#[test]
+fn test_sign_on_update() {
+    let mut signed = create_signed_example(Denial::Nsec);
+    let serial = signed.serial();
+
+    let new_name = Name::parse("new.example.com.", None).unwrap();
+    let mut record = Record::with(new_name.clone(), RecordType::A, 86400);
+    record.set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)));
+    assert!(signed.update_records(&[record.clone()]).unwrap());
+
+    // the new name is signed, and in the NSEC chain
+    let records = zone_records(&signed);
+    assert!(verify_zone(signed.origin(), &records, now()).is_empty());
+    assert_eq!(signed.serial(), serial + 1);
+    assert!(!key_tags(&records, &new_name, RecordType::A).is_empty());
+    assert!(!key_tags(&records, &new_name, RecordType::NSEC).is_empty());
+
+    // an update without changes is not signed again
+    assert!(!signed.update_records(&[record]).unwrap());
+    assert_eq!(signed.serial(), serial + 1);
+}
+
+#[test]
+fn test_resign() {
+    let mut signed = create_signed_example(Denial::Nsec);
+    let serial = signed.serial();
+
+    // signed for a week, renewed once less than a quarter of it is left
+    assert_eq!(signed.resign_margin(), Duration::weeks(1) / 4);
+    assert!(!signed.is_resign_due(Utc::now()));
+    assert!(signed.is_resign_due(Utc::now() + Duration::days(6)));
+
+    let first_expiration = signed.first_expiration().unwrap();
+    signed.set_resign_margin(Duration::weeks(2));
+    assert!(signed.is_resign_due(Utc::now()));
+
+    assert_eq!(signed.resign().unwrap(), serial + 1);
+    assert!(signed.first_expiration().unwrap() >= first_expiration);
+    assert!(verify_zone(signed.origin(), &zone_records(&signed), now()).is_empty());
+}
+
+#[test]
+fn test_catalog_signing_zone() {
+    let key_name = Name::parse("update-key.example.com.", None).unwrap();
+    let tsigner = TSigner::new(
+        key_name.clone(),
+        TsigAlgorithm::HmacSha256,
+        b"a secret shared by client and server".to_vec(),
+        300,
+    );
+
+    let mut example = create_example();
+    let origin = example.origin().clone();
+    example.set_allow_update(true);
+    example.add_update_tsig_key(key_name);
+    let zone_signer = ZoneSigner::new(origin.clone(), vec![signer(&origin)], Denial::Nsec);
+    let signed = SigningAuthority::new(example, zone_signer).unwrap();
+    let serial = signed.serial();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), signed);
+    catalog.add_tsig_key(tsigner.clone());
+
+    let request = |message: Message| {
+        Request {
+            message: Message::from_vec(&message.to_vec().unwrap()).unwrap(),
+            src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53),
+        }
+    };
+    let signed_answers = |name: &Name, record_type: RecordType| {
+        let mut query: Message = Message::new();
+        query.add_query(Query::query(name.clone(), record_type));
+        query.edns_mut().set_dnssec_ok(true);
+
+        let response = catalog.handle_request(&request(query));
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        (
+            count(response.answers(), record_type),
+            count(response.answers(), RecordType::RRSIG),
+        )
+    };
+
+    // an update of the served zone is signed
+    let new_name = Name::parse("new.example.com.", None).unwrap();
+    let mut update: Message = Message::new();
+    update.set_op_code(OpCode::Update);
+    update.add_zone(Query::query(origin.clone(), RecordType::SOA));
+    update.add_update(
+        Record::new()
+            .set_name(new_name.clone())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)))
+            .clone(),
+    );
+    update.finalize(&tsigner, now()).unwrap();
+
+    let response = catalog.handle_request(&request(update));
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(catalog.serial(&origin), Some(serial + 1));
+    assert_eq!(signed_answers(&new_name, RecordType::A), (1, 1));
+
+    // as is the version of the zone which replaces it
+    let replacement = create_example();
+    assert!(catalog.replace(&origin, replacement));
+    assert_eq!(catalog.serial(&origin), Some(create_example().serial() + 1));
+    assert_eq!(signed_answers(&origin, RecordType::SOA), (1, 1));
+
+    // and it is signed again once its signatures are about to expire
+    let serial = catalog.serial(&origin).unwrap();
+    assert!(catalog.resign_due(Utc::now()).is_empty());
+    assert_eq!(catalog.resign_due(Utc::now() + Duration::days(6)), vec![origin.clone()]);
+    assert_eq!(catalog.serial(&origin), Some(serial + 1));
+    assert_eq!(signed_answers(&origin, RecordType::SOA), (1, 1));
+}
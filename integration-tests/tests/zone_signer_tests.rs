@@ -0,0 +1,210 @@
+extern crate chrono;
+extern crate trust_dns;
+extern crate trust_dns_server;
+extern crate trust_dns_integration;
+
+use std::net::Ipv4Addr;
+
+use chrono::{Duration, Utc};
+
+use trust_dns::rr::*;
+use trust_dns::rr::dnssec::*;
+use trust_dns_server::authority::{Denial, ZoneSigner};
+use trust_dns_server::authority::zone_signer::verify_zone;
+
+use trust_dns_integration::authority::create_example;
+
+/// The example zone, with a delegation and its glue, and a name below an empty non-terminal
+fn example_records() -> (Name, Vec<Record>) {
+    let example = create_example();
+    let origin = example.origin().clone();
+    let mut records = example
+        .records()
+        .iter()
+        .flat_map(|(_, rrset)| rrset.iter().cloned())
+        .collect::<Vec<_>>();
+
+    let sub = Name::parse("sub.example.com.", None).unwrap();
+    let ns = Name::parse("ns.sub.example.com.", None).unwrap();
+    records.push(Record::from_rdata(
+        sub,
+        86400,
+        RecordType::NS,
+        RData::NS(ns.clone()),
+    ));
+    records.push(Record::from_rdata(
+        ns,
+        86400,
+        RecordType::A,
+        RData::A(Ipv4Addr::new(10, 0, 0, 1)),
+    ));
+    records.push(Record::from_rdata(
+        Name::parse("a.b.example.com.", None).unwrap(),
+        3600,
+        RecordType::A,
+        RData::A(Ipv4Addr::new(10, 0, 0, 2)),
+    ));
+
+    (origin, records)
+}
+
+fn zone_signer(origin: &Name, denial: Denial) -> ZoneSigner {
+    let key = KeyPair::generate(Algorithm::ECDSAP256SHA256).unwrap();
+    let dnskey = key.to_dnskey(Algorithm::ECDSAP256SHA256).unwrap();
+    let signer = Signer::dnssec(dnskey, key, origin.clone(), Duration::weeks(1));
+
+    ZoneSigner::new(origin.clone(), vec![signer], denial)
+}
+
+fn now() -> u32 {
+    Utc::now().timestamp() as u32
+}
+
+fn count(records: &[Record], record_type: RecordType) -> usize {
+    records
+        .iter()
+        .filter(|record| record.rr_type() == record_type)
+        .count()
+}
+
+fn is_covered(records: &[Record], name: &Name, record_type: RecordType) -> bool {
+    records.iter().any(|record| {
+        record.name() == name &&
+            match *record.rdata() {
+                RData::SIG(ref sig) => sig.type_covered() == record_type,
+                _ => false,
+            }
+    })
+}
+
+#[test]
+fn test_sign_nsec() {
+    let (origin, records) = example_records();
+    let zone_signer = zone_signer(&origin, Denial::Nsec);
+    let signed = zone_signer.sign(&records, Utc::now()).unwrap();
+
+    assert!(verify_zone(&origin, &signed, now()).is_empty());
+
+    // the origin, www, the delegation and a.b, but neither the glue nor the empty non-terminal
+    assert_eq!(count(&signed, RecordType::NSEC), 4);
+    assert_eq!(count(&signed, RecordType::DNSKEY), 1);
+
+    let sub = Name::parse("sub.example.com.", None).unwrap();
+    let ns = Name::parse("ns.sub.example.com.", None).unwrap();
+    assert!(is_covered(&signed, &sub, RecordType::NSEC));
+    assert!(!is_covered(&signed, &sub, RecordType::NS));
+    assert!(!is_covered(&signed, &ns, RecordType::A));
+    assert!(is_covered(&signed, &origin, RecordType::DNSKEY));
+}
+
+#[test]
+fn test_sign_nsec3() {
+    let (origin, records) = example_records();
+    let denial = Denial::Nsec3 {
+        iterations: 5,
+        salt: vec![0xAA, 0xBB, 0xCC, 0xDD],
+        opt_out: false,
+    };
+    let signed = zone_signer(&origin, denial).sign(&records, Utc::now()).unwrap();
+
+    assert!(verify_zone(&origin, &signed, now()).is_empty());
+
+    // the origin, www, the delegation, a.b and the empty non-terminal b
+    assert_eq!(count(&signed, RecordType::NSEC3), 5);
+    assert_eq!(count(&signed, RecordType::NSEC3PARAM), 1);
+    assert_eq!(count(&signed, RecordType::NSEC), 0);
+}
+
+#[test]
+fn test_sign_nsec3_opt_out() {
+    let (origin, records) = example_records();
+    let denial = Denial::Nsec3 {
+        iterations: 0,
+        salt: vec![],
+        opt_out: true,
+    };
+    let signed = zone_signer(&origin, denial).sign(&records, Utc::now()).unwrap();
+
+    assert!(verify_zone(&origin, &signed, now()).is_empty());
+
+    // the insecure delegation is left out
+    assert_eq!(count(&signed, RecordType::NSEC3), 4);
+}
+
+#[test]
+fn test_sign_again() {
+    let (origin, records) = example_records();
+    let signed = zone_signer(&origin, Denial::Nsec)
+        .sign(&records, Utc::now())
+        .unwrap();
+
+    // the NSEC records and signatures are replaced, the first key stays published
+    let denial = Denial::Nsec3 {
+        iterations: 1,
+        salt: vec![1, 2],
+        opt_out: false,
+    };
+    let signed = zone_signer(&origin, denial).sign(&signed, Utc::now()).unwrap();
+
+    assert!(verify_zone(&origin, &signed, now()).is_empty());
+    assert_eq!(count(&signed, RecordType::NSEC), 0);
+    assert_eq!(count(&signed, RecordType::DNSKEY), 2);
+}
+
+#[test]
+fn test_verify_problems() {
+    let (origin, records) = example_records();
+    let signed = zone_signer(&origin, Denial::Nsec)
+        .sign(&records, Utc::now())
+        .unwrap();
+
+    // expired
+    let later = now() + Duration::weeks(2).num_seconds() as u32;
+    assert!(!verify_zone(&origin, &signed, later).is_empty());
+
+    // changed records
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let mut changed = signed.clone();
+    for record in changed.iter_mut() {
+        if record.name() == &www && record.rr_type() == RecordType::A {
+            record.set_rdata(RData::A(Ipv4Addr::new(127, 0, 0, 1)));
+        }
+    }
+    let problems = verify_zone(&origin, &changed, now());
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("www.example.com. A: no valid signature"));
+
+    // a name without an NSEC, and its signature
+    let mut changed = signed.clone();
+    changed.retain(|record| {
+        record.name() != &www ||
+            (record.rr_type() != RecordType::NSEC && record.rr_type() != RecordType::RRSIG)
+    });
+    assert!(!verify_zone(&origin, &changed, now()).is_empty());
+
+    // not signed at all
+    assert!(!verify_zone(&origin, &records, now()).is_empty());
+}
+
+#[test]
+fn test_ds_records() {
+    let (origin, records) = example_records();
+    let zone_signer = zone_signer(&origin, Denial::Nsec);
+    let signed = zone_signer.sign(&records, Utc::now()).unwrap();
+    let ds_records = zone_signer.ds_records(DigestType::SHA256, 3600).unwrap();
+
+    let dnskey = signed
+        .iter()
+        .filter_map(|record| match *record.rdata() {
+            RData::DNSKEY(ref dnskey) => Some(dnskey),
+            _ => None,
+        })
+        .next()
+        .unwrap();
+
+    assert_eq!(ds_records.len(), 1);
+    match *ds_records[0].rdata() {
+        RData::DS(ref ds) => assert!(ds.covers(&origin, dnskey).unwrap()),
+        ref rdata => panic!("expected DS: {:?}", rdata),
+    }
+}
@@ -5,6 +5,7 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::io;
 use std::time::Duration;
@@ -15,12 +16,14 @@ use futures::IntoFuture;
 use futures::stream::{Peekable, Fuse as StreamFuse, Stream};
 use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 use futures::sync::oneshot;
-use rand::Rng;
+use rand::{OsRng, Rng};
 use rand;
 use tokio_core::reactor::{Handle, Timeout};
 
 use error::*;
-use op::{Message, MessageFinalizer, OpCode};
+use op::{Message, MessageFinalizer, OpCode, Query, ResponseCode};
+use rr::{Record, RecordType};
+use serialize::binary::NameCompression;
 
 const QOS_MAX_RECEIVE_MSGS: usize = 100; // max number of messages to receive from the UDP socket
 
@@ -41,6 +44,147 @@ impl DnsStreamHandle for StreamHandle {
     }
 }
 
+/// How the requests which are not answered in time, or which were lost by the stream, are
+///  retransmitted by the `DnsFuture`
+///
+/// Each retransmission has a new random query id, a response to an earlier one is ignored, so
+///  that a spoofed response has to guess the id of the last. The time waited for each response
+///  doubles up to the maximum, and is lengthened by a random part of up to the jitter, so that
+///  clients which lost their responses together do not retransmit together. Updates are never
+///  retransmitted, they are not idempotent.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    attempts: usize,
+    timeout: Duration,
+    max_timeout: Duration,
+    jitter_percent: u32,
+}
+
+impl RetryPolicy {
+    /// A policy which sends each request once, and waits the timeout for the response
+    pub fn new(timeout: Duration) -> Self {
+        RetryPolicy {
+            attempts: 1,
+            timeout: timeout,
+            max_timeout: timeout,
+            jitter_percent: 0,
+        }
+    }
+
+    /// The number of times a request is sent before it fails, at least once
+    pub fn attempts(&self) -> usize {
+        cmp::max(self.attempts, 1)
+    }
+
+    /// Sets the number of times a request is sent before it fails
+    pub fn set_attempts(&mut self, attempts: usize) {
+        self.attempts = attempts;
+    }
+
+    /// The time waited for the response to the first attempt
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// The longest time waited for the response to an attempt
+    pub fn max_timeout(&self) -> Duration {
+        cmp::max(self.max_timeout, self.timeout)
+    }
+
+    /// Sets the longest time waited for the response to an attempt, the timeout doubles with each
+    ///  attempt up to this
+    pub fn set_max_timeout(&mut self, max_timeout: Duration) {
+        self.max_timeout = max_timeout;
+    }
+
+    /// The most by which the time waited for a response is lengthened at random, as a
+    ///  percentage of it
+    pub fn jitter_percent(&self) -> u32 {
+        self.jitter_percent
+    }
+
+    /// Sets the most by which the time waited for a response is lengthened at random
+    pub fn set_jitter_percent(&mut self, jitter_percent: u32) {
+        self.jitter_percent = jitter_percent;
+    }
+
+    /// The time waited for the response to the attempt, counted from 0, before the jitter
+    pub fn attempt_timeout(&self, attempt: usize) -> Duration {
+        let factor = 1_u32 << cmp::min(attempt, 16);
+        self.timeout.checked_mul(factor).map_or(
+            self.max_timeout(),
+            |timeout| cmp::min(timeout, self.max_timeout()),
+        )
+    }
+
+    /// The time waited for the response to the attempt, with the jitter
+    fn jittered_timeout(&self, attempt: usize) -> Duration {
+        let timeout = self.attempt_timeout(attempt);
+        if self.jitter_percent == 0 {
+            return timeout;
+        }
+
+        let timeout_ms = timeout.as_secs() * 1000 + (timeout.subsec_nanos() / 1_000_000) as u64;
+        let jitter_percent = rand::thread_rng().gen_range(0, self.jitter_percent + 1);
+        timeout + Duration::from_millis(timeout_ms * jitter_percent as u64 / 100)
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Sends each request once, and waits 5 seconds for the response
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5))
+    }
+}
+
+/// Returns true if the response is to the question of the request, RFC 5452 section 9.1
+///
+/// The names are compared without regard to case, the case of a randomized query name is checked
+///  by the sender. A response without a question is accepted for an error, which may be of a
+///  question that could not be read, and for an update, whose zone section need not be echoed.
+pub(crate) fn is_answer_to(queries: &[Query], response: &Message) -> bool {
+    if response.queries().is_empty() {
+        return queries.is_empty() || response.response_code() != ResponseCode::NoError ||
+            response.op_code() == OpCode::Update;
+    }
+
+    response.queries() == queries
+}
+
+/// Returns true if the message is signed by the signer of the `DnsFuture`, updates and zone
+///  transfers are signed, other queries need not be authenticated
+fn is_signed(message: &Message) -> bool {
+    message.op_code() == OpCode::Update ||
+        message.queries().iter().any(|query| match query.query_type() {
+            RecordType::AXFR | RecordType::IXFR => true,
+            _ => false,
+        })
+}
+
+/// Completes the request, the requester may have given up on it, e.g. dropped its future
+fn complete_request(complete: Complete<ProtoResult<Message>>, result: ProtoResult<Message>) {
+    if complete.send(result).is_err() {
+        debug!("the requester gave up before the request completed");
+    }
+}
+
+/// A request which is waiting for its response
+struct ActiveRequest {
+    complete: Complete<ProtoResult<Message>>,
+    timeout: Timeout,
+    // the question of the request, which the response must echo
+    queries: Vec<Query>,
+    // the signatures of the request, which the signer verifies the response with
+    signatures: Vec<Record>,
+    // the request to retransmit, None if it is not retransmitted
+    message: Option<Message>,
+    // the request before it was signed, to sign it again with the time of the server after a
+    //  BADTIME response, None if it is not signed or was signed again
+    unsigned: Option<Message>,
+    // the attempt which is waiting, counted from 0
+    attempt: usize,
+}
+
 /// A DNS Client implemented over futures-rs.
 ///
 /// This Client is generic and capable of wrapping UDP, TCP, and other underlying DNS protocol
@@ -49,13 +193,17 @@ impl DnsStreamHandle for StreamHandle {
 pub struct DnsFuture<S: Stream<Item = Vec<u8>, Error = io::Error>, MF: MessageFinalizer> {
     stream: S,
     reactor_handle: Handle,
-    timeout_duration: Duration,
+    retry_policy: RetryPolicy,
     // TODO: genericize and remove this Box
     stream_handle: Box<DnsStreamHandle>,
     new_receiver:
         Peekable<StreamFuse<UnboundedReceiver<(Message, Complete<ProtoResult<Message>>)>>>,
-    active_requests: HashMap<u16, (Complete<ProtoResult<Message>>, Timeout)>,
+    active_requests: HashMap<u16, ActiveRequest>,
     signer: Option<MF>,
+    name_compression: NameCompression,
+    // the random number generator of the OS, for the query ids, so that they can not be
+    //  predicted from those seen before
+    query_ids: OsRng,
 }
 
 impl<S, MF> DnsFuture<S, MF>
@@ -106,22 +254,78 @@ where
         loop_handle: &Handle,
         timeout_duration: Duration,
         signer: Option<MF>,
+    ) -> BasicDnsHandle {
+        Self::with_name_compression(
+            stream,
+            stream_handle,
+            loop_handle,
+            timeout_duration,
+            signer,
+            NameCompression::All,
+        )
+    }
+
+    /// Spawns a new DnsFuture Stream, which compresses only the names of the requests allowed by
+    ///  `name_compression`, for servers, or middleboxes on the way, which fail to decode pointers.
+    ///
+    /// See `with_timeout` for the other arguments.
+    pub fn with_name_compression(
+        stream: Box<Future<Item = S, Error = io::Error>>,
+        stream_handle: Box<DnsStreamHandle>,
+        loop_handle: &Handle,
+        timeout_duration: Duration,
+        signer: Option<MF>,
+        name_compression: NameCompression,
+    ) -> BasicDnsHandle {
+        Self::with_retry_policy(
+            stream,
+            stream_handle,
+            loop_handle,
+            RetryPolicy::new(timeout_duration),
+            signer,
+            name_compression,
+        )
+    }
+
+    /// Spawns a new DnsFuture Stream, which retransmits the requests which are not answered as
+    ///  the `retry_policy` allows, e.g. over UDP.
+    ///
+    /// See `with_name_compression` for the other arguments.
+    pub fn with_retry_policy(
+        stream: Box<Future<Item = S, Error = io::Error>>,
+        stream_handle: Box<DnsStreamHandle>,
+        loop_handle: &Handle,
+        retry_policy: RetryPolicy,
+        signer: Option<MF>,
+        name_compression: NameCompression,
     ) -> BasicDnsHandle {
         let (sender, rx) = unbounded();
 
         let loop_handle_clone = loop_handle.clone();
         loop_handle.spawn(
             stream
+                .and_then(|stream| -> io::Result<(S, OsRng)> {
+                    // the requests fail rather than be sent with ids which may be predicted
+                    let query_ids = try!(OsRng::new().map_err(|e| {
+                        io::Error::new(
+                            io::ErrorKind::Other,
+                            format!("could not open the OS random number generator: {}", e),
+                        )
+                    }));
+                    Ok((stream, query_ids))
+                })
                 .then(move |res| match res {
-                    Ok(stream) => {
+                    Ok((stream, query_ids)) => {
                         ClientStreamOrError::Future(DnsFuture {
                             stream: stream,
                             reactor_handle: loop_handle_clone,
-                            timeout_duration: timeout_duration,
+                            retry_policy: retry_policy,
                             stream_handle: stream_handle,
                             new_receiver: rx.fuse().peekable(),
                             active_requests: HashMap::new(),
                             signer: signer,
+                            name_compression: name_compression,
+                            query_ids: query_ids,
                         })
                     }
                     Err(stream_error) => {
@@ -149,16 +353,16 @@ where
     fn drop_cancelled(&mut self) {
         // TODO: should we have a timeout here? or always expect the caller to do this?
         let mut canceled = HashSet::new();
-        for (&id, &mut (ref mut req, ref mut timeout)) in self.active_requests.iter_mut() {
-            if let Ok(Async::Ready(())) = req.poll_cancel() {
+        let mut timed_out = HashSet::new();
+        for (&id, request) in self.active_requests.iter_mut() {
+            if let Ok(Async::Ready(())) = request.complete.poll_cancel() {
               canceled.insert(id);
             }
 
             // check for timeouts...
-            match timeout.poll() {
+            match request.timeout.poll() {
                 Ok(Async::Ready(_)) => {
-                    warn!("request timeout: {}", id);
-                    canceled.insert(id);
+                    timed_out.insert(id);
                 }
                 Ok(Async::NotReady) => (),
                 Err(e) => {
@@ -170,25 +374,216 @@ where
 
         // drop all the canceled requests
         for id in canceled {
-            if let Some((req, _)) = self.active_requests.remove(&id) {
+            if let Some(request) = self.active_requests.remove(&id) {
                 // TODO, perhaps there is a different reason timeout? but there shouldn't be...
                 //  being lazy and always returning timeout in this case (if it was canceled then the
                 //  then the otherside isn't really paying attention anyway)
 
                 // complete the request, it's failed...
-                req.send(Err(ProtoErrorKind::Timeout.into())).expect(
-                    "error notifying wait, possible future leak",
+                complete_request(request.complete, Err(ProtoErrorKind::Timeout.into()));
+            }
+        }
+
+        // retransmit the timed out requests, or fail them after their last attempt
+        for id in timed_out {
+            if let Some(request) = self.active_requests.remove(&id) {
+                self.retransmit(id, request);
+            }
+        }
+    }
+
+    /// Sends the request again with a new query id, or fails it if that was the last attempt
+    fn retransmit(&mut self, id: u16, request: ActiveRequest) {
+        let ActiveRequest {
+            complete,
+            queries,
+            signatures,
+            message,
+            unsigned,
+            attempt,
+            ..
+        } = request;
+        let attempt = attempt + 1;
+
+        let mut message = match message {
+            Some(ref message) if attempt < self.retry_policy.attempts() => message.clone(),
+            _ => {
+                warn!("request timeout: {}", id);
+                complete_request(complete, Err(ProtoErrorKind::Timeout.into()));
+                return;
+            }
+        };
+
+        // a new id, so that a spoofed response has to guess it again
+        let query_id = match self.next_random_query_id() {
+            Async::Ready(query_id) => query_id,
+            Async::NotReady => {
+                warn!("request timeout, no query id to retransmit it: {}", id);
+                complete_request(complete, Err(ProtoErrorKind::Timeout.into()));
+                return;
+            }
+        };
+        message.set_id(query_id);
+
+        let timeout = self.retry_policy.jittered_timeout(attempt);
+        let sent = Timeout::new(timeout, &self.reactor_handle)
+            .map_err(ProtoError::from)
+            .and_then(|timeout| {
+                let buffer = try!(message.to_vec_with_name_compression(
+                    self.name_compression_of(&message),
+                ));
+                try!(self.stream_handle.send(buffer));
+                Ok(timeout)
+            });
+
+        match sent {
+            Ok(timeout) => {
+                debug!(
+                    "retransmitting request {} as {}, attempt {}",
+                    id,
+                    query_id,
+                    attempt + 1
+                );
+                self.active_requests.insert(
+                    query_id,
+                    ActiveRequest {
+                        complete: complete,
+                        timeout: timeout,
+                        queries: queries,
+                        signatures: signatures,
+                        message: Some(message),
+                        unsigned: unsigned,
+                        attempt: attempt,
+                    },
                 );
+
+                // the new timeout is polled on the next turn
+                task::current().notify();
+            }
+            Err(e) => {
+                warn!("could not retransmit request {}: {}", id, e);
+                complete_request(complete, Err(e));
             }
         }
     }
 
-    /// creates random query_id, validates against all active queries
-    fn next_random_query_id(&self) -> Async<u16> {
-        let mut rand = rand::thread_rng();
+    /// Sends the requests which were lost by the stream again, as the `retry_policy` allows, with
+    ///  new query ids, or fails them with the error, e.g. those sent on the connection of a
+    ///  `TcpClientStream` which was closed before their responses arrived
+    fn requests_lost(&mut self, error: &io::Error) {
+        if self.active_requests.is_empty() {
+            debug!("stream error, no requests lost: {}", error);
+            return;
+        }
+
+        warn!("{} requests lost: {}", self.active_requests.len(), error);
+        let lost = self.active_requests.drain().collect::<Vec<_>>();
+        for (id, request) in lost {
+            if request.message.is_some() && request.attempt + 1 < self.retry_policy.attempts() {
+                self.retransmit(id, request);
+            } else {
+                let failed = io::Error::new(error.kind(), format!("{}", error));
+                complete_request(request.complete, Err(ProtoErrorKind::Io(failed).into()));
+            }
+        }
+    }
+
+    /// Signs the request again with the time of the server, which the signer took from its
+    ///  BADTIME response, and sends it once more with a new query id, or fails the request with
+    ///  the clock skew if it was already signed again
+    fn sign_again(&mut self, id: u16, request: ActiveRequest, error: ProtoError) {
+        let ActiveRequest {
+            complete,
+            timeout,
+            queries,
+            message,
+            unsigned,
+            attempt,
+            ..
+        } = request;
+
+        let mut unsigned = match unsigned {
+            Some(unsigned) => unsigned,
+            None => {
+                warn!("request {} failed: {}", id, error);
+                complete_request(complete, Err(error));
+                return;
+            }
+        };
+
+        let query_id = match self.next_random_query_id() {
+            Async::Ready(query_id) => query_id,
+            Async::NotReady => {
+                warn!("no query id to sign request again: {}", id);
+                complete_request(complete, Err(error));
+                return;
+            }
+        };
+        unsigned.set_id(query_id);
+
+        let mut signed = unsigned;
+        let finalized = match self.signer {
+            Some(ref signer) => signed.finalize(signer, Utc::now().timestamp() as u32),
+            None => Ok(()),
+        };
+        let sent = finalized.and_then(|_| {
+            let buffer = try!(signed.to_vec_with_name_compression(
+                self.name_compression_of(&signed),
+            ));
+            self.stream_handle.send(buffer)
+        });
+
+        match sent {
+            Ok(()) => {
+                debug!("signed request {} again as {} with the time of the server", id, query_id);
+                self.active_requests.insert(
+                    query_id,
+                    ActiveRequest {
+                        complete: complete,
+                        timeout: timeout,
+                        queries: queries,
+                        signatures: signed.sig0().to_vec(),
+                        message: message.map(|_| signed),
+                        unsigned: None,
+                        attempt: attempt,
+                    },
+                );
+            }
+            Err(e) => {
+                warn!("could not sign request {} again: {}", id, e);
+                complete_request(complete, Err(e));
+            }
+        }
+    }
+
+    /// The name compression of the message, a signature covers the message as the signer encoded
+    ///  it, with all names compressed
+    fn name_compression_of(&self, message: &Message) -> NameCompression {
+        if message.sig0().is_empty() {
+            self.name_compression
+        } else {
+            NameCompression::All
+        }
+    }
+
+    /// Verifies the response with the signer, if the request was signed
+    fn verify_response(&self, request: &ActiveRequest, response: &Message) -> ProtoResult<()> {
+        match self.signer {
+            Some(ref signer) if !request.signatures.is_empty() => {
+                signer.verify_response(
+                    &request.signatures,
+                    response,
+                    Utc::now().timestamp() as u32,
+                )
+            }
+            _ => Ok(()),
+        }
+    }
 
+    /// creates random query_id, validates against all active queries
+    fn next_random_query_id(&mut self) -> Async<u16> {
         for _ in 0..100 {
-            let id = rand.gen_range(0_u16, u16::max_value());
+            let id: u16 = self.query_ids.gen();
 
             if !self.active_requests.contains_key(&id) {
                 return Async::Ready(id);
@@ -241,53 +636,70 @@ where
                     let query_id = query_id.expect("query_id should have been set above");
                     message.set_id(query_id);
 
-                    // update messages need to be signed.
-                    if let OpCode::Update = message.op_code() {
+                    // the meaning of more than one query was never defined, and servers answer
+                    //  them with FormErr, so these are refused before they are sent, RFC 9619
+                    if message.queries().len() != 1 {
+                        warn!("refusing to send message with {} queries", message.queries().len());
+                        let count = message.queries().len();
+                        complete_request(complete, Err(ProtoErrorKind::QueryCount(count).into()));
+                        continue; // to the next message...
+                    }
+
+                    // update and zone transfer messages need to be signed.
+                    let mut unsigned = None;
+                    if is_signed(&message) {
                         if let Some(ref signer) = self.signer {
+                            unsigned = Some(message.clone());
                             if let Err(e) = message.finalize(
                                 signer,
                                 Utc::now().timestamp() as u32,
                             )
                             {
                                 warn!("could not sign message: {}", e);
-                                complete.send(Err(e.into())).expect(
-                                    "error notifying wait, possible future leak",
-                                );
+                                complete_request(complete, Err(e.into()));
                                 continue; // to the next message...
                             }
                         }
                     }
 
                     // store a Timeout for this message before sending
-                    let timeout = match Timeout::new(self.timeout_duration, &self.reactor_handle) {
+                    let timeout_duration = self.retry_policy.jittered_timeout(0);
+                    let timeout = match Timeout::new(timeout_duration, &self.reactor_handle) {
                         Ok(timeout) => timeout,
                         Err(e) => {
                             warn!("could not create timer: {}", e);
-                            complete.send(Err(e.into())).expect(
-                                "error notifying wait, possible future leak",
-                            );
+                            complete_request(complete, Err(e.into()));
                             continue; // to the next message...
                         }
                     };
 
                     // send the message
-                    match message.to_vec() {
+                    let name_compression = self.name_compression_of(&message);
+                    match message.to_vec_with_name_compression(name_compression) {
                         Ok(buffer) => {
                             debug!("sending message id: {}", query_id);
                             try!(self.stream_handle.send(buffer));
                             // add to the map -after- the client send b/c we don't want to put it in the map if
                             //  we ended up returning from the send.
+                            let is_retransmitted = self.retry_policy.attempts() > 1 &&
+                                message.op_code() != OpCode::Update;
                             self.active_requests.insert(
                                 message.id(),
-                                (complete, timeout),
+                                ActiveRequest {
+                                    complete: complete,
+                                    timeout: timeout,
+                                    queries: message.queries().to_vec(),
+                                    signatures: message.sig0().to_vec(),
+                                    message: if is_retransmitted { Some(message) } else { None },
+                                    unsigned: unsigned,
+                                    attempt: 0,
+                                },
                             );
                         }
                         Err(e) => {
                             debug!("error message id: {} error: {}", query_id, e);
                             // complete with the error, don't add to the map of active requests
-                            complete.send(Err(e.into())).expect(
-                                "error notifying wait, possible future leak",
-                            );
+                            complete_request(complete, Err(e.into()));
                         }
                     }
                 }
@@ -303,21 +715,65 @@ where
         //   by having a max we will guarantee that the client can't be DOSed in this loop
         // TODO: make the QoS configurable
         let mut messages_received = 0;
+        let mut stream_error = None;
         for i in 0..QOS_MAX_RECEIVE_MSGS {
-            match try!(self.stream.poll()) {
+            let polled = match self.stream.poll() {
+                Ok(polled) => polled,
+                Err(e) => {
+                    // e.g. the connection of a TcpClientStream was lost, the stream may go on
+                    self.requests_lost(&e);
+                    stream_error = Some(e);
+                    continue;
+                }
+            };
+
+            match polled {
                 Async::Ready(Some(buffer)) => {
                     messages_received = i;
 
                     //   deserialize or log decode_error
                     match Message::from_vec(&buffer) {
                         Ok(message) => {
-                            match self.active_requests.remove(&message.id()) {
-                                Some((complete, _)) => {
-                                    complete.send(Ok(message)).expect(
-                                        "error notifying wait, possible future leak",
-                                    )
+                            let (is_answer, verified) = match self.active_requests
+                                .get(&message.id()) {
+                                Some(request) => (
+                                    is_answer_to(&request.queries, &message),
+                                    self.verify_response(request, &message),
+                                ),
+                                None => {
+                                    debug!("unexpected request_id: {}", message.id());
+                                    continue;
                                 }
-                                None => debug!("unexpected request_id: {}", message.id()),
+                            };
+
+                            // the request keeps waiting, a spoofed response must not fail it
+                            if !is_answer {
+                                debug!(
+                                    "response {} does not match the question, ignoring it",
+                                    message.id()
+                                );
+                                continue;
+                            }
+
+                            // the server rejected the time of the request, which is signed
+                            //  again with the time of the server from its response
+                            if let Err(e) = verified {
+                                if let ProtoErrorKind::TsigClockSkew(_) = *e.kind() {
+                                    if let Some(request) =
+                                        self.active_requests.remove(&message.id())
+                                    {
+                                        self.sign_again(message.id(), request, e);
+                                    }
+                                    continue;
+                                }
+
+                                // as above, a forged response must not fail the request
+                                warn!("response {} failed verification: {}", message.id(), e);
+                                continue;
+                            }
+
+                            if let Some(request) = self.active_requests.remove(&message.id()) {
+                                complete_request(request.complete, Ok(message))
                             }
                         }
                         // TODO: return src address for diagnostics
@@ -325,7 +781,13 @@ where
                     }
 
                 }
-                Async::Ready(None) |
+                // the stream ended with the error
+                Async::Ready(None) => {
+                    if let Some(e) = stream_error.take() {
+                        return Err(e.into());
+                    }
+                    break;
+                }
                 Async::NotReady => break,
             }
         }
@@ -370,9 +832,10 @@ impl Future for ClientStreamErrored {
     fn poll(&mut self) -> Poll<(), Self::Error> {
         match self.new_receiver.poll() {
             Ok(Async::Ready(Some((_, complete)))) => {
-                complete
-                    .send(Err(ProtoErrorKind::Msg(self.error_msg.clone()).into()))
-                    .expect("error notifying wait, possible future leak");
+                complete_request(
+                    complete,
+                    Err(ProtoErrorKind::Msg(self.error_msg.clone()).into()),
+                );
 
                 task::current().notify();
                 return Ok(Async::NotReady);
@@ -464,3 +927,79 @@ pub trait DnsHandle: Clone {
     ///               being stable.
     fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = Self::Error>>;
 }
+
+#[test]
+fn test_retry_policy_backoff() {
+    let mut policy = RetryPolicy::new(Duration::from_millis(500));
+    assert_eq!(policy.attempts(), 1);
+    assert_eq!(policy.attempt_timeout(3), Duration::from_millis(500));
+
+    policy.set_attempts(4);
+    policy.set_max_timeout(Duration::from_secs(3));
+    assert_eq!(policy.attempt_timeout(0), Duration::from_millis(500));
+    assert_eq!(policy.attempt_timeout(1), Duration::from_secs(1));
+    assert_eq!(policy.attempt_timeout(2), Duration::from_secs(2));
+    assert_eq!(policy.attempt_timeout(3), Duration::from_secs(3));
+    assert_eq!(policy.attempt_timeout(100), Duration::from_secs(3));
+
+    policy.set_jitter_percent(20);
+    for attempt in 0..4 {
+        let timeout = policy.jittered_timeout(attempt);
+        let base = policy.attempt_timeout(attempt);
+        assert!(timeout >= base, "{:?} below {:?}", timeout, base);
+        assert!(timeout <= base + base / 5, "{:?} above {:?}", timeout, base);
+    }
+}
+
+#[test]
+fn test_is_answer_to() {
+    use std::str::FromStr;
+    use op::MessageType;
+    use rr::{Name, RecordType};
+
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+    let mut response = Message::new();
+    response.set_message_type(MessageType::Response);
+    response.add_query(query.clone());
+    assert!(is_answer_to(&[query.clone()], &response));
+
+    // the case is checked by the sender of a randomized query name
+    let upper = Query::query(Name::from_str("WWW.example.com.").unwrap(), RecordType::A);
+    assert!(is_answer_to(&[upper], &response));
+
+    let other = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::AAAA);
+    assert!(!is_answer_to(&[other], &response));
+
+    // no question, only for errors
+    let mut empty = Message::new();
+    empty.set_message_type(MessageType::Response);
+    assert!(!is_answer_to(&[query.clone()], &empty));
+    empty.set_response_code(ResponseCode::FormErr);
+    assert!(is_answer_to(&[query], &empty));
+}
+
+#[test]
+fn test_complete_abandoned_request() {
+    // the requester dropped its future, e.g. on a timeout of its own
+    let (complete, receiver) = oneshot::channel();
+    drop(receiver);
+    complete_request(complete, Err(ProtoErrorKind::Timeout.into()));
+}
+
+#[test]
+fn test_is_signed() {
+    use std::str::FromStr;
+    use rr::Name;
+
+    let name = Name::from_str("example.com.").unwrap();
+    let mut message = Message::new();
+    message.add_query(Query::query(name.clone(), RecordType::A));
+    assert!(!is_signed(&message));
+
+    message.set_op_code(OpCode::Update);
+    assert!(is_signed(&message));
+
+    let mut transfer = Message::new();
+    transfer.add_query(Query::query(name, RecordType::AXFR));
+    assert!(is_signed(&transfer));
+}
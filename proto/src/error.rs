@@ -117,11 +117,37 @@ error_chain! {
         display("incorrect rdata length read: {} expected: {}", read, len)
       }
 
+      RecordNotAllowedInSection(record_type: ::rr::RecordType, section: ::op::MessageSection) {
+        description("record not allowed in the section of the message")
+        display("{} record not allowed in the {} section of the message", record_type, section)
+      }
+
+      InvalidQuery(query_type: ::rr::RecordType, query_class: ::rr::DNSClass,
+                   reason: &'static str) {
+        description("invalid combination of query type and class")
+        display("invalid query of type {} in class {}: {}", query_type, query_class, reason)
+      }
+
+      QueryCount(count: usize) {
+        description("messages must contain exactly one query")
+        display("messages must contain exactly one query, found: {}", count)
+      }
+
       Timeout {
         description("request timeout")
         display("request timed out")
       }
 
+      Tsig(error: u16) {
+        description("transaction signature not verified")
+        display("transaction signature not verified, TSIG error: {}", error)
+      }
+
+      TsigClockSkew(skew: i64) {
+        description("clock differs from the server beyond the fudge of the TSIG")
+        display("clock differs from the server by {} seconds, beyond the fudge of the TSIG", skew)
+      }
+
       UnknownAlgorithmTypeValue(value: u8) {
         description("algorithm type value unknown")
         display("algorithm type value unknown: {}", value)
@@ -296,6 +322,9 @@ impl Clone for ProtoError {
             &ProtoErrorKind::IncorrectMessageId(got, expect) => {
                 ProtoErrorKind::IncorrectMessageId(got, expect)
             }
+            &ProtoErrorKind::InvalidQuery(query_type, query_class, reason) => {
+                ProtoErrorKind::InvalidQuery(query_type, query_class, reason)
+            }
             &ProtoErrorKind::IncorrectRDataLengthRead(read, len) => {
                 ProtoErrorKind::IncorrectRDataLengthRead(read, len)
             }
@@ -310,7 +339,13 @@ impl Clone for ProtoError {
                 ProtoErrorKind::NotAllBytesReceived(received, expect)
             }
             &ProtoErrorKind::ParseIntError => ProtoErrorKind::ParseIntError,
+            &ProtoErrorKind::QueryCount(count) => ProtoErrorKind::QueryCount(count),
+            &ProtoErrorKind::RecordNotAllowedInSection(record_type, section) => {
+                ProtoErrorKind::RecordNotAllowedInSection(record_type, section)
+            }
             &ProtoErrorKind::Timeout => ProtoErrorKind::Timeout,
+            &ProtoErrorKind::Tsig(error) => ProtoErrorKind::Tsig(error),
+            &ProtoErrorKind::TsigClockSkew(skew) => ProtoErrorKind::TsigClockSkew(skew),
             &ProtoErrorKind::UnknownAlgorithmTypeValue(value) => {
                 ProtoErrorKind::UnknownAlgorithmTypeValue(value)
             }
@@ -20,6 +20,8 @@ extern crate lazy_static;
 extern crate log;
 #[macro_use]
 extern crate futures;
+#[cfg(feature = "mdns")]
+extern crate net2;
 #[cfg(feature = "openssl")]
 extern crate openssl;
 extern crate rand;
@@ -37,13 +39,16 @@ use futures::sync::mpsc::UnboundedSender;
 
 mod dns_handle;
 pub mod error;
+#[cfg(feature = "mdns")]
+pub mod multicast;
 pub mod op;
 pub mod rr;
 pub mod serialize;
 pub mod tcp;
 pub mod udp;
 
-pub use dns_handle::{BasicDnsHandle, DnsFuture, DnsHandle, DnsStreamHandle, StreamHandle};
+pub use dns_handle::{BasicDnsHandle, DnsFuture, DnsHandle, DnsStreamHandle, RetryPolicy,
+                     StreamHandle};
 use op::Message;
 use error::*;
 
@@ -0,0 +1,95 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::io;
+
+use futures::{Async, Future, Poll, Stream};
+use tokio_core::reactor::Handle;
+
+use BufDnsStreamHandle;
+use BufStreamHandle;
+use DnsStreamHandle;
+use multicast::{MdnsQueryType, MdnsStream, MDNS_IPV4, MDNS_IPV6};
+
+/// A DNS client over mDNS, all messages are sent to the multicast group
+///
+/// In `MdnsQueryType::OneShot` mode the responses to each query are returned. In
+///  `MdnsQueryType::Continuous` mode every message received from the link is returned, the
+///  caller is expected to filter these.
+#[must_use = "futures do nothing unless polled"]
+pub struct MdnsClientStream {
+    mdns_stream: MdnsStream,
+}
+
+impl MdnsClientStream {
+    /// Creates a new mDNS client stream over ipv4
+    ///
+    /// # Arguments
+    ///
+    /// * `mdns_query_type` - one-shot or continuous querying, see `MdnsQueryType`
+    /// * `interfaces` - addresses of the interfaces on which to join the multicast group, empty
+    ///                  for the default interface
+    /// * `loop_handle` - handle to the IO loop
+    pub fn new_ipv4(
+        mdns_query_type: MdnsQueryType,
+        interfaces: Vec<Ipv4Addr>,
+        loop_handle: &Handle,
+    ) -> (Box<Future<Item = MdnsClientStream, Error = io::Error>>, Box<DnsStreamHandle>) {
+        let (stream_future, sender) =
+            MdnsStream::new_ipv4(mdns_query_type, interfaces, loop_handle);
+        Self::new(*MDNS_IPV4, stream_future, sender)
+    }
+
+    /// Creates a new mDNS client stream over ipv6
+    ///
+    /// # Arguments
+    ///
+    /// * `mdns_query_type` - one-shot or continuous querying, see `MdnsQueryType`
+    /// * `interfaces` - indexes of the interfaces on which to join the multicast group, empty for
+    ///                  the default interface
+    /// * `loop_handle` - handle to the IO loop
+    pub fn new_ipv6(
+        mdns_query_type: MdnsQueryType,
+        interfaces: Vec<u32>,
+        loop_handle: &Handle,
+    ) -> (Box<Future<Item = MdnsClientStream, Error = io::Error>>, Box<DnsStreamHandle>) {
+        let (stream_future, sender) =
+            MdnsStream::new_ipv6(mdns_query_type, interfaces, loop_handle);
+        Self::new(*MDNS_IPV6, stream_future, sender)
+    }
+
+    fn new(
+        multicast_addr: SocketAddr,
+        stream_future: Box<Future<Item = MdnsStream, Error = io::Error>>,
+        sender: BufStreamHandle,
+    ) -> (Box<Future<Item = MdnsClientStream, Error = io::Error>>, Box<DnsStreamHandle>) {
+        let new_future: Box<Future<Item = MdnsClientStream, Error = io::Error>> =
+            Box::new(stream_future.map(move |mdns_stream| {
+                MdnsClientStream { mdns_stream: mdns_stream }
+            }));
+
+        let sender = Box::new(BufDnsStreamHandle::new(multicast_addr, sender));
+
+        (new_future, sender)
+    }
+}
+
+impl Stream for MdnsClientStream {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match try_ready!(self.mdns_stream.poll()) {
+            Some((buffer, src_addr)) => {
+                debug!("mdns message from: {}", src_addr);
+                Ok(Async::Ready(Some(buffer)))
+            }
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
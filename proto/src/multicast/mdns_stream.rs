@@ -0,0 +1,298 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::io;
+
+use futures::{future, Async, Future, Poll};
+use futures::stream::{Fuse, Peekable, Stream};
+use futures::sync::mpsc::{unbounded, UnboundedReceiver};
+use net2::{UdpBuilder, UdpSocketExt};
+use tokio_core;
+use tokio_core::reactor::Handle;
+
+use BufStreamHandle;
+
+/// The port used for all mDNS traffic
+pub const MDNS_PORT: u16 = 5353;
+
+/// All mDNS packets are sent with an IP TTL, or hop limit, of 255, RFC 6762 section 11
+const MDNS_PACKET_TTL: u32 = 255;
+
+/// mDNS messages may be up to 9000 bytes, RFC 6762 section 17
+const MDNS_MAX_PAYLOAD: usize = 9000;
+
+lazy_static! {
+    /// mDNS ipv4 address, https://www.iana.org/assignments/multicast-addresses/multicast-addresses.xhtml
+    pub static ref MDNS_IPV4: SocketAddr = SocketAddr::new(
+        IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)),
+        MDNS_PORT,
+    );
+    /// link-local mDNS ipv6 address, https://www.iana.org/assignments/ipv6-multicast-addresses/ipv6-multicast-addresses.xhtml
+    pub static ref MDNS_IPV6: SocketAddr = SocketAddr::new(
+        IpAddr::V6(Ipv6Addr::new(0xFF02, 0, 0, 0, 0, 0, 0, 0x00FB)),
+        MDNS_PORT,
+    );
+}
+
+/// The type of mDNS query, see [RFC 6762 section 5](https://tools.ietf.org/html/rfc6762#section-5)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MdnsQueryType {
+    /// Queries are sent from an ephemeral port, responders answer these directly to the querier
+    ///  by unicast and nothing else is received, RFC 6762 section 5.1. This is the mode for
+    ///  simple lookups, and can run along side a system mDNS responder.
+    OneShot,
+    /// The socket is bound to the mDNS port and joins the multicast group on each interface.
+    ///  Answers are multicast, and every answer on the link is received, including those to
+    ///  queries from other hosts and unsolicited announcements, RFC 6762 section 5.2.
+    Continuous,
+}
+
+/// A UDP stream of mDNS messages, sent to the multicast group and received from any host on the
+///  link
+#[must_use = "futures do nothing unless polled"]
+pub struct MdnsStream {
+    socket: tokio_core::net::UdpSocket,
+    outbound_messages: Peekable<Fuse<UnboundedReceiver<(Vec<u8>, SocketAddr)>>>,
+}
+
+impl MdnsStream {
+    /// Creates a new mDNS stream over ipv4, see `new`
+    ///
+    /// # Arguments
+    ///
+    /// * `mdns_query_type` - one-shot or continuous querying, see `MdnsQueryType`
+    /// * `interfaces` - addresses of the interfaces on which to join the multicast group, empty
+    ///                  for the default interface. Queries are sent on the first interface.
+    /// * `loop_handle` - handle to the IO loop
+    pub fn new_ipv4(
+        mdns_query_type: MdnsQueryType,
+        interfaces: Vec<Ipv4Addr>,
+        loop_handle: &Handle,
+    ) -> (Box<Future<Item = MdnsStream, Error = io::Error>>, BufStreamHandle) {
+        Self::new(
+            future::lazy(move || Self::bind_ipv4(mdns_query_type, &interfaces)),
+            loop_handle,
+        )
+    }
+
+    /// Creates a new mDNS stream over ipv6, see `new`
+    ///
+    /// # Arguments
+    ///
+    /// * `mdns_query_type` - one-shot or continuous querying, see `MdnsQueryType`
+    /// * `interfaces` - indexes of the interfaces on which to join the multicast group, empty for
+    ///                  the default interface. Queries are sent on the first interface.
+    /// * `loop_handle` - handle to the IO loop
+    pub fn new_ipv6(
+        mdns_query_type: MdnsQueryType,
+        interfaces: Vec<u32>,
+        loop_handle: &Handle,
+    ) -> (Box<Future<Item = MdnsStream, Error = io::Error>>, BufStreamHandle) {
+        Self::new(
+            future::lazy(move || Self::bind_ipv6(mdns_query_type, &interfaces)),
+            loop_handle,
+        )
+    }
+
+    /// Registers the socket, once bound, with the IO loop
+    ///
+    /// # Return
+    ///
+    /// a tuple of a Future Stream which will handle sending and receiving messsages, and a
+    ///  handle which can be used to send messages into the stream.
+    fn new<F>(
+        next_socket: F,
+        loop_handle: &Handle,
+    ) -> (Box<Future<Item = MdnsStream, Error = io::Error>>, BufStreamHandle)
+    where
+        F: Future<Item = std::net::UdpSocket, Error = io::Error> + 'static,
+    {
+        let (message_sender, outbound_messages) = unbounded();
+        let handle = loop_handle.clone();
+
+        let stream: Box<Future<Item = MdnsStream, Error = io::Error>> =
+            Box::new(next_socket.and_then(move |socket| {
+                tokio_core::net::UdpSocket::from_socket(socket, &handle).map(|socket| {
+                    MdnsStream {
+                        socket: socket,
+                        outbound_messages: outbound_messages.fuse().peekable(),
+                    }
+                })
+            }));
+
+        (stream, message_sender)
+    }
+
+    fn bind_ipv4(
+        mdns_query_type: MdnsQueryType,
+        interfaces: &[Ipv4Addr],
+    ) -> io::Result<std::net::UdpSocket> {
+        let multicast_addr = match *MDNS_IPV4 {
+            SocketAddr::V4(addr) => *addr.ip(),
+            SocketAddr::V6(..) => unreachable!("MDNS_IPV4 is not ipv4"),
+        };
+        let unspecified = Ipv4Addr::new(0, 0, 0, 0);
+
+        let socket = match mdns_query_type {
+            MdnsQueryType::OneShot => {
+                try!(std::net::UdpSocket::bind(
+                    SocketAddr::new(IpAddr::V4(unspecified), 0),
+                ))
+            }
+            MdnsQueryType::Continuous => {
+                let builder = try!(UdpBuilder::new_v4());
+                try!(Self::reuse(&builder));
+                let socket = try!(builder.bind(
+                    SocketAddr::new(IpAddr::V4(unspecified), MDNS_PORT),
+                ));
+
+                if interfaces.is_empty() {
+                    try!(socket.join_multicast_v4(&multicast_addr, &unspecified));
+                }
+                for interface in interfaces {
+                    debug!("joining {} on: {}", multicast_addr, interface);
+                    try!(socket.join_multicast_v4(&multicast_addr, interface));
+                }
+
+                socket
+            }
+        };
+
+        if let Some(interface) = interfaces.first() {
+            try!(socket.set_multicast_if_v4(interface));
+        }
+        try!(socket.set_multicast_ttl_v4(MDNS_PACKET_TTL));
+
+        Ok(socket)
+    }
+
+    fn bind_ipv6(
+        mdns_query_type: MdnsQueryType,
+        interfaces: &[u32],
+    ) -> io::Result<std::net::UdpSocket> {
+        let multicast_addr = match *MDNS_IPV6 {
+            SocketAddr::V6(addr) => *addr.ip(),
+            SocketAddr::V4(..) => unreachable!("MDNS_IPV6 is not ipv6"),
+        };
+        let unspecified = Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0);
+
+        let socket = match mdns_query_type {
+            MdnsQueryType::OneShot => {
+                try!(std::net::UdpSocket::bind(
+                    SocketAddr::new(IpAddr::V6(unspecified), 0),
+                ))
+            }
+            MdnsQueryType::Continuous => {
+                let builder = try!(UdpBuilder::new_v6());
+                try!(builder.only_v6(true));
+                try!(Self::reuse(&builder));
+                let socket = try!(builder.bind(
+                    SocketAddr::new(IpAddr::V6(unspecified), MDNS_PORT),
+                ));
+
+                // interface 0 lets the OS choose
+                if interfaces.is_empty() {
+                    try!(socket.join_multicast_v6(&multicast_addr, 0));
+                }
+                for interface in interfaces {
+                    debug!("joining {} on interface: {}", multicast_addr, interface);
+                    try!(socket.join_multicast_v6(&multicast_addr, *interface));
+                }
+
+                socket
+            }
+        };
+
+        if let Some(interface) = interfaces.first() {
+            try!(socket.set_multicast_if_v6(*interface));
+        }
+        try!(socket.set_multicast_hops_v6(MDNS_PACKET_TTL));
+
+        Ok(socket)
+    }
+
+    /// The mDNS port is generally shared with the system responder, and other queriers
+    fn reuse(builder: &UdpBuilder) -> io::Result<()> {
+        try!(builder.reuse_address(true));
+
+        #[cfg(unix)]
+        {
+            use net2::unix::UnixUdpBuilderExt;
+            try!(builder.reuse_port(true));
+        }
+
+        Ok(())
+    }
+}
+
+impl Stream for MdnsStream {
+    type Item = (Vec<u8>, SocketAddr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        // this will not accept incoming data while there is data to send
+        //  makes this self throttling.
+        loop {
+            // first try to send
+            match try!(self.outbound_messages.peek().map_err(|()| {
+                io::Error::new(io::ErrorKind::Other, "unknown")
+            })) {
+                Async::Ready(Some(&(ref buffer, addr))) => {
+                    match self.socket.poll_write() {
+                        Async::NotReady => return Ok(Async::NotReady),
+                        Async::Ready(_) => {
+                            // will return if the socket will block
+                            try_nb!(self.socket.send_to(buffer, &addr));
+                        }
+                    }
+                }
+                // all others will drop through to the poll()
+                _ => (),
+            }
+
+            // now pop the request and check if we should break or continue.
+            match try!(self.outbound_messages.poll().map_err(|()| {
+                io::Error::new(io::ErrorKind::Other, "unknown")
+            })) {
+                // already handled above, here to make sure the poll() pops the next message
+                Async::Ready(Some(_)) => (),
+                // now we get to drop through to the receives...
+                Async::NotReady | Async::Ready(None) => break,
+            }
+        }
+
+        let mut buf = [0u8; MDNS_MAX_PAYLOAD];
+
+        // responses come from any host on the link, there is no single peer to check against
+        let (len, src) = try_nb!(self.socket.recv_from(&mut buf));
+        Ok(Async::Ready(
+            Some((buf.iter().take(len).cloned().collect(), src)),
+        ))
+    }
+}
+
+#[test]
+fn test_one_shot_mdns_stream_ipv4() {
+    use tokio_core::reactor::Core;
+
+    let mut io_loop = Core::new().unwrap();
+    let (stream, _) = MdnsStream::new_ipv4(MdnsQueryType::OneShot, vec![], &io_loop.handle());
+    let stream = io_loop.run(stream).expect("failed to bind one-shot mdns socket");
+
+    // one-shot queries must not use the mDNS port, otherwise responders multicast the answers
+    assert_ne!(stream.socket.local_addr().unwrap().port(), MDNS_PORT);
+}
+
+#[test]
+fn test_mdns_addrs() {
+    assert!(MDNS_IPV4.ip().is_multicast());
+    assert!(MDNS_IPV6.ip().is_multicast());
+    assert_eq!(MDNS_IPV4.port(), MDNS_PORT);
+    assert_eq!(MDNS_IPV6.port(), MDNS_PORT);
+}
@@ -18,7 +18,7 @@
 
 use rr::{DNSClass, Name, Record, RecordType, RData};
 use rr::rdata::OPT;
-use rr::rdata::opt::{EdnsCode, EdnsOption};
+use rr::rdata::opt::{ClientSubnet, Cookie, EdnsCode, EdnsOption, ExtendedError};
 
 /// Edns implements the higher level concepts for working with extended dns as it is used to create or be
 /// created from OPT record data.
@@ -74,6 +74,38 @@ impl Edns {
         self.options.get(code)
     }
 
+    /// Returns the EDNS Client Subnet option, RFC 7871, None if there is none or it is malformed
+    pub fn client_subnet(&self) -> Option<&ClientSubnet> {
+        match self.option(&EdnsCode::Subnet) {
+            Some(&EdnsOption::Subnet(ref subnet)) => Some(subnet),
+            _ => None,
+        }
+    }
+
+    /// Returns the DNS Cookie option, RFC 7873, None if there is none or it is malformed
+    pub fn cookie(&self) -> Option<&Cookie> {
+        match self.option(&EdnsCode::Cookie) {
+            Some(&EdnsOption::Cookie(ref cookie)) => Some(cookie),
+            _ => None,
+        }
+    }
+
+    /// Returns the Extended DNS Error option, RFC 8914, None if there is none or it is malformed
+    pub fn extended_error(&self) -> Option<&ExtendedError> {
+        match self.option(&EdnsCode::ExtendedError) {
+            Some(&EdnsOption::ExtendedError(ref error)) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of bytes of the Padding option, RFC 7830, None if there is none
+    pub fn padding(&self) -> Option<u16> {
+        match self.option(&EdnsCode::Padding) {
+            Some(&EdnsOption::Padding(len)) => Some(len),
+            _ => None,
+        }
+    }
+
     /// Returns the options portion of EDNS
     pub fn options(&self) -> &OPT {
         &self.options
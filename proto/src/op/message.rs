@@ -16,11 +16,15 @@
 
 //! Basic protocol message for DNS
 
+use std::cmp;
+use std::fmt;
 use std::mem;
 
 use error::*;
-use rr::{Record, RecordType};
-use serialize::binary::{BinEncoder, BinDecoder, BinSerializable, EncodeMode};
+use rr::{Name, RData, Record, RecordType};
+use rr::rdata::opt::EdnsOption;
+use serialize::binary::{BinEncoder, BinDecoder, BinSerializable, DecodeMode, DecodeWarning,
+                        EncodeMode, NameCompression};
 use super::{MessageType, Header, Query, Edns, OpCode, ResponseCode};
 
 /// The basic request and response datastructure, used for all DNS protocols.
@@ -74,6 +78,8 @@ pub struct Message {
     additionals: Vec<Record>,
     sig0: Vec<Record>,
     edns: Option<Edns>,
+    decode_warnings: Vec<DecodeWarning>,
+    signed_bytes: Option<Vec<u8>>,
 }
 
 impl Message {
@@ -87,6 +93,8 @@ impl Message {
             additionals: Vec::new(),
             sig0: Vec::new(),
             edns: None,
+            decode_warnings: Vec::new(),
+            signed_bytes: None,
         }
     }
 
@@ -181,8 +189,18 @@ impl Message {
     }
 
     /// see `Header::set_response_code`
+    ///
+    /// The high bits of an extended `ResponseCode`, e.g. BADCOOKIE, are set in the EDNS of the
+    ///  message, which is added if there is none, so the EDNS must be set before the code
     pub fn set_response_code(&mut self, response_code: ResponseCode) -> &mut Self {
         self.header.set_response_code(response_code);
+
+        let high = response_code.high();
+        if high != 0 {
+            self.edns_mut().set_rcode_high(high);
+        } else if let Some(edns) = self.edns.as_mut() {
+            edns.set_rcode_high(0);
+        }
         self
     }
 
@@ -216,6 +234,7 @@ impl Message {
     }
 
     /// Add an answer to the Message
+    #[deprecated = "does not check the record, use answers_mut().insert()"]
     pub fn add_answer(&mut self, record: Record) -> &mut Self {
         self.answers.push(record);
         self
@@ -227,20 +246,19 @@ impl Message {
         for &r in vector {
             // TODO: in order to get rid of this clone, we need an owned Message for decoding, and a
             //  reference Message for encoding.
-            self.add_answer(r.clone());
+            self.answers.push(r.clone());
         }
         self
     }
 
     /// Add all the records from the iterator to the answers section of the Message
+    #[deprecated = "does not check the records, use answers_mut().insert_all()"]
     pub fn add_answers<R, I>(&mut self, records: R) -> &mut Self
     where
         R: IntoIterator<Item = Record, IntoIter = I>,
         I: Iterator<Item = Record>,
     {
-        for record in records {
-            self.add_answer(record);
-        }
+        self.answers.extend(records);
 
         self
     }
@@ -256,6 +274,7 @@ impl Message {
     }
 
     /// Add a name server record to the Message
+    #[deprecated = "does not check the record, use name_servers_mut().insert()"]
     pub fn add_name_server(&mut self, record: Record) -> &mut Self {
         self.name_servers.push(record);
         self
@@ -267,20 +286,19 @@ impl Message {
         for &r in vector {
             // TODO: in order to get rid of this clone, we need an owned Message for decoding, and a
             //  reference Message for encoding.
-            self.add_name_server(r.clone());
+            self.name_servers.push(r.clone());
         }
         self
     }
 
     /// Add all the records in the Iterator to the name server section of the message
+    #[deprecated = "does not check the records, use name_servers_mut().insert_all()"]
     pub fn add_name_servers<R, I>(&mut self, records: R) -> &mut Self
     where
         R: IntoIterator<Item = Record, IntoIter = I>,
         I: Iterator<Item = Record>,
     {
-        for record in records {
-            self.add_name_server(record);
-        }
+        self.name_servers.extend(records);
 
         self
     }
@@ -296,6 +314,7 @@ impl Message {
     }
 
     /// A an addtional Record to the message
+    #[deprecated = "does not check the record, use additionals_mut().insert()"]
     pub fn add_additional(&mut self, record: Record) -> &mut Self {
         self.additionals.push(record);
         self
@@ -311,17 +330,43 @@ impl Message {
         self.additionals = records;
     }
 
+    /// Returns a handle for inserting records into the answer section, see `SectionMut::insert`
+    pub fn answers_mut(&mut self) -> SectionMut {
+        SectionMut {
+            message: self,
+            section: MessageSection::Answer,
+        }
+    }
+
+    /// Returns a handle for inserting records into the authority section, the update section of
+    ///  an update, see `SectionMut::insert`
+    pub fn name_servers_mut(&mut self) -> SectionMut {
+        SectionMut {
+            message: self,
+            section: MessageSection::Authority,
+        }
+    }
+
+    /// Returns a handle for inserting records into the additional section, see
+    ///  `SectionMut::insert`
+    pub fn additionals_mut(&mut self) -> SectionMut {
+        SectionMut {
+            message: self,
+            section: MessageSection::Additional,
+        }
+    }
+
     /// Add the EDNS section the the Message
     pub fn set_edns(&mut self, edns: Edns) -> &mut Self {
         self.edns = Some(edns);
         self
     }
 
-    /// Add a SIG0 record, i.e. sign this message
+    /// Add a SIG0 or TSIG record, i.e. sign this message
     ///
     /// This must be don't only after all records have been associated. Generally this will be handled by the client and not need to be used directly
     pub fn add_sig0(&mut self, record: Record) -> &mut Self {
-        assert_eq!(RecordType::SIG, record.rr_type());
+        assert!(record.rr_type() == RecordType::SIG || record.rr_type() == RecordType::TSIG);
         self.sig0.push(record);
         self
     }
@@ -389,6 +434,11 @@ impl Message {
         &self.queries
     }
 
+    /// Removes all the queries from the Message
+    pub fn take_queries(&mut self) -> Vec<Query> {
+        mem::replace(&mut self.queries, vec![])
+    }
+
     /// ```text
     /// Answer          Carries RRs which directly answer the query.
     /// ```
@@ -428,6 +478,79 @@ impl Message {
         mem::replace(&mut self.additionals, vec![])
     }
 
+    /// Removes the records of a response which are not about its query, returning the number
+    ///  removed, this is for responses before their records are cached
+    ///
+    /// The answers are kept for the name of the query and the names it is aliased to by the
+    ///  CNAMEs of the answers. The bailiwick of the response is the closest zone of those names
+    ///  with an SOA or NS record in the authority section, the authority records outside of it are
+    ///  removed, all of them if there is no such zone. The additional records are kept for the
+    ///  names of the NS, MX and SRV records kept, if they are within the bailiwick when there is
+    ///  one. Responses to other than one query are left as they are.
+    pub fn remove_out_of_bailiwick(&mut self) -> usize {
+        if self.queries.len() != 1 {
+            return 0;
+        }
+
+        // the query name and its aliases, a loop of CNAMEs ends the chain
+        let mut names = vec![self.queries[0].name().clone()];
+        loop {
+            let target = {
+                let name = names.last().expect("the query name is first");
+                self.answers
+                    .iter()
+                    .filter(|r| r.name() == name)
+                    .filter_map(|r| match *r.rdata() {
+                        RData::CNAME(ref target) => Some(target),
+                        _ => None,
+                    })
+                    .next()
+                    .cloned()
+            };
+
+            match target {
+                Some(target) if !names.contains(&target) => names.push(target),
+                _ => break,
+            }
+        }
+
+        let bailiwick = self.name_servers
+            .iter()
+            .filter(|r| r.rr_type() == RecordType::SOA || r.rr_type() == RecordType::NS)
+            .map(|r| r.name())
+            .filter(|zone| names.iter().any(|name| zone.zone_of(name)))
+            .max_by_key(|zone| zone.num_labels())
+            .cloned();
+        let in_bailiwick =
+            |name: &Name| bailiwick.as_ref().map_or(false, |zone| zone.zone_of(name));
+
+        let count = self.answers.len() + self.name_servers.len() + self.additionals.len();
+        self.answers.retain(|r| names.contains(r.name()));
+        self.name_servers.retain(|r| in_bailiwick(r.name()));
+
+        let targets = self.answers
+            .iter()
+            .chain(self.name_servers.iter())
+            .filter_map(|r| match *r.rdata() {
+                RData::NS(ref target) => Some(target.clone()),
+                RData::MX(ref mx) => Some(mx.exchange().clone()),
+                RData::SRV(ref srv) => Some(srv.target().clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let has_bailiwick = bailiwick.is_some();
+        self.additionals.retain(|r| {
+            targets.contains(r.name()) && (!has_bailiwick || in_bailiwick(r.name()))
+        });
+
+        let removed = count - self.answers.len() - self.name_servers.len() -
+            self.additionals.len();
+        if removed > 0 {
+            debug!("removed {} records out of bailiwick from response {}", removed, self.id());
+        }
+        removed
+    }
+
     /// [RFC 6891, EDNS(0) Extensions, April 2013](https://tools.ietf.org/html/rfc6891#section-6.1.1)
     ///
     /// ```text
@@ -461,6 +584,12 @@ impl Message {
         self.edns.as_ref()
     }
 
+    /// The problems which were passed over when the message was decoded in
+    ///  `DecodeMode::Lenient`, empty for a message which was not decoded or was decoded as sent
+    pub fn decode_warnings(&self) -> &[DecodeWarning] {
+        &self.decode_warnings
+    }
+
     /// If edns is_none, this will create a new default Edns.
     pub fn edns_mut(&mut self) -> &mut Edns {
         if self.edns.is_none() {
@@ -503,6 +632,24 @@ impl Message {
         &self.sig0
     }
 
+    /// The TSIG record which signs the message, RFC 8945, it is kept with the SIG0 records as it
+    ///  must also be the final record of the message
+    pub fn tsig(&self) -> Option<&Record> {
+        self.sig0.last().and_then(|record| if record.rr_type() == RecordType::TSIG {
+            Some(record)
+        } else {
+            None
+        })
+    }
+
+    /// The bytes of the message as it was received, up to the TSIG record, over which the MAC of
+    ///  the TSIG was computed
+    ///
+    /// None if the message was not decoded or was not signed with a TSIG.
+    pub fn signed_bytes(&self) -> Option<&[u8]> {
+        self.signed_bytes.as_ref().map(|bytes| &bytes[..])
+    }
+
     // TODO only necessary in tests, should it be removed?
     /// this is necessary to match the counts in the header from the record sections
     ///  this happens implicitly on write_to, so no need to call before write_to
@@ -538,16 +685,29 @@ impl Message {
     fn read_records(
         decoder: &mut BinDecoder,
         count: usize,
-        is_additional: bool,
-    ) -> ProtoResult<(Vec<Record>, Option<Edns>, Vec<Record>)> {
+        section: MessageSection,
+    ) -> ProtoResult<(Vec<Record>, Option<Edns>, Vec<Record>, Option<usize>)> {
+        let is_additional = section == MessageSection::Additional;
         let mut records: Vec<Record> = Vec::with_capacity(count);
         let mut edns: Option<Edns> = None;
         let mut sig0s: Vec<Record> = Vec::with_capacity(if is_additional { 1 } else { 0 });
+        // the offset of the TSIG record, the end of the bytes covered by its MAC
+        let mut tsig_offset: Option<usize> = None;
 
         // sig0 must be last, once this is set, disable.
         let mut saw_sig0 = false;
-        for _ in 0..count {
-            let record = try!(Record::read(decoder));
+        for read in 0..count {
+            let offset = decoder.index();
+            let warnings = decoder.warnings().len();
+            let record = match Record::read(decoder) {
+                Ok(record) => record,
+                Err(e) => {
+                    let section = section.to_string();
+                    try!(Self::pass_over(decoder, &section, read, count, offset, e));
+                    break;
+                }
+            };
+            decoder.prefix_warnings(warnings, &format!("{}[{}]", section, read));
 
             if !is_additional {
                 if saw_sig0 {
@@ -562,6 +722,17 @@ impl Message {
                         saw_sig0 = true;
                         sig0s.push(record);
                     }
+                    RecordType::TSIG => {
+                        if saw_sig0 {
+                            return Err(
+                                ProtoErrorKind::Message("tsig must be final resource record")
+                                    .into(),
+                            );
+                        }
+                        saw_sig0 = true;
+                        tsig_offset = Some(offset);
+                        sig0s.push(record);
+                    }
                     RecordType::OPT => {
                         if saw_sig0 {
                             return Err(
@@ -596,7 +767,7 @@ impl Message {
             }
         }
 
-        Ok((records, edns, sig0s))
+        Ok((records, edns, sig0s, tsig_offset))
     }
 
     fn emit_records(encoder: &mut BinEncoder, records: &Vec<Record>) -> ProtoResult<()> {
@@ -606,23 +777,85 @@ impl Message {
         Ok(())
     }
 
+    /// In `DecodeMode::Lenient` the rest of the message is passed over when a query or record
+    ///  can not be decoded, in the other modes this is an error
+    fn pass_over(
+        decoder: &mut BinDecoder,
+        section: &str,
+        read: usize,
+        count: usize,
+        offset: usize,
+        error: ProtoError,
+    ) -> ProtoResult<()> {
+        if decoder.mode() != DecodeMode::Lenient {
+            return Err(error);
+        }
+
+        decoder.warn(
+            format!("{}[{}]", section, read),
+            offset,
+            format!("only {} of {} read: {}", read, count, error),
+        );
+        let end = decoder.index() + decoder.len();
+        decoder.set_index(end);
+        Ok(())
+    }
+
     /// Decodes a message from the buffer.
     pub fn from_vec(buffer: &[u8]) -> ProtoResult<Message> {
         let mut decoder = BinDecoder::new(buffer);
         Message::read(&mut decoder)
     }
 
+    /// Decodes a message from the buffer, as strictly as the mode requires, the problems which
+    ///  were passed over in `DecodeMode::Lenient` are kept in `decode_warnings`
+    pub fn from_vec_with_mode(buffer: &[u8], mode: DecodeMode) -> ProtoResult<Message> {
+        let mut decoder = BinDecoder::with_mode(buffer, mode);
+        Message::read(&mut decoder)
+    }
+
     /// Encodes the Message into a buffer
     pub fn to_vec(&self) -> Result<Vec<u8>, ProtoError> {
+        self.to_vec_with_name_compression(NameCompression::All)
+    }
+
+    /// Encodes the Message into a buffer, compressing only the names allowed by `name_compression`
+    pub fn to_vec_with_name_compression(
+        &self,
+        name_compression: NameCompression,
+    ) -> Result<Vec<u8>, ProtoError> {
         let mut buffer = Vec::with_capacity(512);
         {
             let mut encoder = BinEncoder::new(&mut buffer);
+            encoder.set_name_compression(name_compression);
             try!(self.emit(&mut encoder));
         }
 
         Ok(buffer)
     }
 
+    /// Pads the message with the Padding option, RFC 7830, to a multiple of the block size when
+    ///  encoded with `name_compression`, the Block-Length Padding of RFC 8467 section 4.1
+    ///
+    /// An EDNS is added if there is none, and any earlier padding is replaced, so this is the last
+    ///  change to the message before it is sent. A message is not padded beyond 65535 bytes.
+    pub fn pad(&mut self, block_size: u16, name_compression: NameCompression) -> ProtoResult<()> {
+        if block_size == 0 {
+            return Ok(());
+        }
+
+        // the header of the option is part of the length
+        self.edns_mut().set_option(EdnsOption::Padding(0));
+        let len = try!(self.to_vec_with_name_compression(name_compression)).len();
+
+        let block_size = block_size as usize;
+        let padding = (block_size - len % block_size) % block_size;
+        let padding = cmp::min(padding, (u16::max_value() as usize).saturating_sub(len));
+
+        self.edns_mut().set_option(EdnsOption::Padding(padding as u16));
+        Ok(())
+    }
+
     /// Finalize the message prior to sending.
     ///
     /// Subsequent to calling this, the Message should not change.
@@ -637,9 +870,12 @@ impl Message {
         // append all records to message
         for fin in finals {
             match fin.rr_type() {
-                // SIG0's are special, and come at the very end of the message
-                RecordType::SIG => self.add_sig0(fin),
-                _ => self.add_additional(fin),
+                // SIG0's and TSIG's are special, and come at the very end of the message
+                RecordType::SIG | RecordType::TSIG => self.add_sig0(fin),
+                _ => {
+                    self.additionals.push(fin);
+                    self
+                }
             };
         }
 
@@ -647,6 +883,116 @@ impl Message {
     }
 }
 
+/// The sections of a Message which hold records
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MessageSection {
+    /// The answer section, the prerequisite section of an update
+    Answer,
+    /// The authority section, the update section of an update
+    Authority,
+    /// The additional section
+    Additional,
+}
+
+impl fmt::Display for MessageSection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let section = match *self {
+            MessageSection::Answer => "answer",
+            MessageSection::Authority => "authority",
+            MessageSection::Additional => "additional",
+        };
+
+        f.write_str(section)
+    }
+}
+
+/// A handle for inserting records into one section of a Message, see `Message::answers_mut`,
+///  `Message::name_servers_mut` and `Message::additionals_mut`
+pub struct SectionMut<'a> {
+    message: &'a mut Message,
+    section: MessageSection,
+}
+
+impl<'a> SectionMut<'a> {
+    /// Returns the section which records are inserted into
+    pub fn section(&self) -> MessageSection {
+        self.section
+    }
+
+    /// Returns the records of the section
+    pub fn records(&self) -> &[Record] {
+        match self.section {
+            MessageSection::Answer => &self.message.answers,
+            MessageSection::Authority => &self.message.name_servers,
+            MessageSection::Additional => &self.message.additionals,
+        }
+    }
+
+    /// Inserts the record at the end of the section
+    ///
+    /// # Errors
+    ///
+    /// Records which can not appear in the section are rejected, and the Message is left as is:
+    ///
+    /// * OPT is only allowed in the additional section, and only once, RFC 6891 section 6.1.1. It
+    ///   becomes the EDNS of the Message, see `Message::edns`.
+    /// * SOA is not allowed in the additional section, nor in the answer section of an NXDomain
+    ///   response, where it belongs in the authority section, RFC 2308 section 3.
+    pub fn insert(&mut self, record: Record) -> ProtoResult<&mut Self> {
+        match (record.rr_type(), self.section) {
+            (RecordType::OPT, MessageSection::Additional) => {
+                if self.message.edns.is_some() {
+                    return Err(
+                        ProtoErrorKind::Message("a message may only have one OPT record").into(),
+                    );
+                }
+
+                self.message.edns = Some(Edns::from(&record));
+                return Ok(self);
+            }
+            (RecordType::OPT, section) |
+            (RecordType::SOA, section @ MessageSection::Additional) => {
+                return Err(
+                    ProtoErrorKind::RecordNotAllowedInSection(record.rr_type(), section).into(),
+                );
+            }
+            (RecordType::SOA, MessageSection::Answer) => {
+                if self.message.response_code() == ResponseCode::NXDomain {
+                    return Err(
+                        ProtoErrorKind::RecordNotAllowedInSection(
+                            RecordType::SOA,
+                            MessageSection::Answer,
+                        ).into(),
+                    );
+                }
+            }
+            _ => (),
+        }
+
+        match self.section {
+            MessageSection::Answer => self.message.answers.push(record),
+            MessageSection::Authority => self.message.name_servers.push(record),
+            MessageSection::Additional => self.message.additionals.push(record),
+        }
+
+        Ok(self)
+    }
+
+    /// Inserts all the records at the end of the section, see `insert`
+    ///
+    /// The records before one which is rejected remain inserted.
+    pub fn insert_all<R>(&mut self, records: R) -> ProtoResult<&mut Self>
+    where
+        R: IntoIterator<Item = Record>,
+    {
+        for record in records {
+            try!(self.insert(record));
+        }
+
+        Ok(self)
+    }
+}
+
 /// A trait for performing final ammendments to a Message before it is sent.
 ///
 /// An example of this is a SIG0 signer, which needs the final form of the message,
@@ -664,10 +1010,32 @@ pub trait MessageFinalizer {
     ///
     /// A vector to append to the additionals section of the message, sorted in the order as they should appear in the message.
     fn finalize_message(&self, message: &Message, current_time: u32) -> ProtoResult<Vec<Record>>;
+
+    /// Verifies the response to a request which was finalized, e.g. the MAC of a TSIG, which
+    ///  covers the MAC of the request as well. By default all responses are accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `request_signatures` - the records which `finalize_message` returned for the request
+    /// * `response` - the response, as it was decoded
+    /// * `current_time` - the current time as specified by the system
+    ///
+    /// # Return
+    ///
+    /// An error if the response must not be accepted as the answer to the request.
+    fn verify_response(
+        &self,
+        _request_signatures: &[Record],
+        _response: &Message,
+        _current_time: u32,
+    ) -> ProtoResult<()> {
+        Ok(())
+    }
 }
 
 impl BinSerializable<Message> for Message {
     fn read(decoder: &mut BinDecoder) -> ProtoResult<Self> {
+        let start = decoder.index();
         let header = try!(Header::read(decoder));
 
         // TODO/FIXME: return just header, and in the case of the rest of message getting an error.
@@ -676,8 +1044,15 @@ impl BinSerializable<Message> for Message {
         // get the questions
         let count = header.query_count() as usize;
         let mut queries = Vec::with_capacity(count);
-        for _ in 0..count {
-            queries.push(try!(Query::read(decoder)));
+        for read in 0..count {
+            let offset = decoder.index();
+            match Query::read(decoder) {
+                Ok(query) => queries.push(query),
+                Err(e) => {
+                    try!(Self::pass_over(decoder, "query", read, count, offset, e));
+                    break;
+                }
+            }
         }
 
         // get all counts before header moves
@@ -685,9 +1060,35 @@ impl BinSerializable<Message> for Message {
         let name_server_count = header.name_server_count() as usize;
         let additional_count = header.additional_count() as usize;
 
-        let (answers, _, _) = try!(Self::read_records(decoder, answer_count, false));
-        let (name_servers, _, _) = try!(Self::read_records(decoder, name_server_count, false));
-        let (additionals, edns, sig0) = try!(Self::read_records(decoder, additional_count, true));
+        let (answers, _, _, _) =
+            try!(Self::read_records(decoder, answer_count, MessageSection::Answer));
+        let (name_servers, _, _, _) =
+            try!(Self::read_records(decoder, name_server_count, MessageSection::Authority));
+        let (additionals, edns, sig0, tsig_offset) =
+            try!(Self::read_records(decoder, additional_count, MessageSection::Additional));
+        let signed_bytes = tsig_offset
+            .and_then(|offset| decoder.peek_slice(start, offset - start))
+            .map(|bytes| bytes.to_vec());
+
+        // bytes were left over, e.g. more records than the counts of the header
+        if decoder.len() > 0 {
+            match decoder.mode() {
+                DecodeMode::Strict => {
+                    return Err(ProtoErrorKind::Msg(
+                        format!("{} bytes after the last record", decoder.len()),
+                    ).into())
+                }
+                DecodeMode::Lenient => {
+                    let (offset, len) = (decoder.index(), decoder.len());
+                    decoder.warn(
+                        "message".to_string(),
+                        offset,
+                        format!("{} bytes after the last record", len),
+                    );
+                }
+                DecodeMode::Normal => (),
+            }
+        }
 
         Ok(Message {
             header: header,
@@ -697,6 +1098,8 @@ impl BinSerializable<Message> for Message {
             additionals: additionals,
             sig0: sig0,
             edns: edns,
+            decode_warnings: decoder.take_warnings(),
+            signed_bytes: signed_bytes,
         })
     }
 
@@ -780,14 +1183,58 @@ fn test_emit_and_read_records() {
         .set_checking_disabled(true)
         .set_response_code(ResponseCode::ServFail);
 
-    message.add_answer(Record::new());
-    message.add_name_server(Record::new());
-    message.add_additional(Record::new());
+    message.answers_mut().insert(Record::new()).unwrap();
+    message.name_servers_mut().insert(Record::new()).unwrap();
+    message.additionals_mut().insert(Record::new()).unwrap();
     message.update_counts(); // needed for the comparison...
 
     test_emit_and_read(message);
 }
 
+#[test]
+fn test_section_insert() {
+    use rr::{Name, RData};
+    use rr::rdata::SOA;
+
+    let soa = Record::from_rdata(
+        Name::from_labels(vec!["example", "com"]),
+        3600,
+        RecordType::SOA,
+        RData::SOA(SOA::new(
+            Name::from_labels(vec!["ns", "example", "com"]),
+            Name::from_labels(vec!["hostmaster", "example", "com"]),
+            1,
+            3600,
+            600,
+            86400,
+            60,
+        )),
+    );
+    let opt = Record::from(&Edns::new());
+
+    let mut message = Message::new();
+    assert!(message.answers_mut().insert(opt.clone()).is_err());
+    assert!(message.name_servers_mut().insert(opt.clone()).is_err());
+    assert!(message.additionals_mut().insert(soa.clone()).is_err());
+
+    // the OPT record is the EDNS of the message
+    message.additionals_mut().insert(opt.clone()).unwrap();
+    assert!(message.edns().is_some());
+    assert!(message.additionals().is_empty());
+    assert!(message.additionals_mut().insert(opt).is_err());
+
+    // negative responses carry the SOA in the authority section
+    message.set_response_code(ResponseCode::NXDomain);
+    assert!(message.answers_mut().insert(soa.clone()).is_err());
+    assert!(message.answers().is_empty());
+    message.name_servers_mut().insert(soa.clone()).unwrap();
+    assert_eq!(message.name_servers_mut().records(), &[soa.clone()]);
+
+    message.set_response_code(ResponseCode::NoError);
+    message.answers_mut().insert_all(vec![soa.clone(), Record::new()]).unwrap();
+    assert_eq!(message.answers().len(), 2);
+}
+
 #[cfg(test)]
 fn test_emit_and_read(message: Message) {
     let mut byte_vec: Vec<u8> = Vec::with_capacity(512);
@@ -802,6 +1249,27 @@ fn test_emit_and_read(message: Message) {
     assert_eq!(got, message);
 }
 
+#[test]
+fn test_extended_response_code() {
+    // BADVERS shares its value with BADSIG, and is carried in the OPT record
+    let mut message = Message::new();
+    message.set_response_code(ResponseCode::BADVERS);
+    assert_eq!(message.header.response_code(), 0);
+    assert_eq!(message.edns().unwrap().rcode_high(), 1);
+    assert_eq!(message.response_code(), ResponseCode::BADVERS);
+    test_emit_and_read(message.clone());
+
+    // codes which are not known are kept
+    message.set_response_code(ResponseCode::Unknown(3841));
+    test_emit_and_read(message.clone());
+
+    // back to a code which fits in the header
+    message.set_response_code(ResponseCode::Refused);
+    assert_eq!(message.edns().unwrap().rcode_high(), 0);
+    assert_eq!(message.response_code(), ResponseCode::Refused);
+    test_emit_and_read(message);
+}
+
 #[test]
 fn test_legit_message() {
     let buf: Vec<u8> = vec![
@@ -839,3 +1307,235 @@ fn test_legit_message() {
 
     assert_eq!(message.id(), 4096);
 }
+
+#[test]
+fn test_name_compression() {
+    use rr::{Name, RData};
+    use rr::rdata::SOA;
+
+    let origin = Name::parse("example.com.", None).unwrap();
+    let soa = Record::from_rdata(
+        origin.clone(),
+        3600,
+        RecordType::SOA,
+        RData::SOA(SOA::new(
+            Name::parse("ns.example.com.", None).unwrap(),
+            Name::parse("hostmaster.example.com.", None).unwrap(),
+            1,
+            7200,
+            3600,
+            1209600,
+            3600,
+        )),
+    );
+
+    let mut message = Message::new();
+    message.add_query(Query::query(origin, RecordType::SOA));
+    message.add_answer(soa.clone());
+
+    let all = message.to_vec().unwrap();
+    let owner_names = message
+        .to_vec_with_name_compression(NameCompression::OwnerNames)
+        .unwrap();
+    let disabled = message
+        .to_vec_with_name_compression(NameCompression::Disabled)
+        .unwrap();
+
+    // the rname points into the mname, and the owner name into the query
+    assert_eq!(owner_names.len(), all.len() + "example.com.".len() - 1);
+    assert_eq!(disabled.len(), owner_names.len() + "example.com.".len() - 1);
+
+    for buffer in &[all, owner_names, disabled] {
+        let read = Message::from_vec(buffer).unwrap();
+        assert_eq!(read.answers(), &[soa.clone()]);
+    }
+}
+
+#[test]
+fn test_pad() {
+    use std::net::Ipv4Addr;
+
+    use rr::{Name, RData};
+
+    let mut message = Message::new();
+    message.add_query(Query::query(
+        Name::parse("www.example.com.", None).unwrap(),
+        RecordType::A,
+    ));
+
+    message.pad(128, NameCompression::All).unwrap();
+    let bytes = message.to_vec().unwrap();
+    assert_eq!(bytes.len(), 128);
+
+    // the padding is replaced, not added to
+    message.pad(128, NameCompression::All).unwrap();
+    assert_eq!(message.to_vec().unwrap().len(), 128);
+
+    let read = Message::from_vec(&bytes).unwrap();
+    let padding = read.edns().and_then(|edns| edns.padding()).unwrap();
+    assert_eq!(padding, message.edns().and_then(|edns| edns.padding()).unwrap());
+
+    // the length depends on the compression of the names
+    message.add_answer(Record::from_rdata(
+        Name::parse("www.example.com.", None).unwrap(),
+        3600,
+        RecordType::A,
+        RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+    ));
+    message.pad(128, NameCompression::Disabled).unwrap();
+    assert_eq!(
+        message
+            .to_vec_with_name_compression(NameCompression::Disabled)
+            .unwrap()
+            .len(),
+        128
+    );
+}
+
+#[test]
+fn test_decode_modes() {
+    use std::net::Ipv4Addr;
+
+    use rr::{Name, RData};
+
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let mut message = Message::new();
+    message.add_query(Query::query(name.clone(), RecordType::A));
+    message.add_answer(Record::from_rdata(
+        name,
+        3600,
+        RecordType::A,
+        RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+    ));
+    let bytes = message.to_vec().unwrap();
+
+    let decode = |bytes: &[u8], mode: DecodeMode| Message::from_vec_with_mode(bytes, mode);
+
+    // trailing bytes
+    let mut trailing = bytes.clone();
+    trailing.extend_from_slice(&[0, 0]);
+    assert!(decode(&trailing, DecodeMode::Normal).is_ok());
+    assert!(decode(&trailing, DecodeMode::Strict).is_err());
+    let read = decode(&trailing, DecodeMode::Lenient).unwrap();
+    assert_eq!(read.answers().len(), 1);
+    assert_eq!(
+        read.decode_warnings(),
+        &[
+            DecodeWarning::new(
+                "message".to_string(),
+                bytes.len(),
+                "2 bytes after the last record".to_string(),
+            ),
+        ]
+    );
+
+    // an answer count above the records
+    let mut missing = bytes.clone();
+    missing[7] = 2;
+    assert!(decode(&missing, DecodeMode::Normal).is_err());
+    assert!(decode(&missing, DecodeMode::Strict).is_err());
+    let read = decode(&missing, DecodeMode::Lenient).unwrap();
+    assert_eq!(read.answers().len(), 1);
+    assert_eq!(read.decode_warnings().len(), 1);
+    assert_eq!(read.decode_warnings()[0].field(), "answer[1]");
+    assert_eq!(read.decode_warnings()[0].offset(), bytes.len());
+
+    // an rdlength which does not match the rdata
+    let mut rdlength = bytes.clone();
+    let len = rdlength.len();
+    rdlength[len - 5] = 5;
+    rdlength.push(0);
+    assert!(decode(&rdlength, DecodeMode::Normal).is_err());
+    assert!(decode(&rdlength, DecodeMode::Strict).is_err());
+    let read = decode(&rdlength, DecodeMode::Lenient).unwrap();
+    assert_eq!(
+        read.answers()[0].rdata(),
+        &RData::Unknown {
+            code: 1,
+            rdata: vec![192, 0, 2, 1, 0],
+        }
+    );
+    assert_eq!(read.decode_warnings().len(), 1);
+    assert_eq!(read.decode_warnings()[0].field(), "answer[0].rdata");
+    assert_eq!(read.decode_warnings()[0].offset(), bytes.len() - 4);
+
+    // the warnings are only kept for lenient decoding
+    assert!(decode(&bytes, DecodeMode::Lenient).unwrap().decode_warnings().is_empty());
+}
+
+#[test]
+fn test_remove_out_of_bailiwick() {
+    use std::net::Ipv4Addr;
+
+    use rr::rdata::{MX, SOA};
+
+    let name = |name: &str| Name::parse(name, None).unwrap();
+    let record = |owner: &str, rdata: RData| {
+        let rr_type = rdata.to_record_type();
+        Record::from_rdata(name(owner), 300, rr_type, rdata)
+    };
+    let a = |owner: &str| record(owner, RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+
+    let mut message = Message::new();
+    message.add_query(Query::query(name("www.example.com."), RecordType::MX));
+    message.add_answer(record("www.example.com.", RData::CNAME(name("mail.example.com."))));
+    let mx = MX::new(10, name("mx.example.com."));
+    message.add_answer(record("mail.example.com.", RData::MX(mx)));
+    message.add_answer(a("www.bank.com."));
+    message.add_name_server(record("example.com.", RData::NS(name("ns.example.com."))));
+    message.add_name_server(record("bank.com.", RData::NS(name("ns.evil.com."))));
+    message.add_additional(a("mx.example.com."));
+    message.add_additional(a("ns.example.com."));
+    message.add_additional(a("ns.evil.com."));
+    message.add_additional(a("other.example.com."));
+
+    assert_eq!(message.remove_out_of_bailiwick(), 4);
+    assert_eq!(message.answers().len(), 2);
+    assert_eq!(message.name_servers().len(), 1);
+    assert_eq!(message.name_servers()[0].name(), &name("example.com."));
+    let additionals = message.additionals().iter().map(|r| r.name().clone()).collect::<Vec<_>>();
+    assert_eq!(additionals, vec![name("mx.example.com."), name("ns.example.com.")]);
+
+    // no zone in the authority, the authority records are all removed
+    let mut message = Message::new();
+    message.add_query(Query::query(name("www.example.com."), RecordType::A));
+    message.add_answer(a("www.example.com."));
+    message.add_name_server(record(
+        "example.net.",
+        RData::SOA(SOA::new(name("ns.example.net."), name("root.example.net."), 1, 1, 1, 1, 1)),
+    ));
+    assert_eq!(message.remove_out_of_bailiwick(), 1);
+    assert_eq!(message.answers().len(), 1);
+    assert!(message.name_servers().is_empty());
+}
+
+#[test]
+fn test_tsig_signed_bytes() {
+    use rr::DNSClass;
+    use rr::rdata::TSIG;
+
+    let mut message = Message::new();
+    message.add_query(Query::query(Name::parse("example.com.", None).unwrap(), RecordType::AXFR));
+    let unsigned = message.to_vec().unwrap();
+
+    let algorithm = Name::parse("hmac-sha256.", None).unwrap();
+    let tsig = TSIG::new(algorithm, 1, 300, vec![1; 32], 0, 0, vec![]);
+    let key_name = Name::parse("key.", None).unwrap();
+    let mut record = Record::from_rdata(key_name, 0, RecordType::TSIG, RData::TSIG(tsig));
+    record.set_dns_class(DNSClass::ANY);
+    message.add_sig0(record.clone());
+    let bytes = message.to_vec().unwrap();
+
+    let read = Message::from_vec(&bytes).unwrap();
+    assert_eq!(read.tsig(), Some(&record));
+    assert!(read.additionals().is_empty());
+
+    // the signed bytes are as received, the additional count includes the TSIG
+    let signed = read.signed_bytes().unwrap();
+    assert_eq!(signed, &bytes[..unsigned.len()]);
+    assert_eq!(&signed[..10], &unsigned[..10]);
+    assert_eq!(&signed[12..], &unsigned[12..]);
+    assert_eq!(signed[11], unsigned[11] + 1);
+
+    assert!(Message::from_vec(&unsigned).unwrap().signed_bytes().is_none());
+}
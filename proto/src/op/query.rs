@@ -19,12 +19,18 @@
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
+use op::{Message, MessageType, OpCode};
 use rr::domain::Name;
 use rr::record_type::RecordType;
 use rr::dns_class::DNSClass;
 use serialize::binary::*;
 use error::*;
 
+#[cfg(feature = "mdns")]
+/// From [RFC 6762](https://tools.ietf.org/html/rfc6762#section-5.4), the top bit of the class in
+///  a question requests a unicast response
+const MDNS_UNICAST_RESPONSE: u16 = 1 << 15;
+
 /// Query struct for looking up resource records, basically a resource record without RDATA.
 ///
 /// [RFC 1035, DOMAIN NAMES - IMPLEMENTATION AND SPECIFICATION, November 1987](https://tools.ietf.org/html/rfc1035)
@@ -54,6 +60,8 @@ pub struct Query {
     name: Name,
     query_type: RecordType,
     query_class: DNSClass,
+    #[cfg(feature = "mdns")]
+    mdns_unicast_response: bool,
 }
 
 impl Query {
@@ -63,6 +71,8 @@ impl Query {
             name: Name::new(),
             query_type: RecordType::A,
             query_class: DNSClass::IN,
+            #[cfg(feature = "mdns")]
+            mdns_unicast_response: false,
         }
     }
 
@@ -72,6 +82,8 @@ impl Query {
             name,
             query_type,
             query_class: DNSClass::IN,
+            #[cfg(feature = "mdns")]
+            mdns_unicast_response: false,
         }
     }
 
@@ -93,6 +105,14 @@ impl Query {
         self
     }
 
+    /// Changes mDNS unicast-response bit
+    /// See [RFC 6762](https://tools.ietf.org/html/rfc6762#section-5.4)
+    #[cfg(feature = "mdns")]
+    pub fn set_mdns_unicast_response(&mut self, flag: bool) -> &mut Self {
+        self.mdns_unicast_response = flag;
+        self
+    }
+
     /// ```text
     /// QNAME           a domain name represented as a sequence of labels, where
     ///                 each label consists of a length octet followed by that
@@ -122,24 +142,179 @@ impl Query {
     pub fn query_class(&self) -> DNSClass {
         self.query_class
     }
+
+    /// Returns if the mDNS unicast-response bit is set or not
+    /// See [RFC 6762](https://tools.ietf.org/html/rfc6762#section-5.4)
+    #[cfg(feature = "mdns")]
+    pub fn mdns_unicast_response(&self) -> bool {
+        self.mdns_unicast_response
+    }
+}
+
+/// Builds a `Query`, validating the combination of its type and class, see `build`
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use trust_dns_proto::op::QueryBuilder;
+/// use trust_dns_proto::rr::{DNSClass, Name, RecordType};
+///
+/// let mut builder = QueryBuilder::new(Name::from_str("WWW.Example.com").unwrap());
+/// builder.set_query_type(RecordType::AAAA);
+///
+/// let query = builder.build().unwrap();
+/// assert_eq!(query.name(), &Name::from_str("www.example.com.").unwrap());
+/// assert_eq!(query.query_type(), RecordType::AAAA);
+///
+/// // OPT is the pseudo-record of EDNS, not something which can be looked up
+/// builder.set_query_type(RecordType::OPT);
+/// assert!(builder.build().is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct QueryBuilder {
+    name: Name,
+    query_type: RecordType,
+    query_class: DNSClass,
+    recursion_desired: bool,
+    #[cfg(feature = "mdns")]
+    mdns_unicast_response: bool,
+}
+
+impl QueryBuilder {
+    /// A builder of a query of the name, for A records in the IN class, with recursion desired
+    pub fn new(name: Name) -> Self {
+        QueryBuilder {
+            name: name,
+            query_type: RecordType::A,
+            query_class: DNSClass::IN,
+            recursion_desired: true,
+            #[cfg(feature = "mdns")]
+            mdns_unicast_response: false,
+        }
+    }
+
+    /// Specify the RecordType being queried
+    pub fn set_query_type(&mut self, query_type: RecordType) -> &mut Self {
+        self.query_type = query_type;
+        self
+    }
+
+    /// Specify the DNS class of the Query, almost always IN
+    pub fn set_query_class(&mut self, query_class: DNSClass) -> &mut Self {
+        self.query_class = query_class;
+        self
+    }
+
+    /// Specify whether the name server should pursue the query recursively, see
+    ///  `Message::set_recursion_desired`
+    pub fn set_recursion_desired(&mut self, recursion_desired: bool) -> &mut Self {
+        self.recursion_desired = recursion_desired;
+        self
+    }
+
+    /// Changes mDNS unicast-response bit
+    /// See [RFC 6762](https://tools.ietf.org/html/rfc6762#section-5.4)
+    #[cfg(feature = "mdns")]
+    pub fn set_mdns_unicast_response(&mut self, flag: bool) -> &mut Self {
+        self.mdns_unicast_response = flag;
+        self
+    }
+
+    /// Returns true if the caller wants the query to be pursued recursively
+    pub fn recursion_desired(&self) -> bool {
+        self.recursion_desired
+    }
+
+    /// Returns the query, with the name lowercased and fully qualified
+    ///
+    /// # Errors
+    ///
+    /// The combinations which can not be queried are rejected:
+    ///
+    /// * the OPT type, the pseudo-record of EDNS, RFC 6891
+    /// * the NONE class, which is only used in updates, RFC 2136
+    /// * the OPT class, which is the payload size of EDNS, RFC 6891
+    /// * zone transfers in the ANY class, a zone is of one class
+    pub fn build(&self) -> ProtoResult<Query> {
+        let invalid = |reason: &'static str| -> ProtoResult<Query> {
+            Err(
+                ProtoErrorKind::InvalidQuery(self.query_type, self.query_class, reason).into(),
+            )
+        };
+
+        match (self.query_type, self.query_class) {
+            (RecordType::OPT, _) => return invalid("OPT is not a queryable type"),
+            (_, DNSClass::NONE) => return invalid("the NONE class is only used in updates"),
+            (_, DNSClass::OPT(..)) => return invalid("the OPT class is only used by EDNS"),
+            (RecordType::AXFR, DNSClass::ANY) |
+            (RecordType::IXFR, DNSClass::ANY) => {
+                return invalid("zone transfers are of the zone of one class")
+            }
+            _ => (),
+        }
+
+        Ok(Query {
+            name: self.name.to_lowercase().into_fqdn(),
+            query_type: self.query_type,
+            query_class: self.query_class,
+            #[cfg(feature = "mdns")]
+            mdns_unicast_response: self.mdns_unicast_response,
+        })
+    }
+
+    /// Returns a query message with the query, and the recursion desired flag of the builder
+    pub fn build_message(&self) -> ProtoResult<Message> {
+        let query = try!(self.build());
+
+        let mut message = Message::new();
+        message
+            .add_query(query)
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .set_recursion_desired(self.recursion_desired);
+        Ok(message)
+    }
 }
 
 impl BinSerializable<Query> for Query {
     fn read(decoder: &mut BinDecoder) -> ProtoResult<Self> {
         let name = try!(Name::read(decoder));
         let query_type = try!(RecordType::read(decoder));
+
+        #[cfg(feature = "mdns")]
+        let (query_class, mdns_unicast_response) = {
+            // the top bit of the class is the unicast-response bit in mDNS - RFC 6762
+            let value = try!(decoder.read_u16());
+            (
+                try!(DNSClass::from_u16(value & !MDNS_UNICAST_RESPONSE)),
+                value & MDNS_UNICAST_RESPONSE != 0,
+            )
+        };
+        #[cfg(not(feature = "mdns"))]
         let query_class = try!(DNSClass::read(decoder));
 
         Ok(Query {
             name: name,
             query_type: query_type,
             query_class: query_class,
+            #[cfg(feature = "mdns")]
+            mdns_unicast_response: mdns_unicast_response,
         })
     }
 
     fn emit(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
         try!(self.name.emit(encoder));
         try!(self.query_type.emit(encoder));
+
+        #[cfg(feature = "mdns")]
+        {
+            if self.mdns_unicast_response {
+                try!(encoder.emit_u16(u16::from(self.query_class) | MDNS_UNICAST_RESPONSE));
+            } else {
+                try!(self.query_class.emit(encoder));
+            }
+        }
+        #[cfg(not(feature = "mdns"))]
         try!(self.query_class.emit(encoder));
 
         Ok(())
@@ -158,12 +333,41 @@ impl Display for Query {
     }
 }
 
+#[test]
+fn test_query_builder() {
+    let mut builder = QueryBuilder::new(Name::from_labels(vec!["WWW", "example", "com"]));
+    builder.set_query_type(RecordType::MX);
+
+    let query = builder.build().unwrap();
+    assert!(query.name().is_fqdn());
+    assert_eq!(query.name(), &Name::from_labels(vec!["www", "example", "com"]));
+    assert_eq!(query.query_type(), RecordType::MX);
+    assert_eq!(query.query_class(), DNSClass::IN);
+
+    let message = builder.set_recursion_desired(false).build_message().unwrap();
+    assert_eq!(message.queries(), &[query]);
+    assert!(!message.recursion_desired());
+
+    // ANY is queryable in any class but NONE
+    assert!(builder.set_query_type(RecordType::ANY).build().is_ok());
+    assert!(builder.set_query_class(DNSClass::ANY).build().is_ok());
+    assert!(builder.set_query_type(RecordType::AXFR).build().is_err());
+    assert!(builder.set_query_type(RecordType::IXFR).build().is_err());
+    assert!(builder.set_query_class(DNSClass::NONE).build().is_err());
+    assert!(builder.set_query_class(DNSClass::OPT(4096)).build().is_err());
+
+    builder.set_query_type(RecordType::OPT).set_query_class(DNSClass::IN);
+    assert!(builder.build().is_err());
+}
+
 #[test]
 fn test_read_and_emit() {
     let expect = Query {
         name: Name::from_labels(vec!["WWW", "example", "com"]),
         query_type: RecordType::AAAA,
         query_class: DNSClass::IN,
+        #[cfg(feature = "mdns")]
+        mdns_unicast_response: false,
     };
 
     let mut byte_vec: Vec<u8> = Vec::with_capacity(512);
@@ -176,3 +380,24 @@ fn test_read_and_emit() {
     let got = Query::read(&mut decoder).unwrap();
     assert_eq!(got, expect);
 }
+
+#[cfg(feature = "mdns")]
+#[test]
+fn test_mdns_unicast_response_bit() {
+    let mut expect = Query::query(Name::from_labels(vec!["host", "local"]), RecordType::A);
+    expect.set_mdns_unicast_response(true);
+
+    let mut byte_vec: Vec<u8> = Vec::with_capacity(512);
+    {
+        let mut encoder = BinEncoder::new(&mut byte_vec);
+        expect.emit(&mut encoder).unwrap();
+    }
+
+    // the class is the last two bytes
+    assert_eq!(&byte_vec[byte_vec.len() - 2..], &[0x80, 0x01]);
+
+    let mut decoder = BinDecoder::new(&byte_vec);
+    let got = Query::read(&mut decoder).unwrap();
+    assert_eq!(got.query_class(), DNSClass::IN);
+    assert!(got.mdns_unicast_response());
+}
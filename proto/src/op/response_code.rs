@@ -58,7 +58,12 @@ use std::fmt::{Display, Formatter};
 ///
 ///                 6-15            Reserved for future use.
 ///  ```
-#[derive(Debug, PartialEq, PartialOrd, Copy, Clone)]
+///
+/// With EDNS the code is extended to 12 bits, the upper 8 of which are carried in the OPT record,
+///  [RFC 6891](https://tools.ietf.org/html/rfc6891#section-6.1.3), see `high`, `low` and `from`.
+///  The TSIG codes share values with them, 16 is BADVERS in the RCODE of a message, but BADSIG in
+///  the error of a TSIG record, see `from_tsig_error`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Hash, Copy, Clone)]
 #[allow(dead_code)]
 pub enum ResponseCode {
     /// No Error [RFC 1035](https://tools.ietf.org/html/rfc1035)
@@ -121,10 +126,14 @@ pub enum ResponseCode {
 
     /// Bad/missing server cookie [draft-ietf-dnsop-cookies](https://tools.ietf.org/html/draft-ietf-dnsop-cookies-10)
     BADCOOKIE,
-             // 24-3840	Unassigned
-             // 3841-4095	Reserved for Private Use		[RFC6895]
-             // 4096-65534	Unassigned
-             // 65535	Reserved, can be allocated by Standards Action		[RFC6895]
+
+    /// An unassigned or private code, kept as is so that it is not lost
+    Unknown(u16),
+    // 11-15	Unassigned
+    // 24-3840	Unassigned
+    // 3841-4095	Reserved for Private Use		[RFC6895]
+    // 4096-65534	Unassigned
+    // 65535	Reserved, can be allocated by Standards Action		[RFC6895]
 }
 
 impl ResponseCode {
@@ -133,9 +142,10 @@ impl ResponseCode {
         (u16::from(*self) & 0x000F) as u8
     }
 
-    /// returns the high 12 bits for the edns portion of the response code
-    pub fn high(&self) {
-        (u16::from(*self) & 0x0FF0) >> 4;
+    /// returns the high 8 bits of the 12 bit response code, for the edns portion of the response
+    ///  code
+    pub fn high(&self) -> u8 {
+        ((u16::from(*self) & 0x0FF0) >> 4) as u8
     }
 
     /// Combines the EDNS high and low from the Header to produce the Extended ResponseCode
@@ -143,6 +153,20 @@ impl ResponseCode {
         (((high as u16) << 4) | ((low as u16) & 0x000F)).into()
     }
 
+    /// The code of the error field of a TSIG record, where 16 is BADSIG rather than BADVERS,
+    ///  [RFC 8945](https://tools.ietf.org/html/rfc8945#section-5.2)
+    pub fn from_tsig_error(error: u16) -> ResponseCode {
+        match error {
+            16 => ResponseCode::BADSIG,
+            error => error.into(),
+        }
+    }
+
+    /// Returns true if the code does not fit in the 4 bits of the header, and needs EDNS
+    pub fn is_extended(&self) -> bool {
+        self.high() != 0
+    }
+
     /// Transforms the response code into the human message
     pub fn to_str(&self) -> &'static str {
         match *self {
@@ -157,7 +181,7 @@ impl ResponseCode {
             ResponseCode::NXRRSet => "RR Set does not exist",  // 8	  NXRRSet	RR Set that should exist does not	[RFC2136]
             ResponseCode::NotAuth => "Not authorized",  // 9	  NotAuth	Server Not Authoritative for zone	[RFC2136]
             ResponseCode::NotZone => "Name not in zone", // 10	NotZone	Name not contained in zone	[RFC2136]
-            ResponseCode::BADVERS => "Bad option verions", // 16	BADVERS	Bad OPT Version	[RFC6891]
+            ResponseCode::BADVERS => "Bad option version", // 16	BADVERS	Bad OPT Version	[RFC6891]
             ResponseCode::BADSIG => "TSIG Failure", // 16	BADSIG	TSIG Signature Failure	[RFC2845]
             ResponseCode::BADKEY => "Key not recognized", // 17	BADKEY	Key not recognized	[RFC2845]
             ResponseCode::BADTIME => "Signature out of time window", // 18	BADTIME	Signature out of time window	[RFC2845]
@@ -166,6 +190,7 @@ impl ResponseCode {
             ResponseCode::BADALG => "Algorithm not supported", // 21	BADALG	Algorithm not supported	[RFC2930]
             ResponseCode::BADTRUNC => "Bad truncation", // 22	BADTRUNC	Bad Truncation	[RFC4635]
             ResponseCode::BADCOOKIE => "Bad server cookie", // 23	BADCOOKIE (TEMPORARY - registered 2015-07-26, expires 2016-07-26)	Bad/missing server cookie	[draft-ietf-dnsop-cookies]
+            ResponseCode::Unknown(_) => "Unknown response code",
         }
     }
 }
@@ -212,6 +237,7 @@ impl From<ResponseCode> for u16 {
             ResponseCode::BADALG => 21, // 21	BADALG	Algorithm not supported	[RFC2930]
             ResponseCode::BADTRUNC => 22, // 22	BADTRUNC	Bad Truncation	[RFC4635]
             ResponseCode::BADCOOKIE => 23, // 23	BADCOOKIE (TEMPORARY - registered 2015-07-26, expires 2016-07-26)	Bad/missing server cookie	[draft-ietf-dnsop-cookies]
+            ResponseCode::Unknown(value) => value,
         }
     }
 }
@@ -242,9 +268,8 @@ impl From<u16> for ResponseCode {
             8 => ResponseCode::NXRRSet,   // 8	NXRRSet	RR Set that should exist does not	[RFC2136]
             9 => ResponseCode::NotAuth,   // 9	NotAuth	Server Not Authoritative for zone	[RFC2136]
             10 => ResponseCode::NotZone,   // 10	NotZone	Name not contained in zone	[RFC2136]
-            // this looks to be backwards compat for 4 bit ResponseCodes.
-            //16 => ResponseCode::BADVERS,   // 16	BADVERS	Bad OPT Version	[RFC6891]
-            16 => ResponseCode::BADSIG,    // 16	BADSIG	TSIG Signature Failure	[RFC2845]
+            // 16 is BADSIG only in the error of a TSIG record, see `ResponseCode::from_tsig_error`
+            16 => ResponseCode::BADVERS,   // 16	BADVERS	Bad OPT Version	[RFC6891]
             17 => ResponseCode::BADKEY,    // 17	BADKEY	Key not recognized	[RFC2845]
             18 => ResponseCode::BADTIME,   // 18	BADTIME	Signature out of time window	[RFC2845]
             19 => ResponseCode::BADMODE,   // 19	BADMODE	Bad TKEY Mode	[RFC2930]
@@ -252,7 +277,30 @@ impl From<u16> for ResponseCode {
             21 => ResponseCode::BADALG,    // 21	BADALG	Algorithm not supported	[RFC2930]
             22 => ResponseCode::BADTRUNC,  // 22	BADTRUNC	Bad Truncation	[RFC4635]
             23 => ResponseCode::BADCOOKIE, // 23	BADCOOKIE (TEMPORARY - registered 2015-07-26, expires 2016-07-26)	Bad/missing server cookie	[draft-ietf-dnsop-cookies]
-            _ => unimplemented!(),
+            _ => ResponseCode::Unknown(value),
         }
     }
 }
+
+#[test]
+fn test_extended_round_trip() {
+    for value in 0..4096_u16 {
+        let response_code = ResponseCode::from(value);
+        assert_eq!(u16::from(response_code), value);
+        assert_eq!(
+            ResponseCode::from(response_code.high(), response_code.low()),
+            response_code
+        );
+        assert_eq!(response_code.is_extended(), value > 15);
+    }
+}
+
+#[test]
+fn test_tsig_error() {
+    assert_eq!(ResponseCode::from(16), ResponseCode::BADVERS);
+    assert_eq!(ResponseCode::from_tsig_error(16), ResponseCode::BADSIG);
+    assert_eq!(ResponseCode::from_tsig_error(17), ResponseCode::BADKEY);
+    assert_eq!(ResponseCode::from_tsig_error(18), ResponseCode::BADTIME);
+    assert_eq!(ResponseCode::from_tsig_error(22), ResponseCode::BADTRUNC);
+    assert_eq!(u16::from(ResponseCode::BADSIG), 16);
+}
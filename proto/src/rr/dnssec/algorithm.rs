@@ -115,8 +115,11 @@ pub enum Algorithm {
     ECDSAP256SHA256,
     /// [rfc6605](https://tools.ietf.org/html/rfc6605)
     ECDSAP384SHA384,
-    /// [draft-ietf-curdle-dnskey-eddsa-03](https://tools.ietf.org/html/draft-ietf-curdle-dnskey-eddsa-03)
+    /// [RFC 8080](https://tools.ietf.org/html/rfc8080), Ed25519, requires the ring feature
     ED25519,
+    /// [RFC 8080](https://tools.ietf.org/html/rfc8080), Ed448, recognized, but neither ring nor
+    ///  openssl support signing or verifying with it
+    ED448,
 }
 
 impl Algorithm {
@@ -130,6 +133,7 @@ impl Algorithm {
             13 => Ok(Algorithm::ECDSAP256SHA256),
             14 => Ok(Algorithm::ECDSAP384SHA384),
             15 => Ok(Algorithm::ED25519),
+            16 => Ok(Algorithm::ED448),
             _ => Err(ProtoErrorKind::UnknownAlgorithmTypeValue(value).into()),
         }
     }
@@ -143,6 +147,7 @@ impl Algorithm {
             Algorithm::ECDSAP256SHA256 |
             Algorithm::ED25519 => 32, // 256 bits
             Algorithm::ECDSAP384SHA384 => 48,
+            Algorithm::ED448 => 57, // 456 bits
             Algorithm::RSASHA512 => 64, // 512 bites
         }
     }
@@ -157,6 +162,7 @@ impl Algorithm {
             Algorithm::ECDSAP256SHA256 => "ECDSAP256SHA256",
             Algorithm::ECDSAP384SHA384 => "ECDSAP384SHA384",
             Algorithm::ED25519 => "ED25519",
+            Algorithm::ED448 => "ED448",
         }
     }
 }
@@ -185,6 +191,7 @@ impl FromStr for Algorithm {
             "ECDSAP256SHA256" => Ok(Algorithm::ECDSAP256SHA256),
             "ECDSAP384SHA384" => Ok(Algorithm::ECDSAP384SHA384),
             "ED25519" => Ok(Algorithm::ED25519),
+            "ED448" => Ok(Algorithm::ED448),
             _ => Err(
                 ProtoErrorKind::Msg(format!("unrecognized string {}", s)).into(),
             ),
@@ -208,6 +215,7 @@ impl From<Algorithm> for u8 {
             Algorithm::ECDSAP256SHA256 => 13,
             Algorithm::ECDSAP384SHA384 => 14,
             Algorithm::ED25519 => 15,
+            Algorithm::ED448 => 16,
         }
     }
 }
@@ -228,6 +236,7 @@ fn test_into() {
         Algorithm::ECDSAP256SHA256,
         Algorithm::ECDSAP384SHA384,
         Algorithm::ED25519,
+        Algorithm::ED448,
     ]
     {
         assert_eq!(
@@ -247,6 +256,7 @@ fn test_order() {
         Algorithm::ECDSAP256SHA256,
         Algorithm::ECDSAP384SHA384,
         Algorithm::ED25519,
+        Algorithm::ED448,
     ];
 
     algorithms.sort();
@@ -260,6 +270,7 @@ fn test_order() {
             Algorithm::ECDSAP256SHA256,
             Algorithm::ECDSAP384SHA384,
             Algorithm::ED25519,
+            Algorithm::ED448,
         ].iter(),
     )
     {
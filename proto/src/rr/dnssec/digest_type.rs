@@ -33,7 +33,7 @@ use super::Digest;
 /// 2	SHA-256	MANDATORY	[RFC4509]
 /// 3	GOST R 34.11-94	OPTIONAL	[RFC5933]
 /// 4	SHA-384	OPTIONAL	[RFC6605]
-/// 5 ED25519 [RFC draft-ietf-curdle-dnskey-eddsa-03]
+/// 5 ED25519 [RFC 8080], only used internally, not a DS digest type
 /// 5-255	Unassigned	-
 /// ```
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
@@ -47,7 +47,7 @@ pub enum DigestType {
     SHA384,
     /// Undefined
     SHA512,
-    /// This is a passthrough digest as ED25519 is self-packaged
+    /// This is a passthrough digest as ED25519 and ED448 are self-packaged
     ED25519,
 }
 
@@ -153,7 +153,8 @@ impl From<Algorithm> for DigestType {
             Algorithm::RSASHA512 => DigestType::SHA512,
             Algorithm::ECDSAP256SHA256 => DigestType::SHA256,
             Algorithm::ECDSAP384SHA384 => DigestType::SHA384,
-            Algorithm::ED25519 => DigestType::ED25519,
+            Algorithm::ED25519 |
+            Algorithm::ED448 => DigestType::ED25519,
         }
     }
 }
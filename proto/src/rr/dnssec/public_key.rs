@@ -281,13 +281,14 @@ pub struct Ed25519<'k> {
 #[cfg(feature = "ring")]
 impl<'k> Ed25519<'k> {
     /// ```text
-    ///  Internet-Draft              EdDSA for DNSSEC               December 2016
+    ///  RFC 8080                    EdDSA for DNSSEC                February 2017
+    ///
+    ///  3.  DNSKEY Resource Records
     ///
     ///  An Ed25519 public key consists of a 32-octet value, which is encoded
     ///  into the Public Key field of a DNSKEY resource record as a simple bit
     ///  string.  The generation of a public key is defined in Section 5.1.5
-    ///  in [RFC 8032]. Breaking tradition, the keys are encoded in little-
-    ///  endian byte order.
+    ///  of [RFC8032].
     /// ```
     pub fn from_public_bytes(public_key: &'k [u8]) -> ProtoResult<Self> {
         if public_key.len() != ED25519_PUBLIC_KEY_LEN {
@@ -456,6 +457,7 @@ impl<'k> PublicKeyEnum<'k> {
                     Ed25519::from_public_bytes(public_key)?,
                 ))
             }
+            Algorithm::ED448 => Err("ED448 is not supported by ring or openssl".into()),
             #[cfg(any(feature = "openssl", feature = "ring"))]
             Algorithm::RSASHA1 |
             Algorithm::RSASHA1NSEC3SHA1 |
@@ -547,4 +549,63 @@ mod tests {
         test_case(&[0xff, 0x00, 0x80], &[0x00, 0xff, 0x00, 0x80]);
     }
 
+    #[cfg(feature = "ring")]
+    #[test]
+    fn test_rfc8080_ed25519_rrsig() {
+        use data_encoding::base64;
+
+        use rr::{DNSClass, Name, RData, Record, RecordType};
+        use rr::dnssec::{Algorithm, Verifier};
+        use rr::rdata::{DNSKEY, MX, SIG};
+
+        // RFC 8080 section 6.1
+        let name = Name::parse("example.com.", None).unwrap();
+        let public_key = base64::decode(b"l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=").unwrap();
+        let dnskey = DNSKEY::new(true, true, false, Algorithm::ED25519, public_key);
+
+        let signature = base64::decode(
+            b"oL9krJun7xfBOIWcGHi7mag5/hdZrKWw15jPGrHpjQeRAvTdszaPD+QLs3fx8A4M3e23mRZ9VrbpMngwcrqNAg==",
+        ).unwrap();
+        let rrsig = SIG::new(
+            RecordType::MX,
+            Algorithm::ED25519,
+            2,
+            3600,
+            1440021600,
+            1438207200,
+            3613,
+            name.clone(),
+            signature,
+        );
+
+        let mx = |preference| {
+            Record::from_rdata(
+                name.clone(),
+                3600,
+                RecordType::MX,
+                RData::MX(MX::new(
+                    preference,
+                    Name::parse("mail.example.com.", None).unwrap(),
+                )),
+            )
+        };
+
+        assert!(
+            dnskey
+                .verify_rrsig(&name, DNSClass::IN, &rrsig, &[mx(10)])
+                .is_ok()
+        );
+        assert!(
+            dnskey
+                .verify_rrsig(&name, DNSClass::IN, &rrsig, &[mx(20)])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_ed448_unsupported() {
+        use rr::dnssec::{Algorithm, PublicKeyEnum};
+
+        assert!(PublicKeyEnum::from_public_bytes(&[0; 57], Algorithm::ED448).is_err());
+    }
 }
@@ -35,7 +35,7 @@ impl SupportedAlgorithms {
         SupportedAlgorithms { bit_map: 0 }
     }
 
-    /// Specify the entire set is supported
+    /// Specify the entire set is supported, of the algorithms which can be verified, i.e. not ED448
     pub fn all() -> Self {
         SupportedAlgorithms { bit_map: 0b01111111 }
     }
@@ -61,6 +61,7 @@ impl SupportedAlgorithms {
             Algorithm::ECDSAP256SHA256 => 4,
             Algorithm::ECDSAP384SHA384 => 5,
             Algorithm::ED25519 => 6,
+            Algorithm::ED448 => 7,
         };
 
         assert!(bit_pos <= u8::max_value());
@@ -77,6 +78,7 @@ impl SupportedAlgorithms {
             4 => Some(Algorithm::ECDSAP256SHA256),
             5 => Some(Algorithm::ECDSAP384SHA384),
             6 => Some(Algorithm::ED25519),
+            7 => Some(Algorithm::ED448),
             _ => None,
         }
     }
@@ -100,8 +100,9 @@ pub fn rrset_tbs(
         }
     }
 
-    // put records in canonical order
-    rrset.sort();
+    // put records in canonical order, RFC 4034 section 6.3, without duplicates
+    rrset.sort_by(|a, b| a.canonical_cmp(b));
+    rrset.dedup_by(|a, b| a.canonical_eq(b));
 
     let name: Name = if let Some(name) = determine_name(name, num_labels) {
         name
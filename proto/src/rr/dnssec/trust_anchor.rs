@@ -20,9 +20,13 @@ use std::default::Default;
 
 use rr::dnssec::PublicKey;
 
+/// The root KSK-2010, key tag 19036
 const ROOT_ANCHOR: &'static [u8] = include_bytes!("Kjqmt7v.rsa");
+/// The root KSK-2017, key tag 20326, see https://data.iana.org/root-anchors/root-anchors.xml
+const ROOT_ANCHOR_2017: &'static [u8] = include_bytes!("Klajeyz.rsa");
 
 /// The root set of trust anchors for validating DNSSec, anything in this set will be trusted
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TrustAnchor {
     // TODO: these should also store some information, or more specifically, metadata from the signed
     //  public certificate.
@@ -31,7 +35,7 @@ pub struct TrustAnchor {
 
 impl Default for TrustAnchor {
     fn default() -> TrustAnchor {
-        TrustAnchor { pkeys: vec![ROOT_ANCHOR.to_owned()] }
+        TrustAnchor { pkeys: vec![ROOT_ANCHOR.to_owned(), ROOT_ANCHOR_2017.to_owned()] }
     }
 }
 
@@ -62,10 +66,31 @@ impl TrustAnchor {
         }
     }
 
+    /// removes the key from the trusted chain, returns true if it was in the set
+    ///
+    /// # Arguments
+    ///
+    /// * `other_key` - The raw dnskey in bytes
+    pub fn remove_dnskey_bytes(&mut self, other_key: &[u8]) -> bool {
+        let len = self.pkeys.len();
+        self.pkeys.retain(|k| other_key != k.as_slice());
+        self.pkeys.len() != len
+    }
+
     /// get the trust anchor at the specified index
     pub fn get(&self, idx: usize) -> &[u8] {
         &self.pkeys[idx]
     }
+
+    /// number of keys in the trust anchor set
+    pub fn len(&self) -> usize {
+        self.pkeys.len()
+    }
+
+    /// true if there are no keys in the trust anchor set, nothing will validate
+    pub fn is_empty(&self) -> bool {
+        self.pkeys.is_empty()
+    }
 }
 
 #[test]
@@ -74,3 +99,16 @@ fn test_kjqmt7v() {
     assert_eq!(trust.get(0), ROOT_ANCHOR);
     assert!(trust.contains_dnskey_bytes(ROOT_ANCHOR));
 }
+
+#[test]
+fn test_klajeyz() {
+    let mut trust = TrustAnchor::default();
+    assert_eq!(trust.get(1), ROOT_ANCHOR_2017);
+    assert!(trust.contains_dnskey_bytes(ROOT_ANCHOR_2017));
+
+    assert!(trust.remove_dnskey_bytes(ROOT_ANCHOR));
+    assert!(!trust.remove_dnskey_bytes(ROOT_ANCHOR));
+    assert_eq!(trust.len(), 1);
+    assert!(!trust.contains_dnskey_bytes(ROOT_ANCHOR));
+    assert!(trust.contains_dnskey_bytes(ROOT_ANCHOR_2017));
+}
@@ -102,6 +102,29 @@ impl Name {
         self.is_fqdn = val
     }
 
+    /// Returns this name marked as a fully qualified domain name
+    ///
+    /// This is for names which are known to be absolute, but were parsed without the final `.`,
+    ///  so that resolvers look them up as is, rather than with a search list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use trust_dns_proto::rr::domain::Name;
+    ///
+    /// let name = Name::from_str("www.example.com").unwrap();
+    /// assert!(!name.is_fqdn());
+    ///
+    /// let name = name.into_fqdn();
+    /// assert!(name.is_fqdn());
+    /// assert_eq!(name, Name::from_str("www.example.com.").unwrap());
+    /// ```
+    pub fn into_fqdn(mut self) -> Self {
+        self.is_fqdn = true;
+        self
+    }
+
     /// inline builder
     ///
     /// *see: `append_label` for replacement*
@@ -159,6 +182,27 @@ impl Name {
         }
     }
 
+    /// Returns the name of the PTR records of the address, in `in-addr.arpa.` for IPv4, or in
+    ///  `ip6.arpa.` for IPv6, RFC 1035 section 3.5 and RFC 3596 section 2.5
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    /// use std::str::FromStr;
+    /// use trust_dns_proto::rr::domain::Name;
+    ///
+    /// let name = Name::from_ip(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)));
+    /// assert_eq!(name, Name::from_str("1.2.0.192.in-addr.arpa.").unwrap());
+    ///
+    /// let name = Name::from_ip(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)));
+    /// assert_eq!(name.iter().next(), Some("1"));
+    /// assert!(Name::from_str("ip6.arpa.").unwrap().zone_of(&name));
+    /// ```
+    pub fn from_ip(addr: IpAddr) -> Self {
+        addr.into()
+    }
+
     /// Deprecated in favor of `from_labels`
     #[deprecated]
     pub fn with_labels(labels: Vec<String>) -> Self {
@@ -401,6 +445,23 @@ impl Name {
         num
     }
 
+    /// Returns an iterator over the labels, from the leftmost to the TLD, reverse it to walk the
+    ///  name from the root down
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use trust_dns_proto::rr::domain::Name;
+    ///
+    /// let www_example_com = Name::from_labels(vec!["www", "example", "com"]);
+    /// assert_eq!(www_example_com.iter().collect::<Vec<_>>(), vec!["www", "example", "com"]);
+    /// assert_eq!(www_example_com.iter().rev().next(), Some("com"));
+    /// assert_eq!(Name::root().iter().next(), None);
+    /// ```
+    pub fn iter(&self) -> LabelIter {
+        LabelIter(self.labels.iter())
+    }
+
     /// returns the length in bytes of the labels. '.' counts as 1
     ///
     /// This can be used as an estimate, when serializing labels, they will often be compressed
@@ -484,10 +545,14 @@ impl Name {
                 }
                 ParseState::Escape3(i, ii) => {
                     if ch.is_numeric() {
-                        let val: u32 = (i << 16) + (ii << 8) +
+                        // the decimal value of an octet, \DDD
+                        let val: u32 = i * 100 + ii * 10 +
                             try!(ch.to_digit(10).ok_or(ProtoError::from(ProtoErrorKind::Msg(
                                 format!("illegal char: {}", ch),
                             ))));
+                        if val > u8::max_value() as u32 {
+                            return Err(ProtoErrorKind::Msg(format!("illegal octet: {}", val)).into());
+                        }
                         let new: char = try!(char::from_u32(val).ok_or(
                             ProtoError::from(ProtoErrorKind::Msg(
                                 format!("illegal char: {}", ch),
@@ -578,14 +643,14 @@ impl Name {
         encoder: &mut BinEncoder,
         lowercase: bool,
     ) -> ProtoResult<()> {
-        let is_canonical_names = encoder.is_canonical_names();
+        let is_uncompressed_names = encoder.is_uncompressed_names();
         if lowercase {
             self.to_lowercase().emit_as_canonical(
                 encoder,
-                is_canonical_names,
+                is_uncompressed_names,
             )
         } else {
-            self.emit_as_canonical(encoder, is_canonical_names)
+            self.emit_as_canonical(encoder, is_uncompressed_names)
         }
     }
 
@@ -628,6 +693,29 @@ impl Name {
     }
 }
 
+/// An iterator over the labels of a Name, see `Name::iter`
+pub struct LabelIter<'a>(::std::slice::Iter<'a, Rc<String>>);
+
+impl<'a> Iterator for LabelIter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|label| label.as_str())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for LabelIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|label| label.as_str())
+    }
+}
+
+impl<'a> ExactSizeIterator for LabelIter<'a> {}
+
 impl From<IpAddr> for Name {
     fn from(addr: IpAddr) -> Name {
         match addr {
@@ -784,8 +872,8 @@ impl BinSerializable<Name> for Name {
     }
 
     fn emit(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
-        let is_canonical_names = encoder.is_canonical_names();
-        self.emit_as_canonical(encoder, is_canonical_names)
+        let is_uncompressed_names = encoder.is_uncompressed_names();
+        self.emit_as_canonical(encoder, is_uncompressed_names)
     }
 }
 
@@ -1083,6 +1171,7 @@ mod tests {
         let name = Name::from_labels(vec!["103", "0", "3", "26", "in-addr", "arpa"]);
 
         assert_eq!(Into::<Name>::into(ip), name);
+        assert_eq!(Name::from_ip(ip), name);
     }
 
     #[test]
@@ -1140,6 +1229,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_escapes() {
+        assert_eq!(
+            Name::parse("a\\.b.example.", None).unwrap(),
+            Name::from_labels(vec!["a.b", "example"])
+        );
+        assert_eq!(
+            Name::parse("a\\032b\\046c.example.", None).unwrap(),
+            Name::from_labels(vec!["a b.c", "example"])
+        );
+        assert!(Name::parse("a\\256.example.", None).is_err());
+        assert!(Name::parse("a\\2x.example.", None).is_err());
+    }
+
     #[test]
     fn test_fqdn() {
         assert!(Name::root().is_fqdn());
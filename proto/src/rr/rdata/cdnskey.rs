@@ -0,0 +1,136 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! child copy of the DNSKEY record, for the parent zone to create the DS from
+
+use serialize::binary::*;
+use error::*;
+use rr::rdata::DNSKEY;
+use rr::rdata::dnskey;
+
+/// The wire form of the CDNSKEY requesting the removal of the DS RRset, `0 3 0 AA==`
+const DELETE: [u8; 5] = [0, 0, 3, 0, 0];
+
+/// [RFC 7344, Automating DNSSEC Delegation Trust Maintenance, September 2014](https://tools.ietf.org/html/rfc7344#section-3.2)
+///
+/// ```text
+/// 3.2.  CDNSKEY Resource Record Format
+///
+///    The wire and presentation format of the CDNSKEY ("Child DNSKEY")
+///    resource record is identical to the DNSKEY record.  IANA has
+///    allocated RR code 60 for the CDNSKEY resource record via Expert
+///    Review.  The CDNSKEY RR uses the same registries as DNSKEY for its
+///    fields.
+///
+///    No special processing is performed by authoritative servers or by
+///    resolvers, when serving or resolving.  For all practical purposes,
+///    CDNSKEY is a regular RR type.
+/// ```
+///
+/// [RFC 8078, Managing DS Records from the Parent via CDS/CDNSKEY, March 2017](https://tools.ietf.org/html/rfc8078#section-4)
+///  adds the DNSSEC Delete Algorithm, `CDNSKEY 0 3 0 AA==`,
+///  with which the child signals that it would like the DS RRset removed from the parent.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum CDNSKEY {
+    /// The DNSKEY from which the child would like the parent to create the DS
+    DNSKEY(DNSKEY),
+    /// The child would like the DS RRset removed from the parent, the zone becomes insecure
+    Delete,
+}
+
+impl CDNSKEY {
+    /// The DNSKEY, None for the request to delete the DS RRset
+    pub fn dnskey(&self) -> Option<&DNSKEY> {
+        match *self {
+            CDNSKEY::DNSKEY(ref dnskey) => Some(dnskey),
+            CDNSKEY::Delete => None,
+        }
+    }
+
+    /// true if this is the request to delete the DS RRset
+    pub fn is_delete(&self) -> bool {
+        *self == CDNSKEY::Delete
+    }
+}
+
+impl From<DNSKEY> for CDNSKEY {
+    fn from(dnskey: DNSKEY) -> CDNSKEY {
+        CDNSKEY::DNSKEY(dnskey)
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> ProtoResult<CDNSKEY> {
+    // the delete algorithm, 0, is not a DNSSec algorithm, so the delete form is checked first
+    if rdata_length as usize == DELETE.len() {
+        let rdata = try!(decoder.read_vec(DELETE.len()));
+        if rdata[..] == DELETE[..] {
+            return Ok(CDNSKEY::Delete);
+        }
+
+        let mut decoder = BinDecoder::new(&rdata);
+        return dnskey::read(&mut decoder, rdata_length).map(CDNSKEY::DNSKEY);
+    }
+
+    dnskey::read(decoder, rdata_length).map(CDNSKEY::DNSKEY)
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, rdata: &CDNSKEY) -> ProtoResult<()> {
+    match *rdata {
+        CDNSKEY::DNSKEY(ref dnskey) => dnskey::emit(encoder, dnskey),
+        CDNSKEY::Delete => encoder.emit_vec(&DELETE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rr::dnssec::Algorithm;
+
+    use super::*;
+
+    fn round_trip(rdata: CDNSKEY) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+            assert!(emit(&mut encoder, &rdata).is_ok());
+        }
+
+        {
+            let mut decoder: BinDecoder = BinDecoder::new(&bytes);
+            let read_rdata = read(&mut decoder, bytes.len() as u16);
+            assert!(
+                read_rdata.is_ok(),
+                format!("error decoding: {:?}", read_rdata.unwrap_err())
+            );
+            assert_eq!(rdata, read_rdata.unwrap());
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn test() {
+        let dnskey = DNSKEY::new(true, true, false, Algorithm::ED25519, vec![1, 2, 3, 4]);
+
+        assert_eq!(
+            round_trip(CDNSKEY::DNSKEY(dnskey)),
+            vec![1, 1, 3, 15, 1, 2, 3, 4]
+        );
+
+        // a key of a single byte has the same length as the delete form
+        let dnskey = DNSKEY::new(true, false, false, Algorithm::ED25519, vec![0]);
+        round_trip(CDNSKEY::DNSKEY(dnskey));
+    }
+
+    #[test]
+    fn test_delete() {
+        assert_eq!(round_trip(CDNSKEY::Delete), vec![0, 0, 3, 0, 0]);
+        assert!(CDNSKEY::Delete.is_delete());
+        assert!(CDNSKEY::Delete.dnskey().is_none());
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! child copy of the DS record, for the parent zone to publish
+
+use serialize::binary::*;
+use error::*;
+use rr::rdata::DS;
+use rr::rdata::ds;
+
+/// The wire form of the CDS requesting the removal of the DS RRset, `0 0 0 00`
+const DELETE: [u8; 5] = [0, 0, 0, 0, 0];
+
+/// [RFC 7344, Automating DNSSEC Delegation Trust Maintenance, September 2014](https://tools.ietf.org/html/rfc7344#section-3.1)
+///
+/// ```text
+/// 3.1.  CDS Resource Record Format
+///
+///    The wire and presentation format of the Child DS (CDS) resource
+///    record is identical to the DS record [RFC4034].  IANA has allocated
+///    RR code 59 for the CDS resource record via Expert Review
+///    [DNSEXT-EXPERT].  The CDS RR uses the same registries as DS for its
+///    fields.
+/// ```
+///
+/// [RFC 8078, Managing DS Records from the Parent via CDS/CDNSKEY, March 2017](https://tools.ietf.org/html/rfc8078#section-4)
+///  adds the DNSSEC Delete Algorithm, `CDS 0 0 0 00`,
+///  with which the child signals that it would like the DS RRset removed from the parent.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum CDS {
+    /// The DS which the child would like published in the parent
+    DS(DS),
+    /// The child would like the DS RRset removed from the parent, the zone becomes insecure
+    Delete,
+}
+
+impl CDS {
+    /// The DS, None for the request to delete the DS RRset
+    pub fn ds(&self) -> Option<&DS> {
+        match *self {
+            CDS::DS(ref ds) => Some(ds),
+            CDS::Delete => None,
+        }
+    }
+
+    /// true if this is the request to delete the DS RRset
+    pub fn is_delete(&self) -> bool {
+        *self == CDS::Delete
+    }
+}
+
+impl From<DS> for CDS {
+    fn from(ds: DS) -> CDS {
+        CDS::DS(ds)
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> ProtoResult<CDS> {
+    // the delete algorithm, 0, is not a DNSSec algorithm, so the delete form is checked first
+    if rdata_length as usize == DELETE.len() {
+        let rdata = try!(decoder.read_vec(DELETE.len()));
+        if rdata[..] == DELETE[..] {
+            return Ok(CDS::Delete);
+        }
+
+        let mut decoder = BinDecoder::new(&rdata);
+        return ds::read(&mut decoder, rdata_length).map(CDS::DS);
+    }
+
+    ds::read(decoder, rdata_length).map(CDS::DS)
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, rdata: &CDS) -> ProtoResult<()> {
+    match *rdata {
+        CDS::DS(ref ds) => ds::emit(encoder, ds),
+        CDS::Delete => encoder.emit_vec(&DELETE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rr::dnssec::{Algorithm, DigestType};
+
+    use super::*;
+
+    fn round_trip(rdata: CDS) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+            assert!(emit(&mut encoder, &rdata).is_ok());
+        }
+
+        {
+            let mut decoder: BinDecoder = BinDecoder::new(&bytes);
+            let read_rdata = read(&mut decoder, bytes.len() as u16);
+            assert!(
+                read_rdata.is_ok(),
+                format!("error decoding: {:?}", read_rdata.unwrap_err())
+            );
+            assert_eq!(rdata, read_rdata.unwrap());
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn test() {
+        let ds = DS::new(
+            0xF00F,
+            Algorithm::RSASHA256,
+            DigestType::SHA256,
+            vec![5, 6, 7, 8],
+        );
+
+        assert_eq!(
+            round_trip(CDS::DS(ds)),
+            vec![0xF0, 0x0F, 8, 2, 5, 6, 7, 8]
+        );
+
+        // a digest of a single byte has the same length as the delete form
+        let ds = DS::new(0, Algorithm::RSASHA256, DigestType::SHA256, vec![0]);
+        round_trip(CDS::DS(ds));
+    }
+
+    #[test]
+    fn test_delete() {
+        assert_eq!(round_trip(CDS::Delete), vec![0, 0, 0, 0, 0]);
+        assert!(CDS::Delete.is_delete());
+        assert!(CDS::Delete.ds().is_none());
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! child to parent synchronization of the delegation records
+
+use serialize::binary::*;
+use error::*;
+use rr::RecordType;
+use rr::rdata::nsec3;
+
+/// The immediate flag
+const IMMEDIATE: u16 = 0b0000_0000_0000_0001;
+/// The soaminimum flag
+const SOA_MINIMUM: u16 = 0b0000_0000_0000_0010;
+
+/// [RFC 7477, Child-to-Parent Synchronization in DNS, March 2015](https://tools.ietf.org/html/rfc7477#section-2.1.1)
+///
+/// ```text
+/// 2.1.1.  The CSYNC Resource Record Wire Format
+///
+///    The CSYNC RDATA consists of the following fields:
+///
+///                           1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///       0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///      |                          SOA Serial                           |
+///      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///      |       Flags                   |            Type Bit Map       /
+///      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///      /                     Type Bit Map (continued)                  /
+///      +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct CSYNC {
+    soa_serial: u32,
+    immediate: bool,
+    soa_minimum: bool,
+    type_bit_maps: Vec<RecordType>,
+}
+
+impl CSYNC {
+    /// Constructs a new CSYNC RData
+    ///
+    /// # Arguments
+    ///
+    /// * `soa_serial` - the serial of the SOA of the child zone which the records are taken from
+    /// * `immediate` - the parent may process the CSYNC without out of band approval
+    /// * `soa_minimum` - the parent must only process the CSYNC if the child's SOA serial is at
+    ///                   least `soa_serial`
+    /// * `type_bit_maps` - the types of the records at the apex of the child to be copied to the
+    ///                     parent
+    ///
+    /// # Returns
+    ///
+    /// A CSYNC RData for use in a Resource Record
+    pub fn new(
+        soa_serial: u32,
+        immediate: bool,
+        soa_minimum: bool,
+        type_bit_maps: Vec<RecordType>,
+    ) -> CSYNC {
+        CSYNC {
+            soa_serial: soa_serial,
+            immediate: immediate,
+            soa_minimum: soa_minimum,
+            type_bit_maps: type_bit_maps,
+        }
+    }
+
+    /// The serial of the SOA of the child zone, RFC 7477 section 2.1.1.1
+    pub fn soa_serial(&self) -> u32 {
+        self.soa_serial
+    }
+
+    /// The immediate flag, without it the parent must wait for the operation to be approved out of
+    ///  band before processing the CSYNC, RFC 7477 section 2.1.1.2.1
+    pub fn immediate(&self) -> bool {
+        self.immediate
+    }
+
+    /// The soaminimum flag, the parent must only process the records of a child zone whose SOA
+    ///  serial is at least `soa_serial`, RFC 7477 section 2.1.1.2.2
+    pub fn soa_minimum(&self) -> bool {
+        self.soa_minimum
+    }
+
+    /// The types of the records at the apex of the child zone to be copied to the parent,
+    ///  encoded as the type bit maps of NSEC, RFC 7477 section 2.1.1.3
+    pub fn type_bit_maps(&self) -> &[RecordType] {
+        &self.type_bit_maps
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> ProtoResult<CSYNC> {
+    let start_idx = decoder.index();
+
+    let soa_serial = try!(decoder.read_u32());
+
+    // unknown flags are ignored
+    let flags = try!(decoder.read_u16());
+    let immediate = flags & IMMEDIATE == IMMEDIATE;
+    let soa_minimum = flags & SOA_MINIMUM == SOA_MINIMUM;
+
+    let bit_map_len = rdata_length as usize - (decoder.index() - start_idx);
+    let record_types = try!(nsec3::decode_type_bit_maps(decoder, bit_map_len));
+
+    Ok(CSYNC::new(soa_serial, immediate, soa_minimum, record_types))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, rdata: &CSYNC) -> ProtoResult<()> {
+    let mut flags: u16 = 0;
+    if rdata.immediate() {
+        flags |= IMMEDIATE;
+    }
+    if rdata.soa_minimum() {
+        flags |= SOA_MINIMUM;
+    }
+
+    try!(encoder.emit_u32(rdata.soa_serial()));
+    try!(encoder.emit_u16(flags));
+    try!(nsec3::encode_bit_maps(encoder, rdata.type_bit_maps()));
+
+    Ok(())
+}
+
+#[test]
+pub fn test() {
+    // RFC 7477 section 2.2, CSYNC 66 3 A NS AAAA
+    let rdata = CSYNC::new(
+        66,
+        true,
+        true,
+        vec![RecordType::A, RecordType::NS, RecordType::AAAA],
+    );
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    println!("bytes: {:?}", bytes);
+    assert_eq!(&bytes[..6], &[0, 0, 0, 66, 0, 3]);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder, bytes.len() as u16);
+    assert!(
+        read_rdata.is_ok(),
+        format!("error decoding: {:?}", read_rdata.unwrap_err())
+    );
+    assert_eq!(rdata, read_rdata.unwrap());
+}
@@ -189,6 +189,38 @@ impl DNSKEY {
         &self.public_key
     }
 
+    /// The key tag of this DNSKEY, by which the RRSIGs and DS records refer to it
+    ///
+    /// [RFC 4034, DNSSEC Resource Records, March 2005](https://tools.ietf.org/html/rfc4034#appendix-B)
+    ///
+    /// ```text
+    /// Appendix B.  Key Tag Calculation
+    ///
+    ///    The Key Tag field in the RRSIG and DS resource record types provides
+    ///    a mechanism for selecting a public key efficiently.  In most cases, a
+    ///    combination of owner name, algorithm, and key tag can efficiently
+    ///    identify a DNSKEY record.
+    ///
+    ///    The key tag is the same for all DNSKEY algorithm types except
+    ///    algorithm 1 (please see Appendix B.1 for the definition of the key
+    ///    tag for algorithm 1).  The key tag algorithm is the sum of the wire
+    ///    format of the DNSKEY RDATA broken into 2 octet groups.
+    /// ```
+    pub fn calculate_key_tag(&self) -> ProtoResult<u16> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(512);
+        {
+            let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+            try!(emit(&mut encoder, self));
+        }
+
+        let mut ac: u32 = 0;
+        for (i, k) in bytes.iter().enumerate() {
+            ac += (*k as u32) << if i & 0x01 != 0 { 0 } else { 8 };
+        }
+        ac += ac >> 16;
+        Ok((ac & 0xFFFF) as u16)
+    }
+
     /// Creates a message digest for this DNSKEY record.
     ///
     /// ```text
@@ -344,3 +376,27 @@ pub fn test() {
             .is_ok()
     );
 }
+
+#[test]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+pub fn test_rfc8080_ed25519() {
+    use data_encoding::{base64, hex};
+
+    // RFC 8080 section 6.1, example.com. 3600 IN DNSKEY 257 3 15 ( ... )
+    let public_key = base64::decode(b"l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=").unwrap();
+    let rdata = DNSKEY::new(true, true, false, Algorithm::ED25519, public_key);
+
+    // example.com. 3600 IN DS 3613 15 2 ( ... )
+    assert_eq!(rdata.calculate_key_tag().unwrap(), 3613);
+    let digest = rdata
+        .to_digest(
+            &Name::parse("example.com.", None).unwrap(),
+            DigestType::SHA256,
+        )
+        .unwrap();
+    assert_eq!(
+        digest.as_ref(),
+        &hex::decode(b"3AA5AB37EFCE57F737FC1627013FEE07BDF241BD10F3B1964AB55C78E79A304B")
+            .unwrap()[..]
+    );
+}
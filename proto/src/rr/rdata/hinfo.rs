@@ -0,0 +1,108 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! host information, the CPU and OS of a host
+
+use serialize::binary::*;
+use error::*;
+
+/// The CPU of the HINFO synthesized by RFC 8482 servers in answer to queries of type ANY
+pub const RFC8482_CPU: &'static str = "RFC8482";
+
+/// [RFC 1035, DOMAIN NAMES - IMPLEMENTATION AND SPECIFICATION, November 1987](https://tools.ietf.org/html/rfc1035#section-3.3.2)
+///
+/// ```text
+/// 3.3.2. HINFO RDATA format
+///
+///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///     /                      CPU                      /
+///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///     /                       OS                      /
+///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///
+/// where:
+///
+/// CPU             A <character-string> which specifies the CPU type.
+///
+/// OS              A <character-string> which specifies the operating
+///                 system type.
+/// ```
+///
+/// [RFC 8482](https://tools.ietf.org/html/rfc8482#section-4.2) servers may answer queries of
+///  type ANY with a synthesized HINFO of the CPU `RFC8482` and an empty OS.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct HINFO {
+    cpu: String,
+    os: String,
+}
+
+impl HINFO {
+    /// Creates a new HINFO record data.
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - the CPU type of the host
+    /// * `os` - the operating system type of the host
+    pub fn new(cpu: String, os: String) -> HINFO {
+        HINFO { cpu: cpu, os: os }
+    }
+
+    /// The CPU type of the host
+    pub fn cpu(&self) -> &str {
+        &self.cpu
+    }
+
+    /// The operating system type of the host
+    pub fn os(&self) -> &str {
+        &self.os
+    }
+
+    /// Returns true if this is the HINFO an RFC 8482 server answers queries of type ANY with,
+    ///  rather than all of the records of the name
+    pub fn is_rfc8482(&self) -> bool {
+        self.cpu == RFC8482_CPU
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder) -> ProtoResult<HINFO> {
+    let cpu = try!(decoder.read_character_data());
+    let os = try!(decoder.read_character_data());
+
+    Ok(HINFO::new(cpu, os))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, hinfo: &HINFO) -> ProtoResult<()> {
+    try!(encoder.emit_character_data(hinfo.cpu()));
+    try!(encoder.emit_character_data(hinfo.os()));
+
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let rdata = HINFO::new(RFC8482_CPU.to_string(), "".to_string());
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    println!("bytes: {:?}", bytes);
+    assert_eq!(bytes, b"\x07RFC8482\x00");
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder);
+    assert!(
+        read_rdata.is_ok(),
+        format!("error decoding: {:?}", read_rdata.unwrap_err())
+    );
+    let read_rdata = read_rdata.unwrap();
+    assert!(read_rdata.is_rfc8482());
+    assert_eq!(rdata, read_rdata);
+}
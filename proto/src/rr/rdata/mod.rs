@@ -21,31 +21,55 @@
 // each of these module's has the parser for that rdata embedded, to keep the file sizes down...
 pub mod a;
 pub mod aaaa;
+pub mod cdnskey;
+pub mod cds;
+pub mod csync;
 pub mod dnskey;
 pub mod ds;
+pub mod hinfo;
 pub mod key;
 pub mod mx;
 pub mod name;
+pub mod naptr;
 pub mod null;
 pub mod nsec;
 pub mod nsec3;
 pub mod nsec3param;
+pub mod openpgpkey;
 pub mod opt;
 pub mod sig;
 pub mod soa;
 pub mod srv;
+pub mod sshfp;
+pub mod svcb;
+pub mod tlsa;
+pub mod tsig;
 pub mod txt;
+pub mod uri;
+pub mod zonemd;
 
+pub use self::cdnskey::CDNSKEY;
+pub use self::cds::CDS;
+pub use self::csync::CSYNC;
 pub use self::dnskey::DNSKEY;
 pub use self::ds::DS;
+pub use self::hinfo::HINFO;
 pub use self::key::KEY;
 pub use self::mx::MX;
+pub use self::naptr::NAPTR;
 pub use self::nsec::NSEC;
 pub use self::nsec3::NSEC3;
 pub use self::nsec3param::NSEC3PARAM;
 pub use self::null::NULL;
+pub use self::openpgpkey::OPENPGPKEY;
 pub use self::opt::OPT;
 pub use self::sig::SIG;
 pub use self::srv::SRV;
 pub use self::soa::SOA;
+pub use self::sshfp::SSHFP;
+pub use self::svcb::SVCB;
+pub use self::tlsa::TLSA;
+pub use self::tsig::TSIG;
 pub use self::txt::TXT;
+pub use self::uri::URI;
+pub use self::zonemd::ZONEMD;
@@ -0,0 +1,337 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! naming authority pointer, the rewrite rules of the Dynamic Delegation Discovery System
+
+use serialize::binary::*;
+use error::*;
+use rr::domain::Name;
+
+/// [RFC 3403, DDDS Part Three: The DNS Database, October 2002](https://tools.ietf.org/html/rfc3403#section-4.1)
+///
+/// ```text
+/// 4.1 Packet Format
+///
+///   The packet format of the NAPTR RR is given below.  The DNS type code
+///   for NAPTR is 35.
+///
+///      The packet format for the NAPTR record is as follows
+///                                       1  1  1  1  1  1
+///         0  1  2  3  4  5  6  7  8  9  0  1  2  3  4  5
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       |                     ORDER                     |
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       |                   PREFERENCE                  |
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       /                     FLAGS                     /
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       /                   SERVICES                    /
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       /                    REGEXP                     /
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       /                  REPLACEMENT                  /
+///       /                                               /
+///       +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///
+///   <character-string> and <domain-name> as used here are defined in RFC
+///   1035 [7].
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct NAPTR {
+    order: u16,
+    preference: u16,
+    flags: String,
+    services: String,
+    regexp: String,
+    replacement: Name,
+}
+
+/// The substitution expression of the REGEXP field, [RFC 3402](https://tools.ietf.org/html/rfc3402#section-3.2)
+///
+/// ```text
+/// subst-expr   = delim-char  ere  delim-char  repl  delim-char  *flags
+/// delim-char   = "/" / "!" / <Any non-digit or non-flag character other
+///                than backslash '\'. All occurances of a delim_char
+///                in a subst_expr must be the same character.>
+/// ere          = <POSIX Extended Regular Expression>
+/// repl         = *(string / backref)
+/// string       = *(anychar / escapeddelim)
+/// backref      = "\" POS-DIGIT
+/// flags        = "i"
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Substitution {
+    ere: String,
+    repl: String,
+    case_insensitive: bool,
+}
+
+impl Substitution {
+    /// Parses the substitution expression, e.g. `!^.*$!sip:info@example.com!`
+    pub fn parse(subst_expr: &str) -> ProtoResult<Self> {
+        let invalid = || {
+            ProtoError::from(ProtoErrorKind::Msg(
+                format!("invalid substitution expression: {}", subst_expr),
+            ))
+        };
+
+        let mut chars = subst_expr.chars();
+        let delim = try!(chars.next().ok_or_else(&invalid));
+        if delim.is_digit(10) || delim == '\\' || delim == 'i' {
+            return Err(invalid());
+        }
+
+        // the escaped delimiters are unescaped, other escapes are kept for the regex and the
+        //  back references of the replacement
+        let mut parts = vec![String::new()];
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                let escaped = try!(chars.next().ok_or_else(&invalid));
+                let part = parts.last_mut().expect("there is always a part");
+                if escaped != delim {
+                    part.push('\\');
+                }
+                part.push(escaped);
+            } else if c == delim {
+                parts.push(String::new());
+            } else {
+                parts.last_mut().expect("there is always a part").push(c);
+            }
+        }
+
+        // the ere, the repl, and the flags after the last delimiter
+        if parts.len() != 3 {
+            return Err(invalid());
+        }
+        let flags = parts.pop().expect("three parts");
+        let repl = parts.pop().expect("three parts");
+        let ere = parts.pop().expect("three parts");
+
+        let case_insensitive = match flags.as_str() {
+            "" => false,
+            "i" => true,
+            _ => return Err(invalid()),
+        };
+
+        Ok(Substitution {
+            ere: ere,
+            repl: repl,
+            case_insensitive: case_insensitive,
+        })
+    }
+
+    /// The POSIX Extended Regular Expression which is matched against the input
+    pub fn ere(&self) -> &str {
+        &self.ere
+    }
+
+    /// The replacement, with back references `\1` to `\9` to the groups of the match
+    pub fn repl(&self) -> &str {
+        &self.repl
+    }
+
+    /// Returns true if the ere matches regardless of case, the `i` flag
+    pub fn is_case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    /// Expands the replacement with the groups of the match, `groups(n)` returns group `n`, the
+    ///  unmatched groups are empty
+    pub fn expand<'a, F>(&self, groups: F) -> String
+    where
+        F: Fn(usize) -> Option<&'a str>,
+    {
+        let mut expanded = String::with_capacity(self.repl.len());
+        let mut chars = self.repl.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                expanded.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some(d) if d.is_digit(10) && d != '0' => {
+                    let group = d.to_digit(10).expect("is digit") as usize;
+                    expanded.push_str(groups(group).unwrap_or(""));
+                }
+                Some(escaped) => expanded.push(escaped),
+                None => expanded.push('\\'),
+            }
+        }
+
+        expanded
+    }
+}
+
+impl NAPTR {
+    /// Constructs a new NAPTR RData
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - the order in which the records must be processed, lowest first
+    /// * `preference` - the order in which records of the same order should be processed
+    /// * `flags` - e.g. `U` for a terminal rule resulting in a URI, empty for a non-terminal rule
+    /// * `services` - the services and protocols of the rewrite, e.g. `E2U+sip` for ENUM
+    /// * `regexp` - the substitution expression applied to the input, see `Substitution`
+    /// * `replacement` - the next name to look up, `.` if the regexp is used instead
+    ///
+    /// # Returns
+    ///
+    /// A NAPTR RData for use in a Resource Record
+    pub fn new(
+        order: u16,
+        preference: u16,
+        flags: String,
+        services: String,
+        regexp: String,
+        replacement: Name,
+    ) -> NAPTR {
+        NAPTR {
+            order: order,
+            preference: preference,
+            flags: flags,
+            services: services,
+            regexp: regexp,
+            replacement: replacement,
+        }
+    }
+
+    /// The order in which the records must be processed, RFC 3403 section 4.1
+    pub fn order(&self) -> u16 {
+        self.order
+    }
+
+    /// The order in which records of the same order should be processed, RFC 3403 section 4.1
+    pub fn preference(&self) -> u16 {
+        self.preference
+    }
+
+    /// The flags, single characters of `A-Z` and `0-9`, RFC 3403 section 4.1
+    pub fn flags(&self) -> &str {
+        &self.flags
+    }
+
+    /// Returns true if the flag is set, regardless of case
+    pub fn has_flag(&self, flag: char) -> bool {
+        self.flags.chars().any(|f| f.eq_ignore_ascii_case(&flag))
+    }
+
+    /// Returns true if the rule is terminal, the `S`, `A` and `U` flags, RFC 3404 section 4.3;
+    ///  otherwise the rewritten name is looked up for further rules
+    pub fn is_terminal(&self) -> bool {
+        self.has_flag('S') || self.has_flag('A') || self.has_flag('U')
+    }
+
+    /// The services and protocols of the rewrite, RFC 3403 section 4.1
+    pub fn services(&self) -> &str {
+        &self.services
+    }
+
+    /// The substitution expression, empty if the replacement is used instead
+    pub fn regexp(&self) -> &str {
+        &self.regexp
+    }
+
+    /// Parses the substitution expression, None if there is none
+    pub fn substitution(&self) -> ProtoResult<Option<Substitution>> {
+        if self.regexp.is_empty() {
+            return Ok(None);
+        }
+
+        Substitution::parse(&self.regexp).map(Some)
+    }
+
+    /// The next name to look up, the root if the regexp is used instead
+    pub fn replacement(&self) -> &Name {
+        &self.replacement
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder) -> ProtoResult<NAPTR> {
+    Ok(NAPTR::new(
+        try!(decoder.read_u16()),
+        try!(decoder.read_u16()),
+        try!(decoder.read_character_data()),
+        try!(decoder.read_character_data()),
+        try!(decoder.read_character_data()),
+        try!(Name::read(decoder)),
+    ))
+}
+
+/// Write the RData from the given Decoder
+///
+/// The replacement is never compressed, RFC 3403 section 4.1, and is lowercased in the
+///  canonical form of RFC 4034 section 6.2.
+pub fn emit(encoder: &mut BinEncoder, naptr: &NAPTR) -> ProtoResult<()> {
+    try!(encoder.emit_u16(naptr.order()));
+    try!(encoder.emit_u16(naptr.preference()));
+    try!(encoder.emit_character_data(naptr.flags()));
+    try!(encoder.emit_character_data(naptr.services()));
+    try!(encoder.emit_character_data(naptr.regexp()));
+
+    if encoder.is_canonical_names() {
+        try!(naptr.replacement().to_lowercase().emit_as_canonical(encoder, true));
+    } else {
+        try!(naptr.replacement().emit_as_canonical(encoder, true));
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn test() {
+    let rdata = NAPTR::new(
+        100,
+        10,
+        "u".to_string(),
+        "E2U+sip".to_string(),
+        "!^.*$!sip:info@example.com!".to_string(),
+        Name::root(),
+    );
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    println!("bytes: {:?}", bytes);
+    assert_eq!(&bytes[..7], &[0, 100, 0, 10, 1, b'u', 7]);
+    assert_eq!(*bytes.last().unwrap(), 0);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder);
+    assert!(
+        read_rdata.is_ok(),
+        format!("error decoding: {:?}", read_rdata.unwrap_err())
+    );
+    let read_rdata = read_rdata.unwrap();
+    assert!(read_rdata.is_terminal());
+    assert_eq!(rdata, read_rdata);
+}
+
+#[test]
+pub fn test_substitution() {
+    let subst = Substitution::parse("!^.*$!sip:info@example.com!").unwrap();
+    assert_eq!(subst.ere(), "^.*$");
+    assert_eq!(subst.repl(), "sip:info@example.com");
+    assert!(!subst.is_case_insensitive());
+
+    // back references, and an escaped delimiter
+    let subst = Substitution::parse("/^\\+46(.*)$/ldap:\\/\\/ldap.example.se\\/cn=0\\1/i").unwrap();
+    assert_eq!(subst.ere(), "^\\+46(.*)$");
+    assert!(subst.is_case_insensitive());
+    assert_eq!(
+        subst.expand(|group| if group == 1 { Some("8123") } else { None }),
+        "ldap://ldap.example.se/cn=08123"
+    );
+
+    assert!(Substitution::parse("").is_err());
+    assert!(Substitution::parse("!^.*$!sip:info@example.com").is_err());
+    assert!(Substitution::parse("!^.*$!sip:info@example.com!x").is_err());
+    assert!(Substitution::parse("1^.*$1sip:info@example.com1").is_err());
+}
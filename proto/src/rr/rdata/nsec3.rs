@@ -16,7 +16,7 @@
 
 //! hashed negative cache proof for non-existence
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use serialize::binary::*;
 use error::*;
@@ -414,7 +414,7 @@ pub fn emit(encoder: &mut BinEncoder, rdata: &NSEC3) -> ProtoResult<()> {
 /// * `encoder` - the encoder to write to
 /// * `type_bit_maps` - types to encode into the bitmap
 pub fn encode_bit_maps(encoder: &mut BinEncoder, type_bit_maps: &[RecordType]) -> ProtoResult<()> {
-    let mut hash: HashMap<u8, Vec<u8>> = HashMap::new();
+    let mut hash: BTreeMap<u8, Vec<u8>> = BTreeMap::new();
 
     // collect the bitmaps
     for rr_type in type_bit_maps {
@@ -435,7 +435,7 @@ pub fn encode_bit_maps(encoder: &mut BinEncoder, type_bit_maps: &[RecordType]) -
         bit_map[index as usize] |= bit;
     }
 
-    // output bitmaps
+    // output bitmaps, the windows in increasing order, RFC 4034 section 4.1.2
     for (window, bitmap) in hash {
         try!(encoder.emit(window));
         // the hashset should never be larger that 255 based on above logic.
@@ -527,3 +527,21 @@ pub fn test_dups() {
     );
     assert_eq!(rdata_wo, read_rdata.unwrap());
 }
+
+#[test]
+pub fn test_bit_map_windows() {
+    // URI is in the second window
+    let type_bit_maps = vec![RecordType::A, RecordType::URI, RecordType::RRSIG];
+
+    for _ in 0..8 {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+            assert!(encode_bit_maps(&mut encoder, &type_bit_maps).is_ok());
+        }
+
+        // window 0 with A and RRSIG, then window 1 with URI
+        assert_eq!(bytes[0], 0);
+        assert_eq!(bytes[1 + 1 + bytes[1] as usize], 1);
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! OPENPGPKEY records for publishing the OpenPGP key of an email address
+
+use serialize::binary::*;
+use error::*;
+
+/// [RFC 7929, DNS-Based Authentication of Named Entities (DANE) Bindings for OpenPGP, August 2016](https://tools.ietf.org/html/rfc7929#section-2.1)
+///
+/// ```text
+/// 2.1.  The OPENPGPKEY RDATA Component
+///
+///    The RDATA portion of an OPENPGPKEY resource record contains a single
+///    value consisting of a Transferable Public Key formatted as specified
+///    in [RFC4880].
+/// ```
+///
+/// The owner name is the SHA2-256 hash of the local part of the email address, truncated to 28
+///  octets and hex encoded, under `_openpgpkey` of the domain, RFC 7929 section 3.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct OPENPGPKEY {
+    public_key: Vec<u8>,
+}
+
+impl OPENPGPKEY {
+    /// Constructs a new OPENPGPKEY RData
+    ///
+    /// # Arguments
+    ///
+    /// * `public_key` - the Transferable Public Key, in the binary format of OpenPGP, RFC 4880
+    ///
+    /// # Returns
+    ///
+    /// A OPENPGPKEY RData for use in a Resource Record
+    pub fn new(public_key: Vec<u8>) -> OPENPGPKEY {
+        OPENPGPKEY { public_key: public_key }
+    }
+
+    /// The Transferable Public Key, in the binary format of OpenPGP, RFC 4880 section 11.1
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> ProtoResult<OPENPGPKEY> {
+    let public_key = try!(decoder.read_vec(rdata_length as usize));
+
+    Ok(OPENPGPKEY::new(public_key))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, rdata: &OPENPGPKEY) -> ProtoResult<()> {
+    try!(encoder.emit_vec(rdata.public_key()));
+
+    Ok(())
+}
+
+#[test]
+pub fn test() {
+    let rdata = OPENPGPKEY::new(vec![0x99, 0x01, 0x0d, 0x04]);
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    println!("bytes: {:?}", bytes);
+    assert_eq!(bytes, &[0x99, 0x01, 0x0d, 0x04]);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder, bytes.len() as u16);
+    assert!(
+        read_rdata.is_ok(),
+        format!("error decoding: {:?}", read_rdata.unwrap_err())
+    );
+    assert_eq!(rdata, read_rdata.unwrap());
+}
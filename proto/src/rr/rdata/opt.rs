@@ -16,7 +16,10 @@
 
 //! option record for passing protocol options between the client and server
 
+use std::cmp;
 use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use serialize::binary::*;
 use error::*;
@@ -303,12 +306,15 @@ pub enum EdnsCode {
     /// [draft-ietf-dnsop-edns-tcp-keepalive, Optional](https://tools.ietf.org/html/draft-ietf-dnsop-edns-tcp-keepalive-04)
     Keepalive,
 
-    /// [draft-mayrhofer-edns0-padding, Optional](https://tools.ietf.org/html/draft-mayrhofer-edns0-padding-01)
+    /// [RFC 7830, The EDNS(0) Padding Option](https://tools.ietf.org/html/rfc7830)
     Padding,
 
     /// [draft-ietf-dnsop-edns-chain-query](https://tools.ietf.org/html/draft-ietf-dnsop-edns-chain-query-07)
     Chain,
 
+    /// [RFC 8914, Extended DNS Errors](https://tools.ietf.org/html/rfc8914)
+    ExtendedError,
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16),
 }
@@ -331,6 +337,7 @@ impl From<u16> for EdnsCode {
             11 => EdnsCode::Keepalive,
             12 => EdnsCode::Padding,
             13 => EdnsCode::Chain,
+            15 => EdnsCode::ExtendedError,
             _ => EdnsCode::Unknown(value),
         }
     }
@@ -353,6 +360,7 @@ impl From<EdnsCode> for u16 {
             EdnsCode::Keepalive => 11,
             EdnsCode::Padding => 12,
             EdnsCode::Chain => 13,
+            EdnsCode::ExtendedError => 15,
             EdnsCode::Unknown(value) => value,
         }
     }
@@ -374,29 +382,80 @@ pub enum EdnsOption {
     /// [RFC 6975, NSEC3 Hash Understood](https://tools.ietf.org/html/rfc6975)
     N3U(SupportedAlgorithms),
 
+    /// [RFC 7871, Client Subnet](https://tools.ietf.org/html/rfc7871)
+    Subnet(ClientSubnet),
+
+    /// [RFC 7873, DNS Cookies](https://tools.ietf.org/html/rfc7873)
+    Cookie(Cookie),
+
+    /// [RFC 8914, Extended DNS Errors](https://tools.ietf.org/html/rfc8914)
+    ExtendedError(ExtendedError),
+
+    /// [RFC 7830, The EDNS(0) Padding Option](https://tools.ietf.org/html/rfc7830), the number of
+    ///  padding bytes, these are written as zeros and their contents are ignored when read
+    Padding(u16),
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16, Vec<u8>),
 }
 
 impl EdnsOption {
+    /// Returns the code of the EdnsOption
+    pub fn code(&self) -> EdnsCode {
+        self.into()
+    }
+
     /// Returns the length in bytes of the EdnsOption
     pub fn len(&self) -> u16 {
         match *self {
             EdnsOption::DAU(ref algorithms) |
             EdnsOption::DHU(ref algorithms) |
             EdnsOption::N3U(ref algorithms) => algorithms.len(),
+            EdnsOption::Subnet(ref subnet) => subnet.len(),
+            EdnsOption::Cookie(ref cookie) => cookie.len(),
+            EdnsOption::ExtendedError(ref error) => error.len(),
+            EdnsOption::Padding(len) => len,
             EdnsOption::Unknown(_, ref data) => data.len() as u16, // TODO: should we verify?
         }
     }
 }
 
-/// only the supported extensions are listed right now.
+/// only the supported extensions are listed right now, the others and those which are malformed
+///  are kept as `Unknown`.
 impl<'a> From<(EdnsCode, &'a [u8])> for EdnsOption {
     fn from(value: (EdnsCode, &'a [u8])) -> EdnsOption {
         match value.0 {
             EdnsCode::DAU => EdnsOption::DAU(value.1.into()),
             EdnsCode::DHU => EdnsOption::DHU(value.1.into()),
             EdnsCode::N3U => EdnsOption::N3U(value.1.into()),
+            EdnsCode::Subnet => {
+                match ClientSubnet::read(value.1) {
+                    Ok(subnet) => EdnsOption::Subnet(subnet),
+                    Err(e) => {
+                        warn!("malformed EDNS client subnet: {}", e);
+                        EdnsOption::Unknown(value.0.into(), value.1.to_vec())
+                    }
+                }
+            }
+            EdnsCode::Cookie => {
+                match Cookie::read(value.1) {
+                    Ok(cookie) => EdnsOption::Cookie(cookie),
+                    Err(e) => {
+                        warn!("malformed EDNS cookie: {}", e);
+                        EdnsOption::Unknown(value.0.into(), value.1.to_vec())
+                    }
+                }
+            }
+            EdnsCode::ExtendedError => {
+                match ExtendedError::read(value.1) {
+                    Ok(error) => EdnsOption::ExtendedError(error),
+                    Err(e) => {
+                        warn!("malformed EDNS extended error: {}", e);
+                        EdnsOption::Unknown(value.0.into(), value.1.to_vec())
+                    }
+                }
+            }
+            EdnsCode::Padding => EdnsOption::Padding(value.1.len() as u16),
             _ => EdnsOption::Unknown(value.0.into(), value.1.to_vec()),
         }
     }
@@ -408,6 +467,10 @@ impl<'a> From<&'a EdnsOption> for Vec<u8> {
             EdnsOption::DAU(ref algorithms) |
             EdnsOption::DHU(ref algorithms) |
             EdnsOption::N3U(ref algorithms) => algorithms.into(),
+            EdnsOption::Subnet(ref subnet) => subnet.into(),
+            EdnsOption::Cookie(ref cookie) => cookie.into(),
+            EdnsOption::ExtendedError(ref error) => error.into(),
+            EdnsOption::Padding(len) => vec![0; len as usize],
             EdnsOption::Unknown(_, ref data) => data.clone(), // gah, clone needed or make a crazy api.
         }
     }
@@ -419,11 +482,574 @@ impl<'a> From<&'a EdnsOption> for EdnsCode {
             EdnsOption::DAU(..) => EdnsCode::DAU,
             EdnsOption::DHU(..) => EdnsCode::DHU,
             EdnsOption::N3U(..) => EdnsCode::N3U,
-            EdnsOption::Unknown(code, _) => EdnsCode::Unknown(code),
+            EdnsOption::Subnet(..) => EdnsCode::Subnet,
+            EdnsOption::Cookie(..) => EdnsCode::Cookie,
+            EdnsOption::ExtendedError(..) => EdnsCode::ExtendedError,
+            EdnsOption::Padding(..) => EdnsCode::Padding,
+            EdnsOption::Unknown(code, _) => code.into(),
+        }
+    }
+}
+
+/// The block size to which queries are padded, RFC 8467 section 4.1, see `Message::pad`
+pub const QUERY_PADDING_BLOCK_SIZE: u16 = 128;
+
+/// The block size to which responses are padded, RFC 8467 section 4.1, see `Message::pad`
+pub const RESPONSE_PADDING_BLOCK_SIZE: u16 = 468;
+
+/// [RFC 7871, Client Subnet in DNS Queries, May 2016](https://tools.ietf.org/html/rfc7871#section-6)
+///
+/// ```text
+/// 6.  Option Format
+///
+///                 +0 (MSB)                            +1 (LSB)
+///      +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///   0: |                          OPTION-CODE                          |
+///      +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///   2: |                         OPTION-LENGTH                         |
+///      +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///   4: |                            FAMILY                             |
+///      +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///   6: |     SOURCE PREFIX-LENGTH      |     SCOPE PREFIX-LENGTH       |
+///      +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///   8: |                           ADDRESS...                          /
+///      +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///
+///    o  SOURCE PREFIX-LENGTH, an unsigned octet representing the leftmost
+///       number of significant bits of ADDRESS to be used for the lookup.
+///       In responses, it mirrors the same value as in the queries.
+///
+///    o  SCOPE PREFIX-LENGTH, an unsigned octet representing the leftmost
+///       number of significant bits of ADDRESS that the response covers.
+///       In queries, it MUST be set to 0.
+///
+///    o  ADDRESS, variable number of octets, containing either an IPv4 or
+///       IPv6 address, depending on FAMILY, which MUST be truncated to the
+///       number of bits indicated by the SOURCE PREFIX-LENGTH field,
+///       padding with 0 bits to pad to the end of the last octet needed.
+/// ```
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ClientSubnet {
+    address: IpAddr,
+    source_prefix: u8,
+    scope_prefix: u8,
+}
+
+impl ClientSubnet {
+    /// Creates a new client subnet option, the bits of the address past the source prefix are
+    ///  cleared
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - an address of the clients which the query is made for
+    /// * `source_prefix` - the number of leading bits of the address which are significant, at
+    ///                     most the length of the address
+    /// * `scope_prefix` - the number of leading bits of the address which the answer covers, 0 in
+    ///                    queries
+    pub fn new(address: IpAddr, source_prefix: u8, scope_prefix: u8) -> Self {
+        let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+        let source_prefix = cmp::min(source_prefix, max_prefix);
+
+        let address = match address {
+            IpAddr::V4(address) => {
+                let mut octets = address.octets();
+                mask(&mut octets, source_prefix);
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            IpAddr::V6(address) => {
+                let mut octets = address.octets();
+                mask(&mut octets, source_prefix);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+        };
+
+        ClientSubnet {
+            address: address,
+            source_prefix: source_prefix,
+            scope_prefix: cmp::min(scope_prefix, max_prefix),
+        }
+    }
+
+    /// Reads the option from its data
+    pub fn read(data: &[u8]) -> ProtoResult<Self> {
+        if data.len() < 4 {
+            return Err(
+                ProtoErrorKind::Msg(format!("client subnet too short: {}", data.len())).into(),
+            );
+        }
+
+        let family = (data[0] as u16) << 8 | data[1] as u16;
+        let (source_prefix, scope_prefix) = (data[2], data[3]);
+        let address = &data[4..];
+
+        let max_prefix = match family {
+            1 => 32,
+            2 => 128,
+            _ => {
+                return Err(
+                    ProtoErrorKind::Msg(format!("unsupported client subnet family: {}", family))
+                        .into(),
+                )
+            }
+        };
+
+        // the address is truncated to the source prefix, RFC 7871 section 6
+        if source_prefix > max_prefix || scope_prefix > max_prefix ||
+            address.len() != address_len(source_prefix)
+        {
+            return Err(
+                ProtoErrorKind::Msg(format!(
+                    "bad client subnet prefix: {}/{} with {} octets",
+                    source_prefix,
+                    scope_prefix,
+                    address.len()
+                )).into(),
+            );
+        }
+
+        let mut octets = [0u8; 16];
+        octets[..address.len()].copy_from_slice(address);
+        let address = if family == 1 {
+            IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+        } else {
+            IpAddr::V6(Ipv6Addr::from(octets))
+        };
+
+        Ok(Self::new(address, source_prefix, scope_prefix))
+    }
+
+    /// The address of the clients, with the bits past the source prefix cleared
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// The number of leading bits of the address which are significant
+    pub fn source_prefix(&self) -> u8 {
+        self.source_prefix
+    }
+
+    /// The number of leading bits of the address which the answer covers, in responses, 0 if it
+    ///  covers all clients
+    pub fn scope_prefix(&self) -> u8 {
+        self.scope_prefix
+    }
+
+    /// Sets the number of leading bits of the address which the answer covers
+    pub fn set_scope_prefix(&mut self, scope_prefix: u8) {
+        let max_prefix = if self.address.is_ipv4() { 32 } else { 128 };
+        self.scope_prefix = cmp::min(scope_prefix, max_prefix);
+    }
+
+    /// Returns the length in bytes of the option data
+    pub fn len(&self) -> u16 {
+        4 + address_len(self.source_prefix) as u16
+    }
+}
+
+impl<'a> From<&'a ClientSubnet> for Vec<u8> {
+    fn from(value: &'a ClientSubnet) -> Vec<u8> {
+        let (family, octets): (u16, Vec<u8>) = match value.address {
+            IpAddr::V4(address) => (1, address.octets().to_vec()),
+            IpAddr::V6(address) => (2, address.octets().to_vec()),
+        };
+
+        let mut data = Vec::with_capacity(value.len() as usize);
+        data.push((family >> 8) as u8);
+        data.push(family as u8);
+        data.push(value.source_prefix);
+        data.push(value.scope_prefix);
+        data.extend_from_slice(&octets[..address_len(value.source_prefix)]);
+        data
+    }
+}
+
+/// [RFC 7873, Domain Name System (DNS) Cookies, May 2016](https://tools.ietf.org/html/rfc7873#section-4)
+///
+/// ```text
+/// 4.  The COOKIE Option
+///
+///    The COOKIE option is an EDNS option used to carry a Client Cookie or
+///    a Client Cookie and a Server Cookie.
+///
+///       1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |        OPTION-CODE = 10      | OPTION-LENGTH >= 16, <= 40    |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                                                               |
+/// +-+-    Client Cookie (fixed size, 8 bytes)              -+-+-+-+
+/// |                                                               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                                                               |
+/// /       Server Cookie  (variable size, 8 to 32 bytes)           /
+/// /                                                               /
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+///
+/// A query may carry only the Client Cookie, when the client does not yet know a Server Cookie
+///  of the server.
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Hash)]
+pub struct Cookie {
+    client: [u8; 8],
+    server: Option<Vec<u8>>,
+}
+
+/// The length of the Client Cookie
+const CLIENT_COOKIE_LEN: usize = 8;
+/// The shortest Server Cookie
+const MIN_SERVER_COOKIE_LEN: usize = 8;
+/// The longest Server Cookie
+const MAX_SERVER_COOKIE_LEN: usize = 32;
+
+impl Cookie {
+    /// Creates a new cookie option
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - the Client Cookie, a pseudorandom value of the client for the server
+    /// * `server` - the Server Cookie last returned by the server, if any, between 8 and 32 bytes
+    pub fn new(client: [u8; 8], server: Option<Vec<u8>>) -> Self {
+        Cookie {
+            client: client,
+            server: server,
+        }
+    }
+
+    /// Reads the option from its data
+    pub fn read(data: &[u8]) -> ProtoResult<Self> {
+        let server_len = data.len().saturating_sub(CLIENT_COOKIE_LEN);
+        if data.len() < CLIENT_COOKIE_LEN ||
+            (server_len != 0 &&
+                 (server_len < MIN_SERVER_COOKIE_LEN || server_len > MAX_SERVER_COOKIE_LEN))
+        {
+            return Err(
+                ProtoErrorKind::Msg(format!("bad cookie length: {}", data.len())).into(),
+            );
+        }
+
+        let mut client = [0u8; 8];
+        client.copy_from_slice(&data[..CLIENT_COOKIE_LEN]);
+
+        let server = if server_len > 0 {
+            Some(data[CLIENT_COOKIE_LEN..].to_vec())
+        } else {
+            None
+        };
+
+        Ok(Self::new(client, server))
+    }
+
+    /// The Client Cookie
+    pub fn client(&self) -> &[u8; 8] {
+        &self.client
+    }
+
+    /// The Server Cookie, None if the client did not know one
+    pub fn server(&self) -> Option<&[u8]> {
+        self.server.as_ref().map(|server| server.as_slice())
+    }
+
+    /// Returns the length in bytes of the option data
+    pub fn len(&self) -> u16 {
+        (CLIENT_COOKIE_LEN + self.server.as_ref().map_or(0, |server| server.len())) as u16
+    }
+}
+
+impl<'a> From<&'a Cookie> for Vec<u8> {
+    fn from(value: &'a Cookie) -> Vec<u8> {
+        let mut data = Vec::with_capacity(value.len() as usize);
+        data.extend_from_slice(&value.client);
+        if let Some(ref server) = value.server {
+            data.extend_from_slice(server);
+        }
+        data
+    }
+}
+
+/// The INFO-CODE of an Extended DNS Error, [RFC 8914](https://tools.ietf.org/html/rfc8914#section-4)
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ExtendedErrorCode {
+    /// 0 Other, the error does not match any of the others, see the extra text
+    Other,
+    /// 1 Unsupported DNSKEY Algorithm, a DNSKEY RRset of the chain only has unknown algorithms
+    UnsupportedDnskeyAlgorithm,
+    /// 2 Unsupported DS Digest Type, a DS RRset of the chain only has unknown digest types
+    UnsupportedDsDigestType,
+    /// 3 Stale Answer, the answer is stale, served from the cache after its TTL expired, RFC 8767
+    StaleAnswer,
+    /// 4 Forged Answer, the answer was forged by a policy, e.g. a redirect to a block page
+    ForgedAnswer,
+    /// 5 DNSSEC Indeterminate, DNSSEC validation ended in the Indeterminate state, RFC 4035
+    DnssecIndeterminate,
+    /// 6 DNSSEC Bogus, DNSSEC validation ended in the Bogus state, RFC 4035
+    DnssecBogus,
+    /// 7 Signature Expired, the RRSIGs of the answer have all expired
+    SignatureExpired,
+    /// 8 Signature Not Yet Valid, the RRSIGs of the answer are all not yet valid
+    SignatureNotYetValid,
+    /// 9 DNSKEY Missing, a DS record matched no DNSKEY of the child zone
+    DnskeyMissing,
+    /// 10 RRSIGs Missing, the zone is signed, but no RRSIGs were found for the answer
+    RrsigsMissing,
+    /// 11 No Zone Key Bit Set, no DNSKEY of the zone has the Zone Key bit set
+    NoZoneKeyBitSet,
+    /// 12 NSEC Missing, the NSEC or NSEC3 records of a denial of existence were missing
+    NsecMissing,
+    /// 13 Cached Error, the error of a previous attempt, e.g. a SERVFAIL, from the cache
+    CachedError,
+    /// 14 Not Ready, the server was not ready to answer, e.g. the zone has not been loaded
+    NotReady,
+    /// 15 Blocked, the domain is on a blocklist of the operator of the server
+    Blocked,
+    /// 16 Censored, the domain is on a blocklist the operator of the server is required to apply
+    Censored,
+    /// 17 Filtered, the domain is on a blocklist the client asked for
+    Filtered,
+    /// 18 Prohibited, the client is not permitted to query the server
+    Prohibited,
+    /// 19 Stale NXDOMAIN Answer, an NXDOMAIN served from the cache after its TTL expired
+    StaleNxdomainAnswer,
+    /// 20 Not Authoritative, the server is not authoritative for the name, and does not recurse
+    NotAuthoritative,
+    /// 21 Not Supported, the query, or operation, is not supported, e.g. a refused ANY query
+    NotSupported,
+    /// 22 No Reachable Authority, no authoritative server of the name could be reached
+    NoReachableAuthority,
+    /// 23 Network Error, an unrecoverable network error while talking to an authoritative server
+    NetworkError,
+    /// 24 Invalid Data, the authoritative servers returned invalid data
+    InvalidData,
+    /// A code which is not known
+    Unknown(u16),
+}
+
+impl ExtendedErrorCode {
+    /// The purpose of the code, as named in RFC 8914
+    pub fn to_str(&self) -> &'static str {
+        match *self {
+            ExtendedErrorCode::Other => "Other",
+            ExtendedErrorCode::UnsupportedDnskeyAlgorithm => "Unsupported DNSKEY Algorithm",
+            ExtendedErrorCode::UnsupportedDsDigestType => "Unsupported DS Digest Type",
+            ExtendedErrorCode::StaleAnswer => "Stale Answer",
+            ExtendedErrorCode::ForgedAnswer => "Forged Answer",
+            ExtendedErrorCode::DnssecIndeterminate => "DNSSEC Indeterminate",
+            ExtendedErrorCode::DnssecBogus => "DNSSEC Bogus",
+            ExtendedErrorCode::SignatureExpired => "Signature Expired",
+            ExtendedErrorCode::SignatureNotYetValid => "Signature Not Yet Valid",
+            ExtendedErrorCode::DnskeyMissing => "DNSKEY Missing",
+            ExtendedErrorCode::RrsigsMissing => "RRSIGs Missing",
+            ExtendedErrorCode::NoZoneKeyBitSet => "No Zone Key Bit Set",
+            ExtendedErrorCode::NsecMissing => "NSEC Missing",
+            ExtendedErrorCode::CachedError => "Cached Error",
+            ExtendedErrorCode::NotReady => "Not Ready",
+            ExtendedErrorCode::Blocked => "Blocked",
+            ExtendedErrorCode::Censored => "Censored",
+            ExtendedErrorCode::Filtered => "Filtered",
+            ExtendedErrorCode::Prohibited => "Prohibited",
+            ExtendedErrorCode::StaleNxdomainAnswer => "Stale NXDOMAIN Answer",
+            ExtendedErrorCode::NotAuthoritative => "Not Authoritative",
+            ExtendedErrorCode::NotSupported => "Not Supported",
+            ExtendedErrorCode::NoReachableAuthority => "No Reachable Authority",
+            ExtendedErrorCode::NetworkError => "Network Error",
+            ExtendedErrorCode::InvalidData => "Invalid Data",
+            ExtendedErrorCode::Unknown(..) => "Unknown",
+        }
+    }
+}
+
+impl fmt::Display for ExtendedErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExtendedErrorCode::Unknown(code) => write!(f, "Unknown ({})", code),
+            _ => f.write_str(self.to_str()),
+        }
+    }
+}
+
+impl From<u16> for ExtendedErrorCode {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => ExtendedErrorCode::Other,
+            1 => ExtendedErrorCode::UnsupportedDnskeyAlgorithm,
+            2 => ExtendedErrorCode::UnsupportedDsDigestType,
+            3 => ExtendedErrorCode::StaleAnswer,
+            4 => ExtendedErrorCode::ForgedAnswer,
+            5 => ExtendedErrorCode::DnssecIndeterminate,
+            6 => ExtendedErrorCode::DnssecBogus,
+            7 => ExtendedErrorCode::SignatureExpired,
+            8 => ExtendedErrorCode::SignatureNotYetValid,
+            9 => ExtendedErrorCode::DnskeyMissing,
+            10 => ExtendedErrorCode::RrsigsMissing,
+            11 => ExtendedErrorCode::NoZoneKeyBitSet,
+            12 => ExtendedErrorCode::NsecMissing,
+            13 => ExtendedErrorCode::CachedError,
+            14 => ExtendedErrorCode::NotReady,
+            15 => ExtendedErrorCode::Blocked,
+            16 => ExtendedErrorCode::Censored,
+            17 => ExtendedErrorCode::Filtered,
+            18 => ExtendedErrorCode::Prohibited,
+            19 => ExtendedErrorCode::StaleNxdomainAnswer,
+            20 => ExtendedErrorCode::NotAuthoritative,
+            21 => ExtendedErrorCode::NotSupported,
+            22 => ExtendedErrorCode::NoReachableAuthority,
+            23 => ExtendedErrorCode::NetworkError,
+            24 => ExtendedErrorCode::InvalidData,
+            _ => ExtendedErrorCode::Unknown(value),
+        }
+    }
+}
+
+impl From<ExtendedErrorCode> for u16 {
+    fn from(value: ExtendedErrorCode) -> Self {
+        match value {
+            ExtendedErrorCode::Other => 0,
+            ExtendedErrorCode::UnsupportedDnskeyAlgorithm => 1,
+            ExtendedErrorCode::UnsupportedDsDigestType => 2,
+            ExtendedErrorCode::StaleAnswer => 3,
+            ExtendedErrorCode::ForgedAnswer => 4,
+            ExtendedErrorCode::DnssecIndeterminate => 5,
+            ExtendedErrorCode::DnssecBogus => 6,
+            ExtendedErrorCode::SignatureExpired => 7,
+            ExtendedErrorCode::SignatureNotYetValid => 8,
+            ExtendedErrorCode::DnskeyMissing => 9,
+            ExtendedErrorCode::RrsigsMissing => 10,
+            ExtendedErrorCode::NoZoneKeyBitSet => 11,
+            ExtendedErrorCode::NsecMissing => 12,
+            ExtendedErrorCode::CachedError => 13,
+            ExtendedErrorCode::NotReady => 14,
+            ExtendedErrorCode::Blocked => 15,
+            ExtendedErrorCode::Censored => 16,
+            ExtendedErrorCode::Filtered => 17,
+            ExtendedErrorCode::Prohibited => 18,
+            ExtendedErrorCode::StaleNxdomainAnswer => 19,
+            ExtendedErrorCode::NotAuthoritative => 20,
+            ExtendedErrorCode::NotSupported => 21,
+            ExtendedErrorCode::NoReachableAuthority => 22,
+            ExtendedErrorCode::NetworkError => 23,
+            ExtendedErrorCode::InvalidData => 24,
+            ExtendedErrorCode::Unknown(value) => value,
+        }
+    }
+}
+
+/// [RFC 8914, Extended DNS Errors, October 2020](https://tools.ietf.org/html/rfc8914#section-2)
+///
+/// ```text
+/// 2.  Extended DNS Error EDNS0 Option Format
+///
+///                                              1   1   1   1   1   1
+///      0   1   2   3   4   5   6   7   8   9   0   1   2   3   4   5
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// 0: |                            OPTION-CODE                        |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// 2: |                           OPTION-LENGTH                       |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// 4: | INFO-CODE                                                     |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// 6: / EXTRA-TEXT ...                                                /
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///
+///    EXTRA-TEXT: a variable-length, UTF-8-encoded [RFC5198] text field
+///       that may hold additional textual information.  This information is
+///       intended for human consumption (not automated parsing).
+/// ```
+///
+/// A response may carry several, only the last of them is kept in the `OPT`.
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Hash)]
+pub struct ExtendedError {
+    info_code: ExtendedErrorCode,
+    extra_text: String,
+}
+
+impl ExtendedError {
+    /// Creates a new extended error option
+    ///
+    /// # Arguments
+    ///
+    /// * `info_code` - the reason of the error
+    /// * `extra_text` - more about the error, for people rather than programs, may be empty
+    pub fn new(info_code: ExtendedErrorCode, extra_text: String) -> Self {
+        ExtendedError {
+            info_code: info_code,
+            extra_text: extra_text,
+        }
+    }
+
+    /// Reads the option from its data
+    pub fn read(data: &[u8]) -> ProtoResult<Self> {
+        if data.len() < 2 {
+            return Err(
+                ProtoErrorKind::Msg(format!("extended error too short: {}", data.len())).into(),
+            );
+        }
+
+        let info_code = (data[0] as u16) << 8 | data[1] as u16;
+
+        // some implementations terminate the text with a NUL, RFC 8914 section 2
+        let mut text = &data[2..];
+        if text.last() == Some(&0) {
+            text = &text[..text.len() - 1];
+        }
+        let extra_text = try!(String::from_utf8(text.to_vec()).map_err(|e| {
+            ProtoError::from(ProtoErrorKind::Msg(
+                format!("extended error text is not UTF-8: {}", e),
+            ))
+        }));
+
+        Ok(Self::new(info_code.into(), extra_text))
+    }
+
+    /// The reason of the error
+    pub fn info_code(&self) -> ExtendedErrorCode {
+        self.info_code
+    }
+
+    /// More about the error, for people rather than programs, empty if there is none
+    pub fn extra_text(&self) -> &str {
+        &self.extra_text
+    }
+
+    /// Returns the length in bytes of the option data
+    pub fn len(&self) -> u16 {
+        2 + self.extra_text.len() as u16
+    }
+}
+
+impl fmt::Display for ExtendedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.extra_text.is_empty() {
+            write!(f, "{}", self.info_code)
+        } else {
+            write!(f, "{}: {}", self.info_code, self.extra_text)
         }
     }
 }
 
+impl<'a> From<&'a ExtendedError> for Vec<u8> {
+    fn from(value: &'a ExtendedError) -> Vec<u8> {
+        let info_code = u16::from(value.info_code);
+
+        let mut data = Vec::with_capacity(value.len() as usize);
+        data.push((info_code >> 8) as u8);
+        data.push(info_code as u8);
+        data.extend_from_slice(value.extra_text.as_bytes());
+        data
+    }
+}
+
+/// The number of octets of an address with the prefix
+fn address_len(prefix: u8) -> usize {
+    (prefix as usize + 7) / 8
+}
+
+/// Clears the bits of the octets past the prefix
+fn mask(octets: &mut [u8], prefix: u8) {
+    for (i, octet) in octets.iter_mut().enumerate() {
+        let bits = cmp::min(8, (prefix as usize).saturating_sub(i * 8));
+        *octet &= !(0xFFu16 >> bits) as u8;
+    }
+}
+
 #[test]
 pub fn test() {
     let mut rdata = OPT::default();
@@ -444,3 +1070,103 @@ pub fn test() {
     );
     assert_eq!(rdata, read_rdata.unwrap());
 }
+
+#[test]
+pub fn test_client_subnet() {
+    let subnet = ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 77)), 24, 0);
+    assert_eq!(subnet.address(), IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)));
+
+    let mut rdata = OPT::default();
+    rdata.insert(EdnsOption::Subnet(subnet));
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+    assert_eq!(bytes, &[0, 8, 0, 7, 0, 1, 24, 0, 192, 0, 2]);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    assert_eq!(read(&mut decoder, bytes.len() as u16).unwrap(), rdata);
+
+    // a /0 has no address
+    let any = ClientSubnet::new("2001:db8::1".parse().unwrap(), 0, 0);
+    assert_eq!(Vec::<u8>::from(&any), vec![0, 2, 0, 0]);
+    assert_eq!(ClientSubnet::read(&[0, 2, 0, 0]).unwrap(), any);
+
+    // the address must be truncated to the source prefix
+    assert!(ClientSubnet::read(&[0, 1, 24, 0, 192, 0, 2, 0]).is_err());
+    assert!(ClientSubnet::read(&[0, 1, 33, 0, 192, 0, 2, 0, 0]).is_err());
+    assert!(ClientSubnet::read(&[0, 3, 0, 0]).is_err());
+    assert_eq!(
+        EdnsOption::from((EdnsCode::Subnet, &[0, 3, 0, 0][..])),
+        EdnsOption::Unknown(8, vec![0, 3, 0, 0])
+    );
+}
+
+#[test]
+pub fn test_cookie() {
+    let cookie = Cookie::new([1, 2, 3, 4, 5, 6, 7, 8], None);
+    assert_eq!(cookie.len(), 8);
+
+    let mut rdata = OPT::default();
+    rdata.insert(EdnsOption::Cookie(cookie.clone()));
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+    assert_eq!(bytes, &[0, 10, 0, 8, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    assert_eq!(read(&mut decoder, bytes.len() as u16).unwrap(), rdata);
+
+    let server = Cookie::new(*cookie.client(), Some(vec![9; 16]));
+    let data = Vec::<u8>::from(&server);
+    assert_eq!(data.len(), 24);
+    assert_eq!(Cookie::read(&data).unwrap(), server);
+    assert_eq!(server.server(), Some(&[9; 16][..]));
+
+    // the Server Cookie is between 8 and 32 bytes
+    assert!(Cookie::read(&[1, 2, 3, 4]).is_err());
+    assert!(Cookie::read(&[0; 12]).is_err());
+    assert!(Cookie::read(&[0; 41]).is_err());
+    assert!(Cookie::read(&[0; 40]).is_ok());
+    assert_eq!(
+        EdnsOption::from((EdnsCode::Cookie, &[0; 12][..])),
+        EdnsOption::Unknown(10, vec![0; 12])
+    );
+}
+
+#[test]
+pub fn test_extended_error() {
+    let error = ExtendedError::new(ExtendedErrorCode::Blocked, "ads".to_string());
+    assert_eq!(error.to_string(), "Blocked: ads");
+
+    let mut rdata = OPT::default();
+    rdata.insert(EdnsOption::ExtendedError(error.clone()));
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+    assert_eq!(bytes, &[0, 15, 0, 5, 0, 15, b'a', b'd', b's']);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    assert_eq!(read(&mut decoder, bytes.len() as u16).unwrap(), rdata);
+
+    // a trailing NUL is dropped, unknown codes are kept
+    assert_eq!(
+        ExtendedError::read(&[0, 6, b'x', 0]).unwrap(),
+        ExtendedError::new(ExtendedErrorCode::DnssecBogus, "x".to_string())
+    );
+    let unknown = ExtendedError::read(&[2, 0]).unwrap();
+    assert_eq!(unknown.info_code(), ExtendedErrorCode::Unknown(512));
+    assert_eq!(unknown.to_string(), "Unknown (512)");
+
+    assert!(ExtendedError::read(&[0]).is_err());
+    assert!(ExtendedError::read(&[0, 0, 0xFF]).is_err());
+    assert_eq!(
+        EdnsOption::from((EdnsCode::ExtendedError, &[0][..])),
+        EdnsOption::Unknown(15, vec![0])
+    );
+}
@@ -0,0 +1,273 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! SSHFP records for the fingerprints of the SSH host keys of a host
+
+use serialize::binary::*;
+use error::*;
+use rr::dnssec::DigestType;
+
+/// [RFC 4255, Using DNS to Securely Publish SSH Key Fingerprints, January 2006](https://tools.ietf.org/html/rfc4255#section-3.1)
+///
+/// ```text
+/// 3.1.  The SSHFP RDATA Format
+///
+///    The RDATA for a SSHFP RR consists of an algorithm number, fingerprint
+///    type and the fingerprint of the public host key.
+///
+///        1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///        0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///        +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///        |   algorithm   |    fp type    |                               /
+///        +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               /
+///        /                                                               /
+///        /                          fingerprint                          /
+///        /                                                               /
+///        +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct SSHFP {
+    algorithm: Algorithm,
+    fingerprint_type: FingerprintType,
+    fingerprint: Vec<u8>,
+}
+
+/// The algorithm of the SSH host key
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Algorithm {
+    /// 0, reserved
+    Reserved,
+    /// 1, RSA, RFC 4255
+    RSA,
+    /// 2, DSA, RFC 4255
+    DSA,
+    /// 3, ECDSA, RFC 6594
+    ECDSA,
+    /// 4, Ed25519, RFC 7479
+    Ed25519,
+    /// 6, Ed448, RFC 8709
+    Ed448,
+    /// Unassigned algorithms
+    Unassigned(u8),
+}
+
+impl From<u8> for Algorithm {
+    fn from(algorithm: u8) -> Self {
+        match algorithm {
+            0 => Algorithm::Reserved,
+            1 => Algorithm::RSA,
+            2 => Algorithm::DSA,
+            3 => Algorithm::ECDSA,
+            4 => Algorithm::Ed25519,
+            6 => Algorithm::Ed448,
+            _ => Algorithm::Unassigned(algorithm),
+        }
+    }
+}
+
+impl From<Algorithm> for u8 {
+    fn from(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Reserved => 0,
+            Algorithm::RSA => 1,
+            Algorithm::DSA => 2,
+            Algorithm::ECDSA => 3,
+            Algorithm::Ed25519 => 4,
+            Algorithm::Ed448 => 6,
+            Algorithm::Unassigned(algorithm) => algorithm,
+        }
+    }
+}
+
+/// The hash with which the fingerprint of the host key was made
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum FingerprintType {
+    /// 0, reserved
+    Reserved,
+    /// 1, SHA-1, RFC 4255
+    SHA1,
+    /// 2, SHA-256, RFC 6594
+    SHA256,
+    /// Unassigned fingerprint types
+    Unassigned(u8),
+}
+
+impl FingerprintType {
+    /// The digest of the fingerprint type, None if it is not assigned
+    pub fn digest_type(&self) -> Option<DigestType> {
+        match *self {
+            FingerprintType::SHA1 => Some(DigestType::SHA1),
+            FingerprintType::SHA256 => Some(DigestType::SHA256),
+            FingerprintType::Reserved |
+            FingerprintType::Unassigned(_) => None,
+        }
+    }
+}
+
+impl From<u8> for FingerprintType {
+    fn from(fingerprint_type: u8) -> Self {
+        match fingerprint_type {
+            0 => FingerprintType::Reserved,
+            1 => FingerprintType::SHA1,
+            2 => FingerprintType::SHA256,
+            _ => FingerprintType::Unassigned(fingerprint_type),
+        }
+    }
+}
+
+impl From<FingerprintType> for u8 {
+    fn from(fingerprint_type: FingerprintType) -> Self {
+        match fingerprint_type {
+            FingerprintType::Reserved => 0,
+            FingerprintType::SHA1 => 1,
+            FingerprintType::SHA256 => 2,
+            FingerprintType::Unassigned(fingerprint_type) => fingerprint_type,
+        }
+    }
+}
+
+impl SSHFP {
+    /// Constructs a new SSHFP RData
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - the algorithm of the host key
+    /// * `fingerprint_type` - the hash of the fingerprint
+    /// * `fingerprint` - the hash of the host key, in the wire format of the SSH protocol
+    ///
+    /// # Returns
+    ///
+    /// A SSHFP RData for use in a Resource Record
+    pub fn new(
+        algorithm: Algorithm,
+        fingerprint_type: FingerprintType,
+        fingerprint: Vec<u8>,
+    ) -> SSHFP {
+        SSHFP {
+            algorithm: algorithm,
+            fingerprint_type: fingerprint_type,
+            fingerprint: fingerprint,
+        }
+    }
+
+    /// The algorithm of the host key, RFC 4255 section 3.1.1
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// The hash of the fingerprint, RFC 4255 section 3.1.2
+    pub fn fingerprint_type(&self) -> FingerprintType {
+        self.fingerprint_type
+    }
+
+    /// The fingerprint of the host key, RFC 4255 section 3.1.3
+    pub fn fingerprint(&self) -> &[u8] {
+        &self.fingerprint
+    }
+
+    /// Returns true if this is the fingerprint of the host key
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - the algorithm of the host key
+    /// * `host_key` - the public host key, in the wire format of the SSH protocol, RFC 4253
+    ///                section 6.6, i.e. the base64 decoded key of `known_hosts`
+    ///
+    /// # Return
+    ///
+    /// false if the algorithm differs, an error is returned if the fingerprint type is not
+    ///  supported.
+    #[cfg(any(feature = "openssl", feature = "ring"))]
+    pub fn matches(&self, algorithm: Algorithm, host_key: &[u8]) -> ProtoResult<bool> {
+        let digest_type = try!(self.fingerprint_type.digest_type().ok_or_else(|| {
+            ProtoError::from(ProtoErrorKind::Msg(format!(
+                "unsupported SSHFP fingerprint type: {:?}",
+                self.fingerprint_type
+            )))
+        }));
+
+        if algorithm != self.algorithm {
+            return Ok(false);
+        }
+
+        digest_type.hash(host_key).map(|hash| {
+            hash.as_ref() == self.fingerprint()
+        })
+    }
+
+    /// This will always return an error unless the Ring or OpenSSL features are enabled
+    #[cfg(not(any(feature = "openssl", feature = "ring")))]
+    pub fn matches(&self, _: Algorithm, _: &[u8]) -> ProtoResult<bool> {
+        Err(
+            ProtoErrorKind::Message("Ring or OpenSSL must be enabled for this feature").into(),
+        )
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> ProtoResult<SSHFP> {
+    if rdata_length < 2 {
+        return Err(
+            ProtoErrorKind::Msg(format!("SSHFP rdata too short: {}", rdata_length)).into(),
+        );
+    }
+
+    let algorithm = Algorithm::from(try!(decoder.read_u8()));
+    let fingerprint_type = FingerprintType::from(try!(decoder.read_u8()));
+    let fingerprint = try!(decoder.read_vec(rdata_length as usize - 2));
+
+    Ok(SSHFP::new(algorithm, fingerprint_type, fingerprint))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, rdata: &SSHFP) -> ProtoResult<()> {
+    try!(encoder.emit(rdata.algorithm().into()));
+    try!(encoder.emit(rdata.fingerprint_type().into()));
+    try!(encoder.emit_vec(rdata.fingerprint()));
+
+    Ok(())
+}
+
+#[test]
+pub fn test() {
+    let rdata = SSHFP::new(Algorithm::Ed25519, FingerprintType::SHA256, vec![1, 2, 3, 4]);
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    println!("bytes: {:?}", bytes);
+    assert_eq!(bytes, &[4, 2, 1, 2, 3, 4]);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder, bytes.len() as u16);
+    assert!(
+        read_rdata.is_ok(),
+        format!("error decoding: {:?}", read_rdata.unwrap_err())
+    );
+    assert_eq!(rdata, read_rdata.unwrap());
+}
+
+#[test]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+pub fn test_matches() {
+    let host_key = b"\x00\x00\x00\x0bssh-ed25519 and the key";
+    let hash = DigestType::SHA256.hash(host_key).unwrap();
+    let sshfp = SSHFP::new(
+        Algorithm::Ed25519,
+        FingerprintType::SHA256,
+        hash.as_ref().to_vec(),
+    );
+
+    assert!(sshfp.matches(Algorithm::Ed25519, host_key).unwrap());
+    assert!(!sshfp.matches(Algorithm::RSA, host_key).unwrap());
+    assert!(!sshfp.matches(Algorithm::Ed25519, b"another key").unwrap());
+
+    let sshfp = SSHFP::new(Algorithm::Ed25519, FingerprintType::Unassigned(3), vec![]);
+    assert!(sshfp.matches(Algorithm::Ed25519, host_key).is_err());
+}
@@ -0,0 +1,517 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! service binding, the SVCB and HTTPS records
+
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use serialize::binary::*;
+use error::*;
+use rr::domain::Name;
+
+/// [RFC 9460, Service Binding and Parameter Specification via the DNS, November 2023](https://tools.ietf.org/html/rfc9460#section-2.2)
+///
+/// ```text
+/// 2.2.  RDATA Wire Format
+///
+///    The RDATA for the SVCB RR consists of:
+///
+///    *  a 2-octet field for SvcPriority as an integer in network byte
+///       order.
+///
+///    *  the uncompressed, fully qualified TargetName, represented as a
+///       sequence of length-prefixed labels per Section 3.1 of [RFC1035].
+///
+///    *  the SvcParams, consuming the remainder of the record (so smaller
+///       than 65535 octets and constrained by the RDATA and DNS message
+///       sizes).
+/// ```
+///
+/// The HTTPS record, RFC 9460 section 9, has the same format, for the origins of HTTP.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct SVCB {
+    svc_priority: u16,
+    target_name: Name,
+    svc_params: Vec<SvcParam>,
+}
+
+/// The key of a SvcParam, RFC 9460 section 14.3.2
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum SvcParamKey {
+    /// 0, the keys which the client must support to use the record
+    Mandatory,
+    /// 1, the Application-Layer Protocol Negotiation IDs of the protocols supported
+    Alpn,
+    /// 2, the default ALPN of the scheme is not supported
+    NoDefaultAlpn,
+    /// 3, the port at which the service is reached
+    Port,
+    /// 4, addresses of the target which may be used before they are looked up
+    Ipv4Hint,
+    /// 5, the TLS Encrypted ClientHello config list
+    Ech,
+    /// 6, addresses of the target which may be used before they are looked up
+    Ipv6Hint,
+    /// any other key, `keyNNNNN` in the presentation format
+    Key(u16),
+}
+
+impl From<u16> for SvcParamKey {
+    fn from(key: u16) -> Self {
+        match key {
+            0 => SvcParamKey::Mandatory,
+            1 => SvcParamKey::Alpn,
+            2 => SvcParamKey::NoDefaultAlpn,
+            3 => SvcParamKey::Port,
+            4 => SvcParamKey::Ipv4Hint,
+            5 => SvcParamKey::Ech,
+            6 => SvcParamKey::Ipv6Hint,
+            _ => SvcParamKey::Key(key),
+        }
+    }
+}
+
+impl From<SvcParamKey> for u16 {
+    fn from(key: SvcParamKey) -> Self {
+        match key {
+            SvcParamKey::Mandatory => 0,
+            SvcParamKey::Alpn => 1,
+            SvcParamKey::NoDefaultAlpn => 2,
+            SvcParamKey::Port => 3,
+            SvcParamKey::Ipv4Hint => 4,
+            SvcParamKey::Ech => 5,
+            SvcParamKey::Ipv6Hint => 6,
+            SvcParamKey::Key(key) => key,
+        }
+    }
+}
+
+impl SvcParamKey {
+    /// Parses the presentation format of the key, e.g. `alpn` or `key65333`
+    pub fn from_str(key: &str) -> ProtoResult<Self> {
+        match key {
+            "mandatory" => Ok(SvcParamKey::Mandatory),
+            "alpn" => Ok(SvcParamKey::Alpn),
+            "no-default-alpn" => Ok(SvcParamKey::NoDefaultAlpn),
+            "port" => Ok(SvcParamKey::Port),
+            "ipv4hint" => Ok(SvcParamKey::Ipv4Hint),
+            "ech" => Ok(SvcParamKey::Ech),
+            "ipv6hint" => Ok(SvcParamKey::Ipv6Hint),
+            _ if key.starts_with("key") => {
+                key[3..].parse::<u16>().map(SvcParamKey::from).map_err(|_| {
+                    ProtoErrorKind::Msg(format!("invalid SvcParamKey: {}", key)).into()
+                })
+            }
+            _ => Err(
+                ProtoErrorKind::Msg(format!("unknown SvcParamKey: {}", key)).into(),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for SvcParamKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            SvcParamKey::Mandatory => f.write_str("mandatory"),
+            SvcParamKey::Alpn => f.write_str("alpn"),
+            SvcParamKey::NoDefaultAlpn => f.write_str("no-default-alpn"),
+            SvcParamKey::Port => f.write_str("port"),
+            SvcParamKey::Ipv4Hint => f.write_str("ipv4hint"),
+            SvcParamKey::Ech => f.write_str("ech"),
+            SvcParamKey::Ipv6Hint => f.write_str("ipv6hint"),
+            SvcParamKey::Key(key) => write!(f, "key{}", key),
+        }
+    }
+}
+
+/// A service parameter of the SVCB, RFC 9460 section 7
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum SvcParam {
+    /// The keys which the client must support to use the record, section 8
+    Mandatory(Vec<SvcParamKey>),
+    /// The ALPN IDs of the protocols supported by the service, e.g. `h2` and `h3`, section 7.1
+    Alpn(Vec<String>),
+    /// The default ALPN of the scheme, `http/1.1` for HTTPS, is not supported, section 7.1
+    NoDefaultAlpn,
+    /// The port of the service, section 7.2
+    Port(u16),
+    /// Addresses of the target, section 7.3
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// The ECHConfigList, in the wire format of TLS
+    Ech(Vec<u8>),
+    /// Addresses of the target, section 7.3
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// The opaque value of a key not known to this implementation
+    Unknown(u16, Vec<u8>),
+}
+
+impl SvcParam {
+    /// The key of the parameter
+    pub fn key(&self) -> SvcParamKey {
+        match *self {
+            SvcParam::Mandatory(..) => SvcParamKey::Mandatory,
+            SvcParam::Alpn(..) => SvcParamKey::Alpn,
+            SvcParam::NoDefaultAlpn => SvcParamKey::NoDefaultAlpn,
+            SvcParam::Port(..) => SvcParamKey::Port,
+            SvcParam::Ipv4Hint(..) => SvcParamKey::Ipv4Hint,
+            SvcParam::Ech(..) => SvcParamKey::Ech,
+            SvcParam::Ipv6Hint(..) => SvcParamKey::Ipv6Hint,
+            SvcParam::Unknown(key, _) => SvcParamKey::Key(key),
+        }
+    }
+
+    /// Reads the value of the key, from the wire format
+    fn read(key: SvcParamKey, value: &[u8]) -> ProtoResult<SvcParam> {
+        let invalid =
+            || ProtoError::from(ProtoErrorKind::Msg(format!("invalid SvcParam value of {}", key)));
+
+        let param = match key {
+            SvcParamKey::Mandatory => {
+                if value.is_empty() || value.len() % 2 != 0 {
+                    return Err(invalid());
+                }
+                SvcParam::Mandatory(
+                    value
+                        .chunks(2)
+                        .map(|k| SvcParamKey::from(((k[0] as u16) << 8) | k[1] as u16))
+                        .collect(),
+                )
+            }
+            SvcParamKey::Alpn => {
+                let mut decoder = BinDecoder::new(value);
+                let mut alpn = Vec::new();
+                while decoder.len() > 0 {
+                    let id = try!(decoder.read_character_data());
+                    if id.is_empty() {
+                        return Err(invalid());
+                    }
+                    alpn.push(id);
+                }
+                if alpn.is_empty() {
+                    return Err(invalid());
+                }
+                SvcParam::Alpn(alpn)
+            }
+            SvcParamKey::NoDefaultAlpn => {
+                if !value.is_empty() {
+                    return Err(invalid());
+                }
+                SvcParam::NoDefaultAlpn
+            }
+            SvcParamKey::Port => {
+                if value.len() != 2 {
+                    return Err(invalid());
+                }
+                SvcParam::Port(((value[0] as u16) << 8) | value[1] as u16)
+            }
+            SvcParamKey::Ipv4Hint => {
+                if value.is_empty() || value.len() % 4 != 0 {
+                    return Err(invalid());
+                }
+                SvcParam::Ipv4Hint(
+                    value
+                        .chunks(4)
+                        .map(|a| Ipv4Addr::new(a[0], a[1], a[2], a[3]))
+                        .collect(),
+                )
+            }
+            SvcParamKey::Ech => SvcParam::Ech(value.to_vec()),
+            SvcParamKey::Ipv6Hint => {
+                if value.is_empty() || value.len() % 16 != 0 {
+                    return Err(invalid());
+                }
+                SvcParam::Ipv6Hint(
+                    value
+                        .chunks(16)
+                        .map(|a| {
+                            let mut octets = [0u8; 16];
+                            octets.copy_from_slice(a);
+                            Ipv6Addr::from(octets)
+                        })
+                        .collect(),
+                )
+            }
+            SvcParamKey::Key(key) => SvcParam::Unknown(key, value.to_vec()),
+        };
+
+        Ok(param)
+    }
+
+    /// Writes the value of the parameter, in the wire format
+    fn emit_value(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
+        match *self {
+            SvcParam::Mandatory(ref keys) => {
+                for key in keys {
+                    try!(encoder.emit_u16((*key).into()));
+                }
+            }
+            SvcParam::Alpn(ref alpn) => {
+                for id in alpn {
+                    try!(encoder.emit_character_data(id));
+                }
+            }
+            SvcParam::NoDefaultAlpn => (),
+            SvcParam::Port(port) => try!(encoder.emit_u16(port)),
+            SvcParam::Ipv4Hint(ref addrs) => {
+                for addr in addrs {
+                    try!(encoder.emit_vec(&addr.octets()));
+                }
+            }
+            SvcParam::Ech(ref ech) => try!(encoder.emit_vec(ech)),
+            SvcParam::Ipv6Hint(ref addrs) => {
+                for addr in addrs {
+                    try!(encoder.emit_vec(&addr.octets()));
+                }
+            }
+            SvcParam::Unknown(_, ref value) => try!(encoder.emit_vec(value)),
+        }
+
+        Ok(())
+    }
+}
+
+impl SVCB {
+    /// Constructs a new SVCB RData, also that of HTTPS
+    ///
+    /// # Arguments
+    ///
+    /// * `svc_priority` - 0 for AliasMode, otherwise the priority of the ServiceMode record, lower
+    ///                    values are preferred
+    /// * `target_name` - the name of the service, or for AliasMode the name to follow, `.` is
+    ///                   the owner name in ServiceMode, or the service not being available in
+    ///                   AliasMode
+    /// * `svc_params` - the parameters of the service, sorted by key when written
+    ///
+    /// # Returns
+    ///
+    /// A SVCB RData for use in a Resource Record
+    pub fn new(svc_priority: u16, target_name: Name, mut svc_params: Vec<SvcParam>) -> SVCB {
+        svc_params.sort_by_key(SvcParam::key);
+
+        SVCB {
+            svc_priority: svc_priority,
+            target_name: target_name,
+            svc_params: svc_params,
+        }
+    }
+
+    /// The priority of the record, 0 is AliasMode, RFC 9460 section 2.4.1
+    pub fn svc_priority(&self) -> u16 {
+        self.svc_priority
+    }
+
+    /// The name of the service, or the name to follow for AliasMode, RFC 9460 section 2.5
+    pub fn target_name(&self) -> &Name {
+        &self.target_name
+    }
+
+    /// The parameters, in the order of their keys
+    pub fn svc_params(&self) -> &[SvcParam] {
+        &self.svc_params
+    }
+
+    /// Returns true for AliasMode, the target is an alias of the owner name, RFC 9460 section 2.4.2
+    pub fn is_alias(&self) -> bool {
+        self.svc_priority == 0
+    }
+
+    /// Returns the parameter of the key
+    pub fn svc_param(&self, key: SvcParamKey) -> Option<&SvcParam> {
+        self.svc_params.iter().find(|param| param.key() == key)
+    }
+
+    /// The ALPN IDs of the protocols supported by the service, empty if there are none
+    pub fn alpn(&self) -> &[String] {
+        match self.svc_param(SvcParamKey::Alpn) {
+            Some(&SvcParam::Alpn(ref alpn)) => &alpn[..],
+            _ => &[],
+        }
+    }
+
+    /// The port of the service, None for the default port of the scheme
+    pub fn port(&self) -> Option<u16> {
+        match self.svc_param(SvcParamKey::Port) {
+            Some(&SvcParam::Port(port)) => Some(port),
+            _ => None,
+        }
+    }
+
+    /// The IPv4 hints of the target, empty if there are none
+    pub fn ipv4_hint(&self) -> &[Ipv4Addr] {
+        match self.svc_param(SvcParamKey::Ipv4Hint) {
+            Some(&SvcParam::Ipv4Hint(ref addrs)) => &addrs[..],
+            _ => &[],
+        }
+    }
+
+    /// The IPv6 hints of the target, empty if there are none
+    pub fn ipv6_hint(&self) -> &[Ipv6Addr] {
+        match self.svc_param(SvcParamKey::Ipv6Hint) {
+            Some(&SvcParam::Ipv6Hint(ref addrs)) => &addrs[..],
+            _ => &[],
+        }
+    }
+
+    /// The ECHConfigList, in the wire format of TLS
+    pub fn ech(&self) -> Option<&[u8]> {
+        match self.svc_param(SvcParamKey::Ech) {
+            Some(&SvcParam::Ech(ref ech)) => Some(&ech[..]),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the client, which supports the given keys, may use the record, i.e. all of
+    ///  the mandatory keys are supported, RFC 9460 section 8
+    pub fn is_compatible(&self, supported: &[SvcParamKey]) -> bool {
+        match self.svc_param(SvcParamKey::Mandatory) {
+            Some(&SvcParam::Mandatory(ref keys)) => keys.iter().all(|key| supported.contains(key)),
+            _ => true,
+        }
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> ProtoResult<SVCB> {
+    let start_idx = decoder.index();
+
+    let svc_priority = try!(decoder.read_u16());
+    let target_name = try!(Name::read(decoder));
+
+    let mut svc_params: Vec<SvcParam> = Vec::new();
+    while decoder.index() - start_idx < rdata_length as usize {
+        let key = SvcParamKey::from(try!(decoder.read_u16()));
+        let len = try!(decoder.read_u16()) as usize;
+        if decoder.index() - start_idx + len > rdata_length as usize {
+            return Err(ProtoErrorKind::Message("SvcParam value beyond the rdata").into());
+        }
+
+        // the keys must be in strictly increasing order, section 2.2
+        if let Some(last) = svc_params.last() {
+            if last.key() >= key {
+                return Err(
+                    ProtoErrorKind::Msg(format!("SvcParamKey out of order: {}", key)).into(),
+                );
+            }
+        }
+
+        let value = try!(decoder.read_vec(len));
+        svc_params.push(try!(SvcParam::read(key, &value)));
+    }
+
+    Ok(SVCB {
+        svc_priority: svc_priority,
+        target_name: target_name,
+        svc_params: svc_params,
+    })
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, svcb: &SVCB) -> ProtoResult<()> {
+    try!(encoder.emit_u16(svcb.svc_priority()));
+    // the target name is never compressed
+    try!(svcb.target_name().emit_as_canonical(encoder, true));
+
+    for param in svcb.svc_params() {
+        let mut value = Vec::new();
+        {
+            let mut value_encoder = BinEncoder::new(&mut value);
+            try!(param.emit_value(&mut value_encoder));
+        }
+
+        try!(encoder.emit_u16(param.key().into()));
+        try!(encoder.emit_u16(value.len() as u16));
+        try!(encoder.emit_vec(&value));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(rdata: &SVCB) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+            assert!(emit(&mut encoder, rdata).is_ok());
+        }
+
+        {
+            let mut decoder: BinDecoder = BinDecoder::new(&bytes);
+            let read_rdata = read(&mut decoder, bytes.len() as u16);
+            assert!(
+                read_rdata.is_ok(),
+                format!("error decoding: {:?}", read_rdata.unwrap_err())
+            );
+            assert_eq!(rdata, &read_rdata.unwrap());
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn test_alias() {
+        // RFC 9460 appendix D.1, example.com. HTTPS 0 foo.example.com.
+        let rdata = SVCB::new(0, Name::parse("foo.example.com.", None).unwrap(), vec![]);
+        assert!(rdata.is_alias());
+
+        let bytes = round_trip(&rdata);
+        assert_eq!(&bytes[..2], &[0, 0]);
+        assert_eq!(bytes.len(), 2 + 17);
+    }
+
+    #[test]
+    fn test_service() {
+        // RFC 9460 appendix D.2, the order of the params is that of the keys once read
+        let rdata = SVCB::new(
+            16,
+            Name::parse("foo.example.org.", None).unwrap(),
+            vec![
+                SvcParam::Ipv6Hint(vec!["2001:db8::1".parse().unwrap()]),
+                SvcParam::Alpn(vec!["h2".to_string(), "h3-19".to_string()]),
+                SvcParam::Mandatory(vec![SvcParamKey::Alpn, SvcParamKey::Ipv4Hint]),
+                SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1)]),
+                SvcParam::Port(53),
+                SvcParam::Unknown(667, b"hello".to_vec()),
+            ],
+        );
+
+        round_trip(&rdata);
+        assert!(!rdata.is_alias());
+        assert_eq!(rdata.svc_params()[0].key(), SvcParamKey::Mandatory);
+        assert_eq!(rdata.alpn(), &["h2".to_string(), "h3-19".to_string()]);
+        assert_eq!(rdata.port(), Some(53));
+        assert_eq!(rdata.ipv4_hint(), &[Ipv4Addr::new(192, 0, 2, 1)]);
+        assert_eq!(rdata.ipv6_hint().len(), 1);
+        assert_eq!(rdata.ech(), None);
+
+        assert!(rdata.is_compatible(&[SvcParamKey::Alpn, SvcParamKey::Ipv4Hint]));
+        assert!(!rdata.is_compatible(&[SvcParamKey::Alpn]));
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        // port then alpn
+        let bytes = [0, 1, 0, 0, 3, 0, 2, 1, 187, 0, 1, 0, 3, 2, b'h', b'2'];
+        let mut decoder = BinDecoder::new(&bytes);
+        assert!(read(&mut decoder, bytes.len() as u16).is_err());
+    }
+
+    #[test]
+    fn test_key_str() {
+        assert_eq!(SvcParamKey::from_str("alpn").unwrap(), SvcParamKey::Alpn);
+        assert_eq!(
+            SvcParamKey::from_str("key667").unwrap(),
+            SvcParamKey::Key(667)
+        );
+        assert_eq!(SvcParamKey::from_str("key1").unwrap(), SvcParamKey::Alpn);
+        assert!(SvcParamKey::from_str("key").is_err());
+        assert_eq!(SvcParamKey::Key(667).to_string(), "key667");
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! TLSA records for the association of a TLS certificate with a domain name, DANE
+
+use serialize::binary::*;
+use error::*;
+
+/// [RFC 6698, DNS-Based Authentication for TLS, August 2012](https://tools.ietf.org/html/rfc6698#section-2.1)
+///
+/// ```text
+/// 2.1.  TLSA RDATA Wire Format
+///
+///    The RDATA for a TLSA RR consists of a one-octet certificate usage
+///    field, a one-octet selector field, a one-octet matching type field,
+///    and the certificate association data field.
+///
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |  Cert. Usage  |   Selector    | Matching Type |               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+               /
+///    /                                                               /
+///    /                 Certificate Association Data                  /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct TLSA {
+    cert_usage: CertUsage,
+    selector: Selector,
+    matching: Matching,
+    cert_data: Vec<u8>,
+}
+
+/// The certificate usage, which certificate of the chain is matched and how it is validated,
+///  the acronyms are those of RFC 7218
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum CertUsage {
+    /// 0, a CA certificate of the chain, which must also pass PKIX validation, PKIX-TA
+    PkixTa,
+    /// 1, the end entity certificate, which must also pass PKIX validation, PKIX-EE
+    PkixEe,
+    /// 2, the trust anchor of the chain, in place of those of PKIX, DANE-TA
+    DaneTa,
+    /// 3, the end entity certificate, without PKIX validation, DANE-EE
+    DaneEe,
+    /// Unassigned usages, which are unusable
+    Unassigned(u8),
+    /// 255, private use, PrivCert
+    Private,
+}
+
+impl From<u8> for CertUsage {
+    fn from(usage: u8) -> Self {
+        match usage {
+            0 => CertUsage::PkixTa,
+            1 => CertUsage::PkixEe,
+            2 => CertUsage::DaneTa,
+            3 => CertUsage::DaneEe,
+            255 => CertUsage::Private,
+            _ => CertUsage::Unassigned(usage),
+        }
+    }
+}
+
+impl From<CertUsage> for u8 {
+    fn from(usage: CertUsage) -> Self {
+        match usage {
+            CertUsage::PkixTa => 0,
+            CertUsage::PkixEe => 1,
+            CertUsage::DaneTa => 2,
+            CertUsage::DaneEe => 3,
+            CertUsage::Unassigned(usage) => usage,
+            CertUsage::Private => 255,
+        }
+    }
+}
+
+/// Which part of the certificate is matched
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Selector {
+    /// 0, the full DER encoded certificate, Cert
+    Full,
+    /// 1, the DER encoded SubjectPublicKeyInfo of the certificate, SPKI
+    Spki,
+    /// Unassigned selectors, which are unusable
+    Unassigned(u8),
+    /// 255, private use, PrivSel
+    Private,
+}
+
+impl From<u8> for Selector {
+    fn from(selector: u8) -> Self {
+        match selector {
+            0 => Selector::Full,
+            1 => Selector::Spki,
+            255 => Selector::Private,
+            _ => Selector::Unassigned(selector),
+        }
+    }
+}
+
+impl From<Selector> for u8 {
+    fn from(selector: Selector) -> Self {
+        match selector {
+            Selector::Full => 0,
+            Selector::Spki => 1,
+            Selector::Unassigned(selector) => selector,
+            Selector::Private => 255,
+        }
+    }
+}
+
+/// How the selected part of the certificate is matched against the certificate association data
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Matching {
+    /// 0, the data is the selected content itself, Full
+    Raw,
+    /// 1, the SHA-256 digest of the selected content, SHA2-256
+    Sha256,
+    /// 2, the SHA-512 digest of the selected content, SHA2-512
+    Sha512,
+    /// Unassigned matching types, which are unusable
+    Unassigned(u8),
+    /// 255, private use, PrivMatch
+    Private,
+}
+
+impl From<u8> for Matching {
+    fn from(matching: u8) -> Self {
+        match matching {
+            0 => Matching::Raw,
+            1 => Matching::Sha256,
+            2 => Matching::Sha512,
+            255 => Matching::Private,
+            _ => Matching::Unassigned(matching),
+        }
+    }
+}
+
+impl From<Matching> for u8 {
+    fn from(matching: Matching) -> Self {
+        match matching {
+            Matching::Raw => 0,
+            Matching::Sha256 => 1,
+            Matching::Sha512 => 2,
+            Matching::Unassigned(matching) => matching,
+            Matching::Private => 255,
+        }
+    }
+}
+
+impl TLSA {
+    /// Constructs a new TLSA RData
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_usage` - which certificate of the chain is matched, and how it is validated
+    /// * `selector` - the full certificate or its public key
+    /// * `matching` - the selected content, or its SHA-256 or SHA-512 digest
+    /// * `cert_data` - the certificate association data to match
+    ///
+    /// # Returns
+    ///
+    /// A TLSA RData for use in a Resource Record
+    pub fn new(
+        cert_usage: CertUsage,
+        selector: Selector,
+        matching: Matching,
+        cert_data: Vec<u8>,
+    ) -> TLSA {
+        TLSA {
+            cert_usage: cert_usage,
+            selector: selector,
+            matching: matching,
+            cert_data: cert_data,
+        }
+    }
+
+    /// The certificate usage, RFC 6698 section 2.1.1
+    pub fn cert_usage(&self) -> CertUsage {
+        self.cert_usage
+    }
+
+    /// The selector, RFC 6698 section 2.1.2
+    pub fn selector(&self) -> Selector {
+        self.selector
+    }
+
+    /// The matching type, RFC 6698 section 2.1.3
+    pub fn matching(&self) -> Matching {
+        self.matching
+    }
+
+    /// The certificate association data, RFC 6698 section 2.1.4
+    pub fn cert_data(&self) -> &[u8] {
+        &self.cert_data
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> ProtoResult<TLSA> {
+    if rdata_length < 3 {
+        return Err(
+            ProtoErrorKind::Msg(format!("TLSA rdata too short: {}", rdata_length)).into(),
+        );
+    }
+
+    let cert_usage = CertUsage::from(try!(decoder.read_u8()));
+    let selector = Selector::from(try!(decoder.read_u8()));
+    let matching = Matching::from(try!(decoder.read_u8()));
+    let cert_data = try!(decoder.read_vec(rdata_length as usize - 3));
+
+    Ok(TLSA::new(cert_usage, selector, matching, cert_data))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, rdata: &TLSA) -> ProtoResult<()> {
+    try!(encoder.emit(rdata.cert_usage().into()));
+    try!(encoder.emit(rdata.selector().into()));
+    try!(encoder.emit(rdata.matching().into()));
+    try!(encoder.emit_vec(rdata.cert_data()));
+
+    Ok(())
+}
+
+#[test]
+pub fn test() {
+    let rdata = TLSA::new(
+        CertUsage::DaneEe,
+        Selector::Spki,
+        Matching::Sha256,
+        vec![1, 2, 3, 4],
+    );
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    println!("bytes: {:?}", bytes);
+    assert_eq!(bytes, &[3, 1, 1, 1, 2, 3, 4]);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder, bytes.len() as u16);
+    assert!(
+        read_rdata.is_ok(),
+        format!("error decoding: {:?}", read_rdata.unwrap_err())
+    );
+    assert_eq!(rdata, read_rdata.unwrap());
+}
+
+#[test]
+pub fn test_unassigned() {
+    assert_eq!(CertUsage::from(4), CertUsage::Unassigned(4));
+    assert_eq!(u8::from(CertUsage::Unassigned(4)), 4);
+    assert_eq!(Selector::from(255), Selector::Private);
+    assert_eq!(Matching::from(3), Matching::Unassigned(3));
+}
@@ -0,0 +1,289 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! transaction signature for authenticating messages with a shared secret
+
+use serialize::binary::*;
+use error::*;
+use op::ResponseCode;
+use rr::Name;
+
+/// The request was signed with a key which is not known to the server, RFC 8945 section 5.2
+pub const BADKEY: u16 = 17;
+/// The MAC of the message did not match, RFC 8945 section 5.2
+pub const BADSIG: u16 = 16;
+/// The time signed of the message was outside of the fudge, RFC 8945 section 5.2.3
+pub const BADTIME: u16 = 18;
+/// The MAC of the message was truncated below the allowed length, RFC 8945 section 5.2.2.1
+pub const BADTRUNC: u16 = 22;
+
+/// [RFC 8945, Secret Key Transaction Authentication for DNS, November 2020](https://tools.ietf.org/html/rfc8945#section-4.2)
+///
+/// ```text
+/// 4.2.  TSIG Record Format
+///
+///    The fields of the TSIG RR are described below.  All multi-octet
+///    integers in the record are sent in network byte order (see
+///    Section 2.3.2 of [RFC1035]).
+///
+///    NAME:  The name of the key used, in domain name syntax.  The name
+///       should reflect the names of the hosts and uniquely identify the
+///       key among a set of keys these two hosts may share at any given
+///       time.
+///
+///    TYPE:  This MUST be TSIG (250: Transaction SIGnature).
+///
+///    CLASS:  This MUST be ANY.
+///
+///    TTL:  This MUST be 0.
+///
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    /                         Algorithm Name                        /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |                                                               |
+///    |          Time Signed          +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |                               |            Fudge              |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |          MAC Size             |                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+             MAC               /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |          Original ID          |            Error              |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |          Other Len            |                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+           Other Data          /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct TSIG {
+    algorithm: Name,
+    time_signed: u64,
+    fudge: u16,
+    mac: Vec<u8>,
+    original_id: u16,
+    error: u16,
+    other: Vec<u8>,
+}
+
+impl TSIG {
+    /// Constructs a new TSIG RData
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - the name of the MAC algorithm, e.g. `hmac-sha256.`
+    /// * `time_signed` - seconds since the epoch at which the message was signed, only the lower
+    ///                   48 bits are sent
+    /// * `fudge` - seconds of allowed difference between the clocks of the signer and verifier
+    /// * `mac` - the MAC of the message
+    /// * `original_id` - the id of the message when it was signed
+    /// * `error` - the extended rcode of a TSIG failure, e.g. `BADSIG`, or 0
+    /// * `other` - for `BADTIME` errors, the time of the server
+    pub fn new(
+        algorithm: Name,
+        time_signed: u64,
+        fudge: u16,
+        mac: Vec<u8>,
+        original_id: u16,
+        error: u16,
+        other: Vec<u8>,
+    ) -> TSIG {
+        TSIG {
+            algorithm: algorithm,
+            time_signed: time_signed & 0xFFFF_FFFF_FFFF,
+            fudge: fudge,
+            mac: mac,
+            original_id: original_id,
+            error: error,
+            other: other,
+        }
+    }
+
+    /// The name of the MAC algorithm, e.g. `hmac-sha256.`
+    pub fn algorithm(&self) -> &Name {
+        &self.algorithm
+    }
+
+    /// Seconds since the epoch at which the message was signed
+    pub fn time_signed(&self) -> u64 {
+        self.time_signed
+    }
+
+    /// Seconds of allowed difference between the clocks of the signer and verifier
+    pub fn fudge(&self) -> u16 {
+        self.fudge
+    }
+
+    /// The MAC of the message
+    pub fn mac(&self) -> &[u8] {
+        &self.mac
+    }
+
+    /// The id of the message when it was signed, forwarders may change the id of the message
+    pub fn original_id(&self) -> u16 {
+        self.original_id
+    }
+
+    /// The extended rcode of a TSIG failure, e.g. `BADSIG`, or 0
+    pub fn error(&self) -> u16 {
+        self.error
+    }
+
+    /// The error as a `ResponseCode`, e.g. `ResponseCode::BADSIG`, or `NoError`
+    pub fn error_code(&self) -> ResponseCode {
+        ResponseCode::from_tsig_error(self.error)
+    }
+
+    /// For `BADTIME` errors, the time of the server
+    pub fn other(&self) -> &[u8] {
+        &self.other
+    }
+
+    /// Returns true if the time signed is within the fudge of the current time
+    pub fn is_timely(&self, current_time: u64) -> bool {
+        let difference = if current_time > self.time_signed {
+            current_time - self.time_signed
+        } else {
+            self.time_signed - current_time
+        };
+
+        difference <= u64::from(self.fudge)
+    }
+
+    /// Emits the TSIG variables which are covered by the MAC, RFC 8945 section 4.3.3
+    ///
+    /// ```text
+    ///    Source       Field Name       Notes
+    ///    -----------------------------------------------------------
+    ///    TSIG RR      NAME             Key name, in canonical wire format
+    ///    TSIG RR      CLASS            MUST be ANY
+    ///    TSIG RR      TTL              MUST be 0
+    ///    TSIG RDATA   Algorithm Name   in canonical wire format
+    ///    TSIG RDATA   Time Signed      in network byte order
+    ///    TSIG RDATA   Fudge            in network byte order
+    ///    TSIG RDATA   Error            in network byte order
+    ///    TSIG RDATA   Other Len        in network byte order
+    ///    TSIG RDATA   Other Data       exactly as transmitted
+    /// ```
+    pub fn emit_variables(&self, encoder: &mut BinEncoder, key_name: &Name) -> ProtoResult<()> {
+        try!(key_name.to_lowercase().emit_as_canonical(encoder, true));
+        // class ANY
+        try!(encoder.emit_u16(255));
+        // TTL
+        try!(encoder.emit_u32(0));
+        try!(self.algorithm.to_lowercase().emit_as_canonical(encoder, true));
+        try!(emit_time_signed(encoder, self.time_signed));
+        try!(encoder.emit_u16(self.fudge));
+        try!(encoder.emit_u16(self.error));
+        try!(encoder.emit_u16(self.other.len() as u16));
+        try!(encoder.emit_vec(&self.other));
+
+        Ok(())
+    }
+
+    /// Emits the TSIG timers, which the MACs of the messages after the first of a response of
+    ///  several messages, e.g. a zone transfer, cover instead of the variables, RFC 8945 section
+    ///  5.3.1
+    pub fn emit_timers(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
+        try!(emit_time_signed(encoder, self.time_signed));
+        encoder.emit_u16(self.fudge)
+    }
+}
+
+fn emit_time_signed(encoder: &mut BinEncoder, time_signed: u64) -> ProtoResult<()> {
+    try!(encoder.emit_u16((time_signed >> 32) as u16));
+    encoder.emit_u32(time_signed as u32)
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder) -> ProtoResult<TSIG> {
+    let algorithm = try!(Name::read(decoder));
+    let time_high = u64::from(try!(decoder.read_u16()));
+    let time_low = u64::from(try!(decoder.read_u32()));
+    let fudge = try!(decoder.read_u16());
+    let mac_size = try!(decoder.read_u16());
+    let mac = try!(decoder.read_vec(mac_size as usize));
+    let original_id = try!(decoder.read_u16());
+    let error = try!(decoder.read_u16());
+    let other_len = try!(decoder.read_u16());
+    let other = try!(decoder.read_vec(other_len as usize));
+
+    Ok(TSIG::new(
+        algorithm,
+        (time_high << 32) | time_low,
+        fudge,
+        mac,
+        original_id,
+        error,
+        other,
+    ))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, rdata: &TSIG) -> ProtoResult<()> {
+    // the algorithm name is never compressed, RFC 8945 section 4.2
+    try!(rdata.algorithm().emit_as_canonical(encoder, true));
+    try!(emit_time_signed(encoder, rdata.time_signed()));
+    try!(encoder.emit_u16(rdata.fudge()));
+    try!(encoder.emit_u16(rdata.mac().len() as u16));
+    try!(encoder.emit_vec(rdata.mac()));
+    try!(encoder.emit_u16(rdata.original_id()));
+    try!(encoder.emit_u16(rdata.error()));
+    try!(encoder.emit_u16(rdata.other().len() as u16));
+    try!(encoder.emit_vec(rdata.other()));
+
+    Ok(())
+}
+
+#[test]
+pub fn test() {
+    let rdata = TSIG::new(
+        Name::parse("hmac-sha256.", None).unwrap(),
+        0x0001_5A5B_5C5D,
+        300,
+        vec![1, 2, 3, 4],
+        0x1234,
+        0,
+        vec![],
+    );
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    println!("bytes: {:?}", bytes);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder);
+    assert!(
+        read_rdata.is_ok(),
+        format!("error decoding: {:?}", read_rdata.unwrap_err())
+    );
+    assert_eq!(rdata, read_rdata.unwrap());
+}
+
+#[test]
+pub fn test_is_timely() {
+    let rdata = TSIG::new(
+        Name::parse("hmac-sha256.", None).unwrap(),
+        1000,
+        300,
+        vec![],
+        0,
+        0,
+        vec![],
+    );
+
+    assert!(rdata.is_timely(1000));
+    assert!(rdata.is_timely(700));
+    assert!(rdata.is_timely(1300));
+    assert!(!rdata.is_timely(699));
+    assert!(!rdata.is_timely(1301));
+}
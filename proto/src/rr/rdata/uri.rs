@@ -0,0 +1,145 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! URI records for mapping a service name to a URI
+
+use serialize::binary::*;
+use error::*;
+
+/// [RFC 7553, The Uniform Resource Identifier (URI) DNS Resource Record, June 2015](https://tools.ietf.org/html/rfc7553#section-4.5)
+///
+/// ```text
+/// 4.5.  URI RDATA Wire Format
+///
+///    The RDATA for a URI RR consists of a 2-octet Priority field, a
+///    2-octet Weight field, and a variable-length Target field.
+///
+///    Priority and Weight are unsigned integers in network byte order.
+///
+///    The remaining data in the RDATA contains the Target field.  The
+///    Target field contains the URI as a sequence of octets (without the
+///    enclosing double-quote characters used in the presentation format).
+///
+///    The length of the Target field MUST be greater than zero.
+///
+///                        1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |          Priority             |          Weight               |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    /                                                               /
+///    /                             Target                            /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+///
+/// The priority and weight are those of SRV, RFC 2782, the owner name is that of SRV, e.g.
+///  `_ftp._tcp.example.com.`, or has an enumservice as the service, RFC 7553 section 4.1.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct URI {
+    priority: u16,
+    weight: u16,
+    target: String,
+}
+
+impl URI {
+    /// Constructs a new URI RData
+    ///
+    /// # Arguments
+    ///
+    /// * `priority` - lower values are tried first, as with SRV
+    /// * `weight` - the relative weight of the records of the same priority, as with SRV
+    /// * `target` - the URI, RFC 3986
+    ///
+    /// # Returns
+    ///
+    /// A URI RData for use in a Resource Record
+    pub fn new(priority: u16, weight: u16, target: String) -> URI {
+        URI {
+            priority: priority,
+            weight: weight,
+            target: target,
+        }
+    }
+
+    /// The priority of the target, RFC 7553 section 4.3
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// The weight of the target among those of the same priority, RFC 7553 section 4.4
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    /// The URI, RFC 7553 section 4.5
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> ProtoResult<URI> {
+    if rdata_length < 5 {
+        return Err(
+            ProtoErrorKind::Msg(format!("URI rdata too short: {}", rdata_length)).into(),
+        );
+    }
+
+    let priority = try!(decoder.read_u16());
+    let weight = try!(decoder.read_u16());
+    let target = try!(String::from_utf8(
+        try!(decoder.read_vec(rdata_length as usize - 4)),
+    ));
+
+    Ok(URI::new(priority, weight, target))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, uri: &URI) -> ProtoResult<()> {
+    if uri.target().is_empty() {
+        return Err(ProtoErrorKind::Message("URI target must not be empty").into());
+    }
+
+    try!(encoder.emit_u16(uri.priority()));
+    try!(encoder.emit_u16(uri.weight()));
+    try!(encoder.emit_vec(uri.target().as_bytes()));
+
+    Ok(())
+}
+
+#[test]
+pub fn test() {
+    let rdata = URI::new(10, 1, "ftp://ftp1.example.com/public".to_string());
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    println!("bytes: {:?}", bytes);
+    assert_eq!(&bytes[..4], &[0, 10, 0, 1]);
+    assert_eq!(&bytes[4..], b"ftp://ftp1.example.com/public");
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder, bytes.len() as u16);
+    assert!(
+        read_rdata.is_ok(),
+        format!("error decoding: {:?}", read_rdata.unwrap_err())
+    );
+    assert_eq!(rdata, read_rdata.unwrap());
+}
+
+#[test]
+pub fn test_empty_target() {
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &URI::new(10, 1, String::new())).is_err());
+
+    let mut decoder: BinDecoder = BinDecoder::new(&[0, 10, 0, 1]);
+    assert!(read(&mut decoder, 4).is_err());
+}
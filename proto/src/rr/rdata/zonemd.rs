@@ -0,0 +1,278 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! message digest for the contents of an entire zone
+
+use serialize::binary::*;
+use error::*;
+use rr::dnssec::DigestType;
+use rr::{Name, Record};
+
+/// The SIMPLE collation scheme, the only scheme currently defined
+pub const SCHEME_SIMPLE: u8 = 1;
+/// SHA-384 hash algorithm
+pub const HASH_SHA384: u8 = 1;
+/// SHA-512 hash algorithm
+pub const HASH_SHA512: u8 = 2;
+
+/// [RFC 8976, Message Digest for DNS Zones, February 2021](https://tools.ietf.org/html/rfc8976#section-2.2)
+///
+/// ```text
+/// 2.2.  ZONEMD RDATA Wire Format
+///
+///    The ZONEMD RDATA wire format is encoded as follows:
+///
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |                             Serial                            |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |    Scheme     |Hash Algorithm |                               |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               |
+///    |                             Digest                            |
+///    /                                                               /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ZONEMD {
+    serial: u32,
+    scheme: u8,
+    hash_algorithm: u8,
+    digest: Vec<u8>,
+}
+
+impl ZONEMD {
+    /// Constructs a new ZONEMD RData
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - the serial of the SOA for which the digest was created
+    /// * `scheme` - the collation scheme, only `SCHEME_SIMPLE` is defined
+    /// * `hash_algorithm` - the hash used to create the digest, e.g. `HASH_SHA384`
+    /// * `digest` - the digest of the zone
+    pub fn new(serial: u32, scheme: u8, hash_algorithm: u8, digest: Vec<u8>) -> ZONEMD {
+        ZONEMD {
+            serial: serial,
+            scheme: scheme,
+            hash_algorithm: hash_algorithm,
+            digest: digest,
+        }
+    }
+
+    /// [RFC 8976](https://tools.ietf.org/html/rfc8976#section-2.2.1)
+    ///
+    /// ```text
+    /// 2.2.1.  The Serial Field
+    ///
+    ///    The Serial field is a 32-bit unsigned integer in network byte order.
+    ///    It is the serial number from the zone's SOA record ([RFC1035],
+    ///    Section 3.3.13) for which the zone digest was generated.
+    /// ```
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// The collation scheme used to construct the digest
+    pub fn scheme(&self) -> u8 {
+        self.scheme
+    }
+
+    /// The hash algorithm used to construct the digest
+    pub fn hash_algorithm(&self) -> u8 {
+        self.hash_algorithm
+    }
+
+    /// The digest of the zone
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+
+    /// Returns the DigestType for the hash algorithm, if the scheme and hash are supported
+    pub fn digest_type(&self) -> Option<DigestType> {
+        if self.scheme != SCHEME_SIMPLE {
+            return None;
+        }
+
+        match self.hash_algorithm {
+            HASH_SHA384 => Some(DigestType::SHA384),
+            HASH_SHA512 => Some(DigestType::SHA512),
+            _ => None,
+        }
+    }
+
+    /// Validates that the records of the zone match this digest.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the apex of the zone
+    /// * `records` - all records of the zone, including the SOA, RRSIGs and this ZONEMD
+    ///
+    /// # Return
+    ///
+    /// true if and only if the digest of the records is equal to this digest, an error is
+    ///  returned if the scheme or hash algorithm is not supported.
+    #[cfg(any(feature = "openssl", feature = "ring"))]
+    pub fn covers(&self, origin: &Name, records: &[Record]) -> ProtoResult<bool> {
+        let digest_type = try!(self.digest_type().ok_or_else(|| {
+            ProtoError::from(ProtoErrorKind::Msg(format!(
+                "unsupported ZONEMD scheme: {} hash: {}",
+                self.scheme,
+                self.hash_algorithm
+            )))
+        }));
+
+        digest(origin, records, digest_type).map(|hash| hash.as_ref() == self.digest())
+    }
+
+    /// This will always return an error unless the Ring or OpenSSL features are enabled
+    #[cfg(not(any(feature = "openssl", feature = "ring")))]
+    pub fn covers(&self, _: &Name, _: &[Record]) -> ProtoResult<bool> {
+        Err(
+            ProtoErrorKind::Message("Ring or OpenSSL must be enabled for this feature").into(),
+        )
+    }
+}
+
+/// Computes the SIMPLE scheme digest of the zone
+///
+/// [RFC 8976](https://tools.ietf.org/html/rfc8976#section-3.3), all records in canonical form
+///  and order, excluding the apex ZONEMD records and the RRSIGs which cover them, duplicates are
+///  removed.
+#[cfg(any(feature = "openssl", feature = "ring"))]
+pub fn digest(
+    origin: &Name,
+    records: &[Record],
+    digest_type: DigestType,
+) -> ProtoResult<::rr::dnssec::Digest> {
+    use rr::{RData, RecordType};
+
+    let mut records: Vec<&Record> = records
+        .iter()
+        .filter(|record| {
+            if record.name() != origin {
+                return true;
+            }
+
+            match *record.rdata() {
+                RData::ZONEMD(..) => false,
+                RData::SIG(ref sig) if sig.type_covered() == RecordType::ZONEMD => false,
+                _ => true,
+            }
+        })
+        .collect();
+
+    records.sort();
+    records.dedup();
+
+    let mut buf: Vec<u8> = Vec::new();
+    {
+        let mut encoder: BinEncoder = BinEncoder::new(&mut buf);
+        encoder.set_canonical_names(true);
+
+        for record in records {
+            try!(record.name().to_lowercase().emit_as_canonical(
+                &mut encoder,
+                true,
+            ));
+            try!(record.rr_type().emit(&mut encoder));
+            try!(record.dns_class().emit(&mut encoder));
+            try!(encoder.emit_u32(record.ttl()));
+
+            let mut rdata_buf = Vec::new();
+            {
+                let mut rdata_encoder = BinEncoder::new(&mut rdata_buf);
+                rdata_encoder.set_canonical_names(true);
+                try!(record.rdata().emit(&mut rdata_encoder));
+            }
+            try!(encoder.emit_u16(rdata_buf.len() as u16));
+            try!(encoder.emit_vec(&rdata_buf));
+        }
+    }
+
+    digest_type.hash(&buf)
+}
+
+/// Read the RData from the given Decoder
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> ProtoResult<ZONEMD> {
+    let start_idx = decoder.index();
+
+    let serial: u32 = try!(decoder.read_u32());
+    let scheme: u8 = try!(decoder.read_u8());
+    let hash_algorithm: u8 = try!(decoder.read_u8());
+
+    let left: usize = rdata_length as usize - (decoder.index() - start_idx);
+    let digest = try!(decoder.read_vec(left));
+
+    Ok(ZONEMD::new(serial, scheme, hash_algorithm, digest))
+}
+
+/// Write the RData from the given Decoder
+pub fn emit(encoder: &mut BinEncoder, rdata: &ZONEMD) -> ProtoResult<()> {
+    try!(encoder.emit_u32(rdata.serial()));
+    try!(encoder.emit(rdata.scheme()));
+    try!(encoder.emit(rdata.hash_algorithm()));
+    try!(encoder.emit_vec(rdata.digest()));
+
+    Ok(())
+}
+
+#[test]
+pub fn test() {
+    let rdata = ZONEMD::new(2017092201, SCHEME_SIMPLE, HASH_SHA384, vec![5, 6, 7, 8]);
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    println!("bytes: {:?}", bytes);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder, bytes.len() as u16);
+    assert!(
+        read_rdata.is_ok(),
+        format!("error decoding: {:?}", read_rdata.unwrap_err())
+    );
+    assert_eq!(rdata, read_rdata.unwrap());
+}
+
+#[test]
+#[cfg(any(feature = "openssl", feature = "ring"))]
+pub fn test_covers() {
+    use std::net::Ipv4Addr;
+    use rr::{DNSClass, RData, RecordType};
+
+    let origin = Name::parse("example.com.", None).unwrap();
+    let mut records = vec![
+        Record::new()
+            .set_name(Name::parse("www.example.com.", None).unwrap())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 34)))
+            .clone(),
+    ];
+
+    let hash = digest(&origin, &records, DigestType::SHA384).unwrap();
+    let zonemd = ZONEMD::new(1, SCHEME_SIMPLE, HASH_SHA384, hash.as_ref().to_vec());
+
+    // the ZONEMD itself is excluded from the digest
+    records.push(
+        Record::new()
+            .set_name(origin.clone())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::ZONEMD)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::ZONEMD(zonemd.clone()))
+            .clone(),
+    );
+    assert!(zonemd.covers(&origin, &records).unwrap());
+
+    records[0].set_rdata(RData::A(Ipv4Addr::new(10, 0, 0, 1)));
+    assert!(!zonemd.covers(&origin, &records).unwrap());
+}
@@ -26,7 +26,9 @@ use serialize::binary::*;
 use super::domain::Name;
 use super::record_type::RecordType;
 use super::rdata;
-use super::rdata::{DNSKEY, DS, KEY, MX, NSEC, NSEC3, NSEC3PARAM, NULL, OPT, SIG, SOA, SRV, TXT};
+use super::rdata::{CDNSKEY, CDS, CSYNC, DNSKEY, DS, HINFO, KEY, MX, NAPTR, NSEC, NSEC3, NSEC3PARAM,
+                   NULL, OPENPGPKEY, OPT, SIG, SOA, SRV, SSHFP, SVCB, TLSA, TSIG, TXT, URI,
+                   ZONEMD};
 
 /// Record data enum variants
 ///
@@ -84,6 +86,26 @@ pub enum RData {
     /// ```
     AAAA(Ipv6Addr),
 
+    /// ```text
+    /// RFC 7344              Delegation Trust Maintenance        September 2014
+    ///
+    /// 3.2.  CDNSKEY Resource Record Format
+    ///
+    ///    The wire and presentation format of the CDNSKEY ("Child DNSKEY")
+    ///    resource record is identical to the DNSKEY record.
+    /// ```
+    CDNSKEY(CDNSKEY),
+
+    /// ```text
+    /// RFC 7344              Delegation Trust Maintenance        September 2014
+    ///
+    /// 3.1.  CDS Resource Record Format
+    ///
+    ///    The wire and presentation format of the Child DS (CDS) resource
+    ///    record is identical to the DS record [RFC4034].
+    /// ```
+    CDS(CDS),
+
     /// ```text
     ///   3.3. Standard RRs
     ///
@@ -117,6 +139,12 @@ pub enum RData {
     /// ```
     CNAME(Name),
 
+    /// [RFC 7477, Child-to-Parent Synchronization in DNS, March 2015](https://tools.ietf.org/html/rfc7477)
+    ///
+    /// The types of the records at the apex of the child zone, such as NS and glue, which the
+    ///  parent should copy into the delegation.
+    CSYNC(CSYNC),
+
     /// ```text
     /// RFC 4034                DNSSEC Resource Records               March 2005
     ///
@@ -228,6 +256,27 @@ pub enum RData {
     /// ```
     DS(DS),
 
+    /// ```text
+    /// 3.3.2. HINFO RDATA format
+    ///
+    ///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///     /                      CPU                      /
+    ///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ///     /                       OS                      /
+    ///     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    /// ```
+    HINFO(HINFO),
+
+    /// ```text
+    /// RFC 9460                SVCB and HTTPS RRs for DNS         November 2023
+    ///
+    /// 9.  Using Service Bindings with HTTP
+    ///
+    ///    The HTTPS RR is a special version of SVCB that is dedicated to the
+    ///    "https" and "http" schemes.
+    /// ```
+    HTTPS(SVCB),
+
     /// ```text
     /// RFC 2535                DNS Security Extensions               March 1999
     ///
@@ -288,6 +337,16 @@ pub enum RData {
     /// ```
     MX(MX),
 
+    /// ```text
+    /// RFC 3403        DDDS Part Three: The DNS Database       October 2002
+    ///
+    /// 4.1 Packet Format
+    ///
+    ///   The packet format of the NAPTR RR is given below.  The DNS type code
+    ///   for NAPTR is 35.
+    /// ```
+    NAPTR(NAPTR),
+
     /// ```text
     /// 3.3.10. NULL RDATA format (EXPERIMENTAL)
     ///
@@ -476,6 +535,17 @@ pub enum RData {
     /// ```
     NSEC3PARAM(NSEC3PARAM),
 
+    /// ```text
+    /// RFC 7929                   DANE for OpenPGP                  August 2016
+    ///
+    /// 2.1.  The OPENPGPKEY RDATA Component
+    ///
+    ///    The RDATA portion of an OPENPGPKEY resource record contains a single
+    ///    value consisting of a Transferable Public Key formatted as specified
+    ///    in [RFC4880].
+    /// ```
+    OPENPGPKEY(OPENPGPKEY),
+
     /// ```text
     /// RFC 6891                   EDNS(0) Extensions                 April 2013
     /// 6.1.2.  Wire Format
@@ -562,6 +632,16 @@ pub enum RData {
     /// ```
     SIG(SIG),
 
+    /// ```text
+    /// RFC 8162              SMIMEA for DNS-Based S/MIME               May 2017
+    ///
+    /// 2.  The SMIMEA Resource Record
+    ///
+    ///    The SMIMEA wire format and presentation format are the same as for
+    ///    the TLSA record as described in Section 2.1 of [RFC6698].
+    /// ```
+    SMIMEA(TLSA),
+
     /// ```text
     /// 3.3.13. SOA RDATA format
     ///
@@ -639,6 +719,47 @@ pub enum RData {
     /// ```
     SRV(SRV),
 
+    /// ```text
+    /// RFC 4255              SSHFP Resource Record                January 2006
+    ///
+    /// 3.  The SSHFP Resource Record
+    ///
+    ///    The SSHFP resource record (RR) is used to store a fingerprint of an
+    ///    SSH public host key that is associated with a Domain Name System
+    ///    (DNS) name.
+    /// ```
+    SSHFP(SSHFP),
+
+    /// ```text
+    /// RFC 9460                SVCB and HTTPS RRs for DNS         November 2023
+    ///
+    /// 2.  The SVCB Record Type
+    ///
+    ///    The SVCB DNS RR type (RR type 64) is used to locate alternative
+    ///    endpoints for a service.
+    /// ```
+    SVCB(SVCB),
+
+    /// ```text
+    /// RFC 6698            DNS-Based Authentication for TLS         August 2012
+    ///
+    /// The TLSA DNS resource record (RR) is used to associate a TLS server
+    /// certificate or public key with the domain name where the record is
+    /// found, thus forming a "TLSA certificate association".
+    /// ```
+    TLSA(TLSA),
+
+    /// ```text
+    /// RFC 8945        Secret Key Transaction Authentication      November 2020
+    ///
+    /// 4.  TSIG Record Format
+    ///
+    ///    The TSIG RR has the same format as other records in the DNS.  This
+    ///    section describes the specific treatment of the fields when the
+    ///    record is used to authenticate a message.
+    /// ```
+    TSIG(TSIG),
+
     /// ```text
     /// 3.3.14. TXT RDATA format
     ///
@@ -654,13 +775,53 @@ pub enum RData {
     /// depends on the domain where it is found.
     /// ```
     TXT(TXT),
+
+    /// ```text
+    /// RFC 7553                    URI DNS RR                        June 2015
+    ///
+    /// 4.5.  URI RDATA Wire Format
+    ///
+    ///    The RDATA for a URI RR consists of a 2-octet Priority field, a
+    ///    2-octet Weight field, and a variable-length Target field.
+    /// ```
+    URI(URI),
+
+    /// ```text
+    /// RFC 8976                   DNS Zone Digest                 February 2021
+    ///
+    /// The ZONEMD RR provides a cryptographic message digest over DNS zone data
+    /// at rest.
+    /// ```
+    ZONEMD(ZONEMD),
+
+    /// ```text
+    /// RFC 3597        Handling of Unknown DNS RR Types      September 2003
+    ///
+    /// 2.  Definition
+    ///
+    ///    An "RR of unknown type" is an RR whose RDATA format is not known to
+    ///    the DNS implementation at hand, and whose type is not an assigned
+    ///    QTYPE or Meta-TYPE as specified in [RFC2929] (section 3.1) nor
+    ///    within the range reserved in that section for assignment only to
+    ///    QTYPEs and Meta-TYPEs.
+    /// ```
+    ///
+    /// The RDATA is kept as it was received, and is emitted unchanged
+    Unknown {
+        /// The value of the type of the record
+        code: u16,
+        /// The RDATA of the record
+        rdata: Vec<u8>,
+    },
 }
 
 impl RData {
-    fn to_bytes(&self) -> Vec<u8> {
+    /// Returns the canonical form of the RDATA, RFC 4034 section 6.2, see `emit`
+    fn to_canonical_bytes(&self) -> Vec<u8> {
         let mut buf: Vec<u8> = Vec::new();
         {
             let mut encoder: BinEncoder = BinEncoder::new(&mut buf);
+            encoder.set_canonical_names(true);
             self.emit(&mut encoder).unwrap_or_else(|_| {
                 warn!("could not encode RDATA: {:?}", self);
                 ()
@@ -669,14 +830,75 @@ impl RData {
         buf
     }
 
+    /// Compares the canonical forms of the RDATA, the order of the records within an RRset,
+    ///  [RFC 4034](https://tools.ietf.org/html/rfc4034#section-6.3)
+    ///
+    /// The names in the RDATA of the types listed in RFC 4034 section 6.2, e.g. NS, MX or SOA, are
+    ///  compared ignoring their case, those of other types, e.g. NSEC, are compared as they are.
+    ///  Whereas `==` compares names ignoring case whatever the type, but other data, e.g. of a TXT,
+    ///  as it was decoded.
+    pub fn canonical_cmp(&self, other: &Self) -> Ordering {
+        self.to_canonical_bytes().cmp(&other.to_canonical_bytes())
+    }
+
+    /// Returns true if the canonical forms of the RDATA are the same, see `canonical_cmp`
+    pub fn canonical_eq(&self, other: &Self) -> bool {
+        self.canonical_cmp(other) == Ordering::Equal
+    }
+
     /// Read the RData from the given Decoder
+    ///
+    /// In `DecodeMode::Lenient` rdata which can not be decoded, or whose length does not match,
+    ///  is kept as `RData::Unknown`, with a warning.
     pub fn read(
         decoder: &mut BinDecoder,
         record_type: RecordType,
         rdata_length: u16,
     ) -> ProtoResult<Self> {
         let start_idx = decoder.index();
+        let result = Self::read_data(decoder, record_type, rdata_length);
+
+        // we should have read rdata_length, but we did not
+        let read = decoder.index() - start_idx;
+        let error = match result {
+            Ok(rdata) => {
+                if read == rdata_length as usize {
+                    return Ok(rdata);
+                }
+                ProtoError::from(
+                    ProtoErrorKind::IncorrectRDataLengthRead(read, rdata_length as usize),
+                )
+            }
+            Err(e) => e,
+        };
 
+        // the OPT is turned into the Edns of the message, so it must be decoded
+        if decoder.mode() != DecodeMode::Lenient || record_type == RecordType::OPT {
+            return Err(error);
+        }
+
+        let rdata = match decoder.peek_slice(start_idx, rdata_length as usize) {
+            Some(rdata) => rdata.to_vec(),
+            None => return Err(error),
+        };
+        decoder.warn(
+            "rdata".to_string(),
+            start_idx,
+            format!("{} rdata kept as unknown: {}", record_type, error),
+        );
+        decoder.set_index(start_idx + rdata_length as usize);
+
+        Ok(RData::Unknown {
+            code: record_type.into(),
+            rdata: rdata,
+        })
+    }
+
+    fn read_data(
+        decoder: &mut BinDecoder,
+        record_type: RecordType,
+        rdata_length: u16,
+    ) -> ProtoResult<Self> {
         let result = match record_type {
             RecordType::A => {
                 debug!("reading A");
@@ -692,10 +914,22 @@ impl RData {
             rt @ RecordType::AXFR => {
                 return Err(ProtoErrorKind::UnknownRecordTypeValue(rt.into()).into())
             }
+            RecordType::CDNSKEY => {
+                debug!("reading CDNSKEY");
+                RData::CDNSKEY(try!(rdata::cdnskey::read(decoder, rdata_length)))
+            }
+            RecordType::CDS => {
+                debug!("reading CDS");
+                RData::CDS(try!(rdata::cds::read(decoder, rdata_length)))
+            }
             RecordType::CNAME => {
                 debug!("reading CNAME");
                 RData::CNAME(try!(rdata::name::read(decoder)))
             }
+            RecordType::CSYNC => {
+                debug!("reading CSYNC");
+                RData::CSYNC(try!(rdata::csync::read(decoder, rdata_length)))
+            }
             RecordType::DNSKEY => {
                 debug!("reading DNSKEY");
                 RData::DNSKEY(try!(rdata::dnskey::read(decoder, rdata_length)))
@@ -704,6 +938,14 @@ impl RData {
                 debug!("reading DS");
                 RData::DS(try!(rdata::ds::read(decoder, rdata_length)))
             }
+            RecordType::HINFO => {
+                debug!("reading HINFO");
+                RData::HINFO(try!(rdata::hinfo::read(decoder)))
+            }
+            RecordType::HTTPS => {
+                debug!("reading HTTPS");
+                RData::HTTPS(try!(rdata::svcb::read(decoder, rdata_length)))
+            }
             rt @ RecordType::IXFR => {
                 return Err(ProtoErrorKind::UnknownRecordTypeValue(rt.into()).into())
             }
@@ -715,6 +957,10 @@ impl RData {
                 debug!("reading MX");
                 RData::MX(try!(rdata::mx::read(decoder)))
             }
+            RecordType::NAPTR => {
+                debug!("reading NAPTR");
+                RData::NAPTR(try!(rdata::naptr::read(decoder)))
+            }
             RecordType::NULL => {
                 debug!("reading NULL");
                 RData::NULL(try!(rdata::null::read(decoder, rdata_length)))
@@ -735,6 +981,10 @@ impl RData {
                 debug!("reading NSEC3PARAM");
                 RData::NSEC3PARAM(try!(rdata::nsec3param::read(decoder)))
             }
+            RecordType::OPENPGPKEY => {
+                debug!("reading OPENPGPKEY");
+                RData::OPENPGPKEY(try!(rdata::openpgpkey::read(decoder, rdata_length)))
+            }
             RecordType::OPT => {
                 debug!("reading OPT");
                 RData::OPT(try!(rdata::opt::read(decoder, rdata_length)))
@@ -751,6 +1001,10 @@ impl RData {
                 debug!("reading SIG");
                 RData::SIG(try!(rdata::sig::read(decoder, rdata_length)))
             }
+            RecordType::SMIMEA => {
+                debug!("reading SMIMEA");
+                RData::SMIMEA(try!(rdata::tlsa::read(decoder, rdata_length)))
+            }
             RecordType::SOA => {
                 debug!("reading SOA");
                 RData::SOA(try!(rdata::soa::read(decoder)))
@@ -759,19 +1013,43 @@ impl RData {
                 debug!("reading SRV");
                 RData::SRV(try!(rdata::srv::read(decoder)))
             }
+            RecordType::SSHFP => {
+                debug!("reading SSHFP");
+                RData::SSHFP(try!(rdata::sshfp::read(decoder, rdata_length)))
+            }
+            RecordType::SVCB => {
+                debug!("reading SVCB");
+                RData::SVCB(try!(rdata::svcb::read(decoder, rdata_length)))
+            }
+            RecordType::TLSA => {
+                debug!("reading TLSA");
+                RData::TLSA(try!(rdata::tlsa::read(decoder, rdata_length)))
+            }
+            RecordType::TSIG => {
+                debug!("reading TSIG");
+                RData::TSIG(try!(rdata::tsig::read(decoder)))
+            }
             RecordType::TXT => {
                 debug!("reading TXT");
                 RData::TXT(try!(rdata::txt::read(decoder, rdata_length)))
             }
+            RecordType::URI => {
+                debug!("reading URI");
+                RData::URI(try!(rdata::uri::read(decoder, rdata_length)))
+            }
+            RecordType::ZONEMD => {
+                debug!("reading ZONEMD");
+                RData::ZONEMD(try!(rdata::zonemd::read(decoder, rdata_length)))
+            }
+            RecordType::Unknown(code) => {
+                debug!("reading Unknown {}", code);
+                RData::Unknown {
+                    code: code,
+                    rdata: try!(decoder.read_vec(rdata_length as usize)),
+                }
+            }
         };
 
-        // we should have read rdata_length, but we did not
-        let read = decoder.index() - start_idx;
-        if read != rdata_length as usize {
-            return Err(
-                ProtoErrorKind::IncorrectRDataLengthRead(read, rdata_length as usize).into(),
-            );
-        }
         Ok(result)
     }
 
@@ -795,29 +1073,44 @@ impl RData {
         match *self {
             RData::A(ref address) => rdata::a::emit(encoder, address),
             RData::AAAA(ref address) => rdata::aaaa::emit(encoder, address),
+            RData::CDNSKEY(ref cdnskey) => rdata::cdnskey::emit(encoder, cdnskey),
+            RData::CDS(ref cds) => rdata::cds::emit(encoder, cds),
             // to_lowercase for rfc4034 and rfc6840
             RData::CNAME(ref name) => rdata::name::emit(encoder, name),
+            RData::CSYNC(ref csync) => rdata::csync::emit(encoder, csync),
             RData::DS(ref ds) => rdata::ds::emit(encoder, ds),
+            RData::HINFO(ref hinfo) => rdata::hinfo::emit(encoder, hinfo),
+            RData::HTTPS(ref svcb) => rdata::svcb::emit(encoder, svcb),
             RData::KEY(ref key) => rdata::key::emit(encoder, key),
             RData::DNSKEY(ref dnskey) => rdata::dnskey::emit(encoder, dnskey),
             // to_lowercase for rfc4034 and rfc6840
             RData::MX(ref mx) => rdata::mx::emit(encoder, mx),
+            RData::NAPTR(ref naptr) => rdata::naptr::emit(encoder, naptr),
             RData::NULL(ref null) => rdata::null::emit(encoder, null),
             // to_lowercase for rfc4034 and rfc6840
             RData::NS(ref name) => rdata::name::emit(encoder, name),
             RData::NSEC(ref nsec) => rdata::nsec::emit(encoder, nsec),
             RData::NSEC3(ref nsec3) => rdata::nsec3::emit(encoder, nsec3),
             RData::NSEC3PARAM(ref nsec3param) => rdata::nsec3param::emit(encoder, nsec3param),
+            RData::OPENPGPKEY(ref openpgpkey) => rdata::openpgpkey::emit(encoder, openpgpkey),
             RData::OPT(ref opt) => rdata::opt::emit(encoder, opt),
             // to_lowercase for rfc4034 and rfc6840
             RData::PTR(ref name) => rdata::name::emit(encoder, name),
             // to_lowercase for rfc4034 and rfc6840
             RData::SIG(ref sig) => rdata::sig::emit(encoder, sig),
+            RData::SMIMEA(ref smimea) => rdata::tlsa::emit(encoder, smimea),
             // to_lowercase for rfc4034 and rfc6840
             RData::SOA(ref soa) => rdata::soa::emit(encoder, soa),
             // to_lowercase for rfc4034 and rfc6840
             RData::SRV(ref srv) => rdata::srv::emit(encoder, srv),
+            RData::SSHFP(ref sshfp) => rdata::sshfp::emit(encoder, sshfp),
+            RData::SVCB(ref svcb) => rdata::svcb::emit(encoder, svcb),
+            RData::TLSA(ref tlsa) => rdata::tlsa::emit(encoder, tlsa),
+            RData::TSIG(ref tsig) => rdata::tsig::emit(encoder, tsig),
             RData::TXT(ref txt) => rdata::txt::emit(encoder, txt),
+            RData::URI(ref uri) => rdata::uri::emit(encoder, uri),
+            RData::ZONEMD(ref zonemd) => rdata::zonemd::emit(encoder, zonemd),
+            RData::Unknown { ref rdata, .. } => encoder.emit_vec(rdata),
         }
     }
 
@@ -826,22 +1119,37 @@ impl RData {
         match *self {
             RData::A(..) => RecordType::A,
             RData::AAAA(..) => RecordType::AAAA,
+            RData::CDNSKEY(..) => RecordType::CDNSKEY,
+            RData::CDS(..) => RecordType::CDS,
             RData::CNAME(..) => RecordType::CNAME,
+            RData::CSYNC(..) => RecordType::CSYNC,
             RData::DS(..) => RecordType::DS,
+            RData::HINFO(..) => RecordType::HINFO,
+            RData::HTTPS(..) => RecordType::HTTPS,
             RData::KEY(..) => RecordType::KEY,
             RData::DNSKEY(..) => RecordType::DNSKEY,
             RData::MX(..) => RecordType::MX,
+            RData::NAPTR(..) => RecordType::NAPTR,
             RData::NS(..) => RecordType::NS,
             RData::NSEC(..) => RecordType::NSEC,
             RData::NSEC3(..) => RecordType::NSEC3,
             RData::NSEC3PARAM(..) => RecordType::NSEC3PARAM,
             RData::NULL(..) => RecordType::NULL,
+            RData::OPENPGPKEY(..) => RecordType::OPENPGPKEY,
             RData::OPT(..) => RecordType::OPT,
             RData::PTR(..) => RecordType::PTR,
             RData::SIG(..) => RecordType::SIG,
+            RData::SMIMEA(..) => RecordType::SMIMEA,
             RData::SOA(..) => RecordType::SOA,
             RData::SRV(..) => RecordType::SRV,
+            RData::SSHFP(..) => RecordType::SSHFP,
+            RData::SVCB(..) => RecordType::SVCB,
+            RData::TLSA(..) => RecordType::TLSA,
+            RData::TSIG(..) => RecordType::TSIG,
             RData::TXT(..) => RecordType::TXT,
+            RData::URI(..) => RecordType::URI,
+            RData::ZONEMD(..) => RecordType::ZONEMD,
+            RData::Unknown { code, .. } => RecordType::Unknown(code),
         }
     }
 
@@ -863,22 +1171,37 @@ impl<'a> From<&'a RData> for RecordType {
         match *rdata {
             RData::A(..) => RecordType::A,
             RData::AAAA(..) => RecordType::AAAA,
+            RData::CDNSKEY(..) => RecordType::CDNSKEY,
+            RData::CDS(..) => RecordType::CDS,
             RData::CNAME(..) => RecordType::CNAME,
+            RData::CSYNC(..) => RecordType::CSYNC,
             RData::DS(..) => RecordType::DS,
+            RData::HINFO(..) => RecordType::HINFO,
+            RData::HTTPS(..) => RecordType::HTTPS,
             RData::KEY(..) => RecordType::KEY,
             RData::DNSKEY(..) => RecordType::DNSKEY,
             RData::MX(..) => RecordType::MX,
+            RData::NAPTR(..) => RecordType::NAPTR,
             RData::NS(..) => RecordType::NS,
             RData::NSEC(..) => RecordType::NSEC,
             RData::NSEC3(..) => RecordType::NSEC3,
             RData::NSEC3PARAM(..) => RecordType::NSEC3PARAM,
             RData::NULL(..) => RecordType::NULL,
+            RData::OPENPGPKEY(..) => RecordType::OPENPGPKEY,
             RData::OPT(..) => RecordType::OPT,
             RData::PTR(..) => RecordType::PTR,
             RData::SIG(..) => RecordType::SIG,
+            RData::SMIMEA(..) => RecordType::SMIMEA,
             RData::SOA(..) => RecordType::SOA,
             RData::SRV(..) => RecordType::SRV,
+            RData::SSHFP(..) => RecordType::SSHFP,
+            RData::SVCB(..) => RecordType::SVCB,
+            RData::TLSA(..) => RecordType::TLSA,
+            RData::TSIG(..) => RecordType::TSIG,
             RData::TXT(..) => RecordType::TXT,
+            RData::URI(..) => RecordType::URI,
+            RData::ZONEMD(..) => RecordType::ZONEMD,
+            RData::Unknown { code, .. } => RecordType::Unknown(code),
         }
     }
 }
@@ -911,7 +1234,7 @@ impl Ord for RData {
         // TODO: how about we just store the bytes with the decoded data?
         //  the decoded data is useful for queries, the encoded data is needed for transfers, signing
         //  and ordering.
-        self.to_bytes().cmp(&other.to_bytes())
+        self.canonical_cmp(other)
     }
 }
 
@@ -926,7 +1249,7 @@ mod tests {
     use serialize::binary::*;
     use serialize::binary::bin_tests::test_emit_data_set;
     use rr::domain::Name;
-    use rr::rdata::{MX, SOA, SRV, TXT};
+    use rr::rdata::{MX, NSEC, SOA, SRV, TXT};
 
     fn get_data() -> Vec<(RData, Vec<u8>)> {
         vec![
@@ -956,6 +1279,13 @@ mod tests {
                 RData::MX(MX::new(256, Name::from_labels(vec!["n"]))),
                 vec![1, 0, 1, b'n', 0]
             ),
+            (
+                RData::Unknown {
+                    code: 65534,
+                    rdata: vec![10, 0, 0, 1],
+                },
+                vec![10, 0, 0, 1]
+            ),
             (
                 RData::NS(Name::from_labels(vec!["www", "example", "com"])),
                 vec![
@@ -1191,6 +1521,29 @@ mod tests {
         assert_eq!(ordered, unordered);
     }
 
+    #[test]
+    fn test_canonical_cmp() {
+        let lower = Name::from_labels(vec!["mail", "example", "com"]);
+        let upper = Name::from_labels(vec!["Mail", "Example", "COM"]);
+
+        // names in the RDATA of an MX are lowercased in the canonical form
+        let mx = RData::MX(MX::new(10, lower.clone()));
+        assert!(mx.canonical_eq(&RData::MX(MX::new(10, upper.clone()))));
+        assert!(!mx.canonical_eq(&RData::MX(MX::new(20, lower.clone()))));
+        let greater = RData::MX(MX::new(10, Name::from_labels(vec!["Zzzz", "example", "com"])));
+        assert_eq!(mx.canonical_cmp(&greater), Ordering::Less);
+
+        // but not those of an NSEC, RFC 6840 section 5.1
+        let nsec = RData::NSEC(NSEC::new(lower.clone(), vec![RecordType::A]));
+        let nsec_upper = RData::NSEC(NSEC::new(upper.clone(), vec![RecordType::A]));
+        assert_eq!(nsec, nsec_upper);
+        assert!(!nsec.canonical_eq(&nsec_upper));
+
+        // and character strings keep their case
+        let txt = RData::TXT(TXT::new(vec!["text".to_string()]));
+        assert!(!txt.canonical_eq(&RData::TXT(TXT::new(vec!["Text".to_string()]))));
+    }
+
     #[test]
     fn test_read() {
         let mut test_pass = 0;
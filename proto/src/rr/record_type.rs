@@ -41,11 +41,15 @@ pub enum RecordType {
     /// RFC 1035[1]	Authoritative Zone Transfer
     AXFR,
     //  CAA,        //	257	RFC 6844	Certification Authority Authorization
-    //  CDNSKEY,    //	60	RFC 7344	Child DNSKEY
-    //  CDS,        //	59	RFC 7344	Child DS
+    /// RFC 7344	Child DNSKEY
+    CDNSKEY,
+    /// RFC 7344	Child DS
+    CDS,
     //  CERT,       //	37	RFC 4398	Certificate record
     /// RFC 1035[1]	Canonical name record
     CNAME,
+    /// RFC 7477	Child-to-Parent Synchronization
+    CSYNC,
     //  DHCID,      //	49	RFC 4701	DHCP identifier
     //  DLV,        //	32769	RFC 4431	DNSSEC Lookaside Validation record
     //  DNAME,      //	39	RFC 2672	Delegation Name
@@ -53,7 +57,11 @@ pub enum RecordType {
     DNSKEY,
     /// RFC 4034	Delegation signer: RSASHA256 and RSASHA512, RFC5702
     DS,
+    /// RFC 1035[1]	Host information
+    HINFO,
     //  HIP,        //	55	RFC 5205	Host Identity Protocol
+    /// RFC 9460	HTTPS service binding
+    HTTPS,
     //  IPSECKEY,   //	45	RFC 4025	IPsec Key
     /// RFC 1996	Incremental Zone Transfer
     IXFR,
@@ -63,7 +71,8 @@ pub enum RecordType {
     //  LOC,        //	29	RFC 1876	Location record
     /// RFC 1035[1]	Mail exchange record
     MX,
-    //  NAPTR,      //	35	RFC 3403	Naming Authority Pointer
+    /// RFC 3403	Naming Authority Pointer
+    NAPTR,
     /// RFC 1035[1]	Name server record
     NS,
     /// RFC 1035[1]	Null server record, for testing
@@ -74,6 +83,8 @@ pub enum RecordType {
     NSEC3,
     /// RFC 5155	NSEC3 parameters
     NSEC3PARAM,
+    /// RFC 7929	OpenPGP public key
+    OPENPGPKEY,
     /// RFC 6891	Option
     OPT,
     /// RFC 1035[1]	Pointer record
@@ -85,15 +96,28 @@ pub enum RecordType {
     SIG,
     /// RFC 1035[1] and RFC 2308[9]	Start of [a zone of] authority record
     SOA,
+    /// RFC 8162	S/MIME certificate association
+    SMIMEA,
     /// RFC 2782	Service locator
     SRV,
-    //  SSHFP,      //	44	RFC 4255	SSH Public Key Fingerprint
+    /// RFC 4255	SSH Public Key Fingerprint
+    SSHFP,
+    /// RFC 9460	Service binding
+    SVCB,
     //  TA,         //	32768	N/A	DNSSEC Trust Authorities
     //  TKEY,       //	249	RFC 2930	Secret key record
-    //  TLSA,       //	52	RFC 6698	TLSA certificate association
-    //  TSIG,       //	250	RFC 2845	Transaction Signature
+    /// RFC 6698	TLSA certificate association
+    TLSA,
+    /// RFC 8945	Transaction Signature
+    TSIG,
     /// RFC 1035[1]	Text record
     TXT,
+    /// RFC 7553	Uniform Resource Identifier
+    URI,
+    /// RFC 8976	Message Digest for DNS Zones
+    ZONEMD,
+    /// RFC 3597	A type which is not known, by its value, e.g. `TYPE65534`
+    Unknown(u16),
 }
 
 impl RecordType {
@@ -109,17 +133,46 @@ impl RecordType {
         match str {
             "A" => Ok(RecordType::A),
             "AAAA" => Ok(RecordType::AAAA),
+            "CDNSKEY" => Ok(RecordType::CDNSKEY),
+            "CDS" => Ok(RecordType::CDS),
             "CNAME" => Ok(RecordType::CNAME),
+            "HINFO" => Ok(RecordType::HINFO),
+            "HTTPS" => Ok(RecordType::HTTPS),
+            "CSYNC" => Ok(RecordType::CSYNC),
             "NULL" => Ok(RecordType::NULL),
+            "OPENPGPKEY" => Ok(RecordType::OPENPGPKEY),
             "MX" => Ok(RecordType::MX),
+            "NAPTR" => Ok(RecordType::NAPTR),
             "NS" => Ok(RecordType::NS),
             "PTR" => Ok(RecordType::PTR),
+            "SMIMEA" => Ok(RecordType::SMIMEA),
             "SOA" => Ok(RecordType::SOA),
             "SRV" => Ok(RecordType::SRV),
+            "SSHFP" => Ok(RecordType::SSHFP),
+            "SVCB" => Ok(RecordType::SVCB),
+            "TLSA" => Ok(RecordType::TLSA),
             "TXT" => Ok(RecordType::TXT),
+            "URI" => Ok(RecordType::URI),
             "ANY" | "*" => Ok(RecordType::ANY),
             "AXFR" => Ok(RecordType::AXFR),
-            _ => Err(ProtoErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
+            _ => Self::from_generic_str(str),
+        }
+    }
+
+    /// The generic form of a type, `TYPE` followed by its value, RFC 3597 section 5
+    ///
+    /// A known type is returned as itself, e.g. `TYPE1` is `A`.
+    fn from_generic_str(str: &str) -> ProtoResult<Self> {
+        let upper = str.to_uppercase();
+        let value = if upper.starts_with("TYPE") {
+            upper[4..].parse::<u16>().ok()
+        } else {
+            None
+        };
+
+        match value {
+            Some(value) => Self::from_u16(value),
+            None => Err(ProtoErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
         }
     }
 
@@ -137,25 +190,38 @@ impl RecordType {
             28 => Ok(RecordType::AAAA),
             255 => Ok(RecordType::ANY),
             252 => Ok(RecordType::AXFR),
+            60 => Ok(RecordType::CDNSKEY),
+            59 => Ok(RecordType::CDS),
             5 => Ok(RecordType::CNAME),
+            62 => Ok(RecordType::CSYNC),
             48 => Ok(RecordType::DNSKEY),
             43 => Ok(RecordType::DS),
+            13 => Ok(RecordType::HINFO),
             25 => Ok(RecordType::KEY),
             15 => Ok(RecordType::MX),
+            35 => Ok(RecordType::NAPTR),
             2 => Ok(RecordType::NS),
             47 => Ok(RecordType::NSEC),
             50 => Ok(RecordType::NSEC3),
             51 => Ok(RecordType::NSEC3PARAM),
             0 => Ok(RecordType::NULL),
             41 => Ok(RecordType::OPT),
+            61 => Ok(RecordType::OPENPGPKEY),
             12 => Ok(RecordType::PTR),
             46 => Ok(RecordType::RRSIG),
             24 => Ok(RecordType::SIG),
             6 => Ok(RecordType::SOA),
             33 => Ok(RecordType::SRV),
+            44 => Ok(RecordType::SSHFP),
+            52 => Ok(RecordType::TLSA),
+            250 => Ok(RecordType::TSIG),
+            53 => Ok(RecordType::SMIMEA),
             16 => Ok(RecordType::TXT),
-            // TODO: this should probably return a generic value wrapper.
-            _ => Err(ProtoErrorKind::UnknownRecordTypeValue(value).into()),
+            63 => Ok(RecordType::ZONEMD),
+            256 => Ok(RecordType::URI),
+            64 => Ok(RecordType::SVCB),
+            65 => Ok(RecordType::HTTPS),
+            _ => Ok(RecordType::Unknown(value)),
         }
     }
 }
@@ -193,24 +259,39 @@ impl From<RecordType> for &'static str {
             RecordType::AAAA => "AAAA",
             RecordType::ANY => "ANY",
             RecordType::AXFR => "AXFR",
+            RecordType::CDNSKEY => "CDNSKEY",
+            RecordType::CDS => "CDS",
             RecordType::CNAME => "CNAME",
+            RecordType::CSYNC => "CSYNC",
             RecordType::DNSKEY => "DNSKEY",
             RecordType::DS => "DS",
+            RecordType::HINFO => "HINFO",
+            RecordType::HTTPS => "HTTPS",
             RecordType::IXFR => "IXFR",
             RecordType::KEY => "KEY",
             RecordType::MX => "MX",
+            RecordType::NAPTR => "NAPTR",
             RecordType::NULL => "NULL",
             RecordType::NS => "NS",
             RecordType::NSEC => "NSEC",
             RecordType::NSEC3 => "NSEC3",
             RecordType::NSEC3PARAM => "NSEC3PARAM",
+            RecordType::OPENPGPKEY => "OPENPGPKEY",
             RecordType::OPT => "OPT",
             RecordType::PTR => "PTR",
             RecordType::RRSIG => "RRSIG",
             RecordType::SIG => "SIG",
+            RecordType::SMIMEA => "SMIMEA",
             RecordType::SOA => "SOA",
             RecordType::SRV => "SRV",
+            RecordType::SSHFP => "SSHFP",
+            RecordType::SVCB => "SVCB",
+            RecordType::TLSA => "TLSA",
+            RecordType::TSIG => "TSIG",
             RecordType::TXT => "TXT",
+            RecordType::URI => "URI",
+            RecordType::ZONEMD => "ZONEMD",
+            RecordType::Unknown(..) => "Unknown",
         }
     }
 }
@@ -231,24 +312,39 @@ impl From<RecordType> for u16 {
             RecordType::AAAA => 28,
             RecordType::ANY => 255,
             RecordType::AXFR => 252,
+            RecordType::CDNSKEY => 60,
+            RecordType::CDS => 59,
             RecordType::CNAME => 5,
+            RecordType::CSYNC => 62,
             RecordType::KEY => 25,
             RecordType::DNSKEY => 48,
             RecordType::DS => 43,
+            RecordType::HINFO => 13,
+            RecordType::HTTPS => 65,
             RecordType::IXFR => 251,
             RecordType::MX => 15,
+            RecordType::NAPTR => 35,
             RecordType::NS => 2,
             RecordType::NULL => 0,
             RecordType::NSEC => 47,
             RecordType::NSEC3 => 50,
             RecordType::NSEC3PARAM => 51,
+            RecordType::OPENPGPKEY => 61,
             RecordType::OPT => 41,
             RecordType::PTR => 12,
             RecordType::RRSIG => 46,
             RecordType::SIG => 24,
+            RecordType::SMIMEA => 53,
             RecordType::SOA => 6,
             RecordType::SRV => 33,
+            RecordType::SSHFP => 44,
+            RecordType::SVCB => 64,
+            RecordType::TLSA => 52,
+            RecordType::TSIG => 250,
             RecordType::TXT => 16,
+            RecordType::URI => 256,
+            RecordType::ZONEMD => 63,
+            RecordType::Unknown(value) => value,
         }
     }
 }
@@ -267,7 +363,10 @@ impl Ord for RecordType {
 
 impl Display for RecordType {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
-        f.write_str(Into::<&str>::into(*self))
+        match *self {
+            RecordType::Unknown(value) => write!(f, "TYPE{}", value),
+            _ => f.write_str(Into::<&str>::into(*self)),
+        }
     }
 }
 
@@ -313,3 +412,17 @@ fn test_order() {
 
     assert_eq!(ordered, unordered);
 }
+
+#[test]
+fn test_unknown() {
+    assert_eq!(RecordType::from_u16(65534).unwrap(), RecordType::Unknown(65534));
+    assert_eq!(u16::from(RecordType::Unknown(65534)), 65534);
+    assert_eq!(RecordType::Unknown(65534).to_string(), "TYPE65534");
+
+    assert_eq!(RecordType::from_str("TYPE65534").unwrap(), RecordType::Unknown(65534));
+    assert_eq!(RecordType::from_str("type65534").unwrap(), RecordType::Unknown(65534));
+    assert_eq!(RecordType::from_str("TYPE1").unwrap(), RecordType::A);
+    assert!(RecordType::from_str("TYPE").is_err());
+    assert!(RecordType::from_str("TYPE65536").is_err());
+    assert!(RecordType::from_str("TYPEA").is_err());
+}
@@ -28,6 +28,11 @@ use rr::RData;
 use rr::RecordType;
 use rr::RecordSet;
 
+#[cfg(feature = "mdns")]
+/// From [RFC 6762](https://tools.ietf.org/html/rfc6762#section-10.2), the top bit of the class in
+///  a resource record is the cache-flush bit
+const MDNS_CACHE_FLUSH: u16 = 1 << 15;
+
 /// Resource records are storage value in DNS, into which all key/value pair data is stored.
 ///
 /// [RFC 1035](https://tools.ietf.org/html/rfc1035), DOMAIN NAMES - IMPLEMENTATION AND SPECIFICATION, November 1987
@@ -68,6 +73,8 @@ pub struct Record {
     dns_class: DNSClass,
     ttl: u32,
     rdata: RData,
+    #[cfg(feature = "mdns")]
+    mdns_cache_flush: bool,
 }
 
 impl Record {
@@ -83,6 +90,8 @@ impl Record {
             dns_class: DNSClass::IN,
             ttl: 0,
             rdata: RData::NULL(NULL::new()),
+            #[cfg(feature = "mdns")]
+            mdns_cache_flush: false,
         }
     }
 
@@ -100,6 +109,8 @@ impl Record {
             dns_class: DNSClass::IN,
             ttl: ttl,
             rdata: RData::NULL(NULL::new()),
+            #[cfg(feature = "mdns")]
+            mdns_cache_flush: false,
         }
     }
 
@@ -123,6 +134,8 @@ impl Record {
             dns_class: DNSClass::IN,
             ttl: ttl,
             rdata: rdata,
+            #[cfg(feature = "mdns")]
+            mdns_cache_flush: false,
         }
     }
 
@@ -173,6 +186,15 @@ impl Record {
         self
     }
 
+    /// Changes mDNS cache-flush bit, which tells receivers to discard any other records they have
+    ///  cached for this name, type and class
+    /// See [RFC 6762](https://tools.ietf.org/html/rfc6762#section-10.2)
+    #[cfg(feature = "mdns")]
+    pub fn set_mdns_cache_flush(&mut self, flag: bool) -> &mut Self {
+        self.mdns_cache_flush = flag;
+        self
+    }
+
     /// ```text
     /// RDATA           a variable length string of octets that describes the
     ///                 resource.  The format of this information varies
@@ -205,6 +227,13 @@ impl Record {
         self.ttl
     }
 
+    /// Returns if the mDNS cache-flush bit is set or not
+    /// See [RFC 6762](https://tools.ietf.org/html/rfc6762#section-10.2)
+    #[cfg(feature = "mdns")]
+    pub fn mdns_cache_flush(&self) -> bool {
+        self.mdns_cache_flush
+    }
+
     /// Returns the Record Data, i.e. the record information
     pub fn rdata(&self) -> &RData {
         &self.rdata
@@ -219,6 +248,25 @@ impl Record {
     pub fn unwrap_rdata(self) -> RData {
         self.rdata
     }
+
+    /// Compares the canonical forms of the records, RFC 4034 section 6, as needed to sign them and
+    ///  to check the prerequisites of an update
+    ///
+    /// The owner names are ordered as in RFC 4034 section 6.1, ignoring case, then the types and
+    ///  classes, then the RDATA with `RData::canonical_cmp`. Unlike `partial_cmp`, the TTLs are not
+    ///  compared, as they are not by `==`.
+    pub fn canonical_cmp(&self, other: &Self) -> Ordering {
+        self.name_labels
+            .cmp(&other.name_labels)
+            .then(self.rr_type.cmp(&other.rr_type))
+            .then(self.dns_class.cmp(&other.dns_class))
+            .then_with(|| self.rdata.canonical_cmp(&other.rdata))
+    }
+
+    /// Returns true if the canonical forms of the records are the same, see `canonical_cmp`
+    pub fn canonical_eq(&self, other: &Self) -> bool {
+        self.canonical_cmp(other) == Ordering::Equal
+    }
 }
 
 impl IntoRecordSet for Record {
@@ -238,6 +286,9 @@ impl BinSerializable<Record> for Record {
         // TYPE            two octets containing one of the RR TYPE codes.
         let record_type: RecordType = try!(RecordType::read(decoder));
 
+        #[cfg(feature = "mdns")]
+        let mut mdns_cache_flush = false;
+
         // CLASS           two octets containing one of the RR CLASS codes.
         let class: DNSClass = if record_type == RecordType::OPT {
             // verify that the OPT record is Root
@@ -249,7 +300,16 @@ impl BinSerializable<Record> for Record {
             DNSClass::for_opt(try!(decoder.read_u16()))
 
         } else {
-            try!(DNSClass::read(decoder))
+            let value = try!(decoder.read_u16());
+
+            // the top bit of the class is the cache-flush bit in mDNS - RFC 6762
+            #[cfg(feature = "mdns")]
+            let value = {
+                mdns_cache_flush = value & MDNS_CACHE_FLUSH != 0;
+                value & !MDNS_CACHE_FLUSH
+            };
+
+            try!(DNSClass::from_u16(value))
         };
 
         // TTL             a 32 bit signed integer that specifies the time interval
@@ -284,12 +344,24 @@ impl BinSerializable<Record> for Record {
             dns_class: class,
             ttl: ttl,
             rdata: rdata,
+            #[cfg(feature = "mdns")]
+            mdns_cache_flush: mdns_cache_flush,
         })
     }
 
     fn emit(&self, encoder: &mut BinEncoder) -> ProtoResult<()> {
         try!(self.name_labels.emit(encoder));
         try!(self.rr_type.emit(encoder));
+
+        #[cfg(feature = "mdns")]
+        {
+            if self.mdns_cache_flush {
+                try!(encoder.emit_u16(u16::from(self.dns_class) | MDNS_CACHE_FLUSH));
+            } else {
+                try!(self.dns_class.emit(encoder));
+            }
+        }
+        #[cfg(not(feature = "mdns"))]
         try!(self.dns_class.emit(encoder));
         try!(encoder.emit_u32(self.ttl));
 
@@ -302,6 +374,9 @@ impl BinSerializable<Record> for Record {
                 encoder.offset() + 2, /*for u16 len*/
                 EncodeMode::Normal,
             );
+            if encoder.name_compression() != NameCompression::All {
+                tmp_encoder.set_name_compression(NameCompression::Disabled);
+            }
             try!(self.rdata.emit(&mut tmp_encoder));
         }
 
@@ -439,6 +514,33 @@ mod tests {
         assert_eq!(got, record);
     }
 
+    #[cfg(feature = "mdns")]
+    #[test]
+    fn test_emit_and_read_mdns_cache_flush() {
+        let mut record = Record::new();
+        record
+            .set_name(Name::from_str("host.local").unwrap())
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_ttl(120)
+            .set_mdns_cache_flush(true)
+            .set_rdata(RData::A(Ipv4Addr::new(192, 168, 0, 1)));
+
+        let mut vec_bytes: Vec<u8> = Vec::with_capacity(512);
+        {
+            let mut encoder = BinEncoder::new(&mut vec_bytes);
+            record.emit(&mut encoder).unwrap();
+        }
+
+        let mut decoder = BinDecoder::new(&vec_bytes);
+
+        let got = Record::read(&mut decoder).unwrap();
+
+        assert_eq!(got.dns_class(), DNSClass::IN);
+        assert!(got.mdns_cache_flush());
+        assert_eq!(got, record);
+    }
+
     #[test]
     fn test_order() {
         let mut record = Record::new();
@@ -474,4 +576,36 @@ mod tests {
             assert_eq!(r.cmp(g), Ordering::Less);
         }
     }
+
+    #[test]
+    fn test_canonical_cmp() {
+        let mut record = Record::new();
+        record
+            .set_name(Name::from_str("www.example.com").unwrap())
+            .set_rr_type(RecordType::CNAME)
+            .set_dns_class(DNSClass::IN)
+            .set_ttl(5)
+            .set_rdata(RData::CNAME(Name::from_str("host.example.com").unwrap()));
+
+        // the case of the names and the TTL do not matter
+        let mut same = record.clone();
+        same
+            .set_name(Name::from_str("WWW.Example.com").unwrap())
+            .set_ttl(3600)
+            .set_rdata(RData::CNAME(Name::from_str("Host.EXAMPLE.com").unwrap()));
+        assert!(record.canonical_eq(&same));
+        assert_eq!(same.canonical_cmp(&record), Ordering::Equal);
+        assert_eq!(record.partial_cmp(&same), Some(Ordering::Less));
+
+        let mut greater_name = record.clone();
+        greater_name.set_name(Name::from_str("Zzzz.example.com").unwrap());
+
+        let mut greater_rdata = record.clone();
+        greater_rdata.set_rdata(RData::CNAME(Name::from_str("Zzzz.example.com").unwrap()));
+
+        for greater in &[greater_name, greater_rdata] {
+            assert!(!record.canonical_eq(greater));
+            assert_eq!(record.canonical_cmp(greater), Ordering::Less);
+        }
+    }
 }
@@ -13,6 +13,9 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+use std::cmp;
+use std::fmt;
+
 use error::{ProtoErrorKind, ProtoResult};
 
 /// This is non-destructive to the inner buffer, b/c for pointer types we need to perform a reverse
@@ -25,6 +28,8 @@ use error::{ProtoErrorKind, ProtoResult};
 pub struct BinDecoder<'a> {
     buffer: &'a [u8],
     index: usize,
+    mode: DecodeMode,
+    warnings: Vec<DecodeWarning>,
 }
 
 impl<'a> BinDecoder<'a> {
@@ -34,9 +39,52 @@ impl<'a> BinDecoder<'a> {
     ///
     /// * `buffer` - buffer from which all data will be read
     pub fn new(buffer: &'a [u8]) -> Self {
+        Self::with_mode(buffer, DecodeMode::Normal)
+    }
+
+    /// Creates a new BinDecoder which checks the data as strictly as the mode requires
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - buffer from which all data will be read
+    /// * `mode` - see `DecodeMode`
+    pub fn with_mode(buffer: &'a [u8], mode: DecodeMode) -> Self {
         BinDecoder {
             buffer: buffer,
             index: 0,
+            mode: mode,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Returns the current decoding mode
+    pub fn mode(&self) -> DecodeMode {
+        self.mode
+    }
+
+    /// The problems which were passed over while decoding in `DecodeMode::Lenient`
+    pub fn warnings(&self) -> &[DecodeWarning] {
+        &self.warnings
+    }
+
+    /// Takes the problems which were passed over, see `warnings`
+    pub fn take_warnings(&mut self) -> Vec<DecodeWarning> {
+        ::std::mem::replace(&mut self.warnings, Vec::new())
+    }
+
+    /// Records a problem with the field at the offset which was passed over in
+    ///  `DecodeMode::Lenient`
+    pub(crate) fn warn(&mut self, field: String, offset: usize, reason: String) {
+        let warning = DecodeWarning::new(field, offset, reason);
+        debug!("decoding leniently past: {}", warning);
+        self.warnings.push(warning);
+    }
+
+    /// Places the fields of the warnings recorded since `from` within the enclosing field, e.g.
+    ///  `rdata` of the first answer becomes `answer[0].rdata`
+    pub(crate) fn prefix_warnings(&mut self, from: usize, prefix: &str) {
+        for warning in self.warnings.iter_mut().skip(from) {
+            warning.field = format!("{}.{}", prefix, warning.field);
         }
     }
 
@@ -78,9 +126,21 @@ impl<'a> BinDecoder<'a> {
         BinDecoder {
             buffer: self.buffer,
             index: index_at as usize,
+            mode: self.mode,
+            warnings: Vec::new(),
         }
     }
 
+    /// Moves to the index in the buffer, e.g. past data which could not be decoded
+    pub(crate) fn set_index(&mut self, index: usize) {
+        self.index = cmp::min(index, self.buffer.len());
+    }
+
+    /// Returns the bytes of the buffer from the index, without moving the current index forward
+    pub(crate) fn peek_slice(&self, index: usize, len: usize) -> Option<&'a [u8]> {
+        self.buffer.get(index..index + len)
+    }
+
     /// Reads a String from the buffer
     ///
     /// ```text
@@ -187,3 +247,70 @@ impl<'a> BinDecoder<'a> {
         )
     }
 }
+
+/// How strictly a message is checked while it is decoded
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DecodeMode {
+    /// Bytes after the last record are ignored, any other problem is an error, the default
+    Normal,
+    /// Any deviation from the RFCs is an error, including bytes after the last record, e.g. for
+    ///  the requests to a server
+    Strict,
+    /// Decodes as much as possible, e.g. for the captures of diagnostic tools; the problems are
+    ///  collected as warnings, see `Message::decode_warnings`, a record whose rdata can not be decoded
+    ///  is kept as `RData::Unknown`, and the records missing from the sections are left out
+    Lenient,
+}
+
+impl Default for DecodeMode {
+    fn default() -> Self {
+        DecodeMode::Normal
+    }
+}
+
+/// A problem which was passed over while decoding in `DecodeMode::Lenient`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecodeWarning {
+    field: String,
+    offset: usize,
+    reason: String,
+}
+
+impl DecodeWarning {
+    /// Creates a new warning
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - the field which was off, e.g. `answer[0].rdata` or `query[1]`
+    /// * `offset` - the offset of the field in the buffer
+    /// * `reason` - what was wrong with the field
+    pub fn new(field: String, offset: usize, reason: String) -> Self {
+        DecodeWarning {
+            field: field,
+            offset: offset,
+            reason: reason,
+        }
+    }
+
+    /// The field which was off, the section and index of a record followed by the part of it,
+    ///  e.g. `answer[0].rdata`, or `message` for the message as a whole
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// The offset of the field in the buffer which was decoded
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// What was wrong with the field
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}: {}", self.field, self.offset, self.reason)
+    }
+}
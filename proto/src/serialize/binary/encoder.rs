@@ -27,6 +27,7 @@ pub struct BinEncoder<'a> {
     name_pointers: HashMap<Vec<Rc<String>>, u16>, // array of string, label, location in stream
     mode: EncodeMode,
     canonical_names: bool,
+    name_compression: NameCompression,
 }
 
 impl<'a> BinEncoder<'a> {
@@ -60,6 +61,7 @@ impl<'a> BinEncoder<'a> {
             name_pointers: HashMap::new(),
             mode: mode,
             canonical_names: false,
+            name_compression: NameCompression::All,
         }
     }
 
@@ -93,6 +95,22 @@ impl<'a> BinEncoder<'a> {
         self.canonical_names
     }
 
+    /// Sets which names are compressed, see `NameCompression`
+    pub fn set_name_compression(&mut self, name_compression: NameCompression) {
+        self.name_compression = name_compression;
+    }
+
+    /// Returns which names are compressed
+    pub fn name_compression(&self) -> NameCompression {
+        self.name_compression
+    }
+
+    /// Returns true if names are written without pointers, i.e. in canonical form or with
+    ///  compression disabled
+    pub fn is_uncompressed_names(&self) -> bool {
+        self.canonical_names || self.name_compression == NameCompression::Disabled
+    }
+
     /// Reserve specified length in the internal buffer
     pub fn reserve(&mut self, extra: usize) {
         self.buffer.reserve(extra);
@@ -221,3 +239,24 @@ pub enum EncodeMode {
     /// Write records in standard format
     Normal,
 }
+
+/// Which names of a message are compressed with pointers to earlier names, RFC 1035 section 4.1.4
+///
+/// Some middleboxes and embedded resolvers fail to decode pointers, mostly those within the rdata
+///  of records, so compression may be restricted or disabled at the cost of larger messages.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NameCompression {
+    /// All names are compressed, the default
+    All,
+    /// Only the names of the queries and the owner names of records are compressed, the names in
+    ///  rdata, e.g. the target of a CNAME or the exchange of an MX, are written in full
+    OwnerNames,
+    /// No names are compressed
+    Disabled,
+}
+
+impl Default for NameCompression {
+    fn default() -> Self {
+        NameCompression::All
+    }
+}
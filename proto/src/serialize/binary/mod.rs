@@ -20,8 +20,11 @@ mod decoder;
 mod encoder;
 
 pub use self::decoder::BinDecoder;
+pub use self::decoder::DecodeMode;
+pub use self::decoder::DecodeWarning;
 pub use self::encoder::BinEncoder;
 pub use self::encoder::EncodeMode;
+pub use self::encoder::NameCompression;
 
 #[cfg(test)]
 pub mod bin_tests;
@@ -5,25 +5,72 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::cmp;
 use std::io;
+use std::mem;
 use std::net::SocketAddr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use futures::{Async, Future, Poll, Stream};
+use futures::stream::{Fuse, Peekable};
+use futures::sync::mpsc::{unbounded, UnboundedReceiver};
 use tokio_io::{AsyncRead, AsyncWrite};
 use tokio_core::net::TcpStream as TokioTcpStream;
-use tokio_core::reactor::Handle;
+use tokio_core::reactor::{Handle, Timeout};
 
-use BufDnsStreamHandle;
+use {BufDnsStreamHandle, BufStreamHandle};
 use tcp::TcpStream;
 use dns_handle::DnsStreamHandle;
 
+/// The delay before the first reconnect after a connection failed, doubled for each failure
+const MIN_RECONNECT_DELAY_MS: u64 = 500;
+/// The longest delay before a reconnect
+const MAX_RECONNECT_DELAY_S: u64 = 30;
+
+/// Makes a new connection to the name server, along with the sender for its messages
+type Connect<S> = Box<
+    Fn() -> (Box<Future<Item = TcpStream<S>, Error = io::Error>>, BufStreamHandle),
+>;
+
+/// The connection of a TcpClientStream
+enum ConnectionState<S> {
+    /// Connecting, the messages sent meanwhile are buffered by the sender
+    Connecting {
+        stream_future: Box<Future<Item = TcpStream<S>, Error = io::Error>>,
+        sender: BufStreamHandle,
+    },
+    /// Connected, the messages are passed on over the sender, the responses may arrive in any
+    ///  order
+    Connected {
+        tcp_stream: TcpStream<S>,
+        sender: Option<BufStreamHandle>,
+    },
+    /// The connection was closed, the next message is sent over a new connection, after the
+    ///  backoff if the last one failed
+    Disconnected { backoff: Option<Timeout> },
+}
+
 /// Tcp client stream
 ///
 /// Use with `trust_dns::client::DnsFuture` impls
+///
+/// Any number of queries may be outstanding on the connection, the responses are matched to them
+///  by their id in the `DnsFuture`. A connection which is closed, by the name server or after
+///  the idle timeout, is made again for the next query, with a backoff if it failed. A connection
+///  which is lost, closed by the name server or failed, yields an error, as the responses to the
+///  queries sent on it are lost with it, after which the stream goes on over the next connection.
 #[must_use = "futures do nothing unless polled"]
 pub struct TcpClientStream<S> {
-    tcp_stream: TcpStream<S>,
+    state: ConnectionState<S>,
+    // the messages of the handle, which outlive the connections, None for a wrapped stream
+    outbound_messages: Option<Peekable<Fuse<UnboundedReceiver<(Vec<u8>, SocketAddr)>>>>,
+    connect: Option<Connect<S>>,
+    reactor: Option<Handle>,
+    idle_timeout: Option<Duration>,
+    idle_timer: Option<Timeout>,
+    last_activity: Instant,
+    // failed connections since the last response, drives the backoff
+    failures: u32,
 }
 
 impl TcpClientStream<TokioTcpStream> {
@@ -56,21 +103,59 @@ impl TcpClientStream<TokioTcpStream> {
         timeout: Duration,
     ) -> (Box<Future<Item = TcpClientStream<TokioTcpStream>, Error = io::Error>>,
               Box<DnsStreamHandle>) {
-        let (stream_future, sender) = TcpStream::with_timeout(name_server, loop_handle, timeout);
+        Self::with_idle_timeout(name_server, loop_handle, timeout, None)
+    }
 
+    /// Constructs a new TcpStream for a client to the specified SocketAddr, which closes the
+    ///  connection once it is idle.
+    ///
+    /// The returned future fails if the first connection does, later connections are made as
+    ///  they are needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the DNS server to connect to
+    /// * `loop_handle` - reference to the takio_core::Core for future based IO
+    /// * `timeout` - connection timeout
+    /// * `idle_timeout` - the connection is closed after nothing was sent or received for this
+    ///                    long, it should be longer than the timeout of the queries
+    pub fn with_idle_timeout(
+        name_server: SocketAddr,
+        loop_handle: &Handle,
+        timeout: Duration,
+        idle_timeout: Option<Duration>,
+    ) -> (Box<Future<Item = TcpClientStream<TokioTcpStream>, Error = io::Error>>,
+              Box<DnsStreamHandle>) {
+        let (message_sender, outbound_messages) = unbounded();
+        let reactor = loop_handle.clone();
+        let connect_reactor = loop_handle.clone();
+        let connect: Connect<TokioTcpStream> = Box::new(move || {
+            TcpStream::with_timeout(name_server, &connect_reactor, timeout)
+        });
+
+        let (stream_future, sender) = connect();
         let new_future: Box<
             Future<
                 Item = TcpClientStream<TokioTcpStream>,
                 Error = io::Error,
             >,
         > = Box::new(stream_future.map(move |tcp_stream| {
-            TcpClientStream { tcp_stream: tcp_stream }
+            TcpClientStream {
+                state: ConnectionState::Connected {
+                    tcp_stream: tcp_stream,
+                    sender: Some(sender),
+                },
+                outbound_messages: Some(outbound_messages.fuse().peekable()),
+                connect: Some(connect),
+                reactor: Some(reactor),
+                idle_timeout: idle_timeout,
+                idle_timer: None,
+                last_activity: Instant::now(),
+                failures: 0,
+            }
         }));
 
-        let sender = Box::new(BufDnsStreamHandle {
-            name_server: name_server,
-            sender: sender,
-        });
+        let sender = Box::new(BufDnsStreamHandle::new(name_server, message_sender));
 
         (new_future, sender)
     }
@@ -78,8 +163,228 @@ impl TcpClientStream<TokioTcpStream> {
 
 impl<S> TcpClientStream<S> {
     /// Wraps the TcpStream in TcpClientStream
+    ///
+    /// The stream ends when the connection is closed, it is not made again.
     pub fn from_stream(tcp_stream: TcpStream<S>) -> Self {
-        TcpClientStream { tcp_stream: tcp_stream }
+        TcpClientStream {
+            state: ConnectionState::Connected {
+                tcp_stream: tcp_stream,
+                sender: None,
+            },
+            outbound_messages: None,
+            connect: None,
+            reactor: None,
+            idle_timeout: None,
+            idle_timer: None,
+            last_activity: Instant::now(),
+            failures: 0,
+        }
+    }
+}
+
+/// The delay before the next connection after the number of failures
+fn reconnect_delay(failures: u32) -> Duration {
+    let exponent = cmp::min(failures.saturating_sub(1), 16);
+    let delay_ms = MIN_RECONNECT_DELAY_MS * (1 << exponent);
+    cmp::min(
+        Duration::from_millis(delay_ms),
+        Duration::from_secs(MAX_RECONNECT_DELAY_S),
+    )
+}
+
+/// Passes the messages of the handle on to the connection, returns true if there were any
+fn forward(
+    outbound_messages: &mut Option<Peekable<Fuse<UnboundedReceiver<(Vec<u8>, SocketAddr)>>>>,
+    sender: Option<&BufStreamHandle>,
+) -> io::Result<bool> {
+    let (outbound_messages, sender) = match (outbound_messages.as_mut(), sender) {
+        (Some(outbound_messages), Some(sender)) => (outbound_messages, sender),
+        _ => return Ok(false),
+    };
+
+    let mut forwarded = false;
+    while let Async::Ready(Some(message)) = try!(outbound_messages.poll().map_err(|()| {
+        io::Error::new(io::ErrorKind::Other, "unknown")
+    }))
+    {
+        try!(sender.unbounded_send(message).map_err(|e| {
+            io::Error::new(io::ErrorKind::BrokenPipe, format!("{}", e))
+        }));
+        forwarded = true;
+    }
+
+    Ok(forwarded)
+}
+
+/// What `TcpClientStream::poll` returns, if anything, as the connection moves on
+type Ready = Option<Poll<Option<Vec<u8>>, io::Error>>;
+
+/// The error which the stream yields for a lost connection, the responses to the messages sent on
+///  it are lost with it
+fn lost(error: &io::Error) -> io::Error {
+    io::Error::new(error.kind(), format!("tcp connection lost: {}", error))
+}
+
+impl<S: AsyncRead + AsyncWrite> TcpClientStream<S> {
+    /// The state after the connection failed, along with the error yielded for it, or the error
+    ///  if it is not made again
+    fn failed(&mut self, error: io::Error) -> io::Result<(ConnectionState<S>, Ready)> {
+        let reactor = match (self.connect.as_ref(), self.reactor.as_ref()) {
+            (Some(_), Some(reactor)) => reactor,
+            _ => return Err(error),
+        };
+
+        self.failures += 1;
+        let delay = reconnect_delay(self.failures);
+        warn!("tcp connection failed, reconnecting in {:?}: {}", delay, error);
+        let state = ConnectionState::Disconnected {
+            backoff: Some(try!(Timeout::new(delay, reactor))),
+        };
+        Ok((state, Some(Err(lost(&error)))))
+    }
+
+    /// Moves the connection on from the state, returning the next state along with what is to be
+    ///  returned from `poll`, if anything
+    fn advance(&mut self, state: ConnectionState<S>) -> io::Result<(ConnectionState<S>, Ready)> {
+        match state {
+            ConnectionState::Disconnected { backoff } => {
+                if let Some(mut backoff) = backoff {
+                    if let Async::NotReady = try!(backoff.poll()) {
+                        let state = ConnectionState::Disconnected { backoff: Some(backoff) };
+                        return Ok((state, Some(Ok(Async::NotReady))));
+                    }
+                }
+
+                // a new connection is only made when there is something to send
+                let disconnected = ConnectionState::Disconnected { backoff: None };
+                let to_send = match self.outbound_messages {
+                    Some(ref mut outbound_messages) => {
+                        try!(outbound_messages.peek().map_err(|()| {
+                            io::Error::new(io::ErrorKind::Other, "unknown")
+                        })).map(|message| message.is_some())
+                    }
+                    None => Async::Ready(false),
+                };
+                match to_send {
+                    Async::Ready(true) => (),
+                    Async::Ready(false) => return Ok((disconnected, Some(Ok(Async::Ready(None))))),
+                    Async::NotReady => return Ok((disconnected, Some(Ok(Async::NotReady)))),
+                }
+
+                let (stream_future, sender) = match self.connect {
+                    Some(ref connect) => connect(),
+                    None => return Ok((disconnected, Some(Ok(Async::Ready(None))))),
+                };
+                debug!("reconnecting tcp stream");
+                let state = ConnectionState::Connecting {
+                    stream_future: stream_future,
+                    sender: sender,
+                };
+                Ok((state, None))
+            }
+            ConnectionState::Connecting {
+                mut stream_future,
+                sender,
+            } => {
+                try!(forward(&mut self.outbound_messages, Some(&sender)));
+                match stream_future.poll() {
+                    Ok(Async::Ready(tcp_stream)) => {
+                        self.last_activity = Instant::now();
+                        let state = ConnectionState::Connected {
+                            tcp_stream: tcp_stream,
+                            sender: Some(sender),
+                        };
+                        Ok((state, None))
+                    }
+                    Ok(Async::NotReady) => {
+                        let state = ConnectionState::Connecting {
+                            stream_future: stream_future,
+                            sender: sender,
+                        };
+                        Ok((state, Some(Ok(Async::NotReady))))
+                    }
+                    // the messages sent meanwhile are lost
+                    Err(e) => self.failed(e),
+                }
+            }
+            ConnectionState::Connected {
+                mut tcp_stream,
+                sender,
+            } => {
+                if try!(forward(&mut self.outbound_messages, sender.as_ref())) {
+                    self.last_activity = Instant::now();
+                }
+
+                let ready = match tcp_stream.poll() {
+                    Ok(Async::Ready(Some((buffer, src_addr)))) => {
+                        // this is busted if the tcp connection doesn't have a peer
+                        let peer = tcp_stream.peer_addr();
+                        if src_addr != peer {
+                            // FIXME: this should be an error...
+                            warn!("{} does not match name_server: {}", src_addr, peer)
+                        }
+
+                        self.last_activity = Instant::now();
+                        self.failures = 0;
+                        Async::Ready(Some(buffer))
+                    }
+                    Ok(Async::Ready(None)) if self.connect.is_some() => {
+                        // the queries outstanding on the connection are lost, which the
+                        //  `DnsFuture` sends again or fails
+                        let peer = tcp_stream.peer_addr();
+                        debug!("tcp connection closed by {}", peer);
+                        let closed = io::Error::new(
+                            io::ErrorKind::ConnectionAborted,
+                            format!("closed by {}", peer),
+                        );
+                        let state = ConnectionState::Disconnected { backoff: None };
+                        return Ok((state, Some(Err(lost(&closed)))));
+                    }
+                    Ok(Async::Ready(None)) => Async::Ready(None),
+                    Ok(Async::NotReady) => {
+                        if try!(self.is_idle()) {
+                            debug!("closing idle tcp connection to {}", tcp_stream.peer_addr());
+                            return Ok((ConnectionState::Disconnected { backoff: None }, None));
+                        }
+                        Async::NotReady
+                    }
+                    Err(e) => return self.failed(e),
+                };
+
+                let state = ConnectionState::Connected {
+                    tcp_stream: tcp_stream,
+                    sender: sender,
+                };
+                Ok((state, Some(Ok(ready))))
+            }
+        }
+    }
+
+    /// Returns true if the connection has been idle for the idle timeout, otherwise the task is
+    ///  woken up when it would be
+    fn is_idle(&mut self) -> io::Result<bool> {
+        let (idle_timeout, reactor) = match (self.idle_timeout, self.reactor.as_ref()) {
+            (Some(idle_timeout), Some(reactor)) => (idle_timeout, reactor),
+            _ => return Ok(false),
+        };
+
+        loop {
+            let idle_for = self.last_activity.elapsed();
+            if idle_for >= idle_timeout {
+                self.idle_timer = None;
+                return Ok(true);
+            }
+
+            if self.idle_timer.is_none() {
+                self.idle_timer = Some(try!(Timeout::new(idle_timeout - idle_for, reactor)));
+            }
+
+            match try!(self.idle_timer.as_mut().expect("set above").poll()) {
+                // there may have been activity since the timer was set
+                Async::Ready(()) => self.idle_timer = None,
+                Async::NotReady => return Ok(false),
+            }
+        }
     }
 }
 
@@ -88,18 +393,17 @@ impl<S: AsyncRead + AsyncWrite> Stream for TcpClientStream<S> {
     type Error = io::Error;
 
     fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        match try_ready!(self.tcp_stream.poll()) {
-            Some((buffer, src_addr)) => {
-                // this is busted if the tcp connection doesn't have a peer
-                let peer = self.tcp_stream.peer_addr();
-                if src_addr != peer {
-                    // FIXME: this should be an error...
-                    warn!("{} does not match name_server: {}", src_addr, peer)
-                }
-
-                Ok(Async::Ready(Some(buffer)))
+        loop {
+            let state = mem::replace(
+                &mut self.state,
+                ConnectionState::Disconnected { backoff: None },
+            );
+            let (state, ready) = try!(self.advance(state));
+            self.state = state;
+
+            if let Some(ready) = ready {
+                return ready;
             }
-            None => Ok(Async::Ready(None)),
         }
     }
 }
@@ -226,3 +530,78 @@ fn tcp_client_stream_test(server_addr: IpAddr) {
     succeeded.store(true, std::sync::atomic::Ordering::Relaxed);
     server_handle.join().expect("server thread failed");
 }
+
+#[test]
+fn test_reconnect_delay() {
+    assert_eq!(reconnect_delay(1), Duration::from_millis(500));
+    assert_eq!(reconnect_delay(3), Duration::from_secs(2));
+    assert_eq!(
+        reconnect_delay(100),
+        Duration::from_secs(MAX_RECONNECT_DELAY_S)
+    );
+}
+
+#[test]
+fn test_tcp_client_stream_reconnect() {
+    use std::io::{Read, Write};
+    use futures::future::Either;
+    use tokio_core::reactor::Core;
+
+    use std;
+
+    let server = std::net::TcpListener::bind(SocketAddr::new(
+        IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+        0,
+    )).unwrap();
+    let server_addr = server.local_addr().unwrap();
+
+    // answers one message on each connection, and then closes it
+    let server_handle = std::thread::Builder::new()
+        .name("test_tcp_client_stream_reconnect:server".to_string())
+        .spawn(move || for _ in 0..2 {
+            let (mut socket, _) = server.accept().expect("accept failed");
+            socket
+                .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+                .unwrap();
+
+            let mut len_bytes = [0_u8; 2];
+            socket.read_exact(&mut len_bytes).expect(
+                "SERVER: receive failed",
+            );
+            let mut buffer = [0_u8; TEST_BYTES_LEN];
+            socket.read_exact(&mut buffer).unwrap();
+
+            socket.write_all(&len_bytes).expect(
+                "SERVER: send length failed",
+            );
+            socket.write_all(&buffer).expect(
+                "SERVER: send buffer failed",
+            );
+        })
+        .unwrap();
+
+    let mut io_loop = Core::new().unwrap();
+    let (stream, mut sender) = TcpClientStream::new(server_addr, &io_loop.handle());
+    let mut stream = io_loop.run(stream).ok().expect("run failed to get stream");
+
+    for i in 0..2 {
+        sender.send(TEST_BYTES.to_vec()).expect("send failed");
+        let (buffer, stream_tmp) = io_loop.run(stream.into_future()).ok().expect(
+            "future iteration run failed",
+        );
+        assert_eq!(&buffer.expect("no buffer received"), TEST_BYTES);
+
+        if i > 0 {
+            break;
+        }
+
+        // the closed connection ends nothing, the stream waits for the next message
+        let timeout = Timeout::new(Duration::from_millis(500), &io_loop.handle()).unwrap();
+        stream = match io_loop.run(timeout.select2(stream_tmp.into_future())) {
+            Ok(Either::A((_, stream_future))) => stream_future.into_inner().unwrap(),
+            _ => panic!("the stream should still be waiting"),
+        };
+    }
+
+    server_handle.join().expect("server thread failed");
+}
@@ -65,16 +65,22 @@ pub enum ReadTcpState {
 
 
 /// A Stream used for sending data to and from a remote DNS endpoint (client or server).
+///
+/// The messages written to the remote are pulled from `O`, the next one only once the previous
+///  one has been written and flushed.
 #[must_use = "futures do nothing unless polled"]
-pub struct TcpStream<S> {
+pub struct TcpStream<S, O = UnboundedReceiver<(Vec<u8>, SocketAddr)>>
+where
+    O: Stream,
+{
     socket: S,
-    outbound_messages: Peekable<Fuse<UnboundedReceiver<(Vec<u8>, SocketAddr)>>>,
+    outbound_messages: Peekable<Fuse<O>>,
     send_state: Option<WriteTcpState>,
     read_state: ReadTcpState,
     peer_addr: SocketAddr,
 }
 
-impl<S> TcpStream<S> {
+impl<S, O: Stream> TcpStream<S, O> {
     /// Returns the address of the peer connection.
     pub fn peer_addr(&self) -> SocketAddr {
         self.peer_addr
@@ -175,9 +181,27 @@ impl<S: AsyncRead + AsyncWrite> TcpStream<S> {
         peer_addr: SocketAddr,
         receiver: UnboundedReceiver<(Vec<u8>, SocketAddr)>,
     ) -> Self {
+        Self::from_stream_with_outbound(stream, peer_addr, receiver)
+    }
+}
+
+impl<S, O> TcpStream<S, O>
+where
+    S: AsyncRead + AsyncWrite,
+    O: Stream<Item = (Vec<u8>, SocketAddr), Error = ()>,
+{
+    /// Wraps a stream which writes the messages pulled from `outbound`, e.g. a server which
+    ///  produces the messages of a response as they are written
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - the established IO stream for communication
+    /// * `peer_addr` - sources address of the stream
+    /// * `outbound` - the messages to write to the stream, each addressed to `peer_addr`
+    pub fn from_stream_with_outbound(stream: S, peer_addr: SocketAddr, outbound: O) -> Self {
         TcpStream {
             socket: stream,
-            outbound_messages: receiver.fuse().peekable(),
+            outbound_messages: outbound.fuse().peekable(),
             send_state: None,
             read_state: ReadTcpState::LenBytes {
                 pos: 0,
@@ -188,7 +212,11 @@ impl<S: AsyncRead + AsyncWrite> TcpStream<S> {
     }
 }
 
-impl<S: AsyncRead + AsyncWrite> Stream for TcpStream<S> {
+impl<S, O> Stream for TcpStream<S, O>
+where
+    S: AsyncRead + AsyncWrite,
+    O: Stream<Item = (Vec<u8>, SocketAddr), Error = ()>,
+{
     type Item = (Vec<u8>, SocketAddr);
     type Error = io::Error;
 
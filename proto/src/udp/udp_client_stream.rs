@@ -5,27 +5,65 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::net::SocketAddr;
+use std;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::io;
+use std::time::Duration;
 
-use futures::{Async, Future, Poll, Stream};
-use tokio_core::reactor::Handle;
+use futures::{future, Async, Future, Poll, Stream};
+use futures::stream::Fuse;
+use futures::sync::mpsc::{unbounded, UnboundedReceiver};
+use tokio_core;
+use tokio_core::reactor::{Handle, Timeout};
 
 use BufDnsStreamHandle;
 use DnsStreamHandle;
-use udp::UdpStream;
+use dns_handle::is_answer_to;
+use op::{Message, Query};
+
+/// The time a socket waits for the response to its query, see `UdpClientStream::with_timeout`
+const DEFAULT_SOCKET_TIMEOUT_S: u64 = 5;
 
 /// A UDP client stream of DNS binary packets
+///
+/// Each query is sent from a socket of its own, bound to an ephemeral port chosen by the OS, so
+///  a spoofed response has to guess the port of the query along with its id. The socket is closed
+///  once the name server responds with the id and the question of the query, or once the query
+///  has timed out, a datagram which does not match the query is dropped, so that a spoofed one
+///  can not close the socket before the response arrives.
 #[must_use = "futures do nothing unless polled"]
 pub struct UdpClientStream {
     name_server: SocketAddr,
-    udp_stream: UdpStream,
+    reactor: Handle,
+    timeout: Duration,
+    outbound_messages: Fuse<UnboundedReceiver<(Vec<u8>, SocketAddr)>>,
+    sockets: Vec<QuerySocket>,
+}
+
+/// The socket of a query which is waiting for the response
+struct QuerySocket {
+    socket: tokio_core::net::UdpSocket,
+    timeout: Timeout,
+    // the id and the question of the query, which the response must match
+    id: u16,
+    queries: Vec<Query>,
+}
+
+impl QuerySocket {
+    /// Returns true if the datagram is the response to the query, RFC 5452 section 9.1
+    fn is_response(&self, buffer: &[u8]) -> bool {
+        match Message::from_vec(buffer) {
+            Ok(response) => response.id() == self.id && is_answer_to(&self.queries, &response),
+            Err(e) => {
+                debug!("error decoding response to query {}: {}", self.id, e);
+                false
+            }
+        }
+    }
 }
 
 impl UdpClientStream {
-    /// it is expected that the resolver wrapper will be responsible for creating and managing
-    ///  new UdpClients such that each new client would have a random port (reduce chance of cache
-    ///  poisoning)
+    /// Creates a stream to the name server, whose sockets wait 5 seconds for each response
     ///
     /// # Return
     ///
@@ -35,22 +73,122 @@ impl UdpClientStream {
         name_server: SocketAddr,
         loop_handle: &Handle,
     ) -> (Box<Future<Item = UdpClientStream, Error = io::Error>>, Box<DnsStreamHandle>) {
-        let (stream_future, sender) = UdpStream::new(name_server, loop_handle);
+        Self::with_timeout(
+            name_server,
+            loop_handle,
+            Duration::from_secs(DEFAULT_SOCKET_TIMEOUT_S),
+        )
+    }
+
+    /// Creates a stream to the name server, whose sockets wait the timeout for each response
+    ///
+    /// The timeout should be no shorter than that of the `DnsFuture` over the stream, i.e. the
+    ///  longest time a request waits for its response, a later response is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the address of the name server
+    /// * `loop_handle` - handle to the IO loop
+    /// * `timeout` - time after which the socket of a query which was not answered is closed
+    pub fn with_timeout(
+        name_server: SocketAddr,
+        loop_handle: &Handle,
+        timeout: Duration,
+    ) -> (Box<Future<Item = UdpClientStream, Error = io::Error>>, Box<DnsStreamHandle>) {
+        let (sender, outbound_messages) = unbounded();
 
-        let new_future: Box<Future<Item = UdpClientStream, Error = io::Error>> =
-            Box::new(stream_future.map(move |udp_stream| {
-                UdpClientStream {
-                    name_server: name_server,
-                    udp_stream: udp_stream,
-                }
-            }));
+        let stream = UdpClientStream {
+            name_server: name_server,
+            reactor: loop_handle.clone(),
+            timeout: timeout,
+            outbound_messages: outbound_messages.fuse(),
+            sockets: Vec::new(),
+        };
 
         let sender = Box::new(BufDnsStreamHandle {
             name_server: name_server,
             sender: sender,
         });
 
-        (new_future, sender)
+        (Box::new(future::ok(stream)), sender)
+    }
+
+    /// Sends the query from a new socket, bound to a port chosen by the OS
+    fn send_query(&self, buffer: &[u8], name_server: SocketAddr) -> io::Result<QuerySocket> {
+        let query = try!(Message::from_vec(buffer).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{}", e))
+        }));
+
+        let bind_address = match name_server {
+            SocketAddr::V4(..) => IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
+            SocketAddr::V6(..) => IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)),
+        };
+
+        let socket = try!(std::net::UdpSocket::bind(SocketAddr::new(bind_address, 0)));
+        let socket = try!(tokio_core::net::UdpSocket::from_socket(socket, &self.reactor));
+        let timeout = try!(Timeout::new(self.timeout, &self.reactor));
+
+        // the socket is new, nothing is queued on it, so this does not block
+        try!(socket.send_to(buffer, &name_server));
+        Ok(QuerySocket {
+            socket: socket,
+            timeout: timeout,
+            id: query.id(),
+            queries: query.queries().to_vec(),
+        })
+    }
+
+    /// Receives the next response from the name server, closing the socket which received it,
+    ///  and those which timed out
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        // TODO: this should match edns settings
+        let mut buf = [0u8; 2048];
+
+        let mut index = 0;
+        while index < self.sockets.len() {
+            let received = match self.sockets[index].socket.recv_from(&mut buf) {
+                Ok((len, src)) => Some((len, src)),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => None,
+                Err(e) => {
+                    debug!("error receiving response, closing socket: {}", e);
+                    self.sockets.swap_remove(index);
+                    continue;
+                }
+            };
+
+            match received {
+                Some((len, src)) if src == self.name_server => {
+                    if !self.sockets[index].is_response(&buf[..len]) {
+                        // as below, the socket is kept for the response
+                        debug!("datagram from {} does not match the query, ignoring it", src);
+                        continue;
+                    }
+
+                    self.sockets.swap_remove(index);
+                    return Ok(Some(buf.iter().take(len).cloned().collect()));
+                }
+                Some((_, src)) => {
+                    // the socket is kept for the response of the name server
+                    debug!("{} does not match name_server: {}", src, self.name_server);
+                    continue;
+                }
+                None => (),
+            }
+
+            match self.sockets[index].timeout.poll() {
+                Ok(Async::NotReady) => index += 1,
+                Ok(Async::Ready(())) => {
+                    debug!("closing socket of unanswered query to {}", self.name_server);
+                    self.sockets.swap_remove(index);
+                }
+                Err(e) => {
+                    debug!("error polling timeout, closing socket: {}", e);
+                    self.sockets.swap_remove(index);
+                }
+            }
+        }
+
+        Ok(None)
     }
 }
 
@@ -59,30 +197,30 @@ impl Stream for UdpClientStream {
     type Error = io::Error;
 
     fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        match try_ready!(self.udp_stream.poll()) {
-            Some((buffer, src_addr)) => {
-                if src_addr != self.name_server {
-                    debug!(
-                        "{} does not match name_server: {}",
-                        src_addr,
-                        self.name_server
-                    )
-                }
+        // send all the queries, each from a socket of its own
+        loop {
+            let query = match try!(self.outbound_messages.poll().map_err(|()| {
+                io::Error::new(io::ErrorKind::Other, "unknown")
+            })) {
+                Async::Ready(Some(query)) => query,
+                Async::NotReady | Async::Ready(None) => break,
+            };
 
-                Ok(Async::Ready(Some(buffer)))
+            match self.send_query(&query.0, query.1) {
+                Ok(socket) => self.sockets.push(socket),
+                // the query times out in the DnsFuture, the other queries are unaffected
+                Err(e) => warn!("could not send query to {}: {}", query.1, e),
             }
-            None => Ok(Async::Ready(None)),
+        }
+
+        match try!(self.receive()) {
+            Some(buffer) => Ok(Async::Ready(Some(buffer))),
+            None => Ok(Async::NotReady),
         }
     }
 }
 
 
-#[cfg(test)]
-use std::net::{IpAddr, Ipv4Addr};
-#[cfg(not(target_os = "linux"))]
-#[cfg(test)]
-use std::net::Ipv6Addr;
-
 #[test]
 fn test_udp_client_stream_ipv4() {
     udp_client_stream_test(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)))
@@ -125,7 +263,8 @@ fn udp_client_stream_test(server_addr: IpAddr) {
         .unwrap(); // should recieve something within 5 seconds...
     let server_addr = server.local_addr().unwrap();
 
-    let test_bytes: &'static [u8; 8] = b"DEADBEEF";
+    let test_bytes = test_query().to_vec().unwrap();
+    let server_bytes = test_bytes.clone();
     let send_recv_times = 4;
 
     // an in and out server
@@ -133,12 +272,14 @@ fn udp_client_stream_test(server_addr: IpAddr) {
         .name("test_udp_client_stream_ipv4:server".to_string())
         .spawn(move || {
             let mut buffer = [0_u8; 512];
+            let mut ports = Vec::new();
 
             for _ in 0..send_recv_times {
                 // wait for some bytes...
                 let (len, addr) = server.recv_from(&mut buffer).expect("receive failed");
 
-                assert_eq!(&buffer[0..len], test_bytes);
+                assert_eq!(&buffer[0..len], &server_bytes[..]);
+                ports.push(addr.port());
 
                 // bounce them right back...
                 assert_eq!(
@@ -146,6 +287,8 @@ fn udp_client_stream_test(server_addr: IpAddr) {
                     len
                 );
             }
+
+            ports
         })
         .unwrap();
 
@@ -160,12 +303,87 @@ fn udp_client_stream_test(server_addr: IpAddr) {
 
     for _ in 0..send_recv_times {
         // test once
-        sender.send(test_bytes.to_vec()).unwrap();
+        sender.send(test_bytes.clone()).unwrap();
         let (buffer, stream_tmp) = io_loop.run(stream.into_future()).ok().unwrap();
         stream = stream_tmp;
-        assert_eq!(&buffer.expect("no buffer received"), test_bytes);
+        assert_eq!(buffer.expect("no buffer received"), test_bytes);
     }
 
     succeeded.store(true, std::sync::atomic::Ordering::Relaxed);
+    let mut ports = server_handle.join().expect("server thread failed");
+
+    // each query was sent from a socket of its own
+    ports.sort();
+    ports.dedup();
+    assert_eq!(ports.len(), send_recv_times);
+}
+
+#[cfg(test)]
+fn test_query() -> Message {
+    use std::str::FromStr;
+    use rr::{Name, RecordType};
+
+    let mut query = Message::new();
+    query.set_id(0x1234).add_query(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+    query
+}
+
+#[test]
+fn test_udp_client_stream_forged_response() {
+    use std::str::FromStr;
+    use tokio_core::reactor::Core;
+    use op::MessageType;
+    use rr::{Name, RecordType};
+
+    let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    server
+        .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+        .unwrap();
+    let server_addr = server.local_addr().unwrap();
+
+    let mut io_loop = Core::new().unwrap();
+    let (stream, mut sender) = UdpClientStream::new(server_addr, &io_loop.handle());
+    let stream: UdpClientStream = io_loop.run(stream).ok().unwrap();
+
+    let query = test_query();
+    sender.send(query.to_vec().unwrap()).unwrap();
+
+    // the stream sends the query once it is polled, the server waits for it on a thread
+    let server_handle = std::thread::spawn(move || {
+        let mut buffer = [0_u8; 512];
+        let (_, addr) = server.recv_from(&mut buffer).expect("receive failed");
+
+        let mut response = test_query();
+        response.set_message_type(MessageType::Response);
+
+        // datagrams from the name server with another id, and with another question, which
+        //  arrive before the response
+        let mut other_id = response.clone();
+        other_id.set_id(0x4321);
+        server.send_to(&other_id.to_vec().unwrap(), addr).unwrap();
+        let mut other_question = Message::new();
+        other_question
+            .set_id(0x1234)
+            .set_message_type(MessageType::Response)
+            .add_query(Query::query(
+                Name::from_str("www.example.net.").unwrap(),
+                RecordType::A,
+            ));
+        server
+            .send_to(&other_question.to_vec().unwrap(), addr)
+            .unwrap();
+        server.send_to(b"not a message", addr).unwrap();
+
+        server.send_to(&response.to_vec().unwrap(), addr).unwrap();
+    });
+
+    // only the response is passed on, the socket was kept open for it
+    let (buffer, _) = io_loop.run(stream.into_future()).ok().unwrap();
+    let received = Message::from_vec(&buffer.expect("no buffer received")).unwrap();
+    assert_eq!(received.id(), 0x1234);
+    assert_eq!(received.queries(), query.queries());
     server_handle.join().expect("server thread failed");
 }
@@ -6,10 +6,18 @@
 // copied, modified, or distributed except according to those terms.
 
 //! Configuration for a resolver
+use std::io;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::Duration;
 
-use trust_dns::rr::Name;
+use trust_dns::rr::{Name, RecordType};
+use trust_dns::rr::dnssec::{PublicKey, TrustAnchor};
+use trust_dns::rr::rdata::opt::EdnsOption;
+pub use trust_dns::client::RetryPolicy;
+pub use trust_dns::serialize::binary::NameCompression;
+
+use lookup_state::ClientSubnet;
+use system_conf;
 
 /// Configuration for the upstream nameservers to use for resolution
 #[derive(Clone, Debug)]
@@ -20,6 +28,12 @@ pub struct ResolverConfig {
     search: Vec<Name>,
     // nameservers to use for resolution.
     name_servers: Vec<NameServerConfig>,
+    // nameservers used only for names within a domain
+    domain_name_servers: Vec<(Name, NameServerConfig)>,
+    // domains which may resolve to internal addresses, see ResolverOpts::rebind_protection
+    rebind_allowed_domains: Vec<Name>,
+    // keys from which DNSSec validation starts, see ResolverOpts::validate
+    trust_anchor: TrustAnchor,
 }
 
 impl ResolverConfig {
@@ -30,6 +44,9 @@ impl ResolverConfig {
             domain: Name::root(),
             search: vec![],
             name_servers: vec![],
+            domain_name_servers: vec![],
+            rebind_allowed_domains: vec![],
+            trust_anchor: TrustAnchor::default(),
         }
     }
 
@@ -49,9 +66,48 @@ impl ResolverConfig {
             domain,
             search,
             name_servers,
+            domain_name_servers: vec![],
+            rebind_allowed_domains: vec![],
+            trust_anchor: TrustAnchor::default(),
         }
     }
 
+    /// Reads the configuration of the system
+    ///
+    /// This is `/etc/resolv.conf` on Unix OSes, with its `nameserver`, `domain`, `search` and
+    ///  `options` `ndots`, `timeout`, `attempts` and `rotate`, and on macOS also the scoped
+    ///  resolvers in `/etc/resolver`, see `add_domain_name_server`. On Windows the configuration
+    ///  of the network adapters is used.
+    #[cfg(not(all(target_os = "windows", target_pointer_width = "32")))]
+    pub fn from_system() -> io::Result<(ResolverConfig, ResolverOpts)> {
+        system_conf::read_system_conf()
+    }
+
+    /// Reads the configuration from environment variables, e.g. of a container, without code
+    ///  changes or a mounted `/etc/resolv.conf`
+    ///
+    /// The variables which are unset or empty keep the defaults of `ResolverConfig::default` and
+    ///  `ResolverOpts::default`.
+    ///
+    /// * `TRUST_DNS_NAMESERVERS` - the name servers, separated by commas or spaces, e.g.
+    ///                             `10.0.0.1, [2001:db8::1]:5353`, the port defaults to that of
+    ///                             the protocol
+    /// * `TRUST_DNS_PROTOCOL` - `udp`, UDP and TCP (default), `tcp`, `tls` with the
+    ///                          `dns-over-tls` feature, or `https` with the `dns-over-https`
+    ///                          feature, of the `TRUST_DNS_NAMESERVERS`
+    /// * `TRUST_DNS_TLS_DNS_NAME` - the name of the certificates of the name servers, required for
+    ///                              `tls` and `https`, see `NameServerConfig::tls_dns_name`
+    /// * `TRUST_DNS_HTTPS_TEMPLATE` - see `NameServerConfig::https_template`
+    /// * `TRUST_DNS_DOMAIN` - the local domain, see `domain`
+    /// * `TRUST_DNS_SEARCH` - the search domains, separated by commas or spaces, see `search`
+    /// * `TRUST_DNS_NDOTS`, `TRUST_DNS_TIMEOUT` in seconds and `TRUST_DNS_ATTEMPTS` - see
+    ///                                                              `ResolverOpts`
+    ///
+    /// Invalid values are errors, rather than ignored.
+    pub fn from_env() -> io::Result<(ResolverConfig, ResolverOpts)> {
+        system_conf::read_env_conf()
+    }
+
     /// Returns the local domain
     ///
     /// By default any names will be appended to all non-fully-qualified-domain names, and searched for after any ndots rules
@@ -76,6 +132,53 @@ impl ResolverConfig {
     pub fn name_servers(&self) -> &[NameServerConfig] {
         &self.name_servers
     }
+
+    /// Add a name server which is used for the names within the domain, instead of the other
+    ///  name servers, e.g. for the internal domain of a VPN
+    ///
+    /// Where domains are nested, the name servers of the most specific domain are used.
+    pub fn add_domain_name_server(&mut self, domain: Name, name_server: NameServerConfig) {
+        self.domain_name_servers.push((domain, name_server));
+    }
+
+    /// Returns the name servers which are used for the names within a domain
+    pub fn domain_name_servers(&self) -> &[(Name, NameServerConfig)] {
+        &self.domain_name_servers
+    }
+
+    /// Allow the names within the domain to resolve to internal addresses, e.g. the domain of the
+    ///  LAN, see `ResolverOpts::rebind_protection`
+    ///
+    /// The domains of the `domain_name_servers`, `local.` and `localhost.` are always allowed.
+    pub fn add_rebind_allowed_domain(&mut self, domain: Name) {
+        self.rebind_allowed_domains.push(domain);
+    }
+
+    /// Returns the domains which are allowed to resolve to internal addresses
+    pub fn rebind_allowed_domains(&self) -> &[Name] {
+        &self.rebind_allowed_domains
+    }
+
+    /// Trust the key as a DNSSec trust anchor, in addition to those already trusted, see
+    ///  `ResolverOpts::validate`
+    ///
+    /// # Arguments
+    ///
+    /// * `public_key` - the DNSKEY, e.g. of a zone without a chain of trust to the root
+    pub fn add_trust_anchor<P: PublicKey>(&mut self, public_key: P) {
+        self.trust_anchor.insert_trust_anchor(public_key);
+    }
+
+    /// Replace all the trusted keys, including the built in IANA root KSKs, e.g. to pin the keys
+    ///  of a private root
+    pub fn set_trust_anchor(&mut self, trust_anchor: TrustAnchor) {
+        self.trust_anchor = trust_anchor;
+    }
+
+    /// Returns the keys trusted for DNSSec validation, by default the IANA root KSKs
+    pub fn trust_anchor(&self) -> &TrustAnchor {
+        &self.trust_anchor
+    }
 }
 
 impl Default for ResolverConfig {
@@ -87,11 +190,23 @@ impl Default for ResolverConfig {
         let google_ns1 = NameServerConfig {
             socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
             protocol: Protocol::Udp,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
         };
 
         let google_ns2 = NameServerConfig {
             socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 4, 4)), 53),
             protocol: Protocol::Udp,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
         };
 
         let google_v6_ns1 = NameServerConfig {
@@ -109,6 +224,12 @@ impl Default for ResolverConfig {
                 53,
             ),
             protocol: Protocol::Udp,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
         };
 
         let google_v6_ns2 = NameServerConfig {
@@ -126,12 +247,21 @@ impl Default for ResolverConfig {
                 53,
             ),
             protocol: Protocol::Udp,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
         };
 
         ResolverConfig {
             domain,
             search: vec![],
             name_servers: vec![google_ns1, google_ns2, google_v6_ns1, google_v6_ns2],
+            domain_name_servers: vec![],
+            rebind_allowed_domains: vec![],
+            trust_anchor: TrustAnchor::default(),
         }
     }
 }
@@ -143,8 +273,27 @@ pub enum Protocol {
     Udp,
     /// TCP can be used for large queries, but not all NameServers support it
     Tcp,
+    /// DNS over TLS, [RFC 7858](https://tools.ietf.org/html/rfc7858), generally on port 853
+    ///
+    /// The certificate of the NameServer is validated against `NameServerConfig::tls_dns_name`
+    #[cfg(feature = "dns-over-tls")]
+    Tls,
+    /// DNS over HTTPS, [RFC 8484](https://tools.ietf.org/html/rfc8484), generally on port 443
+    ///
+    /// The certificate of the NameServer is validated against `NameServerConfig::tls_dns_name`,
+    ///  and requests are sent to `NameServerConfig::https_template`
+    #[cfg(feature = "dns-over-https")]
+    Https,
+    /// Multicast DNS, [RFC 6762](https://tools.ietf.org/html/rfc6762), on `MDNS_IPV4` or
+    ///  `MDNS_IPV6` from `trust_dns_proto::multicast`
+    ///
+    /// Only names in the `local.` domain are sent to mDNS NameServers, and these names are never
+    ///  sent to the other NameServers when one is configured.
+    #[cfg(feature = "mdns")]
+    Mdns,
     // TODO: add client certificate for mTLS?
-    // Tls,
+    // TODO: DNS over QUIC, RFC 9250, quinn requires newer versions of ring and tokio than the
+    //  rest of the library is built against, revisit once those are upgraded
 }
 
 impl Protocol {
@@ -153,6 +302,32 @@ impl Protocol {
         match *self {
             Protocol::Udp => true,
             Protocol::Tcp => false,
+            #[cfg(feature = "dns-over-tls")]
+            Protocol::Tls => false,
+            #[cfg(feature = "dns-over-https")]
+            Protocol::Https => false,
+            #[cfg(feature = "mdns")]
+            Protocol::Mdns => true,
+        }
+    }
+
+    /// Returns true if this is multicast DNS, which is only used for names in the `local.` domain
+    pub fn is_mdns(&self) -> bool {
+        match *self {
+            #[cfg(feature = "mdns")]
+            Protocol::Mdns => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the connection is encrypted, i.e. DNS over TLS or DNS over HTTPS
+    pub fn is_encrypted(&self) -> bool {
+        match *self {
+            #[cfg(feature = "dns-over-tls")]
+            Protocol::Tls => true,
+            #[cfg(feature = "dns-over-https")]
+            Protocol::Https => true,
+            _ => false,
         }
     }
 
@@ -162,6 +337,27 @@ impl Protocol {
     }
 }
 
+/// How queries are sent to a `Protocol::Udp` NameServer, see
+///  `NameServerConfig::transport_strategy`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TransportStrategy {
+    /// Queries are sent over UDP, and retried over TCP to the same NameServer when the response
+    ///  is truncated, RFC 1035 section 4.2.1 (default)
+    UdpThenTcp,
+    /// Queries are only sent over TCP, e.g. where UDP is blocked
+    TcpOnly,
+    /// Queries are only sent over UDP, truncated responses are returned as they are, unless
+    ///  there are `Protocol::Tcp` NameServers in the pool to retry them with
+    UdpOnly,
+}
+
+impl Default for TransportStrategy {
+    /// Returns UdpThenTcp as the default.
+    fn default() -> Self {
+        TransportStrategy::UdpThenTcp
+    }
+}
+
 /// Configuration for the NameServer
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct NameServerConfig {
@@ -169,6 +365,27 @@ pub struct NameServerConfig {
     pub socket_addr: SocketAddr,
     /// The protocol to use when communicating with the NameServer.
     pub protocol: Protocol,
+    /// The name to validate the certificate of the NameServer against, and send as SNI, this is
+    ///  only used with `Protocol::Tls` and `Protocol::Https`
+    pub tls_dns_name: Option<String>,
+    /// The URI template of the DNS over HTTPS endpoint, e.g.
+    ///  `https://cloudflare-dns.com/dns-query{?dns}` for GET requests, or without `{?dns}` for
+    ///  POST requests. Defaults to POST requests to `https://{tls_dns_name}/dns-query`, this is
+    ///  only used with `Protocol::Https`
+    pub https_template: Option<String>,
+    /// EDNS options to attach to every query sent to the NameServer, e.g. to identify this client
+    ///  to a filtering upstream, an option of the same code in the query is replaced
+    pub edns_options: Vec<EdnsOption>,
+    /// Which names of the queries sent to the NameServer are compressed, for servers, or
+    ///  middleboxes on the way, which fail to decode pointers; defaults to all
+    pub name_compression: NameCompression,
+    /// Pads the queries sent to the NameServer to a multiple of this size with the Padding option,
+    ///  RFC 7830, so that their length gives less away about the names queried. This is meant for
+    ///  the encrypted protocols, RFC 8467 recommends 128, see `opt::QUERY_PADDING_BLOCK_SIZE`
+    pub padding_block_size: Option<u16>,
+    /// Whether queries to a `Protocol::Udp` NameServer are sent over UDP or TCP, and whether
+    ///  truncated responses are retried over TCP; not used with the other protocols
+    pub transport_strategy: TransportStrategy,
 }
 
 /// The lookup ip strategy
@@ -184,6 +401,10 @@ pub enum LookupIpStrategy {
     Ipv6thenIpv4,
     /// Query for Ipv4 if that fails, query for Ipv6
     Ipv4thenIpv6,
+    /// Query for A and AAAA in parallel, the addresses are ordered for connection attempts per
+    ///  [RFC 8305](https://tools.ietf.org/html/rfc8305#section-4), Happy Eyeballs, alternating
+    ///  between the families starting with Ipv6
+    HappyEyeballs,
 }
 
 impl Default for LookupIpStrategy {
@@ -193,6 +414,62 @@ impl Default for LookupIpStrategy {
     }
 }
 
+/// How a response with illegal data, a CNAME with other data at the same name in the answers or
+///  more than one SOA in the authority section, is handled, see `ResolverOpts::illegal_data`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IllegalDataPolicy {
+    /// The response is treated as an error
+    Reject,
+    /// The CNAMEs with other data, and all but the first SOA, are removed from the response
+    Repair,
+    /// The response is used as is, and the illegal data logged (default)
+    Warn,
+}
+
+impl Default for IllegalDataPolicy {
+    /// Returns Warn as the default.
+    fn default() -> Self {
+        IllegalDataPolicy::Warn
+    }
+}
+
+/// The order of the records of a lookup, see `ResolverOpts::answer_order`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AnswerOrder {
+    /// As in the response from the NameServer, which is kept for the lookups answered from the
+    ///  cache (default)
+    Wire,
+    /// Sorted by the wire format of the record data, the canonical order of
+    ///  [RFC 4034](https://tools.ietf.org/html/rfc4034#section-6.3), the same whichever
+    ///  NameServer answered
+    Canonical,
+    /// Shuffled for each lookup, including those answered from the cache
+    Shuffle {
+        /// Seeds the random number generator of the shuffles, so that the orders are
+        ///  reproducible, e.g. in tests of code which depends on the order. None seeds it from
+        ///  the OS
+        seed: Option<u64>,
+    },
+}
+
+impl Default for AnswerOrder {
+    /// Returns Wire as the default.
+    fn default() -> Self {
+        AnswerOrder::Wire
+    }
+}
+
+/// Racing of the NameServers of a preferred protocol against the others, e.g. DNS over TLS
+///  against UDP to the same provider, see `ResolverOpts::transport_race`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TransportRace {
+    /// The protocol of the NameServers which are queried first
+    pub preferred: Protocol,
+    /// Time the NameServers of the preferred protocol have to answer before the other
+    ///  NameServers are also queried, they are queried immediately if the preferred ones fail
+    pub head_start: Duration,
+}
+
 /// Configuration for the Resolver
 #[derive(Clone, Copy)]
 #[allow(dead_code)] // TODO: remove after all params are supported
@@ -200,7 +477,7 @@ pub struct ResolverOpts {
     /// Sets the number of dots that must appear (unless it's a final dot representing the root)
     ///  that must appear before a query is assumted to include the TLD. The default is one, which
     ///  means that `www` would never be assumed to be a TLD, and would always be appended to either
-    ///  the search
+    ///  the search. Names with fewer dots are still tried as is, after the domain and search list
     pub ndots: usize,
     /// Specify the timeout for a request. Defaults to 5 seconds
     pub timeout: Duration,
@@ -213,14 +490,90 @@ pub struct ResolverOpts {
     pub(crate) check_names: bool,
     /// Enable edns, for larger records
     pub(crate) edns0: bool,
-    /// Use DNSSec to validate the request
+    /// Use DNSSec to validate the request, starting from `ResolverConfig::trust_anchor`
+    ///
+    /// Responses which fail validation fail the lookup, and only validated records are cached,
+    ///  see `Lookup::proof`.
+    #[cfg(feature = "dnssec")]
     pub validate: bool,
     /// The ip_strategy for the Resolver to use when lookup Ipv4 or Ipv6 addresses
     pub ip_strategy: LookupIpStrategy,
     /// Cache size is in number of records (some records can be large)
     pub cache_size: usize,
-    /// Check /ect/hosts file before dns requery (only works for unix like OS)
+    /// Check the system hosts file before dns requery, `/etc/hosts` on unix like OSes and
+    ///  `%SystemRoot%\System32\drivers\etc\hosts` on Windows, it is reloaded when modified
     pub use_hosts_file: bool,
+    /// Idle time after which a query is sent to keep a DNS over TLS or DNS over HTTPS connection
+    ///  open, and to detect a dead connection before it is needed, None disables the keepalives.
+    ///  Defaults to 15 seconds
+    pub keepalive_interval: Option<Duration>,
+    /// Queries the NameServers of a preferred protocol first, and the others only once the
+    ///  preferred have had a head start, the first response is used. This prefers an encrypted
+    ///  protocol without waiting for it to time out where it is blocked. Defaults to None, in
+    ///  which the datagram NameServers are queried before the stream NameServers
+    pub transport_race: Option<TransportRace>,
+    /// Looks up all the names from the domain and search list of a name which is not fully
+    ///  qualified at once, rather than one after the other. The answer is the same, that of the
+    ///  first name in the search order with records, but it does not wait for each failure in turn
+    pub search_concurrently: bool,
+    /// Rejects names which are not fully qualified, i.e. do not end with a `.`, rather than
+    ///  searching the domain and search list for them. This is for servers, which should never
+    ///  resolve a relative name by accident, see `Name::into_fqdn` to mark a name as absolute
+    pub require_fqdn: bool,
+    /// Protects against DNS rebinding, removes the loopback, private, link-local and unspecified
+    ///  addresses from the answers for names which are not within an allowed domain, see
+    ///  `ResolverConfig::add_rebind_allowed_domain`. This keeps an external name from pointing a
+    ///  browser on the LAN at internal hosts. Defaults to false
+    pub rebind_protection: bool,
+    /// How responses with illegal data, a CNAME with other data at the same name or more than one
+    ///  SOA, are handled. Such responses are seen from misconfigured servers. Defaults to
+    ///  `IllegalDataPolicy::Warn`
+    pub illegal_data: IllegalDataPolicy,
+    /// Maximum number of CNAMEs followed from the queried name to the records, lookups through a
+    ///  longer chain fail, as do those through a CNAME loop, see `Lookup::cname_chain`. Defaults
+    ///  to 8
+    pub max_cname_chain: usize,
+    /// The order of the records of each lookup, as they were received, sorted or shuffled. A
+    ///  seeded shuffle is reproducible for the same sequence of lookups. Defaults to
+    ///  `AnswerOrder::Wire`
+    pub answer_order: AnswerOrder,
+    /// Keeps the responses from the NameServers along with the records, for tools which need the
+    ///  header flags, authority and additional sections or EDNS options, see `Lookup::responses`.
+    ///  Defaults to false, as the responses take up space in the cache
+    pub keep_response: bool,
+    /// The subnet of the clients which lookups are made for, sent to the NameServers as an EDNS
+    ///  Client Subnet option, RFC 7871, for answers suited to where the clients are, e.g. from a
+    ///  CDN. The answers are cached for the subnets they are scoped to. An ECS option in the
+    ///  `edns_options` of a NameServer takes precedence. Defaults to None
+    pub client_subnet: Option<ClientSubnet>,
+    /// Send DNS Cookies, RFC 7873, to the NameServers, a Client Cookie per NameServer and the
+    ///  Server Cookie last learned from it. Responses with the wrong Client Cookie, or without a
+    ///  cookie from a NameServer known to support them, are discarded as spoofed. Defaults to
+    ///  false
+    pub cookies: bool,
+    /// How the queries over UDP which are not answered are retransmitted to the same NameServer,
+    ///  with backoff and jitter, before it counts as failed, each attempt with a new query id.
+    ///  Defaults to None, in which each query is sent once and waits for the `timeout`
+    pub retry_policy: Option<RetryPolicy>,
+    /// Waits for the response of each NameServer as long as its round trip times suggest, its
+    ///  smoothed round trip time plus four times their deviation, doubled for each failure
+    ///  since its last response, as TCP does, RFC 6298. The `timeout` is the most that is
+    ///  waited, and the wait before the first response. Defaults to false, always waiting the
+    ///  `timeout`
+    pub adaptive_timeout: bool,
+    /// Sends the query name to the UDP NameServers in a random mix of upper and lower case,
+    ///  draft-vixie-dnsext-dns0x20, and discards responses which do not echo it in exactly the
+    ///  same case, as spoofed. The answers are returned in the case of the lookup. Defaults to
+    ///  false, as some NameServers do not preserve the case of the query
+    pub case_randomization: bool,
+    /// Record types which are never queried, e.g. `&[RecordType::ANY]`, the lookups of them fail
+    ///  before anything is sent, as do the queries for them while following a CNAME or validating.
+    ///  Defaults to none
+    pub denied_query_types: &'static [RecordType],
+    /// Record types which are removed from all the sections of the responses, with the RRSIGs
+    ///  which cover them, before they are validated or cached, e.g. `&[RecordType::HINFO]`, a
+    ///  lookup of one of them has no records. Defaults to none
+    pub stripped_record_types: &'static [RecordType],
 }
 
 impl Default for ResolverOpts {
@@ -235,10 +588,27 @@ impl Default for ResolverOpts {
             rotate: false,
             check_names: true,
             edns0: false,
+            #[cfg(feature = "dnssec")]
             validate: false,
             ip_strategy: LookupIpStrategy::default(),
             cache_size: 32,
             use_hosts_file: true,
+            keepalive_interval: Some(Duration::from_secs(15)),
+            transport_race: None,
+            search_concurrently: false,
+            require_fqdn: false,
+            rebind_protection: false,
+            illegal_data: IllegalDataPolicy::default(),
+            max_cname_chain: 8,
+            answer_order: AnswerOrder::default(),
+            keep_response: false,
+            client_subnet: None,
+            cookies: false,
+            retry_policy: None,
+            adaptive_timeout: false,
+            case_randomization: false,
+            denied_query_types: &[],
+            stripped_record_types: &[],
         }
     }
 }
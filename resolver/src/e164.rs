@@ -0,0 +1,322 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! ENUM, the lookup of the URIs of E.164 telephone numbers, e.g. for SIP, per
+//!  [RFC 6116](https://tools.ietf.org/html/rfc6116)
+
+use std::io;
+use std::str::FromStr;
+
+use futures::{Future, Poll};
+use regex::RegexBuilder;
+
+use trust_dns::rr::Name;
+use trust_dns::rr::rdata::NAPTR;
+
+/// The domain of the public ENUM tree, RFC 6116 section 4.1
+pub const E164_ARPA: &'static str = "e164.arpa.";
+
+/// The number of non-terminal rules followed before the lookup fails, as a guard against loops,
+///  RFC 3402 section 4
+pub(crate) const MAX_NON_TERMINAL: usize = 5;
+
+/// Returns the Application Unique String of the number, i.e. the `+` and the digits, RFC 6116
+///  section 2.4
+///
+/// The visual separators, spaces, `-`, `.`, `(` and `)`, are removed, e.g. `+1 (555) 123-4567`
+///  is `+15551234567`.
+pub fn application_unique_string(number: &str) -> io::Result<String> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("not an E.164 number: {}", number),
+        )
+    };
+
+    let mut chars = number.trim().chars();
+    if chars.next() != Some('+') {
+        return Err(invalid());
+    }
+
+    let mut aus = String::from("+");
+    for c in chars {
+        match c {
+            c if c.is_digit(10) => aus.push(c),
+            ' ' | '-' | '.' | '(' | ')' => (),
+            _ => return Err(invalid()),
+        }
+    }
+
+    // E.164 numbers have at most 15 digits
+    if aus.len() < 2 || aus.len() > 16 {
+        return Err(invalid());
+    }
+
+    Ok(aus)
+}
+
+/// Returns the name of the NAPTR records of the number in the domain, the digits in reverse
+///  order as labels, RFC 6116 section 2.4
+///
+/// e.g. `+46-8-9761234` in `e164.arpa.` is `4.3.2.1.6.7.9.8.6.4.e164.arpa.`
+pub fn enum_name(number: &str, domain: &Name) -> io::Result<Name> {
+    let aus = try!(application_unique_string(number));
+    let labels = aus[1..]
+        .chars()
+        .rev()
+        .map(|digit| digit.to_string())
+        .collect::<Vec<_>>();
+
+    Ok(Name::from_labels(labels).append_domain(domain))
+}
+
+/// A URI of a number, from a terminal NAPTR record of ENUM
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnumUri {
+    order: u16,
+    preference: u16,
+    services: String,
+    uri: String,
+}
+
+impl EnumUri {
+    /// The order of the record of the URI
+    pub fn order(&self) -> u16 {
+        self.order
+    }
+
+    /// The preference of the record of the URI, lowest first
+    pub fn preference(&self) -> u16 {
+        self.preference
+    }
+
+    /// The services of the record, e.g. `E2U+sip` or `E2U+voice:tel`
+    pub fn services(&self) -> &str {
+        &self.services
+    }
+
+    /// The Enumservices of the record, e.g. `sip` or `voice:tel`, RFC 6116 section 3.4.3
+    pub fn enumservices(&self) -> Vec<&str> {
+        self.services.split('+').skip(1).collect()
+    }
+
+    /// The URI, e.g. `sip:info@example.com`
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+}
+
+/// The outcome of processing the NAPTR records of a name
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Step {
+    /// The URIs of the first order with matching terminal rules, empty if none matched
+    Uris(Vec<EnumUri>),
+    /// A non-terminal rule matched, the records of the name are to be processed next
+    Next(Name),
+}
+
+/// Processes the NAPTR records of a name against the Application Unique String, see
+///  `application_unique_string`, RFC 6116 section 3.2.1
+///
+/// The records must be ordered, see `NaptrLookup::ordered`. Records which are malformed, or not
+///  for ENUM, are skipped.
+pub(crate) fn process(naptrs: &[&NAPTR], aus: &str) -> Step {
+    let mut uris = Vec::<EnumUri>::new();
+
+    for naptr in naptrs {
+        // the rules of the later orders are only used if none of the earlier ones matched
+        if uris.first().map_or(false, |uri| uri.order() != naptr.order()) {
+            break;
+        }
+
+        if naptr.has_flag('U') && is_e2u(naptr.services()) {
+            if let Some(uri) = rewrite(naptr, aus) {
+                uris.push(EnumUri {
+                    order: naptr.order(),
+                    preference: naptr.preference(),
+                    services: naptr.services().to_string(),
+                    uri: uri,
+                });
+            }
+        } else if naptr.flags().is_empty() && uris.is_empty() {
+            if !naptr.replacement().is_root() {
+                return Step::Next(naptr.replacement().clone());
+            }
+
+            let next = rewrite(naptr, aus).and_then(|next| match Name::from_str(&next) {
+                Ok(name) => Some(name),
+                Err(e) => {
+                    debug!("skipping NAPTR with bad name {}: {}", next, e);
+                    None
+                }
+            });
+            if let Some(next) = next {
+                return Step::Next(next);
+            }
+        }
+    }
+
+    Step::Uris(uris)
+}
+
+/// Returns true for the services of ENUM, `E2U` followed by the Enumservices
+fn is_e2u(services: &str) -> bool {
+    services.to_uppercase().starts_with("E2U")
+}
+
+/// Applies the substitution expression of the record to the input, None if it did not match
+fn rewrite(naptr: &NAPTR, input: &str) -> Option<String> {
+    let substitution = match naptr.substitution() {
+        Ok(Some(substitution)) => substitution,
+        Ok(None) => return None,
+        Err(e) => {
+            debug!("skipping NAPTR: {}", e);
+            return None;
+        }
+    };
+
+    let regex = match RegexBuilder::new(substitution.ere())
+        .case_insensitive(substitution.is_case_insensitive())
+        .build() {
+        Ok(regex) => regex,
+        Err(e) => {
+            debug!("skipping NAPTR with bad regexp {}: {}", naptr.regexp(), e);
+            return None;
+        }
+    };
+
+    regex.captures(input).map(|captures| {
+        substitution.expand(|group| captures.get(group).map(|m| m.as_str()))
+    })
+}
+
+/// The Future returned from ResolverFuture when performing an ENUM lookup
+pub struct LookupEnumFuture(Box<Future<Item = Vec<EnumUri>, Error = io::Error>>);
+
+impl LookupEnumFuture {
+    pub(crate) fn new(future: Box<Future<Item = Vec<EnumUri>, Error = io::Error>>) -> Self {
+        LookupEnumFuture(future)
+    }
+}
+
+impl Future for LookupEnumFuture {
+    type Item = Vec<EnumUri>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.0.poll()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use trust_dns::rr::RData;
+
+    use lookup::{Lookup, NaptrLookup};
+
+    use super::*;
+
+    fn naptr(order: u16, preference: u16, flags: &str, services: &str, regexp: &str) -> RData {
+        RData::NAPTR(NAPTR::new(
+            order,
+            preference,
+            flags.to_string(),
+            services.to_string(),
+            regexp.to_string(),
+            Name::root(),
+        ))
+    }
+
+    fn uris(step: Step) -> Vec<String> {
+        match step {
+            Step::Uris(uris) => uris.iter().map(|uri| uri.uri().to_string()).collect(),
+            Step::Next(name) => panic!("unexpected next name: {}", name),
+        }
+    }
+
+    #[test]
+    fn test_enum_name() {
+        let domain = Name::from_str(E164_ARPA).unwrap();
+        assert_eq!(
+            enum_name("+46-8-9761234", &domain).unwrap(),
+            Name::from_str("4.3.2.1.6.7.9.8.6.4.e164.arpa.").unwrap()
+        );
+        assert_eq!(
+            application_unique_string(" +1 (555) 123-4567").unwrap(),
+            "+15551234567"
+        );
+
+        assert!(enum_name("46-8-9761234", &domain).is_err());
+        assert!(enum_name("+46-8-976123x", &domain).is_err());
+        assert!(enum_name("+", &domain).is_err());
+        assert!(enum_name("+1234567890123456", &domain).is_err());
+    }
+
+    #[test]
+    fn test_process() {
+        let lookup = NaptrLookup::from(Lookup::new(Arc::new(vec![
+            naptr(200, 10, "u", "E2U+mailto", "!^.*$!mailto:info@example.com!"),
+            naptr(100, 20, "u", "E2U+voice:tel", "!^\\+46(.*)$!tel:+46\\1!"),
+            naptr(100, 10, "U", "E2U+sip", "!^\\+46(.*)$!sip:\\1@example.com!"),
+            naptr(100, 30, "u", "E2U+sip", "!^\\+1(.*)$!sip:\\1@example.net!"),
+            naptr(100, 40, "s", "SIP+D2U", "!^.*$!_sip._udp.example.com.!"),
+        ])));
+
+        let naptrs = lookup.ordered();
+        assert_eq!(
+            naptrs.iter().map(|n| (n.order(), n.preference())).collect::<Vec<_>>(),
+            vec![(100, 10), (100, 20), (100, 30), (100, 40), (200, 10)]
+        );
+
+        // the first order matched, so the mailto of the later order is left out
+        assert_eq!(
+            uris(process(&naptrs, "+4689761234")),
+            vec!["sip:89761234@example.com", "tel:+4689761234"]
+        );
+
+        // nothing of the first order matched
+        assert_eq!(
+            uris(process(&naptrs, "+15551234567")),
+            vec!["sip:5551234567@example.net"]
+        );
+        assert_eq!(
+            uris(process(&naptrs, "+4412345678")),
+            vec!["mailto:info@example.com"]
+        );
+    }
+
+    #[test]
+    fn test_process_non_terminal() {
+        let replacement = NAPTR::new(
+            10,
+            10,
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            Name::from_str("enum.example.com.").unwrap(),
+        );
+        assert_eq!(
+            process(&[&replacement], "+4689761234"),
+            Step::Next(Name::from_str("enum.example.com.").unwrap())
+        );
+
+        let lookup = NaptrLookup::from(Lookup::new(Arc::new(vec![
+            naptr(10, 10, "", "", "!^\\+46(.*)$!\\1.se.enum.example.com.!"),
+            naptr(20, 10, "u", "E2U+sip", "!^.*$!sip:info@example.com!"),
+        ])));
+        assert_eq!(
+            process(&lookup.ordered(), "+4689761234"),
+            Step::Next(Name::from_str("89761234.se.enum.example.com.").unwrap())
+        );
+        assert_eq!(
+            uris(process(&lookup.ordered(), "+15551234567")),
+            vec!["sip:info@example.com"]
+        );
+    }
+}
@@ -1,5 +1,11 @@
 #![allow(missing_docs)]
 
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use trust_dns::op::{Query, ResponseCode};
+use trust_dns::rr::rdata::opt::ExtendedError;
 
 error_chain! {
     // The type defined for this error. These are the conventional
@@ -33,4 +39,96 @@ error_chain! {
     // the same as `quick_error!`, but the `from()` and `cause()`
     // syntax is not supported.
     errors {}
-}
\ No newline at end of file
+}
+
+/// A lookup which the NameServers answered with an error, or without any records, it is the
+///  inner error of the `io::Error` of the lookup, see `ResponseError::from_io`
+///
+/// This keeps the Extended DNS Error of the response, RFC 8914, e.g. why a filtering upstream
+///  blocked the name, or why DNSSec validation failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResponseError {
+    query: String,
+    response_code: ResponseCode,
+    extended_error: Option<ExtendedError>,
+}
+
+impl ResponseError {
+    pub(crate) fn new(
+        query: &Query,
+        response_code: ResponseCode,
+        extended_error: Option<ExtendedError>,
+    ) -> Self {
+        ResponseError {
+            query: query.to_string(),
+            response_code,
+            extended_error,
+        }
+    }
+
+    /// Returns the ResponseError of the error of a lookup, None if the lookup failed otherwise,
+    ///  e.g. a timeout
+    pub fn from_io(error: &io::Error) -> Option<&ResponseError> {
+        error.get_ref().and_then(|error| error.downcast_ref())
+    }
+
+    /// The query of the lookup, as text
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// The response code, NoError if the name exists but without records of the type
+    pub fn response_code(&self) -> ResponseCode {
+        self.response_code
+    }
+
+    /// The Extended DNS Error of the response, None if there was none
+    pub fn extended_error(&self) -> Option<&ExtendedError> {
+        self.extended_error.as_ref()
+    }
+
+    fn is_nx(&self) -> bool {
+        match self.response_code {
+            ResponseCode::NoError | ResponseCode::NXDomain => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_nx() {
+            try!(write!(f, "Addr does not exist for: {}", self.query));
+        } else {
+            try!(write!(f, "DNS Error: {}", self.response_code));
+        }
+
+        match self.extended_error {
+            Some(ref extended_error) => write!(f, " ({})", extended_error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl StdError for ResponseError {
+    fn description(&self) -> &str {
+        if self.is_nx() {
+            "address does not exist"
+        } else {
+            "error response"
+        }
+    }
+}
+
+/// NXDomain and NoData are `AddrNotAvailable`, the other errors are `Other`
+impl From<ResponseError> for io::Error {
+    fn from(error: ResponseError) -> Self {
+        let kind = if error.is_nx() {
+            io::ErrorKind::AddrNotAvailable
+        } else {
+            io::ErrorKind::Other
+        };
+
+        io::Error::new(kind, error)
+    }
+}
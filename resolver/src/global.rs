@@ -0,0 +1,210 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A process wide Resolver, for libraries which resolve the odd host name and do not want to
+//!  create, or be handed, a Resolver of their own
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::thread;
+
+use futures::{Future, Stream};
+use futures::sync::mpsc::{unbounded, UnboundedSender};
+use futures::sync::oneshot;
+use tokio_core::reactor::Core;
+
+use config::{ResolverConfig, ResolverOpts};
+use ResolverFuture;
+
+lazy_static! {
+    static ref GLOBAL_RESOLVER: GlobalResolver = GlobalResolver::from_system_conf();
+}
+
+/// A lookup of a host, sent to the thread of the resolver
+struct Request {
+    host: String,
+    complete: oneshot::Sender<io::Result<Vec<IpAddr>>>,
+}
+
+/// A Resolver which runs on a thread of its own, and can be shared by all the threads of the
+///  process
+///
+/// The lookups of all the threads share the one cache. Lookups block the calling thread until
+///  they complete, as with `std::net::ToSocketAddrs`, while the resolver's thread runs them
+///  concurrently. See `global_resolver` for the Resolver of the process, which is created on
+///  first use.
+pub struct GlobalResolver {
+    sender: Mutex<UnboundedSender<Request>>,
+}
+
+impl GlobalResolver {
+    /// Starts the thread of a new resolver, which runs until the GlobalResolver is dropped
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - configuration for the resolver
+    /// * `options` - resolver options for performing lookups
+    pub fn new(config: ResolverConfig, options: ResolverOpts) -> Self {
+        let (sender, receiver) = unbounded::<Request>();
+
+        let spawned = thread::Builder::new()
+            .name("trust-dns-resolver".to_string())
+            .spawn(move || {
+                let mut io_loop = match Core::new() {
+                    Ok(io_loop) => io_loop,
+                    Err(e) => {
+                        error!("global resolver could not start: {}", e);
+                        return;
+                    }
+                };
+                let handle = io_loop.handle();
+                let resolver = ResolverFuture::new(config, options, &handle);
+
+                // the receiver ends once the GlobalResolver is dropped
+                let requests = receiver.for_each(|request| {
+                    let Request { host, complete } = request;
+                    handle.spawn(resolver.lookup_ip(&host).then(move |lookup| {
+                        let ips = lookup.map(|lookup| lookup.iter().collect());
+                        // the caller may have stopped waiting
+                        let _ = complete.send(ips);
+                        Ok(())
+                    }));
+
+                    Ok(())
+                });
+
+                let _ = io_loop.run(requests);
+                debug!("global resolver stopped");
+            });
+
+        if let Err(e) = spawned {
+            error!("global resolver thread could not be spawned: {}", e);
+        }
+
+        GlobalResolver { sender: Mutex::new(sender) }
+    }
+
+    /// Starts a new resolver with the system configuration, or the default configuration if it can
+    ///  not be read, see `ResolverConfig::from_system`
+    pub fn from_system_conf() -> Self {
+        match read_system_conf() {
+            Ok((config, options)) => Self::new(config, options),
+            Err(e) => {
+                warn!("could not read the system configuration, using the defaults: {}", e);
+                Self::new(ResolverConfig::default(), ResolverOpts::default())
+            }
+        }
+    }
+
+    /// Looks up the addresses of the host, see `ResolverFuture::lookup_ip`
+    ///
+    /// A host which is an IP address is returned as is, without a lookup. This blocks until the
+    ///  lookup completes.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
+    pub fn lookup_ip(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+
+        let (complete, receiver) = oneshot::channel();
+        let request = Request {
+            host: host.to_string(),
+            complete,
+        };
+
+        let sent = match self.sender.lock() {
+            Ok(sender) => sender.unbounded_send(request).is_ok(),
+            Err(_) => false,
+        };
+        if !sent {
+            return Err(not_running());
+        }
+
+        match receiver.wait() {
+            Ok(ips) => ips,
+            Err(_) => Err(not_running()),
+        }
+    }
+
+    /// Looks up the addresses of the host, with the port, as `std::net::ToSocketAddrs` does for
+    ///  `(host, port)`
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - string hostname, or IP address
+    /// * `port` - the port of each of the returned addresses
+    pub fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        self.lookup_ip(host).map(|ips| {
+            ips.into_iter().map(|ip| SocketAddr::new(ip, port)).collect()
+        })
+    }
+}
+
+/// Returns the resolver of the process, which is started with the system configuration on first
+///  use, see `GlobalResolver::from_system_conf`
+pub fn global_resolver() -> &'static GlobalResolver {
+    &GLOBAL_RESOLVER
+}
+
+/// Looks up the addresses of the host with the resolver of the process, see `global_resolver`
+///
+/// This is a drop in for `(host, port).to_socket_addrs()`, which uses the resolver of the OS.
+///
+/// ```no_run
+/// let addrs = trust_dns_resolver::resolve("www.example.com.", 443).unwrap();
+/// ```
+pub fn resolve(host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    global_resolver().resolve(host, port)
+}
+
+#[cfg(not(all(target_os = "windows", target_pointer_width = "32")))]
+fn read_system_conf() -> io::Result<(ResolverConfig, ResolverOpts)> {
+    ::system_conf::read_system_conf()
+}
+
+#[cfg(all(target_os = "windows", target_pointer_width = "32"))]
+fn read_system_conf() -> io::Result<(ResolverConfig, ResolverOpts)> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "the system configuration is not supported on this platform",
+    ))
+}
+
+fn not_running() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "the global resolver is not running")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn test_resolve_ip() {
+        let resolver = GlobalResolver::new(ResolverConfig::new(), ResolverOpts::default());
+
+        assert_eq!(
+            resolver.resolve("10.0.0.1", 53).unwrap(),
+            vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 53)]
+        );
+    }
+
+    #[test]
+    fn test_lookup_without_name_servers() {
+        let mut options = ResolverOpts::default();
+        options.use_hosts_file = false;
+
+        // the lookup is run on the resolver's thread, and fails there, there is nothing to ask
+        let resolver = GlobalResolver::new(ResolverConfig::new(), options);
+        assert!(resolver.lookup_ip("www.example.com.").is_err());
+    }
+}
@@ -1,16 +1,27 @@
 //! Hosts result from a configuration of `/etc/hosts`
 
 use std::collections::HashMap;
+#[cfg(windows)]
+use std::env;
 use std::io::{self, BufRead, BufReader};
-use std::fs::File;
+use std::fs::{self, File};
 use std::net::IpAddr;
 use std::str::FromStr;
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
 
+use futures::{Future, Stream};
+use tokio_core::reactor::{Handle, Interval};
 use trust_dns::rr::{Name, RData};
 use lookup::Lookup;
 
+/// Seconds between checks of the hosts file for modifications
+const HOSTS_POLL_INTERVAL_SECS: u64 = 5;
+
+/// Hosts shared between the resolver and the task reloading them, see `watch_hosts_file`
+pub(crate) type SharedHosts = Arc<RwLock<Arc<Hosts>>>;
+
 /// Configuration for the local `/etc/hosts`
 #[derive(Debug, Default, Clone)]
 pub struct Hosts {
@@ -19,10 +30,35 @@ pub struct Hosts {
 }
 
 impl Hosts {
-    /// Creates a new configuration from /etc/hosts, only works for unix like OSes,
-    /// others will return empty configuration
+    /// Creates a new configuration from the system hosts file, `/etc/hosts` on unix like OSes
+    ///  and `%SystemRoot%\System32\drivers\etc\hosts` on Windows. If the file can not be
+    ///  read the configuration is empty.
     pub fn new() -> Hosts {
-        read_hosts_conf("/etc/hosts").unwrap_or_default()
+        read_hosts_conf(hosts_path()).unwrap_or_default()
+    }
+
+    /// Reads the configuration from a file in the hosts file format
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Hosts> {
+        read_hosts_conf(path)
+    }
+
+    /// Adds an address for the name, in addition to any existing addresses for it
+    ///
+    /// This allows static overrides to be supplied to the resolver, see
+    ///  `ResolverFuture::set_hosts`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name to override, matched without regard to case
+    /// * `rdata` - the address of the name, only `RData::A` and `RData::AAAA` are returned from
+    ///             IP lookups
+    pub fn insert(&mut self, name: Name, rdata: RData) {
+        let lookup = self.by_name
+            .get(&name)
+            .map(|lookup| lookup.append(Lookup::new(Arc::new(vec![rdata.clone()]))))
+            .unwrap_or_else(|| Lookup::new(Arc::new(vec![rdata])));
+
+        self.by_name.insert(name, lookup);
     }
 
     /// lookup_static_host looks up the addresses for the given host from /etc/hosts.
@@ -36,8 +72,77 @@ impl Hosts {
     }
 }
 
+/// The location of the system hosts file
+#[cfg(not(windows))]
+fn hosts_path() -> PathBuf {
+    PathBuf::from("/etc/hosts")
+}
+
+/// The location of the system hosts file
+#[cfg(windows)]
+fn hosts_path() -> PathBuf {
+    let mut path = env::var_os("SystemRoot")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("C:\\Windows"));
+    path.push("System32\\drivers\\etc\\hosts");
+    path
+}
+
+/// Loads the system hosts file, and spawns a task onto the reactor which reloads it whenever its
+///  modification time changes. The task ends once the returned hosts are dropped.
+pub(crate) fn watch_system_hosts(reactor: &Handle) -> SharedHosts {
+    watch_hosts_file(hosts_path(), reactor)
+}
+
+fn watch_hosts_file(path: PathBuf, reactor: &Handle) -> SharedHosts {
+    let mut modified = modified_time(&path);
+    let hosts = Arc::new(RwLock::new(
+        Arc::new(read_hosts_conf(&path).unwrap_or_default()),
+    ));
+
+    let interval = match Interval::new(Duration::from_secs(HOSTS_POLL_INTERVAL_SECS), reactor) {
+        Ok(interval) => interval,
+        Err(e) => {
+            warn!("changes to {:?} will be ignored: {}", path, e);
+            return hosts;
+        }
+    };
+
+    let weak_hosts = Arc::downgrade(&hosts);
+    reactor.spawn(
+        interval
+            .map_err(|e| warn!("error polling hosts file: {}", e))
+            .for_each(move |()| {
+                // the resolver is gone, stop watching
+                let hosts = match weak_hosts.upgrade() {
+                    Some(hosts) => hosts,
+                    None => return Err(()),
+                };
+
+                let now_modified = modified_time(&path);
+                if now_modified != modified {
+                    debug!("reloading hosts file: {:?}", path);
+                    modified = now_modified;
+
+                    // a removed file leaves no static hosts
+                    let reloaded = Arc::new(read_hosts_conf(&path).unwrap_or_default());
+                    if let Ok(mut hosts) = hosts.write() {
+                        *hosts = reloaded;
+                    }
+                }
+
+                Ok(())
+            }),
+    );
+
+    hosts
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
 /// parse configuration from `/etc/hosts`
-#[cfg(unix)]
 pub fn read_hosts_conf<P: AsRef<Path>>(path: P) -> io::Result<Hosts> {
     let mut hosts = Hosts {
         by_name: HashMap::new(),
@@ -89,14 +194,6 @@ pub fn read_hosts_conf<P: AsRef<Path>>(path: P) -> io::Result<Hosts> {
     Ok(hosts)
 }
 
-#[cfg(not(unix))]
-pub fn read_hosts_conf<P: AsRef<Path>>(path: P) -> io::Result<Hosts> {
-    Err(io::Error::new(
-        io::ErrorKind::Other,
-        "Non-Posix systems currently not supported".to_string(),
-    ))
-}
-
 /// parse &str to RData::A or RData::AAAA
 pub fn parse_literal_ip(addr: &str) -> Option<RData> {
     match IpAddr::from_str(addr) {
@@ -109,7 +206,6 @@ pub fn parse_literal_ip(addr: &str) -> Option<RData> {
     }
 }
 
-#[cfg(unix)]
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -136,6 +232,33 @@ mod tests {
         assert!(parse_literal_ip("example.com").is_none());
     }
 
+    #[test]
+    fn test_insert() {
+        let mut hosts = Hosts::default();
+        let name = Name::from_str("override.example.com").unwrap();
+
+        hosts.insert(name.clone(), RData::A(Ipv4Addr::new(10, 0, 0, 1)));
+        hosts.insert(
+            name.clone(),
+            RData::AAAA(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)),
+        );
+
+        let rdatas = hosts
+            .lookup_static_host(&Name::from_str("Override.Example.Com.").unwrap())
+            .unwrap()
+            .iter()
+            .map(|r| r.to_owned())
+            .collect::<Vec<RData>>();
+
+        assert_eq!(
+            rdatas,
+            vec![
+                RData::A(Ipv4Addr::new(10, 0, 0, 1)),
+                RData::AAAA(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)),
+            ]
+        );
+    }
+
     #[test]
     fn test_read_hosts_conf() {
         let path = format!("{}/hosts", tests_dir());
@@ -0,0 +1,39 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS over HTTPS, RFC 8484, connections for the NameServerPool
+
+use std::io;
+use std::net::SocketAddr;
+
+use futures::Future;
+use rustls::ClientConfig;
+use tokio_core::reactor::Handle;
+use trust_dns_https::{HttpsClientStream, HttpsClientStreamBuilder};
+use trust_dns_proto::DnsStreamHandle;
+use webpki_roots;
+
+/// Creates a new HTTPS stream to the name server, certificates are validated with the Mozilla
+///  trusted roots, see the `webpki-roots` crate
+pub(crate) fn new_https_stream(
+    socket_addr: SocketAddr,
+    dns_name: String,
+    https_template: Option<String>,
+    reactor: &Handle,
+) -> (Box<Future<Item = HttpsClientStream, Error = io::Error>>, Box<DnsStreamHandle>) {
+    let mut client_config = ClientConfig::new();
+    client_config.root_store.add_server_trust_anchors(
+        &webpki_roots::TLS_SERVER_ROOTS,
+    );
+
+    let mut builder = HttpsClientStreamBuilder::with_client_config(client_config);
+    if let Some(https_template) = https_template {
+        builder.set_url_template(https_template);
+    }
+
+    builder.build(socket_addr, dns_name, reactor)
+}
@@ -71,6 +71,14 @@
 //! let mut response = resolver.lookup_ip("www.example.com.").unwrap();
 //! ```
 //!
+//! ## Using the process wide Resolver
+//!
+//! Libraries which only need the odd address, and would rather not be handed a Resolver, can use the `resolve` function, in place of `std::net::ToSocketAddrs`. It shares one resolver, with the host system config, and its cache between all the threads of the process, the resolver is started on first use on a thread of its own, see `GlobalResolver`.
+//!
+//! ```rust,no_run
+//! let addrs = trust_dns_resolver::resolve("www.example.com.", 443).unwrap();
+//! ```
+//!
 //! ## Using the Tokio/Async Resolver
 //!
 //! For more advanced asynchronous usage, the ResolverFuture is integrated with Tokio. In fact, the ResolverFuture is used by the synchronous Resolver for all lookups.
@@ -130,26 +138,54 @@ extern crate error_chain;
 extern crate futures;
 extern crate lalrpop_util;
 #[macro_use]
+extern crate lazy_static;
+#[macro_use]
 extern crate log;
 extern crate lru_cache;
+extern crate rand;
+#[cfg(feature = "e164")]
+extern crate regex;
+#[cfg(any(feature = "dns-over-rustls", feature = "dns-over-https"))]
+extern crate rustls;
 extern crate tokio_core;
 extern crate trust_dns;
+#[cfg(feature = "dns-over-https")]
+extern crate trust_dns_https;
+#[cfg(feature = "dns-over-native-tls")]
+extern crate trust_dns_native_tls;
 extern crate trust_dns_proto;
+#[cfg(feature = "dns-over-rustls")]
+extern crate trust_dns_rustls;
+#[cfg(any(feature = "dns-over-rustls", feature = "dns-over-https"))]
+extern crate webpki_roots;
 #[cfg(all(target_os = "windows", target_pointer_width = "64"))]
 extern crate ipconfig;
 
 pub mod config;
+#[cfg(feature = "e164")]
+pub mod e164;
 pub mod error;
+mod global;
 pub mod lookup_ip;
 pub mod lookup;
 pub mod lookup_state;
 #[doc(hidden)]
 pub mod name_server_pool;
+pub mod name_server_stats;
+pub mod observer;
+#[cfg(feature = "psl")]
+pub mod psl;
 mod resolver;
 pub mod system_conf;
 mod resolver_future;
 mod hosts;
+mod search;
+#[cfg(feature = "dns-over-https")]
+mod https;
+#[cfg(feature = "dns-over-tls")]
+mod tls;
 
+pub use global::{global_resolver, resolve, GlobalResolver};
 pub use resolver::Resolver;
 pub use resolver_future::ResolverFuture;
 pub use hosts::Hosts;
@@ -9,36 +9,254 @@
 //! Lookup result from a resolution of ipv4 and ipv6 records with a Resolver.
 
 use std::error::Error as StdError;
+use std::cmp;
 use std::io;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::mem;
 use std::slice::Iter;
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::{Async, future, Future, Poll, task};
+use rand;
+use rand::Rng;
 
-use trust_dns::client::{BasicClientHandle, ClientHandle, RetryClientHandle, SecureClientHandle};
+use trust_dns::client::{BasicClientHandle, ClientHandle, RetryClientHandle};
+#[cfg(feature = "dnssec")]
+use trust_dns::client::SecureClientHandle;
 use trust_dns::error::ClientError;
 use trust_dns::op::{Message, Query};
-use trust_dns::rr::{Name, RecordType, RData};
+use trust_dns::rr::{Name, Record, RecordType, RData};
 use trust_dns::rr::rdata;
+use trust_dns::rr::rdata::sshfp;
+use trust_dns::rr::rdata::opt::EdnsOption;
 use trust_dns_proto::DnsHandle;
 
+use config::Protocol;
 use lookup_state::CachingClient;
 use name_server_pool::{ConnectionProvider, NameServerPool, StandardConnection};
 
+/// The DNSSec security state of records, the four states of
+///  [RFC 4035 section 4.3](https://tools.ietf.org/html/rfc4035#section-4.3), see
+///  `ResolverOpts::validate`
+///
+/// The validating Resolver fails lookups of records which are bogus, or proven to be unsigned,
+///  rather than returning them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Proof {
+    /// The records were validated from a key of the `ResolverConfig::trust_anchor`
+    Secure,
+    /// The records are proven to be unsigned, there is a chain of trust to a zone which is not
+    ///  signed
+    Insecure,
+    /// The records were not validated, validation is disabled, or they are not from a NameServer,
+    ///  e.g. the hosts file
+    Indeterminate,
+    /// The records should have validated, but did not, e.g. the signatures have expired
+    Bogus,
+}
+
+impl Proof {
+    /// The weaker of the two, i.e. the state of a set of records with both
+    fn weakest(self, other: Proof) -> Proof {
+        match (self, other) {
+            (Proof::Bogus, _) | (_, Proof::Bogus) => Proof::Bogus,
+            (Proof::Indeterminate, _) |
+            (_, Proof::Indeterminate) => Proof::Indeterminate,
+            (Proof::Insecure, _) | (_, Proof::Insecure) => Proof::Insecure,
+            (Proof::Secure, Proof::Secure) => Proof::Secure,
+        }
+    }
+}
+
+/// The NameServer which answered the query of a Lookup, see `LookupSource::name_server`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NameServerSource {
+    socket_addr: SocketAddr,
+    protocol: Protocol,
+    rtt: Duration,
+}
+
+impl NameServerSource {
+    pub(crate) fn new(socket_addr: SocketAddr, protocol: Protocol, rtt: Duration) -> Self {
+        NameServerSource {
+            socket_addr,
+            protocol,
+            rtt,
+        }
+    }
+
+    /// The address of the NameServer
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.socket_addr
+    }
+
+    /// The protocol of the NameServer, e.g. `Protocol::Tcp` where a truncated UDP response was
+    ///  retried over TCP
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// The time from sending the query to the NameServer to receiving its response
+    pub fn rtt(&self) -> Duration {
+        self.rtt
+    }
+}
+
+/// Where the records of a Lookup came from, and how long it took, see `Lookup::source`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LookupSource {
+    name_server: Option<NameServerSource>,
+    cache_ttl: Option<u32>,
+    elapsed: Duration,
+}
+
+impl LookupSource {
+    /// Returns true if the records were served from the cache, rather than from a NameServer
+    pub fn is_cached(&self) -> bool {
+        self.cache_ttl.is_some()
+    }
+
+    /// The seconds the records had left in the cache when they were served from it, None if they
+    ///  were not
+    pub fn cache_ttl(&self) -> Option<u32> {
+        self.cache_ttl
+    }
+
+    /// The NameServer which answered, for cached records the one which answered when they were
+    ///  cached
+    ///
+    /// None if the records did not come from a NameServer, e.g. the hosts file, or it is not
+    ///  known, e.g. for records added with `ResolverFuture::query_with_server`. For a CNAME chain
+    ///  this is the NameServer which answered for the final name.
+    pub fn name_server(&self) -> Option<NameServerSource> {
+        self.name_server
+    }
+
+    /// The time the lookup took, from the cache or the NameServers, including the queries for
+    ///  the names of a CNAME chain
+    ///
+    /// Zero for lookups which did not go through the cache, e.g. from the hosts file.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The source of the records of both, e.g. of a lookup of both IPv4 and IPv6 addresses: only
+    ///  cached if both were, and the longer of the times
+    fn merge(&self, other: &LookupSource) -> LookupSource {
+        let cache_ttl = match (self.cache_ttl, other.cache_ttl) {
+            (Some(ttl), Some(other_ttl)) => Some(cmp::min(ttl, other_ttl)),
+            _ => None,
+        };
+
+        LookupSource {
+            name_server: self.name_server.or(other.name_server),
+            cache_ttl,
+            elapsed: cmp::max(self.elapsed, other.elapsed),
+        }
+    }
+}
+
 /// Result of a DNS query when querying for any record type supported by the TRust-DNS Client library.
 ///
 /// For IP resolution see LookIp, as it has more features for A and AAAA lookups.
 #[derive(Debug, Clone)]
 pub struct Lookup {
     rdatas: Arc<Vec<RData>>,
+    edns_options: Arc<Vec<EdnsOption>>,
+    additionals: Arc<Vec<Record>>,
+    proofs: Arc<Vec<Proof>>,
+    rrsigs: Arc<Vec<Record>>,
+    cname_chain: Arc<Vec<Name>>,
+    responses: Arc<Vec<Message>>,
+    source: LookupSource,
 }
 
 impl Lookup {
     /// Return new instance with given rdatas
     pub fn new(rdatas: Arc<Vec<RData>>) -> Self {
-        Lookup { rdatas }
+        Self::with_edns_options(rdatas, Arc::new(vec![]))
+    }
+
+    /// Return new instance with given rdatas, and the EDNS options of the response they came from
+    pub fn with_edns_options(rdatas: Arc<Vec<RData>>, edns_options: Arc<Vec<EdnsOption>>) -> Self {
+        let proofs = Arc::new(vec![Proof::Indeterminate; rdatas.len()]);
+        Lookup {
+            rdatas,
+            edns_options,
+            additionals: Arc::new(vec![]),
+            proofs,
+            rrsigs: Arc::new(vec![]),
+            cname_chain: Arc::new(vec![]),
+            responses: Arc::new(vec![]),
+            source: LookupSource::default(),
+        }
+    }
+
+    /// Sets the security state of all of the records, see `proof`
+    pub(crate) fn with_proof(self, proof: Proof) -> Self {
+        let proofs = vec![proof; self.rdatas.len()];
+        self.with_proofs(proofs)
+    }
+
+    /// Sets the security state of each of the records, in the order of the records
+    pub(crate) fn with_proofs(mut self, proofs: Vec<Proof>) -> Self {
+        debug_assert_eq!(proofs.len(), self.rdatas.len());
+        self.proofs = Arc::new(proofs);
+        self
+    }
+
+    /// Adds the RRSIGs which validated the records, see `rrsigs`
+    pub(crate) fn with_rrsigs(mut self, rrsigs: Vec<Record>) -> Self {
+        self.rrsigs = Arc::new(rrsigs);
+        self
+    }
+
+    /// Sets the names followed through CNAMEs to the records, see `cname_chain`
+    pub(crate) fn with_cname_chain(mut self, cname_chain: Vec<Name>) -> Self {
+        self.cname_chain = Arc::new(cname_chain);
+        self
+    }
+
+    /// Keeps the response the records are from, see `responses`
+    pub(crate) fn with_responses(mut self, responses: Vec<Message>) -> Self {
+        self.responses = Arc::new(responses);
+        self
+    }
+
+    /// Sets the NameServer which answered, see `source`
+    pub(crate) fn with_name_server(mut self, name_server: Option<NameServerSource>) -> Self {
+        self.source.name_server = name_server;
+        self
+    }
+
+    /// Marks the records as served from the cache, with the seconds they had left in it
+    pub(crate) fn with_cache_ttl(mut self, cache_ttl: u32) -> Self {
+        self.source.cache_ttl = Some(cache_ttl);
+        self
+    }
+
+    /// Sets the time the lookup took, see `source`
+    pub(crate) fn with_elapsed(mut self, elapsed: Duration) -> Self {
+        self.source.elapsed = elapsed;
+        self
+    }
+
+    /// Adds the address records of the targets, see `additionals`
+    pub(crate) fn with_additionals(mut self, additionals: Vec<Record>) -> Self {
+        self.additionals = Arc::new(additionals);
+        self
+    }
+
+    /// Reorders the records, each keeping its security state, the order is of the indexes of the
+    ///  records, see `ResolverOpts::answer_order`
+    pub(crate) fn with_order(mut self, order: &[usize]) -> Self {
+        debug_assert_eq!(order.len(), self.rdatas.len());
+        let rdatas = order.iter().map(|&i| self.rdatas[i].clone()).collect();
+        let proofs = order.iter().map(|&i| self.proofs[i]).collect();
+        self.rdatas = Arc::new(rdatas);
+        self.proofs = Arc::new(proofs);
+        self
     }
 
     /// Returns a borrowed iterator of the returned IPs
@@ -46,6 +264,90 @@ impl Lookup {
         LookupIter(self.rdatas.iter())
     }
 
+    /// Returns the EDNS options of the response from the NameServer, e.g. policy information
+    ///  from a filtering upstream
+    ///
+    /// These are cached along with the records, empty if the response had no options or the
+    ///  records did not come from a NameServer, e.g. the hosts file.
+    pub fn edns_options(&self) -> &[EdnsOption] {
+        &self.edns_options
+    }
+
+    /// Returns the A and AAAA records of the targets of SRV, MX and NS records, where the
+    ///  NameServer included these in the additional section of the response
+    ///
+    /// Empty if there were none, in which case the targets need to be looked up.
+    pub fn additionals(&self) -> &[Record] {
+        &self.additionals
+    }
+
+    /// Returns the security state of the records together, i.e. the weakest of them, only
+    ///  `Proof::Secure` if all of them were validated, `Proof::Indeterminate` if there are none
+    pub fn proof(&self) -> Proof {
+        let mut proofs = self.proofs.iter().cloned();
+        match proofs.next() {
+            Some(first) => proofs.fold(first, Proof::weakest),
+            None => Proof::Indeterminate,
+        }
+    }
+
+    /// Returns a borrowed iterator of the returned records, each with its security state, e.g. to
+    ///  only use the validated records of a lookup of IPv4 and IPv6 addresses
+    pub fn iter_with_proof<'a>(&'a self) -> Box<Iterator<Item = (&'a RData, Proof)> + 'a> {
+        Box::new(self.rdatas.iter().zip(self.proofs.iter().cloned()))
+    }
+
+    /// Returns the RRSIGs of the response which validated the records, e.g. for a DANE verifier
+    ///  to check the signatures of TLSA records itself
+    ///
+    /// Empty unless the records are `Proof::Secure`, only the signatures of the final name of a
+    ///  CNAME chain are kept.
+    pub fn rrsigs(&self) -> &[Record] {
+        &self.rrsigs
+    }
+
+    /// Returns the names resolved through to reach the records, the queried name, each of the
+    ///  CNAMEs followed from it, and last the name of the records, e.g. to log that
+    ///  `www.example.com.` was resolved through `www.example.com.edgekey.net.`
+    ///
+    /// Empty if the records are at the queried name. The length of the chain is limited by
+    ///  `ResolverOpts::max_cname_chain`, and lookups through a CNAME loop fail.
+    pub fn cname_chain(&self) -> &[Name] {
+        &self.cname_chain
+    }
+
+    /// Returns the responses from the NameServer which the records were taken from, with the
+    ///  header flags, the authority and additional sections and the EDNS options, e.g. for a
+    ///  diagnostic tool
+    ///
+    /// Empty unless `ResolverOpts::keep_response` is set. There is one response for each query of
+    ///  the lookup, that of the final name of a CNAME chain, and two for a lookup of both IPv4 and
+    ///  IPv6 addresses. For a cached lookup these are the responses the records were cached from,
+    ///  with the TTLs as they were then.
+    pub fn responses(&self) -> &[Message] {
+        &self.responses
+    }
+
+    /// Returns where the records came from, the cache or which NameServer, and how long the
+    ///  lookup took, e.g. to find out why an answer was returned
+    pub fn source(&self) -> &LookupSource {
+        &self.source
+    }
+
+    /// Returns the addresses of the name from the additional records
+    fn additional_ips<'a>(&'a self, name: &'a Name) -> Box<Iterator<Item = IpAddr> + 'a> {
+        Box::new(
+            self.additionals
+                .iter()
+                .filter(move |r| r.name() == name)
+                .filter_map(|r| match *r.rdata() {
+                    RData::A(ip) => Some(IpAddr::V4(ip)),
+                    RData::AAAA(ip) => Some(IpAddr::V6(ip)),
+                    _ => None,
+                }),
+        )
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
         self.rdatas.is_empty()
     }
@@ -60,7 +362,40 @@ impl Lookup {
         rdatas.extend_from_slice(&*self.rdatas);
         rdatas.extend_from_slice(&*other.rdatas);
 
-        Self::new(Arc::new(rdatas))
+        let mut edns_options = (*self.edns_options).clone();
+        for option in other.edns_options.iter() {
+            if !edns_options.contains(option) {
+                edns_options.push(option.clone());
+            }
+        }
+
+        let mut additionals = (*self.additionals).clone();
+        additionals.extend_from_slice(&*other.additionals);
+
+        let mut proofs = (*self.proofs).clone();
+        proofs.extend_from_slice(&*other.proofs);
+
+        let mut rrsigs = (*self.rrsigs).clone();
+        rrsigs.extend_from_slice(&*other.rrsigs);
+
+        // e.g. the A and AAAA records of a name, both are reached through the same chain
+        let cname_chain = if self.cname_chain.is_empty() {
+            (*other.cname_chain).clone()
+        } else {
+            (*self.cname_chain).clone()
+        };
+
+        let mut responses = (*self.responses).clone();
+        responses.extend_from_slice(&*other.responses);
+
+        let mut lookup = Self::with_edns_options(Arc::new(rdatas), Arc::new(edns_options))
+            .with_additionals(additionals)
+            .with_proofs(proofs)
+            .with_rrsigs(rrsigs)
+            .with_cname_chain(cname_chain)
+            .with_responses(responses);
+        lookup.source = self.source.merge(&other.source);
+        lookup
     }
 }
 
@@ -80,6 +415,7 @@ impl<'a> Iterator for LookupIter<'a> {
 #[doc(hidden)]
 pub enum LookupEither<C: ClientHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> {
     Retry(RetryClientHandle<NameServerPool<C, P>>),
+    #[cfg(feature = "dnssec")]
     Secure(SecureClientHandle<RetryClientHandle<NameServerPool<C, P>>>),
 }
 
@@ -90,6 +426,7 @@ impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> DnsHandle for Looku
     fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = Self::Error>> {
         match *self {
             LookupEither::Retry(ref mut c) => c.send(message),
+            #[cfg(feature = "dnssec")]
             LookupEither::Secure(ref mut c) => c.send(message),
         }
     }
@@ -99,6 +436,7 @@ impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> ClientHandle for Lo
     fn is_verifying_dnssec(&self) -> bool {
         match *self {
             LookupEither::Retry(ref c) => c.is_verifying_dnssec(),
+            #[cfg(feature = "dnssec")]
             LookupEither::Secure(ref c) => c.is_verifying_dnssec(),
         }
     }
@@ -162,6 +500,20 @@ impl<C: ClientHandle + 'static> InnerLookupFuture<C> {
         }
     }
 
+    /// Wraps a lookup of the record type which has already been started, e.g. a search
+    pub(crate) fn from_future(
+        client_cache: CachingClient<C>,
+        record_type: RecordType,
+        future: Box<Future<Item = Lookup, Error = io::Error>>,
+    ) -> Self {
+        InnerLookupFuture {
+            client_cache,
+            names: vec![],
+            record_type,
+            future,
+        }
+    }
+
     pub(crate) fn error<E: StdError>(client_cache: CachingClient<C>, error: E) -> Self {
         return InnerLookupFuture {
             // errors on names don't need to be cheap... i.e. this clone is unfortunate in this case.
@@ -208,6 +560,21 @@ impl $l {
     pub fn iter(&self) -> $i {
         $i(self.0.iter())
     }
+
+    /// Returns the security state of the records, see `Lookup::proof`
+    pub fn proof(&self) -> Proof {
+        self.0.proof()
+    }
+
+    /// Returns the RRSIGs which validated the records, see `Lookup::rrsigs`
+    pub fn rrsigs(&self) -> &[Record] {
+        self.0.rrsigs()
+    }
+
+    /// Returns the names resolved through to reach the records, see `Lookup::cname_chain`
+    pub fn cname_chain(&self) -> &[Name] {
+        self.0.cname_chain()
+    }
 }
 
 impl From<Lookup> for $l {
@@ -292,6 +659,231 @@ lookup_type!(
     RData::TXT,
     rdata::TXT
 );
+lookup_type!(
+    SoaLookup,
+    SoaLookupIter,
+    SoaLookupFuture,
+    RData::SOA,
+    rdata::SOA
+);
+lookup_type!(NsLookup, NsLookupIter, NsLookupFuture, RData::NS, Name);
+lookup_type!(
+    SshfpLookup,
+    SshfpLookupIter,
+    SshfpLookupFuture,
+    RData::SSHFP,
+    rdata::SSHFP
+);
+lookup_type!(
+    SvcbLookup,
+    SvcbLookupIter,
+    SvcbLookupFuture,
+    RData::SVCB,
+    rdata::SVCB
+);
+lookup_type!(
+    HttpsLookup,
+    HttpsLookupIter,
+    HttpsLookupFuture,
+    RData::HTTPS,
+    rdata::SVCB
+);
+lookup_type!(
+    NaptrLookup,
+    NaptrLookupIter,
+    NaptrLookupFuture,
+    RData::NAPTR,
+    rdata::NAPTR
+);
+
+impl SrvLookup {
+    /// Returns true if the service is decidedly not available at the domain, RFC 2782
+    ///
+    /// That is when there are SRV records and the target of each of them is `.`, the root; a
+    ///  client should then not fall back to any default name or port of the service.
+    pub fn is_unavailable(&self) -> bool {
+        self.iter().next().is_some() && self.iter().all(|srv| srv.target().is_root())
+    }
+
+    /// Returns the targets in the order in which they should be tried, per RFC 2782
+    ///
+    /// The lowest priority comes first, within a priority the targets are picked at random,
+    ///  weighted by their weight. Targets of `.`, see `is_unavailable`, are left out.
+    pub fn ordered(&self) -> Vec<&rdata::SRV> {
+        self.ordered_with(&mut rand::thread_rng())
+    }
+
+    /// Returns the targets in the order in which they should be tried, see `ordered`, picking
+    ///  within a priority with the given random number generator, e.g. a seeded one for tests
+    pub fn ordered_with<R: Rng>(&self, rng: &mut R) -> Vec<&rdata::SRV> {
+        order_srvs(
+            self.iter().filter(|srv| !srv.target().is_root()).collect(),
+            |total| rng.gen_range(0, total + 1),
+        )
+    }
+
+    /// Returns the addresses of the target from the additional records of the response, see
+    ///  `Lookup::additionals`
+    pub fn ip_iter<'a>(&'a self, target: &'a Name) -> Box<Iterator<Item = IpAddr> + 'a> {
+        self.0.additional_ips(target)
+    }
+}
+
+impl MxLookup {
+    /// Returns the exchanges in the order in which they should be tried, lowest preference
+    ///  first, RFC 5321 section 5.1
+    pub fn ordered(&self) -> Vec<&rdata::MX> {
+        let mut mxs = self.iter().collect::<Vec<_>>();
+        mxs.sort_by_key(|mx| mx.preference());
+        mxs
+    }
+
+    /// Returns the addresses of the exchange from the additional records of the response, see
+    ///  `Lookup::additionals`
+    pub fn ip_iter<'a>(&'a self, exchange: &'a Name) -> Box<Iterator<Item = IpAddr> + 'a> {
+        self.0.additional_ips(exchange)
+    }
+}
+
+impl NsLookup {
+    /// Returns the addresses of the name server from the additional records of the response,
+    ///  see `Lookup::additionals`
+    pub fn ip_iter<'a>(&'a self, name_server: &'a Name) -> Box<Iterator<Item = IpAddr> + 'a> {
+        self.0.additional_ips(name_server)
+    }
+}
+
+/// The outcome of the verification of an SSH host key against the SSHFP records of the host
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SshfpVerdict {
+    /// A fingerprint of the records matched the host key, and the records are secure, the key
+    ///  may be trusted
+    Matched,
+    /// There are secure fingerprints for the algorithm of the host key, but none matched, the
+    ///  key must not be trusted
+    Mismatch,
+    /// There are no secure fingerprints for the algorithm of the host key, or of a supported
+    ///  fingerprint type, the key must be verified some other way
+    NoFingerprints,
+    /// The records are not proven secure, see `Proof`, so they must not be used to trust the
+    ///  key, RFC 4255 section 2.4
+    Insecure,
+}
+
+impl SshfpLookup {
+    /// Verifies the host key of an SSH server against the fingerprints of the lookup
+    ///
+    /// The records must be `Proof::Secure`, which requires `ResolverOpts::validate`.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - the algorithm of the host key
+    /// * `host_key` - the public host key, in the wire format of the SSH protocol, i.e. the base64
+    ///                decoded key of `known_hosts`
+    pub fn verify(&self, algorithm: sshfp::Algorithm, host_key: &[u8]) -> SshfpVerdict {
+        if self.proof() != Proof::Secure {
+            return SshfpVerdict::Insecure;
+        }
+
+        let mut verdict = SshfpVerdict::NoFingerprints;
+        for sshfp in self.iter().filter(|sshfp| sshfp.algorithm() == algorithm) {
+            match sshfp.matches(algorithm, host_key) {
+                Ok(true) => return SshfpVerdict::Matched,
+                Ok(false) => verdict = SshfpVerdict::Mismatch,
+                Err(e) => debug!("skipping SSHFP: {}", e),
+            }
+        }
+
+        verdict
+    }
+}
+
+/// Adds the helpers of service bindings, RFC 9460, to the SVCB and HTTPS lookups
+macro_rules! svcb_lookup {
+    ($l:ident) => {
+impl $l {
+    /// Returns the target of the AliasMode record, if there is one, which is to be looked up
+    ///  in place of the name, RFC 9460 section 2.4.2
+    ///
+    /// The ServiceMode records are then to be ignored. If there are several AliasMode records,
+    ///  which there should not be, the first is returned.
+    pub fn alias(&self) -> Option<&Name> {
+        self.iter().find(|svcb| svcb.is_alias()).map(|svcb| svcb.target_name())
+    }
+
+    /// Returns true if the service is decidedly not available at the name, i.e. the target of
+    ///  the AliasMode record is `.`, RFC 9460 section 2.5.1
+    pub fn is_unavailable(&self) -> bool {
+        self.alias().map(|target| target.is_root()).unwrap_or(false)
+    }
+
+    /// Returns the ServiceMode records in the order in which they should be tried, lowest
+    ///  priority first, RFC 9460 section 2.4.1
+    ///
+    /// A target of `.` is the name which was looked up, or the target of the alias. Records with
+    ///  mandatory keys the client does not support should be skipped, see `SVCB::is_compatible`.
+    pub fn ordered(&self) -> Vec<&rdata::SVCB> {
+        let mut svcbs = self.iter().filter(|svcb| !svcb.is_alias()).collect::<Vec<_>>();
+        svcbs.sort_by_key(|svcb| svcb.svc_priority());
+        svcbs
+    }
+
+    /// Returns the addresses of the target from the additional records of the response, see
+    ///  `Lookup::additionals`, these take precedence over the hints of the records
+    pub fn ip_iter<'a>(&'a self, target: &'a Name) -> Box<Iterator<Item = IpAddr> + 'a> {
+        self.0.additional_ips(target)
+    }
+}
+    }
+}
+
+svcb_lookup!(SvcbLookup);
+svcb_lookup!(HttpsLookup);
+
+impl NaptrLookup {
+    /// Returns the rules in the order in which they must be processed, lowest order first, and
+    ///  within an order lowest preference first, RFC 3403 section 4.1
+    ///
+    /// Once a rule of an order has matched, the rules of higher orders are not to be processed.
+    pub fn ordered(&self) -> Vec<&rdata::NAPTR> {
+        let mut naptrs = self.iter().collect::<Vec<_>>();
+        naptrs.sort_by_key(|naptr| (naptr.order(), naptr.preference()));
+        naptrs
+    }
+}
+
+/// Orders the SRV records per the selection algorithm of RFC 2782
+///
+/// `random` returns a number from zero up to and including its argument, the sum of the weights
+///  of the remaining records of a priority.
+fn order_srvs<'a, F>(mut srvs: Vec<&'a rdata::SRV>, mut random: F) -> Vec<&'a rdata::SRV>
+where
+    F: FnMut(u32) -> u32,
+{
+    // the records of weight zero first, these then only have a small chance of being picked
+    srvs.sort_by_key(|srv| (srv.priority(), srv.weight() != 0));
+
+    let mut ordered = Vec::with_capacity(srvs.len());
+    while !srvs.is_empty() {
+        let priority = srvs[0].priority();
+        let count = srvs.iter().take_while(|srv| srv.priority() == priority).count();
+        let total = srvs[..count].iter().map(|srv| srv.weight() as u32).sum::<u32>();
+        let pick = random(total);
+
+        let mut running_sum = 0;
+        let index = srvs[..count]
+            .iter()
+            .position(|srv| {
+                running_sum += srv.weight() as u32;
+                running_sum >= pick
+            })
+            .unwrap_or(0);
+
+        ordered.push(srvs.remove(index));
+    }
+
+    ordered
+}
 
 #[cfg(test)]
 pub mod tests {
@@ -341,6 +933,34 @@ pub mod tests {
         Ok(message)
     }
 
+    pub fn srv_message() -> ClientResult<Message> {
+        let target = Name::from_labels(vec!["target", "example", "com"]);
+        let mut message = Message::new();
+        message.insert_answers(vec![
+            Record::from_rdata(
+                Name::root(),
+                86400,
+                RecordType::SRV,
+                RData::SRV(rdata::SRV::new(0, 0, 443, target.clone()))
+            ),
+        ]);
+        message.insert_additionals(vec![
+            Record::from_rdata(
+                target,
+                86400,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(127, 0, 0, 2))
+            ),
+            Record::from_rdata(
+                Name::from_labels(vec!["other", "example", "com"]),
+                86400,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(127, 0, 0, 3))
+            ),
+        ]);
+        Ok(message)
+    }
+
     pub fn empty() -> ClientResult<Message> {
         Ok(Message::new())
     }
@@ -394,4 +1014,182 @@ pub mod tests {
             io::ErrorKind::AddrNotAvailable
         );
     }
+
+    #[test]
+    fn test_srv_additionals() {
+        let lookup = SrvLookup::from(
+            InnerLookupFuture::lookup(
+                vec![Name::root()],
+                RecordType::SRV,
+                CachingClient::new(0, mock(vec![srv_message()])),
+            ).wait()
+                .unwrap(),
+        );
+
+        // only the address of the target is kept
+        assert_eq!(lookup.0.additionals().len(), 1);
+        let target = lookup.iter().next().unwrap().target().clone();
+        assert_eq!(
+            lookup.ip_iter(&target).collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2))]
+        );
+    }
+
+    #[test]
+    fn test_order_srvs() {
+        let srv = |priority, weight, target| {
+            rdata::SRV::new(priority, weight, 443, Name::from_labels(vec![target]))
+        };
+        let srvs = vec![
+            srv(10, 0, "backup"),
+            srv(0, 10, "light"),
+            srv(0, 0, "zero"),
+            srv(0, 90, "heavy"),
+        ];
+
+        // always picking the top of the range walks past all the lighter records
+        let ordered = order_srvs(srvs.iter().collect(), |total| total);
+        let targets = ordered.iter().map(|srv| srv.target()[0].clone()).collect::<Vec<_>>();
+        assert_eq!(targets, vec!["heavy", "light", "zero", "backup"]);
+
+        // picking zero takes the records of weight zero first
+        let ordered = order_srvs(srvs.iter().collect(), |_| 0);
+        let targets = ordered.iter().map(|srv| srv.target()[0].clone()).collect::<Vec<_>>();
+        assert_eq!(targets, vec!["zero", "light", "heavy", "backup"]);
+    }
+
+    #[test]
+    fn test_srv_unavailable() {
+        let srv = |target: Name| RData::SRV(rdata::SRV::new(0, 0, 443, target));
+
+        let lookup = SrvLookup::from(Lookup::new(Arc::new(vec![srv(Name::root())])));
+        assert!(lookup.is_unavailable());
+        assert!(lookup.ordered().is_empty());
+
+        let lookup = SrvLookup::from(Lookup::new(Arc::new(vec![
+            srv(Name::root()),
+            srv(Name::from_labels(vec!["target"])),
+        ])));
+        assert!(!lookup.is_unavailable());
+        let targets = lookup.ordered().iter().map(|srv| srv.target().clone()).collect::<Vec<_>>();
+        assert_eq!(targets, vec![Name::from_labels(vec!["target"])]);
+
+        assert!(!SrvLookup::from(Lookup::new(Arc::new(vec![]))).is_unavailable());
+    }
+
+    #[test]
+    #[cfg(any(feature = "dnssec-openssl", feature = "dnssec-ring"))]
+    fn test_sshfp_verify() {
+        use trust_dns::rr::dnssec::DigestType;
+        use trust_dns::rr::rdata::sshfp::{Algorithm, FingerprintType};
+
+        let host_key = b"\x00\x00\x00\x0bssh-ed25519 and the key";
+        let fingerprint = DigestType::SHA256.hash(host_key).unwrap().as_ref().to_vec();
+        let rdatas = Arc::new(vec![
+            RData::SSHFP(rdata::SSHFP::new(
+                Algorithm::RSA,
+                FingerprintType::SHA256,
+                vec![0; 32],
+            )),
+            RData::SSHFP(rdata::SSHFP::new(
+                Algorithm::Ed25519,
+                FingerprintType::SHA256,
+                fingerprint,
+            )),
+        ]);
+
+        let lookup = SshfpLookup::from(Lookup::new(rdatas.clone()));
+        assert_eq!(
+            lookup.verify(Algorithm::Ed25519, host_key),
+            SshfpVerdict::Insecure
+        );
+
+        let lookup = SshfpLookup::from(Lookup::new(rdatas).with_proof(Proof::Secure));
+        assert_eq!(
+            lookup.verify(Algorithm::Ed25519, host_key),
+            SshfpVerdict::Matched
+        );
+        assert_eq!(
+            lookup.verify(Algorithm::Ed25519, b"another key"),
+            SshfpVerdict::Mismatch
+        );
+        assert_eq!(
+            lookup.verify(Algorithm::ECDSA, host_key),
+            SshfpVerdict::NoFingerprints
+        );
+    }
+
+    #[test]
+    fn test_https_ordered() {
+        use trust_dns::rr::rdata::svcb::SvcParam;
+
+        let svcb = |priority, target: &str| {
+            RData::HTTPS(rdata::SVCB::new(
+                priority,
+                Name::parse(target, None).unwrap(),
+                vec![SvcParam::Port(443)],
+            ))
+        };
+
+        let lookup = HttpsLookup::from(Lookup::new(Arc::new(vec![
+            svcb(2, "b.example.com."),
+            svcb(1, "a.example.com."),
+            svcb(3, "."),
+        ])));
+        assert!(lookup.alias().is_none());
+        assert!(!lookup.is_unavailable());
+        assert_eq!(
+            lookup
+                .ordered()
+                .iter()
+                .map(|svcb| svcb.svc_priority())
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        let lookup = HttpsLookup::from(Lookup::new(Arc::new(
+            vec![svcb(0, "cdn.example.net.")],
+        )));
+        assert_eq!(
+            lookup.alias(),
+            Some(&Name::parse("cdn.example.net.", None).unwrap())
+        );
+        assert!(lookup.ordered().is_empty());
+
+        let lookup = HttpsLookup::from(Lookup::new(Arc::new(vec![svcb(0, ".")])));
+        assert!(lookup.is_unavailable());
+    }
+
+    #[test]
+    fn test_append_proofs() {
+        let secure = Lookup::new(Arc::new(vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]))
+            .with_proof(Proof::Secure);
+        let indeterminate = Lookup::new(Arc::new(vec![RData::A(Ipv4Addr::new(127, 0, 0, 2))]));
+        assert_eq!(secure.proof(), Proof::Secure);
+
+        // each record keeps its own state, together they are only as secure as the weakest
+        let lookup = secure.append(indeterminate);
+        assert_eq!(lookup.proof(), Proof::Indeterminate);
+        assert_eq!(
+            lookup.iter_with_proof().map(|(_, proof)| proof).collect::<Vec<_>>(),
+            vec![Proof::Secure, Proof::Indeterminate]
+        );
+
+        let bogus = Lookup::new(Arc::new(vec![RData::A(Ipv4Addr::new(127, 0, 0, 3))]))
+            .with_proof(Proof::Bogus);
+        assert_eq!(lookup.append(bogus).proof(), Proof::Bogus);
+        assert_eq!(Lookup::new(Arc::new(vec![])).proof(), Proof::Indeterminate);
+    }
+
+    #[test]
+    fn test_mx_ordered() {
+        let lookup = MxLookup::from(Lookup::new(Arc::new(vec![
+            RData::MX(rdata::MX::new(20, Name::from_labels(vec!["backup"]))),
+            RData::MX(rdata::MX::new(10, Name::from_labels(vec!["primary"]))),
+        ])));
+
+        let ordered = lookup.ordered();
+        assert_eq!(ordered[0].exchange(), &Name::from_labels(vec!["primary"]));
+        assert_eq!(ordered[1].exchange(), &Name::from_labels(vec!["backup"]));
+    }
 }
@@ -13,17 +13,20 @@
 use std::error::Error;
 use std::io;
 use std::mem;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::slice;
 use std::sync::Arc;
+use std::vec;
 
 use futures::{Async, future, Future, Poll, task};
 
 use trust_dns::client::{BasicClientHandle, ClientHandle};
 use trust_dns::op::Query;
-use trust_dns::rr::{Name, RData, RecordType};
+use trust_dns::rr::{Name, RData, Record, RecordType};
+use trust_dns::rr::rdata::opt::EdnsOption;
 
 use config::LookupIpStrategy;
-use lookup::{Lookup, LookupEither, LookupIter};
+use lookup::{Lookup, LookupEither, LookupIter, LookupSource, Proof};
 use lookup_state::CachingClient;
 use name_server_pool::StandardConnection;
 use hosts::Hosts;
@@ -40,6 +43,41 @@ impl LookupIp {
     pub fn iter(&self) -> LookupIpIter {
         LookupIpIter(self.0.iter())
     }
+
+    /// Returns the EDNS options of the response from the NameServer, see `Lookup::edns_options`
+    pub fn edns_options(&self) -> &[EdnsOption] {
+        self.0.edns_options()
+    }
+
+    /// Returns the security state of the addresses together, see `Lookup::proof`
+    pub fn proof(&self) -> Proof {
+        self.0.proof()
+    }
+
+    /// Returns a borrowed iterator of the returned IPs, each with its security state, e.g. the
+    ///  IPv4 addresses may be validated while the IPv6 addresses are not
+    pub fn iter_with_proof<'a>(&'a self) -> Box<Iterator<Item = (IpAddr, Proof)> + 'a> {
+        Box::new(self.0.iter_with_proof().filter_map(|(rdata, proof)| match *rdata {
+            RData::A(ip) => Some((IpAddr::from(ip), proof)),
+            RData::AAAA(ip) => Some((IpAddr::from(ip), proof)),
+            _ => None,
+        }))
+    }
+
+    /// Returns the RRSIGs which validated the addresses, see `Lookup::rrsigs`
+    pub fn rrsigs(&self) -> &[Record] {
+        self.0.rrsigs()
+    }
+
+    /// Returns the names resolved through to reach the addresses, see `Lookup::cname_chain`
+    pub fn cname_chain(&self) -> &[Name] {
+        self.0.cname_chain()
+    }
+
+    /// Returns where the addresses came from, and how long the lookup took, see `Lookup::source`
+    pub fn source(&self) -> &LookupSource {
+        self.0.source()
+    }
 }
 
 impl From<Lookup> for LookupIp {
@@ -64,6 +102,119 @@ impl<'i> Iterator for LookupIpIter<'i> {
     }
 }
 
+/// The addresses of a host with the port to connect to, see `ResolverFuture::lookup_socket_addrs`
+///
+/// This implements `ToSocketAddrs`, so it can be handed to `TcpStream::connect` and the like,
+///  which try each of the addresses in turn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupSocketAddrs(Vec<SocketAddr>);
+
+impl LookupSocketAddrs {
+    /// The addresses of the lookup with the port, ordered per the strategy: the preferred family
+    ///  first for `Ipv4thenIpv6` and `Ipv6thenIpv4`, IPv4 first for `Ipv4AndIpv6`, and alternating
+    ///  families as they are for `HappyEyeballs`
+    pub fn new(lookup: &LookupIp, port: u16, strategy: LookupIpStrategy) -> Self {
+        let mut addrs: Vec<SocketAddr> = lookup
+            .iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect();
+
+        // the sort is stable, the order within each family is kept
+        match strategy {
+            LookupIpStrategy::Ipv4thenIpv6 |
+            LookupIpStrategy::Ipv4AndIpv6 => addrs.sort_by_key(|addr| addr.is_ipv6()),
+            LookupIpStrategy::Ipv6thenIpv4 => addrs.sort_by_key(|addr| addr.is_ipv4()),
+            LookupIpStrategy::Ipv4Only |
+            LookupIpStrategy::Ipv6Only |
+            LookupIpStrategy::HappyEyeballs => (),
+        }
+
+        LookupSocketAddrs(addrs)
+    }
+
+    /// Returns a borrowed iterator of the addresses
+    pub fn iter(&self) -> slice::Iter<SocketAddr> {
+        self.0.iter()
+    }
+
+    /// Returns true if there are no addresses
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<SocketAddr>> for LookupSocketAddrs {
+    fn from(addrs: Vec<SocketAddr>) -> Self {
+        LookupSocketAddrs(addrs)
+    }
+}
+
+impl IntoIterator for LookupSocketAddrs {
+    type Item = SocketAddr;
+    type IntoIter = vec::IntoIter<SocketAddr>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl ToSocketAddrs for LookupSocketAddrs {
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        Ok(self.0.clone().into_iter())
+    }
+}
+
+/// The Future returned from ResolverFuture when looking up the addresses of a host and port
+#[must_use = "futures do nothing unless polled"]
+pub struct LookupSocketAddrsFuture(Box<Future<Item = LookupSocketAddrs, Error = io::Error>>);
+
+impl LookupSocketAddrsFuture {
+    pub(crate) fn new(future: Box<Future<Item = LookupSocketAddrs, Error = io::Error>>) -> Self {
+        LookupSocketAddrsFuture(future)
+    }
+}
+
+impl Future for LookupSocketAddrsFuture {
+    type Item = LookupSocketAddrs;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.0.poll()
+    }
+}
+
+/// Splits `host:port` into the host and the port, an IPv6 address must be in brackets, e.g.
+///  `[::1]:53`
+pub(crate) fn split_host_port(host_port: &str) -> io::Result<(&str, u16)> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("expected host:port, e.g. example.com:443, got: {}", host_port),
+        )
+    };
+
+    let colon = try!(host_port.rfind(':').ok_or_else(|| invalid()));
+    let (host, port) = (&host_port[..colon], &host_port[colon + 1..]);
+    let port = try!(port.parse::<u16>().map_err(|_| invalid()));
+
+    let host = if host.starts_with('[') && host.ends_with(']') {
+        &host[1..host.len() - 1]
+    } else if host.contains(':') {
+        // an IPv6 address without brackets, the port can not be told from the address
+        return Err(invalid());
+    } else {
+        host
+    };
+
+    if host.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok((host, port))
+}
+
 /// The Future returned from ResolverFuture when performing an A or AAAA lookup.
 pub type LookupIpFuture = InnerLookupIpFuture<LookupEither<BasicClientHandle, StandardConnection>>;
 
@@ -120,6 +271,21 @@ impl<C: ClientHandle + 'static> InnerLookupIpFuture<C> {
         }
     }
 
+    /// Wraps a lookup with the strategy which has already been started, e.g. a search
+    pub(crate) fn from_future(
+        client_cache: CachingClient<C>,
+        strategy: LookupIpStrategy,
+        future: Box<Future<Item = Lookup, Error = io::Error>>,
+    ) -> Self {
+        InnerLookupIpFuture {
+            client_cache,
+            names: vec![],
+            strategy,
+            future,
+            hosts: None,
+        }
+    }
+
     pub(crate) fn error<E: Error>(client_cache: CachingClient<C>, error: E) -> Self {
         return InnerLookupIpFuture {
             // errors on names don't need to be cheap... i.e. this clone is unfortunate in this case.
@@ -156,7 +322,7 @@ impl<C: ClientHandle + 'static> Future for InnerLookupIpFuture<C> {
 }
 
 /// returns a new future for lookup
-fn strategic_lookup<C: ClientHandle + 'static>(
+pub(crate) fn strategic_lookup<C: ClientHandle + 'static>(
     name: Name,
     strategy: LookupIpStrategy,
     client: CachingClient<C>,
@@ -164,7 +330,20 @@ fn strategic_lookup<C: ClientHandle + 'static>(
 ) -> Box<Future<Item = Lookup, Error = io::Error>> {
     if let Some(hosts) = hosts {
         if let Some(lookup) = hosts.lookup_static_host(&name) {
-            return Box::new(future::ok(lookup));
+            // only the addresses of the families being looked up, if there are none fall back to DNS
+            let rdatas: Vec<RData> = lookup
+                .iter()
+                .filter(|rdata| match (strategy, *rdata) {
+                    (LookupIpStrategy::Ipv4Only, &RData::AAAA(..)) |
+                    (LookupIpStrategy::Ipv6Only, &RData::A(..)) => false,
+                    _ => true,
+                })
+                .cloned()
+                .collect();
+
+            if !rdatas.is_empty() {
+                return Box::new(future::ok(Lookup::new(Arc::new(rdatas))));
+            }
         };
     }
 
@@ -174,6 +353,7 @@ fn strategic_lookup<C: ClientHandle + 'static>(
         LookupIpStrategy::Ipv4AndIpv6 => ipv4_and_ipv6(name, client),
         LookupIpStrategy::Ipv6thenIpv4 => ipv6_then_ipv4(name, client),
         LookupIpStrategy::Ipv4thenIpv6 => ipv4_then_ipv6(name, client),
+        LookupIpStrategy::HappyEyeballs => happy_eyeballs(name, client),
     }
 }
 
@@ -227,6 +407,86 @@ fn ipv4_and_ipv6<C: ClientHandle + 'static>(
     )
 }
 
+/// queries for A and AAAA in parallel, and orders the results for connection attempts
+///
+/// Unlike the connection attempts of RFC 8305, the lookup waits for both queries, as all the
+///  addresses are returned together. If one query fails, the results of the other are returned.
+fn happy_eyeballs<C: ClientHandle + 'static>(
+    name: Name,
+    client: CachingClient<C>,
+) -> Box<Future<Item = Lookup, Error = io::Error>> {
+    Box::new(ipv4_and_ipv6(name, client).map(happy_eyeballs_order))
+}
+
+/// Orders the addresses per [RFC 8305 section 4](https://tools.ietf.org/html/rfc8305#section-4)
+///
+/// The Ipv6 addresses are sorted by the precedence of the RFC 6724 policy table, the destination
+///  address selection rule which does not depend on the local source addresses, then the families
+///  are interleaved starting with Ipv6. Addresses of equal precedence keep the order in which the
+///  name server returned them.
+fn happy_eyeballs_order(lookup: Lookup) -> Lookup {
+    let mut ipv6 = Vec::new();
+    let mut ipv4 = Vec::new();
+    for (rdata, proof) in lookup.iter_with_proof() {
+        match *rdata {
+            RData::AAAA(..) => ipv6.push((rdata.clone(), proof)),
+            RData::A(..) => ipv4.push((rdata.clone(), proof)),
+            _ => (),
+        }
+    }
+
+    // all ipv4 addresses have the same precedence, as ::ffff:0:0/96
+    ipv6.sort_by(|a, b| match (&a.0, &b.0) {
+        (&RData::AAAA(ref a), &RData::AAAA(ref b)) => {
+            ipv6_precedence(b).cmp(&ipv6_precedence(a))
+        }
+        _ => unreachable!("only AAAA records are sorted"),
+    });
+
+    let mut ordered = Vec::with_capacity(ipv6.len() + ipv4.len());
+    let mut ipv6 = ipv6.into_iter();
+    let mut ipv4 = ipv4.into_iter();
+    loop {
+        match (ipv6.next(), ipv4.next()) {
+            (None, None) => break,
+            (v6, v4) => {
+                ordered.extend(v6);
+                ordered.extend(v4);
+            }
+        }
+    }
+
+    let (rdatas, proofs): (Vec<RData>, Vec<Proof>) = ordered.into_iter().unzip();
+    Lookup::with_edns_options(Arc::new(rdatas), Arc::new(lookup.edns_options().to_vec()))
+        .with_proofs(proofs)
+        .with_rrsigs(lookup.rrsigs().to_vec())
+        .with_cname_chain(lookup.cname_chain().to_vec())
+}
+
+/// Precedence from the default policy table of
+///  [RFC 6724 section 2.1](https://tools.ietf.org/html/rfc6724#section-2.1), higher is preferred
+fn ipv6_precedence(ip: &Ipv6Addr) -> u8 {
+    let segments = ip.segments();
+
+    if ip.is_loopback() {
+        50 // ::1/128
+    } else if segments[0..6] == [0, 0, 0, 0, 0, 0xffff] {
+        35 // ::ffff:0:0/96, ipv4 mapped
+    } else if segments[0] == 0x2002 {
+        30 // 2002::/16, 6to4
+    } else if segments[0] == 0x2001 && segments[1] == 0 {
+        5 // 2001::/32, Teredo
+    } else if segments[0] & 0xfe00 == 0xfc00 {
+        3 // fc00::/7, unique local
+    } else if segments[0..6] == [0, 0, 0, 0, 0, 0] || segments[0] & 0xffc0 == 0xfec0 ||
+               segments[0] == 0x3ffe
+    {
+        1 // ::/96 ipv4 compatible, fec0::/10 site local, 3ffe::/16 6bone
+    } else {
+        40 // ::/0
+    }
+}
+
 /// queries only for AAAA and on no results queries for A
 fn ipv6_then_ipv4<C: ClientHandle + 'static>(
     name: Name,
@@ -298,6 +558,7 @@ pub mod tests {
     #[derive(Clone)]
     pub struct MockClientHandle {
         messages: Arc<Mutex<Vec<ClientResult<Message>>>>,
+        verifying_dnssec: bool,
     }
 
     impl DnsHandle for MockClientHandle {
@@ -312,7 +573,7 @@ pub mod tests {
 
     impl ClientHandle for MockClientHandle {
         fn is_verifying_dnssec(&self) -> bool {
-            false
+            self.verifying_dnssec
         }
     }
 
@@ -351,7 +612,18 @@ pub mod tests {
     }
 
     pub fn mock(messages: Vec<ClientResult<Message>>) -> MockClientHandle {
-        MockClientHandle { messages: Arc::new(Mutex::new(messages)) }
+        MockClientHandle {
+            messages: Arc::new(Mutex::new(messages)),
+            verifying_dnssec: false,
+        }
+    }
+
+    /// A mock of a validating client, i.e. one whose responses have all been validated
+    pub fn secure_mock(messages: Vec<ClientResult<Message>>) -> MockClientHandle {
+        MockClientHandle {
+            messages: Arc::new(Mutex::new(messages)),
+            verifying_dnssec: true,
+        }
     }
 
     #[test]
@@ -457,6 +729,82 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_happy_eyeballs_strategy() {
+        let mut aaaa_message = Message::new();
+        aaaa_message.insert_answers(vec![
+            Record::from_rdata(
+                Name::root(),
+                86400,
+                RecordType::AAAA,
+                RData::AAAA(Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 1))
+            ),
+            Record::from_rdata(
+                Name::root(),
+                86400,
+                RecordType::AAAA,
+                RData::AAAA(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))
+            ),
+        ]);
+        let mut a_message = Message::new();
+        a_message.insert_answers(vec![
+            Record::from_rdata(
+                Name::root(),
+                86400,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1))
+            ),
+            Record::from_rdata(
+                Name::root(),
+                86400,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(192, 0, 2, 2))
+            ),
+        ]);
+
+        // families alternate, starting with the global ipv6 address before the unique local
+        assert_eq!(
+            happy_eyeballs(
+                Name::root(),
+                CachingClient::new(0, mock(vec![Ok(aaaa_message), Ok(a_message)])),
+            ).wait()
+                .unwrap()
+                .iter()
+                .map(|r| r.to_ip_addr().unwrap())
+                .collect::<Vec<IpAddr>>(),
+            vec![
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+                IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+                IpAddr::V6(Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 1)),
+                IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)),
+            ]
+        );
+
+        // only ipv4 available
+        assert_eq!(
+            happy_eyeballs(
+                Name::root(),
+                CachingClient::new(0, mock(vec![error(), v4_message()])),
+            ).wait()
+                .unwrap()
+                .iter()
+                .map(|r| r.to_ip_addr().unwrap())
+                .collect::<Vec<IpAddr>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+    }
+
+    #[test]
+    fn test_ipv6_precedence() {
+        assert_eq!(ipv6_precedence(&Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), 50);
+        assert_eq!(ipv6_precedence(&Ipv6Addr::new(0x2606, 0x2800, 0x220, 0x1, 0, 0, 0, 1)), 40);
+        assert_eq!(ipv6_precedence(&Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0xc000, 0x0201)), 35);
+        assert_eq!(ipv6_precedence(&Ipv6Addr::new(0x2002, 0, 0, 0, 0, 0, 0, 1)), 30);
+        assert_eq!(ipv6_precedence(&Ipv6Addr::new(0x2001, 0, 0, 0, 0, 0, 0, 1)), 5);
+        assert_eq!(ipv6_precedence(&Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)), 3);
+        assert_eq!(ipv6_precedence(&Ipv6Addr::new(0xfec0, 0, 0, 0, 0, 0, 0, 1)), 1);
+    }
+
     #[test]
     fn test_ipv6_then_ipv4_strategy() {
         // ipv6 first
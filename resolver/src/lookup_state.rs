@@ -7,29 +7,36 @@
 
 //! Caching related functionality for the Resolver.
 
-use std::cell::RefCell;
+#[cfg(feature = "psl")]
+use std::collections::HashMap;
+use std::cmp;
+use std::fmt;
 use std::io;
 use std::mem;
-use std::sync::{Arc, Mutex, TryLockError};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
 use std::time::{Duration, Instant};
 
 use futures::{Async, Future, Poll, task};
+use rand::{self, Rng, SeedableRng, XorShiftRng};
 
 use trust_dns::client::ClientHandle;
 use trust_dns::error::ClientError;
 use trust_dns::op::{Message, Query, ResponseCode};
-use trust_dns::rr::{Name, RData, RecordType};
+use trust_dns::rr::{Name, RData, Record, RecordType};
+use trust_dns::rr::rdata::opt::{self, EdnsCode, EdnsOption, ExtendedError};
 
-use lookup::Lookup;
+use config::ResolverOpts;
+use error::ResponseError;
+use lookup::{Lookup, NameServerSource, Proof};
 use lru_cache::LruCache;
+use name_server_pool::AnswerSources;
+use observer::{InFlight, LookupId, LookupObservers};
+#[cfg(feature = "psl")]
+use psl::PublicSuffixList;
 
 /// Maximum TTL as defined in https://tools.ietf.org/html/rfc2181
 const MAX_TTL: u32 = 2147483647_u32;
-const MAX_QUERY_DEPTH: u8 = 8; // arbitrarily chosen number...
-
-thread_local! {
-    static QUERY_DEPTH: RefCell<u8> = RefCell::new(0);
-}
 
 #[derive(Debug)]
 struct LruValue {
@@ -45,50 +52,308 @@ impl LruValue {
     }
 }
 
+/// Counters for the usage of the cache, see `CachingClient::cache_stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups which were answered from the cache
+    pub hits: u64,
+    /// Lookups which were not in the cache, or were expired, and needed to be sent upstream
+    pub misses: u64,
+    /// Lookups which found an unexpired negative, i.e. NXDomain or NoData, response in the cache
+    pub negative_hits: u64,
+    /// Entries which were removed from the cache to make room for new entries
+    pub evictions: u64,
+}
+
+/// The clients of an EDNS Client Subnet option, [RFC 7871](https://tools.ietf.org/html/rfc7871),
+///  an address and the number of its leading bits which are significant
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClientSubnet {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl ClientSubnet {
+    /// Returns the subnet of the address, the bits past the prefix are cleared
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - an address of the subnet
+    /// * `prefix` - the number of leading bits of the address in the subnet, at most the length
+    ///              of the address
+    pub fn new(address: IpAddr, prefix: u8) -> Self {
+        let address = match address {
+            IpAddr::V4(address) => {
+                let mut octets = address.octets();
+                mask(&mut octets, prefix);
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            IpAddr::V6(address) => {
+                let mut octets = address.octets();
+                mask(&mut octets, prefix);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+        };
+        let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+
+        ClientSubnet {
+            address,
+            prefix: cmp::min(prefix, max_prefix),
+        }
+    }
+
+    /// Returns the subnet of the option, to the source prefix, i.e. the clients a query is made
+    ///  for, None if it is not an EDNS Client Subnet option
+    pub fn from_option(option: &EdnsOption) -> Option<Self> {
+        read_ecs(option).map(|ecs| Self::new(ecs.address(), ecs.source_prefix()))
+    }
+
+    /// Returns the EDNS Client Subnet option for queries made for the clients of the subnet
+    pub fn to_option(&self) -> EdnsOption {
+        EdnsOption::Subnet(opt::ClientSubnet::new(self.address, self.prefix, 0))
+    }
+
+    /// Returns the clients for which the answer of the response is valid, the subnet of its ECS
+    ///  option to the scope prefix, None if it has no ECS option or the answer is valid for all
+    fn scope_of(response: &Message) -> Option<Self> {
+        let option = match response.edns().and_then(|edns| edns.option(&EdnsCode::Subnet)) {
+            Some(option) => option,
+            None => return None,
+        };
+
+        // a scope longer than the source is only as specific as the source, RFC 7871 section 7.3.1
+        match read_ecs(option) {
+            Some(ecs) if cmp::min(ecs.source_prefix(), ecs.scope_prefix()) > 0 => {
+                Some(Self::new(
+                    ecs.address(),
+                    cmp::min(ecs.source_prefix(), ecs.scope_prefix()),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the address, with the bits past the prefix cleared
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// Returns the number of leading bits of the address in the subnet
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+}
+
+/// Reads an EDNS Client Subnet option, also one which was set by its data, as an `Unknown` option
+fn read_ecs(option: &EdnsOption) -> Option<opt::ClientSubnet> {
+    match *option {
+        EdnsOption::Subnet(ecs) => Some(ecs),
+        EdnsOption::Unknown(code, ref data) if EdnsCode::from(code) == EdnsCode::Subnet => {
+            opt::ClientSubnet::read(data).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Clears the bits of the octets past the prefix
+fn mask(octets: &mut [u8], prefix: u8) {
+    for (i, octet) in octets.iter_mut().enumerate() {
+        let bits = cmp::min(8, (prefix as usize).saturating_sub(i * 8));
+        *octet &= !(0xFFu16 >> bits) as u8;
+    }
+}
+
+/// The key of a cached answer, the query and what else the answer depends on, so that answers
+///  are not shared between validating and non-validating clients, or between client subnets
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// The query which the answer is for
+    pub query: Query,
+    /// Whether the answer was looked up with the DO bit set, i.e. by a validating client, only
+    ///  these answers were validated
+    pub dnssec_ok: bool,
+    /// The clients for which the answer is valid, from the ECS option of the response, None if it
+    ///  is valid for all
+    pub client_subnet: Option<ClientSubnet>,
+}
+
+impl From<Query> for CacheKey {
+    /// The key of the answer for all clients, looked up without DNSSec
+    fn from(query: Query) -> Self {
+        CacheKey {
+            query,
+            dnssec_ok: false,
+            client_subnet: None,
+        }
+    }
+}
+
+/// A snapshot of a single entry in the cache, see `CachingClient::cache_entries`
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    /// The query which this entry answers
+    pub query: Query,
+    /// Whether the answer was looked up by a validating client, see `CacheKey::dnssec_ok`
+    pub dnssec_ok: bool,
+    /// The clients for which the answer is valid, see `CacheKey::client_subnet`
+    pub client_subnet: Option<ClientSubnet>,
+    /// The cached records, None if this is a negative, i.e. NXDomain or NoData, entry
+    pub lookup: Option<Lookup>,
+    /// The amount of time until this entry expires
+    pub remaining_ttl: Duration,
+}
+
 #[derive(Debug)]
-struct DnsLru(LruCache<Query, LruValue>);
+struct DnsLru {
+    cache: LruCache<CacheKey, LruValue>,
+    stats: CacheStats,
+}
 
 impl DnsLru {
     fn new(capacity: usize) -> Self {
-        DnsLru(LruCache::new(capacity))
-    }
-
-    fn insert(&mut self, query: Query, rdatas_and_ttl: Vec<(RData, u32)>, now: Instant) -> Lookup {
-        let len = rdatas_and_ttl.len();
-        // collapse the values, we're going to take the Minimum TTL as the correct one
-        let (rdatas, ttl): (Vec<RData>, u32) =
-            rdatas_and_ttl.into_iter().fold(
-                (Vec::with_capacity(len), MAX_TTL),
-                |(mut rdatas, mut min_ttl),
-                 (rdata, ttl)| {
-                    rdatas.push(rdata);
-                    min_ttl = if ttl < min_ttl { ttl } else { min_ttl };
-                    (rdatas, min_ttl)
-                },
-            );
+        DnsLru {
+            cache: LruCache::new(capacity),
+            stats: CacheStats::default(),
+        }
+    }
 
-        let ttl = Duration::from_secs(ttl as u64);
-        let ttl_until = now + ttl;
+    /// Inserts the value, tracking if this caused the least recently used entry to be evicted
+    fn insert_value(&mut self, key: CacheKey, value: LruValue) {
+        let len = self.cache.len();
+        let replaced = self.cache.insert(key, value).is_some();
 
-        // insert into the LRU
-        let lookup = Lookup::new(Arc::new(rdatas));
-        self.0.insert(
-            query,
-            LruValue {
-                lookup: Some(lookup.clone()),
-                ttl_until,
-            },
-        );
+        // if this was not a replacement, and the size didn't grow, something was pushed out
+        if !replaced && self.cache.len() == len {
+            self.stats.evictions += 1;
+        }
+    }
 
-        lookup
+    fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Returns all entries which have not yet expired, in least recently used order
+    fn entries(&self, now: Instant) -> Vec<CacheEntry> {
+        self.cache
+            .iter()
+            .filter(|&(_, value)| value.is_current(now))
+            .map(|(key, value)| {
+                CacheEntry {
+                    query: key.query.clone(),
+                    dnssec_ok: key.dnssec_ok,
+                    client_subnet: key.client_subnet,
+                    lookup: value.lookup.clone(),
+                    remaining_ttl: value.ttl_until.duration_since(now),
+                }
+            })
+            .collect()
+    }
+
+    /// Counts the unexpired entries per registrable domain, most entries first
+    ///
+    /// Names which have no registrable domain, e.g. `com.`, are counted under themselves.
+    #[cfg(feature = "psl")]
+    fn domain_counts(&self, psl: &PublicSuffixList, now: Instant) -> Vec<(Name, usize)> {
+        let mut counts = HashMap::<Name, usize>::new();
+        for (key, _) in self.cache.iter().filter(|&(_, value)| value.is_current(now)) {
+            let domain = psl.registrable_domain(key.query.name()).unwrap_or_else(|| {
+                key.query.name().to_lowercase()
+            });
+            *counts.entry(domain).or_insert(0) += 1;
+        }
+
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Removes the entries for the query, for all clients, returns true if there were any
+    fn remove(&mut self, query: &Query) -> bool {
+        self.remove_where(|key| key.query == *query) > 0
+    }
+
+    /// Removes all entries, of any type or class, at or below the name, returning the number removed
+    fn remove_subtree(&mut self, name: &Name) -> usize {
+        self.remove_where(|key| name.zone_of(key.query.name()))
+    }
+
+    fn remove_where<F: Fn(&CacheKey) -> bool>(&mut self, matches: F) -> usize {
+        let keys = self.cache
+            .iter()
+            .filter(|&(key, _)| matches(key))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        for key in &keys {
+            self.cache.remove(key);
+        }
+
+        keys.len()
+    }
+
+    fn insert(
+        &mut self,
+        key: CacheKey,
+        rdatas_and_ttl: Vec<(RData, u32)>,
+        edns_options: Vec<EdnsOption>,
+        now: Instant,
+    ) -> Lookup {
+        let (rdatas, ttl) = min_ttl(rdatas_and_ttl);
+        let lookup = Lookup::with_edns_options(Arc::new(rdatas), Arc::new(edns_options));
+
+        self.insert_lookup(key, lookup, ttl, now)
+    }
+
+    /// Adds the records to those already cached for the query, rather than replacing them. This is
+    ///  for mDNS records received without the cache-flush bit, i.e. records which may be shared by
+    ///  many hosts on the link, see [RFC 6762](https://tools.ietf.org/html/rfc6762#section-10.2).
+    ///
+    /// Records with a TTL of zero are goodbyes, and are removed from the cache.
+    #[cfg(feature = "mdns")]
+    fn insert_shared(
+        &mut self,
+        key: CacheKey,
+        mut rdatas_and_ttl: Vec<(RData, u32)>,
+        edns_options: Vec<EdnsOption>,
+        now: Instant,
+    ) -> io::Result<Lookup> {
+        if let Some(value) = self.cache.get_mut(&key) {
+            let remaining_ttl = if value.is_current(now) {
+                value.ttl_until.duration_since(now).as_secs() as u32
+            } else {
+                0
+            };
+
+            if let Some(ref lookup) = value.lookup {
+                for rdata in lookup.iter() {
+                    if !rdatas_and_ttl.iter().any(|&(ref r, _)| r == rdata) {
+                        rdatas_and_ttl.push((rdata.clone(), remaining_ttl));
+                    }
+                }
+            }
+        }
+
+        rdatas_and_ttl.retain(|&(_, ttl)| ttl != 0);
+        if rdatas_and_ttl.is_empty() {
+            self.cache.remove(&key);
+            return Err(Self::nx_error(&key.query, None));
+        }
+
+        Ok(self.insert(key, rdatas_and_ttl, edns_options, now))
     }
 
-    fn duplicate(&mut self, query: Query, lookup: Lookup, ttl: u32, now: Instant) -> Lookup {
+    /// Inserts the lookup as the result of the query, e.g. one already made up of the records,
+    ///  their targets and security state, or the cached result of the end of a CNAME chain
+    fn insert_lookup(&mut self, key: CacheKey, lookup: Lookup, ttl: u32, now: Instant) -> Lookup {
         let ttl = Duration::from_secs(ttl as u64);
         let ttl_until = now + ttl;
 
-        self.0.insert(
-            query,
+        self.insert_value(
+            key,
             LruValue {
                 lookup: Some(lookup.clone()),
                 ttl_until,
@@ -98,38 +363,64 @@ impl DnsLru {
         lookup
     }
 
-    fn nx_error(query: Query) -> io::Error {
-        io::Error::new(
-            io::ErrorKind::AddrNotAvailable,
-            format!("Addr does not exist for: {}", query),
-        )
+    fn nx_error(query: &Query, extended_error: Option<ExtendedError>) -> io::Error {
+        ResponseError::new(query, ResponseCode::NXDomain, extended_error).into()
     }
 
-    fn negative(&mut self, query: Query, ttl: u32, now: Instant) -> io::Error {
+    fn negative(
+        &mut self,
+        key: CacheKey,
+        ttl: u32,
+        extended_error: Option<ExtendedError>,
+        now: Instant,
+    ) -> io::Error {
         // TODO: if we are getting a negative response, should we instead fallback to cache?
         //   this would cache indefinitely, probably not correct
 
         let ttl = Duration::from_secs(ttl as u64);
         let ttl_until = now + ttl;
 
-        self.0.insert(
-            query.clone(),
+        let query = key.query.clone();
+        self.insert_value(
+            key,
             LruValue {
                 lookup: None,
                 ttl_until,
             },
         );
 
-        Self::nx_error(query)
+        Self::nx_error(&query, extended_error)
+    }
+
+    /// Returns the key of the most specific current answer for the client subnet, i.e. that of the
+    ///  longest scope which contains the subnet, or else the key of the answer for all clients
+    fn subnet_key(&mut self, key: CacheKey, client_subnet: ClientSubnet, now: Instant) -> CacheKey {
+        for prefix in (1..client_subnet.prefix() + 1).rev() {
+            let scoped = CacheKey {
+                client_subnet: Some(ClientSubnet::new(client_subnet.address(), prefix)),
+                ..key.clone()
+            };
+
+            if self.cache.get_mut(&scoped).map_or(false, |value| value.is_current(now)) {
+                return scoped;
+            }
+        }
+
+        key
     }
 
     /// This needs to be mut b/c it's an LRU, meaning the ordering of elements will potentially change on retrieval...
-    fn get(&mut self, query: &Query, now: Instant) -> Option<Lookup> {
+    fn get(&mut self, key: &CacheKey, now: Instant) -> Option<Lookup> {
         let mut out_of_date = false;
-        let lookup = self.0.get_mut(query).and_then(
+        let mut is_negative = false;
+        let lookup = self.cache.get_mut(key).and_then(
             |value| if value.is_current(now) {
                 out_of_date = false;
-                value.lookup.clone()
+                is_negative = value.lookup.is_none();
+                let cache_ttl = value.ttl_until.duration_since(now).as_secs() as u32;
+                value.lookup.clone().map(
+                    |lookup| lookup.with_cache_ttl(cache_ttl),
+                )
             } else {
                 out_of_date = true;
                 None
@@ -140,13 +431,44 @@ impl DnsLru {
         // this assumes time is always moving forward, this would only not be true in contrived situations where now
         //  is not current time, like tests...
         if out_of_date {
-            self.0.remove(query);
+            self.cache.remove(key);
+        }
+
+        if lookup.is_some() {
+            self.stats.hits += 1;
+        } else if is_negative {
+            self.stats.negative_hits += 1;
+        } else {
+            self.stats.misses += 1;
         }
 
         lookup
     }
 }
 
+/// Locks the cache, recovering it when a task panicked while it held the lock
+///
+/// The cache is not left unsafe by a panic, at worst an entry is stale until its TTL expires, so
+///  rather than failing every later lookup with the poisoned lock, the cache keeps being used.
+fn lock_lru(lru: &Mutex<DnsLru>) -> MutexGuard<DnsLru> {
+    lru.lock().unwrap_or_else(|poison| {
+        debug!("recovering cache poisoned by a panicked task");
+        poison.into_inner()
+    })
+}
+
+/// Like `lock_lru`, but None while another task holds the lock
+fn try_lock_lru(lru: &Mutex<DnsLru>) -> Option<MutexGuard<DnsLru>> {
+    match lru.try_lock() {
+        Ok(lru) => Some(lru),
+        Err(TryLockError::WouldBlock) => None,
+        Err(TryLockError::Poisoned(poison)) => {
+            debug!("recovering cache poisoned by a panicked task");
+            Some(poison.into_inner())
+        }
+    }
+}
+
 // TODO: need to consider this storage type as it compares to Authority in server...
 //       should it just be an variation on Authority?
 #[derive(Clone, Debug)]
@@ -155,6 +477,15 @@ pub struct CachingClient<C: ClientHandle> {
     // TODO: switch to FuturesMutex (Mutex will have some undesireable locking)
     lru: Arc<Mutex<DnsLru>>,
     client: C,
+    max_cname_chain: usize,
+    client_subnet: Option<ClientSubnet>,
+    answer_order: AnswerOrder,
+    shuffle_rng: ShuffleRng,
+    keep_response: bool,
+    answer_sources: AnswerSources,
+    observers: LookupObservers,
+    /// the lookup the CNAMEs followed by this client are part of, if it is observed
+    lookup_id: Option<LookupId>,
 }
 
 impl<C: ClientHandle + 'static> CachingClient<C> {
@@ -164,24 +495,285 @@ impl<C: ClientHandle + 'static> CachingClient<C> {
     }
 
     fn with_cache(lru: Arc<Mutex<DnsLru>>, client: C) -> Self {
-        CachingClient { lru, client }
+        CachingClient {
+            lru,
+            client,
+            max_cname_chain: ResolverOpts::default().max_cname_chain,
+            client_subnet: None,
+            answer_order: AnswerOrder::default(),
+            shuffle_rng: ShuffleRng::new(None),
+            keep_response: false,
+            answer_sources: AnswerSources::default(),
+            observers: LookupObservers::default(),
+            lookup_id: None,
+        }
+    }
+
+    /// Sets the maximum number of CNAMEs followed by a lookup, see
+    ///  `ResolverOpts::max_cname_chain`
+    pub fn set_max_cname_chain(&mut self, max_cname_chain: usize) {
+        self.max_cname_chain = max_cname_chain;
+    }
+
+    /// Sets the subnet of the clients the lookups are made for, the subnet of the EDNS Client
+    ///  Subnet option sent to the NameServers
+    ///
+    /// Answers which the NameServers scoped to a subnet are only used for lookups from within
+    ///  that subnet, without a client subnet only answers for all clients are used.
+    pub fn set_client_subnet(&mut self, client_subnet: Option<ClientSubnet>) {
+        self.client_subnet = client_subnet;
+    }
+
+    /// Sets the order of the records of each lookup, see `ResolverOpts::answer_order`
+    ///
+    /// The random number generator of the shuffles is shared with the clones of this client, a
+    ///  seed restarts it.
+    pub fn set_answer_order(&mut self, answer_order: AnswerOrder) {
+        if let AnswerOrder::Shuffle { seed: Some(seed) } = answer_order {
+            self.shuffle_rng = ShuffleRng::new(Some(seed));
+        }
+        self.answer_order = answer_order;
+    }
+
+    /// Keeps the response of each query along with the records, see `ResolverOpts::keep_response`
+    pub fn set_keep_response(&mut self, keep_response: bool) {
+        self.keep_response = keep_response;
+    }
+
+    /// Sets where the NameServers which answered the responses of the client are recorded, for
+    ///  `LookupSource::name_server`, see `NameServerPool::answer_sources`
+    pub(crate) fn set_answer_sources(&mut self, answer_sources: AnswerSources) {
+        self.answer_sources = answer_sources;
+    }
+
+    /// Sets the observer of the lookups, see `ResolverFuture::set_lookup_observer`
+    pub(crate) fn set_observers(&mut self, observers: LookupObservers) {
+        self.observers = observers;
+    }
+
+    fn lock_cache(&self) -> MutexGuard<DnsLru> {
+        lock_lru(&self.lru)
+    }
+
+    /// Returns the hit, miss, and eviction counters for the cache
+    pub fn cache_stats(&self) -> io::Result<CacheStats> {
+        Ok(self.lock_cache().stats())
+    }
+
+    /// Returns a snapshot of all the unexpired entries in the cache, least recently used first
+    pub fn cache_entries(&self) -> io::Result<Vec<CacheEntry>> {
+        Ok(self.lock_cache().entries(Instant::now()))
+    }
+
+    /// Returns the number of unexpired entries in the cache per registrable domain, e.g.
+    ///  `www.example.co.uk.` and `mail.example.co.uk.` are both counted for `example.co.uk.`
+    #[cfg(feature = "psl")]
+    pub fn cache_domains(&self, psl: &PublicSuffixList) -> io::Result<Vec<(Name, usize)>> {
+        Ok(self.lock_cache().domain_counts(psl, Instant::now()))
+    }
+
+    /// Removes all entries from the cache, the statistics are not reset
+    pub fn clear_cache(&self) -> io::Result<()> {
+        Ok(self.lock_cache().clear())
+    }
+
+    /// Removes the cached result for the query, returns true if there was an entry
+    pub fn flush_query(&self, query: &Query) -> io::Result<bool> {
+        Ok(self.lock_cache().remove(query))
+    }
+
+    /// Removes all cached results, of any type, for the name and every name below it
+    ///
+    /// e.g. flushing `example.com.` removes `example.com. A` and `www.example.com. AAAA`
+    ///
+    /// # Returns
+    ///
+    /// the number of entries removed
+    pub fn flush_subtree(&self, name: &Name) -> io::Result<usize> {
+        Ok(self.lock_cache().remove_subtree(name))
+    }
+
+    /// Caches the records of the response which answer the query, replacing any cached result
+    ///
+    /// Only the answers for the query name and type are cached, CNAMEs are not followed. The
+    ///  response is not validated, so this fails if the client validates with DNSSec, only
+    ///  validated records are cached.
+    pub fn cache_response(&self, query: Query, response: &Message) -> io::Result<()> {
+        if self.client.is_verifying_dnssec() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "unvalidated responses are not cached when validating with DNSSec",
+            ));
+        }
+
+        let records = response
+            .answers()
+            .iter()
+            .filter(|r| {
+                r.rr_type() == query.query_type() && r.name() == query.name()
+            })
+            .map(|r| (r.rdata().clone(), r.ttl()))
+            .collect::<Vec<_>>();
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let edns_options: Vec<EdnsOption> = response
+            .edns()
+            .map(|edns| edns.options().options().values().cloned().collect())
+            .unwrap_or_default();
+
+        let key = CacheKey {
+            query,
+            dnssec_ok: false,
+            client_subnet: ClientSubnet::scope_of(response),
+        };
+        self.lock_cache().insert(key, records, edns_options, Instant::now());
+        Ok(())
     }
 
     /// Perform a lookup against this caching client, looking first in the cache for a result
     pub fn lookup(&mut self, query: Query) -> Box<Future<Item = Lookup, Error = io::Error>> {
-        QUERY_DEPTH.with(|c| *c.borrow_mut() += 1);
+        let chase = CnameChase::new(self.max_cname_chain);
+        let answer_order = self.answer_order;
+        let shuffle_rng = self.shuffle_rng.clone();
+        let start = Instant::now();
+        let lookup_id = self.observers.start(&query);
+        let observers = self.observers.clone();
+
+        let lookup = self.lookup_chased(query.clone(), chase, lookup_id).map(move |lookup| {
+            order_answers(lookup, answer_order, &shuffle_rng).with_elapsed(start.elapsed())
+        });
+        match lookup_id {
+            Some(id) => {
+                Box::new(lookup.then(move |result| {
+                    observers.completed(id, &query, result.as_ref(), start.elapsed());
+                    result
+                }))
+            }
+            None => Box::new(lookup),
+        }
+    }
 
-        Box::new(
-            QueryState::lookup(query, &mut self.client, self.lru.clone()).then(|f| {
-                QUERY_DEPTH.with(|c| *c.borrow_mut() -= 1);
-                f
-            }),
-        )
+    /// Looks up the target of a CNAME, continuing the chase of the chain
+    fn lookup_chased(
+        &mut self,
+        query: Query,
+        chase: CnameChase,
+        lookup_id: Option<LookupId>,
+    ) -> Box<Future<Item = Lookup, Error = io::Error>> {
+        Box::new(QueryState::lookup_chased(
+            query,
+            &mut self.client,
+            self.lru.clone(),
+            chase,
+            self.client_subnet,
+            self.keep_response,
+            self.answer_sources.clone(),
+            lookup_id.map(|id| (self.observers.clone(), id)),
+        ))
+    }
+}
+
+/// The random number generator of `AnswerOrder::Shuffle`
+#[derive(Clone)]
+struct ShuffleRng(Arc<Mutex<XorShiftRng>>);
+
+impl ShuffleRng {
+    /// The same seed always gives the same shuffles, None seeds it from the OS
+    fn new(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => {
+                let low = seed as u32;
+                let high = (seed >> 32) as u32;
+
+                // the state of a XorShiftRng must not be all zeros
+                XorShiftRng::from_seed([low, high, low ^ 0x9E37_79B9, high ^ 0x7F4A_7C15])
+            }
+            None => rand::weak_rng(),
+        };
+
+        ShuffleRng(Arc::new(Mutex::new(rng)))
+    }
+
+    fn shuffle<T>(&self, values: &mut [T]) {
+        match self.0.lock() {
+            Ok(mut rng) => rng.shuffle(values),
+            Err(_) => warn!("shuffle rng poisoned, ignoring"),
+        }
+    }
+}
+
+impl fmt::Debug for ShuffleRng {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ShuffleRng")
+    }
+}
+
+/// The CNAMEs followed to reach a query, so that lookups through a loop, or a chain which is too
+///  long, fail rather than query on and on
+#[derive(Clone, Debug)]
+struct CnameChase {
+    /// the names queried before, the original name first, each a CNAME of the one before it
+    names: Vec<Name>,
+    /// see `ResolverOpts::max_cname_chain`
+    max: usize,
+}
+
+impl CnameChase {
+    fn new(max: usize) -> Self {
+        CnameChase { names: vec![], max }
+    }
+
+    /// Checks that the CNAME to `name` may be followed from the end of the chain, the names
+    ///  followed since the query
+    fn check(&self, chain: &[Name], name: &Name) -> io::Result<()> {
+        if self.names.contains(name) || chain.contains(name) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("CNAME loop at: {}", name),
+            ));
+        }
+
+        // the first name of the chain was queried, all the others are CNAMEs
+        if self.names.len() + chain.len() > self.max {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("CNAME chain longer than {} at: {}", self.max, name),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Continues the chain with the chain of the lookup, which starts with the last name of it
+    fn chain_through(&self, mut chain: Vec<Name>, lookup: Lookup) -> io::Result<Lookup> {
+        // a cached lookup was not checked against this chase
+        for name in lookup.cname_chain().iter().skip(1) {
+            try!(self.check(&chain, name));
+            chain.push(name.clone());
+        }
+
+        Ok(lookup.with_cname_chain(chain))
+    }
+}
+
+impl Default for CnameChase {
+    fn default() -> Self {
+        Self::new(ResolverOpts::default().max_cname_chain)
     }
 }
 
 struct FromCache {
-    query: Query,
+    key: CacheKey,
+    /// the subnet of the clients of the lookup, see `CachingClient::set_client_subnet`
+    client_subnet: Option<ClientSubnet>,
+    /// see `CachingClient::set_keep_response`
+    keep_response: bool,
+    /// see `CachingClient::set_answer_sources`
+    answer_sources: AnswerSources,
+    /// the observed lookup the query is part of
+    observed: Option<(LookupObservers, LookupId)>,
     cache: Arc<Mutex<DnsLru>>,
 }
 
@@ -191,19 +783,26 @@ impl Future for FromCache {
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         // first transition any polling that is needed (mutable refs...)
-        match self.cache.try_lock() {
-            Err(TryLockError::WouldBlock) => {
+        match try_lock_lru(&self.cache) {
+            None => {
                 task::current().notify(); // yield
                 return Ok(Async::NotReady);
             }
-            // TODO: need to figure out a way to recover from this.
-            // It requires unwrapping the poisoned error and recreating the Mutex at a higher layer...
-            Err(TryLockError::Poisoned(poison)) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("poisoned: {}", poison),
-            )),
-            Ok(mut lru) => {
-                return Ok(Async::Ready(lru.get(&self.query, Instant::now())));
+            Some(mut lru) => {
+                let now = Instant::now();
+                let key = match self.client_subnet {
+                    Some(client_subnet) => lru.subnet_key(self.key.clone(), client_subnet, now),
+                    None => self.key.clone(),
+                };
+
+                let lookup = lru.get(&key, now);
+                if let Some(&(ref observers, id)) = self.observed.as_ref() {
+                    if lookup.is_some() {
+                        observers.cache_hit(id, &self.key.query);
+                    }
+                }
+
+                return Ok(Async::Ready(lookup));
             }
         }
     }
@@ -217,13 +816,43 @@ struct QueryFuture<C: ClientHandle + 'static> {
     /// is this a DNSSec validating client?
     dnssec: bool,
     client: CachingClient<C>,
+    /// the CNAMEs followed to reach the query
+    chase: CnameChase,
+    /// the clients for which the response is valid, see `ClientSubnet::scope_of`
+    scope: Option<ClientSubnet>,
+    /// keep the response along with the records
+    keep_response: bool,
+    /// the NameServer which answered the query
+    name_server: Option<NameServerSource>,
+    /// relates the attempts at the query to the lookup, if it is observed
+    in_flight: Option<InFlight>,
 }
 
 enum Records {
-    /// The records exists, a vec of rdata with ttl
-    Exists(Vec<(RData, u32)>),
-    /// Records do not exist, ttl for negative caching
-    NoData(Option<u32>),
+    /// The records exist, with what is kept along with them from the response
+    Exists {
+        /// the records, a vec of rdata with ttl
+        records: Vec<(RData, u32)>,
+        /// the EDNS options of the response
+        edns_options: Vec<EdnsOption>,
+        /// the addresses of the targets from the additional section
+        additionals: Vec<Record>,
+        /// whether the records were validated
+        proof: Proof,
+        /// the RRSIGs which validated the records
+        rrsigs: Vec<Record>,
+        /// the names followed through CNAMEs to the records, empty if there were none
+        cname_chain: Vec<Name>,
+        /// the response the records are from, if it is kept
+        responses: Vec<Message>,
+        /// the NameServer which answered, if it is known
+        name_server: Option<NameServerSource>,
+    },
+    /// mDNS records without the cache-flush bit, these are added to any already cached
+    #[cfg(feature = "mdns")]
+    Shared(Vec<(RData, u32)>, Vec<EdnsOption>),
+    /// Records do not exist, ttl for negative caching, and the Extended DNS Error of the response
+    NoData(Option<u32>, Option<ExtendedError>),
     /// Future lookup for recursive cname records
     CnameChain(Box<Future<Item = Lookup, Error = io::Error>>, u32),
     /// Already cached, chained queries
@@ -231,33 +860,46 @@ enum Records {
 }
 
 impl<C: ClientHandle + 'static> QueryFuture<C> {
-    fn next_query(&mut self, query: Query, cname_ttl: u32, message: Message) -> Records {
-        if QUERY_DEPTH.with(|c| *c.borrow() >= MAX_QUERY_DEPTH) {
-            // TODO: This should return an error
-            self.handle_nxdomain(message, true)
-        } else {
-            Records::CnameChain(self.client.lookup(query), cname_ttl)
-        }
+    /// Looks up the last name of the CNAME chain, the chain of the result is continued from it
+    fn next_query(&mut self, query: Query, cname_ttl: u32, cname_chain: Vec<Name>) -> Records {
+        let chase = self.chase.clone();
+        let mut next_chase = self.chase.clone();
+        next_chase
+            .names
+            .extend(cname_chain[..cname_chain.len() - 1].iter().cloned());
+
+        let lookup_id = self.client.lookup_id;
+        let lookup = self.client.lookup_chased(query, next_chase, lookup_id).and_then(
+            move |lookup| chase.chain_through(cname_chain, lookup),
+        );
+        Records::CnameChain(Box::new(lookup), cname_ttl)
     }
 
     fn handle_noerror(&mut self, mut message: Message) -> Poll<Records, io::Error> {
+        // the records are taken out of the message below
+        let responses = if self.keep_response {
+            vec![message.clone()]
+        } else {
+            vec![]
+        };
+
+        // records which are not about the query must not reach the cache
+        message.remove_out_of_bailiwick();
+
         // seek out CNAMES
         // TODO: figure out how to get rid of this clone
         let mut cname_ttl = 0;
-        let mut was_cname = false;
         let mut search_name: Name = self.query.name().clone();
+        let mut cname_chain = vec![search_name.clone()];
         while let Some(cname) = message.answers().iter().find(|r| {
             r.rr_type() == RecordType::CNAME && r.name() == &search_name
         })
         {
-            was_cname = true;
             cname_ttl = cname.ttl();
             if let &RData::CNAME(ref name) = cname.rdata() {
-                if search_name == *name {
-                    break; // already searched for this name
-                } else {
-                    search_name = name.clone();
-                }
+                try!(self.chase.check(&cname_chain, name));
+                cname_chain.push(name.clone());
+                search_name = name.clone();
             } else {
                 // now that is very odd...
                 warn!("Expected RData::CNAME in response record {:?}", cname);
@@ -265,16 +907,45 @@ impl<C: ClientHandle + 'static> QueryFuture<C> {
             }
         }
 
+        // mDNS answers for the local. domain are shared unless flagged otherwise, RFC 6762
+        #[cfg(feature = "mdns")]
+        let mut cache_flush = !Name::from_labels(vec!["local"]).zone_of(&search_name);
+
+        // the signatures of the records, kept by the SecureClientHandle only once validated
+        let rrsigs = if self.dnssec {
+            let query_type = self.query.query_type();
+            message
+                .answers()
+                .iter()
+                .filter(|r| {
+                    r.rr_type() == RecordType::RRSIG && r.name() == &search_name &&
+                        match *r.rdata() {
+                            RData::SIG(ref sig) => sig.type_covered() == query_type,
+                            _ => false,
+                        }
+                })
+                .cloned()
+                .collect()
+        } else {
+            vec![]
+        };
+
         // After following all the CNAMES to the last one, try and lookup the final name
+        let additionals = message.take_additionals();
         let records = message
             .take_answers()
             .into_iter()
-            .chain(message.take_additionals().into_iter())
+            .chain(additionals.iter().cloned())
             .filter_map(|r| {
                 let ttl = r.ttl();
                 // TODO: disable name validation with ResolverOpts?
                 // restrict to the RData type requested
                 if self.query.query_type() == r.rr_type() && &search_name == r.name() {
+                    #[cfg(feature = "mdns")]
+                    {
+                        cache_flush |= r.mdns_cache_flush();
+                    }
+
                     Some((r.unwrap_rdata(), ttl))
                 } else {
                     None
@@ -283,13 +954,48 @@ impl<C: ClientHandle + 'static> QueryFuture<C> {
             .collect::<Vec<_>>();
 
         if !records.is_empty() {
-            Ok(Async::Ready(Records::Exists(records)))
+            // e.g. policy information from a filtering upstream, made available on the Lookup
+            let edns_options: Vec<EdnsOption> = message
+                .edns()
+                .map(|edns| edns.options().options().values().cloned().collect())
+                .unwrap_or_default();
+
+            #[cfg(feature = "mdns")]
+            {
+                if !cache_flush {
+                    return Ok(Async::Ready(Records::Shared(records, edns_options)));
+                }
+            }
+
+            // the SecureClientHandle fails any response which does not validate, without it
+            //  nothing is known of the records' security
+            let proof = if self.dnssec {
+                Proof::Secure
+            } else {
+                Proof::Indeterminate
+            };
+
+            let additionals = target_addresses(&records, additionals);
+            if cname_chain.len() == 1 {
+                cname_chain.clear();
+            }
+
+            Ok(Async::Ready(Records::Exists {
+                records,
+                edns_options,
+                additionals,
+                proof,
+                rrsigs,
+                cname_chain,
+                responses,
+                name_server: self.name_server,
+            }))
         } else {
             // It was a CNAME, but not included in the request...
-            if was_cname {
+            if cname_chain.len() > 1 {
                 let next_query = Query::query(search_name, self.query.query_type());
                 Ok(Async::Ready(
-                    self.next_query(next_query, cname_ttl, message),
+                    self.next_query(next_query, cname_ttl, cname_chain),
                 ))
             } else {
                 // TODO: review See https://tools.ietf.org/html/rfc2308 for NoData section
@@ -314,6 +1020,10 @@ impl<C: ClientHandle + 'static> QueryFuture<C> {
     /// * `message` - message to extract SOA, etc, from for caching failed requests
     /// * `valid_nsec` - species that in DNSSec mode, this request is safe to cache
     fn handle_nxdomain(&self, mut message: Message, valid_nsec: bool) -> Records {
+        // the SOA must be of a zone of the query
+        message.remove_out_of_bailiwick();
+        let extended_error = message.edns().and_then(|edns| edns.extended_error()).cloned();
+
         if valid_nsec || !self.dnssec {
             //  if there were validated NSEC records
             let soa = message.take_name_servers().into_iter().find(|r| {
@@ -327,9 +1037,9 @@ impl<C: ClientHandle + 'static> QueryFuture<C> {
                 None
             };
 
-            Records::NoData(ttl)
+            Records::NoData(ttl, extended_error)
         } else {
-            Records::NoData(None)
+            Records::NoData(None, extended_error)
         }
     }
 }
@@ -343,17 +1053,19 @@ impl<C: ClientHandle + 'static> Future for QueryFuture<C> {
             Ok(Async::Ready(message)) => {
                 // TODO: take all records and cache them?
                 //  if it's DNSSec they must be signed, otherwise?
+                self.scope = ClientSubnet::scope_of(&message);
+                self.name_server = self.client.answer_sources.take(&message);
 
                 match message.response_code() {
-                    ResponseCode::NXDomain => Ok(Async::Ready(self.handle_nxdomain(
-                        message,
-                        false, /* false b/c DNSSec should not cache NXDomain */
-                    ))),
+                    // as with NoData, the secure_client_handle fails NXDomain responses which can
+                    //  not be verified with NSEC or NSEC3, so these are safe to cache
+                    ResponseCode::NXDomain => Ok(Async::Ready(self.handle_nxdomain(message, true))),
                     ResponseCode::NoError => self.handle_noerror(message),
-                    r @ _ => Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("DNS Error: {}", r),
-                    )),
+                    r @ _ => {
+                        let extended_error =
+                            message.edns().and_then(|edns| edns.extended_error()).cloned();
+                        Err(ResponseError::new(&self.query, r, extended_error).into())
+                    }
                 }
 
 
@@ -364,9 +1076,67 @@ impl<C: ClientHandle + 'static> Future for QueryFuture<C> {
     }
 }
 
+/// Orders the records of the lookup, see `ResolverOpts::answer_order`
+fn order_answers(lookup: Lookup, answer_order: AnswerOrder, shuffle_rng: &ShuffleRng) -> Lookup {
+    let mut order = (0..lookup.len()).collect::<Vec<usize>>();
+    match answer_order {
+        AnswerOrder::Wire => return lookup,
+        AnswerOrder::Canonical => {
+            let rdatas = lookup.iter().collect::<Vec<&RData>>();
+            order.sort_by(|&a, &b| rdatas[a].cmp(rdatas[b]));
+        }
+        AnswerOrder::Shuffle { .. } => shuffle_rng.shuffle(&mut order),
+    }
+
+    lookup.with_order(&order)
+}
+
+/// Collapses the records to their rdata, with the minimum TTL of them as the TTL of them all
+fn min_ttl(rdatas_and_ttl: Vec<(RData, u32)>) -> (Vec<RData>, u32) {
+    let len = rdatas_and_ttl.len();
+    rdatas_and_ttl.into_iter().fold(
+        (Vec::with_capacity(len), MAX_TTL),
+        |(mut rdatas, mut min_ttl), (rdata, ttl)| {
+            rdatas.push(rdata);
+            min_ttl = if ttl < min_ttl { ttl } else { min_ttl };
+            (rdatas, min_ttl)
+        },
+    )
+}
+
+/// The error of a future polled again after it completed, or failed
+fn polled_after_completion() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "lookup polled after completion")
+}
+
+/// Returns the A and AAAA records from the additionals for the targets of SRV, MX and NS records
+fn target_addresses(records: &[(RData, u32)], additionals: Vec<Record>) -> Vec<Record> {
+    let targets = records
+        .iter()
+        .filter_map(|&(ref rdata, _)| match *rdata {
+            RData::SRV(ref srv) => Some(srv.target()),
+            RData::MX(ref mx) => Some(mx.exchange()),
+            RData::NS(ref name) => Some(name),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if targets.is_empty() {
+        return vec![];
+    }
+
+    additionals
+        .into_iter()
+        .filter(|r| match r.rr_type() {
+            RecordType::A | RecordType::AAAA => targets.contains(&r.name()),
+            _ => false,
+        })
+        .collect()
+}
+
 struct InsertCache {
     rdatas: Records,
-    query: Query,
+    /// taken when the records are inserted, the future is then complete
+    key: Option<CacheKey>,
     cache: Arc<Mutex<DnsLru>>,
 }
 
@@ -376,35 +1146,62 @@ impl Future for InsertCache {
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         // first transition any polling that is needed (mutable refs...)
-        match self.cache.try_lock() {
-            Err(TryLockError::WouldBlock) => {
+        match try_lock_lru(&self.cache) {
+            None => {
                 task::current().notify(); // yield
                 return Ok(Async::NotReady);
             }
-            // TODO: need to figure out a way to recover from this.
-            // It requires unwrapping the poisoned error and recreating the Mutex at a higher layer...
-            Err(TryLockError::Poisoned(poison)) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("poisoned: {}", poison),
-            )),
-            Ok(mut lru) => {
-                // this will put this object into an inconsistent state, but no one should call poll again...
-                let query = mem::replace(&mut self.query, Query::new());
-                let rdata = mem::replace(&mut self.rdatas, Records::NoData(None));
+            Some(mut lru) => {
+                // the records are inserted once, polling again is an error
+                let key = match self.key.take() {
+                    Some(key) => key,
+                    None => return Err(polled_after_completion()),
+                };
+                let rdata = mem::replace(&mut self.rdatas, Records::NoData(None, None));
 
                 match rdata {
-                    Records::Exists(rdata) => Ok(Async::Ready(
-                        lru.insert(query, rdata, Instant::now()),
-                    )),
-                    Records::Chained(lookup, ttl) => Ok(Async::Ready(lru.duplicate(
-                        query,
+                    Records::Exists {
+                        records,
+                        edns_options,
+                        additionals,
+                        proof,
+                        rrsigs,
+                        cname_chain,
+                        responses,
+                        name_server,
+                    } => {
+                        let (rdatas, ttl) = min_ttl(records);
+                        let lookup =
+                            Lookup::with_edns_options(Arc::new(rdatas), Arc::new(edns_options))
+                                .with_additionals(additionals)
+                                .with_proof(proof)
+                                .with_rrsigs(rrsigs)
+                                .with_cname_chain(cname_chain)
+                                .with_responses(responses)
+                                .with_name_server(name_server);
+
+                        Ok(Async::Ready(
+                            lru.insert_lookup(key, lookup, ttl, Instant::now()),
+                        ))
+                    }
+                    #[cfg(feature = "mdns")]
+                    Records::Shared(rdata, edns_options) => {
+                        lru.insert_shared(key, rdata, edns_options, Instant::now())
+                            .map(Async::Ready)
+                    }
+                    Records::Chained(lookup, ttl) => Ok(Async::Ready(lru.insert_lookup(
+                        key,
                         lookup,
                         ttl,
                         Instant::now(),
                     ))),
-                    Records::NoData(Some(ttl)) => Err(lru.negative(query, ttl, Instant::now())),
-                    Records::NoData(None) |
-                    Records::CnameChain(..) => Err(DnsLru::nx_error(query)),
+                    Records::NoData(Some(ttl), extended_error) => {
+                        Err(lru.negative(key, ttl, extended_error, Instant::now()))
+                    }
+                    Records::NoData(None, extended_error) => {
+                        Err(DnsLru::nx_error(&key.query, extended_error))
+                    }
+                    Records::CnameChain(..) => Err(DnsLru::nx_error(&key.query, None)),
                 }
             }
         }
@@ -413,48 +1210,105 @@ impl Future for InsertCache {
 
 enum QueryState<C: ClientHandle + 'static> {
     /// In the FromCache state we evaluate cache entries for any results
-    FromCache(FromCache, C),
+    FromCache(FromCache, C, CnameChase),
     /// In the query state there is an active query that's been started, see Self::lookup()
     Query(QueryFuture<C>),
     /// CNAME lookup (internally it is making cached queries
-    CnameChain(Box<Future<Item = Lookup, Error = io::Error>>, Query, u32, Arc<Mutex<DnsLru>>),
+    CnameChain(Box<Future<Item = Lookup, Error = io::Error>>, CacheKey, u32, Arc<Mutex<DnsLru>>),
     /// State of adding the item to the cache
     InsertCache(InsertCache),
-    /// A state which should not occur
+    /// The state of a failed transition, polling it is an error
     Error,
 }
 
 impl<C: ClientHandle + 'static> QueryState<C> {
+    #[cfg(test)]
     pub(crate) fn lookup(query: Query, client: &mut C, cache: Arc<Mutex<DnsLru>>) -> QueryState<C> {
-        QueryState::FromCache(FromCache { query, cache }, client.clone())
+        Self::lookup_chased(
+            query,
+            client,
+            cache,
+            CnameChase::default(),
+            None,
+            false,
+            AnswerSources::default(),
+            None,
+        )
+    }
+
+    fn lookup_chased(
+        query: Query,
+        client: &mut C,
+        cache: Arc<Mutex<DnsLru>>,
+        chase: CnameChase,
+        client_subnet: Option<ClientSubnet>,
+        keep_response: bool,
+        answer_sources: AnswerSources,
+        observed: Option<(LookupObservers, LookupId)>,
+    ) -> QueryState<C> {
+        let key = CacheKey {
+            query,
+            dnssec_ok: client.is_verifying_dnssec(),
+            client_subnet: None,
+        };
+
+        QueryState::FromCache(
+            FromCache {
+                key,
+                client_subnet,
+                keep_response,
+                answer_sources,
+                observed,
+                cache,
+            },
+            client.clone(),
+            chase,
+        )
     }
 
     /// Query after a failed cache lookup
     ///
-    /// # Panics
-    ///
-    /// This will panic if the current state is not FromCache.
+    /// The state is left as Error if it is not FromCache.
     fn query_after_cache(&mut self) {
         let from_cache_state = mem::replace(self, QueryState::Error);
 
         // TODO: with specialization, could we define a custom query only on the FromCache type?
         match from_cache_state {
-            QueryState::FromCache(from_cache, mut client) => {
+            QueryState::FromCache(from_cache, mut client, chase) => {
                 let cache = from_cache.cache;
-                let query = from_cache.query;
+                let query = from_cache.key.query;
+                let dnssec = from_cache.key.dnssec_ok;
+                // the query is in flight before it is sent, for the first attempt
+                let in_flight = from_cache.observed.as_ref().map(|&(ref observers, id)| {
+                    observers.upstream(id, &query)
+                });
                 let message_future = client.lookup(query.clone());
+
+                let mut caching_client = CachingClient::with_cache(cache.clone(), client);
+                caching_client.set_client_subnet(from_cache.client_subnet);
+                caching_client.set_keep_response(from_cache.keep_response);
+                caching_client.set_answer_sources(from_cache.answer_sources);
+                if let Some((observers, id)) = from_cache.observed {
+                    caching_client.set_observers(observers);
+                    caching_client.lookup_id = Some(id);
+                }
                 mem::replace(
                     self,
                     QueryState::Query(QueryFuture {
                         message_future,
                         query,
-                        cache: cache.clone(),
-                        dnssec: client.is_verifying_dnssec(),
-                        client: CachingClient::with_cache(cache, client),
+                        cache,
+                        dnssec,
+                        client: caching_client,
+                        chase,
+                        scope: None,
+                        keep_response: from_cache.keep_response,
+                        name_server: None,
+                        in_flight,
                     }),
                 );
             }
-            _ => panic!("bad state, expected FromCache"),
+            _ => error!("bad state, expected FromCache"),
         }
     }
 
@@ -467,15 +1321,22 @@ impl<C: ClientHandle + 'static> QueryState<C> {
                                   message_future: _,
                                   query,
                                   cache,
-                                  dnssec: _,
+                                  dnssec,
                                   client: _,
+                                  chase: _,
+                                  scope,
+                                  keep_response: _,
+                                  name_server: _,
+                                  in_flight: _,
                               }) => {
-                mem::replace(
-                    self,
-                    QueryState::CnameChain(future, query, cname_ttl, cache),
-                );
+                let key = CacheKey {
+                    query,
+                    dnssec_ok: dnssec,
+                    client_subnet: scope,
+                };
+                mem::replace(self, QueryState::CnameChain(future, key, cname_ttl, cache));
             }
-            _ => panic!("bad state, expected Query"),
+            _ => error!("bad state, expected Query"),
         }
     }
 
@@ -488,45 +1349,55 @@ impl<C: ClientHandle + 'static> QueryState<C> {
                                   message_future: _,
                                   query,
                                   cache,
-                                  dnssec: _,
+                                  dnssec,
                                   client: _,
+                                  chase: _,
+                                  scope,
+                                  keep_response: _,
+                                  name_server: _,
+                                  in_flight: _,
                               }) => {
                 match rdatas {
                     // There are Cnames to lookup
                     Records::CnameChain(..) => {
-                        panic!("CnameChain should have been polled in poll() of QueryState");
+                        error!("CnameChain should have been polled in poll() of QueryState");
                     }
                     rdatas @ _ => {
+                        let key = CacheKey {
+                            query,
+                            dnssec_ok: dnssec,
+                            client_subnet: scope,
+                        };
                         mem::replace(
                             self,
                             QueryState::InsertCache(InsertCache {
                                 rdatas,
-                                query,
+                                key: Some(key),
                                 cache,
                             }),
                         );
                     }
                 }
             }
-            QueryState::CnameChain(_, query, _, cache) => {
+            QueryState::CnameChain(_, key, _, cache) => {
                 match rdatas {
                     // There are Cnames to lookup
                     Records::CnameChain(..) => {
-                        panic!("CnameChain should have been polled in poll() of QueryState");
+                        error!("CnameChain should have been polled in poll() of QueryState");
                     }
                     rdatas @ _ => {
                         mem::replace(
                             self,
                             QueryState::InsertCache(InsertCache {
                                 rdatas,
-                                query,
+                                key: Some(key),
                                 cache,
                             }),
                         );
                     }
                 }
             }
-            _ => panic!("bad state, expected Query"),
+            _ => error!("bad state, expected Query"),
         }
     }
 }
@@ -537,71 +1408,43 @@ impl<C: ClientHandle + 'static> Future for QueryState<C> {
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         // first transition any polling that is needed (mutable refs...)
-        let records: Option<Records>;
-        match *self {
+        let records = match *self {
             QueryState::FromCache(ref mut from_cache, ..) => {
-                match from_cache.poll() {
+                match try!(from_cache.poll()) {
                     // need to query since it wasn't in the cache
-                    Ok(Async::Ready(None)) => (), // handled below
-                    Ok(Async::Ready(Some(ips))) => return Ok(Async::Ready(ips)),
-                    Ok(Async::NotReady) => return Ok(Async::NotReady),
-                    Err(error) => return Err(error),
-                };
-
-                records = None;
+                    Async::Ready(None) => None,
+                    Async::Ready(Some(ips)) => return Ok(Async::Ready(ips)),
+                    Async::NotReady => return Ok(Async::NotReady),
+                }
             }
-            QueryState::Query(ref mut query, ..) => {
-                let poll = query.poll().map_err(|e| e.into());
-                match poll {
-                    Ok(Async::NotReady) => {
-                        return Ok(Async::NotReady);
-                    }
-                    Ok(Async::Ready(rdatas)) => records = Some(rdatas), // handled in next match
-                    Err(e) => {
-                        return Err(e);
-                    }
+            QueryState::Query(ref mut query) => {
+                match try!(query.poll()) {
+                    Async::Ready(rdatas) => Some(rdatas),
+                    Async::NotReady => return Ok(Async::NotReady),
                 }
             }
             QueryState::CnameChain(ref mut future, _, ttl, _) => {
-                let poll = future.poll();
-                match poll {
-                    Ok(Async::NotReady) => {
-                        return Ok(Async::NotReady);
-                    }
-                    Ok(Async::Ready(lookup)) => {
-                        records = Some(Records::Chained(lookup, ttl));
-                    }
-                    Err(e) => {
-                        return Err(e);
-                    }
+                match try!(future.poll()) {
+                    Async::Ready(lookup) => Some(Records::Chained(lookup, ttl)),
+                    Async::NotReady => return Ok(Async::NotReady),
                 }
             }
             QueryState::InsertCache(ref mut insert_cache) => {
                 return insert_cache.poll();
             }
-            QueryState::Error => panic!("invalid error state"),
+            QueryState::Error => return Err(polled_after_completion()),
+        };
+
+        // getting here means there are Aync::Ready available, a transition from a state which
+        //  does not expect it leaves the Error state
+        match records {
+            None => self.query_after_cache(),
+            Some(Records::CnameChain(future, ttl)) => self.cname(future, ttl),
+            Some(records) => self.cache(records),
         }
 
-        // getting here means there are Aync::Ready available.
-        match *self {
-            QueryState::FromCache(..) => self.query_after_cache(),
-            QueryState::Query(..) => {
-                match records {
-                    Some(Records::CnameChain(future, ttl)) => self.cname(future, ttl),
-                    Some(records) => {
-                        self.cache(records);
-                    }
-                    None => panic!("should have returned earlier"),
-                }
-            }
-            QueryState::CnameChain(..) => {
-                match records {
-                    Some(records) => self.cache(records),
-                    None => panic!("should have returned earlier"),
-                }
-            }
-            QueryState::InsertCache(..) |
-            QueryState::Error => panic!("should have returned earlier"),
+        if let QueryState::Error = *self {
+            return Err(io::Error::new(io::ErrorKind::Other, "invalid lookup state transition"));
         }
 
         task::current().notify(); // yield
@@ -616,10 +1459,17 @@ mod tests {
     use std::str::FromStr;
     use std::time::*;
 
+    use futures::future::poll_fn;
+    use trust_dns::error::ClientResult;
     use trust_dns::op::Query;
     use trust_dns::rr::{Name, RecordType};
+    use trust_dns::rr::dnssec::Algorithm;
+    use trust_dns::rr::rdata::{SIG, SRV};
+    use trust_dns::rr::rdata::opt::ExtendedErrorCode;
 
+    use observer::{LookupEvent, LookupObserver};
     use super::*;
+    use config::Protocol;
     use lookup_ip::tests::*;
 
     #[test]
@@ -643,22 +1493,64 @@ mod tests {
     #[test]
     fn test_insert() {
         let now = Instant::now();
-        let name = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let name = CacheKey::from(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
         let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 1)];
         let ips = vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))];
         let mut lru = DnsLru::new(1);
 
-        let rc_ips = lru.insert(name.clone(), ips_ttl, now);
+        let rc_ips = lru.insert(name.clone(), ips_ttl, vec![], now);
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
 
         let rc_ips = lru.get(&name, now).unwrap();
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
     }
 
+    #[cfg(feature = "mdns")]
+    #[test]
+    fn test_insert_shared() {
+        let now = Instant::now();
+        let name = CacheKey::from(Query::query(
+            Name::from_str("printer.local.").unwrap(),
+            RecordType::A,
+        ));
+        let first = RData::A(Ipv4Addr::new(169, 254, 0, 1));
+        let second = RData::A(Ipv4Addr::new(169, 254, 0, 2));
+        let mut lru = DnsLru::new(1);
+
+        lru.insert_shared(name.clone(), vec![(first.clone(), 120)], vec![], now)
+            .unwrap();
+        let lookup = lru.insert_shared(name.clone(), vec![(second.clone(), 120)], vec![], now)
+            .unwrap();
+        assert_eq!(lookup.iter().cloned().collect::<Vec<_>>(), vec![second.clone(), first]);
+
+        // a goodbye removes only the one record
+        let lookup = lru.insert_shared(name.clone(), vec![(second, 0)], vec![], now)
+            .unwrap();
+        assert_eq!(lookup.iter().count(), 1);
+
+        // records with the cache-flush bit replace everything
+        let third = RData::A(Ipv4Addr::new(169, 254, 0, 3));
+        let lookup = lru.insert(name.clone(), vec![(third.clone(), 120)], vec![], now);
+        assert_eq!(lookup.iter().cloned().collect::<Vec<_>>(), vec![third.clone()]);
+
+        // once all are gone, so is the entry
+        assert!(
+            lru.insert_shared(name.clone(), vec![(third, 0)], vec![], now)
+                .is_err()
+        );
+        assert!(lru.get(&name, now).is_none());
+    }
+
     #[test]
     fn test_insert_ttl() {
         let now = Instant::now();
-        let name = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+        let name = CacheKey::from(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
         // TTL should be 1
         let ips_ttl = vec![
             (RData::A(Ipv4Addr::new(127, 0, 0, 1)), 1),
@@ -670,7 +1562,7 @@ mod tests {
         ];
         let mut lru = DnsLru::new(1);
 
-        lru.insert(name.clone(), ips_ttl, now);
+        lru.insert(name.clone(), ips_ttl, vec![], now);
 
         // still valid
         let rc_ips = lru.get(&name, now + Duration::from_secs(1)).unwrap();
@@ -681,6 +1573,161 @@ mod tests {
         assert!(rc_ips.is_none());
     }
 
+    #[test]
+    fn test_stats() {
+        let now = Instant::now();
+        let name = CacheKey::from(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let nx_name = CacheKey::from(Query::query(
+            Name::from_str("nx.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 1)];
+        let mut lru = DnsLru::new(1);
+
+        assert!(lru.get(&name, now).is_none());
+        lru.insert(name.clone(), ips_ttl, vec![], now);
+        assert!(lru.get(&name, now).is_some());
+
+        // pushes out the first entry
+        lru.negative(nx_name.clone(), 1, None, now);
+        assert!(lru.get(&nx_name, now).is_none());
+        assert!(lru.get(&name, now).is_none());
+
+        assert_eq!(
+            lru.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 2,
+                negative_hits: 1,
+                evictions: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_entries() {
+        let now = Instant::now();
+        let name = CacheKey::from(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let nx_name = CacheKey::from(Query::query(
+            Name::from_str("nx.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 2)];
+        let mut lru = DnsLru::new(2);
+
+        lru.insert(name.clone(), ips_ttl, vec![], now);
+        lru.negative(nx_name.clone(), 1, None, now);
+
+        let entries = lru.entries(now + Duration::from_secs(1));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].query, name.query);
+        assert_eq!(entries[0].remaining_ttl, Duration::from_secs(1));
+        assert_eq!(
+            entries[0]
+                .lookup
+                .as_ref()
+                .unwrap()
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+        assert_eq!(entries[1].query, nx_name.query);
+        assert!(entries[1].lookup.is_none());
+
+        // the negative entry has expired
+        let entries = lru.entries(now + Duration::from_secs(2));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].query, name.query);
+    }
+
+    #[cfg(feature = "psl")]
+    #[test]
+    fn test_domain_counts() {
+        let now = Instant::now();
+        let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 10)];
+        let psl = PublicSuffixList::parse("uk\nco.uk\n");
+        let mut lru = DnsLru::new(4);
+
+        for name in vec!["www.example.co.uk.", "mail.Example.co.uk.", "co.uk.", "www.example.test."] {
+            let query = Query::query(Name::from_str(name).unwrap(), RecordType::A);
+            lru.insert(query.into(), ips_ttl.clone(), vec![], now);
+        }
+
+        let counts = lru.domain_counts(&psl, now);
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[0], (Name::from_str("example.co.uk.").unwrap(), 2));
+        // a public suffix is counted under itself
+        assert!(counts.contains(&(Name::from_str("co.uk.").unwrap(), 1)));
+        assert!(counts.contains(&(Name::from_str("example.test.").unwrap(), 1)));
+        assert!(lru.domain_counts(&psl, now + Duration::from_secs(11)).is_empty());
+    }
+
+    #[test]
+    fn test_flush() {
+        let now = Instant::now();
+        let ips_ttl = vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), 10)];
+        let apex = CacheKey::from(Query::query(
+            Name::from_str("example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let www = CacheKey::from(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::AAAA,
+        ));
+        let other = CacheKey::from(Query::query(
+            Name::from_str("www.example.net.").unwrap(),
+            RecordType::A,
+        ));
+        let mut lru = DnsLru::new(3);
+
+        lru.insert(apex.clone(), ips_ttl.clone(), vec![], now);
+        lru.insert(www.clone(), ips_ttl.clone(), vec![], now);
+        lru.insert(other.clone(), ips_ttl.clone(), vec![], now);
+
+        assert!(lru.remove(&other.query));
+        assert!(!lru.remove(&other.query));
+        assert!(lru.get(&other, now).is_none());
+
+        lru.insert(other.clone(), ips_ttl.clone(), vec![], now);
+        assert_eq!(
+            lru.remove_subtree(&Name::from_str("EXAMPLE.com.").unwrap()),
+            2
+        );
+        assert!(lru.get(&apex, now).is_none());
+        assert!(lru.get(&www, now).is_none());
+        assert!(lru.get(&other, now).is_some());
+
+        lru.clear();
+        assert!(lru.get(&other, now).is_none());
+        assert!(lru.entries(now).is_empty());
+    }
+
+    #[test]
+    fn test_poll_after_completion() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![v4_message()]);
+        let mut state = QueryState::lookup(Query::new(), &mut client, cache);
+
+        let ips = poll_fn(|| state.poll()).wait().unwrap();
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+
+        // polling the completed lookup again fails it, rather than panicking
+        assert!(poll_fn(|| state.poll()).wait().is_err());
+
+        let mut state = QueryState::<MockClientHandle>::Error;
+        assert!(poll_fn(|| state.poll()).wait().is_err());
+    }
+
     #[test]
     fn test_empty_cache() {
         let cache = Arc::new(Mutex::new(DnsLru::new(1)));
@@ -701,6 +1748,7 @@ mod tests {
         cache.lock().unwrap().insert(
             Query::new(),
             vec![(RData::A(Ipv4Addr::new(127, 0, 0, 1)), u32::max_value())],
+            vec![],
             Instant::now(),
         );
 
@@ -743,4 +1791,704 @@ mod tests {
             vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
         );
     }
+
+    #[test]
+    fn test_poisoned_cache() {
+        use std::thread;
+
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![v4_message()]);
+        QueryState::lookup(Query::new(), &mut client, cache.clone())
+            .wait()
+            .unwrap();
+
+        // a task which panics while it holds the lock
+        let poisoner = cache.clone();
+        assert!(
+            thread::spawn(move || {
+                let _lru = poisoner.lock().unwrap();
+                panic!("poisoning the cache");
+            }).join()
+                .is_err()
+        );
+        assert!(cache.is_poisoned());
+
+        // the cache is still used, both to answer and to insert
+        let mut client = mock(vec![empty()]);
+        let ips = QueryState::lookup(Query::new(), &mut client, cache.clone())
+            .wait()
+            .unwrap();
+        assert_eq!(
+            ips.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]
+        );
+
+        let caching_client = CachingClient::with_cache(cache, mock(vec![]));
+        assert!(caching_client.clear_cache().is_ok());
+        assert!(caching_client.cache_entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_response_edns_options() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let policy = EdnsOption::Unknown(65001, b"blocked:ads".to_vec());
+
+        let mut message = v4_message().unwrap();
+        message.edns_mut().set_option(policy.clone());
+        let mut client = mock(vec![Ok(message)]);
+
+        let lookup = QueryState::lookup(Query::new(), &mut client, cache.clone())
+            .wait()
+            .unwrap();
+        assert_eq!(lookup.edns_options(), &[policy.clone()]);
+
+        // the options are cached along with the records
+        let mut client = mock(vec![empty()]);
+        let lookup = QueryState::lookup(Query::new(), &mut client, cache)
+            .wait()
+            .unwrap();
+        assert_eq!(lookup.edns_options(), &[policy]);
+    }
+
+    #[test]
+    fn test_extended_error() {
+        let blocked = ExtendedError::new(ExtendedErrorCode::Blocked, "ads".to_string());
+
+        let mut message = Message::new();
+        message.edns_mut().set_option(EdnsOption::ExtendedError(blocked.clone()));
+        message.set_response_code(ResponseCode::NXDomain);
+
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![Ok(message)]);
+        let error = QueryState::lookup(Query::new(), &mut client, cache)
+            .wait()
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AddrNotAvailable);
+
+        let response_error = ResponseError::from_io(&error).expect("not a ResponseError");
+        assert_eq!(response_error.response_code(), ResponseCode::NXDomain);
+        assert_eq!(response_error.extended_error(), Some(&blocked));
+
+        let mut message = Message::new();
+        message.edns_mut().set_option(EdnsOption::ExtendedError(ExtendedError::new(
+            ExtendedErrorCode::NoReachableAuthority,
+            String::new(),
+        )));
+        message.set_response_code(ResponseCode::ServFail);
+
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![Ok(message)]);
+        let error = QueryState::lookup(Query::new(), &mut client, cache)
+            .wait()
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Other);
+
+        let response_error = ResponseError::from_io(&error).expect("not a ResponseError");
+        assert_eq!(response_error.response_code(), ResponseCode::ServFail);
+        assert_eq!(
+            response_error.extended_error().map(|e| e.info_code()),
+            Some(ExtendedErrorCode::NoReachableAuthority)
+        );
+    }
+
+    #[test]
+    fn test_secure_proof() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = secure_mock(vec![v4_message()]);
+
+        let lookup = QueryState::lookup(Query::new(), &mut client, cache.clone())
+            .wait()
+            .unwrap();
+        assert_eq!(lookup.proof(), Proof::Secure);
+
+        // the proof is cached along with the records
+        let mut client = mock(vec![empty()]);
+        let lookup = QueryState::lookup(Query::new(), &mut client, cache)
+            .wait()
+            .unwrap();
+        assert_eq!(lookup.proof(), Proof::Secure);
+    }
+
+    #[test]
+    fn test_indeterminate_proof() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![v4_message()]);
+
+        let lookup = QueryState::lookup(Query::new(), &mut client, cache)
+            .wait()
+            .unwrap();
+        assert_eq!(lookup.proof(), Proof::Indeterminate);
+        assert!(lookup.rrsigs().is_empty());
+    }
+
+    fn rrsig(type_covered: RecordType) -> Record {
+        Record::from_rdata(
+            Name::root(),
+            86400,
+            RecordType::RRSIG,
+            RData::SIG(SIG::new(
+                type_covered,
+                Algorithm::RSASHA256,
+                0,
+                86400,
+                0,
+                0,
+                20326,
+                Name::root(),
+                vec![],
+            )),
+        )
+    }
+
+    #[test]
+    fn test_secure_rrsigs() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut message = v4_message().unwrap();
+        message.add_answer(rrsig(RecordType::A));
+        message.add_answer(rrsig(RecordType::NS));
+        let mut client = secure_mock(vec![Ok(message)]);
+
+        let lookup = QueryState::lookup(Query::new(), &mut client, cache.clone())
+            .wait()
+            .unwrap();
+        assert_eq!(lookup.rrsigs(), &[rrsig(RecordType::A)]);
+        assert_eq!(
+            lookup.iter_with_proof().collect::<Vec<_>>(),
+            vec![(&RData::A(Ipv4Addr::new(127, 0, 0, 1)), Proof::Secure)]
+        );
+
+        // the signatures are cached along with the records
+        let mut client = mock(vec![empty()]);
+        let lookup = QueryState::lookup(Query::new(), &mut client, cache)
+            .wait()
+            .unwrap();
+        assert_eq!(lookup.rrsigs(), &[rrsig(RecordType::A)]);
+    }
+
+    #[test]
+    fn test_unvalidated_rrsigs() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut message = v4_message().unwrap();
+        message.add_answer(rrsig(RecordType::A));
+        let mut client = mock(vec![Ok(message)]);
+
+        let lookup = QueryState::lookup(Query::new(), &mut client, cache)
+            .wait()
+            .unwrap();
+        assert!(lookup.rrsigs().is_empty());
+    }
+
+    #[test]
+    fn test_cache_response_when_validating() {
+        let message = v4_message().unwrap();
+
+        let client = CachingClient::new(1, secure_mock(vec![]));
+        assert!(client.cache_response(Query::new(), &message).is_err());
+        assert!(client.cache_entries().unwrap().is_empty());
+
+        let client = CachingClient::new(1, mock(vec![]));
+        client.cache_response(Query::new(), &message).unwrap();
+        assert_eq!(
+            client.cache_entries().unwrap()[0].lookup.as_ref().unwrap().proof(),
+            Proof::Indeterminate
+        );
+    }
+
+    fn cname(name: &str, target: &str) -> Record {
+        Record::from_rdata(
+            Name::from_str(name).unwrap(),
+            86400,
+            RecordType::CNAME,
+            RData::CNAME(Name::from_str(target).unwrap()),
+        )
+    }
+
+    fn cname_message(cnames: Vec<Record>, a_name: Option<&str>) -> ClientResult<Message> {
+        let mut message = Message::new();
+        message.insert_answers(cnames);
+        if let Some(a_name) = a_name {
+            message.add_answer(Record::from_rdata(
+                Name::from_str(a_name).unwrap(),
+                86400,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(127, 0, 0, 1)),
+            ));
+        }
+        Ok(message)
+    }
+
+    fn www_query() -> Query {
+        Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A)
+    }
+
+    fn names(names: &[&str]) -> Vec<Name> {
+        names.iter().map(|n| Name::from_str(n).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_cname_chain() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let message = cname_message(
+            vec![
+                cname("www.example.com.", "cdn.example.net."),
+                cname("cdn.example.net.", "edge.example.net."),
+            ],
+            Some("edge.example.net."),
+        );
+        let mut client = mock(vec![message]);
+
+        let lookup = QueryState::lookup(www_query(), &mut client, cache.clone())
+            .wait()
+            .unwrap();
+        let chain = names(&["www.example.com.", "cdn.example.net.", "edge.example.net."]);
+        assert_eq!(lookup.cname_chain(), &chain[..]);
+
+        // the chain is cached along with the records
+        let mut client = mock(vec![empty()]);
+        let lookup = QueryState::lookup(www_query(), &mut client, cache)
+            .wait()
+            .unwrap();
+        assert_eq!(lookup.cname_chain(), &chain[..]);
+
+        // no chain without CNAMEs
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![v4_message()]);
+        let lookup = QueryState::lookup(Query::new(), &mut client, cache)
+            .wait()
+            .unwrap();
+        assert!(lookup.cname_chain().is_empty());
+    }
+
+    #[test]
+    fn test_cname_chain_across_queries() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(2)));
+        // the mock responds from the end
+        let mut client = mock(vec![
+            cname_message(
+                vec![cname("cdn.example.net.", "edge.example.net.")],
+                Some("edge.example.net."),
+            ),
+            cname_message(vec![cname("www.example.com.", "cdn.example.net.")], None),
+        ]);
+
+        let lookup = QueryState::lookup(www_query(), &mut client, cache)
+            .wait()
+            .unwrap();
+        assert_eq!(
+            lookup.cname_chain(),
+            &names(&["www.example.com.", "cdn.example.net.", "edge.example.net."])[..]
+        );
+    }
+
+    #[test]
+    fn test_cname_loop() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![cname_message(
+            vec![cname("www.example.com.", "www.example.com.")],
+            Some("www.example.com."),
+        )]);
+        assert!(
+            QueryState::lookup(www_query(), &mut client, cache)
+                .wait()
+                .is_err()
+        );
+
+        // the loop back to the queried name is only seen in the second response
+        let cache = Arc::new(Mutex::new(DnsLru::new(2)));
+        let mut client = mock(vec![
+            cname_message(vec![cname("cdn.example.net.", "www.example.com.")], None),
+            cname_message(vec![cname("www.example.com.", "cdn.example.net.")], None),
+        ]);
+        let error = QueryState::lookup(www_query(), &mut client, cache)
+            .wait()
+            .unwrap_err();
+        assert!(format!("{}", error).contains("CNAME loop"));
+    }
+
+    #[test]
+    fn test_cname_chain_too_long() {
+        let message = || {
+            cname_message(
+                vec![
+                    cname("www.example.com.", "cdn.example.net."),
+                    cname("cdn.example.net.", "edge.example.net."),
+                ],
+                Some("edge.example.net."),
+            )
+        };
+
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![message()]);
+        let error = QueryState::lookup_chased(
+            www_query(),
+            &mut client,
+            cache,
+            CnameChase::new(1),
+            None,
+            false,
+            AnswerSources::default(),
+            None,
+        ).wait()
+            .unwrap_err();
+        assert!(format!("{}", error).contains("CNAME chain longer than 1"));
+
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![message()]);
+        assert!(
+            QueryState::lookup_chased(
+                www_query(),
+                &mut client,
+                cache,
+                CnameChase::new(2),
+                None,
+                false,
+                AnswerSources::default(),
+                None,
+            )
+                .wait()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_dnssec_cache_key() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(2)));
+        let mut client = secure_mock(vec![v4_message()]);
+        assert!(
+            QueryState::lookup(Query::new(), &mut client, cache.clone())
+                .wait()
+                .is_ok()
+        );
+
+        // the validated answer is not shared with a client which does not validate, nor the
+        //  reverse
+        let mut client = mock(vec![v4_message(), empty()]);
+        assert!(
+            QueryState::lookup(Query::new(), &mut client, cache.clone())
+                .wait()
+                .is_err()
+        );
+        let mut client = secure_mock(vec![empty()]);
+        assert_eq!(
+            QueryState::lookup(Query::new(), &mut client, cache)
+                .wait()
+                .unwrap()
+                .proof(),
+            Proof::Secure
+        );
+    }
+
+    fn ecs_message(scope: u8) -> ClientResult<Message> {
+        let mut message = v4_message().unwrap();
+        message
+            .edns_mut()
+            .set_option(EdnsOption::Unknown(8, vec![0, 1, 24, scope, 192, 0, 2]));
+        Ok(message)
+    }
+
+    fn subnet(address: [u8; 4], prefix: u8) -> Option<ClientSubnet> {
+        Some(ClientSubnet::new(IpAddr::V4(Ipv4Addr::from(address)), prefix))
+    }
+
+    #[test]
+    fn test_client_subnet_cache_key() {
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![ecs_message(24)]);
+        assert!(
+            QueryState::lookup(Query::new(), &mut client, cache.clone())
+                .wait()
+                .is_ok()
+        );
+        assert_eq!(
+            cache.lock().unwrap().entries(Instant::now())[0].client_subnet,
+            subnet([192, 0, 2, 0], 24)
+        );
+
+        // only clients within the scope are answered from the cache
+        let lookup = |client_subnet| {
+            let mut client = mock(vec![empty()]);
+            QueryState::lookup_chased(
+                Query::new(),
+                &mut client,
+                cache.clone(),
+                CnameChase::default(),
+                client_subnet,
+                false,
+                AnswerSources::default(),
+                None,
+            ).wait()
+        };
+        assert!(lookup(subnet([192, 0, 2, 77], 32)).is_ok());
+        assert!(lookup(subnet([192, 0, 2, 0], 24)).is_ok());
+        assert!(lookup(subnet([192, 0, 0, 0], 16)).is_err());
+        assert!(lookup(subnet([198, 51, 100, 0], 24)).is_err());
+        assert!(lookup(None).is_err());
+    }
+
+    #[test]
+    fn test_client_subnet_scope_zero() {
+        // the answer is the same for all clients
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![ecs_message(0)]);
+        assert!(
+            QueryState::lookup(Query::new(), &mut client, cache.clone())
+                .wait()
+                .is_ok()
+        );
+
+        let mut client = mock(vec![empty()]);
+        assert!(
+            QueryState::lookup(Query::new(), &mut client, cache)
+                .wait()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_client_subnet() {
+        let subnet = ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 77)), 20);
+        assert_eq!(subnet.address(), IpAddr::V4(Ipv4Addr::new(192, 0, 0, 0)));
+        assert_eq!(subnet.prefix(), 20);
+
+        let address = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        let subnet = ClientSubnet::new(address, 200);
+        assert_eq!(subnet.address(), address);
+        assert_eq!(subnet.prefix(), 128);
+
+        let option = EdnsOption::Unknown(8, vec![0, 2, 32, 0, 0x20, 0x01, 0x0d, 0xb8]);
+        assert_eq!(
+            ClientSubnet::from_option(&option),
+            Some(ClientSubnet::new(address, 32))
+        );
+        assert!(ClientSubnet::from_option(&EdnsOption::Unknown(65001, vec![])).is_none());
+
+        let subnet = ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 77)), 24);
+        assert_eq!(ClientSubnet::from_option(&subnet.to_option()), Some(subnet));
+    }
+
+    #[test]
+    fn test_out_of_bailiwick_additionals() {
+        let name = Name::from_str("_sip._udp.example.com.").unwrap();
+        let query = Query::query(name, RecordType::SRV);
+        let target = Name::from_str("sip.example.com.").unwrap();
+        let other = Name::from_str("sip.example.net.").unwrap();
+        let a = |name: &Name, octet: u8| {
+            let rdata = RData::A(Ipv4Addr::new(10, 0, 0, octet));
+            Record::from_rdata(name.clone(), 86400, RecordType::A, rdata)
+        };
+
+        let mut message = Message::new();
+        message.add_query(query.clone());
+        message.insert_answers(vec![
+            Record::from_rdata(
+                query.name().clone(),
+                86400,
+                RecordType::SRV,
+                RData::SRV(SRV::new(0, 0, 5060, target.clone())),
+            ),
+            Record::from_rdata(
+                query.name().clone(),
+                86400,
+                RecordType::SRV,
+                RData::SRV(SRV::new(0, 0, 5060, other.clone())),
+            ),
+        ]);
+        message.insert_name_servers(vec![
+            Record::from_rdata(
+                Name::from_str("example.com.").unwrap(),
+                86400,
+                RecordType::NS,
+                RData::NS(Name::from_str("ns.example.com.").unwrap()),
+            ),
+        ]);
+        message.insert_additionals(vec![a(&target, 1), a(&other, 2)]);
+
+        let cache = Arc::new(Mutex::new(DnsLru::new(1)));
+        let mut client = mock(vec![Ok(message)]);
+        let lookup = QueryState::lookup(query, &mut client, cache).wait().unwrap();
+
+        // the address of the target outside of example.com. is not kept
+        assert_eq!(lookup.iter().count(), 2);
+        assert_eq!(lookup.additionals(), &[a(&target, 1)]);
+    }
+
+    fn a_message(octets: &[u8]) -> ClientResult<Message> {
+        let mut message = Message::new();
+        message.insert_answers(
+            octets
+                .iter()
+                .map(|&octet| {
+                    Record::from_rdata(
+                        Name::root(),
+                        86400,
+                        RecordType::A,
+                        RData::A(Ipv4Addr::new(10, 0, 0, octet)),
+                    )
+                })
+                .collect(),
+        );
+        Ok(message)
+    }
+
+    fn last_octets(lookup: Lookup) -> Vec<u8> {
+        lookup
+            .iter()
+            .map(|rdata| match *rdata {
+                RData::A(ip) => ip.octets()[3],
+                _ => panic!("expected A: {:?}", rdata),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lookup_source() {
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53)), 53);
+        let source = NameServerSource::new(socket_addr, Protocol::Udp, Duration::from_millis(20));
+        let response = v4_message().unwrap();
+        let answer_sources = AnswerSources::default();
+        answer_sources.insert(&response, source);
+
+        let mut client = CachingClient::new(1, mock(vec![Ok(response)]));
+        client.set_answer_sources(answer_sources);
+
+        let lookup = client.lookup(Query::new()).wait().unwrap();
+        assert!(!lookup.source().is_cached());
+        assert_eq!(lookup.source().name_server(), Some(source));
+
+        // the NameServer of cached records is that which answered when they were cached
+        let lookup = client.lookup(Query::new()).wait().unwrap();
+        assert!(lookup.source().is_cached());
+        assert!(lookup.source().cache_ttl().unwrap() <= 86400);
+        assert_eq!(lookup.source().name_server(), Some(source));
+
+        // the source of an unknown response is not known
+        let mut client = CachingClient::new(1, mock(vec![v4_message()]));
+        let lookup = client.lookup(Query::new()).wait().unwrap();
+        assert_eq!(lookup.source().name_server(), None);
+    }
+
+    #[test]
+    fn test_answer_order() {
+        let mut client = CachingClient::new(1, mock(vec![a_message(&[3, 1, 2])]));
+        let lookup = client.lookup(Query::new()).wait().unwrap();
+        assert_eq!(last_octets(lookup), vec![3, 1, 2]);
+
+        // the cached records are kept in the order they were received
+        client.set_answer_order(AnswerOrder::Canonical);
+        let lookup = client.lookup(Query::new()).wait().unwrap();
+        assert_eq!(last_octets(lookup), vec![1, 2, 3]);
+
+        client.set_answer_order(AnswerOrder::Wire);
+        let lookup = client.lookup(Query::new()).wait().unwrap();
+        assert_eq!(last_octets(lookup), vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn test_answer_order_shuffle() {
+        let shuffles = |seed: u64| {
+            let mut client =
+                CachingClient::new(1, mock(vec![a_message(&[1, 2, 3, 4, 5, 6, 7, 8])]));
+            client.set_answer_order(AnswerOrder::Shuffle { seed: Some(seed) });
+
+            (0..4)
+                .map(|_| last_octets(client.lookup(Query::new()).wait().unwrap()))
+                .collect::<Vec<_>>()
+        };
+
+        // the same seed, the same shuffles
+        let shuffled = shuffles(1);
+        assert_eq!(shuffled, shuffles(1));
+        assert!(shuffled != shuffles(2));
+
+        // each lookup from the cache is shuffled anew
+        assert!(shuffled.iter().any(|octets| *octets != shuffled[0]));
+        for octets in shuffled {
+            let mut sorted = octets.clone();
+            sorted.sort();
+            assert_eq!(sorted, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        }
+    }
+
+    #[test]
+    fn test_keep_response() {
+        let mut client = CachingClient::new(1, mock(vec![a_message(&[1, 2])]));
+        let lookup = client.lookup(Query::new()).wait().unwrap();
+        assert!(lookup.responses().is_empty());
+
+        let mut client = CachingClient::new(1, mock(vec![a_message(&[1, 2])]));
+        client.set_keep_response(true);
+        let lookup = client.lookup(Query::new()).wait().unwrap();
+        assert_eq!(lookup.responses().len(), 1);
+        assert_eq!(lookup.responses()[0].answers().len(), 2);
+
+        // the response is cached along with the records
+        let lookup = client.lookup(Query::new()).wait().unwrap();
+        assert_eq!(lookup.responses().len(), 1);
+    }
+
+    #[test]
+    fn test_keep_response_of_cname_chain() {
+        // the mock responds from the end
+        let mut client = CachingClient::new(
+            2,
+            mock(vec![
+                cname_message(
+                    vec![cname("cdn.example.net.", "edge.example.net.")],
+                    Some("edge.example.net."),
+                ),
+                cname_message(vec![cname("www.example.com.", "cdn.example.net.")], None),
+            ]),
+        );
+        client.set_keep_response(true);
+
+        // that of the query of the final name
+        let lookup = client.lookup(www_query()).wait().unwrap();
+        assert_eq!(lookup.responses().len(), 1);
+        assert_eq!(lookup.responses()[0].answers().len(), 2);
+    }
+
+    #[derive(Default)]
+    struct EventRecorder(Mutex<Vec<String>>);
+
+    impl LookupObserver for EventRecorder {
+        fn on_event(&self, event: &LookupEvent) {
+            let event = match *event {
+                LookupEvent::Started { .. } => "started".to_string(),
+                LookupEvent::CacheHit { .. } => "cache hit".to_string(),
+                LookupEvent::UpstreamAttempt { attempt, .. } => format!("attempt {}", attempt),
+                LookupEvent::AttemptFailed { .. } => "attempt failed".to_string(),
+                LookupEvent::Completed { result: Ok(lookup), .. } => {
+                    format!("completed with {} records", lookup.len())
+                }
+                LookupEvent::Completed { result: Err(_), .. } => "failed".to_string(),
+            };
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    #[test]
+    fn test_lookup_observer() {
+        let recorder = Arc::new(EventRecorder::default());
+        let observers = LookupObservers::default();
+        observers.set_observer(Some(recorder.clone()));
+
+        let mut client = CachingClient::new(1, mock(vec![v4_message()]));
+        client.set_observers(observers);
+        client.lookup(Query::new()).wait().unwrap();
+        client.lookup(Query::new()).wait().unwrap();
+        assert!(client.lookup(www_query()).wait().is_err());
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                "started",
+                "completed with 1 records",
+                "started",
+                "cache hit",
+                "completed with 1 records",
+                "started",
+                "failed",
+            ]
+        );
+    }
 }
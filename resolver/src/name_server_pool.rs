@@ -5,28 +5,50 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::cmp::{self, Ordering};
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::mem;
-use std::sync::{Arc, Mutex, TryLockError};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex, TryLockError, Weak};
 use std::time::{Duration, Instant};
 
-use futures::{Async, future, Future, Poll, task};
-use tokio_core::reactor::Handle;
+use futures::{Async, future, Future, Poll, Stream, task};
+use rand;
+use tokio_core::reactor::{Handle, Interval, Timeout};
 
 use trust_dns::error::*;
 use trust_dns::client::{BasicClientHandle, ClientFuture, ClientHandle};
 use trust_dns::op::{Edns, Message, ResponseCode};
+use trust_dns::rr::{DNSClass, Name, RData, Record, RecordType};
+use trust_dns::rr::rdata::opt::{Cookie, EdnsOption};
 use trust_dns::udp::UdpClientStream;
 use trust_dns::tcp::TcpClientStream;
 use trust_dns_proto::DnsHandle;
+#[cfg(feature = "mdns")]
+use trust_dns_proto::multicast::{MdnsClientStream, MdnsQueryType};
 
-use config::{NameServerConfig, Protocol, ResolverConfig, ResolverOpts};
+use lookup::NameServerSource;
+use name_server_stats::{from_micros, to_micros, NameServerStatistics};
+use observer::LookupObservers;
+use config::{IllegalDataPolicy, NameCompression, NameServerConfig, Protocol, ResolverConfig,
+             ResolverOpts, RetryPolicy, TransportRace, TransportStrategy};
 
 const MIN_RETRY_DELAY_MS: u64 = 500;
 const MAX_RETRY_DELAY_S: u64 = 360;
 
+/// Upper bound, as a percentage of the retry delay, of the random delay added to each reconnect
+const MAX_RETRY_JITTER_PERCENT: u32 = 50;
+
+/// Seconds for which a NameServer which answered with SERVFAIL is the least preferred
+const SERVFAIL_QUARANTINE_S: u64 = 30;
+
+/// Weight, out of 10, of the previous smoothed round trip time when a new one is measured
+const SRTT_DECAY: u64 = 7;
+
+/// Lower bound of the adaptive timeout, see `ResolverOpts::adaptive_timeout`
+const MIN_ADAPTIVE_TIMEOUT_MS: u64 = 200;
+
 /// State of a connection with a remote NameServer.
 #[derive(Clone, Debug)]
 enum NameServerState {
@@ -78,6 +100,18 @@ struct NameServerStats {
     state: NameServerState,
     successes: usize,
     failures: usize,
+    /// failures since the last success, drives the reconnect backoff
+    consecutive_failures: usize,
+    /// smoothed round trip time of successful requests, in microseconds
+    srtt_micros: Option<u64>,
+    /// mean deviation of the round trip times from the smoothed one, in microseconds
+    rttvar_micros: Option<u64>,
+    /// the NameServer answered with SERVFAIL, it is the least preferred until this time
+    quarantined_until: Option<Instant>,
+    /// the last time a response was received, or a request failed
+    last_activity: Option<Instant>,
+    /// chosen at each failure, spreads out the reconnects of resolvers which failed together
+    retry_jitter_percent: u32,
 }
 
 impl Default for NameServerStats {
@@ -92,12 +126,37 @@ impl NameServerStats {
             state: NameServerState::Init { send_edns },
             successes,
             failures,
-            // TODO: incorporate latency
+            consecutive_failures: 0,
+            srtt_micros: None,
+            rttvar_micros: None,
+            quarantined_until: None,
+            last_activity: None,
+            retry_jitter_percent: 0,
         }
     }
 
-    fn next_success(&mut self, remote_edns: Option<Edns>) {
+    fn next_success(&mut self, remote_edns: Option<Edns>, rtt: Duration) {
         self.successes += 1;
+        self.consecutive_failures = 0;
+        self.last_activity = Some(Instant::now());
+
+        // the deviation is from the smoothed time before this measurement, RFC 6298 section 2
+        let rtt_micros = to_micros(rtt);
+        self.rttvar_micros = Some(match (self.srtt_micros, self.rttvar_micros) {
+            (Some(srtt), Some(rttvar)) => {
+                let deviation = if srtt > rtt_micros {
+                    srtt - rtt_micros
+                } else {
+                    rtt_micros - srtt
+                };
+                (rttvar * 3 + deviation) / 4
+            }
+            _ => rtt_micros / 2,
+        });
+        self.srtt_micros = Some(match self.srtt_micros {
+            Some(srtt) => (srtt * SRTT_DECAY + rtt_micros * (10 - SRTT_DECAY)) / 10,
+            None => rtt_micros,
+        });
 
         // update current state
 
@@ -124,10 +183,79 @@ impl NameServerStats {
 
     fn next_failure(&mut self, error: ClientError, when: Instant) {
         self.failures += 1;
+        self.consecutive_failures += 1;
+        self.last_activity = Some(when);
+        self.retry_jitter_percent = rand::random::<u32>() % (MAX_RETRY_JITTER_PERCENT + 1);
 
         // update current state
         mem::replace(&mut self.state, NameServerState::Failed { error, when });
     }
+
+    /// The NameServer is reachable, but could not answer, other NameServers are preferred for a
+    ///  while
+    fn next_servfail(&mut self, remote_edns: Option<Edns>, rtt: Duration, when: Instant) {
+        self.next_success(remote_edns, rtt);
+        self.quarantined_until = Some(when + Duration::from_secs(SERVFAIL_QUARANTINE_S));
+    }
+
+    fn is_quarantined(&self) -> bool {
+        self.quarantined_until.map_or(false, |until| Instant::now() < until)
+    }
+
+    /// True if nothing has been heard from the NameServer for at least the interval
+    fn is_idle(&self, interval: Duration) -> bool {
+        self.last_activity.map_or(true, |last| last.elapsed() >= interval)
+    }
+
+    /// Time to wait for a response, the smoothed round trip time plus four times its deviation,
+    ///  as the retransmission timeout of TCP, RFC 6298, doubled for each failure since the last
+    ///  success. Never above the maximum, which is also the timeout before the first response.
+    fn adaptive_timeout(&self, max_timeout: Duration) -> Duration {
+        let (srtt, rttvar) = match (self.srtt_micros, self.rttvar_micros) {
+            (Some(srtt), Some(rttvar)) => (srtt, rttvar),
+            _ => return max_timeout,
+        };
+
+        let timeout_micros = cmp::max(
+            srtt.saturating_add(rttvar.saturating_mul(4)),
+            MIN_ADAPTIVE_TIMEOUT_MS * 1_000,
+        );
+        let backoff = 1_u64 << cmp::min(self.consecutive_failures, 16);
+        cmp::min(
+            from_micros(timeout_micros.saturating_mul(backoff)),
+            max_timeout,
+        )
+    }
+
+    /// Takes the history of the NameServer from statistics saved earlier, e.g. before a restart
+    fn restore(&mut self, statistics: &NameServerStatistics) {
+        self.successes = statistics.successes();
+        self.failures = statistics.failures();
+        self.srtt_micros = statistics.srtt().map(to_micros);
+        self.rttvar_micros = statistics.rttvar().map(to_micros);
+    }
+
+    /// Delay after a failure before reconnecting, the backoff is based on the failures since the
+    ///  last success, plus the jitter chosen at the failure
+    fn retry_delay(&self) -> Duration {
+        let max_delay = Duration::from_secs(MAX_RETRY_DELAY_S);
+        let min_delay = Duration::from_millis(MIN_RETRY_DELAY_MS);
+        let failures = self.consecutive_failures;
+        let retry_delay = Duration::from_millis(failures.saturating_mul(10) as u64); // 10 ms backoff
+
+        // TODO: switch to min|max when they stabalize
+        let retry_delay = if retry_delay < max_delay {
+            if retry_delay > min_delay {
+                retry_delay
+            } else {
+                min_delay
+            }
+        } else {
+            max_delay
+        };
+
+        retry_delay * (100 + self.retry_jitter_percent) / 100
+    }
 }
 
 impl Ord for NameServerStats {
@@ -138,7 +266,24 @@ impl Ord for NameServerStats {
             return Ordering::Equal;
         }
 
-        // otherwise, run our evaluation to determine the next to be returned from the Heap
+        // otherwise, run our evaluation to determine the most preferred, quarantined servers
+        //  are only used when nothing else is available
+        match other.is_quarantined().cmp(&self.is_quarantined()) {
+            Ordering::Equal => (),
+            o @ _ => {
+                return o;
+            }
+        }
+
+        // invert failure comparisons, fewer failures are preferred, this is before the state so
+        //  that a reconnecting server does not jump ahead of healthy ones
+        match other.consecutive_failures.cmp(&self.consecutive_failures) {
+            Ordering::Equal => (),
+            o @ _ => {
+                return o;
+            }
+        }
+
         match self.state.cmp(&other.state) {
             Ordering::Equal => (),
             o @ _ => {
@@ -146,15 +291,25 @@ impl Ord for NameServerStats {
             }
         }
 
-        // TODO: track latency and use lowest latency connection...
+        // the fastest connection is preferred, an unmeasured connection is tried first
+        match (self.srtt_micros, other.srtt_micros) {
+            (Some(srtt), Some(other_srtt)) if srtt != other_srtt => {
+                return other_srtt.cmp(&srtt);
+            }
+            (None, Some(..)) => return Ordering::Greater,
+            (Some(..), None) => return Ordering::Less,
+            _ => (),
+        }
 
-        // invert failure comparison
-        if self.failures <= other.failures {
-            return Ordering::Greater;
+        match other.failures.cmp(&self.failures) {
+            Ordering::Equal => (),
+            o @ _ => {
+                return o;
+            }
         }
 
         // at this point we'll go with the lesser of successes to make sure there is ballance
-        self.successes.cmp(&other.successes)
+        other.successes.cmp(&self.successes)
     }
 }
 
@@ -187,19 +342,304 @@ impl ConnectionProvider for StandardConnection {
         options: &ResolverOpts,
         reactor: &Handle,
     ) -> Self::ConnHandle {
-        match config.protocol {
+        match connection_protocol(config) {
             Protocol::Udp => {
-                let (stream, handle) = UdpClientStream::new(config.socket_addr, reactor);
+                let retry_policy = options.retry_policy.unwrap_or_else(
+                    || RetryPolicy::new(options.timeout),
+                );
+                // each query has a socket of its own, which waits as long as the retry policy
+                let socket_timeout = retry_policy.max_timeout() *
+                    (100 + retry_policy.jitter_percent()) / 100;
+                let (stream, handle) =
+                    UdpClientStream::with_timeout(config.socket_addr, reactor, socket_timeout);
                 // TODO: need config for Signer...
-                ClientFuture::with_timeout(stream, handle, reactor, options.timeout, None)
+                ClientFuture::with_retry_policy(
+                    stream,
+                    handle,
+                    reactor,
+                    retry_policy,
+                    None,
+                    config.name_compression,
+                )
             }
             Protocol::Tcp => {
                 let (stream, handle) =
                     TcpClientStream::with_timeout(config.socket_addr, reactor, options.timeout);
                 // TODO: need config for Signer...
-                ClientFuture::with_timeout(stream, handle, reactor, options.timeout, None)
+                ClientFuture::with_name_compression(
+                    stream,
+                    handle,
+                    reactor,
+                    options.timeout,
+                    None,
+                    config.name_compression,
+                )
+            }
+            #[cfg(feature = "dns-over-tls")]
+            Protocol::Tls => {
+                let dns_name = config.tls_dns_name.clone().unwrap_or_else(|| {
+                    warn!(
+                        "no tls_dns_name for {}, certificate validation will likely fail",
+                        config.socket_addr
+                    );
+                    config.socket_addr.ip().to_string()
+                });
+                let (stream, handle) = ::tls::new_tls_stream(config.socket_addr, dns_name, reactor);
+                // TODO: need config for Signer...
+                ClientFuture::with_name_compression(
+                    stream,
+                    handle,
+                    reactor,
+                    options.timeout,
+                    None,
+                    config.name_compression,
+                )
+            }
+            #[cfg(feature = "dns-over-https")]
+            Protocol::Https => {
+                let dns_name = config.tls_dns_name.clone().unwrap_or_else(|| {
+                    warn!(
+                        "no tls_dns_name for {}, certificate validation will likely fail",
+                        config.socket_addr
+                    );
+                    config.socket_addr.ip().to_string()
+                });
+                let (stream, handle) = ::https::new_https_stream(
+                    config.socket_addr,
+                    dns_name,
+                    config.https_template.clone(),
+                    reactor,
+                );
+                // TODO: need config for Signer...
+                ClientFuture::with_name_compression(
+                    stream,
+                    handle,
+                    reactor,
+                    options.timeout,
+                    None,
+                    config.name_compression,
+                )
+            }
+            #[cfg(feature = "mdns")]
+            Protocol::Mdns => {
+                // responders answer one-shot queries by unicast, so these work along side any
+                //  system responder which already has the mDNS port
+                let (stream, handle) = if config.socket_addr.is_ipv4() {
+                    MdnsClientStream::new_ipv4(MdnsQueryType::OneShot, vec![], reactor)
+                } else {
+                    MdnsClientStream::new_ipv6(MdnsQueryType::OneShot, vec![], reactor)
+                };
+                ClientFuture::with_name_compression(
+                    stream,
+                    handle,
+                    reactor,
+                    options.timeout,
+                    None,
+                    config.name_compression,
+                )
+            }
+        }
+    }
+}
+
+/// The protocol of the connection to the NameServer, TCP for a `Protocol::Udp` NameServer with
+///  `TransportStrategy::TcpOnly`
+fn connection_protocol(config: &NameServerConfig) -> Protocol {
+    match (config.protocol, config.transport_strategy) {
+        (Protocol::Udp, TransportStrategy::TcpOnly) => Protocol::Tcp,
+        (protocol, _) => protocol,
+    }
+}
+
+/// The number of answer sources kept before the stale ones are dropped, see `AnswerSources`
+const MAX_ANSWER_SOURCES: usize = 1024;
+
+/// The NameServers which answered the responses on their way from the NameServerPool to the
+///  `CachingClient`, by the id of the response, see `LookupSource::name_server`
+///
+/// The responses which are never taken, e.g. those of the DNSSec validation, or which lost a
+///  race to another NameServer, are dropped after a minute once there are many of them.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AnswerSources(Arc<Mutex<HashMap<u16, (Instant, NameServerSource)>>>);
+
+impl AnswerSources {
+    /// Records the NameServer which answered the response
+    pub(crate) fn insert(&self, response: &Message, source: NameServerSource) {
+        if let Ok(mut sources) = self.0.lock() {
+            let now = Instant::now();
+            if sources.len() >= MAX_ANSWER_SOURCES {
+                sources.retain(|_, &mut (when, _)| {
+                    now.duration_since(when) < Duration::from_secs(60)
+                });
+            }
+
+            sources.insert(response.id(), (now, source));
+        }
+    }
+
+    /// Takes the NameServer which answered the response, None if it is not known
+    pub(crate) fn take(&self, response: &Message) -> Option<NameServerSource> {
+        self.0
+            .lock()
+            .ok()
+            .and_then(|mut sources| sources.remove(&response.id()))
+            .map(|(_, source)| source)
+    }
+}
+
+/// The DNS Cookies of a NameServer, RFC 7873 section 5.1
+///
+/// The Client Cookie is random, rather than derived from the addresses and a secret, it is kept
+///  for the lifetime of the NameServer, across reconnects.
+struct Cookies {
+    client: [u8; 8],
+    server: Option<Vec<u8>>,
+}
+
+impl Cookies {
+    fn new() -> Self {
+        let mut client = [0u8; 8];
+        for octet in client.iter_mut() {
+            *octet = rand::random();
+        }
+
+        Cookies {
+            client: client,
+            server: None,
+        }
+    }
+
+    /// The cookie option to send, with the last Server Cookie of the NameServer if there is one
+    fn option(&self) -> EdnsOption {
+        EdnsOption::Cookie(Cookie::new(self.client, self.server.clone()))
+    }
+
+    /// Checks the cookie of the response, and learns the Server Cookie, RFC 7873 section 5.3
+    ///
+    /// Returns an error if the response is to be discarded: the Client Cookie is not ours, or
+    ///  there is no cookie while the NameServer is known to support them.
+    fn check(&mut self, response: &Message) -> ClientResult<()> {
+        match response.edns().and_then(|edns| edns.cookie()) {
+            Some(cookie) => {
+                if *cookie.client() != self.client {
+                    return Err(
+                        ClientErrorKind::Msg("response with a wrong client cookie".to_string())
+                            .into(),
+                    );
+                }
+
+                if let Some(server) = cookie.server() {
+                    self.server = Some(server.to_vec());
+                }
+                Ok(())
             }
-            // TODO: Protocol::Tls => TlsClientStream::new(config.socket_addr, reactor),
+            None if self.server.is_some() => Err(
+                ClientErrorKind::Msg("response without a cookie".to_string())
+                    .into(),
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Locks the cookies, returning the option to send
+fn cookie_option(cookies: &Mutex<Cookies>) -> ClientResult<EdnsOption> {
+    cookies.lock().map(|cookies| cookies.option()).map_err(|e| {
+        format!("Error acquiring Cookies lock: {}", e).into()
+    })
+}
+
+/// Locks the cookies, and checks the cookie of the response, see `Cookies::check`
+fn check_cookie(cookies: &Mutex<Cookies>, response: &Message) -> ClientResult<()> {
+    match cookies.lock() {
+        Ok(mut cookies) => cookies.check(response),
+        Err(e) => Err(format!("Error acquiring Cookies lock: {}", e).into()),
+    }
+}
+
+/// The query name as it was, and in the random case it was sent in, see
+///  `ResolverOpts::case_randomization`
+#[derive(Clone)]
+struct QueryCase {
+    original: Name,
+    sent: Name,
+}
+
+/// Sends the query name in a random mix of upper and lower case, 0x20 encoding, so that a spoofed
+///  response also has to guess the case. None if there is not exactly one query
+fn randomize_query_case(message: &mut Message) -> Option<QueryCase> {
+    if message.queries().len() != 1 {
+        return None;
+    }
+
+    let mut queries = message.take_queries();
+    let original = queries[0].name().clone();
+    let labels = original
+        .iter()
+        .map(|label| {
+            label
+                .chars()
+                .map(|c| match c {
+                    'a'...'z' | 'A'...'Z' if rand::random() => ((c as u8) ^ 0x20) as char,
+                    c => c,
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+    let mut sent = Name::from_labels(labels);
+    sent.set_fqdn(original.is_fqdn());
+
+    queries[0].set_name(sent.clone());
+    message.add_queries(queries);
+    Some(QueryCase { original, sent })
+}
+
+/// Checks that the response has the query name in exactly the case it was sent in, and puts the
+///  query name back as it was, in the query and in the records of the name
+fn check_query_case(mut response: Message, query_case: &QueryCase) -> ClientResult<Message> {
+    let echoed = response.queries().len() == 1 &&
+        response.queries()[0].name().cmp_with_case(&query_case.sent, false) == Ordering::Equal;
+    if !echoed {
+        return Err(
+            ClientErrorKind::Msg(format!(
+                "the case of the query name in the response does not match {}, discarding it",
+                query_case.sent
+            )).into(),
+        );
+    }
+
+    let original = &query_case.original;
+    let mut queries = response.take_queries();
+    for query in queries.iter_mut() {
+        query.set_name(original.clone());
+    }
+    response.add_queries(queries);
+
+    let restore = |mut records: Vec<Record>| {
+        for record in records.iter_mut().filter(|record| record.name() == original) {
+            record.set_name(original.clone());
+        }
+        records
+    };
+    let answers = restore(response.take_answers());
+    response.insert_answers(answers);
+    let name_servers = restore(response.take_name_servers());
+    response.insert_name_servers(name_servers);
+    let additionals = restore(response.take_additionals());
+    response.insert_additionals(additionals);
+
+    Ok(response)
+}
+
+/// Pads the query to a multiple of the block size, if there is one, see `Message::pad`
+fn pad_query(
+    message: &mut Message,
+    padding_block_size: Option<u16>,
+    name_compression: NameCompression,
+) {
+    if let Some(block_size) = padding_block_size {
+        if let Err(e) = message.pad(block_size, name_compression) {
+            warn!("could not pad query, sending it unpadded: {}", e);
         }
     }
 }
@@ -210,8 +650,13 @@ pub struct NameServer<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> {
     config: NameServerConfig,
     options: ResolverOpts,
     client: C,
+    // the connection over TCP for truncated responses, see TransportStrategy::UdpThenTcp
+    stream_client: Arc<Mutex<Option<C>>>,
     // TODO: switch to FuturesMutex? (Mutex will have some undesireable locking)
     stats: Arc<Mutex<NameServerStats>>,
+    cookies: Arc<Mutex<Cookies>>,
+    answer_sources: AnswerSources,
+    observers: LookupObservers,
     reactor: Handle,
     phantom: PhantomData<P>,
 }
@@ -229,7 +674,11 @@ impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> NameServer<C, P> {
             config,
             options,
             client,
+            stream_client: Arc::new(Mutex::new(None)),
             stats: Arc::new(Mutex::new(NameServerStats::default())),
+            cookies: Arc::new(Mutex::new(Cookies::new())),
+            answer_sources: AnswerSources::default(),
+            observers: LookupObservers::default(),
             reactor: reactor.clone(),
             phantom: PhantomData,
         }
@@ -246,7 +695,11 @@ impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> NameServer<C, P> {
             config,
             options,
             client,
+            stream_client: Arc::new(Mutex::new(None)),
             stats: Arc::new(Mutex::new(NameServerStats::default())),
+            cookies: Arc::new(Mutex::new(Cookies::new())),
+            answer_sources: AnswerSources::default(),
+            observers: LookupObservers::default(),
             reactor: reactor.clone(),
             phantom: PhantomData,
         }
@@ -257,14 +710,14 @@ impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> NameServer<C, P> {
     ///  then reconnect.
     #[allow(unused_must_use)] // TODO: remove must use from BasicClientHandle
     fn try_reconnect(&mut self) -> ClientResult<()> {
-        let error_opt: Option<(ClientError, Instant, usize, usize)> = self.stats
+        let error_opt: Option<(ClientError, Instant, NameServerStats)> = self.stats
             .lock()
             .map(|stats| if let NameServerState::Failed {
                 ref error,
                 when,
             } = stats.state
             {
-                Some((error.clone(), when, stats.successes, stats.failures))
+                Some((error.clone(), when, stats.clone()))
             } else {
                 None
             })
@@ -275,35 +728,17 @@ impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> NameServer<C, P> {
             })?;
 
         // if this is in a failure state
-        if let Some((error, when, successes, failures)) = error_opt {
-            // Backoff is based on successes vs. failures...
-            let max_delay = Duration::from_secs(MAX_RETRY_DELAY_S);
-            let min_delay = Duration::from_millis(MIN_RETRY_DELAY_MS);
-            let failures = failures.saturating_sub(successes);
-            let retry_delay = Duration::from_millis(failures.saturating_mul(10) as u64); // 10 ms backoff
-
-            // TODO: switch to min|max when they stabalize
-            let retry_delay = if retry_delay < max_delay {
-                if retry_delay > min_delay {
-                    retry_delay
-                } else {
-                    min_delay
-                }
-            } else {
-                max_delay
-            };
-
-            if Instant::now().duration_since(when) > retry_delay {
+        if let Some((error, when, stats)) = error_opt {
+            if Instant::now().duration_since(when) > stats.retry_delay() {
                 debug!("reconnecting: {:?}", self.config);
                 // establish a new connection
                 let client = P::new_connection(&self.config, &self.options, &self.reactor);
                 mem::replace(&mut self.client, client);
 
-                // reinitialize the mutex (in case it was poisoned before)
-                mem::replace(
-                    &mut self.stats,
-                    Arc::new(Mutex::new(NameServerStats::init(None, successes, failures))),
-                );
+                // reinitialize the mutex (in case it was poisoned before), keeping the history
+                let mut stats = stats;
+                stats.state = NameServerState::Init { send_edns: None };
+                mem::replace(&mut self.stats, Arc::new(Mutex::new(stats)));
                 Ok(())
             } else {
                 Err(error)
@@ -312,50 +747,309 @@ impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> NameServer<C, P> {
             Ok(())
         }
     }
+
+    /// True if nothing has been heard from the NameServer for at least the interval
+    fn is_idle(&self, interval: Duration) -> bool {
+        self.stats
+            .lock()
+            .map(|stats| stats.is_idle(interval))
+            .unwrap_or(true)
+    }
+
+    /// A snapshot of the statistics of the NameServer, None if its stats are poisoned
+    fn statistics(&self) -> Option<NameServerStatistics> {
+        let stats = match self.stats.lock() {
+            Ok(stats) => stats,
+            Err(_) => return None,
+        };
+
+        Some(NameServerStatistics::new(
+            self.config.socket_addr,
+            self.config.protocol,
+            stats.successes,
+            stats.failures,
+            stats.consecutive_failures,
+            stats.srtt_micros.map(from_micros),
+            stats.rttvar_micros.map(from_micros),
+            self.timeout_with(&stats),
+        ))
+    }
+
+    /// Takes the history of the NameServer from the statistics with its address and protocol,
+    ///  returns true if there were any
+    fn restore_statistics(&self, statistics: &[NameServerStatistics]) -> bool {
+        let restored = statistics.iter().find(|statistics| {
+            statistics.socket_addr() == self.config.socket_addr &&
+                statistics.protocol() == self.config.protocol
+        });
+
+        match (restored, self.stats.lock()) {
+            (Some(restored), Ok(mut stats)) => {
+                stats.restore(restored);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The time a request waits for a response, see `ResolverOpts::adaptive_timeout`
+    fn timeout_with(&self, stats: &NameServerStats) -> Duration {
+        if self.options.adaptive_timeout {
+            stats.adaptive_timeout(self.options.timeout)
+        } else {
+            self.options.timeout
+        }
+    }
+
+    /// The retry over TCP of the message, if it is sent over UDP with
+    ///  `TransportStrategy::UdpThenTcp`
+    fn tcp_fallback(&self, message: &Message) -> Option<TcpFallback<C, P>> {
+        if connection_protocol(&self.config) != Protocol::Udp ||
+            self.config.transport_strategy != TransportStrategy::UdpThenTcp
+        {
+            return None;
+        }
+
+        Some(TcpFallback {
+            config: self.config.clone(),
+            options: self.options.clone(),
+            stream_client: self.stream_client.clone(),
+            reactor: self.reactor.clone(),
+            message: message.clone(),
+            phantom: PhantomData,
+        })
+    }
+}
+
+/// Retries a truncated response over TCP to the same NameServer, RFC 1035 section 4.2.1
+///
+/// The connection is only made for the first truncated response, and is then kept.
+struct TcpFallback<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> {
+    config: NameServerConfig,
+    options: ResolverOpts,
+    stream_client: Arc<Mutex<Option<C>>>,
+    reactor: Handle,
+    message: Message,
+    phantom: PhantomData<P>,
+}
+
+impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> TcpFallback<C, P> {
+    fn send(self) -> Box<Future<Item = Message, Error = ClientError>> {
+        let mut client = match self.stream_client.lock() {
+            Ok(mut stream_client) => {
+                let (config, options, reactor) = (&self.config, &self.options, &self.reactor);
+                stream_client
+                    .get_or_insert_with(|| {
+                        let mut config = config.clone();
+                        config.protocol = Protocol::Tcp;
+                        P::new_connection(&config, options, reactor)
+                    })
+                    .clone()
+            }
+            Err(e) => {
+                return Box::new(future::err(
+                    format!("Error acquiring stream client lock: {}", e).into(),
+                ))
+            }
+        };
+
+        // a failed connection is made again for the next truncated response
+        let stream_client = self.stream_client;
+        Box::new(client.send(self.message).or_else(move |error| {
+            if let Ok(mut stream_client) = stream_client.lock() {
+                stream_client.take();
+            }
+            future::err(error)
+        }))
+    }
 }
 
 impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> DnsHandle for NameServer<C, P> {
     type Error = ClientError;
 
-    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = Self::Error>> {
+    fn send(&mut self, mut message: Message) -> Box<Future<Item = Message, Error = Self::Error>> {
         // if state is failed, return future::err(), unless retry delay expired...
         if let Err(error) = self.try_reconnect() {
             return Box::new(future::err(error));
         }
 
+        if let Some(client_subnet) = self.options.client_subnet {
+            message.edns_mut().set_option(client_subnet.to_option());
+        }
+
+        if !self.config.edns_options.is_empty() {
+            let edns = message.edns_mut();
+            for option in &self.config.edns_options {
+                edns.set_option(option.clone());
+            }
+        }
+
+        // before the message is kept for a BADCOOKIE retry, so that it is sent in the same case,
+        //  only over UDP, mDNS responders answer in the case of their records
+        let query_case = if self.options.case_randomization &&
+            connection_protocol(&self.config) == Protocol::Udp
+        {
+            randomize_query_case(&mut message)
+        } else {
+            None
+        };
+
+        // mDNS responders do not know of cookies
+        let cookies = if self.options.cookies && !self.config.protocol.is_mdns() {
+            match cookie_option(&self.cookies) {
+                Ok(option) => message.edns_mut().set_option(option),
+                Err(error) => return Box::new(future::err(error)),
+            }
+            Some((self.cookies.clone(), message.clone()))
+        } else {
+            None
+        };
+
+        let (padding_block_size, name_compression) =
+            (self.config.padding_block_size, self.config.name_compression);
+        pad_query(&mut message, padding_block_size, name_compression);
+
         // Becuase a Poisoned lock error could have occured, make sure to create a new Mutex...
 
         // grab a reference to the stats for this NameServer
         let mutex1 = self.stats.clone();
         let mutex2 = self.stats.clone();
-        Box::new(self.client.send(message).and_then(move |response| {
-            // TODO: consider making message::take_edns...
-            let remote_edns = response.edns().cloned();
+        let answer_sources = self.answer_sources.clone();
+        let (socket_addr, protocol) = (self.config.socket_addr, connection_protocol(&self.config));
+        let observers = self.observers.clone();
+        let observed = observers.attempt(&message, socket_addr, protocol);
+        let sent = Instant::now();
+        let request: Box<Future<Item = (Message, Protocol), Error = Self::Error>> =
+            match self.tcp_fallback(&message) {
+                Some(tcp_fallback) => Box::new(self.client.send(message).and_then(
+                    move |response| if response.truncated() {
+                        debug!("truncated response from {}, retrying over TCP", socket_addr);
+                        future::Either::A(tcp_fallback.send().map(
+                            |response| (response, Protocol::Tcp),
+                        ))
+                    } else {
+                        future::Either::B(future::ok((response, protocol)))
+                    },
+                )),
+                None => Box::new(self.client.send(message).map(
+                    move |response| (response, protocol),
+                )),
+            };
 
-            // this transitions the state to success
-            let response = 
-                mutex1
+        // the request is given up on before the timeout of the connection once the round trip
+        //  times to the NameServer are known
+        let request: Box<Future<Item = (Message, Protocol), Error = Self::Error>> =
+            if self.options.adaptive_timeout {
+                let timeout = match self.stats.lock() {
+                    Ok(stats) => stats.adaptive_timeout(self.options.timeout),
+                    Err(_) => self.options.timeout,
+                };
+                match Timeout::new(timeout, &self.reactor) {
+                    Ok(timeout) => {
+                        let timeout = timeout.then(|_| {
+                            future::err::<(Message, Protocol), _>(ClientErrorKind::Timeout.into())
+                        });
+                        Box::new(request.select(timeout).map(|(response, _)| response).map_err(
+                            |(error, _)| error,
+                        ))
+                    }
+                    Err(e) => {
+                        warn!("error starting adaptive timeout, waiting for the connection: {}", e);
+                        request
+                    }
+                }
+            } else {
+                request
+            };
+        let response: Box<Future<Item = Message, Error = Self::Error>> =
+            Box::new(request.and_then(move |(response, protocol)| {
+                // TODO: consider making message::take_edns...
+                let remote_edns = response.edns().cloned();
+                let rtt = sent.elapsed();
+                answer_sources.insert(&response, NameServerSource::new(socket_addr, protocol, rtt));
+
+                // this transitions the state to success, SERVFAIL also quarantines the NameServer
+                let response =
+                    mutex1
+                        .lock()
+                        .and_then(|mut stats| {
+                            if response.response_code() == ResponseCode::ServFail {
+                                stats.next_servfail(remote_edns, rtt, Instant::now());
+                            } else {
+                                stats.next_success(remote_edns, rtt);
+                            }
+                            Ok(response)
+                        })
+                        .map_err(|e| format!("Error acquiring NameServerStats lock: {}", e).into());
+
+                future::result(response)
+            }).or_else(move |error| {
+                // this transitions the state to failure
+                mutex2
                     .lock()
-                    .and_then(|mut stats| { stats.next_success(remote_edns); Ok(response) })
-                    .map_err(|e| format!("Error acquiring NameServerStats lock: {}", e).into());
-
-            future::result(response)
-        }).or_else(move |error| {
-            // this transitions the state to failure
-            mutex2
-                .lock()
-                .and_then(|mut stats| {
-                    stats.next_failure(error.clone(), Instant::now());
-                    Ok(())
-                })
-                .or_else(|e| {
-                    warn!("Error acquiring NameServerStats lock (already in error state, ignoring): {}", e);
-                    Err(()) 
-                })
-                .is_ok(); // ignoring error, as this connection is already marked in error...
+                    .and_then(|mut stats| {
+                        stats.next_failure(error.clone(), Instant::now());
+                        Ok(())
+                    })
+                    .or_else(|e| {
+                        warn!("Error acquiring NameServerStats lock (already in error state, ignoring): {}", e);
+                        Err(()) 
+                    })
+                    .is_ok(); // ignoring error, as this connection is already marked in error...
+
+                if let Some((id, query)) = observed {
+                    observers.attempt_failed(id, &query, socket_addr, &error);
+                }
 
-            // These are connection failures, not lookup failures, that is handled in the resolver layer
-            future::err(error)
+                // These are connection failures, not lookup failures, that is handled in the resolver layer
+                future::err(error)
+            }));
+
+        // the case is checked after the stats, a spoofed response must not fail the NameServer
+        let response: Box<Future<Item = Message, Error = Self::Error>> = match query_case.clone() {
+            Some(query_case) => {
+                Box::new(response.and_then(
+                    move |response| check_query_case(response, &query_case),
+                ))
+            }
+            None => response,
+        };
+
+        let (cookies, mut retry) = match cookies {
+            Some(cookies) => cookies,
+            None => return response,
+        };
+
+        // the cookies are checked after the stats, a spoofed response must not fail the NameServer
+        let mut client = self.client.clone();
+        let answer_sources = self.answer_sources.clone();
+        Box::new(response.and_then(move |response| {
+            if let Err(error) = check_cookie(&cookies, &response) {
+                return future::Either::A(future::err(error));
+            }
+
+            if response.response_code() != ResponseCode::BADCOOKIE {
+                return future::Either::A(future::ok(response));
+            }
+
+            // the response carried a fresh Server Cookie, retry once with it, RFC 7873 section 5.3
+            debug!("BADCOOKIE, retrying with the new server cookie");
+            match cookie_option(&cookies) {
+                Ok(option) => retry.edns_mut().set_option(option),
+                Err(error) => return future::Either::A(future::err(error)),
+            }
+            pad_query(&mut retry, padding_block_size, name_compression);
+
+            let sent = Instant::now();
+            future::Either::B(client.send(retry).and_then(move |response| {
+                let source = NameServerSource::new(socket_addr, protocol, sent.elapsed());
+                answer_sources.insert(&response, source);
+                try!(check_cookie(&cookies, &response));
+                match query_case {
+                    Some(ref query_case) => check_query_case(response, query_case),
+                    None => Ok(response),
+                }
+            }))
         }))
     }
 }
@@ -368,7 +1062,7 @@ impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> ClientHandle for Na
 }
 
 impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> Ord for NameServer<C, P> {
-    /// Custom implementation of Ord for NameServer which incorporates the performance of the connection into it's ranking
+    /// Custom implementation of Ord for NameServer which incorporates the performance of the connection into it's ranking, the greatest is the most preferred
     fn cmp(&self, other: &Self) -> Ordering {
         // if they are literally equal, just return
         if self == other {
@@ -400,14 +1094,29 @@ impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> PartialEq for NameS
 
 impl<C: ClientHandle, P: ConnectionProvider<ConnHandle = C>> Eq for NameServer<C, P> {}
 
+/// The NameServers used for the names within a domain, see
+///  `ResolverConfig::add_domain_name_server`
+struct DomainConns<C: ClientHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> {
+    domain: Name,
+    datagram_conns: Arc<Mutex<Vec<NameServer<C, P>>>>,
+    stream_conns: Arc<Mutex<Vec<NameServer<C, P>>>>,
+}
+
 /// A pool of NameServers
 ///
 /// This is not expected to be used directly, see `ResolverFuture`.
 #[derive(Clone)]
 pub struct NameServerPool<C: ClientHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> {
     // TODO: switch to FuturesMutex (Mutex will have some undesireable locking)
-    datagram_conns: Arc<Mutex<BinaryHeap<NameServer<C, P>>>>, /* All NameServers must be the same type */
-    stream_conns: Arc<Mutex<BinaryHeap<NameServer<C, P>>>>, /* All NameServers must be the same type */
+    datagram_conns: Arc<Mutex<Vec<NameServer<C, P>>>>, /* All NameServers must be the same type */
+    stream_conns: Arc<Mutex<Vec<NameServer<C, P>>>>, /* All NameServers must be the same type */
+    #[cfg(feature = "mdns")]
+    mdns_conns: Arc<Mutex<Vec<NameServer<C, P>>>>, /* All NameServers must be the same type */
+    domain_conns: Arc<Vec<DomainConns<C, P>>>,
+    // domains which may resolve to internal addresses, see ResolverOpts::rebind_protection
+    rebind_allowed: Arc<Vec<Name>>,
+    answer_sources: AnswerSources,
+    observers: LookupObservers,
     options: ResolverOpts,
     phantom: PhantomData<P>,
 }
@@ -419,37 +1128,98 @@ impl<C: ClientHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static>
         options: &ResolverOpts,
         reactor: &Handle,
     ) -> NameServerPool<BasicClientHandle, StandardConnection> {
-        let datagram_conns: BinaryHeap<NameServer<BasicClientHandle, StandardConnection>> =
+        let answer_sources = AnswerSources::default();
+        let observers = LookupObservers::default();
+        let new_name_server = |ns_config: &NameServerConfig| {
+            let mut name_server = NameServer::<_, StandardConnection>::new(
+                ns_config.clone(),
+                options.clone(),
+                reactor,
+            );
+            name_server.answer_sources = answer_sources.clone();
+            name_server.observers = observers.clone();
+            name_server
+        };
+
+        let datagram_conns: Vec<NameServer<BasicClientHandle, StandardConnection>> =
             config
                 .name_servers()
                 .iter()
-                .filter(|ns_config| ns_config.protocol.is_datagram())
-                .map(|ns_config| {
-                    NameServer::<_, StandardConnection>::new(
-                        ns_config.clone(),
-                        options.clone(),
-                        reactor,
-                    )
+                .filter(|ns_config| {
+                    ns_config.protocol.is_datagram() && !ns_config.protocol.is_mdns()
                 })
+                .map(|ns_config| new_name_server(ns_config))
                 .collect();
 
-        let stream_conns: BinaryHeap<NameServer<BasicClientHandle, StandardConnection>> =
+        let stream_conns: Vec<NameServer<BasicClientHandle, StandardConnection>> =
             config
                 .name_servers()
                 .iter()
                 .filter(|ns_config| ns_config.protocol.is_stream())
-                .map(|ns_config| {
-                    NameServer::<_, StandardConnection>::new(
-                        ns_config.clone(),
-                        options.clone(),
-                        reactor,
-                    )
-                })
+                .map(|ns_config| new_name_server(ns_config))
+                .collect();
+
+        #[cfg(feature = "mdns")]
+        let mdns_conns: Vec<NameServer<BasicClientHandle, StandardConnection>> =
+            config
+                .name_servers()
+                .iter()
+                .filter(|ns_config| ns_config.protocol.is_mdns())
+                .map(|ns_config| new_name_server(ns_config))
                 .collect();
 
+        let encrypted = stream_conns.iter().any(
+            |conn| conn.config.protocol.is_encrypted(),
+        );
+        let stream_conns = Arc::new(Mutex::new(stream_conns));
+        if let Some(interval) = options.keepalive_interval {
+            if encrypted {
+                spawn_keepalive(Arc::downgrade(&stream_conns), interval, reactor);
+            }
+        }
+
+        // grouped by domain, in the order of the config
+        let mut domain_conns = Vec::<DomainConns<_, _>>::new();
+        for &(ref domain, ref ns_config) in config.domain_name_servers() {
+            if ns_config.protocol.is_mdns() {
+                continue;
+            }
+
+            if !domain_conns.iter().any(|conns| conns.domain == *domain) {
+                domain_conns.push(DomainConns {
+                    domain: domain.clone(),
+                    datagram_conns: Arc::new(Mutex::new(vec![])),
+                    stream_conns: Arc::new(Mutex::new(vec![])),
+                });
+            }
+            let conns = domain_conns
+                .iter()
+                .find(|conns| conns.domain == *domain)
+                .expect("domain was just added");
+
+            let name_server = new_name_server(ns_config);
+            let conns = if ns_config.protocol.is_datagram() {
+                &conns.datagram_conns
+            } else {
+                &conns.stream_conns
+            };
+            conns.lock().expect("new conns are not poisoned").push(name_server);
+        }
+
+        let mut rebind_allowed = config.rebind_allowed_domains().to_vec();
+        rebind_allowed.extend(domain_conns.iter().map(|conns| conns.domain.clone()));
+        rebind_allowed.push(Name::from_labels(vec!["local"]));
+        rebind_allowed.push(Name::from_labels(vec!["localhost"]));
+
         NameServerPool {
             datagram_conns: Arc::new(Mutex::new(datagram_conns)),
-            stream_conns: Arc::new(Mutex::new(stream_conns)),
+            stream_conns,
+            #[cfg(feature = "mdns")]
+            mdns_conns: Arc::new(Mutex::new(mdns_conns)),
+            domain_conns: Arc::new(domain_conns),
+            rebind_allowed: Arc::new(rebind_allowed),
+            answer_sources,
+            observers,
             options: options.clone(),
             phantom: PhantomData,
         }
@@ -461,52 +1231,320 @@ impl<C: ClientHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static>
         datagram_conns: Vec<NameServer<C, P>>,
         stream_conns: Vec<NameServer<C, P>>,
     ) -> Self {
+        let answer_sources = AnswerSources::default();
+        let observers = LookupObservers::default();
+        let mut datagram_conns = datagram_conns;
+        let mut stream_conns = stream_conns;
+        for name_server in datagram_conns.iter_mut().chain(stream_conns.iter_mut()) {
+            name_server.answer_sources = answer_sources.clone();
+            name_server.observers = observers.clone();
+        }
+
         NameServerPool {
-            datagram_conns: Arc::new(Mutex::new(datagram_conns.into_iter().collect())),
-            stream_conns: Arc::new(Mutex::new(stream_conns.into_iter().collect())),
+            datagram_conns: Arc::new(Mutex::new(datagram_conns)),
+            stream_conns: Arc::new(Mutex::new(stream_conns)),
+            #[cfg(feature = "mdns")]
+            mdns_conns: Arc::new(Mutex::new(Vec::new())),
+            domain_conns: Arc::new(Vec::new()),
+            rebind_allowed: Arc::new(Vec::new()),
+            answer_sources,
+            observers,
             options: options.clone(),
             phantom: PhantomData,
         }
     }
 
-    fn try_send(
-        conns: Arc<Mutex<BinaryHeap<NameServer<C, P>>>>,
-        message: Message,
-    ) -> TrySend<C, P> {
-        TrySend::Lock {
-            conns,
-            message: Some(message),
-        }
+    /// The NameServers which answered the responses, see `CachingClient::set_answer_sources`
+    pub(crate) fn answer_sources(&self) -> AnswerSources {
+        self.answer_sources.clone()
     }
-}
 
-impl<C, P> DnsHandle for NameServerPool<C, P>
-where
-    C: ClientHandle + 'static,
-    P: ConnectionProvider<ConnHandle = C> + 'static,
-{
-    type Error = ClientError;
+    /// The observer of the lookups, see `ResolverFuture::set_lookup_observer`
+    pub(crate) fn observers(&self) -> LookupObservers {
+        self.observers.clone()
+    }
 
-    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = Self::Error>> {
-        let datagram_conns = self.datagram_conns.clone();
-        let stream_conns1 = self.stream_conns.clone();
-        let stream_conns2 = self.stream_conns.clone();
-        // TODO: remove this clone, return the Message in the error?
-        let tcp_message1 = message.clone();
-        let tcp_message2 = message.clone();
+    /// Returns a snapshot of the statistics of all the NameServers
+    pub fn statistics(&self) -> Vec<NameServerStatistics> {
+        let mut statistics = Vec::new();
+        for conns in self.all_conns() {
+            if let Ok(conns) = conns.lock() {
+                statistics.extend(conns.iter().filter_map(|conn| conn.statistics()));
+            }
+        }
 
-        Box::new(
-            Self::try_send(datagram_conns, message)
-                .and_then(move |response| {
-                    // handling promotion from datagram to stream base on truncation in message
+        statistics
+    }
+
+    /// Takes the history of the NameServers from statistics saved earlier, e.g. with
+    ///  `name_server_stats::write_statistics` before a restart, so that the fastest NameServers
+    ///  are preferred, and the adaptive timeouts apply, from the first request. Returns the
+    ///  number of NameServers which were in the statistics
+    pub fn restore_statistics(&self, statistics: &[NameServerStatistics]) -> usize {
+        let mut restored = 0;
+        for conns in self.all_conns() {
+            if let Ok(conns) = conns.lock() {
+                restored += conns
+                    .iter()
+                    .filter(|conn| conn.restore_statistics(statistics))
+                    .count();
+            }
+        }
+
+        restored
+    }
+
+    /// All the lists of NameServers, including those for domains
+    fn all_conns(&self) -> Vec<Arc<Mutex<Vec<NameServer<C, P>>>>> {
+        let mut all_conns = vec![self.datagram_conns.clone(), self.stream_conns.clone()];
+        #[cfg(feature = "mdns")]
+        {
+            all_conns.push(self.mdns_conns.clone());
+        }
+        for conns in self.domain_conns.iter() {
+            all_conns.push(conns.datagram_conns.clone());
+            all_conns.push(conns.stream_conns.clone());
+        }
+
+        all_conns
+    }
+
+    /// Returns the mDNS connections if the message is for a name in the `local.` domain, and there
+    ///  are any mDNS NameServers configured
+    #[cfg(feature = "mdns")]
+    fn mdns_conns_for(
+        &self,
+        message: &Message,
+    ) -> Option<Arc<Mutex<Vec<NameServer<C, P>>>>> {
+        let local = Name::from_labels(vec!["local"]);
+        if !message.queries().iter().any(|q| local.zone_of(q.name())) {
+            return None;
+        }
+
+        match self.mdns_conns.lock() {
+            Ok(ref conns) if !conns.is_empty() => Some(self.mdns_conns.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns the datagram and stream connections of the most specific domain which the names of
+    ///  the message are within, if any
+    fn domain_conns_for(
+        &self,
+        message: &Message,
+    ) -> Option<(Arc<Mutex<Vec<NameServer<C, P>>>>, Arc<Mutex<Vec<NameServer<C, P>>>>)> {
+        self.domain_conns
+            .iter()
+            .filter(|conns| {
+                !message.queries().is_empty() &&
+                    message.queries().iter().all(
+                        |q| conns.domain.zone_of(q.name()),
+                    )
+            })
+            .max_by_key(|conns| conns.domain.num_labels())
+            .map(|conns| {
+                (conns.datagram_conns.clone(), conns.stream_conns.clone())
+            })
+    }
+
+    /// Sends the message to the datagram NameServers, and then to the stream NameServers if the
+    ///  response is truncated or all the datagram NameServers failed
+    ///
+    /// # Arguments
+    ///
+    /// * `datagram_conns` - the datagram NameServers
+    /// * `stream_conns` - the stream NameServers
+    /// * `message` - the message to send
+    /// * `excluded` - NameServers to which the message is not sent
+    fn datagram_then_stream(
+        datagram_conns: Arc<Mutex<Vec<NameServer<C, P>>>>,
+        stream_conns: Arc<Mutex<Vec<NameServer<C, P>>>>,
+        message: Message,
+        excluded: Vec<NameServerConfig>,
+    ) -> Box<Future<Item = Message, Error = ClientError>> {
+        let stream_conns1 = stream_conns.clone();
+        let stream_conns2 = stream_conns;
+        // TODO: remove this clone, return the Message in the error?
+        let tcp_message1 = message.clone();
+        let tcp_message2 = message.clone();
+        let excluded1 = excluded.clone();
+        let excluded2 = excluded.clone();
+
+        Box::new(
+            Self::try_send(datagram_conns, message, excluded)
+                .and_then(move |response| {
+                    // handling promotion from datagram to stream base on truncation in message
                     if ResponseCode::NoError == response.response_code() && response.truncated() {
-                        future::Either::A(Self::try_send(stream_conns1, tcp_message1))
+                        future::Either::A(Self::try_send(stream_conns1, tcp_message1, excluded1))
                     } else {
                         future::Either::B(future::ok(response))
                     }
 
                 })
-                .or_else(move |_| Self::try_send(stream_conns2, tcp_message2)),
+                .or_else(move |_| Self::try_send(stream_conns2, tcp_message2, excluded2)),
+        )
+    }
+
+    /// Sends the message to the NameServers of the preferred protocol, and after the head start
+    ///  also to the others, see `TransportRace`
+    ///
+    /// Returns the message if there are no NameServers of the preferred protocol, or no others,
+    ///  in which case there is nothing to race.
+    fn race_send(&self, race: TransportRace, message: Message) -> Result<RaceSend, Message> {
+        let conns = if race.preferred.is_datagram() {
+            self.datagram_conns.clone()
+        } else {
+            self.stream_conns.clone()
+        };
+
+        let (preferred, others, reactor) = match conns.lock() {
+            Ok(conns) => {
+                let (preferred, others): (Vec<_>, Vec<_>) = conns.iter().partition(|conn| {
+                    conn.config.protocol == race.preferred
+                });
+
+                (
+                    preferred.iter().map(|conn| conn.config.clone()).collect::<Vec<_>>(),
+                    others.iter().map(|conn| conn.config.clone()).collect::<Vec<_>>(),
+                    preferred.first().map(|conn| conn.reactor.clone()),
+                )
+            }
+            Err(_) => return Err(message),
+        };
+
+        let reactor = match reactor {
+            Some(reactor) => reactor,
+            None => return Err(message),
+        };
+
+        // the NameServers in the other list are never of the preferred protocol
+        let other_conns = if race.preferred.is_datagram() {
+            &self.stream_conns
+        } else {
+            &self.datagram_conns
+        };
+        let has_others = !others.is_empty() ||
+            other_conns.lock().map(|conns| !conns.is_empty()).unwrap_or(false);
+        if !has_others {
+            return Err(message);
+        }
+
+        let head_start = match Timeout::new(race.head_start, &reactor) {
+            Ok(head_start) => Some(head_start),
+            Err(e) => {
+                warn!("error starting head start, racing all transports: {}", e);
+                None
+            }
+        };
+
+        Ok(RaceSend {
+            preferred: Some(Box::new(Self::try_send(conns, message.clone(), others))),
+            head_start,
+            others: Some(Self::datagram_then_stream(
+                self.datagram_conns.clone(),
+                self.stream_conns.clone(),
+                message,
+                preferred,
+            )),
+            error: None,
+        })
+    }
+
+    fn try_send(
+        conns: Arc<Mutex<Vec<NameServer<C, P>>>>,
+        message: Message,
+        excluded: Vec<NameServerConfig>,
+    ) -> TrySend<C, P> {
+        TrySend {
+            conns,
+            message,
+            tried: excluded,
+            future: None,
+            servfail: None,
+            error: None,
+        }
+    }
+}
+
+impl<C, P> DnsHandle for NameServerPool<C, P>
+where
+    C: ClientHandle + 'static,
+    P: ConnectionProvider<ConnHandle = C> + 'static,
+{
+    type Error = ClientError;
+
+    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = Self::Error>> {
+        let denied_query_types = self.options.denied_query_types;
+        let denied = message
+            .queries()
+            .iter()
+            .map(|query| query.query_type())
+            .find(|query_type| denied_query_types.contains(query_type));
+        if let Some(query_type) = denied {
+            return Box::new(future::err(
+                ClientErrorKind::Msg(format!(
+                    "query type is denied: {:?}, see ResolverOpts::denied_query_types",
+                    query_type
+                )).into(),
+            ));
+        }
+
+        let illegal_data = self.options.illegal_data;
+        let rebind_allowed = if self.options.rebind_protection {
+            Some(self.rebind_allowed.clone())
+        } else {
+            None
+        };
+        let stripped_record_types = self.options.stripped_record_types;
+
+        Box::new(self.send_to_conns(message).and_then(move |response| {
+            let response = strip_record_types(response, stripped_record_types);
+            let response = try!(check_illegal_data(response, illegal_data));
+
+            Ok(match rebind_allowed {
+                Some(ref allowed) => strip_internal_addresses(response, allowed),
+                None => response,
+            })
+        }))
+    }
+}
+
+impl<C, P> NameServerPool<C, P>
+where
+    C: ClientHandle + 'static,
+    P: ConnectionProvider<ConnHandle = C> + 'static,
+{
+    /// Sends the message to the NameServers which are to answer it
+    fn send_to_conns(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+        // names in the local. domain are only ever resolved on the link, RFC 6762 section 3
+        #[cfg(feature = "mdns")]
+        {
+            if let Some(mdns_conns) = self.mdns_conns_for(&message) {
+                return Box::new(Self::try_send(mdns_conns, message, vec![]));
+            }
+        }
+
+        // names within a domain with its own NameServers are only resolved by those
+        if let Some((datagram_conns, stream_conns)) = self.domain_conns_for(&message) {
+            return Self::datagram_then_stream(datagram_conns, stream_conns, message, vec![]);
+        }
+
+        let transport_race = self.options.transport_race;
+        let message = match transport_race {
+            Some(race) => {
+                match self.race_send(race, message) {
+                    Ok(race_send) => return Box::new(race_send),
+                    Err(message) => message,
+                }
+            }
+            None => message,
+        };
+
+        Self::datagram_then_stream(
+            self.datagram_conns.clone(),
+            self.stream_conns.clone(),
+            message,
+            vec![],
         )
     }
 }
@@ -524,58 +1562,370 @@ where
     }
 }
 
-enum TrySend<C: ClientHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> {
-    Lock {
-        conns: Arc<Mutex<BinaryHeap<NameServer<C, P>>>>,
-        message: Option<Message>,
-    },
-    DoSend(Box<Future<Item = Message, Error = ClientError>>),
+/// Checks the response for illegal data, a CNAME with other data at the same name in the answers
+///  or more than one SOA in the authority section, and handles it per the policy
+fn check_illegal_data(mut response: Message, policy: IllegalDataPolicy) -> ClientResult<Message> {
+    let cname_and_other_data = response
+        .answers()
+        .iter()
+        .filter(|cname| cname.rr_type() == RecordType::CNAME)
+        .filter(|cname| {
+            response.answers().iter().any(|r| {
+                r.name() == cname.name() && !is_allowed_with_cname(r.rr_type())
+            })
+        })
+        .map(|cname| cname.name().clone())
+        .collect::<Vec<_>>();
+    let soa_count = response
+        .name_servers()
+        .iter()
+        .filter(|r| r.rr_type() == RecordType::SOA)
+        .count();
+
+    if cname_and_other_data.is_empty() && soa_count <= 1 {
+        return Ok(response);
+    }
+
+    let description = format!(
+        "illegal data in response for {:?}, CNAME and other data at: {:?}, SOAs: {}",
+        response.queries().iter().map(|q| q.name()).collect::<Vec<_>>(),
+        cname_and_other_data,
+        soa_count
+    );
+
+    match policy {
+        IllegalDataPolicy::Reject => return Err(ClientErrorKind::Msg(description).into()),
+        IllegalDataPolicy::Warn => {
+            warn!("{}", description);
+            return Ok(response);
+        }
+        IllegalDataPolicy::Repair => warn!("repairing {}", description),
+    }
+
+    // the other data is kept, as it answers the query directly
+    let answers = response
+        .take_answers()
+        .into_iter()
+        .filter(|r| {
+            r.rr_type() != RecordType::CNAME || !cname_and_other_data.contains(r.name())
+        })
+        .collect();
+
+    let mut has_soa = false;
+    let name_servers = response
+        .take_name_servers()
+        .into_iter()
+        .filter(|r| if r.rr_type() == RecordType::SOA {
+            !mem::replace(&mut has_soa, true)
+        } else {
+            true
+        })
+        .collect();
+
+    response.insert_answers(answers);
+    response.insert_name_servers(name_servers);
+    Ok(response)
 }
 
-impl<C: ClientHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> Future
-    for TrySend<C, P> {
+fn is_allowed_with_cname(record_type: RecordType) -> bool {
+    match record_type {
+        RecordType::CNAME | RecordType::RRSIG | RecordType::NSEC | RecordType::NSEC3 => true,
+        _ => false,
+    }
+}
+
+/// Removes the records of the types from all the sections of the response, and the RRSIGs which
+///  cover them, see `ResolverOpts::stripped_record_types`
+fn strip_record_types(mut response: Message, stripped: &[RecordType]) -> Message {
+    if stripped.is_empty() {
+        return response;
+    }
+
+    let is_kept = |record: &Record| match *record.rdata() {
+        RData::SIG(ref sig) if stripped.contains(&sig.type_covered()) => false,
+        _ => !stripped.contains(&record.rr_type()),
+    };
+
+    let answers = response.take_answers();
+    let name_servers = response.take_name_servers();
+    let additionals = response.take_additionals();
+    let (answers, removed): (Vec<_>, Vec<_>) = answers.into_iter().partition(|r| is_kept(r));
+    let name_servers = name_servers.into_iter().filter(|r| is_kept(r)).collect();
+    let additionals = additionals.into_iter().filter(|r| is_kept(r)).collect();
+
+    if !removed.is_empty() {
+        debug!(
+            "removed {} answers of stripped types for {:?}",
+            removed.len(),
+            response.queries().iter().map(|q| q.name()).collect::<Vec<_>>(),
+        );
+    }
+
+    response.insert_answers(answers);
+    response.insert_name_servers(name_servers);
+    response.insert_additionals(additionals);
+    response
+}
+
+/// Removes the internal addresses from the answers and additionals of the response, unless the
+///  query is for a name within one of the allowed domains, see `ResolverOpts::rebind_protection`
+///
+/// The query name is checked, rather than the names of the records, so that an external name
+///  can not reach an internal address through a CNAME to an allowed domain.
+fn strip_internal_addresses(mut response: Message, allowed: &[Name]) -> Message {
+    let is_allowed = response.queries().iter().all(|query| {
+        allowed.iter().any(|domain| domain.zone_of(query.name()))
+    });
+    if is_allowed {
+        return response;
+    }
+
+    let is_external = |record: &Record| match *record.rdata() {
+        RData::A(ip) => !is_internal_address(IpAddr::V4(ip)),
+        RData::AAAA(ip) => !is_internal_address(IpAddr::V6(ip)),
+        _ => true,
+    };
+
+    let answers = response.take_answers();
+    let additionals = response.take_additionals();
+    let (answers, stripped): (Vec<_>, Vec<_>) = answers.into_iter().partition(|r| is_external(r));
+    let additionals = additionals.into_iter().filter(|r| is_external(r)).collect();
+
+    if !stripped.is_empty() {
+        warn!(
+            "removed internal addresses from answers for {:?}, possible DNS rebinding: {:?}",
+            response.queries().iter().map(|q| q.name()).collect::<Vec<_>>(),
+            stripped.iter().map(|r| r.rdata()).collect::<Vec<_>>(),
+        );
+    }
+
+    response.insert_answers(answers);
+    response.insert_additionals(additionals);
+    response
+}
+
+/// Returns true if the address is loopback, private, link-local or unspecified
+fn is_internal_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_ipv4(&ip),
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            let mapped = segments[..5].iter().all(|s| *s == 0) && segments[5] == 0xffff;
+
+            ip.is_loopback() || ip.is_unspecified() ||
+                // link-local fe80::/10 and unique local fc00::/7
+                segments[0] & 0xffc0 == 0xfe80 || segments[0] & 0xfe00 == 0xfc00 ||
+                (mapped && is_internal_ipv4(&Ipv4Addr::new(
+                    (segments[6] >> 8) as u8,
+                    segments[6] as u8,
+                    (segments[7] >> 8) as u8,
+                    segments[7] as u8,
+                )))
+        }
+    }
+}
+
+fn is_internal_ipv4(ip: &Ipv4Addr) -> bool {
+    ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified()
+}
+
+/// Spawns a task onto the reactor which keeps the DNS over TLS and DNS over HTTPS connections
+///  warm, the task ends once the NameServers are dropped.
+///
+/// A root NS query is sent on each connection which has been idle for the interval. This
+///  exercises the whole path to the NameServer, where an HTTP/2 PING would only reach the HTTP
+///  layer. A query which fails marks the NameServer as failed, it is reconnected by a later
+///  keepalive, after the retry delay, rather than by the next lookup.
+fn spawn_keepalive<C, P>(
+    conns: Weak<Mutex<Vec<NameServer<C, P>>>>,
+    interval: Duration,
+    reactor: &Handle,
+) where
+    C: ClientHandle + 'static,
+    P: ConnectionProvider<ConnHandle = C> + 'static,
+{
+    // checking at half the interval keeps an idle connection from going unused for much more
+    //  than the interval
+    let ticks = match Interval::new(interval / 2, reactor) {
+        Ok(ticks) => ticks,
+        Err(e) => {
+            warn!("encrypted connections will not be kept alive: {}", e);
+            return;
+        }
+    };
+
+    let handle = reactor.clone();
+    reactor.spawn(
+        ticks
+            .map_err(|e| warn!("error in keepalive interval: {}", e))
+            .for_each(move |()| {
+                // the resolver is gone, stop the keepalives
+                let conns = match conns.upgrade() {
+                    Some(conns) => conns,
+                    None => return Err(()),
+                };
+
+                // lookups hold the lock only briefly, if it is held now, try on the next tick
+                let mut conns = match conns.try_lock() {
+                    Ok(conns) => conns,
+                    Err(TryLockError::WouldBlock) => return Ok(()),
+                    Err(TryLockError::Poisoned(_)) => return Err(()),
+                };
+
+                for conn in conns.iter_mut().filter(|conn| {
+                    conn.config.protocol.is_encrypted() && conn.is_idle(interval)
+                })
+                {
+                    debug!("keepalive: {:?}", conn.config);
+                    let keepalive = conn.query(Name::root(), DNSClass::IN, RecordType::NS);
+                    handle.spawn(keepalive.map(|_| ()).map_err(
+                        |e| debug!("keepalive failed: {}", e),
+                    ));
+                }
+
+                Ok(())
+            }),
+    );
+}
+
+/// Races the NameServers of the preferred protocol against the others, the first response wins
+///
+/// The others are only sent the message once the head start has elapsed, or all the NameServers
+///  of the preferred protocol have failed.
+struct RaceSend {
+    preferred: Option<Box<Future<Item = Message, Error = ClientError>>>,
+    head_start: Option<Timeout>,
+    others: Option<Box<Future<Item = Message, Error = ClientError>>>,
+    error: Option<ClientError>,
+}
+
+impl Future for RaceSend {
     type Item = Message;
     type Error = ClientError;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let future;
+        if let Some(mut preferred) = self.preferred.take() {
+            match preferred.poll() {
+                Ok(Async::Ready(response)) => return Ok(Async::Ready(response)),
+                Ok(Async::NotReady) => self.preferred = Some(preferred),
+                Err(error) => {
+                    // there is nothing left to wait for
+                    debug!("preferred transport failed, ending its head start: {}", error);
+                    self.head_start = None;
+                    self.error = Some(error);
+                }
+            }
+        }
 
-        match *self {
-            TrySend::Lock {
-                ref conns,
-                ref mut message,
-            } => {
-                // pull a lock on the shared connections, lock releases at the end of the method
-                let conns = conns.try_lock();
-                match conns {
-                    Err(TryLockError::Poisoned(_)) => {
-                        // TODO: what to do on poisoned errors? this is non-recoverable, right?
-                        return Err(ClientErrorKind::Msg("Lock Poisoned".to_string()).into());
+        if let Some(mut head_start) = self.head_start.take() {
+            match head_start.poll() {
+                Ok(Async::NotReady) => {
+                    self.head_start = Some(head_start);
+                    return Ok(Async::NotReady);
+                }
+                Ok(Async::Ready(())) => debug!("head start elapsed, racing the other transports"),
+                Err(e) => warn!("error in head start, racing the other transports: {}", e),
+            }
+        }
+
+        if let Some(mut others) = self.others.take() {
+            match others.poll() {
+                Ok(Async::Ready(response)) => return Ok(Async::Ready(response)),
+                Ok(Async::NotReady) => self.others = Some(others),
+                Err(error) => {
+                    if self.error.is_none() {
+                        self.error = Some(error);
                     }
-                    Err(TryLockError::WouldBlock) => return Ok(Async::NotReady),
-                    Ok(mut conns) => {
-                        // select the highest priority connection
-                        let conn = conns.peek_mut();
+                }
+            }
+        }
+
+        if self.preferred.is_none() && self.others.is_none() {
+            // the error of the preferred transport if it failed
+            return Err(self.error.take().unwrap_or_else(|| {
+                ClientErrorKind::Message("No connections available").into()
+            }));
+        }
 
-                        if conn.is_none() {
-                            return Err(ClientErrorKind::Message("No connections available").into());
+        Ok(Async::NotReady)
+    }
+}
+
+/// Sends the message to the most preferred NameServer, failing over to the next most preferred
+///  on a connection failure or SERVFAIL, until every NameServer has been tried
+struct TrySend<C: ClientHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> {
+    conns: Arc<Mutex<Vec<NameServer<C, P>>>>,
+    message: Message,
+    tried: Vec<NameServerConfig>,
+    future: Option<Box<Future<Item = Message, Error = ClientError>>>,
+    servfail: Option<Message>,
+    error: Option<ClientError>,
+}
+
+impl<C: ClientHandle + 'static, P: ConnectionProvider<ConnHandle = C> + 'static> Future
+    for TrySend<C, P> {
+    type Item = Message;
+    type Error = ClientError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            if let Some(ref mut future) = self.future {
+                match future.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Ok(Async::Ready(response)) => {
+                        if response.response_code() != ResponseCode::ServFail {
+                            return Ok(Async::Ready(response));
                         }
 
-                        let message = mem::replace(message, None);
-                        future = conn.unwrap().send(message.expect(
-                            "bad state, mesage should never be None",
-                        ));
+                        // returned if no other NameServer can do better
+                        self.servfail = Some(response);
+                    }
+                    Err(error) => {
+                        debug!("failing over to the next name server: {}", error);
+                        self.error = Some(error);
                     }
                 }
             }
-            TrySend::DoSend(ref mut future) => return future.poll(),
-        }
 
-        // can only get here if we were in the TrySend::Lock state
-        mem::replace(self, TrySend::DoSend(future));
+            // pull a lock on the shared connections, lock releases at the end of the loop
+            let mut conns = match self.conns.try_lock() {
+                Err(TryLockError::Poisoned(_)) => {
+                    // TODO: what to do on poisoned errors? this is non-recoverable, right?
+                    return Err(ClientErrorKind::Msg("Lock Poisoned".to_string()).into());
+                }
+                Err(TryLockError::WouldBlock) => {
+                    task::current().notify();
+                    return Ok(Async::NotReady);
+                }
+                Ok(conns) => conns,
+            };
+
+            if conns.is_empty() {
+                return Err(ClientErrorKind::Message("No connections available").into());
+            }
 
-        task::current().notify();
-        Ok(Async::NotReady)
+            // the stats change as responses arrive, so rank the connections at each selection
+            conns.sort_by(|a, b| b.cmp(a));
+
+            // select the highest priority connection which has not been tried
+            let tried = &mut self.tried;
+            let conn = conns.iter_mut().find(|conn| !tried.contains(&conn.config));
+            match conn {
+                Some(conn) => {
+                    tried.push(conn.config.clone());
+                    self.future = Some(conn.send(self.message.clone()));
+                }
+                None => {
+                    if let Some(response) = self.servfail.take() {
+                        return Ok(Async::Ready(response));
+                    }
+
+                    return Err(self.error.take().unwrap_or_else(|| {
+                        ClientErrorKind::Message("No connections available").into()
+                    }));
+                }
+            }
+        }
     }
 }
 
@@ -598,12 +1948,24 @@ mod tests {
             state: NameServerState::Init { send_edns: None },
             successes: 0,
             failures: 0,
+            consecutive_failures: 0,
+            srtt_micros: None,
+            rttvar_micros: None,
+            quarantined_until: None,
+            last_activity: None,
+            retry_jitter_percent: 0,
         };
 
         let established = NameServerStats {
             state: NameServerState::Established { remote_edns: None },
             successes: 0,
             failures: 0,
+            consecutive_failures: 0,
+            srtt_micros: None,
+            rttvar_micros: None,
+            quarantined_until: None,
+            last_activity: None,
+            retry_jitter_percent: 0,
         };
 
         let failed = NameServerStats {
@@ -613,18 +1975,36 @@ mod tests {
             },
             successes: 0,
             failures: 0,
+            consecutive_failures: 0,
+            srtt_micros: None,
+            rttvar_micros: None,
+            quarantined_until: None,
+            last_activity: None,
+            retry_jitter_percent: 0,
         };
 
         let established_successes = NameServerStats {
             state: NameServerState::Established { remote_edns: None },
             successes: 1,
             failures: 0,
+            consecutive_failures: 0,
+            srtt_micros: None,
+            rttvar_micros: None,
+            quarantined_until: None,
+            last_activity: None,
+            retry_jitter_percent: 0,
         };
 
         let established_failed = NameServerStats {
             state: NameServerState::Established { remote_edns: None },
             successes: 0,
             failures: 1,
+            consecutive_failures: 1,
+            srtt_micros: None,
+            rttvar_micros: None,
+            quarantined_until: None,
+            last_activity: None,
+            retry_jitter_percent: 0,
         };
 
 
@@ -635,11 +2015,451 @@ mod tests {
         assert_eq!(established.cmp(&established_failed), Ordering::Greater);
     }
 
+    #[test]
+    fn test_rtt_and_quarantine_cmp() {
+        let mut fast = NameServerStats::default();
+        fast.next_success(None, Duration::from_millis(10));
+        let mut slow = NameServerStats::default();
+        slow.next_success(None, Duration::from_millis(50));
+
+        assert_eq!(fast.cmp(&slow), Ordering::Greater);
+
+        // the round trip time is smoothed
+        slow.next_success(None, Duration::from_millis(10));
+        assert_eq!(slow.srtt_micros, Some(38_000));
+        assert_eq!(fast.cmp(&slow), Ordering::Greater);
+
+        let mut servfail = fast.clone();
+        servfail.next_servfail(None, Duration::from_millis(10), Instant::now());
+        assert!(servfail.is_quarantined());
+        assert_eq!(slow.cmp(&servfail), Ordering::Greater);
+
+        // the quarantine expires
+        let mut expired = fast.clone();
+        let servfail_at = Instant::now() - Duration::from_secs(SERVFAIL_QUARANTINE_S + 1);
+        expired.next_servfail(None, Duration::from_millis(10), servfail_at);
+        assert!(!expired.is_quarantined());
+
+        // consecutive failures are reset by a success
+        let mut failed = fast.clone();
+        failed.next_failure(ClientErrorKind::Msg("test".to_string()).into(), Instant::now());
+        assert_eq!(failed.consecutive_failures, 1);
+        failed.next_success(None, Duration::from_millis(10));
+        assert_eq!(failed.consecutive_failures, 0);
+        assert_eq!(failed.failures, 1);
+    }
+
+    #[test]
+    fn test_adaptive_timeout() {
+        let max_timeout = Duration::from_secs(5);
+        let mut stats = NameServerStats::default();
+        assert_eq!(stats.adaptive_timeout(max_timeout), max_timeout);
+
+        // the first measurement sets the deviation to half of it
+        stats.next_success(None, Duration::from_millis(100));
+        assert_eq!(stats.rttvar_micros, Some(50_000));
+        assert_eq!(stats.adaptive_timeout(max_timeout), Duration::from_millis(300));
+
+        // a steady round trip time lowers the deviation, down to the lower bound
+        for _ in 0..20 {
+            stats.next_success(None, Duration::from_millis(20));
+        }
+        assert_eq!(
+            stats.adaptive_timeout(max_timeout),
+            Duration::from_millis(MIN_ADAPTIVE_TIMEOUT_MS)
+        );
+
+        // each failure doubles it, up to the maximum
+        let error: ClientError = ClientErrorKind::Timeout.into();
+        stats.next_failure(error.clone(), Instant::now());
+        assert_eq!(
+            stats.adaptive_timeout(max_timeout),
+            Duration::from_millis(MIN_ADAPTIVE_TIMEOUT_MS * 2)
+        );
+        for _ in 0..10 {
+            stats.next_failure(error.clone(), Instant::now());
+        }
+        assert_eq!(stats.adaptive_timeout(max_timeout), max_timeout);
+
+        // the history is kept across restarts
+        let mut restored = NameServerStats::default();
+        restored.restore(&NameServerStatistics::new(
+            "8.8.8.8:53".parse().unwrap(),
+            Protocol::Udp,
+            21,
+            11,
+            11,
+            stats.srtt_micros.map(from_micros),
+            stats.rttvar_micros.map(from_micros),
+            max_timeout,
+        ));
+        assert_eq!(restored.srtt_micros, stats.srtt_micros);
+        assert_eq!(restored.consecutive_failures, 0);
+        assert_eq!(
+            restored.adaptive_timeout(max_timeout),
+            Duration::from_millis(MIN_ADAPTIVE_TIMEOUT_MS)
+        );
+    }
+
+    #[test]
+    fn test_query_case() {
+        use std::str::FromStr;
+        use trust_dns::op::Query;
+
+        let name = Name::from_str("www.example-0x20.com.").unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(name.clone(), RecordType::A));
+
+        let query_case = randomize_query_case(&mut message).expect("one query");
+        assert_eq!(query_case.original.cmp_with_case(&name, false), Ordering::Equal);
+        assert_eq!(query_case.sent, name);
+        assert!(query_case.sent.is_fqdn());
+        assert_eq!(
+            message.queries()[0].name().cmp_with_case(&query_case.sent, false),
+            Ordering::Equal
+        );
+
+        // the response echoes the case, the records are put back in the case of the query
+        let mut response = message.clone();
+        let mut record = Record::with(query_case.sent.clone(), RecordType::A, 86400);
+        record.set_rdata(RData::A(Ipv4Addr::new(127, 0, 0, 1)));
+        response.insert_answers(vec![record]);
+        let response = check_query_case(response, &query_case).expect("echoed case");
+        assert_eq!(response.queries()[0].name().cmp_with_case(&name, false), Ordering::Equal);
+        assert_eq!(response.answers()[0].name().cmp_with_case(&name, false), Ordering::Equal);
+
+        // a response in another case is discarded
+        let mut labels = query_case.sent.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        labels[0] = if labels[0].starts_with('w') { "Www" } else { "www" }.to_string();
+        let mut spoofed = Message::new();
+        spoofed.add_query(Query::query(Name::from_labels(labels), RecordType::A));
+        assert!(check_query_case(spoofed, &query_case).is_err());
+        assert!(check_query_case(Message::new(), &query_case).is_err());
+    }
+
+    #[test]
+    fn test_strip_internal_addresses() {
+        use std::net::Ipv6Addr;
+        use std::str::FromStr;
+        use trust_dns::op::Query;
+
+        let response = |name: &str| {
+            let name = Name::from_str(name).unwrap();
+            let mut message = Message::new();
+            message.add_query(Query::query(name.clone(), RecordType::A));
+            message.insert_answers(vec![
+                Record::from_rdata(
+                    name.clone(),
+                    60,
+                    RecordType::A,
+                    RData::A(Ipv4Addr::new(192, 168, 1, 1)),
+                ),
+                Record::from_rdata(
+                    name.clone(),
+                    60,
+                    RecordType::A,
+                    RData::A(Ipv4Addr::new(93, 184, 216, 34)),
+                ),
+            ]);
+            message.insert_additionals(vec![
+                Record::from_rdata(
+                    name,
+                    60,
+                    RecordType::AAAA,
+                    RData::AAAA(Ipv6Addr::from_str("::ffff:127.0.0.1").unwrap()),
+                ),
+            ]);
+            message
+        };
+        let allowed = vec![Name::from_str("lan.").unwrap()];
+
+        let stripped = strip_internal_addresses(response("rebind.example.com."), &allowed);
+        assert_eq!(stripped.answers().len(), 1);
+        assert_eq!(
+            stripped.answers()[0].rdata(),
+            &RData::A(Ipv4Addr::new(93, 184, 216, 34))
+        );
+        assert!(stripped.additionals().is_empty());
+
+        let kept = strip_internal_addresses(response("printer.lan."), &allowed);
+        assert_eq!(kept.answers().len(), 2);
+        assert_eq!(kept.additionals().len(), 1);
+
+        assert!(is_internal_address(IpAddr::from_str("fe80::1").unwrap()));
+        assert!(is_internal_address(IpAddr::from_str("fd00::1").unwrap()));
+        assert!(is_internal_address(IpAddr::from_str("0.0.0.0").unwrap()));
+        assert!(!is_internal_address(IpAddr::from_str("2001:db8::1").unwrap()));
+    }
+
+    #[test]
+    fn test_check_illegal_data() {
+        use std::str::FromStr;
+        use trust_dns::rr::rdata::SOA;
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let soa = |serial| {
+            Record::from_rdata(
+                Name::from_str("example.com.").unwrap(),
+                60,
+                RecordType::SOA,
+                RData::SOA(SOA::new(
+                    Name::from_str("ns.example.com.").unwrap(),
+                    Name::from_str("hostmaster.example.com.").unwrap(),
+                    serial,
+                    7200,
+                    3600,
+                    1209600,
+                    60,
+                )),
+            )
+        };
+
+        let mut response = Message::new();
+        response.insert_answers(vec![
+            Record::from_rdata(
+                name.clone(),
+                60,
+                RecordType::CNAME,
+                RData::CNAME(Name::from_str("example.net.").unwrap()),
+            ),
+            Record::from_rdata(
+                name.clone(),
+                60,
+                RecordType::A,
+                RData::A(Ipv4Addr::new(93, 184, 216, 34)),
+            ),
+        ]);
+        response.insert_name_servers(vec![soa(1), soa(2)]);
+
+        assert!(check_illegal_data(response.clone(), IllegalDataPolicy::Reject).is_err());
+
+        let warned = check_illegal_data(response.clone(), IllegalDataPolicy::Warn).unwrap();
+        assert_eq!(warned.answers().len(), 2);
+        assert_eq!(warned.name_servers().len(), 2);
+
+        let repaired = check_illegal_data(response, IllegalDataPolicy::Repair).unwrap();
+        assert_eq!(repaired.answers().len(), 1);
+        assert_eq!(repaired.answers()[0].rr_type(), RecordType::A);
+        assert_eq!(repaired.name_servers().to_vec(), vec![soa(1)]);
+
+        // a CNAME on its own is legal
+        let mut response = Message::new();
+        response.insert_answers(vec![
+            Record::from_rdata(
+                name,
+                60,
+                RecordType::CNAME,
+                RData::CNAME(Name::from_str("example.net.").unwrap()),
+            ),
+        ]);
+        assert!(check_illegal_data(response, IllegalDataPolicy::Reject).is_ok());
+    }
+
+    #[test]
+    fn test_strip_record_types() {
+        use std::str::FromStr;
+        use trust_dns::rr::dnssec::Algorithm;
+        use trust_dns::rr::rdata::{HINFO, SIG};
+
+        let name = Name::from_str("www.example.com.").unwrap();
+        let a = Record::from_rdata(
+            name.clone(),
+            60,
+            RecordType::A,
+            RData::A(Ipv4Addr::new(93, 184, 216, 34)),
+        );
+        let hinfo = Record::from_rdata(
+            name.clone(),
+            60,
+            RecordType::HINFO,
+            RData::HINFO(HINFO::new("cpu".to_string(), "os".to_string())),
+        );
+        let rrsig = |type_covered| {
+            Record::from_rdata(
+                name.clone(),
+                60,
+                RecordType::RRSIG,
+                RData::SIG(SIG::new(
+                    type_covered,
+                    Algorithm::RSASHA256,
+                    3,
+                    60,
+                    0,
+                    0,
+                    0,
+                    Name::from_str("example.com.").unwrap(),
+                    vec![],
+                )),
+            )
+        };
+
+        let mut response = Message::new();
+        response.insert_answers(vec![
+            a.clone(),
+            rrsig(RecordType::A),
+            hinfo.clone(),
+            rrsig(RecordType::HINFO),
+        ]);
+        response.insert_additionals(vec![hinfo.clone()]);
+
+        let stripped = strip_record_types(response.clone(), &[RecordType::HINFO]);
+        assert_eq!(stripped.answers().to_vec(), vec![a, rrsig(RecordType::A)]);
+        assert!(stripped.additionals().is_empty());
+
+        let kept = strip_record_types(response, &[]);
+        assert_eq!(kept.answers().len(), 4);
+        assert_eq!(kept.additionals().to_vec(), vec![hinfo]);
+    }
+
+    #[test]
+    fn test_cookies() {
+        let mut cookies = Cookies::new();
+        let client = cookies.client;
+        let response = |cookie: Option<Cookie>| {
+            let mut response = Message::new();
+            response.set_edns(Edns::new());
+            if let Some(cookie) = cookie {
+                response.edns_mut().set_option(EdnsOption::Cookie(cookie));
+            }
+            response
+        };
+
+        // servers without cookies are fine, until one is learned
+        assert!(cookies.check(&response(None)).is_ok());
+        assert_eq!(cookies.option(), EdnsOption::Cookie(Cookie::new(client, None)));
+
+        assert!(
+            cookies
+                .check(&response(Some(Cookie::new(client, Some(vec![1; 8])))))
+                .is_ok()
+        );
+        assert_eq!(
+            cookies.option(),
+            EdnsOption::Cookie(Cookie::new(client, Some(vec![1; 8])))
+        );
+
+        // spoofed responses
+        let mut other = client;
+        other[0] ^= 0xFF;
+        assert!(
+            cookies
+                .check(&response(Some(Cookie::new(other, Some(vec![2; 8])))))
+                .is_err()
+        );
+        assert!(cookies.check(&response(None)).is_err());
+        assert_eq!(
+            cookies.option(),
+            EdnsOption::Cookie(Cookie::new(client, Some(vec![1; 8])))
+        );
+    }
+
+    #[test]
+    fn test_pad_query() {
+        use trust_dns::op::Query;
+
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::parse("www.example.com.", None).unwrap(),
+            RecordType::A,
+        ));
+
+        pad_query(&mut message, None, NameCompression::All);
+        assert!(message.edns().is_none());
+
+        pad_query(&mut message, Some(128), NameCompression::All);
+        assert_eq!(message.to_vec().unwrap().len(), 128);
+    }
+
+    #[test]
+    fn test_keepalive_idle_and_retry_jitter() {
+        let interval = Duration::from_secs(30);
+        let mut stats = NameServerStats::default();
+        assert!(stats.is_idle(interval));
+
+        stats.next_success(None, Duration::from_millis(10));
+        assert!(!stats.is_idle(interval));
+
+        let long_ago = Instant::now() - interval;
+        stats.next_failure(ClientErrorKind::Msg("test".to_string()).into(), long_ago);
+        assert!(stats.is_idle(interval));
+
+        // the jitter only ever adds to the backoff, by at most MAX_RETRY_JITTER_PERCENT
+        let min_delay = Duration::from_millis(MIN_RETRY_DELAY_MS);
+        let retry_delay = stats.retry_delay();
+        assert!(retry_delay >= min_delay);
+        assert!(retry_delay <= min_delay * (100 + MAX_RETRY_JITTER_PERCENT) / 100);
+
+        stats.retry_jitter_percent = 0;
+        assert_eq!(stats.retry_delay(), min_delay);
+        stats.retry_jitter_percent = MAX_RETRY_JITTER_PERCENT;
+        assert_eq!(stats.retry_delay(), min_delay * 3 / 2);
+    }
+
     #[test]
     fn test_name_server() {
         let config = NameServerConfig {
             socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
             protocol: Protocol::Udp,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
+        };
+        let mut io_loop = Core::new().unwrap();
+        let mut name_server = NameServer::<_, StandardConnection>::new(
+            config,
+            ResolverOpts::default(),
+            &io_loop.handle(),
+        );
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let response = io_loop
+            .run(name_server.query(name.clone(), DNSClass::IN, RecordType::A))
+            .expect("query failed");
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+    }
+
+    #[test]
+    #[cfg(feature = "dns-over-tls")]
+    fn test_tls_name_server() {
+        let config = NameServerConfig {
+            socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 853),
+            protocol: Protocol::Tls,
+            tls_dns_name: Some("cloudflare-dns.com".to_string()),
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
+        };
+        let mut io_loop = Core::new().unwrap();
+        let mut name_server = NameServer::<_, StandardConnection>::new(
+            config,
+            ResolverOpts::default(),
+            &io_loop.handle(),
+        );
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let response = io_loop
+            .run(name_server.query(name.clone(), DNSClass::IN, RecordType::A))
+            .expect("query failed");
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+    }
+
+    #[test]
+    #[cfg(feature = "dns-over-https")]
+    fn test_https_name_server() {
+        let config = NameServerConfig {
+            socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 443),
+            protocol: Protocol::Https,
+            tls_dns_name: Some("cloudflare-dns.com".to_string()),
+            https_template: Some("https://cloudflare-dns.com/dns-query{?dns}".to_string()),
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
         };
         let mut io_loop = Core::new().unwrap();
         let mut name_server = NameServer::<_, StandardConnection>::new(
@@ -662,6 +2482,12 @@ mod tests {
         let config = NameServerConfig {
             socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 252)), 252),
             protocol: Protocol::Udp,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
         };
         let mut io_loop = Core::new().unwrap();
         let mut name_server =
@@ -682,11 +2508,23 @@ mod tests {
         let config1 = NameServerConfig {
             socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 252)), 253),
             protocol: Protocol::Udp,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
         };
 
         let config2 = NameServerConfig {
             socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
             protocol: Protocol::Udp,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
         };
 
         let mut resolver_config = ResolverConfig::new();
@@ -0,0 +1,275 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Statistics of the upstream NameServers, for monitoring them and for keeping what was learned
+//!  of them across restarts
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use config::Protocol;
+
+/// A snapshot of the statistics of a NameServer, see `ResolverFuture::name_server_statistics`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameServerStatistics {
+    socket_addr: SocketAddr,
+    protocol: Protocol,
+    successes: usize,
+    failures: usize,
+    consecutive_failures: usize,
+    srtt: Option<Duration>,
+    rttvar: Option<Duration>,
+    timeout: Duration,
+}
+
+impl NameServerStatistics {
+    pub(crate) fn new(
+        socket_addr: SocketAddr,
+        protocol: Protocol,
+        successes: usize,
+        failures: usize,
+        consecutive_failures: usize,
+        srtt: Option<Duration>,
+        rttvar: Option<Duration>,
+        timeout: Duration,
+    ) -> Self {
+        NameServerStatistics {
+            socket_addr,
+            protocol,
+            successes,
+            failures,
+            consecutive_failures,
+            srtt,
+            rttvar,
+            timeout,
+        }
+    }
+
+    /// The address of the NameServer
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.socket_addr
+    }
+
+    /// The protocol of the NameServer
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// The number of requests which were answered
+    pub fn successes(&self) -> usize {
+        self.successes
+    }
+
+    /// The number of requests which failed, e.g. timed out
+    pub fn failures(&self) -> usize {
+        self.failures
+    }
+
+    /// The number of requests which failed since the last one which was answered
+    pub fn consecutive_failures(&self) -> usize {
+        self.consecutive_failures
+    }
+
+    /// The smoothed round trip time of the answered requests, None before the first answer
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt
+    }
+
+    /// The mean deviation of the round trip times from the smoothed round trip time
+    pub fn rttvar(&self) -> Option<Duration> {
+        self.rttvar
+    }
+
+    /// The time the next request waits for a response, see `ResolverOpts::adaptive_timeout`
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+/// Writes the statistics to the file, one NameServer per line, to be read back with
+///  `read_statistics`
+pub fn write_statistics<P: AsRef<Path>>(
+    path: P,
+    statistics: &[NameServerStatistics],
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    format_statistics(&mut file, statistics)?;
+    file.flush()
+}
+
+/// Reads the statistics written by `write_statistics`
+///
+/// Lines which can not be read, e.g. of a protocol not enabled in this build, are skipped, the
+///  statistics only speed up the choice of NameServers.
+pub fn read_statistics<P: AsRef<Path>>(path: P) -> io::Result<Vec<NameServerStatistics>> {
+    let file = File::open(path)?;
+    parse_statistics(BufReader::new(file))
+}
+
+// lines have the form `addr protocol successes failures srtt_micros rttvar_micros`, with `-` for
+//  an unmeasured round trip time
+fn format_statistics<W: Write>(
+    writer: &mut W,
+    statistics: &[NameServerStatistics],
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "# addr protocol successes failures srtt_micros rttvar_micros"
+    )?;
+
+    for stats in statistics {
+        writeln!(
+            writer,
+            "{} {} {} {} {} {}",
+            stats.socket_addr,
+            protocol_name(stats.protocol),
+            stats.successes,
+            stats.failures,
+            stats.srtt.map_or("-".to_string(), |srtt| to_micros(srtt).to_string()),
+            stats.rttvar.map_or("-".to_string(), |rttvar| to_micros(rttvar).to_string())
+        )?;
+    }
+
+    Ok(())
+}
+
+fn parse_statistics<R: BufRead>(reader: R) -> io::Result<Vec<NameServerStatistics>> {
+    let mut statistics = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match parse_line(line) {
+            Some(stats) => statistics.push(stats),
+            None => warn!("skipping name server statistics: {}", line),
+        }
+    }
+
+    Ok(statistics)
+}
+
+fn parse_line(line: &str) -> Option<NameServerStatistics> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    if fields.len() != 6 {
+        return None;
+    }
+
+    let micros = |field: &str| -> Result<Option<Duration>, ()> {
+        if field == "-" {
+            Ok(None)
+        } else {
+            u64::from_str(field).map(|micros| Some(from_micros(micros))).map_err(|_| ())
+        }
+    };
+
+    let socket_addr = SocketAddr::from_str(fields[0]).map_err(|_| ());
+    let protocol = parse_protocol(fields[1]).ok_or(());
+    let successes = usize::from_str(fields[2]).map_err(|_| ());
+    let failures = usize::from_str(fields[3]).map_err(|_| ());
+
+    match (socket_addr, protocol, successes, failures, micros(fields[4]), micros(fields[5])) {
+        (Ok(socket_addr), Ok(protocol), Ok(successes), Ok(failures), Ok(srtt), Ok(rttvar)) => {
+            Some(NameServerStatistics::new(
+                socket_addr,
+                protocol,
+                successes,
+                failures,
+                0,
+                srtt,
+                rttvar,
+                Duration::from_secs(0),
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn protocol_name(protocol: Protocol) -> &'static str {
+    match protocol {
+        Protocol::Udp => "udp",
+        Protocol::Tcp => "tcp",
+        #[cfg(feature = "dns-over-tls")]
+        Protocol::Tls => "tls",
+        #[cfg(feature = "dns-over-https")]
+        Protocol::Https => "https",
+        #[cfg(feature = "mdns")]
+        Protocol::Mdns => "mdns",
+    }
+}
+
+fn parse_protocol(name: &str) -> Option<Protocol> {
+    match name {
+        "udp" => Some(Protocol::Udp),
+        "tcp" => Some(Protocol::Tcp),
+        #[cfg(feature = "dns-over-tls")]
+        "tls" => Some(Protocol::Tls),
+        #[cfg(feature = "dns-over-https")]
+        "https" => Some(Protocol::Https),
+        #[cfg(feature = "mdns")]
+        "mdns" => Some(Protocol::Mdns),
+        _ => None,
+    }
+}
+
+pub(crate) fn to_micros(duration: Duration) -> u64 {
+    duration
+        .as_secs()
+        .saturating_mul(1_000_000)
+        .saturating_add((duration.subsec_nanos() / 1_000) as u64)
+}
+
+pub(crate) fn from_micros(micros: u64) -> Duration {
+    Duration::new(micros / 1_000_000, (micros % 1_000_000) as u32 * 1_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statistics_round_trip() {
+        let statistics = vec![
+            NameServerStatistics::new(
+                "8.8.8.8:53".parse().unwrap(),
+                Protocol::Udp,
+                10,
+                2,
+                0,
+                Some(Duration::from_millis(25)),
+                Some(from_micros(4_500)),
+                Duration::from_secs(0),
+            ),
+            NameServerStatistics::new(
+                "[2001:4860:4860::8888]:53".parse().unwrap(),
+                Protocol::Tcp,
+                0,
+                1,
+                0,
+                None,
+                None,
+                Duration::from_secs(0),
+            ),
+        ];
+
+        let mut buffer = Vec::new();
+        format_statistics(&mut buffer, &statistics).unwrap();
+        assert_eq!(parse_statistics(&buffer[..]).unwrap(), statistics);
+
+        // a line which can not be read is skipped
+        let mut buffer = b"1.1.1.1:53 quic 1 0 - -\n".to_vec();
+        format_statistics(&mut buffer, &statistics[..1]).unwrap();
+        assert_eq!(parse_statistics(&buffer[..]).unwrap(), &statistics[..1]);
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Observation of the lifecycle of lookups, for metrics and tracing
+//!
+//! The resolver does not record any metrics itself, an application registers a `LookupObserver`
+//!  with `ResolverFuture::set_lookup_observer` and passes the events on to its own systems.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use trust_dns::error::ClientError;
+use trust_dns::op::{Message, Query};
+
+use config::Protocol;
+use lookup::Lookup;
+
+/// Identifies a lookup in the events of it, unique for the lifetime of the resolver
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LookupId(usize);
+
+/// An event in the lifecycle of a lookup
+///
+/// Each lookup starts with `Started` and ends with `Completed`, with the same `LookupId`. In
+///  between the lookup is answered from the cache, or is sent to the NameServers; the CNAMEs of
+///  an answer are followed with further queries, which are reported with the id of the lookup.
+#[derive(Debug)]
+pub enum LookupEvent<'a> {
+    /// The lookup of the query started
+    Started {
+        /// the lookup
+        id: LookupId,
+        /// the query which is looked up
+        query: &'a Query,
+    },
+    /// The query was answered from the cache
+    CacheHit {
+        /// the lookup
+        id: LookupId,
+        /// the query, the target of a CNAME if the lookup is following one
+        query: &'a Query,
+    },
+    /// The query was sent to a NameServer
+    UpstreamAttempt {
+        /// the lookup
+        id: LookupId,
+        /// the query, the target of a CNAME if the lookup is following one
+        query: &'a Query,
+        /// the NameServer the query was sent to
+        name_server: SocketAddr,
+        /// the protocol the query was sent over
+        protocol: Protocol,
+        /// the number of the attempt for the query, starting at 1, later attempts are retries
+        attempt: usize,
+    },
+    /// An attempt failed, without a response from the NameServer, e.g. it timed out
+    ///
+    /// This is followed by another `UpstreamAttempt` while attempts remain, see
+    ///  `ResolverOpts::attempts`.
+    AttemptFailed {
+        /// the lookup
+        id: LookupId,
+        /// the query, the target of a CNAME if the lookup is following one
+        query: &'a Query,
+        /// the NameServer the query was sent to
+        name_server: SocketAddr,
+        /// the error of the attempt
+        error: &'a ClientError,
+    },
+    /// The lookup completed
+    Completed {
+        /// the lookup
+        id: LookupId,
+        /// the query which was looked up
+        query: &'a Query,
+        /// the outcome of the lookup, records or the reason there are none
+        result: Result<&'a Lookup, &'a io::Error>,
+        /// the time from the start of the lookup
+        elapsed: Duration,
+    },
+}
+
+/// Receives the events of all the lookups of a resolver
+///
+/// The events are delivered from within the polling of the lookups, so the observer must not
+///  block. Lookups run concurrently, their events are interleaved.
+pub trait LookupObserver: Send + Sync {
+    /// Called for each event of each lookup
+    fn on_event(&self, event: &LookupEvent);
+}
+
+/// The observer of the lookups, shared by the cache and the NameServers of a resolver
+///
+/// The queries sent to the NameServers are related to their lookup through the queries in flight,
+///  concurrent lookups of the same query are reported for the latest of them.
+#[derive(Clone, Default)]
+pub(crate) struct LookupObservers {
+    observer: Arc<RwLock<Option<Arc<LookupObserver>>>>,
+    next_id: Arc<AtomicUsize>,
+    in_flight: Arc<Mutex<HashMap<Query, (LookupId, usize)>>>,
+}
+
+impl LookupObservers {
+    /// Replaces the observer of all the clones of these observers
+    pub(crate) fn set_observer(&self, observer: Option<Arc<LookupObserver>>) {
+        match self.observer.write() {
+            Ok(mut current) => *current = observer,
+            Err(poisoned) => *poisoned.into_inner() = observer,
+        }
+    }
+
+    fn observer(&self) -> Option<Arc<LookupObserver>> {
+        match self.observer.read() {
+            Ok(observer) => observer.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    fn emit(&self, event: &LookupEvent) {
+        if let Some(observer) = self.observer() {
+            observer.on_event(event);
+        }
+    }
+
+    /// Starts the observation of a lookup, None if there is no observer
+    pub(crate) fn start(&self, query: &Query) -> Option<LookupId> {
+        let observer = match self.observer() {
+            Some(observer) => observer,
+            None => return None,
+        };
+
+        let id = LookupId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        observer.on_event(&LookupEvent::Started { id, query });
+        Some(id)
+    }
+
+    pub(crate) fn cache_hit(&self, id: LookupId, query: &Query) {
+        self.emit(&LookupEvent::CacheHit { id, query });
+    }
+
+    pub(crate) fn completed(
+        &self,
+        id: LookupId,
+        query: &Query,
+        result: Result<&Lookup, &io::Error>,
+        elapsed: Duration,
+    ) {
+        self.emit(&LookupEvent::Completed {
+            id,
+            query,
+            result,
+            elapsed,
+        });
+    }
+
+    /// Relates the queries sent to the NameServers to the lookup until the guard is dropped
+    pub(crate) fn upstream(&self, id: LookupId, query: &Query) -> InFlight {
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+            in_flight.insert(query.clone(), (id, 0));
+        }
+
+        InFlight {
+            observers: self.clone(),
+            id,
+            query: query.clone(),
+        }
+    }
+
+    /// Reports the message being sent to the NameServer, returns the lookup and query of it if
+    ///  the lookup is observed
+    pub(crate) fn attempt(
+        &self,
+        message: &Message,
+        name_server: SocketAddr,
+        protocol: Protocol,
+    ) -> Option<(LookupId, Query)> {
+        let query = match message.queries().first() {
+            Some(query) => query,
+            None => return None,
+        };
+        let (id, attempt) = match self.in_flight.lock() {
+            Ok(mut in_flight) => {
+                match in_flight.get_mut(query) {
+                    Some(&mut (id, ref mut attempts)) => {
+                        *attempts += 1;
+                        (id, *attempts)
+                    }
+                    None => return None,
+                }
+            }
+            Err(_) => return None,
+        };
+
+        self.emit(&LookupEvent::UpstreamAttempt {
+            id,
+            query,
+            name_server,
+            protocol,
+            attempt,
+        });
+        Some((id, query.clone()))
+    }
+
+    pub(crate) fn attempt_failed(
+        &self,
+        id: LookupId,
+        query: &Query,
+        name_server: SocketAddr,
+        error: &ClientError,
+    ) {
+        self.emit(&LookupEvent::AttemptFailed {
+            id,
+            query,
+            name_server,
+            error,
+        });
+    }
+}
+
+/// A query of an observed lookup which is sent to the NameServers, see `LookupObservers::upstream`
+pub(crate) struct InFlight {
+    observers: LookupObservers,
+    id: LookupId,
+    query: Query,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.observers.in_flight.lock() {
+            // a later lookup of the same query replaced this one
+            if in_flight.get(&self.query).map_or(false, |&(id, _)| id == self.id) {
+                in_flight.remove(&self.query);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use trust_dns::op::{Message, Query};
+    use trust_dns::rr::{Name, RecordType};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl LookupObserver for Recorder {
+        fn on_event(&self, event: &LookupEvent) {
+            let event = match *event {
+                LookupEvent::Started { id, .. } => format!("started {:?}", id),
+                LookupEvent::CacheHit { id, .. } => format!("cache {:?}", id),
+                LookupEvent::UpstreamAttempt { id, attempt, .. } => {
+                    format!("attempt {:?} {}", id, attempt)
+                }
+                LookupEvent::AttemptFailed { id, .. } => format!("failed {:?}", id),
+                LookupEvent::Completed { id, .. } => format!("completed {:?}", id),
+            };
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    fn query_message(query: &Query) -> Message {
+        let mut message = Message::new();
+        message.add_query(query.clone());
+        message
+    }
+
+    #[test]
+    fn test_no_observer() {
+        let observers = LookupObservers::default();
+        let query = Query::query(Name::from_labels(vec!["www", "example", "com"]), RecordType::A);
+        assert!(observers.start(&query).is_none());
+    }
+
+    #[test]
+    fn test_attempts() {
+        let recorder = Arc::new(Recorder::default());
+        let observers = LookupObservers::default();
+        observers.set_observer(Some(recorder.clone()));
+
+        let name_server = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53);
+        let query = Query::query(Name::from_labels(vec!["www", "example", "com"]), RecordType::A);
+        let other = Query::query(Name::from_labels(vec!["www", "example", "net"]), RecordType::A);
+        let id = observers.start(&query).expect("observed");
+
+        {
+            let _in_flight = observers.upstream(id, &query);
+            let message = query_message(&query);
+            assert_eq!(
+                observers.attempt(&message, name_server, Protocol::Udp),
+                Some((id, query.clone()))
+            );
+            assert_eq!(
+                observers.attempt(&message, name_server, Protocol::Udp),
+                Some((id, query.clone()))
+            );
+
+            // not part of a lookup
+            let message = query_message(&other);
+            assert!(observers.attempt(&message, name_server, Protocol::Udp).is_none());
+        }
+
+        // the lookup is no longer in flight
+        let message = query_message(&query);
+        assert!(observers.attempt(&message, name_server, Protocol::Udp).is_none());
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                "started LookupId(0)",
+                "attempt LookupId(0) 1",
+                "attempt LookupId(0) 2",
+            ]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Public Suffix List, see [publicsuffix.org](https://publicsuffix.org), for finding the
+//!  registrable domain of a name, e.g. `example.co.uk.` for `www.example.co.uk.`
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use trust_dns::rr::Name;
+
+/// Where the list is commonly installed by the OS, e.g. the `publicsuffix` package on Debian
+const SYSTEM_PSL_PATH: &'static str = "/usr/share/publicsuffix/public_suffix_list.dat";
+
+/// The rules of a Public Suffix List
+///
+/// Labels are matched without regard to ASCII case. Internationalized rules are matched as
+///  written, so must be in the same form, Unicode or punycode, as the names.
+#[derive(Clone, Debug, Default)]
+pub struct PublicSuffixList {
+    /// e.g. `co.uk`
+    rules: HashSet<String>,
+    /// `*.ck` is stored as `ck`
+    wildcards: HashSet<String>,
+    /// `!www.ck` is stored as `www.ck`
+    exceptions: HashSet<String>,
+}
+
+impl PublicSuffixList {
+    /// Reads the list installed with the OS, `/usr/share/publicsuffix/public_suffix_list.dat`
+    pub fn from_system() -> io::Result<Self> {
+        Self::from_file(SYSTEM_PSL_PATH)
+    }
+
+    /// Reads the list from a file in the format of `public_suffix_list.dat`
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut data = String::new();
+        File::open(path)?.read_to_string(&mut data)?;
+
+        Ok(Self::parse(&data))
+    }
+
+    /// Parses the list from the format of `public_suffix_list.dat`
+    ///
+    /// Each line holds one rule, the first word of the line. Empty lines and comments, starting
+    ///  with `//`, are skipped.
+    pub fn parse(data: &str) -> Self {
+        let mut list = PublicSuffixList::default();
+
+        for line in data.lines() {
+            let rule = match line.split_whitespace().next() {
+                Some(rule) if !rule.starts_with("//") => rule.trim_right_matches('.').to_lowercase(),
+                _ => continue,
+            };
+
+            if rule.starts_with("!") {
+                list.exceptions.insert(rule[1..].to_string());
+            } else if rule.starts_with("*.") {
+                list.wildcards.insert(rule[2..].to_string());
+            } else if !rule.is_empty() {
+                list.rules.insert(rule);
+            }
+        }
+
+        list
+    }
+
+    /// Returns the registrable domain of the name, the public suffix and one more label, e.g.
+    ///  `example.co.uk.` for `www.example.co.uk.`
+    ///
+    /// Returns None if the name is a public suffix, or the root.
+    pub fn registrable_domain(&self, name: &Name) -> Option<Name> {
+        let labels = labels(name);
+        let suffix_len = self.public_suffix_len(&labels);
+        if labels.len() > suffix_len {
+            Some(name.to_lowercase().trim_to(suffix_len + 1))
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the name is a public suffix, e.g. `co.uk.`, under which anyone may
+    ///  register a name
+    pub fn is_public_suffix(&self, name: &Name) -> bool {
+        let labels = labels(name);
+        !labels.is_empty() && labels.len() == self.public_suffix_len(&labels)
+    }
+
+    /// The number of labels of the public suffix of the labels, per the algorithm of
+    ///  publicsuffix.org, where a name matching no rule is under a single label suffix, `*`
+    fn public_suffix_len(&self, labels: &[String]) -> usize {
+        let mut suffix_len = 1;
+
+        for len in 1..(labels.len() + 1) {
+            let suffix = labels[labels.len() - len..].join(".");
+
+            // exceptions take precedence over all other rules
+            if self.exceptions.contains(&suffix) {
+                return len - 1;
+            }
+
+            let parent = labels[labels.len() - len + 1..].join(".");
+            if self.rules.contains(&suffix) || (len > 1 && self.wildcards.contains(&parent)) {
+                suffix_len = len;
+            }
+        }
+
+        suffix_len
+    }
+}
+
+/// The lowercase labels of the name, leftmost first
+fn labels(name: &Name) -> Vec<String> {
+    let mut labels = Vec::new();
+    let mut name = name.to_lowercase();
+
+    loop {
+        // the base of the root is the root
+        let base = name.base_name();
+        if base == name {
+            return labels;
+        }
+
+        labels.push(name[0].clone());
+        name = base;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const LIST: &'static str = "// comment
+com
+uk
+co.uk
+
+// wildcard and exception
+*.ck
+!www.ck
+";
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        let list = PublicSuffixList::parse(LIST);
+
+        assert_eq!(
+            list.registrable_domain(&name("www.Example.com.")),
+            Some(name("example.com."))
+        );
+        assert_eq!(
+            list.registrable_domain(&name("www.example.co.uk.")),
+            Some(name("example.co.uk."))
+        );
+        assert_eq!(
+            list.registrable_domain(&name("a.b.example.ck.")),
+            Some(name("b.example.ck."))
+        );
+        assert_eq!(list.registrable_domain(&name("www.ck.")), Some(name("www.ck.")));
+        assert_eq!(list.registrable_domain(&name("co.uk.")), None);
+        assert_eq!(list.registrable_domain(&Name::root()), None);
+
+        // no rule matches, the default rule is a single label
+        assert_eq!(
+            list.registrable_domain(&name("www.example.test.")),
+            Some(name("example.test."))
+        );
+    }
+
+    #[test]
+    fn test_is_public_suffix() {
+        let list = PublicSuffixList::parse(LIST);
+
+        assert!(list.is_public_suffix(&name("com.")));
+        assert!(list.is_public_suffix(&name("CO.uk.")));
+        assert!(list.is_public_suffix(&name("example.ck.")));
+        assert!(!list.is_public_suffix(&name("www.ck.")));
+        assert!(!list.is_public_suffix(&name("example.com.")));
+        assert!(!list.is_public_suffix(&Name::root()));
+    }
+}
@@ -10,20 +10,36 @@
 use std::cell::RefCell;
 use std::net::IpAddr;
 use std::io;
+use std::path::Path;
+use std::sync::Arc;
 
 use tokio_core::reactor::Core;
-use trust_dns::rr::RecordType;
+use trust_dns::op::{Message, Query};
+use trust_dns::rr::{Name, RecordType};
 
-use config::{ResolverConfig, ResolverOpts};
+use config::{NameServerConfig, ResolverConfig, ResolverOpts};
+#[cfg(feature = "e164")]
+use e164::EnumUri;
 use lookup;
+use hosts::Hosts;
 use lookup::Lookup;
-use lookup_ip::LookupIp;
+use lookup_ip::{LookupIp, LookupSocketAddrs};
+use lookup_state::{CacheEntry, CacheStats};
+use name_server_stats::NameServerStatistics;
+use observer::LookupObserver;
+#[cfg(feature = "psl")]
+use psl::PublicSuffixList;
 use ResolverFuture;
 use system_conf;
 
 /// The Resolver is used for performing DNS queries.
 ///
 /// For forward (A) lookups, hostname -> IP address, see: `Resolver::lookup_ip`
+///
+/// This is a blocking facade over `ResolverFuture`, with the same lookups. It owns its event
+///  loop, which is run on the calling thread for the duration of each lookup, so no knowledge of
+///  futures or tokio is needed to use it. It is not `Send`, each thread needs its own Resolver,
+///  or see `ResolverFuture` for sharing one between tasks.
 pub struct Resolver {
     resolver_future: RefCell<ResolverFuture>,
     io_loop: RefCell<Core>,
@@ -91,6 +107,82 @@ impl Resolver {
         Self::new(config, options)
     }
 
+    /// Sends the query to the name server, bypassing the configured name servers and the cache,
+    ///  see `ResolverFuture::query_with_server`
+    pub fn query_with_server(
+        &self,
+        name_server: NameServerConfig,
+        query: Query,
+        cache_response: bool,
+    ) -> io::Result<Message> {
+        self.io_loop.borrow_mut().run(
+            self.resolver_future.borrow().query_with_server(
+                name_server,
+                query,
+                cache_response,
+            ),
+        )
+    }
+
+    /// Replaces the hosts consulted by `lookup_ip`, see `ResolverFuture::set_hosts`
+    pub fn set_hosts(&self, hosts: Option<Hosts>) {
+        self.resolver_future.borrow_mut().set_hosts(hosts)
+    }
+
+    /// Sets the observer of the events of each lookup, see `ResolverFuture::set_lookup_observer`
+    pub fn set_lookup_observer(&self, observer: Option<Arc<LookupObserver>>) {
+        self.resolver_future.borrow().set_lookup_observer(observer)
+    }
+
+    /// Returns the hit, miss, and eviction counters of the resolver's cache
+    pub fn cache_stats(&self) -> io::Result<CacheStats> {
+        self.resolver_future.borrow().cache_stats()
+    }
+
+    /// Returns a snapshot of all the unexpired entries in the resolver's cache
+    pub fn cache_entries(&self) -> io::Result<Vec<CacheEntry>> {
+        self.resolver_future.borrow().cache_entries()
+    }
+
+    /// Returns the number of unexpired entries in the resolver's cache per registrable domain,
+    ///  see `ResolverFuture::cache_domains`
+    #[cfg(feature = "psl")]
+    pub fn cache_domains(&self, psl: &PublicSuffixList) -> io::Result<Vec<(Name, usize)>> {
+        self.resolver_future.borrow().cache_domains(psl)
+    }
+
+    /// Returns a snapshot of the statistics of each of the configured name servers
+    pub fn name_server_statistics(&self) -> Vec<NameServerStatistics> {
+        self.resolver_future.borrow().name_server_statistics()
+    }
+
+    /// Writes the statistics of the name servers to the file, see
+    ///  `ResolverFuture::save_name_server_statistics`
+    pub fn save_name_server_statistics<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.resolver_future.borrow().save_name_server_statistics(path)
+    }
+
+    /// Loads the statistics of the name servers from the file, see
+    ///  `ResolverFuture::load_name_server_statistics`
+    pub fn load_name_server_statistics<P: AsRef<Path>>(&self, path: P) -> io::Result<usize> {
+        self.resolver_future.borrow().load_name_server_statistics(path)
+    }
+
+    /// Removes all entries from the resolver's cache
+    pub fn clear_cache(&self) -> io::Result<()> {
+        self.resolver_future.borrow().clear_cache()
+    }
+
+    /// Removes the cached result for the query, returns true if there was an entry
+    pub fn flush_query(&self, query: &Query) -> io::Result<bool> {
+        self.resolver_future.borrow().flush_query(query)
+    }
+
+    /// Removes all cached results for the name and every name below it, returns the number removed
+    pub fn flush_subtree(&self, name: &Name) -> io::Result<usize> {
+        self.resolver_future.borrow().flush_subtree(name)
+    }
+
     /// Generic lookup for any RecordType
     ///
     /// *WARNING* This interface may change in the future
@@ -109,7 +201,7 @@ impl Resolver {
 
     /// Performs a dual-stack DNS lookup for the IP for the given hostname.
     ///
-    /// See the configuration and options parameters for controlling the way in which A(Ipv4) and AAAA(Ipv6) lookups will be performed. For the least expensive query a fully-qualified-domain-name, FQDN, which ends in a final `.`, e.g. `www.example.com.`, will only issue one query. Anything else will always incur the cost of querying the `ResolverConfig::domain` and `ResolverConfig::search`.
+    /// See the configuration and options parameters for controlling the way in which A(Ipv4) and AAAA(Ipv6) lookups will be performed. For the least expensive query a fully-qualified-domain-name, FQDN, which ends in a final `.`, e.g. `www.example.com.`, will only issue one query. Anything else will incur the cost of querying the `ResolverConfig::domain` and `ResolverConfig::search`, until a name from these has answered, which is then tried first.
     ///
     /// # Arguments
     ///
@@ -122,6 +214,50 @@ impl Resolver {
         )
     }
 
+    /// Looks up the addresses of the host, with the port, for connecting to, e.g.
+    ///  `example.com:443`, see `ResolverFuture::lookup_socket_addrs`
+    ///
+    /// The result can be handed to `std::net::TcpStream::connect`.
+    ///
+    /// # Arguments
+    ///
+    /// * `host_port` - the hostname and port separated by a `:`, an IPv6 address must be in
+    ///                 brackets
+    pub fn lookup_socket_addrs(&self, host_port: &str) -> io::Result<LookupSocketAddrs> {
+        self.io_loop.borrow_mut().run(
+            self.resolver_future
+                .borrow()
+                .lookup_socket_addrs(host_port),
+        )
+    }
+
+    /// Looks up the addresses of the host, with the port, see `lookup_socket_addrs`
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - string hostname, or IP address
+    /// * `port` - the port of each of the addresses
+    pub fn lookup_host_port(&self, host: &str, port: u16) -> io::Result<LookupSocketAddrs> {
+        self.io_loop.borrow_mut().run(
+            self.resolver_future
+                .borrow()
+                .lookup_host_port(host, port),
+        )
+    }
+
+    /// Looks up the URIs of the E.164 telephone number in ENUM, see
+    ///  `ResolverFuture::lookup_enum`
+    ///
+    /// # Arguments
+    ///
+    /// * `number` - the number with its country code, e.g. `+46-8-9761234`
+    #[cfg(feature = "e164")]
+    pub fn lookup_enum(&self, number: &str) -> io::Result<Vec<EnumUri>> {
+        self.io_loop.borrow_mut().run(
+            self.resolver_future.borrow().lookup_enum(number),
+        )
+    }
+
     /// Performs a DNS lookup for an SRV record for the specified service type and protocol at the given name.
     ///
     /// This is a convenience method over `lookup_srv`, it combines the service, protocol and name into a single name: `_service._protocol.name`.
@@ -152,14 +288,79 @@ impl Resolver {
     lookup_fn!(mx_lookup, lookup::MxLookup);
     lookup_fn!(srv_lookup, lookup::SrvLookup);
     lookup_fn!(txt_lookup, lookup::TxtLookup);
+    lookup_fn!(soa_lookup, lookup::SoaLookup);
+    lookup_fn!(ns_lookup, lookup::NsLookup);
+    lookup_fn!(sshfp_lookup, lookup::SshfpLookup);
+    lookup_fn!(svcb_lookup, lookup::SvcbLookup);
+    lookup_fn!(https_lookup, lookup::HttpsLookup);
+    lookup_fn!(naptr_lookup, lookup::NaptrLookup);
 }
 
 #[cfg(test)]
 mod tests {
     use std::net::*;
+    use std::str::FromStr;
 
+    use trust_dns::rr::RData;
+
+    use config::LookupIpStrategy;
     use super::*;
 
+    #[test]
+    fn test_lookup_hosts() {
+        // no name servers, only the hosts can answer
+        let resolver = Resolver::new(ResolverConfig::new(), ResolverOpts::default()).unwrap();
+        let mut hosts = Hosts::default();
+        hosts.insert(
+            Name::from_str("static.example.com.").unwrap(),
+            RData::A(Ipv4Addr::new(10, 0, 0, 1)),
+        );
+        resolver.set_hosts(Some(hosts));
+
+        let response = resolver.lookup_ip("static.example.com.").unwrap();
+        assert_eq!(
+            response.iter().collect::<Vec<_>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]
+        );
+        assert!(resolver.cache_stats().is_ok());
+    }
+
+    #[test]
+    fn test_lookup_socket_addrs() {
+        // no name servers, only the hosts can answer
+        let mut options = ResolverOpts::default();
+        options.ip_strategy = LookupIpStrategy::Ipv6thenIpv4;
+        let resolver = Resolver::new(ResolverConfig::new(), options).unwrap();
+        let mut hosts = Hosts::default();
+        let name = Name::from_str("static.example.com.").unwrap();
+        hosts.insert(name.clone(), RData::A(Ipv4Addr::new(10, 0, 0, 1)));
+        hosts.insert(name, RData::AAAA(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)));
+        resolver.set_hosts(Some(hosts));
+
+        // the preferred family first
+        let addrs = resolver
+            .lookup_socket_addrs("static.example.com.:443")
+            .unwrap();
+        assert_eq!(
+            addrs.to_socket_addrs().unwrap().collect::<Vec<_>>(),
+            vec![
+                SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)), 443),
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 443),
+            ]
+        );
+
+        assert_eq!(
+            resolver
+                .lookup_socket_addrs("[::1]:53")
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), 53)]
+        );
+        assert!(resolver.lookup_socket_addrs("static.example.com.").is_err());
+        assert!(resolver.lookup_socket_addrs("::1:53").is_err());
+    }
+
     #[test]
     fn test_lookup() {
         let resolver = Resolver::new(ResolverConfig::default(), ResolverOpts::default()).unwrap();
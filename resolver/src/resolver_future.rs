@@ -7,29 +7,47 @@
 
 //! Structs for creating and using a ResolverFuture
 use std::io;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
+use futures::{future, Future};
+#[cfg(feature = "e164")]
+use futures::future::Loop;
 use tokio_core::reactor::Handle;
-use trust_dns::client::{BasicClientHandle, RetryClientHandle, SecureClientHandle};
+use trust_dns::client::{BasicClientHandle, ClientHandle, RetryClientHandle};
+#[cfg(feature = "dnssec")]
+use trust_dns::client::SecureClientHandle;
+use trust_dns::op::{Message, Query, QueryBuilder, ResponseCode};
 use trust_dns::rr::{Name, RecordType};
 
-use config::{ResolverConfig, ResolverOpts};
-use lookup_state::CachingClient;
-use name_server_pool::{NameServerPool, StandardConnection};
-use lookup_ip::{InnerLookupIpFuture, LookupIpFuture};
+use config::{NameServerConfig, ResolverConfig, ResolverOpts};
+#[cfg(feature = "e164")]
+use e164::{self, EnumUri, LookupEnumFuture};
+use lookup_state::{CacheEntry, CacheStats, CachingClient, ClientSubnet};
+#[cfg(feature = "psl")]
+use psl::PublicSuffixList;
+use name_server_pool::{NameServer, NameServerPool, StandardConnection};
+use name_server_stats::{self, NameServerStatistics};
+use observer::LookupObserver;
+use lookup_ip::{self, InnerLookupIpFuture, LookupIpFuture, LookupSocketAddrs,
+                LookupSocketAddrsFuture};
 use lookup;
-use lookup::{InnerLookupFuture, LookupEither, LookupFuture};
+use lookup::{InnerLookupFuture, Lookup, LookupEither, LookupFuture, NaptrLookup};
 use system_conf;
-use hosts::Hosts;
+use hosts::{self, Hosts, SharedHosts};
+use search::{self, SearchCache, SearchFuture};
 
 /// A Resolver for DNS records.
 pub struct ResolverFuture {
     config: ResolverConfig,
     options: ResolverOpts,
     client_cache: CachingClient<LookupEither<BasicClientHandle, StandardConnection>>,
-    hosts: Option<Hosts>,
+    pool: NameServerPool<BasicClientHandle, StandardConnection>,
+    hosts: Option<SharedHosts>,
+    search_cache: SearchCache,
+    reactor: Handle,
 }
 
 macro_rules! lookup_fn {
@@ -73,25 +91,50 @@ impl ResolverFuture {
             &options,
             reactor,
         );
-        let either;
         let client = RetryClientHandle::new(pool.clone(), options.attempts);
-        if options.validate {
-            either = LookupEither::Secure(SecureClientHandle::new(client));
+        #[cfg(feature = "dnssec")]
+        let either = if options.validate {
+            LookupEither::Secure(SecureClientHandle::with_trust_anchor(
+                client,
+                config.trust_anchor().clone(),
+            ))
         } else {
-            either = LookupEither::Retry(client);
-        }
+            LookupEither::Retry(client)
+        };
+        #[cfg(not(feature = "dnssec"))]
+        let either = LookupEither::Retry(client);
 
         let hosts = if options.use_hosts_file {
-            Some(Hosts::new())
+            Some(hosts::watch_system_hosts(reactor))
         } else {
-            None 
+            None
         };
 
+        // answers scoped to a subnet are for that of the ECS option sent to the NameServers
+        let client_subnet = config
+            .name_servers()
+            .iter()
+            .flat_map(|name_server| name_server.edns_options.iter())
+            .filter_map(ClientSubnet::from_option)
+            .next()
+            .or(options.client_subnet);
+
+        let mut client_cache = CachingClient::new(options.cache_size, either);
+        client_cache.set_max_cname_chain(options.max_cname_chain);
+        client_cache.set_client_subnet(client_subnet);
+        client_cache.set_answer_order(options.answer_order);
+        client_cache.set_keep_response(options.keep_response);
+        client_cache.set_answer_sources(pool.answer_sources());
+        client_cache.set_observers(pool.observers());
+
         ResolverFuture {
             config,
             options,
-            client_cache: CachingClient::new(options.cache_size, either),
+            client_cache,
+            pool,
             hosts: hosts,
+            search_cache: SearchCache::new(options.cache_size),
+            reactor: reactor.clone(),
         }
     }
 
@@ -104,39 +147,159 @@ impl ResolverFuture {
         Ok(Self::new(config, options, reactor))
     }
 
-    fn push_name(name: Name, names: &mut Vec<Name>) {
-        if !names.contains(&name) {
-            names.push(name);
-        }
+    /// Replaces the hosts consulted by `lookup_ip` before the cache and name servers
+    ///
+    /// By default these are read from the system hosts file, which is reloaded when modified,
+    ///  see `ResolverOpts::use_hosts_file`. The supplied hosts are static, they are not reloaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `hosts` - static hosts to use for all lookups, None to disable hosts lookups
+    pub fn set_hosts(&mut self, hosts: Option<Hosts>) {
+        self.hosts = hosts.map(|hosts| Arc::new(RwLock::new(Arc::new(hosts))));
     }
 
-    fn build_names(&self, name: Name) -> Vec<Name> {
-        // if it's fully qualified, we can short circuit the lookup logic
-        if name.is_fqdn() {
-            vec![name]
-        } else {
-            // Otherwise we have to build the search list
-            // Note: the vec is built in reverse order of precedence, for stack semantics
-            let mut names =
-                Vec::<Name>::with_capacity(1 /*FQDN*/ + 1 /*DOMAIN*/ + self.config.search().len());
-
-            for search in self.config.search().iter().rev() {
-                let name_search = name.clone().append_domain(search);
-                Self::push_name(name_search, &mut names);
-            }
+    /// Sets the observer which receives the events of each lookup, from its start through the
+    ///  cache and the attempts at the name servers to its outcome, see `LookupEvent`
+    ///
+    /// This applies to the lookups started after it is set, lookups answered from the hosts are
+    ///  not observed.
+    ///
+    /// # Arguments
+    ///
+    /// * `observer` - the observer of all lookups of the resolver, None to stop observing them
+    pub fn set_lookup_observer(&self, observer: Option<Arc<LookupObserver>>) {
+        self.pool.observers().set_observer(observer);
+    }
 
-            let domain = name.clone().append_domain(&self.config.domain());
-            Self::push_name(domain, &mut names);
+    /// Sends the query to the name server, bypassing the configured name servers and the cache
+    ///
+    /// This is for diagnostics, e.g. to check the answer of a particular authoritative server. The
+    ///  query is attempted `ResolverOpts::attempts` times, each with `ResolverOpts::timeout`. The
+    ///  response is returned as is, it is not validated with DNSSec even if `validate` is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the name server to query, it need not be one of the configured servers
+    /// * `query` - the query to send
+    /// * `cache_response` - if true, the answers in a NoError response are added to the cache,
+    ///   this fails if `validate` is set, as only validated records are cached
+    pub fn query_with_server(
+        &self,
+        name_server: NameServerConfig,
+        query: Query,
+        cache_response: bool,
+    ) -> Box<Future<Item = Message, Error = io::Error>> {
+        let name_server =
+            NameServer::<_, StandardConnection>::new(name_server, self.options, &self.reactor);
+        let mut client = RetryClientHandle::new(name_server, self.options.attempts);
+        let client_cache = self.client_cache.clone();
+
+        Box::new(
+            client
+                .lookup(query.clone())
+                .map_err(io::Error::from)
+                .and_then(move |response| {
+                    if cache_response && response.response_code() == ResponseCode::NoError {
+                        client_cache.cache_response(query, &response).map(|()| response)
+                    } else {
+                        Ok(response)
+                    }
+                }),
+        )
+    }
 
-            // this is the direct name lookup
-            // number of dots will always be one less than the number of labels
-            if name.num_labels() as usize > self.options.ndots {
-                // adding the name as though it's an FQDN for lookup
-                names.push(name.clone());
-            }
+    /// Returns the hit, miss, and eviction counters of the resolver's cache
+    pub fn cache_stats(&self) -> io::Result<CacheStats> {
+        self.client_cache.cache_stats()
+    }
+
+    /// Returns a snapshot of all the unexpired entries in the resolver's cache
+    pub fn cache_entries(&self) -> io::Result<Vec<CacheEntry>> {
+        self.client_cache.cache_entries()
+    }
+
+    /// Returns the number of unexpired entries in the resolver's cache per registrable domain,
+    ///  most entries first
+    #[cfg(feature = "psl")]
+    pub fn cache_domains(&self, psl: &PublicSuffixList) -> io::Result<Vec<(Name, usize)>> {
+        self.client_cache.cache_domains(psl)
+    }
+
+    /// Returns a snapshot of the statistics of each of the configured name servers, their round
+    ///  trip times, failures and current timeouts, e.g. for metrics
+    pub fn name_server_statistics(&self) -> Vec<NameServerStatistics> {
+        self.pool.statistics()
+    }
+
+    /// Writes the statistics of the name servers to the file, to be loaded after a restart with
+    ///  `load_name_server_statistics`
+    pub fn save_name_server_statistics<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        name_server_stats::write_statistics(path, &self.pool.statistics())
+    }
 
-            names
+    /// Loads the statistics saved with `save_name_server_statistics`, so that the round trip
+    ///  times of the name servers are known before the first lookup. Returns the number of
+    ///  configured name servers which were in the file, the others are left as they are
+    pub fn load_name_server_statistics<P: AsRef<Path>>(&self, path: P) -> io::Result<usize> {
+        let statistics = name_server_stats::read_statistics(path)?;
+        Ok(self.pool.restore_statistics(&statistics))
+    }
+
+    /// Removes all entries from the resolver's cache, including the names which answered in
+    ///  searches of the domain and search list
+    pub fn clear_cache(&self) -> io::Result<()> {
+        self.search_cache.clear();
+        self.client_cache.clear_cache()
+    }
+
+    /// Removes the cached result for the query, returns true if there was an entry
+    pub fn flush_query(&self, query: &Query) -> io::Result<bool> {
+        self.client_cache.flush_query(query)
+    }
+
+    /// Removes all cached results for the name and every name below it, returns the number removed
+    pub fn flush_subtree(&self, name: &Name) -> io::Result<usize> {
+        self.client_cache.flush_subtree(name)
+    }
+
+    /// Looks up the name, or if it is not fully qualified, each of the names from the domain and
+    ///  search list until one has records, see `ResolverOpts::ndots`
+    ///
+    /// The name which answers is remembered, and tried first in later lookups of the name.
+    fn search<L>(&self, name: Name, lookup: L) -> Box<Future<Item = Lookup, Error = io::Error>>
+    where
+        L: FnMut(Name) -> Box<Future<Item = Lookup, Error = io::Error>> + 'static,
+    {
+        if self.options.require_fqdn && !name.is_fqdn() {
+            return Box::new(future::err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "name is not fully qualified: {}",
+                    name.to_string().trim_right_matches('.')
+                ),
+            )));
         }
+
+        let mut names = search::search_names(
+            &name,
+            self.config.domain(),
+            self.config.search(),
+            self.options.ndots,
+        );
+        self.search_cache.prefer_answered(&name, &mut names);
+
+        let search_cache = self.search_cache.clone();
+        Box::new(
+            SearchFuture::new(names, self.options.search_concurrently, lookup).map(
+                move |(answered, lookup)| {
+                    if !name.is_fqdn() && !lookup.is_empty() {
+                        search_cache.insert(name, answered);
+                    }
+                    lookup
+                },
+            ),
+        )
     }
 
     /// Generic lookup for any RecordType
@@ -159,17 +322,29 @@ impl ResolverFuture {
             }
         };
 
+        // e.g. OPT, which is not a queryable type, the name is left as is for the search
+        if let Err(err) = QueryBuilder::new(name.clone())
+            .set_query_type(record_type)
+            .build()
+        {
+            return InnerLookupFuture::error(self.client_cache.clone(), err);
+        }
+
         self.inner_lookup(name, record_type)
     }
 
     fn inner_lookup(&self, name: Name, record_type: RecordType) -> LookupFuture {
-        let names = self.build_names(name);
-        LookupFuture::lookup(names, record_type, self.client_cache.clone())
+        let mut client_cache = self.client_cache.clone();
+        let future = self.search(name, move |name| {
+            client_cache.lookup(Query::query(name, record_type))
+        });
+
+        LookupFuture::from_future(self.client_cache.clone(), record_type, future)
     }
 
     /// Performs a dual-stack DNS lookup for the IP for the given hostname.
     ///
-    /// See the configuration and options parameters for controlling the way in which A(Ipv4) and AAAA(Ipv6) lookups will be performed. For the least expensive query a fully-qualified-domain-name, FQDN, which ends in a final `.`, e.g. `www.example.com.`, will only issue one query. Anything else will always incur the cost of querying the `ResolverConfig::domain` and `ResolverConfig::search`.
+    /// See the configuration and options parameters for controlling the way in which A(Ipv4) and AAAA(Ipv6) lookups will be performed. For the least expensive query a fully-qualified-domain-name, FQDN, which ends in a final `.`, e.g. `www.example.com.`, will only issue one query. Anything else will incur the cost of querying the `ResolverConfig::domain` and `ResolverConfig::search`, until a name from these has answered, which is then tried first.
     ///
     /// # Arguments
     /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
@@ -181,13 +356,52 @@ impl ResolverFuture {
             }
         };
 
-        let names = self.build_names(name);
-        let hosts = if let Some(ref hosts) = self.hosts {
-            Some(Arc::new(hosts.clone()))
-        } else {
-            None
-        };
-        LookupIpFuture::lookup(names, self.options.ip_strategy, self.client_cache.clone(), hosts)
+        let hosts = self.hosts.as_ref().and_then(|hosts| {
+            hosts.read().ok().map(|hosts| Arc::clone(&*hosts))
+        });
+        let strategy = self.options.ip_strategy;
+        let client_cache = self.client_cache.clone();
+        let future = self.search(name, move |name| {
+            lookup_ip::strategic_lookup(name, strategy, client_cache.clone(), hosts.clone())
+        });
+
+        LookupIpFuture::from_future(self.client_cache.clone(), strategy, future)
+    }
+
+    /// Looks up the addresses of the host, with the port, for connecting to, e.g.
+    ///  `example.com:443`, see `LookupSocketAddrs`
+    ///
+    /// The addresses are ordered per the `LookupIpStrategy`, see `LookupSocketAddrs::new`. An IP
+    ///  address, e.g. `[::1]:53`, is returned as is, without a lookup.
+    ///
+    /// # Arguments
+    ///
+    /// * `host_port` - the hostname and port separated by a `:`, an IPv6 address must be in
+    ///                 brackets
+    pub fn lookup_socket_addrs(&self, host_port: &str) -> LookupSocketAddrsFuture {
+        match lookup_ip::split_host_port(host_port) {
+            Ok((host, port)) => self.lookup_host_port(host, port),
+            Err(e) => LookupSocketAddrsFuture::new(Box::new(future::err(e))),
+        }
+    }
+
+    /// Looks up the addresses of the host, with the port, as `lookup_socket_addrs` does for
+    ///  `host:port`
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - string hostname, or IP address
+    /// * `port` - the port of each of the addresses
+    pub fn lookup_host_port(&self, host: &str, port: u16) -> LookupSocketAddrsFuture {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            let addrs = LookupSocketAddrs::from(vec![SocketAddr::new(ip, port)]);
+            return LookupSocketAddrsFuture::new(Box::new(future::ok(addrs)));
+        }
+
+        let strategy = self.options.ip_strategy;
+        LookupSocketAddrsFuture::new(Box::new(self.lookup_ip(host).map(move |lookup| {
+            LookupSocketAddrs::new(&lookup, port, strategy)
+        })))
     }
 
     /// Performs a DNS lookup for an SRV record for the specified service type and protocol at the given name.
@@ -209,6 +423,54 @@ impl ResolverFuture {
         self.srv_lookup(&name)
     }
 
+    /// Looks up the URIs of the E.164 telephone number in ENUM, the NAPTR records of the number
+    ///  under `e164.arpa.`, RFC 6116
+    ///
+    /// The non-terminal rules are followed to the terminal ones, the URIs are those of the first
+    ///  order with matching rules, lowest preference first. No URIs is not an error, the number
+    ///  then has no URIs for the services of ENUM.
+    ///
+    /// # Arguments
+    ///
+    /// * `number` - the number with its country code, e.g. `+46-8-9761234`, see
+    ///              `e164::application_unique_string`
+    #[cfg(feature = "e164")]
+    pub fn lookup_enum(&self, number: &str) -> LookupEnumFuture {
+        let domain = Name::from_str(e164::E164_ARPA).expect("e164.arpa. is a valid name");
+        let (name, aus) = match e164::enum_name(number, &domain).and_then(|name| {
+            e164::application_unique_string(number).map(|aus| (name, aus))
+        }) {
+            Ok(name_aus) => name_aus,
+            Err(e) => return LookupEnumFuture::new(Box::new(future::err(e))),
+        };
+
+        let client_cache = self.client_cache.clone();
+        let future = future::loop_fn((name, 0), move |(name, followed)| {
+            if followed > e164::MAX_NON_TERMINAL {
+                return Box::new(future::err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("too many non-terminal NAPTR rules at: {}", name),
+                ))) as Box<Future<Item = Loop<Vec<EnumUri>, (Name, usize)>, Error = io::Error>>;
+            }
+
+            let aus = aus.clone();
+            let mut client_cache = client_cache.clone();
+            Box::new(
+                client_cache
+                    .lookup(Query::query(name, RecordType::NAPTR))
+                    .map(move |lookup| {
+                        let naptrs = NaptrLookup::from(lookup);
+                        match e164::process(&naptrs.ordered(), &aus) {
+                            e164::Step::Uris(uris) => Loop::Break(uris),
+                            e164::Step::Next(name) => Loop::Continue((name, followed + 1)),
+                        }
+                    }),
+            )
+        });
+
+        LookupEnumFuture::new(Box::new(future))
+    }
+
     lookup_fn!(
         reverse_lookup,
         lookup::ReverseLookupFuture,
@@ -220,6 +482,12 @@ impl ResolverFuture {
     lookup_fn!(mx_lookup, lookup::MxLookupFuture, RecordType::MX);
     lookup_fn!(srv_lookup, lookup::SrvLookupFuture, RecordType::SRV);
     lookup_fn!(txt_lookup, lookup::TxtLookupFuture, RecordType::TXT);
+    lookup_fn!(soa_lookup, lookup::SoaLookupFuture, RecordType::SOA);
+    lookup_fn!(ns_lookup, lookup::NsLookupFuture, RecordType::NS);
+    lookup_fn!(sshfp_lookup, lookup::SshfpLookupFuture, RecordType::SSHFP);
+    lookup_fn!(svcb_lookup, lookup::SvcbLookupFuture, RecordType::SVCB);
+    lookup_fn!(https_lookup, lookup::HttpsLookupFuture, RecordType::HTTPS);
+    lookup_fn!(naptr_lookup, lookup::NaptrLookupFuture, RecordType::NAPTR);
 }
 
 #[cfg(test)]
@@ -229,8 +497,9 @@ mod tests {
     use std::net::*;
 
     use self::tokio_core::reactor::Core;
+    use trust_dns::rr::RData;
 
-    use config::{NameServerConfig, LookupIpStrategy};
+    use config::{NameServerConfig, NameCompression, LookupIpStrategy, Protocol, TransportStrategy};
 
     use super::*;
 
@@ -271,6 +540,7 @@ mod tests {
 
     #[test]
     #[ignore] // these appear to not work on travis
+    #[cfg(feature = "dnssec")]
     fn test_sec_lookup() {
         let mut io_loop = Core::new().unwrap();
         let resolver = ResolverFuture::new(
@@ -310,6 +580,7 @@ mod tests {
 
     #[test]
     #[ignore] // these appear to not work on travis
+    #[cfg(feature = "dnssec")]
     fn test_sec_lookup_fails() {
         let mut io_loop = Core::new().unwrap();
         let resolver = ResolverFuture::new(
@@ -389,6 +660,117 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_set_hosts() {
+        let mut io_loop = Core::new().unwrap();
+        let mut resolver = ResolverFuture::new(
+            ResolverConfig::default(),
+            ResolverOpts {
+                ip_strategy: LookupIpStrategy::Ipv4Only,
+                ..ResolverOpts::default()
+            },
+            &io_loop.handle(),
+        );
+
+        let mut hosts = Hosts::default();
+        hosts.insert(
+            Name::from_str("override.example.com").unwrap(),
+            RData::A(Ipv4Addr::new(10, 0, 0, 1)),
+        );
+        hosts.insert(
+            Name::from_str("override.example.com").unwrap(),
+            RData::AAAA(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)),
+        );
+        resolver.set_hosts(Some(hosts));
+
+        // answered from the hosts, only the ipv4 address for the strategy
+        let response = io_loop
+            .run(resolver.lookup_ip("override.example.com."))
+            .expect("failed to run lookup");
+
+        assert_eq!(
+            response.iter().collect::<Vec<IpAddr>>(),
+            vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]
+        );
+    }
+
+    #[test]
+    fn test_query_with_server() {
+        let mut io_loop = Core::new().unwrap();
+        let resolver = ResolverFuture::new(
+            ResolverConfig::default(),
+            ResolverOpts::default(),
+            &io_loop.handle(),
+        );
+
+        let name_server = NameServerConfig {
+            socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
+            protocol: Protocol::Udp,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
+        };
+        let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+        let response = io_loop
+            .run(resolver.query_with_server(name_server.clone(), query.clone(), false))
+            .expect("failed to run query");
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(!response.answers().is_empty());
+        assert!(resolver.cache_entries().unwrap().is_empty());
+
+        io_loop
+            .run(resolver.query_with_server(name_server, query.clone(), true))
+            .expect("failed to run query");
+        let entries = resolver.cache_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].query, query);
+    }
+
+    #[test]
+    fn test_require_fqdn() {
+        let mut io_loop = Core::new().unwrap();
+        let resolver = ResolverFuture::new(
+            ResolverConfig::default(),
+            ResolverOpts {
+                require_fqdn: true,
+                ..ResolverOpts::default()
+            },
+            &io_loop.handle(),
+        );
+
+        // relative names are rejected before anything is sent
+        let error = io_loop.run(resolver.lookup_ip("www.example.com")).expect_err(
+            "relative name should be rejected",
+        );
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(
+            io_loop
+                .run(resolver.lookup("example.com", RecordType::MX))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_lookup_invalid_query() {
+        let mut io_loop = Core::new().unwrap();
+        let resolver = ResolverFuture::new(
+            ResolverConfig::default(),
+            ResolverOpts::default(),
+            &io_loop.handle(),
+        );
+
+        // OPT is rejected before anything is sent
+        assert!(
+            io_loop
+                .run(resolver.lookup("www.example.com.", RecordType::OPT))
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_fqdn() {
         let domain = Name::from_str("incorrect.example.com.").unwrap();
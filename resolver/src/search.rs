@@ -0,0 +1,318 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Search of the candidate names for a name which is not fully qualified, as with `search` and
+//!  `ndots` in resolv.conf
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use futures::{Async, Future, Poll};
+use lru_cache::LruCache;
+
+use trust_dns::rr::Name;
+
+use lookup::Lookup;
+
+/// Builds the names to try for the name, in the order in which they are to be tried
+///
+/// A fully qualified name is only tried as is. Otherwise a name with at least `ndots` dots is
+///  tried as is first, and then with the domain and each of the search domains appended. A name
+///  with fewer dots is tried with the domain and search domains first, and as is last.
+pub(crate) fn search_names(name: &Name, domain: &Name, search: &[Name], ndots: usize) -> Vec<Name> {
+    // if it's fully qualified, we can short circuit the lookup logic
+    if name.is_fqdn() {
+        return vec![name.clone()];
+    }
+
+    let mut names = Vec::<Name>::with_capacity(1 /*FQDN*/ + 1 /*DOMAIN*/ + search.len());
+
+    // number of dots will always be one less than the number of labels
+    let dots = (name.num_labels() as usize).saturating_sub(1);
+    if dots >= ndots {
+        names.push(name.clone());
+    }
+
+    push_name(name.clone().append_domain(domain), &mut names);
+    for search in search {
+        push_name(name.clone().append_domain(search), &mut names);
+    }
+
+    if dots < ndots {
+        push_name(name.clone(), &mut names);
+    }
+
+    names
+}
+
+fn push_name(name: Name, names: &mut Vec<Name>) {
+    if !names.contains(&name) {
+        names.push(name);
+    }
+}
+
+/// The candidate names which answered for names which are not fully qualified
+///
+/// Later lookups of the same name try the name which answered first, rather than walking the
+///  search list again.
+#[derive(Clone)]
+pub(crate) struct SearchCache(Arc<Mutex<LruCache<Name, Name>>>);
+
+impl SearchCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        SearchCache(Arc::new(Mutex::new(LruCache::new(capacity))))
+    }
+
+    /// Moves the name which last answered for `name` to the front of the names
+    pub(crate) fn prefer_answered(&self, name: &Name, names: &mut Vec<Name>) {
+        let answered = match self.0.lock() {
+            Ok(mut cache) => cache.get_mut(name).cloned(),
+            Err(_) => None,
+        };
+
+        // the search list could have changed since, only a current candidate is moved
+        if let Some(answered) = answered {
+            if let Some(index) = names.iter().position(|name| *name == answered) {
+                let answered = names.remove(index);
+                names.insert(0, answered);
+            }
+        }
+    }
+
+    /// Records the name which answered for `name`
+    pub(crate) fn insert(&self, name: Name, answered: Name) {
+        if let Ok(mut cache) = self.0.lock() {
+            cache.insert(name, answered);
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        if let Ok(mut cache) = self.0.lock() {
+            cache.clear();
+        }
+    }
+}
+
+struct Candidate {
+    name: Name,
+    future: Option<Box<Future<Item = Lookup, Error = io::Error>>>,
+    result: Option<Result<Lookup, io::Error>>,
+}
+
+/// Looks up each of the names, resolving to the first non-empty result in the order of the names
+///  and the name which it was for
+///
+/// Sequentially a name is only looked up once the lookups of the names before it failed or were
+///  empty. Concurrently all the names are looked up at once, but a result is still only used once
+///  all the names before it have failed, so the result is the same either way. If all fail, the
+///  result is that of the last name.
+#[must_use = "futures do nothing unless polled"]
+pub(crate) struct SearchFuture<F> {
+    candidates: VecDeque<Candidate>,
+    concurrent: bool,
+    lookup: F,
+}
+
+impl<F> SearchFuture<F>
+where
+    F: FnMut(Name) -> Box<Future<Item = Lookup, Error = io::Error>>,
+{
+    /// # Arguments
+    ///
+    /// * `names` - the names to look up, in order of preference, see `search_names`
+    /// * `concurrent` - if true all the names are looked up at once
+    /// * `lookup` - starts the lookup of a name
+    pub(crate) fn new(names: Vec<Name>, concurrent: bool, lookup: F) -> Self {
+        SearchFuture {
+            candidates: names
+                .into_iter()
+                .map(|name| {
+                    Candidate {
+                        name,
+                        future: None,
+                        result: None,
+                    }
+                })
+                .collect(),
+            concurrent,
+            lookup,
+        }
+    }
+}
+
+impl<F> Future for SearchFuture<F>
+where
+    F: FnMut(Name) -> Box<Future<Item = Lookup, Error = io::Error>>,
+{
+    type Item = (Name, Lookup);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            // start the next lookup, or all of them
+            for candidate in self.candidates.iter_mut() {
+                if candidate.future.is_none() && candidate.result.is_none() {
+                    candidate.future = Some((self.lookup)(candidate.name.clone()));
+                }
+
+                if !self.concurrent {
+                    break;
+                }
+            }
+
+            for candidate in self.candidates.iter_mut() {
+                let result = match candidate.future {
+                    Some(ref mut future) => {
+                        match future.poll() {
+                            Ok(Async::NotReady) => continue,
+                            Ok(Async::Ready(lookup)) => Ok(lookup),
+                            Err(e) => Err(e),
+                        }
+                    }
+                    None => continue,
+                };
+
+                candidate.future = None;
+                candidate.result = Some(result);
+            }
+
+            // the most preferred name decides, the others wait on it
+            let decided = match self.candidates.front() {
+                Some(candidate) => candidate.result.is_some(),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "no names to search",
+                    ))
+                }
+            };
+            if !decided {
+                return Ok(Async::NotReady);
+            }
+
+            let candidate = self.candidates.pop_front().expect("front was just checked");
+            let last = self.candidates.is_empty();
+            match candidate.result.expect("result was just checked") {
+                Ok(lookup) => {
+                    if !lookup.is_empty() || last {
+                        return Ok(Async::Ready((candidate.name, lookup)));
+                    }
+                }
+                Err(e) => {
+                    if last {
+                        return Err(e);
+                    }
+                    debug!("search of {} failed: {}", candidate.name, e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use futures::future;
+
+    use trust_dns::rr::RData;
+
+    use super::*;
+
+    /// Only the answering name has any records, all others fail
+    fn answer_only(answering: &Name, name: Name) -> Box<Future<Item = Lookup, Error = io::Error>> {
+        if name == *answering {
+            Box::new(future::ok(Lookup::new(
+                Arc::new(vec![RData::A(Ipv4Addr::new(127, 0, 0, 1))]),
+            )))
+        } else {
+            Box::new(future::err(io::Error::new(io::ErrorKind::Other, "nxdomain")))
+        }
+    }
+
+    #[test]
+    fn test_search_names() {
+        let domain = Name::from_str("example.com.").unwrap();
+        let search = vec![Name::from_str("example.net.").unwrap()];
+
+        // fewer than ndots, the search list is tried first
+        assert_eq!(
+            search_names(&Name::from_str("www").unwrap(), &domain, &search, 1),
+            vec![
+                Name::from_str("www.example.com.").unwrap(),
+                Name::from_str("www.example.net.").unwrap(),
+                Name::from_str("www").unwrap(),
+            ]
+        );
+
+        // at least ndots, the name is tried as is first
+        assert_eq!(
+            search_names(&Name::from_str("www.test").unwrap(), &domain, &search, 1),
+            vec![
+                Name::from_str("www.test").unwrap(),
+                Name::from_str("www.test.example.com.").unwrap(),
+                Name::from_str("www.test.example.net.").unwrap(),
+            ]
+        );
+
+        // fully qualified names are never searched
+        assert_eq!(
+            search_names(&Name::from_str("www.test.").unwrap(), &domain, &search, 1),
+            vec![Name::from_str("www.test.").unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_search_future() {
+        let names = vec![
+            Name::from_str("www.example.com.").unwrap(),
+            Name::from_str("www.example.net.").unwrap(),
+            Name::from_str("www").unwrap(),
+        ];
+
+        let answering = Name::from_str("www.example.net.").unwrap();
+        let unknown = Name::from_str("www.example.org.").unwrap();
+
+        // the result is the same whether the names are looked up one at a time or all at once
+        for concurrent in vec![false, true] {
+            let (name, lookup) =
+                SearchFuture::new(names.clone(), concurrent, |name| answer_only(&answering, name))
+                    .wait()
+                    .unwrap();
+            assert_eq!(name, answering);
+            assert!(!lookup.is_empty());
+
+            assert!(
+                SearchFuture::new(names.clone(), concurrent, |name| answer_only(&unknown, name))
+                    .wait()
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_cache() {
+        let search_cache = SearchCache::new(1);
+        let name = Name::from_str("www").unwrap();
+        let mut names = vec![
+            Name::from_str("www.example.com.").unwrap(),
+            Name::from_str("www.example.net.").unwrap(),
+        ];
+
+        search_cache.insert(name.clone(), names[1].clone());
+        search_cache.prefer_answered(&name, &mut names);
+        assert_eq!(names[0], Name::from_str("www.example.net.").unwrap());
+        assert_eq!(names.len(), 2);
+
+        search_cache.clear();
+        names.reverse();
+        search_cache.prefer_answered(&name, &mut names);
+        assert_eq!(names[0], Name::from_str("www.example.com.").unwrap());
+    }
+}
@@ -18,10 +18,14 @@ mod resolv_conf_ast;
 #[cfg(all(target_os = "windows", target_pointer_width = "64"))]
 mod windows;
 
-use std::fs::File;
+use std::env;
+use std::fs::{self, File};
 use std::io;
 use std::io::Read;
+use std::net::{IpAddr, SocketAddr};
 use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
 
 use trust_dns::rr::Name;
 
@@ -35,9 +39,23 @@ pub(crate) mod resolv_conf {
     include!(concat!(env!("OUT_DIR"), "/system_conf/resolv_conf.rs"));
 }
 
+/// Directory of the scoped resolvers, one file per domain, see `read_scoped_resolvers`
+#[cfg(target_os = "macos")]
+const SCOPED_RESOLVERS_DIR: Option<&'static str> = Some("/etc/resolver");
+#[cfg(not(target_os = "macos"))]
+const SCOPED_RESOLVERS_DIR: Option<&'static str> = None;
+
 #[cfg(unix)]
 pub(crate) fn read_system_conf() -> io::Result<(ResolverConfig, ResolverOpts)> {
-    read_resolv_conf("/etc/resolv.conf")
+    let (mut config, options) = read_resolv_conf("/etc/resolv.conf")?;
+
+    if let Some(dir) = SCOPED_RESOLVERS_DIR {
+        for (domain, name_server) in read_scoped_resolvers(dir)? {
+            config.add_domain_name_server(domain, name_server);
+        }
+    }
+
+    Ok((config, options))
 }
 
 /// Support only 64-bit until https://github.com/liranringel/ipconfig/issues/1 is resolved.
@@ -65,7 +83,8 @@ pub fn into_resolver_config(config_opts: Vec<ConfigOption>) -> (ResolverConfig,
     let mut domain = Option::None::<Name>;
     let mut search = Option::None::<Vec<Name>>;
     let mut nameservers = Vec::<NameServerConfig>::new();
-    let mut options = Option::None::<ResolverOpts>;
+    // options may be spread over many lines
+    let mut options = ResolverOpts::default();
 
     for config_opt in config_opts {
         match config_opt {
@@ -75,20 +94,15 @@ pub fn into_resolver_config(config_opts: Vec<ConfigOption>) -> (ResolverConfig,
                 nameserver.push_nameserver(&mut nameservers).ok();
             }
             ConfigOption::Advanced(advanced_opts) => {
-                options = Some(advanced_opts.into_iter().fold(
-                    ResolverOpts::default(),
-                    |mut ropts, advanced| {
-                        match advanced {
-                            AdvancedOption::NumberOfDots(ndots) => ropts.ndots = ndots as usize,
-                            AdvancedOption::Timeout(dur) => ropts.timeout = dur,
-                            AdvancedOption::Attempts(attempts) => {
-                                ropts.attempts = attempts as usize
-                            }
-                            AdvancedOption::Unknown(..) => (),
-                        }
-                        ropts
-                    },
-                ))
+                for advanced in advanced_opts {
+                    match advanced {
+                        AdvancedOption::NumberOfDots(ndots) => options.ndots = ndots as usize,
+                        AdvancedOption::Timeout(dur) => options.timeout = dur,
+                        AdvancedOption::Attempts(attempts) => options.attempts = attempts as usize,
+                        AdvancedOption::Rotate => options.rotate = true,
+                        AdvancedOption::Unknown(..) => (),
+                    }
+                }
             }
             //_ => (),
         }
@@ -106,7 +120,215 @@ pub fn into_resolver_config(config_opts: Vec<ConfigOption>) -> (ResolverConfig,
         warn!("no nameservers found in config");
     }
 
-    (config, options.unwrap_or_else(ResolverOpts::default))
+    (config, options)
+}
+
+/// Reads the scoped resolvers of macOS, see `man 5 resolver`
+///
+/// Each file in the directory configures the name servers for the domain of its file name, or of
+///  its `domain` line. Only the `nameserver`, `port` and `domain` lines are used, the name servers
+///  are queried over UDP and TCP. A missing directory has no scoped resolvers.
+pub fn read_scoped_resolvers<P: AsRef<Path>>(dir: P) -> io::Result<Vec<(Name, NameServerConfig)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+
+    let mut name_servers = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let domain = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        let mut data = String::new();
+        File::open(&path)?.read_to_string(&mut data)?;
+
+        match parse_scoped_resolver(&domain, &data) {
+            Ok(scoped) => name_servers.extend(scoped),
+            Err(e) => warn!("ignoring scoped resolver {:?}: {}", path, e),
+        }
+    }
+
+    Ok(name_servers)
+}
+
+fn parse_scoped_resolver(domain: &str, data: &str) -> io::Result<Vec<(Name, NameServerConfig)>> {
+    let invalid = |what: &str, value: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid {}: {}", what, value),
+        )
+    };
+
+    let mut domain = Name::from_str(domain).map_err(|_| invalid("domain", domain))?;
+    let mut port = 53;
+    let mut ips = Vec::<IpAddr>::new();
+
+    for line in data.lines() {
+        // comments run to the end of the line
+        let line = line.split(|c| c == '#' || c == ';').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+
+        match (words.next(), words.next()) {
+            (Some("nameserver"), Some(ip)) => {
+                ips.push(IpAddr::from_str(ip).map_err(|_| invalid("nameserver", ip))?)
+            }
+            (Some("port"), Some(value)) => {
+                port = u16::from_str(value).map_err(|_| invalid("port", value))?
+            }
+            (Some("domain"), Some(name)) => {
+                domain = Name::from_str(name).map_err(|_| invalid("domain", name))?
+            }
+            // search_order, timeout, etc. don't apply
+            _ => (),
+        }
+    }
+
+    let domain = domain.into_fqdn();
+    let mut name_servers = Vec::with_capacity(ips.len() * 2);
+    for ip in ips {
+        let mut servers = Vec::with_capacity(2);
+        BasicOption::Nameserver(ip).push_nameserver(&mut servers).ok();
+
+        for mut server in servers {
+            server.socket_addr = SocketAddr::new(ip, port);
+            name_servers.push((domain.clone(), server));
+        }
+    }
+
+    Ok(name_servers)
+}
+
+/// Reads the configuration from the environment variables, see `ResolverConfig::from_env`
+pub fn read_env_conf() -> io::Result<(ResolverConfig, ResolverOpts)> {
+    parse_env_conf(|key| env::var(key).ok())
+}
+
+/// Builds the configuration from the variables which `var` looks up, unset and empty variables
+///  keep their defaults
+fn parse_env_conf<F>(var: F) -> io::Result<(ResolverConfig, ResolverOpts)>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let var = |key: &str| var(key).and_then(|value| {
+        let value = value.trim().to_string();
+        if value.is_empty() { None } else { Some(value) }
+    });
+    let invalid = |key: &str, value: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid {}: {}", key, value),
+        )
+    };
+    let name = |key: &str, value: &str| {
+        Name::from_str(value)
+            .map(Name::into_fqdn)
+            .map_err(|_| invalid(key, value))
+    };
+    let number = |key: &str| -> io::Result<Option<u64>> {
+        match var(key) {
+            Some(value) => u64::from_str(&value).map(Some).map_err(|_| invalid(key, &value)),
+            None => Ok(None),
+        }
+    };
+    // addresses and names are separated by commas, whitespace or both
+    let list = |value: &str| -> Vec<String> {
+        value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+
+    let domain = match var("TRUST_DNS_DOMAIN") {
+        Some(value) => name("TRUST_DNS_DOMAIN", &value)?,
+        None => Name::root(),
+    };
+    let mut search = vec![];
+    for value in var("TRUST_DNS_SEARCH").map_or(vec![], |value| list(&value)) {
+        search.push(name("TRUST_DNS_SEARCH", &value)?);
+    }
+
+    let (protocols, port) = match var("TRUST_DNS_PROTOCOL") {
+        None => (vec![Protocol::Udp, Protocol::Tcp], 53),
+        Some(value) => {
+            match &value.to_lowercase()[..] {
+                "udp" => (vec![Protocol::Udp, Protocol::Tcp], 53),
+                "tcp" => (vec![Protocol::Tcp], 53),
+                #[cfg(feature = "dns-over-tls")]
+                "tls" => (vec![Protocol::Tls], 853),
+                #[cfg(feature = "dns-over-https")]
+                "https" => (vec![Protocol::Https], 443),
+                _ => return Err(invalid("TRUST_DNS_PROTOCOL", &value)),
+            }
+        }
+    };
+    let tls_dns_name = var("TRUST_DNS_TLS_DNS_NAME");
+    let https_template = var("TRUST_DNS_HTTPS_TEMPLATE");
+    let is_encrypted = protocols.iter().any(Protocol::is_encrypted);
+    if is_encrypted && tls_dns_name.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "TRUST_DNS_TLS_DNS_NAME is required for TLS and HTTPS",
+        ));
+    }
+
+    let mut name_servers = vec![];
+    for value in var("TRUST_DNS_NAMESERVERS").map_or(vec![], |value| list(&value)) {
+        let socket_addr = match SocketAddr::from_str(&value) {
+            Ok(socket_addr) => socket_addr,
+            Err(_) => {
+                let ip = IpAddr::from_str(&value).map_err(|_| {
+                    invalid("TRUST_DNS_NAMESERVERS", &value)
+                })?;
+                SocketAddr::new(ip, port)
+            }
+        };
+
+        for protocol in &protocols {
+            name_servers.push(NameServerConfig {
+                socket_addr,
+                protocol: *protocol,
+                tls_dns_name: tls_dns_name.clone(),
+                https_template: https_template.clone(),
+                edns_options: vec![],
+                name_compression: NameCompression::All,
+                padding_block_size: None,
+                transport_strategy: TransportStrategy::UdpThenTcp,
+            });
+        }
+    }
+
+    // the protocol is that of the name servers of the variable, the default ones are queried
+    //  over UDP and TCP
+    if name_servers.is_empty() {
+        if is_encrypted || var("TRUST_DNS_PROTOCOL").is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "TRUST_DNS_NAMESERVERS is required with TRUST_DNS_PROTOCOL",
+            ));
+        }
+        name_servers = ResolverConfig::default().name_servers().to_vec();
+    }
+
+    let mut options = ResolverOpts::default();
+    if let Some(ndots) = number("TRUST_DNS_NDOTS")? {
+        options.ndots = ndots as usize;
+    }
+    if let Some(timeout) = number("TRUST_DNS_TIMEOUT")? {
+        options.timeout = Duration::from_secs(timeout);
+    }
+    if let Some(attempts) = number("TRUST_DNS_ATTEMPTS")? {
+        options.attempts = attempts as usize;
+    }
+
+    Ok((
+        ResolverConfig::from_parts(domain, search, name_servers),
+        options,
+    ))
 }
 
 #[cfg(test)]
@@ -366,7 +588,7 @@ mod tests {
                 ConfigOption::Basic(BasicOption::Nameserver(
                     IpAddr::from_str("8.8.4.4").unwrap(),
                 )),
-                ConfigOption::Advanced(vec![AdvancedOption::Unknown("rotate", None)]),
+                ConfigOption::Advanced(vec![AdvancedOption::Rotate]),
                 ConfigOption::Advanced(vec![
                     AdvancedOption::Unknown("inet6", None),
                     AdvancedOption::Unknown("no-tld-query", None),
@@ -389,4 +611,150 @@ mod tests {
         read_resolv_conf(format!("{}/resolv.conf-macos", tests_dir())).expect("macos failed");
         read_resolv_conf(format!("{}/resolv.conf-linux", tests_dir())).expect("linux failed");
     }
+
+    #[test]
+    fn test_resolv_conf_options() {
+        let (config, options) =
+            read_resolv_conf(format!("{}/resolv.conf-linux", tests_dir())).expect("linux failed");
+
+        // the later options lines do not reset the earlier ones
+        assert_eq!(options.ndots, 8);
+        assert_eq!(options.timeout, Duration::from_secs(8));
+        assert_eq!(options.attempts, 8);
+        assert!(options.rotate);
+
+        assert_eq!(config.domain(), &Name::from_labels(vec!["example", "com"]));
+        assert_eq!(config.search().len(), 2);
+        // each nameserver over UDP and TCP
+        assert_eq!(config.name_servers().len(), 8);
+    }
+
+    fn env_conf(vars: &[(&str, &str)]) -> io::Result<(ResolverConfig, ResolverOpts)> {
+        parse_env_conf(|key| {
+            vars.iter().find(|&&(k, _)| k == key).map(
+                |&(_, v)| v.to_string(),
+            )
+        })
+    }
+
+    #[test]
+    fn test_env_conf() {
+        // nothing set is the default
+        let (config, options) = env_conf(&[]).expect("failed");
+        assert_eq!(
+            config.name_servers(),
+            ResolverConfig::default().name_servers()
+        );
+        assert_eq!(options.timeout, ResolverOpts::default().timeout);
+
+        let (config, options) = env_conf(&[
+            ("TRUST_DNS_NAMESERVERS", "10.0.0.1, [2001:db8::1]:5353"),
+            ("TRUST_DNS_DOMAIN", "example.com"),
+            ("TRUST_DNS_SEARCH", "example.com sub.example.com"),
+            ("TRUST_DNS_NDOTS", "3"),
+            ("TRUST_DNS_TIMEOUT", "2"),
+            ("TRUST_DNS_ATTEMPTS", ""),
+        ]).expect("failed");
+
+        // each nameserver over UDP and TCP
+        let name_servers = config.name_servers();
+        assert_eq!(name_servers.len(), 4);
+        assert_eq!(name_servers[0].socket_addr, "10.0.0.1:53".parse().unwrap());
+        assert_eq!(name_servers[0].protocol, Protocol::Udp);
+        assert_eq!(name_servers[1].protocol, Protocol::Tcp);
+        assert_eq!(
+            name_servers[2].socket_addr,
+            "[2001:db8::1]:5353".parse().unwrap()
+        );
+
+        assert_eq!(config.domain(), &Name::from_str("example.com.").unwrap());
+        assert!(config.domain().is_fqdn());
+        assert_eq!(
+            config.search(),
+            &[
+                Name::from_str("example.com.").unwrap(),
+                Name::from_str("sub.example.com.").unwrap(),
+            ]
+        );
+        assert_eq!(options.ndots, 3);
+        assert_eq!(options.timeout, Duration::from_secs(2));
+        // empty is unset
+        assert_eq!(options.attempts, ResolverOpts::default().attempts);
+
+        let (config, _) = env_conf(&[
+            ("TRUST_DNS_NAMESERVERS", "10.0.0.1"),
+            ("TRUST_DNS_PROTOCOL", "TCP"),
+        ]).expect("failed");
+        assert_eq!(config.name_servers().len(), 1);
+        assert_eq!(config.name_servers()[0].protocol, Protocol::Tcp);
+    }
+
+    #[test]
+    fn test_env_conf_invalid() {
+        assert!(env_conf(&[("TRUST_DNS_NAMESERVERS", "10.0.0.1, bad")]).is_err());
+        assert!(env_conf(&[("TRUST_DNS_TIMEOUT", "5s")]).is_err());
+        assert!(env_conf(&[("TRUST_DNS_DOMAIN", "example\tcom")]).is_err());
+        assert!(
+            env_conf(&[
+                ("TRUST_DNS_NAMESERVERS", "10.0.0.1"),
+                ("TRUST_DNS_PROTOCOL", "quic"),
+            ]).is_err()
+        );
+        // the protocol is of the configured name servers
+        assert!(env_conf(&[("TRUST_DNS_PROTOCOL", "tcp")]).is_err());
+    }
+
+    #[cfg(feature = "dns-over-tls")]
+    #[test]
+    fn test_env_conf_tls() {
+        let (config, _) = env_conf(&[
+            ("TRUST_DNS_NAMESERVERS", "1.1.1.1"),
+            ("TRUST_DNS_PROTOCOL", "tls"),
+            ("TRUST_DNS_TLS_DNS_NAME", "cloudflare-dns.com"),
+        ]).expect("failed");
+        assert_eq!(config.name_servers().len(), 1);
+        let name_server = &config.name_servers()[0];
+        assert_eq!(name_server.socket_addr, "1.1.1.1:853".parse().unwrap());
+        assert_eq!(name_server.protocol, Protocol::Tls);
+        assert_eq!(
+            name_server.tls_dns_name,
+            Some("cloudflare-dns.com".to_string())
+        );
+
+        // the certificates are validated against the name
+        assert!(
+            env_conf(&[
+                ("TRUST_DNS_NAMESERVERS", "1.1.1.1"),
+                ("TRUST_DNS_PROTOCOL", "tls"),
+            ]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_scoped_resolver() {
+        let name_servers = parse_scoped_resolver(
+            "corp.example.com",
+            "# VPN\nnameserver 10.0.0.1\nport 5353\nsearch_order 1\n",
+        ).expect("failed");
+
+        assert_eq!(name_servers.len(), 2);
+        for &(ref domain, ref name_server) in &name_servers {
+            assert_eq!(domain, &Name::from_str("corp.example.com.").unwrap());
+            assert!(domain.is_fqdn());
+            assert_eq!(name_server.socket_addr, "10.0.0.1:5353".parse().unwrap());
+        }
+
+        // the domain line takes precedence over the file name
+        let name_servers =
+            parse_scoped_resolver("local", "domain example.net\nnameserver ::1").expect("failed");
+        assert_eq!(name_servers[0].0, Name::from_str("example.net.").unwrap());
+        assert_eq!(name_servers[0].1.socket_addr.port(), 53);
+
+        assert!(parse_scoped_resolver("example.com", "nameserver bad").is_err());
+        assert!(
+            read_scoped_resolvers(format!("{}/no-such-dir", tests_dir()))
+                .expect("missing dir failed")
+                .is_empty()
+        );
+    }
 }
@@ -45,10 +45,22 @@ impl<'input> BasicOption<'input> {
             servers.push(NameServerConfig {
                 socket_addr,
                 protocol: Protocol::Udp,
+                tls_dns_name: None,
+                https_template: None,
+                edns_options: vec![],
+                name_compression: NameCompression::All,
+                padding_block_size: None,
+                transport_strategy: TransportStrategy::UdpThenTcp,
             });
             servers.push(NameServerConfig {
                 socket_addr,
                 protocol: Protocol::Tcp,
+                tls_dns_name: None,
+                https_template: None,
+                edns_options: vec![],
+                name_compression: NameCompression::All,
+                padding_block_size: None,
+                transport_strategy: TransportStrategy::UdpThenTcp,
             });
             // Ok(vec![
             //     NameServerConfig {
@@ -76,6 +88,8 @@ pub enum AdvancedOption<'input> {
     Timeout(Duration),
     /// Number of attempts before giving up on requests
     Attempts(u8),
+    /// Round robin between the name servers
+    Rotate,
     /// Unsupported option, possibly "name" of "name:option"
     Unknown(&'input str, Option<&'input str>),
 }
@@ -101,6 +115,7 @@ impl<'input> AdvancedOption<'input> {
             "attempts" => AdvancedOption::Attempts(
                 value.and_then(|s| u8::from_str(s).ok()).unwrap_or(2),
             ),
+            "rotate" => AdvancedOption::Rotate,
             ref s => AdvancedOption::Unknown(s, value),
         }
     }
@@ -15,7 +15,8 @@ use ipconfig::get_adapters;
 use ipconfig::computer::{get_search_list, get_domain, is_round_robin_enabled};
 
 use trust_dns::rr::Name;
-use config::{ResolverConfig, ResolverOpts, NameServerConfig, Protocol};
+use config::{ResolverConfig, ResolverOpts, NameServerConfig, NameCompression, Protocol,
+             TransportStrategy};
 
 
 macro_rules! map_ipconfig_to_io_error {
@@ -32,10 +33,22 @@ fn get_name_servers() -> io::Result<Vec<NameServerConfig>> {
         name_servers.push(NameServerConfig {
             socket_addr,
             protocol: Protocol::Udp,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
         });
         name_servers.push(NameServerConfig {
             socket_addr,
             protocol: Protocol::Tcp,
+            tls_dns_name: None,
+            https_template: None,
+            edns_options: vec![],
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            transport_strategy: TransportStrategy::UdpThenTcp,
         });
     };
     Ok(name_servers)
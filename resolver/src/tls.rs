@@ -0,0 +1,54 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS over TLS, RFC 7858, connections for the NameServerPool
+//!
+//! When both `dns-over-native-tls` and `dns-over-rustls` are enabled, native-tls is used.
+
+use std::io;
+use std::net::SocketAddr;
+
+use futures::Future;
+use tokio_core::reactor::Handle;
+use trust_dns_proto::DnsStreamHandle;
+
+#[cfg(feature = "dns-over-native-tls")]
+use trust_dns_native_tls::{TlsClientStream, TlsClientStreamBuilder};
+
+#[cfg(all(feature = "dns-over-rustls", not(feature = "dns-over-native-tls")))]
+use rustls::ClientConfig;
+#[cfg(all(feature = "dns-over-rustls", not(feature = "dns-over-native-tls")))]
+use trust_dns_rustls::{TlsClientStream, TlsClientStreamBuilder};
+#[cfg(all(feature = "dns-over-rustls", not(feature = "dns-over-native-tls")))]
+use webpki_roots;
+
+/// Creates a new TLS stream to the name server, certificates are validated with the system's
+///  trusted roots
+#[cfg(feature = "dns-over-native-tls")]
+pub(crate) fn new_tls_stream(
+    socket_addr: SocketAddr,
+    dns_name: String,
+    reactor: &Handle,
+) -> (Box<Future<Item = TlsClientStream, Error = io::Error>>, Box<DnsStreamHandle>) {
+    TlsClientStreamBuilder::new().build(socket_addr, dns_name, reactor)
+}
+
+/// Creates a new TLS stream to the name server, certificates are validated with the Mozilla
+///  trusted roots, see the `webpki-roots` crate
+#[cfg(all(feature = "dns-over-rustls", not(feature = "dns-over-native-tls")))]
+pub(crate) fn new_tls_stream(
+    socket_addr: SocketAddr,
+    dns_name: String,
+    reactor: &Handle,
+) -> (Box<Future<Item = TlsClientStream, Error = io::Error>>, Box<DnsStreamHandle>) {
+    let mut client_config = ClientConfig::new();
+    client_config.root_store.add_server_trust_anchors(
+        &webpki_roots::TLS_SERVER_ROOTS,
+    );
+
+    TlsClientStreamBuilder::with_client_config(client_config).build(socket_addr, dns_name, reactor)
+}
@@ -9,7 +9,7 @@ use std::net::SocketAddr;
 use std::io;
 
 use futures::Future;
-use rustls::{Certificate, ClientSession};
+use rustls::{Certificate, ClientConfig, ClientSession};
 use tokio_core::net::TcpStream as TokioTcpStream;
 use tokio_core::reactor::Handle;
 use tokio_rustls::TlsStream as TokioTlsStream;
@@ -28,6 +28,12 @@ impl TlsClientStreamBuilder {
         TlsClientStreamBuilder(TlsStreamBuilder::new())
     }
 
+    /// Creates a builder from an existing configuration, e.g. one which already trusts a set of
+    ///  root certificates
+    pub fn with_client_config(client_config: ClientConfig) -> TlsClientStreamBuilder {
+        TlsClientStreamBuilder(TlsStreamBuilder::with_client_config(client_config))
+    }
+
     /// Add a custom trusted peer certificate or certificate auhtority.
     ///
     /// If this is the 'client' then the 'server' must have it associated as it's `identity`, or have had the `identity` signed by this certificate.
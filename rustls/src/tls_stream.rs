@@ -21,8 +21,10 @@ use trust_dns::tcp::TcpStream;
 
 pub type TlsStream = TcpStream<TokioTlsStream<TokioTcpStream, ClientSession>>;
 
-fn tls_new(certs: &[Certificate] /*, pkcs12: Option<Pkcs12>*/) -> io::Result<Arc<ClientConfig>> {
-    let mut builder = ClientConfig::new();
+fn tls_new(
+    mut builder: ClientConfig,
+    certs: &[Certificate], /*, pkcs12: Option<Pkcs12>*/
+) -> io::Result<Arc<ClientConfig>> {
 
     // mutate the trust_store
     {
@@ -65,6 +67,7 @@ pub fn tls_from_stream(
 }
 
 pub struct TlsStreamBuilder {
+    client_config: ClientConfig,
     ca_chain: Vec<Certificate>,
     //identity: Option<Pkcs12>,
 }
@@ -72,7 +75,14 @@ pub struct TlsStreamBuilder {
 impl TlsStreamBuilder {
     /// Constructs a new TlsStreamBuilder
     pub fn new() -> TlsStreamBuilder {
+        Self::with_client_config(ClientConfig::new())
+    }
+
+    /// Constructs a new TlsStreamBuilder from an existing configuration, e.g. one which already
+    ///  trusts a set of root certificates
+    pub fn with_client_config(client_config: ClientConfig) -> TlsStreamBuilder {
         TlsStreamBuilder {
+            client_config: client_config,
             ca_chain: vec![],
             // identity: None,
         }
@@ -124,7 +134,10 @@ impl TlsStreamBuilder {
         loop_handle: &Handle,
     ) -> (Box<Future<Item = TlsStream, Error = io::Error>>, BufStreamHandle) {
         let (message_sender, outbound_messages) = unbounded();
-        let tls_connector = match ::tls_stream::tls_new(&self.ca_chain /*, self.identity*/) {
+        let tls_connector = match ::tls_stream::tls_new(
+            self.client_config,
+            &self.ca_chain, /*, self.identity*/
+        ) {
             Ok(c) => c,
             Err(e) => {
                 return (
@@ -0,0 +1,114 @@
+#![feature(test)]
+
+extern crate test;
+
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+use test::Bencher;
+
+use trust_dns::op::{Message, Query, ResponseCode};
+use trust_dns::rr::*;
+use trust_dns::rr::rdata::SOA;
+use trust_dns_server::authority::{Authority, Catalog, ZoneType};
+
+const ZONES: usize = 10_000;
+
+fn name(name: &str) -> Name {
+    Name::parse(name, None).unwrap()
+}
+
+fn create_authority(origin: Name) -> Authority {
+    let mut authority = Authority::new(
+        origin.clone(),
+        BTreeMap::new(),
+        ZoneType::Master,
+        false,
+        false,
+    );
+    authority.upsert(
+        Record::from_rdata(
+            origin.clone(),
+            3600,
+            RecordType::SOA,
+            RData::SOA(SOA::new(
+                name("sns.dns.icann.org."),
+                name("noc.dns.icann.org."),
+                2017101601,
+                7200,
+                3600,
+                1209600,
+                3600,
+            )),
+        ),
+        0,
+    );
+    authority.upsert(
+        Record::from_rdata(
+            Name::parse("www", Some(&origin)).unwrap(),
+            3600,
+            RecordType::A,
+            RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+        ),
+        0,
+    );
+
+    authority
+}
+
+/// A catalog of `ZONES` zones, `zone0.example.` through `zone9999.example.`, each with a `www`
+fn create_catalog() -> Catalog {
+    let mut catalog = Catalog::new();
+    for i in 0..ZONES {
+        let origin = name(&format!("zone{}.example.", i));
+        catalog.upsert(origin.clone(), create_authority(origin));
+    }
+
+    catalog
+}
+
+fn query(name: Name) -> Message {
+    let mut query = Query::new();
+    query.set_name(name).set_query_type(RecordType::A);
+
+    let mut message = Message::new();
+    message.add_query(query);
+    message
+}
+
+#[bench]
+fn catalog_lookup_10k_zones(b: &mut Bencher) {
+    let catalog = create_catalog();
+    let request = query(name("www.zone9999.example."));
+
+    b.iter(|| {
+        let response = catalog.lookup(&request);
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+    });
+}
+
+#[bench]
+fn catalog_lookup_10k_zones_deep_name(b: &mut Bencher) {
+    let catalog = create_catalog();
+    let request = query(name("a.b.c.d.e.f.www.zone5000.example."));
+
+    b.iter(|| {
+        let response = catalog.lookup(&request);
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    });
+}
+
+#[bench]
+fn catalog_lookup_10k_zones_no_zone(b: &mut Bencher) {
+    let catalog = create_catalog();
+    let request = query(name("www.example.com."));
+
+    b.iter(|| {
+        let response = catalog.lookup(&request);
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    });
+}
@@ -15,19 +15,34 @@
  */
 //! All authority related types
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+#[cfg(feature = "dnssec")]
+use std::collections::Bound;
+use std::iter;
+use std::mem;
+use std::sync::Arc;
 
 use chrono::Utc;
+#[cfg(feature = "dnssec")]
+use data_encoding::base32hex;
 
+use trust_dns::client::ZoneDiff;
 use trust_dns::error::*;
 use trust_dns::op::{Message, UpdateMessage, ResponseCode, Query};
 use trust_dns::rr::{DNSClass, Name, RData, Record, RecordType, RrKey, RecordSet};
-use trust_dns::rr::rdata::{NSEC, SIG};
+use trust_dns::rr::rdata::{NSEC, SIG, SOA};
 use trust_dns::rr::dnssec::{tbs, Signer, SupportedAlgorithms, Verifier};
+use trust_dns::serialize::txt::write_zone;
 
-use authority::{Journal, UpdateResult, ZoneType};
-use error::{PersistenceErrorKind, PersistenceResult};
+use authority::{illegal_data, Denial, IllegalData, IllegalDataPolicy, Journal, UpdateResult,
+                ZoneStore, ZoneType};
+#[cfg(feature = "dnssec")]
+use authority::{zone_signer, MAX_NSEC3_ITERATIONS};
+use authority::change_hook::{self, ChangeHook, RRsetChange};
+use error::{PersistenceError, PersistenceErrorKind, PersistenceResult};
 
+/// The number of changes to a zone which are kept for incremental zone transfers by default
+const DEFAULT_IXFR_HISTORY_SIZE: usize = 10;
 
 /// Authority is responsible for storing the resource records for a particular zone.
 ///
@@ -37,7 +52,7 @@ pub struct Authority {
     origin: Name,
     class: DNSClass,
     journal: Option<Journal>,
-    records: BTreeMap<RrKey, RecordSet>,
+    records: Box<ZoneStore>,
     zone_type: ZoneType,
     allow_update: bool,
     is_dnssec_enabled: bool,
@@ -47,6 +62,14 @@ pub struct Authority {
     //   may not support dynamic updates to register the new key... Trust-DNS will provide support
     //   for this, in some form, perhaps alternate root zones...
     secure_keys: Vec<Signer>,
+    denial: Denial,
+    // the NSEC3 records of the zone by their decoded hashed owner names, see `index_nsec3`
+    nsec3_index: BTreeMap<Vec<u8>, RrKey>,
+    update_tsig_keys: Vec<Name>,
+    change_hooks: Vec<Arc<ChangeHook>>,
+    // the latest changes to the zone, oldest first, for incremental zone transfers
+    ixfr_history: VecDeque<ZoneDiff>,
+    ixfr_history_size: usize,
 }
 
 impl Authority {
@@ -72,7 +95,34 @@ impl Authority {
         allow_update: bool,
         is_dnssec_enabled: bool,
     ) -> Authority {
-        Authority {
+        Self::with_store(
+            origin,
+            Box::new(records),
+            zone_type,
+            allow_update,
+            is_dnssec_enabled,
+        )
+    }
+
+    /// Creates a new Authority which keeps its records in the store, see `ZoneStore`
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - The zone `Name` being created, this should match that of the `RecordType::SOA`
+    ///              record.
+    /// * `records` - The store of the records in the zone, with the initial set of them.
+    /// * `zone_type` - The type of zone, i.e. is this authoritative?
+    /// * `allow_update` - If true, then this zone accepts dynamic updates.
+    /// * `is_dnssec_enabled` - If true, then the zone will sign the zone with all registered keys,
+    ///                         (see `add_secure_key()`)
+    pub fn with_store(
+        origin: Name,
+        records: Box<ZoneStore>,
+        zone_type: ZoneType,
+        allow_update: bool,
+        is_dnssec_enabled: bool,
+    ) -> Authority {
+        let mut authority = Authority {
             origin: origin,
             class: DNSClass::IN,
             journal: None,
@@ -81,7 +131,17 @@ impl Authority {
             allow_update: allow_update,
             is_dnssec_enabled: is_dnssec_enabled,
             secure_keys: Vec::new(),
-        }
+            denial: Denial::Nsec,
+            nsec3_index: BTreeMap::new(),
+            update_tsig_keys: Vec::new(),
+            change_hooks: Vec::new(),
+            ixfr_history: VecDeque::new(),
+            ixfr_history_size: DEFAULT_IXFR_HISTORY_SIZE,
+        };
+
+        // the records may be of a signed zone
+        authority.index_nsec3();
+        authority
     }
 
     /// By adding a secure key, this will implicitly enable dnssec for the zone.
@@ -125,6 +185,8 @@ impl Authority {
             //  authority.
             if record.rr_type() == RecordType::AXFR {
                 self.records.clear();
+            } else if record.rr_type() == RecordType::RRSIG {
+                self.insert_rrsig(record);
             } else {
                 match self.update_records(&[record], false) {
                     Err(error) => {
@@ -154,9 +216,9 @@ impl Authority {
                 Record::new().set_rr_type(RecordType::AXFR),
             ));
 
-            for rr_set in self.records.values() {
+            for (_, rr_set) in self.records.iter() {
                 // TODO: should we preserve rr_sets or not?
-                for record in rr_set.iter() {
+                for record in rr_set.iter().chain(rr_set.rrsigs()) {
                     try!(journal.insert_record(serial, record));
                 }
             }
@@ -182,11 +244,153 @@ impl Authority {
         self.allow_update = allow_update;
     }
 
+    /// Authorizes updates signed with the TSIG key of the name, the key itself is verified by the
+    ///  Catalog, see `Catalog::add_tsig_key`
+    ///
+    /// # Arguments
+    ///
+    /// * `key_name` - the name of the key, e.g. `update-key.example.com.`
+    pub fn add_update_tsig_key(&mut self, key_name: Name) {
+        if !self.update_tsig_keys.contains(&key_name) {
+            self.update_tsig_keys.push(key_name);
+        }
+    }
+
+    /// The names of the TSIG keys which are authorized to update this zone
+    pub fn update_tsig_keys(&self) -> &[Name] {
+        &self.update_tsig_keys
+    }
+
+    /// Adds a hook which is called with the changed RRsets after each update of the records, see
+    ///  `ChangeHook`
+    ///
+    /// The changes are those of `update_records`, i.e. of dynamic updates and of the recovery from
+    ///  the journal, and of replacing the zone with `Catalog::replace`. Records inserted directly
+    ///  with `upsert` are not reported.
+    pub fn add_change_hook(&mut self, hook: Arc<ChangeHook>) {
+        self.change_hooks.push(hook);
+    }
+
+    /// Returns the hooks called with the changes to the records
+    pub fn change_hooks(&self) -> &[Arc<ChangeHook>] {
+        &self.change_hooks
+    }
+
+    /// Calls the change hooks with the changes, if there are any
+    pub(crate) fn call_change_hooks(&self, changes: &[RRsetChange]) {
+        if changes.is_empty() {
+            return;
+        }
+
+        let serial = self.serial();
+        for hook in &self.change_hooks {
+            hook.on_records_changed(&self.origin, serial, changes);
+        }
+    }
+
+    /// Sets the number of the latest changes to the zone which are kept for incremental zone
+    ///  transfers, IXFR, 10 by default, 0 keeps none
+    ///
+    /// The changes are those reported to the change hooks, see `add_change_hook`, which are from
+    ///  one serial to the next. A secondary which is further behind is sent the whole zone.
+    pub fn set_ixfr_history_size(&mut self, ixfr_history_size: usize) {
+        self.ixfr_history_size = ixfr_history_size;
+        while self.ixfr_history.len() > ixfr_history_size {
+            self.ixfr_history.pop_front();
+        }
+    }
+
+    /// Returns the number of the latest changes to the zone which are kept for incremental zone
+    ///  transfers
+    pub fn ixfr_history_size(&self) -> usize {
+        self.ixfr_history_size
+    }
+
+    /// Returns the changes to the zone since the version with the serial, oldest first, None if
+    ///  they are not all kept
+    pub fn changes_since(&self, serial: u32) -> Option<Vec<&ZoneDiff>> {
+        let first = match self.ixfr_history.iter().position(
+            |diff| diff.from_serial() == serial,
+        ) {
+            Some(first) => first,
+            None => return None,
+        };
+
+        let changes = self.ixfr_history.iter().skip(first).collect::<Vec<_>>();
+        if changes.last().map(|diff| diff.to_serial()) != Some(self.serial()) {
+            return None;
+        }
+        Some(changes)
+    }
+
+    /// Adds the change from the records before it to the current ones to the kept changes
+    ///
+    /// A change which does not lead from one serial to another, e.g. without an SOA, breaks the
+    ///  chain of the versions of the zone, all kept changes are dropped.
+    pub(crate) fn add_to_ixfr_history(
+        &mut self,
+        before: &ZoneStore,
+        changes: &[RRsetChange],
+    ) {
+        if self.ixfr_history_size == 0 || changes.is_empty() {
+            return;
+        }
+
+        let old_soa = before
+            .get_rrset(&RrKey::new(&self.origin, RecordType::SOA))
+            .and_then(|rrset| rrset.iter().next())
+            .cloned();
+        let diff = match (old_soa, self.soa().cloned()) {
+            (Some(old_soa), Some(new_soa)) => change_hook::zone_diff(old_soa, new_soa, changes),
+            _ => {
+                self.ixfr_history.clear();
+                return;
+            }
+        };
+
+        let follows_on = self.ixfr_history.back().map_or(true, |last| {
+            last.to_serial() == diff.from_serial()
+        });
+        if diff.from_serial() == diff.to_serial() || !follows_on {
+            debug!(
+                "changes to {} do not follow on from its ixfr history, dropping it",
+                self.origin
+            );
+            self.ixfr_history.clear();
+            return;
+        }
+
+        self.ixfr_history.push_back(diff);
+        while self.ixfr_history.len() > self.ixfr_history_size {
+            self.ixfr_history.pop_front();
+        }
+    }
+
+    /// Takes over the kept changes of the version of the zone which this replaces, and adds the
+    ///  change to this version to them, see `Catalog::replace`
+    pub(crate) fn take_ixfr_history(&mut self, replaced: &mut Authority, changes: &[RRsetChange]) {
+        let ixfr_history_size = self.ixfr_history_size;
+        self.ixfr_history = mem::replace(&mut replaced.ixfr_history, VecDeque::new());
+        self.set_ixfr_history_size(ixfr_history_size);
+        self.add_to_ixfr_history(replaced.records(), changes);
+    }
+
     /// Retrieve the Signer, which contains the private keys, for this zone
     pub fn secure_keys(&self) -> &[Signer] {
         &self.secure_keys
     }
 
+    /// Sets how the zone proves that names and types do not exist once it is signed, see
+    ///  `secure_zone`, NSEC records by default
+    pub fn set_denial(&mut self, denial: Denial) {
+        self.denial = denial;
+    }
+
+    /// How the zone proves that names and types do not exist once it is signed
+    pub fn denial(&self) -> &Denial {
+        &self.denial
+    }
+
     /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
     pub fn origin(&self) -> &Name {
         &self.origin
@@ -197,9 +401,69 @@ impl Authority {
         self.zone_type
     }
 
-    /// Get all the
-    pub fn records(&self) -> &BTreeMap<RrKey, RecordSet> {
-        &self.records
+    /// Get all the records of the zone, from the store which keeps them
+    pub fn records(&self) -> &ZoneStore {
+        &*self.records
+    }
+
+    /// Writes the records of the zone as a master file, which `Parser` reads back, see
+    ///  `trust_dns::serialize::txt::write_zone`
+    ///
+    /// The DNSSEC records, the RRSIGs, NSEC, NSEC3 and NSEC3PARAM records and the DNSKEYs, are
+    ///  left out, as the zone generates them from its keys when it is signed, see
+    ///  `to_zone_file_with_dnssec` to include them.
+    pub fn to_zone_file(&self) -> Result<String, EncodeError> {
+        let records = self.records
+            .iter()
+            .flat_map(|(_, rrset)| rrset.iter())
+            .filter(|record| !is_dnssec_type(record.rr_type()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        write_zone(&self.origin, &records)
+    }
+
+    /// Writes all the records of the zone as a master file, with the RRSIGs and the other DNSSEC
+    ///  records, e.g. for a zone which was signed elsewhere, or to check the signatures
+    pub fn to_zone_file_with_dnssec(&self) -> Result<String, EncodeError> {
+        let records = self.records
+            .iter()
+            .flat_map(|(_, rrset)| rrset.iter().chain(rrset.rrsigs()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        write_zone(&self.origin, &records)
+    }
+
+    /// Checks the zone for illegal data, e.g. a CNAME at the apex, and handles it per the policy
+    ///
+    /// Returns the illegal data which was found, or the first of it if the policy is to reject.
+    pub fn check_illegal_data(
+        &mut self,
+        policy: IllegalDataPolicy,
+    ) -> Result<Vec<IllegalData>, IllegalData> {
+        let illegal = illegal_data::find_illegal_data(&self.origin, &*self.records);
+
+        match policy {
+            IllegalDataPolicy::Reject => {
+                if let Some(first) = illegal.first() {
+                    return Err(first.clone());
+                }
+            }
+            IllegalDataPolicy::Repair => {
+                for data in &illegal {
+                    warn!("{}: repairing {}", self.origin, data);
+                }
+                illegal_data::repair_illegal_data(&illegal, &mut *self.records);
+            }
+            IllegalDataPolicy::Warn => {
+                for data in &illegal {
+                    warn!("{}: {}", self.origin, data);
+                }
+            }
+        }
+
+        Ok(illegal)
     }
 
     /// Returns the SOA of the authority.
@@ -277,6 +541,50 @@ impl Authority {
         return serial;
     }
 
+    /// Sets the SOA serial to the one after `serial`, unless it is already after it in the serial
+    ///  number arithmetic of [RFC 1982](https://tools.ietf.org/html/rfc1982), returns the serial
+    ///  of the zone
+    ///
+    /// This is for a zone which replaces a version of itself which was served with `serial`, so
+    ///  that secondaries see the change.
+    pub fn advance_serial(&mut self, serial: u32) -> u32 {
+        let current = self.serial();
+        if (current.wrapping_sub(serial) as i32) > 0 {
+            return current;
+        }
+
+        let mut soa = if let Some(soa_record) = self.soa() {
+            soa_record.clone()
+        } else {
+            warn!("no soa record found for zone: {}", self.origin);
+            return 0;
+        };
+
+        let next = serial.wrapping_add(1);
+        if let &mut RData::SOA(ref mut soa_rdata) = soa.rdata_mut() {
+            let advanced = SOA::new(
+                soa_rdata.mname().clone(),
+                soa_rdata.rname().clone(),
+                next,
+                soa_rdata.refresh(),
+                soa_rdata.retry(),
+                soa_rdata.expire(),
+                soa_rdata.minimum(),
+            );
+            *soa_rdata = advanced;
+        } else {
+            panic!("This was not an SOA record"); // valid panic, never should happen
+        }
+
+        // the record set only takes a numerically greater serial, which a wrapped one is not
+        if let Err(error) = self.records.delete(&RrKey::new(&self.origin, RecordType::SOA)) {
+            error!("could not advance the serial of {}: {}", self.origin, error);
+            return current;
+        }
+        self.upsert(soa, next);
+        next
+    }
+
     /// Get the NS, NameServer, record for the zone
     pub fn ns(&self, is_secure: bool, supported_algorithms: SupportedAlgorithms) -> Vec<&Record> {
         self.lookup(
@@ -434,7 +742,7 @@ impl Authority {
           // zone     rrset    rr       RRset exists (value dependent)
           if self.lookup(require.name(), require.rr_type(), false, SupportedAlgorithms::new())
                  .iter()
-                 .filter(|rr| *rr == &require)
+                 .filter(|rr| rr.canonical_eq(&require))
                  .next()
                  .is_none() {
             return Err(ResponseCode::NXRRSet);
@@ -491,7 +799,23 @@ impl Authority {
             return Err(ResponseCode::Refused);
         }
 
-        // verify sig0, currently the only authorization that is accepted.
+        // the TSIG was verified by the Catalog before the update got here, only the key needs to be
+        //  allowed for this zone
+        if let Some(tsig) = update_message.tsig() {
+            if self.update_tsig_keys.contains(tsig.name()) {
+                info!("authorized update with TSIG key: {}", tsig.name());
+                return Ok(());
+            }
+
+            warn!(
+                "TSIG key {} is not allowed to update: {}",
+                tsig.name(),
+                self.origin
+            );
+            return Err(ResponseCode::Refused);
+        }
+
+        // verify sig0
         let sig0s: &[Record] = update_message.sig0();
         debug!("authorizing with: {:?}", sig0s);
         if !sig0s.is_empty() &&
@@ -660,8 +984,14 @@ impl Authority {
         records: &[Record],
         auto_signing_and_increment: bool,
     ) -> UpdateResult<bool> {
-        let mut updated = false;
         let serial: u32 = self.serial();
+        // the records are only compared for the changes if there is anyone to tell of them, or
+        //  they are kept for incremental zone transfers
+        let before = if self.change_hooks.is_empty() && self.ixfr_history_size == 0 {
+            None
+        } else {
+            Some(self.records.to_map())
+        };
 
         // the persistence act as a write-ahead log. The WAL will also be used for recovery of a zone
         //  subsequent to a failure of the server.
@@ -672,6 +1002,46 @@ impl Authority {
             }
         }
 
+        // the changes are made all together, or not at all, RFC 2136 section 3.4.2.1
+        if let Err(error) = self.records.begin() {
+            error!("could not start update of {}: {}", self.origin, error);
+            return Err(ResponseCode::ServFail);
+        }
+        let updated = match self.apply_update_records(records, auto_signing_and_increment, serial) {
+            Ok(updated) => updated,
+            Err(response_code) => {
+                self.records.rollback();
+                self.index_nsec3();
+                return Err(response_code);
+            }
+        };
+        if let Err(error) = self.records.commit() {
+            error!("could not commit update of {}: {}", self.origin, error);
+            self.index_nsec3();
+            return Err(ResponseCode::ServFail);
+        }
+
+        // after the signing, so that the hooks see the zone as it is served
+        if let Some(before) = before {
+            if updated {
+                let changes = change_hook::diff(&before, &*self.records);
+                self.add_to_ixfr_history(&before, &changes);
+                self.call_change_hooks(&changes);
+            }
+        }
+
+        Ok(updated)
+    }
+
+    /// Makes the changes of `update_records`, between the `begin` and the `commit` of the store
+    fn apply_update_records(
+        &mut self,
+        records: &[Record],
+        auto_signing_and_increment: bool,
+        serial: u32,
+    ) -> UpdateResult<bool> {
+        let mut updated = false;
+
         // 3.4.2.7 - Pseudocode For Update Section Processing
         //
         //      [rr] for rr in updates
@@ -729,7 +1099,8 @@ impl Authority {
 
                     // zone     rrset    rr       Add to an RRset
                     info!("upserting record: {:?}", rr);
-                    updated = self.upsert(rr.clone(), serial) || updated;
+                    updated = try!(self.try_upsert(rr.clone(), serial).map_err(write_failed)) ||
+                        updated;
                 }
                 DNSClass::ANY => {
                     // This is a delete of entire RRSETs, either many or one. In either case, the spec is clear:
@@ -752,7 +1123,8 @@ impl Authority {
                                 rr.name()
                             );
                             let to_delete = self.records
-                                .keys()
+                                .iter()
+                                .map(|(k, _)| k)
                                 .filter(|k| {
                                     !((k.record_type == RecordType::SOA ||
                                            k.record_type == RecordType::NS) &&
@@ -762,7 +1134,7 @@ impl Authority {
                                 .cloned()
                                 .collect::<Vec<RrKey>>();
                             for delete in to_delete {
-                                self.records.remove(&delete);
+                                try!(self.records.delete(&delete).map_err(write_failed));
                                 updated = true;
                             }
                         }
@@ -774,7 +1146,8 @@ impl Authority {
 
                             // ANY      rrset    empty    Delete an RRset
                             if let &RData::NULL(..) = rr.rdata() {
-                                let deleted = self.records.remove(&rr_key);
+                                let deleted =
+                                    try!(self.records.delete(&rr_key).map_err(write_failed));
                                 info!("deleted rrset: {:?}", deleted);
                                 updated = updated || deleted.is_some();
                             } else {
@@ -787,9 +1160,12 @@ impl Authority {
                 DNSClass::NONE => {
                     info!("deleting specific record: {:?}", rr);
                     // NONE     rrset    rr       Delete an RR from an RRset
-                    if let Some(rrset) = self.records.get_mut(&rr_key) {
+                    if let Some(mut rrset) = self.records.get_rrset(&rr_key).cloned() {
                         let deleted = rrset.remove(rr, serial);
                         info!("deleted ({}) specific record: {:?}", deleted, rr);
+                        if deleted {
+                            try!(self.records.upsert(rr_key, rrset).map_err(write_failed));
+                        }
                         updated = updated || deleted;
                     }
                 }
@@ -817,6 +1193,95 @@ impl Authority {
         Ok(updated)
     }
 
+    /// Replaces all the records of the zone, e.g. with those transferred from the primary of a
+    ///  secondary zone, returns the serial of the zone
+    ///
+    /// As for an update, the change is kept for incremental zone transfers and reported to the
+    ///  change hooks. Records of another class than that of the zone are dropped, and RRSIGs are
+    ///  served along side the records they cover. If the zone has a journal, the zone is written
+    ///  to it as a whole, so that it is recovered at this serial.
+    pub fn replace_records(&mut self, records: Vec<Record>) -> u32 {
+        let serial = records
+            .iter()
+            .filter(|record| record.name() == &self.origin)
+            .filter_map(|record| match *record.rdata() {
+                RData::SOA(ref soa) => Some(soa.serial()),
+                _ => None,
+            })
+            .next()
+            .unwrap_or(0);
+
+        let (rrsigs, records): (Vec<Record>, Vec<Record>) = records.into_iter().partition(
+            |record| record.rr_type() == RecordType::RRSIG,
+        );
+
+        let before = self.records.to_map();
+        self.records.clear();
+        for record in records.into_iter().chain(rrsigs) {
+            if record.dns_class() != self.class {
+                warn!(
+                    "dropping {} of class {:?} from {}",
+                    record.name(),
+                    record.dns_class(),
+                    self.origin
+                );
+                continue;
+            }
+
+            if record.rr_type() == RecordType::RRSIG {
+                self.insert_rrsig(record);
+            } else {
+                self.upsert(record, serial);
+            }
+        }
+
+        if let Some(journal) = self.journal.as_ref() {
+            if let Err(error) = journal.clear() {
+                error!("could not clear journal of {}: {}", self.origin, error);
+            }
+        }
+        if let Err(error) = self.persist_to_journal() {
+            error!("could not persist {} to journal: {}", self.origin, error);
+        }
+
+        self.index_nsec3();
+
+        let changes = change_hook::diff(&before, &*self.records);
+        self.add_to_ixfr_history(&before, &changes);
+        self.call_change_hooks(&changes);
+        self.serial()
+    }
+
+    /// Adds the RRSIG to the RRset it covers, returns false if the zone does not have the RRset
+    fn insert_rrsig(&mut self, rrsig: Record) -> bool {
+        let type_covered = match *rrsig.rdata() {
+            RData::SIG(ref sig) => sig.type_covered(),
+            _ => return false,
+        };
+
+        let rr_key = RrKey::new(rrsig.name(), type_covered);
+        match self.records.get_rrset(&rr_key).cloned() {
+            Some(mut rrset) => {
+                rrset.insert_rrsig(rrsig);
+                match self.records.upsert(rr_key, rrset) {
+                    Ok(_) => true,
+                    Err(error) => {
+                        error!("could not write RRSIG to {}: {}", self.origin, error);
+                        false
+                    }
+                }
+            }
+            None => {
+                warn!(
+                    "dropping RRSIG of {} {}, which is not in the zone",
+                    rrsig.name(),
+                    type_covered
+                );
+                false
+            }
+        }
+    }
+
     /// Inserts or updates a `Record` depending on it's existence in the authority.
     ///
     /// Guarantees that SOA, CNAME only has one record, will implicitly update if they already exist.
@@ -828,18 +1293,33 @@ impl Authority {
     ///
     /// # Return value
     ///
-    /// Ok() on success or Err() with the `ResponseCode` associated with the error.
+    /// true if the record was inserted, false if it was already in the zone, or it could not be
+    ///  written to the store.
     pub fn upsert(&mut self, record: Record, serial: u32) -> bool {
+        match self.try_upsert(record, serial) {
+            Ok(inserted) => inserted,
+            Err(error) => {
+                error!("could not write record to {}: {}", self.origin, error);
+                false
+            }
+        }
+    }
+
+    /// Inserts or updates a `Record`, see `upsert`, returns an error if it could not be written to
+    ///  the store
+    fn try_upsert(&mut self, record: Record, serial: u32) -> PersistenceResult<bool> {
         assert_eq!(self.class, record.dns_class());
 
         let rr_key = RrKey::new(record.name(), record.rr_type());
-        let records: &mut RecordSet = self.records.entry(rr_key).or_insert(RecordSet::new(
-            record.name(),
-            record.rr_type(),
-            serial,
-        ));
+        let mut rrset = self.records.get_rrset(&rr_key).cloned().unwrap_or_else(|| {
+            RecordSet::new(record.name(), record.rr_type(), serial)
+        });
 
-        records.insert(record, serial)
+        let inserted = rrset.insert(record, serial);
+        if inserted {
+            try!(self.records.upsert(rr_key, rrset));
+        }
+        Ok(inserted)
     }
 
     /// Takes the UpdateMessage, extracts the Records, and applies the changes to the record set.
@@ -927,35 +1407,102 @@ impl Authority {
     ) -> Vec<&Record> {
         let record_type: RecordType = query.query_type();
 
-        // if this is an AXFR zone transfer, verify that this is either the slave or master
-        //  for AXFR the first and last record must be the SOA
+        // for AXFR the first and last record must be the SOA, see `axfr`
         if RecordType::AXFR == record_type {
-            match self.zone_type() {
-                ZoneType::Master | ZoneType::Slave => (),
-                // TODO Forward?
-                _ => return vec![], // TODO this sould be an error.
-            }
+            return self.axfr(is_secure, supported_algorithms).map_or(
+                vec![],
+                |records| records.collect(),
+            );
         }
 
         // it would be better to stream this back, rather than packaging everything up in an array
         //  though for UDP it would still need to be bundled
-        let mut query_result: Vec<_> =
-            self.lookup(query.name(), record_type, is_secure, supported_algorithms);
+        self.lookup(query.name(), record_type, is_secure, supported_algorithms)
+    }
 
-        if RecordType::AXFR == record_type {
-            if let Some(soa) = self.soa() {
-                let mut xfr: Vec<&Record> = query_result;
-                // TODO: probably make Records Rc or Arc, to remove the clone
-                xfr.insert(0, soa);
-                xfr.push(soa);
+    /// Returns the records of a zone transfer, AXFR, as they are read from the zone, the SOA, the
+    ///  other RRsets, and the SOA again, RFC 5936 section 2.2
+    ///
+    /// None is returned for a zone which is neither a master nor a slave, or which has no SOA.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_secure` - if true, then RRSIG records (if this is a secure zone) will be returned.
+    pub fn axfr<'a>(
+        &'a self,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Option<Box<Iterator<Item = &'a Record> + 'a>> {
+        match self.zone_type() {
+            ZoneType::Master | ZoneType::Slave => (),
+            // TODO Forward?
+            _ => return None,
+        }
+        let soa = match self.soa() {
+            Some(soa) => soa,
+            None => return None,
+        };
 
-                query_result = xfr;
-            } else {
-                return vec![]; // TODO is this an error?
-            }
+        let records = self.records
+            .iter()
+            .filter(|&(_, rr_set)| rr_set.record_type() != RecordType::SOA)
+            .flat_map(move |(_, rr_set)| rr_set.records(is_secure, supported_algorithms));
+
+        Some(Box::new(
+            iter::once(soa).chain(records).chain(iter::once(soa)),
+        ))
+    }
+
+    /// Returns the records of an incremental zone transfer, IXFR, to a client with the version of
+    ///  the zone with the serial, RFC 1995
+    ///
+    /// These are the SOA of the zone, the changes since the serial, see `ZoneDiff::records`, and
+    ///  the SOA again. Only the SOA is returned if the client is up to date, and the whole zone,
+    ///  as for an AXFR, if the changes are not kept, see `set_ixfr_history_size`. Nothing is
+    ///  returned for a zone which is neither a master nor a slave.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - the serial of the version of the zone which the client has
+    /// * `is_secure` - if true, then the RRSIG records of the changes will be returned.
+    pub fn ixfr(
+        &self,
+        serial: u32,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Vec<&Record> {
+        match self.zone_type() {
+            ZoneType::Master | ZoneType::Slave => (),
+            _ => return vec![],
+        }
+
+        let soa = match self.soa() {
+            Some(soa) => soa,
+            None => return vec![],
+        };
+
+        // the client is up to date, or even ahead
+        if (self.serial().wrapping_sub(serial) as i32) <= 0 {
+            return vec![soa];
         }
 
-        query_result
+        let changes = match self.changes_since(serial) {
+            Some(changes) => changes,
+            None => {
+                debug!("no ixfr history of {} since {}, sending the zone", self.origin, serial);
+                let query = Query::query(self.origin.clone(), RecordType::AXFR);
+                return self.search(&query, is_secure, supported_algorithms);
+            }
+        };
+
+        let mut ixfr = vec![soa];
+        for diff in changes {
+            ixfr.extend(diff.records().into_iter().filter(|record| {
+                is_secure || record.rr_type() != RecordType::RRSIG
+            }));
+        }
+        ixfr.push(soa);
+        ixfr
     }
 
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
@@ -991,7 +1538,8 @@ impl Authority {
         let result: Vec<&Record> = match rtype {
             RecordType::ANY | RecordType::AXFR => {
                 self.records
-                    .values()
+                    .iter()
+                    .map(|(_, rr_set)| rr_set)
                     .filter(|rr_set| {
                         rtype == RecordType::ANY || rr_set.record_type() != RecordType::SOA
                     })
@@ -1002,7 +1550,7 @@ impl Authority {
                     })
             }
             _ => {
-                self.records.get(&rr_key).map_or(vec![], |rr_set| {
+                self.records.get_rrset(&rr_key).map_or(vec![], |rr_set| {
                     rr_set
                         .records(is_secure, supported_algorithms)
                         .into_iter()
@@ -1028,7 +1576,8 @@ impl Authority {
         supported_algorithms: SupportedAlgorithms,
     ) -> Vec<&Record> {
         self.records
-            .values()
+            .iter()
+            .map(|(_, rr_set)| rr_set)
             .filter(|rr_set| rr_set.record_type() == RecordType::NSEC)
             .skip_while(|rr_set| name < rr_set.name())
             .next()
@@ -1040,11 +1589,210 @@ impl Authority {
             })
     }
 
+    /// Returns the records which prove that the name, or the type at the name, does not exist
+    ///
+    /// The proof is made of NSEC3 records if the zone has an NSEC3PARAM at its apex, see
+    ///  `get_nsec3_records`, otherwise of the NSEC record, see `get_nsec_records`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the lookup name
+    /// * `exists` - true if the name exists, and only the type does not, false if the name does
+    ///              not exist
+    /// * `is_secure` - if true then it will return RRSIG records as well
+    pub fn get_denial_records(
+        &self,
+        name: &Name,
+        exists: bool,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Vec<&Record> {
+        match self.get_nsec3_records(name, exists, is_secure, supported_algorithms) {
+            Some(records) => records,
+            None => self.get_nsec_records(name, is_secure, supported_algorithms),
+        }
+    }
+
+    /// Return the NSEC3 records which prove that the name, or the type at the name, does not
+    ///  exist, RFC 5155 section 7.2, None if the zone has no NSEC3PARAM
+    ///
+    /// If the name has an NSEC3 record, that is the proof that the type does not exist. Otherwise
+    ///  the proof is the NSEC3 record of the closest encloser, the one covering the next closer
+    ///  name, and, if the name does not exist, the one covering the wildcard at the closest
+    ///  encloser.
+    #[cfg(feature = "dnssec")]
+    fn get_nsec3_records(
+        &self,
+        name: &Name,
+        exists: bool,
+        is_secure: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Option<Vec<&Record>> {
+        let param_key = RrKey::new(&self.origin, RecordType::NSEC3PARAM);
+        let param = match self.records
+            .get_rrset(&param_key)
+            .and_then(|rrset| rrset.iter().next())
+            .map(Record::rdata) {
+            Some(&RData::NSEC3PARAM(ref param)) => param,
+            _ => return None,
+        };
+
+        // each iteration costs a hash of every name in the proof, RFC 9276 section 3.2
+        if param.iterations() > MAX_NSEC3_ITERATIONS {
+            warn!(
+                "not proving denial for {}, {} has more than {} NSEC3 iterations",
+                name,
+                self.origin,
+                MAX_NSEC3_ITERATIONS
+            );
+            return Some(vec![]);
+        }
+
+        let hash = |name: &Name| -> Option<Vec<u8>> {
+            match param.hash_algorithm().hash(param.salt(), name, param.iterations()) {
+                Ok(digest) => Some(digest.as_ref().to_vec()),
+                Err(e) => {
+                    warn!("could not hash {} for NSEC3: {}", name, e);
+                    None
+                }
+            }
+        };
+
+        let mut proof: Vec<&RecordSet> = vec![];
+        if let Some(rrset) = hash(name).and_then(|hash| self.matching_nsec3(&hash)) {
+            proof.push(rrset);
+        } else {
+            // find the closest encloser, the longest name above the lookup name in the chain
+            let mut next_closer = name.clone();
+            let mut closest_encloser = name.base_name();
+            let mut encloser_nsec3 = None;
+            while self.origin.zone_of(&closest_encloser) {
+                encloser_nsec3 = hash(&closest_encloser).and_then(
+                    |hash| self.matching_nsec3(&hash),
+                );
+                if encloser_nsec3.is_some() {
+                    break;
+                }
+
+                next_closer = closest_encloser.clone();
+                closest_encloser = closest_encloser.base_name();
+            }
+
+            proof.extend(encloser_nsec3);
+            proof.extend(hash(&next_closer).and_then(
+                |hash| self.covering_nsec3(&hash),
+            ));
+
+            if !exists {
+                let wildcard = Name::from_labels(vec!["*"]).append_domain(&closest_encloser);
+                proof.extend(hash(&wildcard).and_then(|hash| self.covering_nsec3(&hash)));
+            }
+        }
+
+        proof.sort_by(|a, b| a.name().cmp(b.name()));
+        proof.dedup_by(|a, b| a.name() == b.name());
+
+        Some(
+            proof
+                .into_iter()
+                .flat_map(|rrset| rrset.records(is_secure, supported_algorithms))
+                .collect(),
+        )
+    }
+
+    #[cfg(not(feature = "dnssec"))]
+    fn get_nsec3_records(
+        &self,
+        _name: &Name,
+        _exists: bool,
+        _is_secure: bool,
+        _supported_algorithms: SupportedAlgorithms,
+    ) -> Option<Vec<&Record>> {
+        None
+    }
+
+    /// The NSEC3 RRset of the hash, if it is in the chain
+    #[cfg(feature = "dnssec")]
+    fn matching_nsec3(&self, hash: &[u8]) -> Option<&RecordSet> {
+        let label = base32hex::encode(hash).to_lowercase();
+        let owner = Name::from_labels(vec![label]).append_domain(&self.origin);
+        self.records.get_rrset(&RrKey::new(&owner, RecordType::NSEC3))
+    }
+
+    /// The NSEC3 RRset which covers the hash, i.e. the hash falls between its owner and the next
+    ///  hashed owner name, the last NSEC3 covers the hashes after it and before the first
+    #[cfg(feature = "dnssec")]
+    fn covering_nsec3(&self, hash: &[u8]) -> Option<&RecordSet> {
+        // the NSEC3 before the hash, or the last one, which wraps around to the first
+        let (owner, key) = match self.nsec3_index
+            .range::<[u8], _>((Bound::Unbounded, Bound::Excluded(hash)))
+            .next_back()
+            .or_else(|| self.nsec3_index.iter().next_back()) {
+            Some(entry) => entry,
+            None => return None,
+        };
+
+        let rrset = match self.records.get_rrset(key) {
+            Some(rrset) => rrset,
+            None => return None,
+        };
+        let next = match rrset.iter().next().map(Record::rdata) {
+            Some(&RData::NSEC3(ref nsec3)) => nsec3.next_hashed_owner_name(),
+            _ => return None,
+        };
+
+        let is_covered = if owner.as_slice() < next {
+            owner.as_slice() < hash && hash < next
+        } else {
+            owner.as_slice() < hash || hash < next
+        };
+        if is_covered { Some(rrset) } else { None }
+    }
+
+    /// Rebuilds the index of the NSEC3 records by their hashes, for `covering_nsec3`
+    ///
+    /// This is called whenever the NSEC3 chain may have changed, i.e. when the zone is created,
+    ///  replaced or secured.
+    #[cfg(feature = "dnssec")]
+    fn index_nsec3(&mut self) {
+        let index: BTreeMap<Vec<u8>, RrKey> = self.records
+            .iter()
+            .filter(|&(key, _)| key.record_type == RecordType::NSEC3)
+            .filter_map(|(key, _)| {
+                key.name
+                    .iter()
+                    .next()
+                    .and_then(|label| base32hex::decode(label.to_uppercase().as_bytes()).ok())
+                    .map(|hash| (hash, key.clone()))
+            })
+            .collect();
+        self.nsec3_index = index;
+    }
+
+    #[cfg(not(feature = "dnssec"))]
+    fn index_nsec3(&mut self) {
+        self.nsec3_index.clear();
+    }
+
+    /// Returns true if the zone has an NSEC3 chain, whose proofs hash the names of each lookup
+    pub fn is_nsec3(&self) -> bool {
+        !self.nsec3_index.is_empty()
+    }
+
+    /// Returns true if the name exists in the zone, i.e. it has records, or it is an empty
+    ///  non-terminal, a name with records only below it
+    pub fn contains_name(&self, name: &Name) -> bool {
+        self.records.iter().any(|(key, rrset)| {
+            !rrset.is_empty() && name.zone_of(&key.name)
+        })
+    }
+
     /// (Re)generates the nsec records, increments the serial number nad signs the zone
     pub fn secure_zone(&mut self) -> DnsSecResult<()> {
         // TODO: only call nsec_zone after adds/deletes
         // needs to be called before incrementing the soa serial, to make sur IXFR works properly
-        self.nsec_zone();
+        try!(self.nsec_zone());
+        self.index_nsec3();
 
         // need to resign any records at the current serial number and bump the number.
         // first bump the serial number on the SOA, so that it is resigned with the new serial.
@@ -1054,63 +1802,92 @@ impl Authority {
         self.sign_zone()
     }
 
-    /// Creates all nsec records needed for the zone, replaces any existing records.
-    fn nsec_zone(&mut self) {
+    /// Creates all nsec, or nsec3, records needed for the zone, replaces any existing records.
+    fn nsec_zone(&mut self) -> DnsSecResult<()> {
         // only create nsec records for secure zones
         if self.secure_keys.is_empty() {
-            return;
+            return Ok(());
         }
         debug!("generating nsec records: {}", self.origin);
 
-        // first remove all existing nsec records
+        // first remove all existing nsec, nsec3 and nsec3param records
         let delete_keys: Vec<RrKey> = self.records
-            .keys()
-            .filter(|k| k.record_type == RecordType::NSEC)
+            .iter()
+            .map(|(k, _)| k)
+            .filter(|k| match k.record_type {
+                RecordType::NSEC | RecordType::NSEC3 | RecordType::NSEC3PARAM => true,
+                _ => false,
+            })
             .cloned()
             .collect();
 
         for key in delete_keys {
-            self.records.remove(&key);
+            try!(self.records.delete(&key).map_err(|e| {
+                DnsSecErrorKind::Msg(format!("could not delete {}: {}", key.name, e))
+            }));
         }
 
         // now go through and generate the nsec records
         let ttl = self.minimum_ttl();
         let serial = self.serial();
-        let mut records: Vec<Record> = vec![];
+        let records = match self.denial {
+            Denial::Nsec => self.nsec_records(ttl),
+            Denial::Nsec3 { .. } => try!(self.nsec3_records(ttl)),
+        };
 
-        {
-            let mut nsec_info: Option<(&Name, Vec<RecordType>)> = None;
-            for key in self.records.keys() {
-                match nsec_info {
-                    None => nsec_info = Some((&key.name, vec![key.record_type])),
-                    Some((name, ref mut vec)) if name == &key.name => vec.push(key.record_type),
-                    Some((name, vec)) => {
-                        // names aren't equal, create the NSEC record
-                        let mut record = Record::with(name.clone(), RecordType::NSEC, ttl);
-                        let rdata = NSEC::new(key.name.clone(), vec);
-                        record.set_rdata(RData::NSEC(rdata));
-                        records.push(record);
-
-                        // new record...
-                        nsec_info = Some((&key.name, vec![key.record_type]))
-                    }
-                }
-            }
+        // insert all the nsec records
+        for record in records {
+            self.upsert(record, serial);
+        }
 
-            // the last record
-            if let Some((name, vec)) = nsec_info {
-                // names aren't equal, create the NSEC record
-                let mut record = Record::with(name.clone(), RecordType::NSEC, ttl);
-                let rdata = NSEC::new(self.origin().clone(), vec);
-                record.set_rdata(RData::NSEC(rdata));
-                records.push(record);
+        Ok(())
+    }
+
+    /// The NSEC records of the zone, each linking a name to the next, the last to the origin
+    fn nsec_records(&self, ttl: u32) -> Vec<Record> {
+        let mut records: Vec<Record> = vec![];
+        let mut nsec_info: Option<(&Name, Vec<RecordType>)> = None;
+        for (key, _) in self.records.iter() {
+            match nsec_info {
+                None => nsec_info = Some((&key.name, vec![key.record_type])),
+                Some((name, ref mut vec)) if name == &key.name => vec.push(key.record_type),
+                Some((name, vec)) => {
+                    // names aren't equal, create the NSEC record
+                    let mut record = Record::with(name.clone(), RecordType::NSEC, ttl);
+                    let rdata = NSEC::new(key.name.clone(), vec);
+                    record.set_rdata(RData::NSEC(rdata));
+                    records.push(record);
+
+                    // new record...
+                    nsec_info = Some((&key.name, vec![key.record_type]))
+                }
             }
         }
 
-        // insert all the nsec records
-        for record in records {
-            self.upsert(record, serial);
+        // the last record
+        if let Some((name, vec)) = nsec_info {
+            // names aren't equal, create the NSEC record
+            let mut record = Record::with(name.clone(), RecordType::NSEC, ttl);
+            let rdata = NSEC::new(self.origin().clone(), vec);
+            record.set_rdata(RData::NSEC(rdata));
+            records.push(record);
         }
+
+        records
+    }
+
+    /// The NSEC3 records of the zone, with the empty non-terminals, and its NSEC3PARAM, see
+    ///  `Denial::Nsec3`
+    #[cfg(feature = "dnssec")]
+    fn nsec3_records(&self, ttl: u32) -> DnsSecResult<Vec<Record>> {
+        zone_signer::denial_records(&self.origin, &self.records.to_map(), &self.denial, ttl)
+    }
+
+    #[cfg(not(feature = "dnssec"))]
+    fn nsec3_records(&self, _ttl: u32) -> DnsSecResult<Vec<Record>> {
+        Err(
+            DnsSecErrorKind::Msg("NSEC3 requires the dnssec feature".to_string()).into(),
+        )
     }
 
     /// Signs any records in the zone that have serial numbers greater than or equal to `serial`
@@ -1125,7 +1902,8 @@ impl Authority {
         }
 
         // sign all record_sets, as of 0.12.1 this includes DNSKEY
-        for (_, rr_set) in self.records.iter_mut() {
+        // the RRsets are signed apart from the store, and then written back to it
+        for (key, mut rr_set) in self.records.to_map() {
             rr_set.clear_rrsigs();
             let rrsig_temp = Record::with(rr_set.name().clone(), RecordType::RRSIG, zone_ttl);
 
@@ -1201,8 +1979,31 @@ impl Authority {
 
                 rr_set.insert_rrsig(rrsig);
             }
+
+            try!(self.records.upsert(key, rr_set).map_err(|e| {
+                DnsSecErrorKind::Msg(format!("could not write signatures: {}", e))
+            }));
         }
 
         Ok(())
     }
 }
+
+/// The response to an update which could not be written to the store
+fn write_failed(error: PersistenceError) -> ResponseCode {
+    error!("could not write update: {}", error);
+    ResponseCode::ServFail
+}
+
+/// Returns true for the types of the DNSSEC records which a signed zone generates, see
+///  `Authority::to_zone_file`
+fn is_dnssec_type(record_type: RecordType) -> bool {
+    match record_type {
+        RecordType::DNSKEY |
+        RecordType::NSEC |
+        RecordType::NSEC3 |
+        RecordType::NSEC3PARAM |
+        RecordType::RRSIG => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A zone transfer which is read from the zone as it is sent, see `AxfrStream`
+
+use std::io;
+use std::mem;
+use std::sync::{Arc, RwLock};
+
+use futures::{Async, Poll, Stream};
+use trust_dns::op::{Message, MessageType, OpCode, ResponseCode};
+use trust_dns::rr::{Record, RecordType, RrKey};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::serialize::binary::{BinEncoder, BinSerializable, NameCompression};
+
+use authority::{Authority, ResponsePolicy, Zone};
+
+/// Where the next message of an `AxfrStream` starts
+enum Position {
+    /// The first message, which starts with the SOA
+    Start,
+    /// At the record of the index in the records of the RRset of the key, see
+    ///  `RecordSet::records`
+    At(RrKey, usize),
+    /// Only the closing SOA is left
+    End,
+    /// The whole zone was sent
+    Done,
+}
+
+/// The messages of a zone transfer, AXFR, RFC 5936 section 2.2, each read from the zone only when
+///  it is polled
+///
+/// Each message holds at most `max_len` octets of records, and the zone is locked only while one
+///  message is read, so that neither is the whole zone copied, nor are the updates to the zone held
+///  up while it is sent. Each message resumes where the one before it ended, the zone is sent from
+///  its SOA to its SOA again. If the serial of the zone changes before the last message, the
+///  transfer fails with a `ServFail`, RFC 5936 section 2.2, as the client would otherwise have
+///  parts of two versions of the zone.
+pub(crate) struct AxfrStream {
+    zone: Arc<RwLock<Zone>>,
+    request: Message,
+    serial: u32,
+    is_dnssec: bool,
+    supported_algorithms: SupportedAlgorithms,
+    policy: Option<ResponsePolicy>,
+    max_len: usize,
+    position: Position,
+    buffer: Vec<u8>,
+}
+
+impl AxfrStream {
+    /// Returns the transfer of the zone, which is read from its serial when it is first polled
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the zone which is transferred
+    /// * `request` - the AXFR request, whose question is in the first message
+    /// * `is_dnssec` - if true, the RRSIGs of the records are sent too
+    /// * `supported_algorithms` - the algorithms of the RRSIGs which are sent
+    /// * `policy` - the response policy of the zone, which hides records and clamps their TTLs
+    /// * `max_len` - the encoded length of the records of each message
+    pub(crate) fn new(
+        zone: Arc<RwLock<Zone>>,
+        request: &Message,
+        is_dnssec: bool,
+        supported_algorithms: SupportedAlgorithms,
+        policy: Option<ResponsePolicy>,
+        max_len: usize,
+    ) -> Self {
+        AxfrStream {
+            zone: zone,
+            request: request.clone(),
+            serial: 0,
+            is_dnssec: is_dnssec,
+            supported_algorithms: supported_algorithms,
+            policy: policy,
+            max_len: max_len,
+            position: Position::Start,
+            buffer: Vec::with_capacity(512),
+        }
+    }
+
+    /// Reads the next message from the zone, or a `ServFail` if the zone changed
+    fn next_message(&mut self) -> Message {
+        let zone = self.zone.clone();
+        let zone = zone.read().unwrap(); // poison errors should panic
+
+        let position = mem::replace(&mut self.position, Position::Done);
+        let is_first = match position {
+            Position::Start => {
+                self.serial = zone.serial();
+                true
+            }
+            _ => false,
+        };
+        if zone.serial() != self.serial {
+            warn!(
+                "zone {} changed from serial {} to {} during transfer for request {}",
+                zone.origin(),
+                self.serial,
+                zone.serial(),
+                self.request.id()
+            );
+            return self.failed();
+        }
+
+        let (records, position) = match zone.soa() {
+            Some(soa) => self.read_records(&zone, soa, position),
+            None => {
+                warn!("zone {} has no SOA, transfer failed", zone.origin());
+                return self.failed();
+            }
+        };
+        self.position = position;
+
+        self.message(is_first, records)
+    }
+
+    /// The message which fails the transfer, the messages already sent can not be taken back
+    fn failed(&self) -> Message {
+        Message::error_msg(self.request.id(), self.request.op_code(), ResponseCode::ServFail)
+    }
+
+    /// Reads the records of one message from the position on, and returns them with the position
+    ///  at which the next message starts
+    fn read_records(
+        &mut self,
+        authority: &Authority,
+        soa: &Record,
+        position: Position,
+    ) -> (Vec<Record>, Position) {
+        let mut records: Vec<Record> = Vec::new();
+        let mut len = 0;
+
+        let resume = match position {
+            Position::Start => {
+                self.take(&mut records, &mut len, soa);
+                None
+            }
+            Position::At(key, index) => Some((key, index)),
+            Position::End => {
+                self.take(&mut records, &mut len, soa);
+                return (records, Position::Done);
+            }
+            Position::Done => return (records, Position::Done),
+        };
+
+        let rrsets = match resume {
+            Some((ref key, _)) => authority.records().iter_from(key),
+            None => authority.records().iter(),
+        };
+        for (key, rrset) in rrsets {
+            if rrset.record_type() == RecordType::SOA {
+                continue;
+            }
+
+            let skip = match resume {
+                Some((ref resume_key, index)) if resume_key == key => index,
+                _ => 0,
+            };
+            let rrset_records = rrset.records(self.is_dnssec, self.supported_algorithms);
+            for (index, record) in rrset_records.into_iter().enumerate().skip(skip) {
+                if !self.take(&mut records, &mut len, record) {
+                    return (records, Position::At(key.clone(), index));
+                }
+            }
+        }
+
+        if !self.take(&mut records, &mut len, soa) {
+            return (records, Position::End);
+        }
+        (records, Position::Done)
+    }
+
+    /// Adds the record to those of the message, unless it is hidden by the response policy,
+    ///  returns false if it does not fit in the message
+    fn take(&mut self, records: &mut Vec<Record>, len: &mut usize, record: &Record) -> bool {
+        if self.policy.as_ref().map_or(false, |policy| policy.is_hidden(record)) {
+            return true;
+        }
+
+        let record_len = encoded_len(record, &mut self.buffer);
+        if !records.is_empty() && *len + record_len > self.max_len {
+            return false;
+        }
+
+        records.push(record.clone());
+        *len += record_len;
+        true
+    }
+
+    /// One message of the transfer, with the records in its answer section
+    ///
+    /// Only the first message has the question, RFC 5936 section 2.2.1.
+    fn message(&self, is_first: bool, mut records: Vec<Record>) -> Message {
+        let request = &self.request;
+        let mut response: Message = Message::new();
+        response.set_id(request.id());
+        response.set_op_code(OpCode::Query);
+        response.set_message_type(MessageType::Response);
+        response.set_authoritative(true);
+        if is_first {
+            response.add_queries(request.queries().into_iter().cloned());
+        }
+
+        if let Some(ref policy) = self.policy {
+            policy.clamp_ttls(&mut records);
+        }
+        let inserted = response.answers_mut().insert_all(records).map(|_| ());
+        if let Err(e) = inserted {
+            warn!("malformed zone transfer for request {}: {}", request.id(), e);
+            response = self.failed();
+        }
+
+        response
+    }
+}
+
+impl Stream for AxfrStream {
+    type Item = Message;
+    type Error = io::Error;
+
+    /// Reads the next message, the transfer ends after the closing SOA, or a `ServFail`
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if let Position::Done = self.position {
+            return Ok(Async::Ready(None));
+        }
+
+        Ok(Async::Ready(Some(self.next_message())))
+    }
+}
+
+/// The length of the record encoded without name compression, which is the most it takes in a
+///  message, or 0 if it can not be encoded
+fn encoded_len(record: &Record, buffer: &mut Vec<u8>) -> usize {
+    buffer.clear();
+    let mut encoder = BinEncoder::new(buffer);
+    encoder.set_name_compression(NameCompression::Disabled);
+    match record.emit(&mut encoder) {
+        Ok(()) => encoder.len(),
+        Err(_) => 0,
+    }
+}
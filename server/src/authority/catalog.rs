@@ -17,28 +17,335 @@
 //  should be the only "front-end" for lookups, where if that misses, then we go to the catalog
 //  then, if requested, do a recursive lookup... i.e. the catalog would only point to files.
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock};
 
+#[cfg(feature = "dnssec")]
+use chrono::DateTime;
+use chrono::Utc;
+use futures::{future, stream, Future, Stream};
+use tokio_core::reactor::Handle;
 use trust_dns::op::{Edns, Message, MessageType, OpCode, Query, UpdateMessage, ResponseCode};
-use trust_dns::rr::{Name, RecordType};
-use trust_dns::rr::dnssec::{Algorithm, SupportedAlgorithms};
-use trust_dns::rr::rdata::opt::{EdnsCode, EdnsOption};
+use trust_dns::rr::{DNSClass, Name, RData, Record, RecordType};
+use trust_dns::rr::dnssec::{time_other, Algorithm, SupportedAlgorithms, TSigner};
+use trust_dns::rr::rdata::{TSIG, TXT};
+use trust_dns::rr::rdata::tsig::{BADKEY, BADSIG, BADTIME};
+use trust_dns::rr::rdata::opt::{Cookie, EdnsCode, EdnsOption, ExtendedError, ExtendedErrorCode};
+use trust_dns_proto::error::ProtoErrorKind;
 use server::{Request, RequestHandler};
 
-use authority::{Authority, ZoneType};
+use authority::{Authority, ChangeHook, ResponsePolicy, Zone, ZoneType};
+use authority::axfr_stream::AxfrStream;
+use authority::change_hook;
+use authority::notifier::{self, Notifies, NotifyChange, NotifyRecorder, NotifyRetry};
+use authority::refresher::{self, Refreshes};
+use authority::response_cache::{CachedAnswer, ResponseCache, ResponseKey};
+use authority::reverse_zone;
+use authority::update_forwarder::{self, UpdateForwarder};
+use authority::zone_index::ZoneIndex;
+use authority::zone_stats::{TransferStatus, ZoneHealth, ZoneHealthStatus, ZoneStats};
 
 /// Set of authorities, zones, available to this server.
+///
+/// Zones may be added, removed and replaced while the server is running, see `add_zone`. Each
+///  query is answered from the version of the zone it found, even if the zone is removed or
+///  replaced while it is being answered.
 pub struct Catalog {
-    authorities: HashMap<Name, RwLock<Authority>>,
+    authorities: RwLock<ZoneIndex<Arc<RwLock<Zone>>>>,
+    failed_zones: RwLock<HashMap<Name, String>>,
+    response_cache: ResponseCache,
+    zone_stats: Arc<Mutex<HashMap<Name, ZoneStats>>>,
+    primaries: HashMap<Name, SocketAddr>,
+    also_notify: HashMap<Name, Vec<SocketAddr>>,
+    notify_retry: NotifyRetry,
+    notifies: Notifies,
+    refreshes: Refreshes,
+    update_forwarder: UpdateForwarder,
+    response_policies: HashMap<Name, ResponsePolicy>,
+    cookie_secret: RandomState,
+    require_cookies: bool,
+    health_query: bool,
+    tsig_keys: HashMap<Name, TSigner>,
+    change_hooks: Vec<Arc<ChangeHook>>,
+    // the forward zones of each reverse zone, and the PTR records generated from them
+    reverse_zones: HashMap<Name, Vec<Name>>,
+    generated_ptrs: Mutex<HashMap<Name, Vec<Record>>>,
 }
 
+/// The encoded length of the records of each message of a zone transfer, well within the 65535
+///  octets of a message over TCP, RFC 1035 section 4.2.2, with room for the header, the question,
+///  the EDNS and the TSIG
+const AXFR_MESSAGE_LEN: usize = 16 * 1024;
+
 impl RequestHandler for Catalog {
     /// Determine's what needs to happen given the type of request, i.e. Query or Update.
     ///
+    /// A zone transfer is answered in one message, see `response_stream`.
+    ///
     /// # Arguments
     ///
     /// * `request` - the requested action to perform.
     fn handle_request(&self, request: &Request) -> Message {
+        let mut finisher = match self.prepare(request) {
+            Ok(finisher) => finisher,
+            Err(response) => return response,
+        };
+
+        finisher.finish(self.answer(request))
+    }
+
+    /// The records of a zone transfer, AXFR, are read from the zone as they are sent, in messages
+    ///  of at most `AXFR_MESSAGE_LEN` octets of records each, RFC 5936 section 2.2, see
+    ///  `AxfrStream`, rather than assembled in one message first, so that transfers of large zones
+    ///  neither hold a copy of the whole zone, nor hold up the other requests while they are
+    ///  encoded. Such a transfer is not cached, see `set_response_cache_size`.
+    ///
+    /// Every transfer is answered by the stream, once its request is verified, the transfers
+    ///  which fail before the zone is read, e.g. those with a bad TSIG, or of zones which are not
+    ///  served, with the one message of the error, so that the request is verified only once.
+    ///  The messages to requests signed with TSIG are signed as they are produced, which the
+    ///  `ServerFuture` does on its worker pool, see `is_cpu_intensive`.
+    fn response_stream(
+        &self,
+        request: &Request,
+    ) -> Option<Box<Stream<Item = Message, Error = io::Error> + Send>> {
+        let request_message = &request.message;
+        if request_message.message_type() != MessageType::Query ||
+            request_message.op_code() != OpCode::Query ||
+            !request_message.queries().iter().any(|query| {
+                query.query_type() == RecordType::AXFR
+            })
+        {
+            return None;
+        }
+
+        let mut finisher = match self.prepare(request) {
+            Ok(finisher) => finisher,
+            Err(response) => return Some(Box::new(stream::once(Ok(response)))),
+        };
+        let responses: Box<Stream<Item = Message, Error = io::Error> + Send> =
+            match self.stream_axfr(request_message, AXFR_MESSAGE_LEN) {
+                Some(responses) => Box::new(responses),
+                None => Box::new(stream::once(Ok(self.answer(request)))),
+            };
+
+        Some(Box::new(
+            responses.map(move |response| finisher.finish(response)),
+        ))
+    }
+
+    /// Updates to a secondary are forwarded to the primary, RFC 2136 section 6, as they are, the
+    ///  primary verifies their signatures. The primary's response is returned as it is. Unsigned
+    ///  updates are refused rather than forwarded, see `reserve_forward`.
+    fn forward_request(
+        &self,
+        request: &Request,
+        handle: &Handle,
+    ) -> Option<Box<Future<Item = Message, Error = io::Error>>> {
+        let message = &request.message;
+        if message.message_type() != MessageType::Query || message.op_code() != OpCode::Update {
+            return None;
+        }
+
+        self.forward_target(message).map(|(origin, configured, mname)| {
+            self.forward_update(message, origin, configured, mname, handle)
+        })
+    }
+
+    /// Updates require verification of the SIG0 signature of the request, and for DNSSEC enabled
+    ///  zones the resigning of the zone, both of which are expensive. Requests signed with TSIG
+    ///  are verified, and their responses signed, each message of a zone transfer, and the denial
+    ///  of existence for DNSSEC queries to zones with an NSEC3 chain hashes several names, see
+    ///  `Authority::get_denial_records`. The messages of unsigned zone transfers are only read
+    ///  from the zone, one at a time, see `response_stream`.
+    fn is_cpu_intensive(&self, request: &Request) -> bool {
+        if request.message.message_type() != MessageType::Query {
+            return false;
+        }
+        if request.message.tsig().is_some() {
+            return true;
+        }
+
+        match request.message.op_code() {
+            OpCode::Update => true,
+            OpCode::Query => {
+                let is_dnssec = request.message.edns().map_or(false, Edns::dnssec_ok);
+                is_dnssec &&
+                    request.message.queries().iter().any(|query| {
+                        self.is_nsec3_zone(query.name())
+                    })
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Catalog {
+    /// Constructs a new Catalog
+    pub fn new() -> Self {
+        Catalog {
+            authorities: RwLock::new(ZoneIndex::new()),
+            failed_zones: RwLock::new(HashMap::new()),
+            response_cache: ResponseCache::new(0),
+            zone_stats: Arc::new(Mutex::new(HashMap::new())),
+            primaries: HashMap::new(),
+            also_notify: HashMap::new(),
+            notify_retry: NotifyRetry::default(),
+            notifies: Notifies::new(),
+            refreshes: Refreshes::new(),
+            update_forwarder: UpdateForwarder::new(),
+            response_policies: HashMap::new(),
+            cookie_secret: RandomState::new(),
+            require_cookies: false,
+            health_query: false,
+            tsig_keys: HashMap::new(),
+            change_hooks: Vec::new(),
+            reverse_zones: HashMap::new(),
+            generated_ptrs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets whether queries with a client cookie, but without a valid server cookie, are answered
+    ///  with BADCOOKIE rather than with the answer, RFC 7873 section 5.2.3
+    ///
+    /// Either way every query with a client cookie gets a fresh server cookie in its response, and
+    ///  queries without any cookie are answered, for the clients which do not support them.
+    pub fn set_require_cookies(&mut self, require_cookies: bool) {
+        self.require_cookies = require_cookies;
+    }
+
+    /// Sets whether a TXT query for `health.server.` in the CHAOS class is answered with the
+    ///  health of each zone, see `health`, false by default
+    ///
+    /// Each zone is one TXT record, of the zone, its status, and if it loaded its serial and the
+    ///  seconds since it was last refreshed. The answer lists the names of the zones to anyone
+    ///  who can query the server, which is why it is not enabled by default.
+    pub fn set_health_query(&mut self, health_query: bool) {
+        self.health_query = health_query;
+    }
+
+    /// Adds a TSIG key, RFC 8945, requests signed with it are verified, and their responses
+    ///  signed with it. Requests signed with unknown keys are answered with NotAuth.
+    ///
+    /// The key only authenticates the requests, updates must also be allowed for the key by the
+    ///  zone, see `Authority::add_update_tsig_key`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tsigner` - the key, by its name, which replaces any key of the same name
+    pub fn add_tsig_key(&mut self, tsigner: TSigner) {
+        self.tsig_keys.insert(tsigner.key_name().clone(), tsigner);
+    }
+
+    /// Verifies the TSIG of the request, if it is signed, RFC 8945 section 5.2
+    ///
+    /// Returns the key and the MAC of the request to sign the response with, or the response to a
+    ///  request which failed verification.
+    fn verify_tsig(&self, request: &Message) -> Result<Option<(&TSigner, Vec<u8>)>, Message> {
+        let (key_name, tsig) = match request.tsig() {
+            Some(record) => {
+                match *record.rdata() {
+                    RData::TSIG(ref tsig) => (record.name(), tsig),
+                    _ => return Ok(None),
+                }
+            }
+            None => return Ok(None),
+        };
+
+        let tsigner = match self.tsig_keys.get(key_name) {
+            Some(tsigner) => tsigner,
+            None => {
+                warn!("unknown TSIG key {} in request: {}", key_name, request.id());
+                return Err(Self::tsig_error_msg(request, key_name, tsig, BADKEY));
+            }
+        };
+
+        let current_time = Utc::now().timestamp() as u64;
+        let error = match tsigner.verify_message(request, None, current_time) {
+            Ok(_) => return Ok(Some((tsigner, tsig.mac().to_vec()))),
+            Err(e) => {
+                warn!("TSIG of request {} not verified: {}", request.id(), e);
+                match *e.kind() {
+                    ProtoErrorKind::Tsig(error) => error,
+                    _ => BADSIG,
+                }
+            }
+        };
+
+        if error != BADTIME {
+            return Err(Self::tsig_error_msg(request, key_name, tsig, error));
+        }
+
+        // the MAC was valid, so the response is signed, with the time of the server for the client
+        //  to adjust, RFC 8945 section 5.2.3
+        let mut response =
+            Message::error_msg(request.id(), request.op_code(), ResponseCode::NotAuth);
+        response.add_queries(request.queries().to_vec());
+        match tsigner.sign_message(
+            &response,
+            Some(tsig.mac()),
+            tsig.time_signed(),
+            BADTIME,
+            time_other(current_time),
+        ) {
+            Ok(tsig) => {
+                response.add_sig0(tsig);
+            }
+            Err(e) => error!("could not sign response {}: {}", response.id(), e),
+        }
+        Err(response)
+    }
+
+    /// The unsigned NotAuth response to a request with an unknown key or an invalid MAC, RFC 8945
+    ///  section 5.3.2
+    fn tsig_error_msg(request: &Message, key_name: &Name, tsig: &TSIG, error: u16) -> Message {
+        let mut response =
+            Message::error_msg(request.id(), request.op_code(), ResponseCode::NotAuth);
+        response.add_queries(request.queries().to_vec());
+
+        let tsig = TSIG::new(
+            tsig.algorithm().clone(),
+            tsig.time_signed(),
+            tsig.fudge(),
+            vec![],
+            tsig.original_id(),
+            error,
+            vec![],
+        );
+        let mut record =
+            Record::from_rdata(key_name.clone(), 0, RecordType::TSIG, RData::TSIG(tsig));
+        record.set_dns_class(DNSClass::ANY);
+        response.add_sig0(record);
+        response
+    }
+
+    /// Returns the server cookie of the client, RFC 7873 section 4.2
+    ///
+    /// The cookie is a keyed hash of the client cookie and the address of the client, the key is
+    ///  random per Catalog, so the cookies change when the server restarts.
+    fn server_cookie(&self, client_cookie: &[u8; 8], client_ip: IpAddr) -> [u8; 8] {
+        let mut hasher = self.cookie_secret.build_hasher();
+        client_cookie.hash(&mut hasher);
+        client_ip.hash(&mut hasher);
+
+        let hash = hasher.finish();
+        let mut server_cookie = [0u8; 8];
+        for (i, octet) in server_cookie.iter_mut().enumerate() {
+            *octet = (hash >> (56 - i * 8)) as u8;
+        }
+        server_cookie
+    }
+
+    /// Verifies the request and reads its EDNS, and returns what finishes each response to it, or
+    ///  the response to a request which is answered before it is handled, e.g. with a bad TSIG
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the requested action to perform.
+    fn prepare(&self, request: &Request) -> Result<ResponseFinisher, Message> {
         let request_message = &request.message;
         info!(
             "request id: {} type: {:?} op_code: {:?}",
@@ -48,13 +355,16 @@ impl RequestHandler for Catalog {
         );
         trace!("request: {:?}", request_message);
 
+        // TSIG, RFC 8945 section 5.2, the request is verified before anything else, and the
+        //  response is signed with the key of the request
+        let request_tsig = try!(self.verify_tsig(request_message)).map(
+            |(tsigner, mac)| (tsigner.clone(), mac),
+        );
+
         let mut resp_edns_opt: Option<Edns> = None;
 
         // check if it's edns
         if let Some(req_edns) = request_message.edns() {
-            let mut response = Message::new();
-            response.set_id(request_message.id());
-
             let mut resp_edns: Edns = Edns::new();
 
             // check our version against the request
@@ -74,9 +384,63 @@ impl RequestHandler for Catalog {
                     our_version,
                     req_edns.version()
                 );
-                response.set_response_code(ResponseCode::BADVERS);
-                response.set_edns(resp_edns);
-                return response;
+                return Err(Self::early_error(
+                    request_message,
+                    ResponseCode::BADVERS,
+                    resp_edns,
+                    request_tsig,
+                ));
+            }
+
+            // an ECS option which could not be read is a FormErr, RFC 7871 section 7.1.2
+            if let Some(&EdnsOption::Unknown(..)) = req_edns.option(&EdnsCode::Subnet) {
+                warn!("malformed client subnet in request: {}", request_message.id());
+                return Err(Self::early_error(
+                    request_message,
+                    ResponseCode::FormErr,
+                    resp_edns,
+                    request_tsig,
+                ));
+            }
+
+            // DNS Cookies, RFC 7873 section 5.2, a fresh server cookie is returned to every client
+            //  which sent a cookie
+            match req_edns.option(&EdnsCode::Cookie) {
+                Some(&EdnsOption::Cookie(ref cookie)) => {
+                    let server_cookie = self.server_cookie(cookie.client(), request.src.ip());
+                    let is_valid = cookie.server() == Some(&server_cookie[..]);
+                    resp_edns.set_option(EdnsOption::Cookie(
+                        Cookie::new(*cookie.client(), Some(server_cookie.to_vec())),
+                    ));
+
+                    if !is_valid && self.require_cookies {
+                        debug!("bad or missing server cookie in request: {}", request_message.id());
+                        return Err(Self::early_error(
+                            request_message,
+                            ResponseCode::BADCOOKIE,
+                            resp_edns,
+                            request_tsig,
+                        ));
+                    }
+                }
+                Some(_) => {
+                    warn!("malformed cookie in request: {}", request_message.id());
+                    return Err(Self::early_error(
+                        request_message,
+                        ResponseCode::FormErr,
+                        resp_edns,
+                        request_tsig,
+                    ));
+                }
+                None => (),
+            }
+
+            // the answers of the zones are the same for all clients, so the subnet is echoed with
+            //  a scope of 0, RFC 7871 section 7.2.1
+            if let Some(client_subnet) = req_edns.client_subnet() {
+                let mut client_subnet = *client_subnet;
+                client_subnet.set_scope_prefix(0);
+                resp_edns.set_option(EdnsOption::Subnet(client_subnet));
             }
 
             // TODO: add padding for private key hashing, need better knowledge of the length of the
@@ -86,7 +450,41 @@ impl RequestHandler for Catalog {
             resp_edns_opt = Some(resp_edns);
         }
 
-        let mut response: Message = match request_message.message_type() {
+        Ok(ResponseFinisher {
+            resp_edns: resp_edns_opt,
+            request_tsig: request_tsig,
+            prior_mac: None,
+        })
+    }
+
+    /// The response to a request which is answered before it is handled, e.g. with BADCOOKIE, see
+    ///  `prepare`
+    ///
+    /// The response echoes the question of the request, so that the client matches it to the
+    ///  request, RFC 7873 section 5.3, and is finished as any other response, so that it is
+    ///  signed with the TSIG key of the request.
+    fn early_error(
+        request: &Message,
+        response_code: ResponseCode,
+        resp_edns: Edns,
+        request_tsig: Option<(TSigner, Vec<u8>)>,
+    ) -> Message {
+        let mut response = Message::error_msg(request.id(), request.op_code(), response_code);
+        response.add_queries(request.queries().to_vec());
+
+        let mut finisher = ResponseFinisher {
+            resp_edns: Some(resp_edns),
+            request_tsig: request_tsig,
+            prior_mac: None,
+        };
+        finisher.finish(response)
+    }
+
+    /// The answer to a request which was verified, see `prepare`, by its type and op code, before
+    ///  it is finished
+    fn answer(&self, request: &Request) -> Message {
+        let request_message = &request.message;
+        match request_message.message_type() {
             // TODO think about threading query lookups for multiple lookups, this could be a huge improvement
             //  especially for recursive lookups
             MessageType::Query => {
@@ -103,6 +501,11 @@ impl RequestHandler for Catalog {
                         trace!("update response: {:?}", response);
                         response
                     }
+                    OpCode::Notify => {
+                        let response = self.handle_notify(request_message, request.src.ip());
+                        trace!("notify response: {:?}", response);
+                        response
+                    }
                     c @ _ => {
                         error!("unimplemented op_code: {:?}", c);
                         Message::error_msg(
@@ -124,37 +527,56 @@ impl RequestHandler for Catalog {
                     ResponseCode::NotImp,
                 )
             }
-        };
+        }
+    }
 
-        if let Some(mut resp_edns) = resp_edns_opt {
-            // set edns DAU and DHU
-            // send along the algorithms which are supported by this authority
-            let mut algorithms = SupportedAlgorithms::new();
-            algorithms.set(Algorithm::RSASHA256);
-            algorithms.set(Algorithm::ECDSAP256SHA256);
-            algorithms.set(Algorithm::ECDSAP384SHA384);
-            algorithms.set(Algorithm::ED25519);
+    /// Sets the EDNS of the response, with the algorithms which are supported by this server, and
+    ///  the extended error of the response, if it has one
+    fn set_response_edns(response: &mut Message, mut resp_edns: Edns) {
+        // set edns DAU and DHU
+        // send along the algorithms which are supported by this authority
+        let mut algorithms = SupportedAlgorithms::new();
+        algorithms.set(Algorithm::RSASHA256);
+        algorithms.set(Algorithm::ECDSAP256SHA256);
+        algorithms.set(Algorithm::ECDSAP384SHA384);
+        algorithms.set(Algorithm::ED25519);
 
-            let dau = EdnsOption::DAU(algorithms);
-            let dhu = EdnsOption::DHU(algorithms);
+        let dau = EdnsOption::DAU(algorithms);
+        let dhu = EdnsOption::DHU(algorithms);
 
-            resp_edns.set_option(dau);
-            resp_edns.set_option(dhu);
+        resp_edns.set_option(dau);
+        resp_edns.set_option(dhu);
 
-            response.set_edns(resp_edns);
-            // TODO: if DNSSec supported, sign the package with SIG0
-            // get this servers private key ideally use pkcs11
-            // sign response and then add SIG0 or TSIG to response
+        // e.g. the reason of a failure, see `set_extended_error`
+        if let Some(error) = response.edns().and_then(|edns| edns.extended_error()) {
+            resp_edns.set_option(EdnsOption::ExtendedError(error.clone()));
         }
 
-        response
+        // the upper bits of an extended response code, e.g. BADCOOKIE, are kept in the EDNS
+        let response_code = response.response_code();
+        response.set_edns(resp_edns);
+        response.set_response_code(response_code);
+        // TODO: if DNSSec supported, sign the package with SIG0
+        // get this servers private key ideally use pkcs11
+        // sign response and then add SIG0 or TSIG to response
     }
-}
 
-impl Catalog {
-    /// Constructs a new Catalog
-    pub fn new() -> Self {
-        Catalog { authorities: HashMap::new() }
+    /// Enables caching of assembled answers, a size of 0 disables the cache (the default)
+    ///
+    /// Answers are cached per query name, type and class, and the DNSSEC options of the request.
+    ///  Any change to a zone, i.e. a change in the SOA serial, an update, or replacing the zone,
+    ///  invalidates the cached answers for that zone.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - maximum number of answers to retain
+    pub fn set_response_cache_size(&mut self, size: usize) {
+        self.response_cache = ResponseCache::new(size);
+    }
+
+    /// Returns the maximum number of answers which will be cached
+    pub fn response_cache_size(&self) -> usize {
+        self.response_cache.capacity()
     }
 
     /// Insert or update a zone authority
@@ -162,9 +584,460 @@ impl Catalog {
     /// # Arguments
     ///
     /// * `name` - zone name, e.g. example.com.
-    /// * `authority` - the zone data
-    pub fn upsert(&mut self, name: Name, authority: Authority) {
-        self.authorities.insert(name, RwLock::new(authority));
+    /// * `authority` - the zone data, an `Authority` or a `SigningAuthority`
+    pub fn upsert<A: Into<Zone>>(&mut self, name: Name, authority: A) {
+        let mut authority = authority.into();
+        self.add_change_hooks(&mut authority);
+        self.response_cache.invalidate_zone(&name);
+        self.clear_failed(&name);
+        self.with_zone_stats(&name, |stats| stats.record_refresh());
+        self.authorities
+            .write()
+            .unwrap() // poison errors should panic...
+            .insert(name.clone(), Arc::new(RwLock::new(authority)));
+        self.sync_reverse_zones(&name);
+    }
+
+    /// Adds a zone to the running server, returns false if the zone is already being served, see
+    ///  `replace` for changing a zone
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com.
+    /// * `authority` - the zone data, an `Authority` or a `SigningAuthority`
+    pub fn add_zone<A: Into<Zone>>(&self, name: Name, authority: A) -> bool {
+        let mut authority = authority.into();
+        self.add_change_hooks(&mut authority);
+        {
+            let mut authorities = self.authorities.write().unwrap(); // poison errors should panic...
+            if authorities.contains_key(&name) {
+                return false;
+            }
+
+            authorities.insert(name.clone(), Arc::new(RwLock::new(authority)));
+        }
+
+        info!("added zone: {}", name);
+        self.clear_failed(&name);
+        self.with_zone_stats(&name, |stats| stats.record_refresh());
+        self.sync_reverse_zones(&name);
+        true
+    }
+
+    /// Removes a zone from the running server, returns false if the zone was not being served
+    ///
+    /// Its names are then answered by the closest enclosing zone, if there is one. Queries which
+    ///  already found the zone are still answered from it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com.
+    pub fn remove_zone(&self, name: &Name) -> bool {
+        let removed = self.authorities
+            .write()
+            .unwrap() // poison errors should panic...
+            .remove(name)
+            .is_some();
+        if !removed {
+            return false;
+        }
+
+        info!("removed zone: {}", name);
+        self.response_cache.invalidate_zone(name);
+        if let Ok(mut zone_stats) = self.zone_stats.lock() {
+            zone_stats.remove(name);
+        }
+        self.sync_reverse_zones(name);
+        true
+    }
+
+    /// Returns the names of the zones being served, in no particular order
+    pub fn zone_names(&self) -> Vec<Name> {
+        self.authorities
+            .read()
+            .unwrap() // poison errors should panic
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Replaces the records of a zone which is being served, e.g. with those of its reloaded zone
+    ///  file, returns false if the zone is not being served
+    ///
+    /// Unlike `upsert` this only needs a shared reference, so the zones can be replaced while the
+    ///  server is running.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com.
+    /// * `authority` - the new zone data
+    ///
+    /// The new records of a zone which is signed as it changes, see `SigningAuthority`, are signed
+    ///  with its keys first, returns false if they can not be signed, and the zone is left as it
+    ///  was. The secondaries of a primary zone are notified if the serial changed, see `notify`.
+    pub fn replace(&self, name: &Name, mut authority: Authority) -> bool {
+        let changed = match self.zone(name) {
+            Some(zone) => {
+                let mut zone = zone.write().unwrap(); // poison errors should panic...
+                if !Self::sign_replacement(&zone, &mut authority) {
+                    return false;
+                }
+                let changed = zone.serial() != authority.serial();
+
+                // the hooks of the zone, including those of the catalog, carry over to the new
+                //  records, and are told of the difference
+                for hook in zone.change_hooks() {
+                    authority.add_change_hook(hook.clone());
+                }
+                let changes =
+                    if authority.change_hooks().is_empty() && authority.ixfr_history_size() == 0 {
+                        vec![]
+                    } else {
+                        change_hook::diff(zone.records(), authority.records())
+                    };
+                // as are the changes kept for incremental zone transfers
+                let zone = zone.authority_mut();
+                authority.take_ixfr_history(zone, &changes);
+                *zone = authority;
+                zone.call_change_hooks(&changes);
+                changed
+            }
+            None => return false,
+        };
+
+        self.response_cache.invalidate_zone(name);
+        self.clear_failed(name);
+        self.with_zone_stats(name, |stats| stats.record_refresh());
+        if changed {
+            self.notify(name);
+        }
+        self.sync_reverse_zones(name);
+        true
+    }
+
+    /// Signs the records which replace those of a zone which is signed as it changes, returns false
+    ///  if they could not be signed
+    #[cfg(feature = "dnssec")]
+    fn sign_replacement(zone: &Zone, authority: &mut Authority) -> bool {
+        let signing = match zone.signing_authority() {
+            Some(signing) => signing,
+            None => return true,
+        };
+
+        match signing.sign_authority(authority) {
+            Ok(serial) => {
+                info!("signed replacement of zone {} at serial {}", zone.origin(), serial);
+                true
+            }
+            Err(e) => {
+                warn!("could not sign replacement of zone {}, not replaced: {}", zone.origin(), e);
+                false
+            }
+        }
+    }
+
+    #[cfg(not(feature = "dnssec"))]
+    fn sign_replacement(_zone: &Zone, _authority: &mut Authority) -> bool {
+        true
+    }
+
+    /// Signs the zones which are signed as they change again, once their signatures are about to
+    ///  expire, see `SigningAuthority::is_resign_due`, returns the names of the zones re-signed
+    ///
+    /// Each zone is signed while it is still being served, and only held to replace its records
+    ///  with the signed ones, so this is best run off the IO loop, e.g. on the `WorkerPool` of the
+    ///  `ServerFuture`. A zone which changes while it is being signed is signed again on the next
+    ///  call, as is one which fails to be signed.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - the time from which the new signatures are valid
+    #[cfg(feature = "dnssec")]
+    pub fn resign_due(&self, now: DateTime<Utc>) -> Vec<Name> {
+        let zones: Vec<(Name, Arc<RwLock<Zone>>)> = self.authorities
+            .read()
+            .unwrap() // poison errors should panic
+            .iter()
+            .map(|(name, zone)| (name.clone(), zone.clone()))
+            .collect();
+
+        let mut resigned = Vec::new();
+        for (name, zone) in zones {
+            let (serial, signed) = {
+                let zone = zone.read().unwrap(); // poison errors should panic
+                let signing = match zone.signing_authority() {
+                    Some(signing) if signing.is_resign_due(now) => signing,
+                    _ => continue,
+                };
+
+                (zone.serial(), signing.resigned_records(now))
+            };
+
+            let signed = match signed {
+                Ok(signed) => signed,
+                Err(e) => {
+                    warn!("could not re-sign zone {}: {}", name, e);
+                    continue;
+                }
+            };
+
+            {
+                let mut zone = zone.write().unwrap(); // poison errors should panic...
+                if zone.serial() != serial {
+                    debug!("zone {} changed while it was re-signed, re-signing later", name);
+                    continue;
+                }
+
+                match zone.signing_authority_mut() {
+                    Some(signing) => {
+                        let serial = signing.replace_signed(signed);
+                        info!("re-signed zone {} at serial {}", name, serial);
+                    }
+                    None => continue,
+                }
+            }
+
+            self.response_cache.invalidate_zone(&name);
+            self.notify(&name);
+            resigned.push(name);
+        }
+
+        resigned
+    }
+
+    /// Adds a hook which is called with the changes to the records of every zone, those being
+    ///  served and those added later, see `Authority::add_change_hook`
+    pub fn add_change_hook(&mut self, hook: Arc<ChangeHook>) {
+        for (_, zone) in self.authorities.read().unwrap().iter() {
+            zone.write().unwrap().add_change_hook(hook.clone()); // poison errors should panic...
+        }
+        self.change_hooks.push(hook);
+    }
+
+    /// Adds the hooks of the catalog to a zone which is added to it
+    fn add_change_hooks(&self, authority: &mut Zone) {
+        for hook in &self.change_hooks {
+            authority.add_change_hook(hook.clone());
+        }
+    }
+
+    /// Returns the SOA serial of the zone, if it is being served
+    pub fn serial(&self, name: &Name) -> Option<u32> {
+        self.zone(name).map(|zone| {
+            zone.read().unwrap().serial() // poison errors should panic
+        })
+    }
+
+    /// Sets the primary to which updates for a secondary zone are forwarded, and from which the
+    ///  zone is refreshed when it sends a NOTIFY
+    ///
+    /// Without this updates are forwarded to, and the zone refreshed from, the MNAME of the zone's
+    ///  SOA, on port 53, and a NOTIFY is accepted from any address.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com.
+    /// * `primary` - address of the primary master for the zone
+    pub fn set_primary(&mut self, name: Name, primary: SocketAddr) {
+        self.primaries.insert(name, primary);
+    }
+
+    /// Sets the addresses which are sent a NOTIFY when a primary zone changes, in addition to the
+    ///  name servers of the zone, e.g. secondaries which are not listed in the NS records
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com.
+    /// * `also_notify` - addresses of the secondaries, e.g. `10.0.0.2:53`
+    pub fn set_also_notify(&mut self, name: Name, also_notify: Vec<SocketAddr>) {
+        self.also_notify.insert(name, also_notify);
+    }
+
+    /// Sets how the answers of a zone are altered as they are served, e.g. clamping their TTLs,
+    ///  see `ResponsePolicy`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com.
+    /// * `policy` - the policy for the answers of the zone
+    pub fn set_response_policy(&mut self, name: Name, policy: ResponsePolicy) {
+        self.response_cache.invalidate_zone(&name);
+        self.response_policies.insert(name, policy);
+    }
+
+    /// Generates the PTR records of a reverse zone, in `in-addr.arpa.` or `ip6.arpa.`, from the A
+    ///  and AAAA records of forward zones, for the addresses within the reverse zone
+    ///
+    /// The reverse zone keeps its own records, e.g. its SOA and NS records from its zone file,
+    ///  and the PTR records are added to them. They are kept in sync with the forward zones as
+    ///  those are updated, replaced, added or removed, each change to them is an update of the
+    ///  reverse zone, which increments its serial and notifies its secondaries. A PTR record of
+    ///  the reverse zone which is the same as a generated one is taken over by it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - reverse zone name, e.g. 2.0.192.in-addr.arpa.
+    /// * `forward_zones` - names of the zones with the A and AAAA records, e.g. example.com.
+    pub fn set_reverse_zone(&mut self, name: Name, forward_zones: Vec<Name>) {
+        self.reverse_zones.insert(name.clone(), forward_zones);
+        self.sync_reverse_zone(&name, false);
+    }
+
+    /// Sets how NOTIFYs are retried and jittered, see `NotifyRetry::default`
+    pub fn set_notify_retry(&mut self, notify_retry: NotifyRetry) {
+        self.notify_retry = notify_retry;
+    }
+
+    /// Sends a NOTIFY of the zone's current SOA to its secondaries, RFC 1996
+    ///
+    /// The NOTIFY is sent to the name servers of the zone, other than the MNAME of its SOA, and
+    ///  to the addresses of `set_also_notify`. This is done in the background, by one thread for
+    ///  all the zones, each secondary is retried until it responds, and the outcome for each is
+    ///  recorded in the zone's `ZoneStats::notify_status`. The NOTIFYs of an earlier serial of
+    ///  the zone which are still being retried are replaced by these. Zones are notified when
+    ///  they are updated or replaced.
+    ///
+    /// # Returns
+    ///
+    /// false if the zone is not a primary zone with an SOA being served, nothing is sent
+    pub fn notify(&self, name: &Name) -> bool {
+        let (message, serial, name_servers) = match self.zone(name) {
+            Some(zone) => {
+                let authority = zone.read().unwrap(); // poison errors should panic...
+                if authority.zone_type() != ZoneType::Master {
+                    return false;
+                }
+
+                match notifier::notify_message(&*authority) {
+                    Some(message) => (
+                        message,
+                        authority.serial(),
+                        notifier::notify_name_servers(&*authority),
+                    ),
+                    None => return false,
+                }
+            }
+            None => return false,
+        };
+
+        let also_notify = self.also_notify.get(name).cloned().unwrap_or_default();
+        info!(
+            "notifying secondaries of {} serial {}: {} name servers, {} also notify",
+            name,
+            serial,
+            name_servers.len(),
+            also_notify.len()
+        );
+
+        self.notifies.notify(NotifyChange {
+            message: message,
+            serial: serial,
+            name_servers: name_servers,
+            also_notify: also_notify,
+            retry: self.notify_retry,
+            recorder: NotifyRecorder::new(name.clone(), self.zone_stats.clone()),
+        });
+        true
+    }
+
+    /// Records that the zone could not be loaded, at startup, or when it was reloaded while the
+    ///  server is running
+    ///
+    /// If a previous version of the zone was loaded, it continues to be served, and its health is
+    ///  `ReloadFailed`. Otherwise queries for names in the zone are answered with ServFail, rather
+    ///  than with the response of any parent zone. Either way the failure is counted in the
+    ///  `ZoneStats` of the zone, and the reason given in its `health`. This is cleared the next
+    ///  time the zone is upserted, added or replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - zone name, e.g. example.com.
+    /// * `error` - the reason the zone could not be loaded
+    pub fn mark_failed(&self, name: Name, error: String) {
+        self.with_zone_stats(&name, |stats| stats.record_load_failure());
+        self.failed_zones
+            .write()
+            .unwrap() // poison errors should panic...
+            .insert(name, error);
+    }
+
+    /// Returns the zones which failed to load, and the reason for each, including those which
+    ///  still serve a previous version
+    pub fn failed_zones(&self) -> HashMap<Name, String> {
+        self.failed_zones
+            .read()
+            .unwrap() // poison errors should panic
+            .clone()
+    }
+
+    /// Clears the failure of the zone, once a version of it loaded
+    fn clear_failed(&self, name: &Name) {
+        self.failed_zones
+            .write()
+            .unwrap() // poison errors should panic...
+            .remove(name);
+    }
+
+    /// Records the outcome of a transfer of the zone, a successful transfer refreshes the zone
+    pub fn record_transfer(&self, name: &Name, transfer_status: TransferStatus) {
+        self.with_zone_stats(name, |stats| stats.record_transfer(transfer_status));
+    }
+
+    /// Records that the zone was confirmed to be current, e.g. the primary returned the same SOA
+    ///  serial as is being served
+    pub fn record_refresh(&self, name: &Name) {
+        self.with_zone_stats(name, |stats| stats.record_refresh());
+    }
+
+    /// Returns a snapshot of the statistics for each zone
+    pub fn zone_stats(&self) -> HashMap<Name, ZoneStats> {
+        match self.zone_stats.lock() {
+            Ok(zone_stats) => zone_stats.clone(),
+            Err(_) => {
+                warn!("zone stats poisoned, ignoring");
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Returns the health of every zone, including those which failed to load
+    ///
+    /// Secondaries which have not been refreshed within the SOA refresh interval, or are nearing
+    ///  the SOA expire interval, are flagged so that broken replication can be caught before the
+    ///  zone expires. Zones which failed to reload, but still serve their previous version, are
+    ///  `ReloadFailed`, see `mark_failed`.
+    pub fn health(&self) -> Vec<ZoneHealth> {
+        let zone_stats = self.zone_stats();
+        let mut failed_zones = self.failed_zones();
+
+        let authorities = self.authorities.read().unwrap(); // poison errors should panic
+        let mut health = authorities
+            .iter()
+            .map(|(name, authority)| {
+                let authority = authority.read().unwrap(); // poison errors should panic
+                let stats = zone_stats.get(name).cloned().unwrap_or_else(ZoneStats::new);
+                let health = ZoneHealth::check(&*authority, &stats);
+                match failed_zones.remove(name) {
+                    Some(error) => health.with_error(error),
+                    None => health,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        health.extend(failed_zones.into_iter().map(|(name, error)| {
+            ZoneHealth::failed(name).with_error(error)
+        }));
+
+        health
+    }
+
+    fn with_zone_stats<F: FnOnce(&mut ZoneStats)>(&self, name: &Name, f: F) {
+        match self.zone_stats.lock() {
+            Ok(mut zone_stats) => {
+                f(zone_stats.entry(name.clone()).or_insert_with(ZoneStats::new))
+            }
+            Err(_) => warn!("zone stats poisoned, ignoring"),
+        }
     }
 
     /// Update the zone given the Update request.
@@ -212,6 +1085,12 @@ impl Catalog {
     ///  The response will be in the alternate, all 0's format described in RFC 2136 section 3.8
     ///  as this is more efficient.
     ///
+    /// Updates to a secondary are forwarded to the primary, see `set_primary`, and the primary's
+    ///  response is returned. If the primary can not be reached the response is ServFail. This
+    ///  blocks until the primary has answered, the `ServerFuture` instead waits for the primary on
+    ///  its IO loop, see `forward_request`. Unsigned updates are refused, and those past the
+    ///  updates being forwarded at once answered with ServFail, see `reserve_forward`.
+    ///
     /// # Arguments
     ///
     /// * `request` - an update message
@@ -234,20 +1113,47 @@ impl Catalog {
             return response;
         }
 
-        if let Some(authority) = self.find_auth_recurse(zones[0].name()) {
+        if let Some(failed_zone) = self.find_failed_zone(zones[0].name()) {
+            warn!("update for zone which failed to load: {}", failed_zone);
+            response.set_response_code(ResponseCode::ServFail);
+            Self::set_extended_error(
+                update,
+                &mut response,
+                ExtendedErrorCode::NotReady,
+                "the zone failed to load",
+            );
+            return response;
+        }
+
+        if let Some(authority) = self.find_auth(zones[0].name()) {
             let mut authority = authority.write().unwrap(); // poison errors should panic...
             match authority.zone_type() {
                 ZoneType::Slave => {
-                    error!("slave forwarding for update not yet implemented");
-                    response.set_response_code(ResponseCode::NotImp);
-                    return response;
+                    let origin = authority.origin().clone();
+                    let configured = self.primaries.get(&origin).cloned();
+                    let mname = authority.soa().and_then(|soa| match *soa.rdata() {
+                        RData::SOA(ref soa) => Some(soa.mname().clone()),
+                        _ => None,
+                    });
+
+                    // don't hold the zone while waiting on the primary
+                    drop(authority);
+                    return self.forward_update_blocking(update, &origin, configured, mname);
                 }
                 ZoneType::Master => {
                     let update_result = authority.update(update);
+                    self.response_cache.invalidate_zone(authority.origin());
                     match update_result {
                         // successful update
-                        Ok(..) => {
+                        Ok(changed) => {
                             response.set_response_code(ResponseCode::NoError);
+
+                            if changed {
+                                let origin = authority.origin().clone();
+                                drop(authority);
+                                self.notify(&origin);
+                                self.sync_reverse_zones(&origin);
+                            }
                         }
                         Err(response_code) => {
                             response.set_response_code(response_code);
@@ -266,6 +1172,382 @@ impl Catalog {
         }
     }
 
+    /// The zone of the update, its configured primary and its SOA MNAME, if the zone is a
+    ///  secondary, to which the update is forwarded
+    fn forward_target(&self, update: &Message) -> Option<(Name, Option<SocketAddr>, Option<Name>)> {
+        let zones: &[Query] = update.zones();
+        if zones.len() != 1 || zones[0].query_type() != RecordType::SOA ||
+            self.find_failed_zone(zones[0].name()).is_some()
+        {
+            return None;
+        }
+
+        let authority = match self.find_auth(zones[0].name()) {
+            Some(authority) => authority,
+            None => return None,
+        };
+        let authority = authority.read().unwrap(); // poison errors should panic
+        if authority.zone_type() != ZoneType::Slave {
+            return None;
+        }
+
+        let configured = self.primaries.get(authority.origin()).cloned();
+        let mname = authority.soa().and_then(|soa| match *soa.rdata() {
+            RData::SOA(ref soa) => Some(soa.mname().clone()),
+            _ => None,
+        });
+        Some((authority.origin().clone(), configured, mname))
+    }
+
+    /// Reserves the forward of the update to the primary, or returns the response to an update
+    ///  which is not forwarded
+    ///
+    /// Only updates signed with SIG(0) or TSIG are forwarded, as the primary would otherwise see
+    ///  an update of any requestor as one sent by this server. At most
+    ///  `update_forwarder::MAX_FORWARDS` updates are forwarded at once, the updates past them are
+    ///  answered with ServFail.
+    fn reserve_forward(&self, update: &Message) -> Result<update_forwarder::Forward, Message> {
+        if update.sig0().is_empty() {
+            warn!("not forwarding unsigned update {} to the primary", update.id());
+            let mut response = Self::forward_failed(
+                update,
+                ExtendedErrorCode::Prohibited,
+                "unsigned updates are not forwarded to the primary",
+            );
+            response.set_response_code(ResponseCode::Refused);
+            return Err(response);
+        }
+
+        self.update_forwarder.reserve().ok_or_else(|| {
+            warn!("too many updates forwarded, failing update: {}", update.id());
+            Self::forward_failed(
+                update,
+                ExtendedErrorCode::Other,
+                "too many updates are being forwarded to the primary",
+            )
+        })
+    }
+
+    /// Forwards the update toward the primary master, RFC 2136 section 6, the future of the
+    ///  primary's response, or of ServFail if there is no primary or it can not be reached
+    ///
+    /// If there is no configured primary, the MNAME resolved by the last refresh of the zone is
+    ///  used, only before the zone was first refreshed is it resolved for the update, off the IO
+    ///  loop of the handle, see `UpdateForwarder::resolve_primary_future`.
+    fn forward_update(
+        &self,
+        update: &Message,
+        origin: Name,
+        configured: Option<SocketAddr>,
+        mname: Option<Name>,
+        handle: &Handle,
+    ) -> Box<Future<Item = Message, Error = io::Error>> {
+        let forward = match self.reserve_forward(update) {
+            Ok(forward) => forward,
+            Err(response) => return Box::new(future::ok(response)),
+        };
+
+        let primary: Box<Future<Item = Option<SocketAddr>, Error = io::Error>> =
+            match (configured, mname) {
+                (Some(configured), _) => Box::new(future::ok(Some(configured))),
+                (None, Some(mname)) => self.update_forwarder.resolve_primary_future(origin, mname),
+                (None, None) => Box::new(future::ok(None)),
+            };
+
+        let forwarded = update.clone();
+        let failed = update.clone();
+        let handle = handle.clone();
+        Box::new(
+            primary
+                .and_then(move |primary| -> Box<Future<Item = Message, Error = io::Error>> {
+                    let primary = match primary {
+                        Some(primary) => primary,
+                        None => {
+                            warn!("no primary to forward update: {}", forwarded.id());
+                            return Box::new(future::ok(Self::forward_failed(
+                                &forwarded,
+                                ExtendedErrorCode::NoReachableAuthority,
+                                "there is no primary to forward the update to",
+                            )));
+                        }
+                    };
+
+                    debug!("forwarding update {} to primary: {}", forwarded.id(), primary);
+                    update_forwarder::forward_update(&forwarded, primary, &handle)
+                })
+                .or_else(move |e| -> io::Result<Message> {
+                    warn!("could not forward update {} to the primary: {}", failed.id(), e);
+                    Ok(Self::forward_failed(
+                        &failed,
+                        ExtendedErrorCode::NetworkError,
+                        "the update could not be forwarded to the primary",
+                    ))
+                })
+                .then(move |response| {
+                    // released once the primary responded, or if the update is abandoned before
+                    drop(forward);
+                    response
+                }),
+        )
+    }
+
+    /// Forwards the update toward the primary master, as `forward_update` does, and blocks until
+    ///  the primary has responded
+    ///
+    /// If there is no configured primary, and the zone was not refreshed yet, the MNAME is
+    ///  resolved on this thread.
+    fn forward_update_blocking(
+        &self,
+        update: &Message,
+        origin: &Name,
+        configured: Option<SocketAddr>,
+        mname: Option<Name>,
+    ) -> Message {
+        let _forward = match self.reserve_forward(update) {
+            Ok(forward) => forward,
+            Err(response) => return response,
+        };
+
+        let primary = configured.or_else(|| {
+            mname.and_then(|mname| {
+                self.update_forwarder.resolved_primary(origin, &mname).or_else(|| {
+                    self.update_forwarder.resolve_primary(origin, &mname)
+                })
+            })
+        });
+        let primary = match primary {
+            Some(primary) => primary,
+            None => {
+                warn!("no primary to forward update: {}", update.id());
+                return Self::forward_failed(
+                    update,
+                    ExtendedErrorCode::NoReachableAuthority,
+                    "there is no primary to forward the update to",
+                );
+            }
+        };
+
+        debug!("forwarding update {} to primary: {}", update.id(), primary);
+        update_forwarder::forward_update_blocking(update, primary).unwrap_or_else(|e| {
+            warn!("could not forward update {} to the primary: {}", update.id(), e);
+            Self::forward_failed(
+                update,
+                ExtendedErrorCode::NetworkError,
+                "the update could not be forwarded to the primary",
+            )
+        })
+    }
+
+    /// The ServFail response to an update which could not be forwarded to the primary
+    fn forward_failed(update: &Message, info_code: ExtendedErrorCode, extra_text: &str) -> Message {
+        let mut response: Message = Message::new();
+        response.set_id(update.id());
+        response.set_op_code(OpCode::Update);
+        response.set_message_type(MessageType::Response);
+        response.set_response_code(ResponseCode::ServFail);
+        Self::set_extended_error(update, &mut response, info_code, extra_text);
+        response
+    }
+
+    /// Handles a NOTIFY of a change to a secondary zone, sent by its primary, RFC 1996 section 4
+    ///
+    /// The zone is refreshed from the primary in the background, see `set_primary`, unless the SOA
+    ///  in the NOTIFY shows that it is up to date. The outcome of the refresh is recorded in the
+    ///  `ZoneStats` of the zone. A NOTIFY for a zone which is not a secondary of this server is
+    ///  refused, as is one from another address than the configured primary of the zone.
+    ///
+    /// # Arguments
+    ///
+    /// * `notify` - the NOTIFY message
+    /// * `src` - the address from which the NOTIFY was received
+    pub fn handle_notify(&self, notify: &Message, src: IpAddr) -> Message {
+        let mut response: Message = Message::new();
+        response
+            .set_id(notify.id())
+            .set_op_code(OpCode::Notify)
+            .set_message_type(MessageType::Response);
+        response.add_queries(notify.queries().into_iter().cloned());
+
+        // 3.7 - the question is the SOA of the zone
+        if notify.queries().len() != 1 || notify.queries()[0].query_type() != RecordType::SOA {
+            response.set_response_code(ResponseCode::FormErr);
+            return response;
+        }
+        let origin = notify.queries()[0].name();
+
+        let zone = match self.zone(origin) {
+            Some(zone) => zone,
+            None => {
+                response.set_response_code(ResponseCode::NotAuth);
+                return response;
+            }
+        };
+
+        let configured = self.primaries.get(origin).cloned();
+        {
+            let authority = zone.read().unwrap(); // poison errors should panic
+            match authority.zone_type() {
+                ZoneType::Slave => (),
+                _ => {
+                    debug!("notify of {}, which is not a secondary zone", origin);
+                    response.set_response_code(ResponseCode::Refused);
+                    return response;
+                }
+            }
+
+            // 3.10 - only the primary is listened to
+            if let Some(primary) = configured {
+                if primary.ip() != src {
+                    warn!("notify of {} from {}, which is not its primary", origin, src);
+                    response.set_response_code(ResponseCode::Refused);
+                    return response;
+                }
+            }
+
+            // 3.11 - the SOA in the answer section is a hint of the new serial
+            let hint = notify
+                .answers()
+                .iter()
+                .filter(|record| record.name() == origin)
+                .filter_map(|record| match *record.rdata() {
+                    RData::SOA(ref soa) => Some(soa.serial()),
+                    _ => None,
+                })
+                .next();
+            if let Some(hint) = hint {
+                if (hint.wrapping_sub(authority.serial()) as i32) <= 0 {
+                    debug!("notify of {} serial {}, which is not newer", origin, hint);
+                    response.set_response_code(ResponseCode::NoError);
+                    return response;
+                }
+            }
+        }
+
+        info!("notify of {} from {}, refreshing", origin, src);
+        self.refreshes.spawn_refresh(
+            origin.clone(),
+            zone,
+            configured,
+            self.zone_stats.clone(),
+            self.update_forwarder.clone(),
+        );
+        response.set_response_code(ResponseCode::NoError);
+        response
+    }
+
+    /// Refreshes the secondary zones which are due from their primary, in the background, returns
+    ///  the zones whose refresh was started
+    ///
+    /// A zone is due once the SOA refresh interval passed since it was last refreshed, and after
+    ///  a failed attempt once the SOA retry interval passed, RFC 1034 section 4.3.5. A zone which
+    ///  has not been transferred yet is transferred from its primary with an AXFR, see
+    ///  `set_primary`. This is meant to be called every second or so, the outcome is recorded in
+    ///  the `ZoneStats` of the zones, and a zone which passes the SOA expire interval without
+    ///  being refreshed is answered with ServFail.
+    pub fn refresh_secondaries(&self) -> Vec<Name> {
+        let zone_stats = self.zone_stats();
+        let secondaries = self.authorities
+            .read()
+            .unwrap() // poison errors should panic
+            .iter()
+            .filter(|&(_, zone)| {
+                zone.read().unwrap().zone_type() == ZoneType::Slave // poison errors should panic
+            })
+            .map(|(name, zone)| (name.clone(), zone.clone()))
+            .collect::<Vec<_>>();
+
+        let mut refreshed = Vec::new();
+        for (origin, zone) in secondaries {
+            if self.refreshes.is_refreshing(&origin) {
+                continue;
+            }
+
+            let is_due = {
+                let authority = zone.read().unwrap(); // poison errors should panic
+                let stats = zone_stats.get(&origin).cloned().unwrap_or_else(ZoneStats::new);
+                refresher::is_refresh_due(&*authority, &stats)
+            };
+            if !is_due {
+                continue;
+            }
+
+            debug!("refreshing secondary zone: {}", origin);
+            let configured = self.primaries.get(&origin).cloned();
+            self.refreshes.spawn_refresh(
+                origin.clone(),
+                zone,
+                configured,
+                self.zone_stats.clone(),
+                self.update_forwarder.clone(),
+            );
+            refreshed.push(origin);
+        }
+
+        refreshed
+    }
+
+    /// Returns why the secondary zone is not served, it has not been transferred yet, or it
+    ///  expired, RFC 1034 section 4.3.5, None if it is served or is not a secondary
+    fn unserved_secondary(&self, authority: &Authority) -> Option<&'static str> {
+        if authority.zone_type() != ZoneType::Slave {
+            return None;
+        }
+        if authority.soa().is_none() {
+            return Some("the zone has not been transferred from its primary");
+        }
+
+        let status = match self.zone_stats.lock() {
+            Ok(zone_stats) => {
+                zone_stats.get(authority.origin()).map(|stats| {
+                    ZoneHealth::check(authority, stats).status()
+                })
+            }
+            Err(_) => None,
+        };
+        match status {
+            Some(ZoneHealthStatus::Expired) => Some("the zone expired"),
+            _ => None,
+        }
+    }
+
+    /// True if the query is for `health.server.` in the CHAOS class, and those are answered, see
+    ///  `set_health_query`
+    fn is_health_query(&self, query: &Query) -> bool {
+        self.health_query && query.query_class() == DNSClass::CH &&
+            *query.name() == Name::from_labels(vec!["health", "server"])
+    }
+
+    /// The TXT records of the health of each zone, in the order of the zone names
+    fn health_records(&self, name: &Name) -> Vec<Record> {
+        let mut health = self.health();
+        health.sort_by(|a, b| a.zone().cmp(b.zone()));
+
+        health
+            .iter()
+            .map(|health| {
+                let mut txt = vec![health.zone().to_string(), format!("{:?}", health.status())];
+                if let Some(serial) = health.serial() {
+                    txt.push(format!("serial {}", serial));
+                }
+                if let Some(since_refresh) = health.since_refresh() {
+                    txt.push(format!("refreshed {}s ago", since_refresh.as_secs()));
+                }
+                if let Some(error) = health.error_text() {
+                    txt.push(error);
+                }
+
+                let mut record = Record::from_rdata(
+                    name.clone(),
+                    0,
+                    RecordType::TXT,
+                    RData::TXT(TXT::new(txt)),
+                );
+                record.set_dns_class(DNSClass::CH);
+                record
+            })
+            .collect()
+    }
+
     /// Given the requested query, lookup and return any matching results.
     ///
     /// # Arguments
@@ -278,72 +1560,160 @@ impl Catalog {
         response.set_message_type(MessageType::Response);
         response.add_queries(request.queries().into_iter().cloned());
 
-        // TODO: the spec is very unclear on what to do with multiple queries
-        //  we will search for each, in the future, maybe make this threaded to respond even faster.
+        // the meaning of more than one query was never defined, only exactly one is answered,
+        //  RFC 9619
+        if request.queries().len() != 1 {
+            warn!(
+                "request: {} has {} queries, expected 1",
+                request.id(),
+                request.queries().len()
+            );
+            response.set_response_code(ResponseCode::FormErr);
+            return response;
+        }
+
         for query in request.queries() {
-            if let Some(ref_authority) = self.find_auth_recurse(query.name()) {
+            if self.is_health_query(query) {
+                debug!("request: {} is a health query", request.id());
+                response.set_response_code(ResponseCode::NoError);
+                response.set_authoritative(true);
+                if query.query_type() == RecordType::TXT || query.query_type() == RecordType::ANY {
+                    response.add_answers(self.health_records(query.name()));
+                }
+                continue;
+            }
+
+            if let Some(failed_zone) = self.find_failed_zone(query.name()) {
+                warn!("query for zone which failed to load: {}", failed_zone);
+                self.with_zone_stats(&failed_zone, |stats| {
+                    stats.record_response(ResponseCode::ServFail)
+                });
+                response.set_response_code(ResponseCode::ServFail);
+                Self::set_extended_error(
+                    request,
+                    &mut response,
+                    ExtendedErrorCode::NotReady,
+                    "the zone failed to load",
+                );
+                continue;
+            }
+
+            if let Some(ref_authority) = self.find_auth(query.name()) {
                 let authority = &ref_authority.read().unwrap(); // poison errors should panic
                 debug!("found authority: {:?}", authority.origin());
-                let (is_dnssec, supported_algorithms) =
-                    request.edns().map_or(
-                        (false, SupportedAlgorithms::new()),
-                        |edns| {
-                            let supported_algorithms =
-                                if let Some(&EdnsOption::DAU(algs)) = edns.option(&EdnsCode::DAU) {
-                                    algs
-                                } else {
-                                    debug!("no DAU in request, used default SupportAlgorithms");
-                                    Default::default()
-                                };
-
-                            (edns.dnssec_ok(), supported_algorithms)
-                        },
+
+                if let Some(reason) = self.unserved_secondary(authority) {
+                    warn!(
+                        "query for secondary zone {}, which is not served: {}",
+                        authority.origin(),
+                        reason
                     );
-                debug!(
-                    "request: {} supported_algs: {}",
-                    request.id(),
-                    supported_algorithms
-                );
+                    self.with_zone_stats(authority.origin(), |stats| {
+                        stats.record_response(ResponseCode::ServFail)
+                    });
+                    response.set_response_code(ResponseCode::ServFail);
+                    Self::set_extended_error(
+                        request,
+                        &mut response,
+                        ExtendedErrorCode::NotReady,
+                        reason,
+                    );
+                    continue;
+                }
+                let (is_dnssec, supported_algorithms) = Self::dnssec_options(request);
 
-                let records = authority.search(query, is_dnssec, supported_algorithms);
-                if !records.is_empty() {
-                    response.set_response_code(ResponseCode::NoError);
-                    response.set_authoritative(true);
-                    response.add_answers(records.into_iter().cloned());
+                let policy = self.response_policies.get(authority.origin());
+                if policy.map_or(false, |policy| policy.is_refused(query.query_type())) {
+                    debug!("request: {} refused by the response policy", request.id());
+                    self.with_zone_stats(authority.origin(), |stats| {
+                        stats.record_response(ResponseCode::Refused)
+                    });
+                    response.set_response_code(ResponseCode::Refused);
+                    Self::set_extended_error(
+                        request,
+                        &mut response,
+                        ExtendedErrorCode::NotSupported,
+                        "ANY queries are refused, RFC 8482",
+                    );
+                    continue;
+                }
 
-                    // get the NS records
-                    let ns = authority.ns(is_dnssec, supported_algorithms);
-                    if ns.is_empty() {
-                        warn!("there are no NS records for: {:?}", authority.origin());
-                    } else {
-                        response.add_name_servers(ns.into_iter().cloned());
-                    }
+                // the answer to an IXFR depends on the version of the zone which the client has,
+                //  it is not cached
+                let answer = if query.query_type() == RecordType::IXFR {
+                    Arc::new(Self::assemble_ixfr(
+                        authority,
+                        request,
+                        query,
+                        is_dnssec,
+                        supported_algorithms,
+                        policy,
+                    ))
                 } else {
-                    if is_dnssec {
-                        // get NSEC records
-                        let nsecs = authority.get_nsec_records(
-                            query.name(),
-                            is_dnssec,
-                            supported_algorithms,
-                        );
-                        response.add_name_servers(nsecs.into_iter().cloned());
-                    }
-
-                    // in the not found case it's standard to return the SOA in the authority section
-                    // TODO: improve: see https://tools.ietf.org/html/rfc2308 for proper response construct
-                    response.set_response_code(ResponseCode::NXDomain);
-
-                    let soa = authority.soa_secure(is_dnssec, supported_algorithms);
-                    if soa.is_empty() {
-                        warn!("there is no SOA record for: {:?}", authority.origin());
+                    let key = ResponseKey::new(query, is_dnssec, supported_algorithms);
+                    // the serial is used to detect changes to the zone, only needed when caching
+                    let serial = if self.response_cache.capacity() > 0 {
+                        authority.serial()
                     } else {
-                        response.add_name_servers(soa.into_iter().cloned());
+                        0
+                    };
+                    match self.response_cache.get(&key, authority.origin(), serial) {
+                        Some(answer) => {
+                            debug!("request: {} answered from response cache", request.id());
+                            answer
+                        }
+                        None => {
+                            let answer = Arc::new(Self::assemble_answer(
+                                authority,
+                                query,
+                                serial,
+                                is_dnssec,
+                                supported_algorithms,
+                                policy,
+                            ));
+                            self.response_cache.insert(key, answer.clone());
+                            answer
+                        }
                     }
+                };
+
+                self.with_zone_stats(authority.origin(), |stats| {
+                    stats.record_response(answer.response_code)
+                });
+                response.set_response_code(answer.response_code);
+                if answer.authoritative {
+                    response.set_authoritative(true);
+                }
+                let mut inserted = response
+                    .answers_mut()
+                    .insert_all(answer.answers.iter().cloned())
+                    .map(|_| ());
+                if inserted.is_ok() {
+                    inserted = response
+                        .name_servers_mut()
+                        .insert_all(answer.name_servers.iter().cloned())
+                        .map(|_| ());
+                }
+                if let Err(e) = inserted {
+                    warn!("malformed answer for {}: {}", query.name(), e);
+                    response.set_response_code(ResponseCode::ServFail);
+                    Self::set_extended_error(
+                        request,
+                        &mut response,
+                        ExtendedErrorCode::Other,
+                        "the answer is malformed",
+                    );
                 }
             } else {
                 // we found nothing.
                 // TODO: improve: see https://tools.ietf.org/html/rfc2308 for proper response construct
                 response.set_response_code(ResponseCode::NXDomain);
+                Self::set_extended_error(
+                    request,
+                    &mut response,
+                    ExtendedErrorCode::NotAuthoritative,
+                    "",
+                );
             }
         }
 
@@ -352,18 +1722,403 @@ impl Catalog {
         response
     }
 
-    /// recursively searches the catalog for a matching auhtority.
-    fn find_auth_recurse(&self, name: &Name) -> Option<&RwLock<Authority>> {
-        let authority = self.authorities.get(name);
-        if authority.is_some() {
-            return authority;
+    /// Returns whether the request asks for the DNSSEC records, and the algorithms of the
+    ///  signatures it asks for, from its EDNS, RFC 6975
+    fn dnssec_options(request: &Message) -> (bool, SupportedAlgorithms) {
+        let (is_dnssec, supported_algorithms) =
+            request.edns().map_or(
+                (false, SupportedAlgorithms::new()),
+                |edns| {
+                    let supported_algorithms =
+                        if let Some(&EdnsOption::DAU(algs)) = edns.option(&EdnsCode::DAU) {
+                            algs
+                        } else {
+                            debug!("no DAU in request, used default SupportAlgorithms");
+                            Default::default()
+                        };
+
+                    (edns.dnssec_ok(), supported_algorithms)
+                },
+            );
+        debug!(
+            "request: {} supported_algs: {}",
+            request.id(),
+            supported_algorithms
+        );
+
+        (is_dnssec, supported_algorithms)
+    }
+
+    /// Returns the zone transfer, AXFR, which the request asks for, as the messages of at most
+    ///  `max_len` octets of records each, which are read from the zone as they are sent, see
+    ///  `AxfrStream`
+    ///
+    /// Returns None for any request other than the transfer of a zone which is served, to be
+    ///  answered as any other query, see `lookup`.
+    fn stream_axfr(&self, request: &Message, max_len: usize) -> Option<AxfrStream> {
+        if request.queries().len() != 1 {
+            return None;
+        }
+        let query = &request.queries()[0];
+        if query.query_type() != RecordType::AXFR || self.find_failed_zone(query.name()).is_some() {
+            return None;
+        }
+
+        let ref_authority = match self.find_auth(query.name()) {
+            Some(ref_authority) => ref_authority,
+            None => return None,
+        };
+        let authority = &ref_authority.read().unwrap(); // poison errors should panic
+        if query.name() != authority.origin() || self.unserved_secondary(authority).is_some() {
+            return None;
+        }
+
+        let policy = self.response_policies.get(authority.origin());
+        if policy.map_or(false, |policy| policy.is_refused(RecordType::AXFR)) {
+            return None;
+        }
+
+        let (is_dnssec, supported_algorithms) = Self::dnssec_options(request);
+        if authority.axfr(is_dnssec, supported_algorithms).is_none() {
+            return None;
+        }
+
+        debug!("request: {} streaming transfer of zone: {}", request.id(), authority.origin());
+        self.with_zone_stats(authority.origin(), |stats| {
+            stats.record_response(ResponseCode::NoError)
+        });
+
+        Some(AxfrStream::new(
+            ref_authority.clone(),
+            request,
+            is_dnssec,
+            supported_algorithms,
+            policy.cloned(),
+            max_len,
+        ))
+    }
+
+    /// Attaches the reason of a failure to the response as an Extended DNS Error, RFC 8914, if the
+    ///  request has EDNS, a response to a request without it must not have any
+    fn set_extended_error(
+        request: &Message,
+        response: &mut Message,
+        info_code: ExtendedErrorCode,
+        extra_text: &str,
+    ) {
+        if request.edns().is_none() {
+            return;
+        }
+
+        let error = ExtendedError::new(info_code, extra_text.to_string());
+        response.edns_mut().set_option(EdnsOption::ExtendedError(error));
+    }
+
+    /// Assembles the answer and authority sections for a query against the authority, altered by
+    ///  the response policy of the zone, if it has one
+    fn assemble_answer(
+        authority: &Authority,
+        query: &Query,
+        serial: u32,
+        is_dnssec: bool,
+        supported_algorithms: SupportedAlgorithms,
+        policy: Option<&ResponsePolicy>,
+    ) -> CachedAnswer {
+        let mut answer = CachedAnswer {
+            origin: authority.origin().clone(),
+            serial: serial,
+            response_code: ResponseCode::NoError,
+            authoritative: false,
+            answers: vec![],
+            name_servers: vec![],
+        };
+
+        let mut records = authority.search(query, is_dnssec, supported_algorithms);
+        let found = !records.is_empty();
+        if let Some(policy) = policy {
+            let len = records.len();
+            records.retain(|record| !policy.is_hidden(record));
+
+            // the signatures of the hidden records are not served alone
+            if records.len() != len &&
+                records.iter().all(|record| record.rr_type() == RecordType::RRSIG)
+            {
+                records.clear();
+            }
+        }
+
+        if !records.is_empty() {
+            answer.response_code = ResponseCode::NoError;
+            answer.authoritative = true;
+            answer.answers.extend(records.into_iter().cloned());
+
+            // get the NS records
+            let ns = authority.ns(is_dnssec, supported_algorithms);
+            if ns.is_empty() {
+                warn!("there are no NS records for: {:?}", authority.origin());
+            } else {
+                answer.name_servers.extend(ns.into_iter().cloned());
+            }
         } else {
-            let name = name.base_name();
-            if !name.is_root() {
-                return self.find_auth_recurse(&name);
+            // the name exists if it has records of other types, or only names below it
+            let exists = found || authority.contains_name(query.name());
+
+            if is_dnssec && !found {
+                // get the NSEC or NSEC3 records of the proof
+                let denials = authority.get_denial_records(
+                    query.name(),
+                    exists,
+                    is_dnssec,
+                    supported_algorithms,
+                );
+                answer.name_servers.extend(denials.into_iter().cloned());
+            }
+
+            // in the not found case it's standard to return the SOA in the authority section
+            // TODO: improve: see https://tools.ietf.org/html/rfc2308 for proper response construct
+            answer.response_code = if exists {
+                // NoData, or the records were all hidden by the policy, the name still exists
+                ResponseCode::NoError
+            } else {
+                ResponseCode::NXDomain
+            };
+
+            let soa = authority.soa_secure(is_dnssec, supported_algorithms);
+            if soa.is_empty() {
+                warn!("there is no SOA record for: {:?}", authority.origin());
+            } else {
+                answer.name_servers.extend(soa.into_iter().cloned());
+            }
+        }
+
+        if let Some(policy) = policy {
+            policy.clamp_ttls(&mut answer.answers);
+            policy.clamp_ttls(&mut answer.name_servers);
+        }
+
+        answer
+    }
+
+    /// Assembles the answer to an incremental zone transfer, IXFR, from the version of the zone
+    ///  with the serial of the SOA in the authority section of the request, RFC 1995
+    fn assemble_ixfr(
+        authority: &Authority,
+        request: &Message,
+        query: &Query,
+        is_dnssec: bool,
+        supported_algorithms: SupportedAlgorithms,
+        policy: Option<&ResponsePolicy>,
+    ) -> CachedAnswer {
+        let mut answer = CachedAnswer {
+            origin: authority.origin().clone(),
+            serial: authority.serial(),
+            response_code: ResponseCode::NoError,
+            authoritative: false,
+            answers: vec![],
+            name_servers: vec![],
+        };
+
+        // only whole zones are transferred
+        if query.name() != authority.origin() {
+            answer.response_code = ResponseCode::NotAuth;
+            return answer;
+        }
+
+        let serial = request
+            .name_servers()
+            .iter()
+            .filter(|record| record.name() == authority.origin())
+            .filter_map(|record| match *record.rdata() {
+                RData::SOA(ref soa) => Some(soa.serial()),
+                _ => None,
+            })
+            .next();
+        let serial = match serial {
+            Some(serial) => serial,
+            None => {
+                warn!("request: {} is an IXFR without the SOA of the client", request.id());
+                answer.response_code = ResponseCode::FormErr;
+                return answer;
+            }
+        };
+
+        let mut records = authority.ixfr(serial, is_dnssec, supported_algorithms);
+        if records.is_empty() {
+            answer.response_code = ResponseCode::Refused;
+            return answer;
+        }
+
+        if let Some(policy) = policy {
+            records.retain(|record| !policy.is_hidden(record));
+        }
+        answer.authoritative = true;
+        answer.answers.extend(records.into_iter().cloned());
+        if let Some(policy) = policy {
+            policy.clamp_ttls(&mut answer.answers);
+        }
+
+        answer
+    }
+
+    /// Returns true if the closest enclosing zone of the name has an NSEC3 chain
+    fn is_nsec3_zone(&self, name: &Name) -> bool {
+        match self.find_auth(name) {
+            Some(authority) => {
+                let is_nsec3 = authority
+                    .read()
+                    .unwrap() // poison errors should panic
+                    .is_nsec3();
+                is_nsec3
             }
+            None => false,
+        }
+    }
+
+    /// Returns the closest enclosing zone of the name, in one walk down the labels of the name
+    fn find_auth(&self, name: &Name) -> Option<Arc<RwLock<Zone>>> {
+        self.authorities
+            .read()
+            .unwrap() // poison errors should panic
+            .find(name)
+            .map(|(_, authority)| authority.clone())
+    }
+
+    /// Returns the zone with exactly this origin
+    /// Brings the PTR records of the reverse zones in line with the zone, which changed, if it is
+    ///  one of their forward zones, or regenerates them if it is a reverse zone
+    fn sync_reverse_zones(&self, name: &Name) {
+        for (reverse, forward_zones) in &self.reverse_zones {
+            if reverse == name {
+                // the zone was replaced, without the PTR records which were generated
+                self.sync_reverse_zone(reverse, true);
+            } else if forward_zones.contains(name) {
+                self.sync_reverse_zone(reverse, false);
+            }
+        }
+    }
+
+    /// Updates the reverse zone with the changes to the PTR records generated from its forward
+    ///  zones since they were last generated, or with all of them if it is `regenerate`d
+    fn sync_reverse_zone(&self, name: &Name, regenerate: bool) {
+        let forward_zones = match self.reverse_zones.get(name) {
+            Some(forward_zones) => forward_zones,
+            None => return,
+        };
+        let zone = match self.zone(name) {
+            Some(zone) => zone,
+            None => return,
+        };
+
+        let mut ptrs = Vec::new();
+        for forward in forward_zones {
+            if let Some(forward) = self.zone(forward) {
+                // poison errors should panic...
+                ptrs.extend(reverse_zone::ptr_records(name, &forward.read().unwrap()));
+            }
+        }
+
+        // poison errors should panic...
+        let mut generated_ptrs = self.generated_ptrs.lock().unwrap();
+        let generated = generated_ptrs.entry(name.clone()).or_insert_with(Vec::new);
+        if regenerate {
+            generated.clear();
+        }
+        let update = reverse_zone::ptr_update(generated, &ptrs);
+        if update.is_empty() {
+            return;
+        }
+
+        // the zone is released before it is notified, poison errors should panic...
+        let result = zone.write().unwrap().update_records(&update, true);
+        match result {
+            Ok(changed) => {
+                debug!("generated {} PTR records of {}", ptrs.len(), name);
+                *generated = ptrs;
+                self.response_cache.invalidate_zone(name);
+                if changed {
+                    self.notify(name);
+                }
+            }
+            Err(response_code) => {
+                warn!("could not generate PTR records of {}: {:?}", name, response_code)
+            }
+        }
+    }
+
+    fn zone(&self, name: &Name) -> Option<Arc<RwLock<Zone>>> {
+        self.authorities
+            .read()
+            .unwrap() // poison errors should panic
+            .get(name)
+            .cloned()
+    }
+
+    /// Returns the closest enclosing zone of the name if it failed to load, and no version of it is
+    ///  being served
+    fn find_failed_zone(&self, name: &Name) -> Option<Name> {
+        let is_failed = {
+            let failed_zones = self.failed_zones.read().unwrap(); // poison errors should panic
+            if failed_zones.is_empty() {
+                return None;
+            }
+            failed_zones.contains_key(name)
+        };
+        if self.zone(name).is_some() {
+            return None;
+        }
+
+        if is_failed {
+            return Some(name.clone());
+        }
+
+        let name = name.base_name();
+        if !name.is_root() {
+            return self.find_failed_zone(&name);
         }
 
         None
     }
 }
+
+/// Finishes the responses to a request, see `Catalog::prepare`
+struct ResponseFinisher {
+    resp_edns: Option<Edns>,
+    // the key which signed the request, and the MAC of the request
+    request_tsig: Option<(TSigner, Vec<u8>)>,
+    // the MAC of the previous response, which the next one continues from
+    prior_mac: Option<Vec<u8>>,
+}
+
+impl ResponseFinisher {
+    /// Each response is sent with the EDNS of the request, and signed with its key, RFC 8945
+    ///  section 5.3.1, the TSIG must be the last record of the response, RFC 8945 section 5.3
+    ///
+    /// The responses after the first, e.g. the messages of a zone transfer, are signed as a
+    ///  continuation of the one before, RFC 8945 section 5.3.1.
+    fn finish(&mut self, mut response: Message) -> Message {
+        if let Some(ref resp_edns) = self.resp_edns {
+            Catalog::set_response_edns(&mut response, resp_edns.clone());
+        }
+
+        if let Some((ref tsigner, ref request_mac)) = self.request_tsig {
+            let current_time = Utc::now().timestamp() as u64;
+            let signed = match self.prior_mac {
+                Some(ref prior_mac) => {
+                    tsigner.sign_message_continued(&response, prior_mac, current_time)
+                }
+                None => tsigner.sign_message(&response, Some(request_mac), current_time, 0, vec![]),
+            };
+            match signed {
+                Ok(tsig) => {
+                    if let RData::TSIG(ref tsig) = *tsig.rdata() {
+                        self.prior_mac = Some(tsig.mac().to_vec());
+                    }
+                    response.add_sig0(tsig);
+                }
+                Err(e) => error!("could not sign response {}: {}", response.id(), e),
+            }
+        }
+
+        response
+    }
+}
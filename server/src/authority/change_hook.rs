@@ -0,0 +1,148 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Hooks called with the changes to the records of a zone, e.g. to mirror them elsewhere
+
+use trust_dns::client::ZoneDiff;
+use trust_dns::rr::{Name, Record, RecordSet, RecordType};
+
+use authority::ZoneStore;
+
+/// The change to a single RRset of a zone
+#[derive(Clone, Debug, PartialEq)]
+pub struct RRsetChange {
+    name: Name,
+    record_type: RecordType,
+    before: Option<RecordSet>,
+    after: Option<RecordSet>,
+}
+
+impl RRsetChange {
+    /// The name of the RRset
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// The type of the RRset
+    pub fn record_type(&self) -> RecordType {
+        self.record_type
+    }
+
+    /// The RRset before the change, None if it was added
+    pub fn before(&self) -> Option<&RecordSet> {
+        self.before.as_ref()
+    }
+
+    /// The RRset after the change, None if it was deleted
+    pub fn after(&self) -> Option<&RecordSet> {
+        self.after.as_ref()
+    }
+}
+
+/// Called with the changes to the records of a zone, see `Authority::add_change_hook`
+///
+/// The hooks are called while the zone is locked for the change, before it is served or the
+///  update is answered, so that what they mirror is never behind what is served. They must not
+///  block for long, nor use the zone themselves.
+pub trait ChangeHook: Send + Sync {
+    /// Called once for each change to the zone which altered any RRsets
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone which changed
+    /// * `serial` - the SOA serial of the zone after the change
+    /// * `changes` - the RRsets which changed, in the order of their names and types, including
+    ///               the SOA and, for a signed zone, the RRsets which were signed again
+    fn on_records_changed(&self, origin: &Name, serial: u32, changes: &[RRsetChange]);
+}
+
+/// Returns true if the RRsets have the same records and signatures, the serials at which they
+///  were changed are not compared
+fn same_rrset(before: &RecordSet, after: &RecordSet) -> bool {
+    before.ttl() == after.ttl() && before.iter().eq(after.iter()) &&
+        before.rrsigs() == after.rrsigs()
+}
+
+/// Returns the RRsets which differ between the records of a zone before and after a change,
+///  empty RRsets are taken as deleted
+pub(crate) fn diff(before: &ZoneStore, after: &ZoneStore) -> Vec<RRsetChange> {
+    let non_empty = |rrset: &RecordSet| if rrset.is_empty() && rrset.rrsigs().is_empty() {
+        None
+    } else {
+        Some(rrset.clone())
+    };
+
+    let mut keys = before
+        .iter()
+        .chain(after.iter())
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let old = before.get_rrset(key).and_then(&non_empty);
+            let new = after.get_rrset(key).and_then(&non_empty);
+            let changed = match (old.as_ref(), new.as_ref()) {
+                (Some(old), Some(new)) => !same_rrset(old, new),
+                (None, None) => false,
+                _ => true,
+            };
+
+            if !changed {
+                return None;
+            }
+            Some(RRsetChange {
+                name: key.name.clone(),
+                record_type: key.record_type,
+                before: old,
+                after: new,
+            })
+        })
+        .collect()
+}
+
+/// Returns the records which were deleted and added by the changes, as the step from the version
+///  of the zone with the old SOA to the one with the new SOA of an incremental zone transfer
+pub(crate) fn zone_diff(old_soa: Record, new_soa: Record, changes: &[RRsetChange]) -> ZoneDiff {
+    // the SOA is carried apart from the other records, its signatures are not
+    let records = |rrset: Option<&RecordSet>| -> Vec<Record> {
+        rrset.map_or(vec![], |rrset| {
+            rrset
+                .iter()
+                .filter(|record| record.rr_type() != RecordType::SOA)
+                .chain(rrset.rrsigs().iter())
+                .cloned()
+                .collect()
+        })
+    };
+
+    // records are equal regardless of their TTLs, a change of TTL is a change to transfer
+    let contains = |records: &[Record], record: &Record| {
+        records.iter().any(|other| other == record && other.ttl() == record.ttl())
+    };
+
+    let mut deleted = vec![];
+    let mut added = vec![];
+    for change in changes {
+        let before = records(change.before());
+        let after = records(change.after());
+
+        deleted.extend(
+            before
+                .iter()
+                .filter(|record| !contains(&after[..], *record))
+                .cloned(),
+        );
+        added.extend(after.into_iter().filter(
+            |record| !contains(&before[..], record),
+        ));
+    }
+
+    ZoneDiff::new(old_soa, deleted, new_soa, added)
+}
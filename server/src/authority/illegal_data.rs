@@ -0,0 +1,118 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Detection of illegal data which is nonetheless seen in the wild, e.g. a CNAME at the apex of
+//!  a zone
+
+use std::fmt;
+
+use trust_dns::rr::{Name, RecordType, RrKey};
+
+use authority::ZoneStore;
+
+/// How a zone with illegal data is handled when it is loaded
+#[derive(RustcDecodable, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum IllegalDataPolicy {
+    /// The zone is not loaded
+    Reject,
+    /// The offending records are removed, the CNAME of a CNAME with other data, and an SOA which
+    ///  is not at the apex
+    Repair,
+    /// The zone is loaded as is, and the illegal data logged, this is the default
+    Warn,
+}
+
+impl Default for IllegalDataPolicy {
+    fn default() -> Self {
+        IllegalDataPolicy::Warn
+    }
+}
+
+/// Data which is illegal in a zone
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum IllegalData {
+    /// A CNAME at the apex, where the SOA and NS records must be, RFC 1912 section 2.4
+    CnameAtApex(Name),
+    /// A CNAME with other data at the same name, RFC 2181 section 10.1
+    CnameAndOtherData(Name),
+    /// An SOA other than the one at the apex, a zone has exactly one, RFC 1035 section 5.2
+    DuplicateSoa(Name),
+}
+
+impl IllegalData {
+    /// The name at which the illegal data is
+    pub fn name(&self) -> &Name {
+        match *self {
+            IllegalData::CnameAtApex(ref name) |
+            IllegalData::CnameAndOtherData(ref name) |
+            IllegalData::DuplicateSoa(ref name) => name,
+        }
+    }
+}
+
+impl fmt::Display for IllegalData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IllegalData::CnameAtApex(ref name) => write!(f, "CNAME at the zone apex: {}", name),
+            IllegalData::CnameAndOtherData(ref name) => {
+                write!(f, "CNAME and other data at: {}", name)
+            }
+            IllegalData::DuplicateSoa(ref name) => write!(f, "SOA not at the zone apex: {}", name),
+        }
+    }
+}
+
+/// Finds the illegal data in the records of the zone
+///
+/// Records of DNSSEC types, RRSIG, NSEC and NSEC3, are allowed with a CNAME. Multiple SOAs at
+///  the apex are already merged into one, the highest serial, as the records are inserted.
+pub fn find_illegal_data(origin: &Name, records: &ZoneStore) -> Vec<IllegalData> {
+    let mut illegal = Vec::new();
+
+    for (key, _) in records.iter() {
+        match key.record_type {
+            RecordType::CNAME => {
+                if key.name == *origin {
+                    illegal.push(IllegalData::CnameAtApex(key.name.clone()));
+                } else if records.iter().any(|(other, _)| {
+                    other.name == key.name && !is_allowed_with_cname(other.record_type)
+                })
+                {
+                    illegal.push(IllegalData::CnameAndOtherData(key.name.clone()));
+                }
+            }
+            RecordType::SOA if key.name != *origin => {
+                illegal.push(IllegalData::DuplicateSoa(key.name.clone()))
+            }
+            _ => (),
+        }
+    }
+
+    illegal
+}
+
+/// Removes the records which make the data illegal, see `IllegalDataPolicy::Repair`
+pub fn repair_illegal_data(illegal: &[IllegalData], records: &mut ZoneStore) {
+    for illegal in illegal {
+        let record_type = match *illegal {
+            IllegalData::CnameAtApex(..) |
+            IllegalData::CnameAndOtherData(..) => RecordType::CNAME,
+            IllegalData::DuplicateSoa(..) => RecordType::SOA,
+        };
+
+        if let Err(error) = records.delete(&RrKey::new(illegal.name(), record_type)) {
+            error!("could not repair {}: {}", illegal.name(), error);
+        }
+    }
+}
+
+fn is_allowed_with_cname(record_type: RecordType) -> bool {
+    match record_type {
+        RecordType::CNAME | RecordType::RRSIG | RecordType::NSEC | RecordType::NSEC3 => true,
+        _ => false,
+    }
+}
@@ -34,10 +34,66 @@ pub enum ZoneType {
     Forward,
 }
 
+/// The most additional iterations of the NSEC3 hash which a zone may use
+///
+/// RFC 9276 recommends no additional iterations, and allows validators to treat zones with more
+///  than 100 as insecure, as each costs a hash for every name in every proof.
+pub const MAX_NSEC3_ITERATIONS: u16 = 100;
+
+/// How a signed zone proves that names and types do not exist
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Denial {
+    /// NSEC records, which link the names of the zone in canonical order, RFC 4034
+    Nsec,
+    /// NSEC3 records, which link the hashes of the names of the zone, RFC 5155
+    Nsec3 {
+        /// additional iterations of the hash, at most `MAX_NSEC3_ITERATIONS`
+        iterations: u16,
+        /// appended to the names before they are hashed
+        salt: Vec<u8>,
+        /// if true, delegations without DS records are left out of the chain
+        opt_out: bool,
+    },
+}
+
 pub mod authority;
+mod axfr_stream;
 mod catalog;
+pub mod change_hook;
+pub mod illegal_data;
+mod notifier;
 pub mod persistence;
+mod refresher;
+mod response_cache;
+pub mod response_policy;
+mod reverse_zone;
+#[cfg(feature = "dnssec")]
+pub mod signing_authority;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
+pub mod transfer;
+mod update_forwarder;
+pub mod zone;
+mod zone_index;
+pub mod zone_stats;
+pub mod zone_store;
+#[cfg(feature = "dnssec")]
+pub mod zone_signer;
 
 pub use self::authority::Authority;
 pub use self::catalog::Catalog;
+pub use self::change_hook::{ChangeHook, RRsetChange};
+pub use self::illegal_data::{IllegalData, IllegalDataPolicy};
+pub use self::notifier::NotifyRetry;
 pub use self::persistence::Journal;
+pub use self::response_policy::ResponsePolicy;
+#[cfg(feature = "dnssec")]
+pub use self::signing_authority::SigningAuthority;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite_store::SqliteStore;
+pub use self::transfer::TransferValidation;
+pub use self::zone::Zone;
+pub use self::zone_stats::{NotifyStatus, TransferStatus, ZoneHealth, ZoneHealthStatus, ZoneStats};
+pub use self::zone_store::ZoneStore;
+#[cfg(feature = "dnssec")]
+pub use self::zone_signer::ZoneSigner;
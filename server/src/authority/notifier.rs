@@ -0,0 +1,454 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! NOTIFY of the secondaries of a primary zone when the zone changes, RFC 1996
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand;
+use trust_dns::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns::rr::{Name, RData, RecordType};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+
+use authority::Authority;
+use authority::update_forwarder::DNS_PORT;
+use authority::zone_stats::{NotifyStatus, ZoneStats};
+
+/// How NOTIFYs are retried when a secondary does not respond, see `Catalog::set_notify_retry`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NotifyRetry {
+    /// Number of times the NOTIFY is sent before the secondary is given up on
+    pub attempts: u32,
+    /// Time waited for the response to the first NOTIFY, doubled for each retry
+    pub timeout: Duration,
+    /// Each NOTIFY is delayed by a random time up to this, so that the secondaries do not all
+    ///  request the zone at once
+    pub max_jitter: Duration,
+}
+
+impl Default for NotifyRetry {
+    /// Five attempts, the first waiting 2 seconds, with up to a second of jitter
+    fn default() -> Self {
+        NotifyRetry {
+            attempts: 5,
+            timeout: Duration::from_secs(2),
+            max_jitter: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Builds the NOTIFY of the zone's SOA, None if the zone has no SOA
+///
+/// The answer section holds the new SOA, as a hint to the secondaries, RFC 1996 section 3.7.
+pub(crate) fn notify_message(authority: &Authority) -> Option<Message> {
+    let soa = match authority.soa() {
+        Some(soa) => soa.clone(),
+        None => return None,
+    };
+
+    let mut message = Message::new();
+    message
+        .set_id(rand::random())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Notify)
+        .set_authoritative(true);
+    message.add_query(Query::query(authority.origin().clone(), RecordType::SOA));
+    message.add_answer(soa);
+
+    Some(message)
+}
+
+/// The name servers of the zone which are notified, all those of the NS records at the apex
+///  except the primary named in the SOA MNAME, the "Notify Set" of RFC 1996
+pub(crate) fn notify_name_servers(authority: &Authority) -> Vec<Name> {
+    let mname = authority.soa().and_then(|soa| match *soa.rdata() {
+        RData::SOA(ref soa) => Some(soa.mname().clone()),
+        _ => None,
+    });
+
+    authority
+        .ns(false, SupportedAlgorithms::new())
+        .into_iter()
+        .filter_map(|ns| match *ns.rdata() {
+            RData::NS(ref name) => Some(name.clone()),
+            _ => None,
+        })
+        .filter(|name| Some(name) != mname.as_ref())
+        .collect()
+}
+
+/// Records the progress of the NOTIFYs of a zone in its `ZoneStats`
+#[derive(Clone)]
+pub(crate) struct NotifyRecorder {
+    zone: Name,
+    zone_stats: Arc<Mutex<HashMap<Name, ZoneStats>>>,
+}
+
+impl NotifyRecorder {
+    pub(crate) fn new(zone: Name, zone_stats: Arc<Mutex<HashMap<Name, ZoneStats>>>) -> Self {
+        NotifyRecorder { zone, zone_stats }
+    }
+
+    fn record(&self, target: SocketAddr, notify_status: NotifyStatus) {
+        match self.zone_stats.lock() {
+            Ok(mut zone_stats) => {
+                zone_stats
+                    .entry(self.zone.clone())
+                    .or_insert_with(ZoneStats::new)
+                    .record_notify(target, notify_status)
+            }
+            Err(_) => warn!("zone stats poisoned, ignoring"),
+        }
+    }
+}
+
+/// A change to a zone, of which its secondaries are notified, see `Notifies`
+pub(crate) struct NotifyChange {
+    /// the NOTIFY, see `notify_message`
+    pub(crate) message: Message,
+    /// the serial of the zone in the NOTIFY
+    pub(crate) serial: u32,
+    /// the name servers which are notified, see `notify_name_servers`
+    pub(crate) name_servers: Vec<Name>,
+    /// the addresses which are notified in addition to the name servers
+    pub(crate) also_notify: Vec<SocketAddr>,
+    /// how the NOTIFY is retried
+    pub(crate) retry: NotifyRetry,
+    /// where the outcome for each secondary is recorded, by the zone
+    pub(crate) recorder: NotifyRecorder,
+}
+
+/// The NOTIFYs of the changes to the zones of a `Catalog`, which are being sent
+///
+/// All of them are sent by one thread, which is started for the first change, and stops once
+///  every secondary has responded, or was given up on. The NOTIFYs of a zone are kept only for
+///  its latest change, one which is queued while the NOTIFYs of an earlier serial are still being
+///  retried replaces them, so that a burst of updates costs one entry per zone, and the
+///  secondaries are only told of the latest serial.
+#[derive(Clone, Default)]
+pub(crate) struct Notifies {
+    queue: Arc<Mutex<NotifyQueue>>,
+}
+
+#[derive(Default)]
+struct NotifyQueue {
+    // the latest change of each zone, which the thread has not taken yet
+    changes: HashMap<Name, NotifyChange>,
+    // true while the thread runs
+    running: bool,
+}
+
+impl Notifies {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues the NOTIFYs of the change, in place of those of any earlier change to its zone,
+    ///  and starts the thread which sends them if it is not running
+    pub(crate) fn notify(&self, change: NotifyChange) {
+        let mut queue = match self.queue.lock() {
+            Ok(queue) => queue,
+            Err(_) => {
+                warn!("notifies poisoned, not notifying: {}", change.recorder.zone);
+                return;
+            }
+        };
+
+        queue.changes.insert(change.recorder.zone.clone(), change);
+        if queue.running {
+            return;
+        }
+
+        let notifies = self.queue.clone();
+        let spawned = thread::Builder::new()
+            .name("notify".to_string())
+            .spawn(move || send_notifies(&notifies));
+        match spawned {
+            Ok(_) => queue.running = true,
+            // the change stays queued, for the next one to start the thread
+            Err(e) => warn!("could not spawn notify thread: {}", e),
+        }
+    }
+}
+
+/// Time waited for responses before the thread looks for NOTIFYs which are due, and changes
+///  which were queued
+const RECEIVE_TIMEOUT_MS: u64 = 10;
+
+/// Responses taken from a socket each time, so that a flood of datagrams can not keep the thread
+///  from the NOTIFYs which are due
+const RECEIVE_BUDGET: usize = 64;
+
+/// Sends the NOTIFYs of the queued changes, and takes the responses to them, until none are left
+fn send_notifies(queue: &Mutex<NotifyQueue>) {
+    let mut sockets = NotifySockets::default();
+    let mut zones: HashMap<Name, ZoneNotify> = HashMap::new();
+
+    loop {
+        let changes = match queue.lock() {
+            Ok(mut queue) => {
+                if queue.changes.is_empty() && zones.is_empty() {
+                    queue.running = false;
+                    return;
+                }
+                queue.changes.drain().map(|(_, change)| change).collect::<Vec<_>>()
+            }
+            Err(_) => {
+                warn!("notifies poisoned, no longer notifying");
+                return;
+            }
+        };
+
+        // the secondaries which have not responded to an earlier serial are not retried
+        for change in changes {
+            let zone = change.recorder.zone.clone();
+            match ZoneNotify::new(change) {
+                Some(notify) => {
+                    if zones.insert(zone.clone(), notify).is_some() {
+                        debug!("notifying {} of its latest serial instead", zone);
+                    }
+                }
+                None => {
+                    zones.remove(&zone);
+                }
+            }
+        }
+
+        let now = Instant::now();
+        for notify in zones.values_mut() {
+            notify.send_due(&mut sockets, now);
+        }
+
+        sockets.receive(&mut |from, response| {
+            for notify in zones.values_mut() {
+                notify.respond(from, &response);
+            }
+        });
+        zones.retain(|_, notify| !notify.is_done());
+    }
+}
+
+/// One secondary which is notified of a change
+struct NotifyTarget {
+    addr: SocketAddr,
+    attempts: u32,
+    timeout: Duration,
+    // when the NOTIFY is sent next, or the secondary given up on
+    next: Instant,
+    last_error: Option<String>,
+    done: bool,
+}
+
+/// The NOTIFYs of the latest change to a zone
+struct ZoneNotify {
+    id: u16,
+    buffer: Vec<u8>,
+    serial: u32,
+    attempts: u32,
+    recorder: NotifyRecorder,
+    targets: Vec<NotifyTarget>,
+}
+
+impl ZoneNotify {
+    /// Resolves the name servers to notify, each NOTIFY is delayed by its jitter, returns None
+    ///  if the NOTIFY can not be encoded
+    fn new(change: NotifyChange) -> Option<Self> {
+        let buffer = match change.message.to_vec() {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                warn!("could not encode notify of {}: {}", change.recorder.zone, e);
+                return None;
+            }
+        };
+
+        let mut addrs = change.also_notify;
+        for name_server in change.name_servers {
+            let host = name_server.to_string();
+            match (host.trim_right_matches('.'), DNS_PORT).to_socket_addrs() {
+                Ok(resolved) => addrs.extend(resolved),
+                Err(e) => warn!("could not resolve name server {} to notify: {}", name_server, e),
+            }
+        }
+
+        let now = Instant::now();
+        let retry = change.retry;
+        let targets = addrs
+            .into_iter()
+            .map(|addr| {
+                NotifyTarget {
+                    addr: addr,
+                    attempts: 0,
+                    timeout: retry.timeout,
+                    next: now + jitter(retry.max_jitter),
+                    last_error: None,
+                    done: false,
+                }
+            })
+            .collect();
+
+        Some(ZoneNotify {
+            id: change.message.id(),
+            buffer: buffer,
+            serial: change.serial,
+            attempts: retry.attempts.max(1),
+            recorder: change.recorder,
+            targets: targets,
+        })
+    }
+
+    /// Sends the NOTIFY to the secondaries whose time has come, doubling the time waited for a
+    ///  response with each attempt, or gives up on those which did not respond to any of them
+    fn send_due(&mut self, sockets: &mut NotifySockets, now: Instant) {
+        let serial = self.serial;
+        for target in self.targets.iter_mut() {
+            if target.done || target.next > now {
+                continue;
+            }
+
+            if target.attempts >= self.attempts {
+                let error = target.last_error.take().unwrap_or_else(
+                    || "no response".to_string(),
+                );
+                warn!("giving up on notify of {}: {}", target.addr, error);
+                self.recorder.record(target.addr, NotifyStatus::Failed { serial, error });
+                target.done = true;
+                continue;
+            }
+
+            target.attempts += 1;
+            let attempts = target.attempts;
+            self.recorder.record(target.addr, NotifyStatus::Pending { serial, attempts });
+
+            debug!("notifying {} of serial {}, attempt {}", target.addr, serial, attempts);
+            if let Err(e) = sockets.send(&self.buffer, target.addr) {
+                debug!("could not notify {}: {}", target.addr, e);
+                target.last_error = Some(format!("{}", e));
+            }
+            target.next = now + target.timeout;
+            target.timeout = target.timeout * 2;
+        }
+    }
+
+    /// Takes the response, if it is from a secondary which was sent this NOTIFY
+    fn respond(&mut self, from: SocketAddr, response: &Message) {
+        if response.id() != self.id || response.message_type() != MessageType::Response ||
+            response.op_code() != OpCode::Notify
+        {
+            return;
+        }
+
+        let serial = self.serial;
+        for target in self.targets.iter_mut() {
+            if target.done || target.attempts == 0 || target.addr != from {
+                continue;
+            }
+
+            target.done = true;
+            let attempts = target.attempts;
+            match response.response_code() {
+                // a secondary without NOTIFY support has still received it, RFC 1996 section 3.12
+                ResponseCode::NoError |
+                ResponseCode::NotImp => {
+                    self.recorder.record(from, NotifyStatus::Succeeded { serial, attempts })
+                }
+                response_code => {
+                    warn!("notify of {} refused: {}", from, response_code);
+                    self.recorder.record(
+                        from,
+                        NotifyStatus::Failed {
+                            serial,
+                            error: format!("{}", response_code),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// True once every secondary responded, or was given up on
+    fn is_done(&self) -> bool {
+        self.targets.iter().all(|target| target.done)
+    }
+}
+
+/// The sockets from which the NOTIFYs are sent, one for each address family, bound as they are
+///  needed
+#[derive(Default)]
+struct NotifySockets {
+    v4: Option<UdpSocket>,
+    v6: Option<UdpSocket>,
+}
+
+impl NotifySockets {
+    fn send(&mut self, buffer: &[u8], target: SocketAddr) -> io::Result<()> {
+        let socket = try!(self.socket(target));
+        try!(socket.send_to(buffer, target));
+        Ok(())
+    }
+
+    fn socket(&mut self, target: SocketAddr) -> io::Result<&UdpSocket> {
+        let (socket, local) = match target {
+            SocketAddr::V4(..) => (&mut self.v4, IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
+            SocketAddr::V6(..) => {
+                (&mut self.v6, IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)))
+            }
+        };
+
+        if socket.is_none() {
+            let bound = try!(UdpSocket::bind(SocketAddr::new(local, 0)));
+            try!(bound.set_read_timeout(
+                Some(Duration::from_millis(RECEIVE_TIMEOUT_MS)),
+            ));
+            *socket = Some(bound);
+        }
+        Ok(socket.as_ref().expect("bound above"))
+    }
+
+    /// Passes the responses which arrive within the receive timeout to `received`, or sleeps
+    ///  for the timeout if nothing was sent yet
+    fn receive(&self, received: &mut FnMut(SocketAddr, Message)) {
+        if self.v4.is_none() && self.v6.is_none() {
+            thread::sleep(Duration::from_millis(RECEIVE_TIMEOUT_MS));
+            return;
+        }
+
+        let mut buffer = [0u8; 4096];
+        for socket in self.v4.iter().chain(self.v6.iter()) {
+            for _ in 0..RECEIVE_BUDGET {
+                let (len, from) = match socket.recv_from(&mut buffer) {
+                    Ok(received) => received,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock ||
+                                  e.kind() == io::ErrorKind::TimedOut => break,
+                    Err(e) => {
+                        debug!("error receiving notify responses: {}", e);
+                        break;
+                    }
+                };
+
+                // anything else is not the response to a NOTIFY
+                if let Ok(response) = Message::from_vec(&buffer[..len]) {
+                    received(from, response);
+                }
+            }
+        }
+    }
+}
+
+/// A random delay, up to the maximum
+fn jitter(max_jitter: Duration) -> Duration {
+    let max_millis = max_jitter.as_secs() * 1000 + (max_jitter.subsec_nanos() / 1_000_000) as u64;
+    if max_millis == 0 {
+        return Duration::from_millis(0);
+    }
+
+    Duration::from_millis(rand::random::<u64>() % (max_millis + 1))
+}
@@ -9,6 +9,7 @@
 
 use std::iter::Iterator;
 use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
 
 use time;
 use rusqlite;
@@ -23,8 +24,11 @@ use error::{PersistenceErrorKind, PersistenceResult};
 pub const CURRENT_VERSION: i64 = 1;
 
 /// The Journal is the audit log of all changes to a zone after initial creation.
+///
+/// The Sqlite Connection is guarded by a Mutex, this allows the Journal, and therefor the
+///  `Authority`, to be shared with worker threads.
 pub struct Journal {
-    conn: Connection,
+    conn: Mutex<Connection>,
     version: i64,
 }
 
@@ -33,7 +37,7 @@ impl Journal {
     pub fn new(conn: Connection) -> PersistenceResult<Journal> {
         let version = Self::select_schema_version(&conn);
         Ok(Journal {
-               conn: conn,
+               conn: Mutex::new(conn),
                version: try!(version),
            })
     }
@@ -50,9 +54,9 @@ impl Journal {
         }
     }
 
-    /// Returns a reference to the Sqlite Connection
-    pub fn conn(&self) -> &Connection {
-        &self.conn
+    /// Returns a guard to the Sqlite Connection, the Journal is locked until this is dropped
+    pub fn conn(&self) -> MutexGuard<Connection> {
+        self.conn.lock().unwrap() // poison errors should panic...
     }
 
     /// Returns the current schema version of the journal
@@ -88,7 +92,7 @@ impl Journal {
         let client_id: i64 = 0; // TODO: we need better id information about the client, like pub_key
         let soa_serial: i64 = soa_serial as i64;
 
-        let count = try!(self.conn
+        let count = try!(self.conn()
                              .execute("INSERT
                                           \
                                             INTO records (client_id, soa_serial, timestamp, \
@@ -114,6 +118,15 @@ impl Journal {
         Ok(())
     }
 
+    /// Removes all the records, e.g. before the zone is persisted again as a whole
+    pub fn clear(&self) -> PersistenceResult<()> {
+        assert!(self.version == CURRENT_VERSION,
+                "schema version mismatch, schema_up() resolves this");
+
+        try!(self.conn().execute("DELETE FROM records", &[]));
+        Ok(())
+    }
+
     /// Selects a record from the given row_id.
     ///
     /// This allows for the entire set of records to be iterated through, by starting at 0, and
@@ -127,7 +140,8 @@ impl Journal {
         assert!(self.version == CURRENT_VERSION,
                 "schema version mismatch, schema_up() resolves this");
 
-        let mut stmt = try!(self.conn
+        let conn = self.conn();
+        let mut stmt = try!(conn
                                 .prepare("SELECT _rowid_, record
                                             \
                                                FROM records
@@ -202,7 +216,7 @@ impl Journal {
         // validate the versions of all the schemas...
         assert!(new_version <= CURRENT_VERSION);
 
-        let count = try!(self.conn
+        let count = try!(self.conn()
                              .execute("UPDATE tdns_schema SET version = $1", &[&new_version]));
 
         //
@@ -227,7 +241,7 @@ impl Journal {
 
     /// initial schema, include the tdns_schema table for tracking the Journal version
     fn init_up(&self) -> PersistenceResult<i64> {
-        let count = try!(self.conn
+        let count = try!(self.conn()
                              .execute("CREATE TABLE tdns_schema (
                                           \
                                             version INTEGER NOT NULL
@@ -237,7 +251,7 @@ impl Journal {
         //
         assert_eq!(count, 0);
 
-        let count = try!(self.conn
+        let count = try!(self.conn()
                              .execute("INSERT INTO tdns_schema (version) VALUES (0)", &[]));
         //
         assert_eq!(count, 1);
@@ -249,7 +263,7 @@ impl Journal {
     ///  authority. Each record is expected to be in the format of an update record
     fn records_up(&self) -> PersistenceResult<i64> {
         // we'll be using rowid for our primary key, basically: `rowid INTEGER PRIMARY KEY ASC`
-        let count = try!(self.conn
+        let count = try!(self.conn()
                              .execute("CREATE TABLE records (
                                           \
                                             client_id      INTEGER NOT NULL,
@@ -0,0 +1,258 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Refresh of secondary zones from their primary, on the timers of their SOA, RFC 1034 section
+//!  4.3.5, and when the primary sends a NOTIFY, RFC 1996 section 4
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use trust_dns::client::IncrementalChange;
+use trust_dns::rr::{Name, RData, RecordType};
+use trust_dns::tcp::TcpClientConnection;
+
+use authority::{Authority, TransferValidation, Zone};
+use authority::update_forwarder::UpdateForwarder;
+use authority::zone_stats::{TransferStatus, ZoneStats};
+
+/// Seconds between the attempts to transfer a secondary zone which has not been transferred yet,
+///  and so has no SOA with a retry interval
+const BOOTSTRAP_RETRY: u64 = 60;
+
+/// The refreshes of secondary zones which are in progress
+///
+/// A NOTIFY received during the refresh of its zone queues one more refresh, once it is done,
+///  any further ones are dropped, RFC 1996 section 4.4.
+#[derive(Clone, Default)]
+pub(crate) struct Refreshes {
+    // the zones being refreshed, true if another refresh is queued
+    in_progress: Arc<Mutex<HashMap<Name, bool>>>,
+}
+
+impl Refreshes {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if the zone is being refreshed
+    pub(crate) fn is_refreshing(&self, origin: &Name) -> bool {
+        self.in_progress
+            .lock()
+            .map(|in_progress| in_progress.contains_key(origin))
+            .unwrap_or(false)
+    }
+
+    /// Refreshes the zone from its primary on a thread of its own, or queues the refresh if the
+    ///  zone is already being refreshed
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the name of the zone
+    /// * `zone` - the zone, its records are replaced by those of the primary
+    /// * `primary` - the configured primary, otherwise the MNAME of the zone's SOA
+    /// * `zone_stats` - where the outcome of the transfer is recorded
+    /// * `forwarder` - keeps the primary resolved from the MNAME, for the updates forwarded to it
+    pub(crate) fn spawn_refresh(
+        &self,
+        origin: Name,
+        zone: Arc<RwLock<Zone>>,
+        primary: Option<SocketAddr>,
+        zone_stats: Arc<Mutex<HashMap<Name, ZoneStats>>>,
+        forwarder: UpdateForwarder,
+    ) {
+        {
+            let mut in_progress = match self.in_progress.lock() {
+                Ok(in_progress) => in_progress,
+                Err(_) => {
+                    warn!("refreshes poisoned, not refreshing: {}", origin);
+                    return;
+                }
+            };
+
+            if let Some(queued) = in_progress.get_mut(&origin) {
+                debug!("already refreshing {}, queueing another refresh", origin);
+                *queued = true;
+                return;
+            }
+            in_progress.insert(origin.clone(), false);
+        }
+
+        let refreshes = self.clone();
+        let thread_origin = origin.clone();
+        let spawned = thread::Builder::new()
+            .name(format!("refresh {}", origin))
+            .spawn(move || {
+                let origin = thread_origin;
+                loop {
+                    let result = refresh(&zone, primary, &forwarder);
+                    match zone_stats.lock() {
+                        Ok(mut zone_stats) => {
+                            let stats = zone_stats.entry(origin.clone()).or_insert_with(
+                                ZoneStats::new,
+                            );
+                            match result {
+                                Ok(Some(serial)) => {
+                                    info!("refreshed {} to serial {}", origin, serial);
+                                    stats.record_transfer(TransferStatus::Succeeded(serial))
+                                }
+                                Ok(None) => stats.record_refresh(),
+                                Err(error) => {
+                                    warn!("could not refresh {}: {}", origin, error);
+                                    stats.record_transfer(TransferStatus::Failed(error))
+                                }
+                            }
+                        }
+                        Err(_) => warn!("zone stats poisoned, ignoring"),
+                    }
+
+                    if !refreshes.take_queued(&origin) {
+                        break;
+                    }
+                }
+            });
+
+        if let Err(e) = spawned {
+            warn!("could not spawn refresh thread for {}: {}", origin, e);
+            if let Ok(mut in_progress) = self.in_progress.lock() {
+                in_progress.remove(&origin);
+            }
+        }
+    }
+
+    /// Returns true if another refresh of the zone was queued, otherwise the refresh of the zone
+    ///  is done
+    fn take_queued(&self, origin: &Name) -> bool {
+        let mut in_progress = match self.in_progress.lock() {
+            Ok(in_progress) => in_progress,
+            Err(_) => return false,
+        };
+
+        match in_progress.get(origin).cloned() {
+            Some(true) => {
+                in_progress.insert(origin.clone(), false);
+                true
+            }
+            Some(false) => {
+                in_progress.remove(origin);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// Returns true if the secondary zone is due to be refreshed, RFC 1034 section 4.3.5
+///
+/// That is once the SOA refresh interval passed since it was last refreshed, and, after an attempt
+///  which failed, once the SOA retry interval passed since that attempt. A zone which has not
+///  been transferred yet is due at once, and then every `BOOTSTRAP_RETRY` seconds.
+pub(crate) fn is_refresh_due(authority: &Authority, stats: &ZoneStats) -> bool {
+    let (refresh, retry) = match authority.soa().map(|soa| soa.rdata()) {
+        // negative intervals are nonsensical, treat them as already elapsed
+        Some(&RData::SOA(ref soa)) => (soa.refresh().max(0) as u64, soa.retry().max(0) as u64),
+        _ => (0, BOOTSTRAP_RETRY),
+    };
+
+    let is_retry_due = match *stats.transfer_status() {
+        TransferStatus::Failed(..) => {
+            stats.since_transfer().map_or(
+                true,
+                |since_transfer| since_transfer >= Duration::from_secs(retry),
+            )
+        }
+        _ => true,
+    };
+
+    stats.since_refresh() >= Duration::from_secs(refresh) && is_retry_due
+}
+
+/// Transfers the changes to the zone since its serial from the primary, and applies them to the
+///  zone, returns the new serial, or None if the zone is up to date
+///
+/// The changes are requested with an IXFR, to which a primary without them responds with the whole
+///  zone, and a primary without changes with just its SOA, which serves as the SOA query. A zone
+///  which has not been transferred yet is requested with an AXFR. The MNAME of a zone without a
+///  configured primary is resolved again for each refresh.
+fn refresh(
+    zone: &RwLock<Zone>,
+    configured: Option<SocketAddr>,
+    forwarder: &UpdateForwarder,
+) -> Result<Option<u32>, String> {
+    let (origin, serial, mname, is_transferred) = {
+        let authority = zone.read().unwrap(); // poison errors should panic...
+        let mname = authority.soa().and_then(|soa| match *soa.rdata() {
+            RData::SOA(ref soa) => Some(soa.mname().clone()),
+            _ => None,
+        });
+        let is_transferred = authority.soa().is_some();
+        (authority.origin().clone(), authority.serial(), mname, is_transferred)
+    };
+
+    let primary = configured.or_else(|| {
+        mname.and_then(|mname| forwarder.resolve_primary(&origin, &mname))
+    });
+    let primary = match primary {
+        Some(primary) => primary,
+        None => return Err("there is no primary to transfer the zone from".to_string()),
+    };
+
+    debug!("refreshing {} from serial {} from primary: {}", origin, serial, primary);
+    let connection = try!(TcpClientConnection::new(primary).map_err(|e| format!("{}", e)));
+    let changes = if is_transferred {
+        try!(connection.ixfr(origin.clone(), serial).map_err(
+            |e| format!("{}", e),
+        ))
+    } else {
+        vec![
+            IncrementalChange::Zone(try!(connection.axfr(origin.clone()).map_err(
+                |e| format!("{}", e),
+            ))),
+        ]
+    };
+    if changes.is_empty() {
+        debug!("{} is up to date at serial {}", origin, serial);
+        return Ok(None);
+    }
+
+    // the zone is not held while it is transferred, it must not have changed meanwhile
+    let mut authority = zone.write().unwrap(); // poison errors should panic...
+    if authority.serial() != serial {
+        return Err(format!(
+            "the zone changed from serial {} to {} during the transfer",
+            serial,
+            authority.serial()
+        ));
+    }
+
+    let mut records = authority
+        .records()
+        .iter()
+        .flat_map(|(_, rrset)| rrset.iter().chain(rrset.rrsigs().iter()))
+        .cloned()
+        .collect::<Vec<_>>();
+    for change in changes {
+        match change {
+            IncrementalChange::Zone(zone_records) => records = zone_records,
+            IncrementalChange::Diff(diff) => {
+                records.retain(|record| {
+                    !(record.name() == &origin && record.rr_type() == RecordType::SOA) &&
+                        !diff.deleted().contains(record)
+                });
+                records.push(diff.new_soa().clone());
+                records.extend(diff.added().iter().cloned());
+            }
+        }
+    }
+
+    try!(TransferValidation::new().validate(&origin, &records).map_err(
+        |e| format!("{}", e),
+    ));
+    Ok(Some(authority.authority_mut().replace_records(records)))
+}
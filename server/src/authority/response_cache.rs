@@ -0,0 +1,140 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Cache of assembled answers for the `Catalog`
+
+use std::sync::{Arc, Mutex};
+
+use lru_cache::LruCache;
+
+use trust_dns::op::{Query, ResponseCode};
+use trust_dns::rr::{DNSClass, Name, Record, RecordType};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+
+/// Everything that can change the assembled answer to a query, other than the zone data itself
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct ResponseKey {
+    name: Name,
+    query_type: RecordType,
+    query_class: DNSClass,
+    is_dnssec: bool,
+    supported_algorithms: SupportedAlgorithms,
+}
+
+impl ResponseKey {
+    pub(crate) fn new(
+        query: &Query,
+        is_dnssec: bool,
+        supported_algorithms: SupportedAlgorithms,
+    ) -> Self {
+        ResponseKey {
+            name: query.name().clone(),
+            query_type: query.query_type(),
+            query_class: query.query_class(),
+            is_dnssec: is_dnssec,
+            // the algorithms only matter when RRSIGs are being returned
+            supported_algorithms: if is_dnssec {
+                supported_algorithms
+            } else {
+                SupportedAlgorithms::new()
+            },
+        }
+    }
+}
+
+/// The portion of a response which was assembled from a zone for a single query
+#[derive(Debug)]
+pub(crate) struct CachedAnswer {
+    /// The zone from which the answer was assembled
+    pub origin: Name,
+    /// The serial of the zone at the time the answer was assembled
+    pub serial: u32,
+    pub response_code: ResponseCode,
+    pub authoritative: bool,
+    pub answers: Vec<Record>,
+    pub name_servers: Vec<Record>,
+}
+
+/// An LRU of assembled answers, a capacity of 0 disables the cache
+pub(crate) struct ResponseCache {
+    capacity: usize,
+    cache: Mutex<LruCache<ResponseKey, Arc<CachedAnswer>>>,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        ResponseCache {
+            capacity: capacity,
+            cache: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the cached answer, if it was assembled from the same version of the zone
+    pub(crate) fn get(&self, key: &ResponseKey, origin: &Name, serial: u32) -> Option<Arc<CachedAnswer>> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let mut cache = match self.cache.lock() {
+            Ok(cache) => cache,
+            Err(_) => {
+                warn!("response cache poisoned, ignoring");
+                return None;
+            }
+        };
+
+        let is_stale = match cache.get_mut(key) {
+            Some(answer) => {
+                if &answer.origin == origin && answer.serial == serial {
+                    return Some(answer.clone());
+                }
+                true
+            }
+            None => false,
+        };
+
+        if is_stale {
+            cache.remove(key);
+        }
+
+        None
+    }
+
+    pub(crate) fn insert(&self, key: ResponseKey, answer: Arc<CachedAnswer>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(key, answer);
+        }
+    }
+
+    /// Removes all answers which were assembled from the specified zone
+    pub(crate) fn invalidate_zone(&self, origin: &Name) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if let Ok(mut cache) = self.cache.lock() {
+            let keys = cache
+                .iter()
+                .filter(|&(_, answer)| &answer.origin == origin)
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>();
+
+            debug!("invalidating {} cached responses for: {}", keys.len(), origin);
+            for key in keys {
+                cache.remove(&key);
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Policies applied to the answers of a zone as they are served, rather than to its records
+
+use std::cmp;
+
+use regex::{self, Regex};
+
+use trust_dns::rr::{RData, Record, RecordType};
+
+/// How the answers of a zone are altered when they are served, see `Catalog::set_response_policy`
+///
+/// The records of the zone are left as they are, so this is for zones whose data can not be
+///  changed, e.g. a zone transferred from a primary. Answers are altered after they are signed,
+///  so hiding records from a signed zone makes its RRsets fail validation; TTLs may be lowered,
+///  validators use the lower of the TTL and the original TTL of the RRSIG.
+#[derive(Clone, Debug, Default)]
+pub struct ResponsePolicy {
+    min_ttl: Option<u32>,
+    max_ttl: Option<u32>,
+    hidden_txt: Vec<Regex>,
+    refuse_any: bool,
+}
+
+impl ResponsePolicy {
+    /// A policy which serves the answers as they are
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the lowest TTL which is served, a lower TTL is raised to this
+    pub fn set_min_ttl(&mut self, min_ttl: Option<u32>) {
+        self.min_ttl = min_ttl;
+    }
+
+    /// Sets the highest TTL which is served, a higher TTL is lowered to this
+    pub fn set_max_ttl(&mut self, max_ttl: Option<u32>) {
+        self.max_ttl = max_ttl;
+    }
+
+    /// Hides the TXT records whose text matches the regular expression, the strings of a record
+    ///  are matched as one, e.g. `^google-site-verification=`
+    pub fn hide_txt(&mut self, pattern: &str) -> Result<(), regex::Error> {
+        self.hidden_txt.push(try!(Regex::new(pattern)));
+        Ok(())
+    }
+
+    /// Sets whether queries of type ANY are refused, rather than answered with all of the
+    ///  records of the name, see RFC 8482
+    pub fn set_refuse_any(&mut self, refuse_any: bool) {
+        self.refuse_any = refuse_any;
+    }
+
+    /// Returns true if queries of the type are refused
+    pub fn is_refused(&self, query_type: RecordType) -> bool {
+        self.refuse_any && query_type == RecordType::ANY
+    }
+
+    /// Returns true if the record is hidden from answers
+    pub fn is_hidden(&self, record: &Record) -> bool {
+        if self.hidden_txt.is_empty() {
+            return false;
+        }
+
+        match *record.rdata() {
+            RData::TXT(ref txt) => {
+                let text = txt.txt_data().concat();
+                self.hidden_txt.iter().any(|regex| regex.is_match(&text))
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the TTL to serve in place of the TTL, the minimum wins if it is above the maximum
+    pub fn clamp_ttl(&self, ttl: u32) -> u32 {
+        let ttl = self.max_ttl.map_or(ttl, |max_ttl| cmp::min(ttl, max_ttl));
+        self.min_ttl.map_or(ttl, |min_ttl| cmp::max(ttl, min_ttl))
+    }
+
+    /// Clamps the TTLs of the records, see `clamp_ttl`
+    pub fn clamp_ttls(&self, records: &mut [Record]) {
+        if self.min_ttl.is_none() && self.max_ttl.is_none() {
+            return;
+        }
+
+        for record in records {
+            let ttl = self.clamp_ttl(record.ttl());
+            record.set_ttl(ttl);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Reverse zones, in `in-addr.arpa.` and `ip6.arpa.`, with PTR records generated from the A and
+//!  AAAA records of forward zones, see `Catalog::set_reverse_zone`
+
+use std::net::IpAddr;
+
+use trust_dns::rr::{DNSClass, Name, RData, Record, RecordType};
+
+use authority::Authority;
+
+/// Returns the PTR records of the addresses of the A and AAAA records of the forward zone which
+///  are within the reverse zone, pointing to the names of those records, with their TTLs
+///
+/// Wildcard names are left out, they do not name a host.
+pub(crate) fn ptr_records(reverse_origin: &Name, forward: &Authority) -> Vec<Record> {
+    forward
+        .records()
+        .iter()
+        .filter(|&(key, _)| {
+            key.record_type == RecordType::A || key.record_type == RecordType::AAAA
+        })
+        .flat_map(|(_, rrset)| rrset.iter())
+        .filter(|record| record.name().iter().next() != Some("*"))
+        .filter_map(|record| {
+            let address = match *record.rdata() {
+                RData::A(address) => IpAddr::V4(address),
+                RData::AAAA(address) => IpAddr::V6(address),
+                _ => return None,
+            };
+
+            let name = Name::from(address);
+            if !reverse_origin.zone_of(&name) {
+                return None;
+            }
+
+            let mut ptr = Record::with(name, RecordType::PTR, record.ttl());
+            ptr.set_dns_class(record.dns_class())
+                .set_rdata(RData::PTR(record.name().clone()));
+            Some(ptr)
+        })
+        .collect()
+}
+
+/// Returns the update which changes the generated PTR records of a reverse zone from those
+///  before to those after, the deletes, RFC 2136 section 2.5.4, and then the additions, see
+///  `Authority::update_records`
+///
+/// A record of which only the TTL changed is deleted and added again.
+pub(crate) fn ptr_update(before: &[Record], after: &[Record]) -> Vec<Record> {
+    // records are equal regardless of their TTLs
+    let contains = |records: &[Record], record: &Record| {
+        records.iter().any(
+            |other| other == record && other.ttl() == record.ttl(),
+        )
+    };
+
+    let deletes = before
+        .iter()
+        .filter(|record| !contains(after, record))
+        .map(|record| {
+            let mut delete = record.clone();
+            delete.set_dns_class(DNSClass::NONE).set_ttl(0);
+            delete
+        });
+    let additions = after
+        .iter()
+        .filter(|record| !contains(before, record))
+        .cloned();
+
+    deletes.chain(additions).collect()
+}
@@ -0,0 +1,254 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! In-line signing of a zone as it is served, see `SigningAuthority`
+
+use std::ops::Deref;
+
+use chrono::{DateTime, Duration, Utc};
+
+use trust_dns::error::*;
+use trust_dns::op::{Message, ResponseCode};
+use trust_dns::rr::{RData, Record, RecordType};
+use trust_dns::rr::dnssec::DigestType;
+
+use authority::{Authority, UpdateResult, ZoneSigner};
+
+/// An `Authority` which is signed with the keys of a `ZoneSigner` as it is served
+///
+/// The zone is signed when it is wrapped, and again after each dynamic update, with the NSEC or
+///  NSEC3 chain of the `ZoneSigner` regenerated, and the serial incremented. The signatures are
+///  renewed once they are about to expire, see `is_resign_due` and `Catalog::resign_due`. Only
+///  the authoritative RRsets are signed, i.e. neither the NS records at a delegation nor the glue
+///  below it, and the DNSKEY RRset is signed by the key signing keys, if there are any.
+///
+/// The wrapped `Authority` should not have keys of its own, see `Authority::add_secure_key`, and
+///  it should be changed through this wrapper, which dereferences to it for everything else. The
+///  `Catalog` serves it as a `Zone`, e.g. `catalog.upsert(name, signing_authority)`.
+pub struct SigningAuthority {
+    authority: Authority,
+    signer: ZoneSigner,
+    resign_margin: Duration,
+}
+
+impl SigningAuthority {
+    /// Signs the zone, and returns it wrapped
+    ///
+    /// The signatures are renewed a quarter of the shortest `sig_duration` of the keys before they
+    ///  expire, see `set_resign_margin`.
+    ///
+    /// # Arguments
+    ///
+    /// * `authority` - the zone, any DNSSEC records it has are replaced, but for its DNSKEYs
+    /// * `signer` - the keys and the denial of existence the zone is signed with, for the same
+    ///              origin as the zone
+    pub fn new(authority: Authority, signer: ZoneSigner) -> DnsSecResult<Self> {
+        if signer.origin() != authority.origin() {
+            return Err(
+                DnsSecErrorKind::Msg(format!(
+                    "signer of {} for the zone {}",
+                    signer.origin(),
+                    authority.origin()
+                )).into(),
+            );
+        }
+
+        let resign_margin = signer
+            .signers()
+            .iter()
+            .chain(signer.key_signers())
+            .map(|signer| signer.sig_duration())
+            .min()
+            .unwrap_or_else(Duration::zero) / 4;
+
+        let mut signing_authority = SigningAuthority {
+            authority: authority,
+            signer: signer,
+            resign_margin: resign_margin,
+        };
+        try!(signing_authority.resign());
+
+        Ok(signing_authority)
+    }
+
+    /// The signed zone
+    pub fn authority(&self) -> &Authority {
+        &self.authority
+    }
+
+    /// Returns the signed zone, which is no longer signed as it changes
+    pub fn into_authority(self) -> Authority {
+        self.authority
+    }
+
+    /// The keys the zone is signed with
+    pub fn signer(&self) -> &ZoneSigner {
+        &self.signer
+    }
+
+    /// Sets how long before the first of its signatures expires the zone is signed again
+    pub fn set_resign_margin(&mut self, resign_margin: Duration) {
+        self.resign_margin = resign_margin;
+    }
+
+    /// How long before the first of its signatures expires the zone is signed again
+    pub fn resign_margin(&self) -> Duration {
+        self.resign_margin
+    }
+
+    /// The DS records of the zone, for its parent zone, see `ZoneSigner::ds_records`
+    pub fn ds_records(&self, digest_type: DigestType, ttl: u32) -> DnsSecResult<Vec<Record>> {
+        self.signer.ds_records(digest_type, ttl)
+    }
+
+    /// Authorizes and applies the dynamic update, and signs the changed zone, see
+    ///  `Authority::update`
+    pub fn update(&mut self, update: &Message) -> UpdateResult<bool> {
+        try!(self.authority.authorize(update));
+        try!(self.authority.verify_prerequisites(update.prerequisites()));
+        try!(self.authority.pre_scan(update.updates()));
+
+        self.update_records(update.updates())
+    }
+
+    /// Applies the update records, see `Authority::update_records`, and signs the changed zone
+    ///
+    /// The update is applied to a copy of the zone, which then replaces the records of the zone
+    ///  once it is signed, so that the update is one change to the zone, for the journal, the
+    ///  incremental zone transfers and the change hooks.
+    pub fn update_records(&mut self, records: &[Record]) -> UpdateResult<bool> {
+        let mut updated = Authority::new(
+            self.authority.origin().clone(),
+            self.authority.records().to_map(),
+            self.authority.zone_type(),
+            true,
+            false,
+        );
+        updated.set_ixfr_history_size(0);
+
+        if !try!(updated.update_records(records, false)) {
+            return Ok(false);
+        }
+
+        let records = unsigned_records(&updated);
+        try!(self.sign(records, Utc::now()).map_err(|e| {
+            error!("failure signing zone {}: {}", self.authority.origin(), e);
+            ResponseCode::ServFail
+        }));
+
+        Ok(true)
+    }
+
+    /// The first expiration of the signatures of the zone, in seconds since the epoch, None if
+    ///  there are no signatures
+    pub fn first_expiration(&self) -> Option<u32> {
+        self.authority
+            .records()
+            .iter()
+            .flat_map(|(_, rrset)| rrset.rrsigs().iter())
+            .filter_map(|rrsig| match *rrsig.rdata() {
+                RData::SIG(ref sig) => Some(sig.sig_expiration()),
+                _ => None,
+            })
+            .min()
+    }
+
+    /// Returns true if the first of the signatures of the zone expires within the resign margin
+    ///  of `now`, or if the zone has no signatures
+    pub fn is_resign_due(&self, now: DateTime<Utc>) -> bool {
+        match self.first_expiration() {
+            Some(expiration) => (now + self.resign_margin).timestamp() >= expiration as i64,
+            None => true,
+        }
+    }
+
+    /// Signs the zone again, with signatures from now on, returns the serial of the zone
+    pub fn resign(&mut self) -> DnsSecResult<u32> {
+        let records = unsigned_records(&self.authority);
+        self.sign(records, Utc::now())
+    }
+
+    /// The records of the zone signed again, with signatures from `inception` on, to replace those
+    ///  of the zone with `replace_signed`
+    ///
+    /// This only needs a shared reference, so that the zone is still served while it is signed,
+    ///  see `Catalog::resign_due`.
+    pub(crate) fn resigned_records(&self, inception: DateTime<Utc>) -> DnsSecResult<Vec<Record>> {
+        self.signed_records(unsigned_records(&self.authority), inception)
+    }
+
+    /// Replaces the records of the zone with those of `resigned_records`, returns the serial of
+    ///  the zone
+    pub(crate) fn replace_signed(&mut self, signed: Vec<Record>) -> u32 {
+        info!("signed zone: {}", self.authority.origin());
+        self.authority.replace_records(signed)
+    }
+
+    /// Signs the records of another version of the zone, e.g. its reloaded zone file, with the
+    ///  keys of this zone, returns the serial of the signed version
+    pub(crate) fn sign_authority(&self, authority: &mut Authority) -> DnsSecResult<u32> {
+        let signed = try!(self.signed_records(unsigned_records(authority), Utc::now()));
+        Ok(authority.replace_records(signed))
+    }
+
+    /// The records of the zone, changes through this are not signed
+    pub(crate) fn authority_mut(&mut self) -> &mut Authority {
+        &mut self.authority
+    }
+
+    /// Increments the serial of the records of the zone, signs them, and replaces the records of
+    ///  the zone with them, returns the serial of the zone
+    fn sign(&mut self, records: Vec<Record>, inception: DateTime<Utc>) -> DnsSecResult<u32> {
+        let signed = try!(self.signed_records(records, inception));
+        Ok(self.replace_signed(signed))
+    }
+
+    /// Increments the serial of the records of the zone, and signs them
+    fn signed_records(
+        &self,
+        records: Vec<Record>,
+        inception: DateTime<Utc>,
+    ) -> DnsSecResult<Vec<Record>> {
+        let origin = self.authority.origin();
+
+        let is_apex_ds =
+            |record: &Record| record.name() == origin && record.rr_type() == RecordType::DS;
+        let mut records = records;
+        if records.iter().any(&is_apex_ds) {
+            warn!("dropping DS at the apex of {}, it belongs in the parent zone", origin);
+            records.retain(|record| !is_apex_ds(record));
+        }
+
+        for record in &mut records {
+            if record.name() == origin {
+                if let RData::SOA(ref mut soa) = *record.rdata_mut() {
+                    soa.increment_serial();
+                }
+            }
+        }
+
+        self.signer.sign(&records, inception)
+    }
+}
+
+impl Deref for SigningAuthority {
+    type Target = Authority;
+
+    fn deref(&self) -> &Authority {
+        &self.authority
+    }
+}
+
+/// The records of the zone, without the signatures
+fn unsigned_records(authority: &Authority) -> Vec<Record> {
+    authority
+        .records()
+        .iter()
+        .flat_map(|(_, rrset)| rrset.iter())
+        .cloned()
+        .collect()
+}
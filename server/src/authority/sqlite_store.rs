@@ -0,0 +1,377 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A `ZoneStore` which persists the RRsets of a zone to SQLite, see `SqliteStore`
+
+use std::cmp;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use rusqlite;
+use rusqlite::Connection;
+
+use trust_dns::rr::{Name, Record, RecordSet, RecordType, RrKey};
+use trust_dns::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+
+use authority::ZoneStore;
+use error::{PersistenceError, PersistenceErrorKind, PersistenceResult};
+
+/// The number of entries the journal may always grow to before it is compacted
+const COMPACT_MIN_ENTRIES: usize = 1024;
+
+/// A store of the RRsets of a zone in memory, which journals each change of an RRset to SQLite
+///
+/// Every RRset written with `upsert` or removed with `delete` is appended to the journal as it is,
+///  the SOA included, so that replaying the journal, see `from_file`, brings back the zone exactly
+///  as it was, at the same serial. This differs from the `Journal`, which holds the updates to the
+///  zone, and which are replayed without incrementing the serial.
+///
+/// Once the journal holds more than twice as many entries as there are RRsets, and at least
+///  `COMPACT_MIN_ENTRIES`, it is compacted to the current RRsets, see `compact`.
+///
+/// A change which could not be written to the journal is not made in memory either, and the error
+///  is returned. The changes between `begin` and `commit` are journaled in one SQLite transaction,
+///  if any of them could not be written, none of them are kept, see `rollback`.
+pub struct SqliteStore {
+    records: BTreeMap<RrKey, RecordSet>,
+    conn: Mutex<Connection>,
+    entries: usize,
+    pending: Option<Pending>,
+}
+
+/// The changes since `begin`, to undo them on `rollback`
+struct Pending {
+    // the RRsets as they were before they were first changed, None for those which were added
+    undo: BTreeMap<RrKey, Option<RecordSet>>,
+    entries: usize,
+    // the first change which could not be written
+    error: Option<String>,
+}
+
+impl SqliteStore {
+    /// Opens the store in the SQLite connection, and replays its journal
+    pub fn new(conn: Connection) -> PersistenceResult<SqliteStore> {
+        try!(conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS rrsets (
+                name           BLOB NOT NULL,
+                record_type    INTEGER NOT NULL,
+                serial         INTEGER NOT NULL,
+                records        BLOB,
+                rrsigs         BLOB
+            );",
+        ));
+
+        let mut store = SqliteStore {
+            records: BTreeMap::new(),
+            conn: Mutex::new(conn),
+            entries: 0,
+            pending: None,
+        };
+        try!(store.replay());
+
+        // only the current RRsets are kept from one start to the next
+        if store.entries > store.records.len() {
+            try!(store.compact());
+        }
+
+        Ok(store)
+    }
+
+    /// Opens the store in the SQLite file at the path, which is created if it does not exist yet,
+    ///  and replays its journal
+    pub fn from_file(path: &Path) -> PersistenceResult<SqliteStore> {
+        Self::new(try!(Connection::open(path)))
+    }
+
+    /// Returns the number of entries in the journal
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Replaces the journal with one entry for each of the current RRsets, in one transaction
+    pub fn compact(&mut self) -> PersistenceResult<()> {
+        {
+            let mut conn = self.conn();
+            let tx = try!(conn.transaction());
+            try!(tx.execute("DELETE FROM rrsets", &[]));
+            for (key, rrset) in &self.records {
+                try!(insert_entry(&tx, key, Some(rrset)));
+            }
+            try!(tx.commit());
+        }
+
+        self.entries = self.records.len();
+        Ok(())
+    }
+
+    fn conn(&self) -> MutexGuard<Connection> {
+        self.conn.lock().unwrap() // poison errors should panic...
+    }
+
+    /// Reads back the RRsets from the journal, the last entry of each RRset wins, an entry without
+    ///  records is a delete
+    fn replay(&mut self) -> PersistenceResult<()> {
+        let entries = {
+            let conn = self.conn();
+            let mut stmt = try!(conn.prepare(
+                "SELECT name, record_type, serial, records, rrsigs FROM rrsets ORDER BY _rowid_",
+            ));
+            let rows = try!(stmt.query_and_then(&[], |row| -> Result<_, rusqlite::Error> {
+                let name: Vec<u8> = try!(row.get_checked(0));
+                let record_type: i64 = try!(row.get_checked(1));
+                let serial: i64 = try!(row.get_checked(2));
+                let records: Option<Vec<u8>> = try!(row.get_checked(3));
+                let rrsigs: Option<Vec<u8>> = try!(row.get_checked(4));
+                Ok((name, record_type, serial, records, rrsigs))
+            }));
+
+            let mut entries = Vec::new();
+            for row in rows {
+                entries.push(try!(row));
+            }
+            entries
+        };
+
+        self.entries = entries.len();
+        for (name, record_type, serial, records, rrsigs) in entries {
+            let name = try!(Name::read(&mut BinDecoder::new(&name)));
+            let record_type = try!(RecordType::from_u16(record_type as u16));
+            let key = RrKey::new(&name, record_type);
+
+            let records = match records {
+                Some(records) => records,
+                None => {
+                    self.records.remove(&key);
+                    continue;
+                }
+            };
+
+            let mut rrset = RecordSet::new(&name, record_type, serial as u32);
+            for record in try!(read_records(&records)) {
+                rrset.set_dns_class(record.dns_class());
+                rrset.insert(record, serial as u32);
+            }
+            for rrsig in try!(read_records(&rrsigs.unwrap_or_default())) {
+                rrset.insert_rrsig(rrsig);
+            }
+            self.records.insert(key, rrset);
+        }
+
+        info!("replayed {} journal entries to {} RRsets", self.entries, self.records.len());
+        Ok(())
+    }
+
+    /// Appends the current RRset of the key, or its delete if there is none, to the journal, and
+    ///  compacts the journal if it has grown too large
+    fn journal(&mut self, key: &RrKey) -> PersistenceResult<()> {
+        try!(insert_entry(&self.conn(), key, self.records.get(key)));
+        self.entries += 1;
+
+        // a transaction is compacted once it is committed
+        if self.pending.is_none() {
+            self.compact_if_large();
+        }
+        Ok(())
+    }
+
+    fn compact_if_large(&mut self) {
+        if self.entries > cmp::max(COMPACT_MIN_ENTRIES, self.records.len() * 2) {
+            debug!("compacting journal of {} entries", self.entries);
+            if let Err(error) = self.compact() {
+                error!("could not compact journal: {}", error);
+            }
+        }
+    }
+
+    /// Keeps the RRset of the key as it was before the change, for `rollback`
+    fn changed(&mut self, key: &RrKey, previous: &Option<RecordSet>) {
+        if let Some(ref mut pending) = self.pending {
+            pending.undo.entry(key.clone()).or_insert_with(
+                || previous.clone(),
+            );
+        }
+    }
+
+    /// Remembers the change which could not be written, so that the transaction is not committed
+    fn failed(&mut self, error: &PersistenceError) {
+        if let Some(ref mut pending) = self.pending {
+            if pending.error.is_none() {
+                pending.error = Some(error.to_string());
+            }
+        }
+    }
+}
+
+impl ZoneStore for SqliteStore {
+    fn get_rrset(&self, key: &RrKey) -> Option<&RecordSet> {
+        self.records.get(key)
+    }
+
+    fn upsert(&mut self, key: RrKey, rrset: RecordSet) -> PersistenceResult<Option<RecordSet>> {
+        let previous = self.records.insert(key.clone(), rrset);
+        if let Err(error) = self.journal(&key) {
+            self.failed(&error);
+            match previous {
+                Some(previous) => self.records.insert(key, previous),
+                None => self.records.remove(&key),
+            };
+            return Err(error);
+        }
+        self.changed(&key, &previous);
+        Ok(previous)
+    }
+
+    fn delete(&mut self, key: &RrKey) -> PersistenceResult<Option<RecordSet>> {
+        let removed = self.records.remove(key);
+        if removed.is_some() {
+            if let Err(error) = self.journal(key) {
+                self.failed(&error);
+                if let Some(rrset) = removed {
+                    self.records.insert(key.clone(), rrset);
+                }
+                return Err(error);
+            }
+            self.changed(key, &removed);
+        }
+        Ok(removed)
+    }
+
+    fn begin(&mut self) -> PersistenceResult<()> {
+        if self.pending.is_some() {
+            return Err(
+                PersistenceErrorKind::Msg("a transaction is already begun".to_string()).into(),
+            );
+        }
+
+        try!(self.conn().execute_batch("BEGIN"));
+        self.pending = Some(Pending {
+            undo: BTreeMap::new(),
+            entries: self.entries,
+            error: None,
+        });
+        Ok(())
+    }
+
+    fn commit(&mut self) -> PersistenceResult<()> {
+        let error = match self.pending {
+            Some(ref pending) => pending.error.clone(),
+            None => return Ok(()),
+        };
+        if let Some(error) = error {
+            self.rollback();
+            return Err(
+                PersistenceErrorKind::Msg(format!("a change could not be journaled: {}", error))
+                    .into(),
+            );
+        }
+
+        let committed = self.conn().execute_batch("COMMIT");
+        if let Err(error) = committed {
+            self.rollback();
+            return Err(error.into());
+        }
+
+        self.pending = None;
+        self.compact_if_large();
+        Ok(())
+    }
+
+    fn rollback(&mut self) {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return,
+        };
+
+        if let Err(error) = self.conn().execute_batch("ROLLBACK") {
+            error!("could not roll back journal: {}", error);
+        }
+        for (key, rrset) in pending.undo {
+            match rrset {
+                Some(rrset) => self.records.insert(key, rrset),
+                None => self.records.remove(&key),
+            };
+        }
+        self.entries = pending.entries;
+    }
+
+    fn iter<'a>(&'a self) -> Box<Iterator<Item = (&'a RrKey, &'a RecordSet)> + 'a> {
+        Box::new(self.records.iter())
+    }
+
+    fn iter_from<'a>(
+        &'a self,
+        key: &RrKey,
+    ) -> Box<Iterator<Item = (&'a RrKey, &'a RecordSet)> + 'a> {
+        Box::new(self.records.range(key.clone()..))
+    }
+
+    fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    fn clear(&mut self) {
+        self.records.clear();
+        if let Err(error) = self.compact() {
+            error!("could not clear journal: {}", error);
+        }
+    }
+
+    fn to_map(&self) -> BTreeMap<RrKey, RecordSet> {
+        self.records.clone()
+    }
+}
+
+/// Appends an entry for the RRset to the journal, None is a delete of the RRset
+fn insert_entry(
+    conn: &Connection,
+    key: &RrKey,
+    rrset: Option<&RecordSet>,
+) -> PersistenceResult<()> {
+    let mut name: Vec<u8> = Vec::with_capacity(64);
+    {
+        let mut encoder = BinEncoder::new(&mut name);
+        try!(key.name.emit(&mut encoder));
+    }
+
+    let record_type: i64 = u16::from(key.record_type) as i64;
+    let serial: i64 = rrset.map_or(0, |rrset| rrset.serial()) as i64;
+    let (records, rrsigs) = match rrset {
+        Some(rrset) => (
+            Some(try!(emit_records(rrset.iter()))),
+            Some(try!(emit_records(rrset.rrsigs().iter()))),
+        ),
+        None => (None, None),
+    };
+
+    try!(conn.execute(
+        "INSERT INTO rrsets (name, record_type, serial, records, rrsigs)
+            VALUES ($1, $2, $3, $4, $5)",
+        &[&name, &record_type, &serial, &records, &rrsigs],
+    ));
+    Ok(())
+}
+
+fn emit_records<'r, I: Iterator<Item = &'r Record>>(records: I) -> PersistenceResult<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(512);
+    {
+        let mut encoder = BinEncoder::new(&mut bytes);
+        for record in records {
+            try!(record.emit(&mut encoder));
+        }
+    }
+    Ok(bytes)
+}
+
+fn read_records(bytes: &[u8]) -> PersistenceResult<Vec<Record>> {
+    let mut decoder = BinDecoder::new(bytes);
+    let mut records = Vec::new();
+    while decoder.len() > 0 {
+        records.push(try!(Record::read(&mut decoder)));
+    }
+    Ok(records)
+}
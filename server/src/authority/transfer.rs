@@ -0,0 +1,277 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Validation of zones received by transfer, before they are served
+
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+
+use trust_dns::rr::{Name, RData, Record, RecordSet, RecordType, RrKey};
+use trust_dns::rr::dnssec::{TrustAnchor, Verifier};
+use trust_dns::rr::rdata::{DNSKEY, SIG};
+
+use authority::{Authority, ZoneType};
+use error::{TransferErrorKind, TransferResult};
+
+/// Checks which are performed on a transferred zone before it is loaded.
+///
+/// The structure of the transfer is always checked, i.e. that there is an SOA for the zone and
+///  that all records are in the zone. By default nothing else is validated.
+pub struct TransferValidation {
+    verify_zonemd: bool,
+    verify_dnssec: bool,
+    trust_anchor: Option<TrustAnchor>,
+}
+
+impl TransferValidation {
+    /// Returns validation which only checks the structure of the transfer
+    pub fn new() -> Self {
+        TransferValidation {
+            verify_zonemd: false,
+            verify_dnssec: false,
+            trust_anchor: None,
+        }
+    }
+
+    /// If true, the zone must contain a ZONEMD for the current serial which matches the digest of
+    ///  the transferred records, [RFC 8976](https://tools.ietf.org/html/rfc8976)
+    pub fn set_verify_zonemd(&mut self, verify_zonemd: bool) {
+        self.verify_zonemd = verify_zonemd;
+    }
+
+    /// If true, the apex DNSKEY, SOA and (if present) ZONEMD record sets must have valid RRSIGs
+    pub fn set_verify_dnssec(&mut self, verify_dnssec: bool) {
+        self.verify_dnssec = verify_dnssec;
+    }
+
+    /// The DNSKEY record set must be signed by one of these keys, otherwise any key in the record
+    ///  set is accepted, i.e. the zone is only checked for being self consistent.
+    pub fn set_trust_anchor(&mut self, trust_anchor: TrustAnchor) {
+        self.trust_anchor = Some(trust_anchor);
+    }
+
+    /// Validates the transferred records for the zone
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone which was transferred
+    /// * `records` - all the records received in the transfer, the trailing SOA of an AXFR is
+    ///               allowed
+    pub fn validate(&self, origin: &Name, records: &[Record]) -> TransferResult<()> {
+        let soa_serial = try!(Self::soa_serial(origin, records));
+
+        if let Some(record) = records.iter().find(|r| !origin.zone_of(r.name())) {
+            return Err(
+                TransferErrorKind::OutOfZone(record.name().clone(), origin.clone()).into(),
+            );
+        }
+
+        if self.verify_zonemd {
+            try!(Self::verify_zonemd(origin, soa_serial, records));
+        }
+
+        if self.verify_dnssec {
+            try!(self.verify_apex_signatures(origin, records));
+        }
+
+        Ok(())
+    }
+
+    fn soa_serial(origin: &Name, records: &[Record]) -> TransferResult<u32> {
+        records
+            .iter()
+            .filter(|r| r.name() == origin)
+            .filter_map(|r| if let RData::SOA(ref soa) = *r.rdata() {
+                Some(soa.serial())
+            } else {
+                None
+            })
+            .next()
+            .ok_or_else(|| TransferErrorKind::MissingSoa(origin.clone()).into())
+    }
+
+    fn verify_zonemd(origin: &Name, soa_serial: u32, records: &[Record]) -> TransferResult<()> {
+        // only ZONEMDs for this serial, with a supported scheme and hash, are considered
+        let zonemds = records
+            .iter()
+            .filter(|r| r.name() == origin)
+            .filter_map(|r| if let RData::ZONEMD(ref zonemd) = *r.rdata() {
+                Some(zonemd)
+            } else {
+                None
+            })
+            .filter(|zonemd| zonemd.serial() == soa_serial && zonemd.digest_type().is_some())
+            .collect::<Vec<_>>();
+
+        if zonemds.is_empty() {
+            return Err(TransferErrorKind::MissingZonemd(origin.clone()).into());
+        }
+
+        // any single matching digest is sufficient
+        for zonemd in zonemds {
+            if try!(zonemd.covers(origin, records)) {
+                debug!("ZONEMD verified for: {} serial: {}", origin, soa_serial);
+                return Ok(());
+            }
+        }
+
+        warn!("ZONEMD mismatch for: {} serial: {}", origin, soa_serial);
+        Err(
+            TransferErrorKind::ZonemdMismatch(origin.clone(), soa_serial).into(),
+        )
+    }
+
+    fn verify_apex_signatures(&self, origin: &Name, records: &[Record]) -> TransferResult<()> {
+        let dnskeys = records
+            .iter()
+            .filter(|r| r.name() == origin)
+            .filter_map(|r| if let RData::DNSKEY(ref dnskey) = *r.rdata() {
+                Some(dnskey)
+            } else {
+                None
+            })
+            .filter(|dnskey| dnskey.zone_key() && !dnskey.revoke())
+            .collect::<Vec<_>>();
+
+        // the DNSKEY set is the root of trust for the rest of the zone
+        let trusted = dnskeys
+            .iter()
+            .cloned()
+            .filter(|dnskey| match self.trust_anchor {
+                Some(ref anchor) => anchor.contains_dnskey_bytes(dnskey.public_key()),
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        try!(Self::verify_rrset(origin, RecordType::DNSKEY, &trusted, records));
+        try!(Self::verify_rrset(origin, RecordType::SOA, &dnskeys, records));
+
+        let has_zonemd = records.iter().any(|r| {
+            r.name() == origin && r.rr_type() == RecordType::ZONEMD
+        });
+        if has_zonemd {
+            try!(Self::verify_rrset(origin, RecordType::ZONEMD, &dnskeys, records));
+        }
+
+        Ok(())
+    }
+
+    /// Requires at least one current RRSIG over the record set from one of the keys
+    fn verify_rrset(
+        origin: &Name,
+        record_type: RecordType,
+        dnskeys: &[&DNSKEY],
+        records: &[Record],
+    ) -> TransferResult<()> {
+        let mut rrset = records
+            .iter()
+            .filter(|r| r.name() == origin && r.rr_type() == record_type)
+            .cloned()
+            .collect::<Vec<_>>();
+        // an AXFR repeats the SOA at the end
+        rrset.sort();
+        rrset.dedup();
+        let now = Utc::now().timestamp() as u32;
+
+        let verified = records
+            .iter()
+            .filter(|r| r.name() == origin && r.rr_type() == RecordType::RRSIG)
+            .filter_map(|r| if let RData::SIG(ref sig) = *r.rdata() {
+                Some((r, sig))
+            } else {
+                None
+            })
+            .filter(|&(_, sig)| {
+                sig.type_covered() == record_type && sig.sig_inception() <= now &&
+                    now <= sig.sig_expiration()
+            })
+            .any(|(rrsig, sig)| {
+                dnskeys.iter().any(|dnskey| {
+                    Self::verify_with_dnskey(dnskey, rrsig, sig, &rrset)
+                })
+            });
+
+        if verified {
+            Ok(())
+        } else {
+            warn!("no valid RRSIG for: {} {}", origin, record_type);
+            Err(
+                TransferErrorKind::MissingSignature(origin.clone(), record_type).into(),
+            )
+        }
+    }
+
+    fn verify_with_dnskey(dnskey: &DNSKEY, rrsig: &Record, sig: &SIG, rrset: &[Record]) -> bool {
+        if dnskey.algorithm() != sig.algorithm() {
+            return false;
+        }
+
+        dnskey
+            .verify_rrsig(rrsig.name(), rrsig.dns_class(), sig, rrset)
+            .map_err(|e| debug!("RRSIG failed to verify: {}", e))
+            .is_ok()
+    }
+}
+
+impl Default for TransferValidation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Validates the transferred records, and then constructs the Authority for them
+///
+/// Nothing is loaded if validation fails, the existing zone should continue to be served.
+///
+/// # Arguments
+///
+/// * `origin` - the zone which was transferred
+/// * `records` - all the records received in the transfer
+/// * `zone_type` - type of the new zone, generally `ZoneType::Slave`
+/// * `validation` - the checks to perform before the zone is loaded
+pub fn load_transfer(
+    origin: Name,
+    records: Vec<Record>,
+    zone_type: ZoneType,
+    validation: &TransferValidation,
+) -> TransferResult<Authority> {
+    try!(validation.validate(&origin, &records));
+    let serial = try!(TransferValidation::soa_serial(&origin, &records));
+
+    let mut rrsets: BTreeMap<RrKey, RecordSet> = BTreeMap::new();
+    let (rrsigs, records): (Vec<Record>, Vec<Record>) = records.into_iter().partition(|r| {
+        r.rr_type() == RecordType::RRSIG
+    });
+    let is_signed = !rrsigs.is_empty();
+
+    for record in records {
+        let rr_key = RrKey::new(record.name(), record.rr_type());
+        let rrset: &mut RecordSet = rrsets.entry(rr_key).or_insert(RecordSet::new(
+            record.name(),
+            record.rr_type(),
+            serial,
+        ));
+        rrset.insert(record, serial);
+    }
+
+    // the signatures are served along side the record sets they cover
+    for rrsig in rrsigs {
+        let type_covered = if let RData::SIG(ref sig) = *rrsig.rdata() {
+            sig.type_covered()
+        } else {
+            continue;
+        };
+
+        if let Some(rrset) = rrsets.get_mut(&RrKey::new(rrsig.name(), type_covered)) {
+            rrset.insert_rrsig(rrsig);
+        }
+    }
+
+    info!("loaded transfer of: {} serial: {}", origin, serial);
+    Ok(Authority::new(origin, rrsets, zone_type, false, is_signed))
+}
@@ -0,0 +1,269 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Forwarding of updates from a secondary toward the primary, RFC 2136 section 6
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{self, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use futures::{future, Future, Stream};
+use futures::future::Either;
+use futures::sync::oneshot;
+use tokio_core::reactor::{Handle, Timeout};
+use trust_dns::op::Message;
+use trust_dns::rr::Name;
+use trust_dns::tcp::TcpStream;
+
+/// Port on which the primary named by the SOA MNAME is contacted
+pub(crate) const DNS_PORT: u16 = 53;
+
+/// Seconds allowed for the connection to the primary, and again for its response to arrive
+const FORWARD_TIMEOUT_SECS: u64 = 5;
+
+/// The most updates which are forwarded at once, further updates are answered with ServFail
+pub(crate) const MAX_FORWARDS: usize = 32;
+
+/// The updates being forwarded to the primaries of the secondary zones, and the primaries which
+///  were resolved from the MNAMEs of the zones
+///
+/// The MNAME of a zone without a configured primary is resolved when the zone is refreshed, see
+///  `resolve_primary`, so that an update is only forwarded after resolving it itself until the
+///  zone was first refreshed. At most `MAX_FORWARDS` updates are forwarded at once, including
+///  their resolution, which bounds both the connections to the primaries and the threads which
+///  resolve the MNAMEs.
+#[derive(Clone, Default)]
+pub(crate) struct UpdateForwarder {
+    forwards: Arc<AtomicUsize>,
+    // the MNAME of each zone, and the address it resolved to
+    primaries: Arc<Mutex<HashMap<Name, (Name, SocketAddr)>>>,
+}
+
+impl UpdateForwarder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserves one of the `MAX_FORWARDS` forwards, None if all of them are in use
+    pub(crate) fn reserve(&self) -> Option<Forward> {
+        if self.forwards.fetch_add(1, Ordering::SeqCst) >= MAX_FORWARDS {
+            self.forwards.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+
+        Some(Forward { forwards: self.forwards.clone() })
+    }
+
+    /// Resolves the MNAME of the zone, blocking on the system resolver, and keeps the address for
+    ///  the updates forwarded to the zone's primary, see `resolved_primary`
+    pub(crate) fn resolve_primary(&self, origin: &Name, mname: &Name) -> Option<SocketAddr> {
+        let primary = resolve_mname(mname);
+        match self.primaries.lock() {
+            Ok(mut primaries) => {
+                match primary {
+                    Some(primary) => primaries.insert(origin.clone(), (mname.clone(), primary)),
+                    None => primaries.remove(origin),
+                };
+            }
+            Err(_) => warn!("resolved primaries poisoned, ignoring"),
+        }
+        primary
+    }
+
+    /// The address which the MNAME of the zone was last resolved to, None if it was not, or the
+    ///  MNAME changed since
+    pub(crate) fn resolved_primary(&self, origin: &Name, mname: &Name) -> Option<SocketAddr> {
+        let primaries = match self.primaries.lock() {
+            Ok(primaries) => primaries,
+            Err(_) => return None,
+        };
+
+        match primaries.get(origin) {
+            Some(&(ref resolved, primary)) if resolved == mname => Some(primary),
+            _ => None,
+        }
+    }
+
+    /// The resolved primary of the zone, or else resolves the MNAME on a thread of its own, so
+    ///  that the system resolver does not block the IO loop which waits on the future
+    pub(crate) fn resolve_primary_future(
+        &self,
+        origin: Name,
+        mname: Name,
+    ) -> Box<Future<Item = Option<SocketAddr>, Error = io::Error>> {
+        if let Some(primary) = self.resolved_primary(&origin, &mname) {
+            return Box::new(future::ok(Some(primary)));
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let forwarder = self.clone();
+        let spawned = thread::Builder::new()
+            .name("trust-dns-resolve-primary".to_string())
+            .spawn(move || {
+                // the receiver is gone if the update was abandoned
+                let _ = sender.send(forwarder.resolve_primary(&origin, &mname));
+            });
+        if let Err(e) = spawned {
+            return Box::new(future::err(e));
+        }
+
+        Box::new(receiver.map_err(|_| {
+            io::Error::new(io::ErrorKind::Other, "resolution of the primary was abandoned")
+        }))
+    }
+}
+
+/// One of the `MAX_FORWARDS` updates being forwarded, it is released once dropped
+pub(crate) struct Forward {
+    forwards: Arc<AtomicUsize>,
+}
+
+impl Drop for Forward {
+    fn drop(&mut self) {
+        self.forwards.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Resolves the MNAME of the zone's SOA, the primary master, to an address
+///
+/// This blocks on the system resolver, see `UpdateForwarder::resolve_primary_future` for the IO
+///  loop.
+fn resolve_mname(mname: &Name) -> Option<SocketAddr> {
+    let host = mname.to_string();
+    match (host.trim_right_matches('.'), DNS_PORT).to_socket_addrs() {
+        Ok(mut addrs) => addrs.next(),
+        Err(e) => {
+            warn!("could not resolve primary {}: {}", mname, e);
+            None
+        }
+    }
+}
+
+/// Sends the update to the primary, the future of the primary's response
+///
+/// The update is always sent over TCP. As the connection is only used for this update, the ID
+///  can not collide with that of another forwarded update, so it is left as is, which keeps a
+///  SIG(0) signature of the requestor valid at the primary. Neither the connection nor the wait
+///  for the response blocks the IO loop of the handle, the connection times out after
+///  `FORWARD_TIMEOUT_SECS`, and the response after twice that.
+pub(crate) fn forward_update(
+    update: &Message,
+    primary: SocketAddr,
+    handle: &Handle,
+) -> Box<Future<Item = Message, Error = io::Error>> {
+    let buffer = match encode(update) {
+        Ok(buffer) => buffer,
+        Err(e) => return Box::new(future::err(e)),
+    };
+
+    let timeout = Duration::from_secs(FORWARD_TIMEOUT_SECS);
+    let (connect, stream_handle) = TcpStream::with_timeout(primary, handle, timeout);
+    // the update waits in the handle until the connection is made, TCP messages are prefixed
+    //  with their length by the stream, RFC 1035 section 4.2.2
+    if stream_handle.unbounded_send((buffer, primary)).is_err() {
+        return Box::new(future::err(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "could not queue the update",
+        )));
+    }
+
+    let id = update.id();
+    let response = connect
+        .and_then(|stream| stream.into_future().map_err(|(e, _)| e))
+        .and_then(move |(response, _)| -> io::Result<Message> {
+            let buffer = match response {
+                Some((buffer, _)) => buffer,
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "the primary closed the connection without a response",
+                    ))
+                }
+            };
+
+            decode(&buffer, id)
+        });
+
+    // the connection has a timeout of its own, this is for the response
+    let response_timeout = match Timeout::new(timeout * 2, handle) {
+        Ok(response_timeout) => response_timeout,
+        Err(e) => return Box::new(future::err(e)),
+    };
+    Box::new(response_timeout.select2(response).then(
+        move |selected| match selected {
+            Ok(Either::A(_)) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("timed out waiting for the primary: {}", primary),
+            )),
+            Ok(Either::B((response, _))) => Ok(response),
+            Err(Either::A((e, _))) |
+            Err(Either::B((e, _))) => Err(e),
+        },
+    ))
+}
+
+/// Sends the update to the primary, and blocks until the primary has responded, see
+///  `forward_update` for the IO loop
+///
+/// The connection, and the write of the update, time out after `FORWARD_TIMEOUT_SECS`, and the
+///  response after twice that.
+pub(crate) fn forward_update_blocking(
+    update: &Message,
+    primary: SocketAddr,
+) -> io::Result<Message> {
+    let buffer = try!(encode(update));
+
+    let timeout = Duration::from_secs(FORWARD_TIMEOUT_SECS);
+    let mut stream = try!(net::TcpStream::connect_timeout(&primary, timeout));
+    try!(stream.set_write_timeout(Some(timeout)));
+    try!(stream.set_read_timeout(Some(timeout * 2)));
+
+    // TCP messages are prefixed with their length, RFC 1035 section 4.2.2
+    try!(stream.write_all(&[(buffer.len() >> 8) as u8, buffer.len() as u8]));
+    try!(stream.write_all(&buffer));
+
+    let mut len = [0u8; 2];
+    try!(stream.read_exact(&mut len));
+    let mut buffer = vec![0u8; ((len[0] as usize) << 8) | len[1] as usize];
+    try!(stream.read_exact(&mut buffer));
+
+    decode(&buffer, update.id())
+}
+
+/// The update as it is sent over TCP, without the length prefix
+fn encode(update: &Message) -> io::Result<Vec<u8>> {
+    let buffer = try!(update.to_vec().map_err(to_io_error));
+    if buffer.len() > u16::max_value() as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "update too large to forward",
+        ));
+    }
+
+    Ok(buffer)
+}
+
+/// The primary's response to the update with the id
+fn decode(buffer: &[u8], id: u16) -> io::Result<Message> {
+    let response = try!(Message::from_vec(buffer).map_err(to_io_error));
+    if response.id() != id {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("response id {} does not match the update {}", response.id(), id),
+        ));
+    }
+
+    Ok(response)
+}
+
+pub(crate) fn to_io_error<E: ::std::fmt::Display>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, format!("{}", error))
+}
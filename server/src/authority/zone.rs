@@ -0,0 +1,116 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A zone served by the `Catalog`, see `Zone`
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use trust_dns::op::Message;
+use trust_dns::rr::Record;
+
+use authority::{Authority, ChangeHook, UpdateResult};
+#[cfg(feature = "dnssec")]
+use authority::SigningAuthority;
+
+/// A zone served by the `Catalog`, either as it is, or signed as it changes
+///
+/// Both dereference to the `Authority` of the zone, which answers the queries. The changes to the
+///  zone go through the `Zone`, so that those of a signed zone are signed.
+pub enum Zone {
+    /// A zone which is served as it is, or which was signed with `Authority::secure_zone`
+    Authority(Authority),
+    /// A zone which is signed again as it changes, see `SigningAuthority`
+    #[cfg(feature = "dnssec")]
+    Signing(SigningAuthority),
+}
+
+impl Zone {
+    /// Authorizes and applies the dynamic update, see `Authority::update` and
+    ///  `SigningAuthority::update`
+    pub fn update(&mut self, update: &Message) -> UpdateResult<bool> {
+        match *self {
+            Zone::Authority(ref mut authority) => authority.update(update),
+            #[cfg(feature = "dnssec")]
+            Zone::Signing(ref mut signing) => signing.update(update),
+        }
+    }
+
+    /// Applies the update records, see `Authority::update_records`, a signed zone is always
+    ///  signed again and its serial incremented
+    pub fn update_records(
+        &mut self,
+        records: &[Record],
+        auto_signing_and_increment: bool,
+    ) -> UpdateResult<bool> {
+        match *self {
+            Zone::Authority(ref mut authority) => {
+                authority.update_records(records, auto_signing_and_increment)
+            }
+            #[cfg(feature = "dnssec")]
+            Zone::Signing(ref mut signing) => signing.update_records(records),
+        }
+    }
+
+    /// Adds a hook which is called with the changes to the records of the zone, see
+    ///  `Authority::add_change_hook`
+    pub fn add_change_hook(&mut self, hook: Arc<ChangeHook>) {
+        self.authority_mut().add_change_hook(hook)
+    }
+
+    /// The signing zone, if the zone is signed as it changes
+    #[cfg(feature = "dnssec")]
+    pub fn signing_authority(&self) -> Option<&SigningAuthority> {
+        match *self {
+            Zone::Authority(..) => None,
+            Zone::Signing(ref signing) => Some(signing),
+        }
+    }
+
+    /// The signing zone, if the zone is signed as it changes
+    #[cfg(feature = "dnssec")]
+    pub(crate) fn signing_authority_mut(&mut self) -> Option<&mut SigningAuthority> {
+        match *self {
+            Zone::Authority(..) => None,
+            Zone::Signing(ref mut signing) => Some(signing),
+        }
+    }
+
+    /// The records of the zone, changes through this are not signed
+    pub(crate) fn authority_mut(&mut self) -> &mut Authority {
+        match *self {
+            Zone::Authority(ref mut authority) => authority,
+            #[cfg(feature = "dnssec")]
+            Zone::Signing(ref mut signing) => signing.authority_mut(),
+        }
+    }
+}
+
+impl Deref for Zone {
+    type Target = Authority;
+
+    fn deref(&self) -> &Authority {
+        match *self {
+            Zone::Authority(ref authority) => authority,
+            #[cfg(feature = "dnssec")]
+            Zone::Signing(ref signing) => signing.authority(),
+        }
+    }
+}
+
+impl From<Authority> for Zone {
+    fn from(authority: Authority) -> Self {
+        Zone::Authority(authority)
+    }
+}
+
+#[cfg(feature = "dnssec")]
+impl From<SigningAuthority> for Zone {
+    fn from(signing: SigningAuthority) -> Self {
+        Zone::Signing(signing)
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Index of the zones of the `Catalog` by their origin, for finding the zone of a name
+
+use std::collections::HashMap;
+
+use trust_dns::rr::Name;
+
+/// The zones of a catalog in a tree of their labels, from the root down
+///
+/// Finding the closest enclosing zone of a name walks the labels of the name once, so the cost
+///  of a query does not grow with the number of zones being served.
+pub(crate) struct ZoneIndex<T> {
+    root: Node<T>,
+}
+
+struct Node<T> {
+    zone: Option<(Name, T)>,
+    children: HashMap<String, Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Node {
+            zone: None,
+            children: HashMap::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.zone.is_none() && self.children.is_empty()
+    }
+
+    /// Removes the zone at the labels below this node, and the nodes which it leaves empty
+    fn remove(&mut self, labels: &[String]) -> Option<(Name, T)> {
+        let (label, rest) = match labels.split_first() {
+            Some(split) => split,
+            None => return self.zone.take(),
+        };
+
+        let (removed, is_empty) = match self.children.get_mut(label) {
+            Some(child) => {
+                let removed = child.remove(rest);
+                (removed, child.is_empty())
+            }
+            None => return None,
+        };
+
+        if is_empty {
+            self.children.remove(label);
+        }
+        removed
+    }
+}
+
+impl<T> ZoneIndex<T> {
+    pub(crate) fn new() -> Self {
+        ZoneIndex { root: Node::new() }
+    }
+
+    /// Inserts the zone, returning the one it replaced
+    pub(crate) fn insert(&mut self, name: Name, zone: T) -> Option<T> {
+        let mut node = &mut self.root;
+        for label in name.iter().rev() {
+            // move the reference along, otherwise the node would stay borrowed by the entry
+            let current = node;
+            node = current
+                .children
+                .entry(label.to_lowercase())
+                .or_insert_with(Node::new);
+        }
+
+        let replaced = node.zone.take().map(|(_, zone)| zone);
+        node.zone = Some((name, zone));
+        replaced
+    }
+
+    /// Removes the zone with exactly this origin, returning it
+    pub(crate) fn remove(&mut self, name: &Name) -> Option<T> {
+        let labels = name.iter()
+            .rev()
+            .map(|label| label.to_lowercase())
+            .collect::<Vec<_>>();
+
+        self.root.remove(&labels).map(|(_, zone)| zone)
+    }
+
+    /// Returns the zone with exactly this origin
+    pub(crate) fn get(&self, name: &Name) -> Option<&T> {
+        self.node(name)
+            .and_then(|node| node.zone.as_ref())
+            .map(|&(_, ref zone)| zone)
+    }
+
+    /// Returns true if there is a zone with exactly this origin
+    pub(crate) fn contains_key(&self, name: &Name) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Returns the closest enclosing zone of the name, i.e. that of the longest origin which is
+    ///  a suffix of the name, along with its origin
+    pub(crate) fn find(&self, name: &Name) -> Option<(&Name, &T)> {
+        let mut node = &self.root;
+        let mut found = node.zone.as_ref();
+
+        for label in name.iter().rev() {
+            match node.children.get(&label.to_lowercase()) {
+                Some(child) => node = child,
+                None => break,
+            }
+
+            if node.zone.is_some() {
+                found = node.zone.as_ref();
+            }
+        }
+
+        found.map(|&(ref name, ref zone)| (name, zone))
+    }
+
+    /// Returns an iterator over the origins and zones, in no particular order
+    pub(crate) fn iter(&self) -> Iter<T> {
+        Iter { stack: vec![&self.root] }
+    }
+
+    fn node(&self, name: &Name) -> Option<&Node<T>> {
+        let mut node = &self.root;
+        for label in name.iter().rev() {
+            node = match node.children.get(&label.to_lowercase()) {
+                Some(child) => child,
+                None => return None,
+            };
+        }
+
+        Some(node)
+    }
+}
+
+/// An iterator over the zones of a `ZoneIndex`
+pub(crate) struct Iter<'a, T: 'a> {
+    stack: Vec<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (&'a Name, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            self.stack.extend(node.children.values());
+            if let Some((ref name, ref zone)) = node.zone {
+                return Some((name, zone));
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,652 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Offline signing of zones, and verification of signed zones
+//!
+//! Unlike the `Authority`, which signs the zone it serves, this signs the records of a zone file
+//!  once, with NSEC or NSEC3 records for the denial of existence, see the `dnssec-zone` binary.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Utc};
+use data_encoding::base32hex;
+
+use trust_dns::error::*;
+use trust_dns::rr::{DNSClass, Name, RData, Record, RecordSet, RecordType, RrKey};
+use trust_dns::rr::dnssec::{tbs, DigestType, Nsec3HashAlgorithm, Signer, Verifier};
+use trust_dns::rr::rdata::{DNSKEY, NSEC, NSEC3, NSEC3PARAM, SIG};
+
+use authority::{Denial, MAX_NSEC3_ITERATIONS};
+
+/// Signs all the records of a zone with a set of keys
+///
+/// The zone signing keys sign every RRset, the key signing keys, if there are any, sign the DNSKEY
+///  RRset in their place, and are the keys of the DS records for the parent zone.
+pub struct ZoneSigner {
+    origin: Name,
+    signers: Vec<Signer>,
+    key_signers: Vec<Signer>,
+    denial: Denial,
+}
+
+impl ZoneSigner {
+    /// Creates a new signer for the zone
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone which is signed
+    /// * `signers` - the keys to sign with, each signs every RRset and is published as a DNSKEY
+    /// * `denial` - how the signed zone proves that names and types do not exist
+    pub fn new(origin: Name, signers: Vec<Signer>, denial: Denial) -> Self {
+        Self::with_key_signers(origin, signers, vec![], denial)
+    }
+
+    /// Creates a new signer for the zone, with separate zone and key signing keys
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone which is signed
+    /// * `signers` - the zone signing keys, each signs every RRset but the DNSKEY RRset, unless
+    ///               there are no key signing keys, and is published as a DNSKEY
+    /// * `key_signers` - the key signing keys, each signs the DNSKEY RRset and is published as a
+    ///                   DNSKEY
+    /// * `denial` - how the signed zone proves that names and types do not exist
+    pub fn with_key_signers(
+        origin: Name,
+        signers: Vec<Signer>,
+        key_signers: Vec<Signer>,
+        denial: Denial,
+    ) -> Self {
+        ZoneSigner {
+            origin: origin,
+            signers: signers,
+            key_signers: key_signers,
+            denial: denial,
+        }
+    }
+
+    /// The zone which is signed
+    pub fn origin(&self) -> &Name {
+        &self.origin
+    }
+
+    /// The keys the zone is signed with
+    pub fn signers(&self) -> &[Signer] {
+        &self.signers
+    }
+
+    /// The keys the DNSKEY RRset is signed with, none if it is signed with the zone signing keys
+    pub fn key_signers(&self) -> &[Signer] {
+        &self.key_signers
+    }
+
+    /// How the signed zone proves that names and types do not exist
+    pub fn denial(&self) -> &Denial {
+        &self.denial
+    }
+
+    /// Signs the records of the zone
+    ///
+    /// Any RRSIG, NSEC, NSEC3 and NSEC3PARAM records are replaced, so a signed zone may be signed
+    ///  again, e.g. to renew the signatures. The DNSKEYs of the signers are added to any already
+    ///  in the zone.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - all the records of the zone, including its SOA
+    /// * `inception` - the time from which the signatures are valid, they expire after the
+    ///                 `sig_duration` of their signer
+    ///
+    /// # Return
+    ///
+    /// The records of the signed zone in canonical order, each RRset followed by its RRSIGs
+    pub fn sign(&self, records: &[Record], inception: DateTime<Utc>) -> DnsSecResult<Vec<Record>> {
+        let mut rrsets = try!(collect_rrsets(&self.origin, records));
+        let (dns_class, ttl) = try!(soa_class_and_ttl(&self.origin, &rrsets));
+
+        for signer in self.signers.iter().chain(&self.key_signers) {
+            let dnskey = try!(signer.key().to_dnskey(signer.algorithm()));
+            let mut dnskey = Record::from_rdata(
+                self.origin.clone(),
+                ttl,
+                RecordType::DNSKEY,
+                RData::DNSKEY(dnskey),
+            );
+            dnskey.set_dns_class(dns_class);
+            insert(&mut rrsets, dnskey);
+        }
+
+        let delegations = delegations(&self.origin, &rrsets);
+        for mut record in try!(denial_records(&self.origin, &rrsets, &self.denial, ttl)) {
+            record.set_dns_class(dns_class);
+            insert(&mut rrsets, record);
+        }
+
+        let mut signed = Vec::with_capacity(rrsets.len() * (1 + self.signers.len()));
+        for (key, rrset) in &rrsets {
+            signed.extend(rrset.iter().cloned());
+            if !is_signed(&delegations, &key.name, key.record_type) {
+                continue;
+            }
+
+            for signer in self.signers_of(key) {
+                signed.push(try!(sign_rrset(rrset, dns_class, signer, inception)));
+            }
+        }
+
+        Ok(signed)
+    }
+
+    /// Returns the DS records for the parent zone, one for the DNSKEY of each key signing key, or
+    ///  of each signer if there are none
+    ///
+    /// # Arguments
+    ///
+    /// * `digest_type` - the digest of the DNSKEYs in the DS records
+    /// * `ttl` - the TTL of the DS records
+    pub fn ds_records(&self, digest_type: DigestType, ttl: u32) -> DnsSecResult<Vec<Record>> {
+        let signers = if self.key_signers.is_empty() {
+            &self.signers
+        } else {
+            &self.key_signers
+        };
+
+        signers
+            .iter()
+            .map(|signer| {
+                let ds = try!(signer.key().to_ds(&self.origin, signer.algorithm(), digest_type));
+                Ok(Record::from_rdata(
+                    self.origin.clone(),
+                    ttl,
+                    RecordType::DS,
+                    RData::DS(ds),
+                ))
+            })
+            .collect()
+    }
+
+    /// The keys which sign the RRset
+    fn signers_of(&self, key: &RrKey) -> &[Signer] {
+        if key.record_type == RecordType::DNSKEY && key.name == self.origin &&
+            !self.key_signers.is_empty()
+        {
+            &self.key_signers
+        } else {
+            &self.signers
+        }
+    }
+}
+
+/// Checks a signed zone, returns the problems found, none if the zone is valid
+///
+/// Every authoritative RRset must have a signature which is valid at `now` and verifies with one
+///  of the DNSKEYs at the apex of the zone. The NSEC or NSEC3 records must be those which signing
+///  the zone would produce, i.e. the chain must cover all the names and types of the zone.
+///
+/// # Arguments
+///
+/// * `origin` - the zone which is checked
+/// * `records` - all the records of the signed zone
+/// * `now` - the time at which the signatures must be valid, in seconds since the epoch
+pub fn verify_zone(origin: &Name, records: &[Record], now: u32) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let rrsets = match collect_rrsets(origin, records) {
+        Ok(rrsets) => rrsets,
+        Err(e) => return vec![e.to_string()],
+    };
+    let (dns_class, ttl) = match soa_class_and_ttl(origin, &rrsets) {
+        Ok(soa) => soa,
+        Err(e) => return vec![e.to_string()],
+    };
+
+    let dnskeys = rrsets
+        .get(&RrKey::new(origin, RecordType::DNSKEY))
+        .map(|rrset| {
+            rrset
+                .iter()
+                .filter_map(|record| match *record.rdata() {
+                    RData::DNSKEY(ref dnskey) if dnskey.zone_key() => Some(dnskey),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if dnskeys.is_empty() {
+        problems.push(format!("{}: no DNSKEY for signing the zone", origin));
+        return problems;
+    }
+
+    // the records generated by signing, which are checked against those expected
+    let mut generated: BTreeMap<RrKey, RecordSet> = BTreeMap::new();
+    let mut rrsigs: BTreeMap<RrKey, Vec<&SIG>> = BTreeMap::new();
+    for record in records {
+        match record.rr_type() {
+            RecordType::RRSIG => {
+                if let RData::SIG(ref sig) = *record.rdata() {
+                    rrsigs
+                        .entry(RrKey::new(record.name(), sig.type_covered()))
+                        .or_insert_with(Vec::new)
+                        .push(sig);
+                }
+            }
+            record_type if is_generated(record_type) => insert(&mut generated, record.clone()),
+            _ => (),
+        }
+    }
+
+    // the signatures
+    let delegations = delegations(origin, &rrsets);
+    for (key, rrset) in rrsets.iter().chain(generated.iter()) {
+        if !is_signed(&delegations, &key.name, key.record_type) {
+            continue;
+        }
+
+        let sigs = rrsigs.get(key).map(|sigs| sigs.as_slice()).unwrap_or(&[]);
+        if sigs.is_empty() {
+            problems.push(format!("{} {}: not signed", key.name, key.record_type));
+            continue;
+        }
+
+        let records = rrset.iter().cloned().collect::<Vec<Record>>();
+        let mut failures = Vec::new();
+        let is_valid = sigs.iter().any(|sig| {
+            match verify_rrsig(origin, &dnskeys, dns_class, &key.name, sig, &records, now) {
+                Ok(()) => true,
+                Err(e) => {
+                    failures.push(format!("key {}: {}", sig.key_tag(), e));
+                    false
+                }
+            }
+        });
+
+        if !is_valid {
+            problems.push(format!(
+                "{} {}: no valid signature, {}",
+                key.name,
+                key.record_type,
+                failures.join(", ")
+            ));
+        }
+    }
+
+    // the denial of existence, as it was signed
+    let denial = match generated
+        .get(&RrKey::new(origin, RecordType::NSEC3PARAM))
+        .and_then(|rrset| rrset.iter().next())
+        .map(|record| record.rdata()) {
+        Some(&RData::NSEC3PARAM(ref param)) => Denial::Nsec3 {
+            iterations: param.iterations(),
+            salt: param.salt().to_vec(),
+            opt_out: generated.values().flat_map(|rrset| rrset.iter()).any(
+                |record| match *record.rdata() {
+                    RData::NSEC3(ref nsec3) => nsec3.opt_out(),
+                    _ => false,
+                },
+            ),
+        },
+        _ => Denial::Nsec,
+    };
+
+    let expected = match denial_records(origin, &rrsets, &denial, ttl) {
+        Ok(expected) => expected,
+        Err(e) => {
+            problems.push(format!("could not generate the denial of existence: {}", e));
+            return problems;
+        }
+    };
+
+    let mut expected_keys = BTreeSet::new();
+    for record in &expected {
+        let key = RrKey::new(record.name(), record.rr_type());
+        match generated.get(&key).map(|rrset| {
+            rrset.iter().any(|present| present.rdata() == record.rdata())
+        }) {
+            Some(true) => (),
+            Some(false) => {
+                problems.push(format!(
+                    "{} {}: does not match the zone, expected: {:?}",
+                    key.name,
+                    key.record_type,
+                    record.rdata()
+                ))
+            }
+            None => problems.push(format!("{} {}: missing", key.name, key.record_type)),
+        }
+        expected_keys.insert(key);
+    }
+
+    for key in generated.keys().filter(|key| !expected_keys.contains(key)) {
+        problems.push(format!("{} {}: not part of the zone", key.name, key.record_type));
+    }
+
+    problems
+}
+
+/// Returns true for the types of the records which are generated when the zone is signed
+fn is_generated(record_type: RecordType) -> bool {
+    match record_type {
+        RecordType::RRSIG | RecordType::NSEC | RecordType::NSEC3 | RecordType::NSEC3PARAM => true,
+        _ => false,
+    }
+}
+
+fn insert(rrsets: &mut BTreeMap<RrKey, RecordSet>, record: Record) {
+    let key = RrKey::new(record.name(), record.rr_type());
+    rrsets
+        .entry(key)
+        .or_insert_with(|| RecordSet::new(record.name(), record.rr_type(), 0))
+        .insert(record, 0);
+}
+
+/// Groups the records of the zone into RRsets, leaving out those generated by signing
+fn collect_rrsets(origin: &Name, records: &[Record]) -> DnsSecResult<BTreeMap<RrKey, RecordSet>> {
+    let mut rrsets = BTreeMap::new();
+    for record in records {
+        if !origin.zone_of(record.name()) {
+            return Err(
+                DnsSecErrorKind::Msg(format!(
+                    "record not in the zone {}: {} {}",
+                    origin,
+                    record.name(),
+                    record.rr_type()
+                )).into(),
+            );
+        }
+
+        if !is_generated(record.rr_type()) {
+            insert(&mut rrsets, record.clone());
+        }
+    }
+
+    Ok(rrsets)
+}
+
+/// Returns the class of the zone, and the minimum TTL of its SOA, used for the generated records
+fn soa_class_and_ttl(
+    origin: &Name,
+    rrsets: &BTreeMap<RrKey, RecordSet>,
+) -> DnsSecResult<(DNSClass, u32)> {
+    rrsets
+        .get(&RrKey::new(origin, RecordType::SOA))
+        .and_then(|rrset| rrset.iter().next())
+        .and_then(|record| match *record.rdata() {
+            RData::SOA(ref soa) => Some((record.dns_class(), soa.minimum())),
+            _ => None,
+        })
+        .ok_or_else(|| DnsSecErrorKind::Msg(format!("no SOA for the zone: {}", origin)).into())
+}
+
+/// Returns the names, other than the origin, at which the zone is delegated
+fn delegations(origin: &Name, rrsets: &BTreeMap<RrKey, RecordSet>) -> BTreeSet<Name> {
+    rrsets
+        .keys()
+        .filter(|key| key.record_type == RecordType::NS && &key.name != origin)
+        .map(|key| key.name.clone())
+        .collect()
+}
+
+/// Returns true if the name is below a delegation, i.e. it is glue which is not authoritative
+fn is_below_delegation(delegations: &BTreeSet<Name>, name: &Name) -> bool {
+    delegations
+        .iter()
+        .any(|delegation| delegation != name && delegation.zone_of(name))
+}
+
+/// Returns true if the RRset is authoritative, only those are signed
+fn is_signed(delegations: &BTreeSet<Name>, name: &Name, record_type: RecordType) -> bool {
+    if is_below_delegation(delegations, name) {
+        return false;
+    }
+
+    // the NS records at a delegation belong to the child zone
+    if delegations.contains(name) {
+        return record_type == RecordType::DS || record_type == RecordType::NSEC;
+    }
+
+    true
+}
+
+/// Returns the NSEC or NSEC3 records, and the NSEC3PARAM, for the RRsets of the zone
+pub(crate) fn denial_records(
+    origin: &Name,
+    rrsets: &BTreeMap<RrKey, RecordSet>,
+    denial: &Denial,
+    ttl: u32,
+) -> DnsSecResult<Vec<Record>> {
+    let delegations = delegations(origin, rrsets);
+
+    // the names of the zone with the types at each, those below a delegation are not in the chain
+    let mut names: BTreeMap<Name, Vec<RecordType>> = BTreeMap::new();
+    for key in rrsets.keys().filter(|key| !is_generated(key.record_type)) {
+        if !is_below_delegation(&delegations, &key.name) {
+            names.entry(key.name.clone()).or_insert_with(Vec::new).push(
+                key.record_type,
+            );
+        }
+    }
+
+    match *denial {
+        Denial::Nsec => Ok(nsec_records(origin, names, ttl)),
+        Denial::Nsec3 {
+            iterations,
+            ref salt,
+            opt_out,
+        } => nsec3_records(origin, &delegations, names, iterations, salt, opt_out, ttl),
+    }
+}
+
+fn nsec_records(origin: &Name, names: BTreeMap<Name, Vec<RecordType>>, ttl: u32) -> Vec<Record> {
+    let next_names = names
+        .keys()
+        .skip(1)
+        .cloned()
+        .chain(Some(origin.clone()))
+        .collect::<Vec<_>>();
+
+    names
+        .into_iter()
+        .zip(next_names)
+        .map(|((name, mut types), next_name)| {
+            types.push(RecordType::RRSIG);
+            types.push(RecordType::NSEC);
+            types.sort();
+            types.dedup();
+
+            let rdata = NSEC::new(next_name, types);
+            Record::from_rdata(name, ttl, RecordType::NSEC, RData::NSEC(rdata))
+        })
+        .collect()
+}
+
+fn nsec3_records(
+    origin: &Name,
+    delegations: &BTreeSet<Name>,
+    names: BTreeMap<Name, Vec<RecordType>>,
+    iterations: u16,
+    salt: &[u8],
+    opt_out: bool,
+    ttl: u32,
+) -> DnsSecResult<Vec<Record>> {
+    if iterations > MAX_NSEC3_ITERATIONS {
+        return Err(
+            DnsSecErrorKind::Msg(format!(
+                "more than {} NSEC3 iterations: {}",
+                MAX_NSEC3_ITERATIONS,
+                iterations
+            )).into(),
+        );
+    }
+
+    let origin_labels = origin.iter().count();
+
+    // the names in the chain, including the empty non-terminals, RFC 5155 section 7.1
+    let mut chain: BTreeMap<Name, Vec<RecordType>> = BTreeMap::new();
+    for (name, mut types) in names {
+        let is_insecure = delegations.contains(&name) && !types.contains(&RecordType::DS);
+        if is_insecure && opt_out {
+            continue;
+        }
+
+        for num_labels in (origin_labels + 1)..name.iter().count() {
+            chain.entry(name.trim_to(num_labels)).or_insert_with(
+                Vec::new,
+            );
+        }
+
+        // the NS records of an insecure delegation are the only ones not signed
+        if !is_insecure {
+            types.push(RecordType::RRSIG);
+        }
+        if &name == origin {
+            types.push(RecordType::NSEC3PARAM);
+        }
+        types.sort();
+        types.dedup();
+        chain.insert(name, types);
+    }
+
+    let mut hashes: BTreeMap<Vec<u8>, Vec<RecordType>> = BTreeMap::new();
+    for (name, types) in chain {
+        let hash = try!(Nsec3HashAlgorithm::SHA1.hash(salt, &name, iterations));
+        if hashes.insert(hash.as_ref().to_vec(), types).is_some() {
+            return Err(
+                DnsSecErrorKind::Msg(format!("NSEC3 hash collision for: {}", name)).into(),
+            );
+        }
+    }
+
+    let next_hashes = hashes
+        .keys()
+        .skip(1)
+        .chain(hashes.keys().take(1))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut records = hashes
+        .into_iter()
+        .zip(next_hashes)
+        .map(|((hash, types), next_hash)| {
+            let label = base32hex::encode(&hash).to_lowercase();
+            let rdata = NSEC3::new(
+                Nsec3HashAlgorithm::SHA1,
+                opt_out,
+                iterations,
+                salt.to_vec(),
+                next_hash,
+                types,
+            );
+
+            Record::from_rdata(
+                Name::from_labels(vec![label]).append_domain(origin),
+                ttl,
+                RecordType::NSEC3,
+                RData::NSEC3(rdata),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // the flags of the NSEC3PARAM are always zero, RFC 5155 section 4.1.2
+    let param = NSEC3PARAM::new(Nsec3HashAlgorithm::SHA1, false, iterations, salt.to_vec());
+    records.push(Record::from_rdata(
+        origin.clone(),
+        ttl,
+        RecordType::NSEC3PARAM,
+        RData::NSEC3PARAM(param),
+    ));
+
+    Ok(records)
+}
+
+/// Signs the RRset with the signer, returns the RRSIG
+fn sign_rrset(
+    rrset: &RecordSet,
+    dns_class: DNSClass,
+    signer: &Signer,
+    inception: DateTime<Utc>,
+) -> DnsSecResult<Record> {
+    let expiration = inception + signer.sig_duration();
+    let key_tag = try!(signer.calculate_key_tag());
+    let records = rrset.iter().cloned().collect::<Vec<Record>>();
+
+    let tbs = try!(tbs::rrset_tbs(
+        rrset.name(),
+        dns_class,
+        rrset.name().num_labels(),
+        rrset.record_type(),
+        signer.algorithm(),
+        rrset.ttl(),
+        expiration.timestamp() as u32,
+        inception.timestamp() as u32,
+        key_tag,
+        signer.signer_name(),
+        &records,
+    ));
+    let signature = try!(signer.sign(&tbs));
+
+    let mut rrsig = Record::from_rdata(
+        rrset.name().clone(),
+        rrset.ttl(),
+        RecordType::RRSIG,
+        RData::SIG(SIG::new(
+            rrset.record_type(),
+            signer.algorithm(),
+            rrset.name().num_labels(),
+            rrset.ttl(),
+            expiration.timestamp() as u32,
+            inception.timestamp() as u32,
+            key_tag,
+            signer.signer_name().clone(),
+            signature,
+        )),
+    );
+    rrsig.set_dns_class(dns_class);
+
+    Ok(rrsig)
+}
+
+/// Verifies the RRSIG of the RRset with the matching DNSKEY of the zone
+fn verify_rrsig(
+    origin: &Name,
+    dnskeys: &[&DNSKEY],
+    dns_class: DNSClass,
+    name: &Name,
+    sig: &SIG,
+    records: &[Record],
+    now: u32,
+) -> DnsSecResult<()> {
+    if sig.signer_name() != origin {
+        return Err(
+            DnsSecErrorKind::Msg(format!("signed by another zone: {}", sig.signer_name())).into(),
+        );
+    }
+    if now < sig.sig_inception() {
+        return Err(DnsSecErrorKind::Message("signature not yet valid").into());
+    }
+    if now > sig.sig_expiration() {
+        return Err(DnsSecErrorKind::Message("signature expired").into());
+    }
+
+    let mut dnskeys = dnskeys.iter().filter(|dnskey| {
+        dnskey.algorithm() == sig.algorithm() &&
+            dnskey.calculate_key_tag().ok() == Some(sig.key_tag())
+    });
+
+    // key tags may collide, any of the keys with the tag may have made the signature
+    let mut result: DnsSecResult<()> =
+        Err(DnsSecErrorKind::Message("no DNSKEY for the signature").into());
+    while let Some(dnskey) = dnskeys.next() {
+        result = dnskey
+            .verify_rrsig(name, dns_class, sig, records)
+            .map_err(Into::into);
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    result
+}
@@ -0,0 +1,319 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Per zone statistics and health, for monitoring the zones in a `Catalog`
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use trust_dns::op::ResponseCode;
+use trust_dns::rr::{Name, RData};
+
+use authority::{Authority, ZoneType};
+
+/// Percentage of the SOA expire interval, since the last refresh, after which a secondary zone is
+///  reported as `ZoneHealthStatus::NearExpiry`
+pub const EXPIRY_WARNING_PERCENT: u64 = 80;
+
+/// Outcome of the most recent transfer of a zone
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransferStatus {
+    /// No transfer has been recorded for the zone
+    None,
+    /// The transfer succeeded, the serial of the transferred zone
+    Succeeded(u32),
+    /// The transfer failed, the reason for the failure
+    Failed(String),
+}
+
+/// Outcome of the NOTIFY of a change to the zone, sent by a primary to one of its secondaries
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NotifyStatus {
+    /// The NOTIFY is being sent, the serial notified and the number of attempts so far
+    Pending {
+        /// serial of the zone in the NOTIFY
+        serial: u32,
+        /// number of times the NOTIFY was sent
+        attempts: u32,
+    },
+    /// The secondary responded to the NOTIFY
+    Succeeded {
+        /// serial of the zone in the NOTIFY
+        serial: u32,
+        /// number of times the NOTIFY was sent
+        attempts: u32,
+    },
+    /// The secondary responded with an error, or did not respond to any of the attempts
+    Failed {
+        /// serial of the zone in the NOTIFY
+        serial: u32,
+        /// the reason for the failure
+        error: String,
+    },
+}
+
+/// Counters for the queries answered from a single zone
+#[derive(Clone, Debug)]
+pub struct ZoneStats {
+    queries: u64,
+    response_codes: HashMap<ResponseCode, u64>,
+    transfer_status: TransferStatus,
+    last_transfer: Option<Instant>,
+    last_refresh: Instant,
+    notify_status: HashMap<SocketAddr, NotifyStatus>,
+    load_failures: u64,
+}
+
+impl ZoneStats {
+    pub(crate) fn new() -> Self {
+        ZoneStats {
+            queries: 0,
+            response_codes: HashMap::new(),
+            transfer_status: TransferStatus::None,
+            last_transfer: None,
+            last_refresh: Instant::now(),
+            notify_status: HashMap::new(),
+            load_failures: 0,
+        }
+    }
+
+    pub(crate) fn record_response(&mut self, response_code: ResponseCode) {
+        self.queries += 1;
+        *self.response_codes.entry(response_code).or_insert(0) += 1;
+    }
+
+    pub(crate) fn record_transfer(&mut self, transfer_status: TransferStatus) {
+        let now = Instant::now();
+        if let TransferStatus::Succeeded(..) = transfer_status {
+            self.last_refresh = now;
+        }
+
+        self.last_transfer = Some(now);
+        self.transfer_status = transfer_status;
+    }
+
+    pub(crate) fn record_refresh(&mut self) {
+        self.last_refresh = Instant::now();
+    }
+
+    pub(crate) fn record_notify(&mut self, target: SocketAddr, notify_status: NotifyStatus) {
+        self.notify_status.insert(target, notify_status);
+    }
+
+    pub(crate) fn record_load_failure(&mut self) {
+        self.load_failures += 1;
+    }
+
+    /// Total number of queries answered from the zone
+    pub fn queries(&self) -> u64 {
+        self.queries
+    }
+
+    /// Number of queries answered with each response code
+    pub fn response_codes(&self) -> &HashMap<ResponseCode, u64> {
+        &self.response_codes
+    }
+
+    /// Number of queries answered with the response code
+    pub fn response_code_count(&self, response_code: ResponseCode) -> u64 {
+        self.response_codes.get(&response_code).cloned().unwrap_or(0)
+    }
+
+    /// Outcome of the most recent transfer of the zone
+    pub fn transfer_status(&self) -> &TransferStatus {
+        &self.transfer_status
+    }
+
+    /// Time since the most recent transfer was attempted
+    pub fn since_transfer(&self) -> Option<Duration> {
+        self.last_transfer.map(|last_transfer| last_transfer.elapsed())
+    }
+
+    /// Time since the zone was loaded, transferred, or confirmed to be current with the primary
+    pub fn since_refresh(&self) -> Duration {
+        self.last_refresh.elapsed()
+    }
+
+    /// Outcome of the most recent NOTIFY to each of the secondaries, empty unless this is a
+    ///  primary which has changed, see `Catalog::notify`
+    pub fn notify_status(&self) -> &HashMap<SocketAddr, NotifyStatus> {
+        &self.notify_status
+    }
+
+    /// Number of times the zone failed to load, or to reload, see `Catalog::mark_failed`
+    pub fn load_failures(&self) -> u64 {
+        self.load_failures
+    }
+}
+
+/// The health of a zone, see `ZoneHealth`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZoneHealthStatus {
+    /// The zone is current
+    Healthy,
+    /// A secondary has not been refreshed within the SOA refresh interval
+    RefreshOverdue,
+    /// A secondary has passed `EXPIRY_WARNING_PERCENT` of the SOA expire interval without being
+    ///  refreshed, replication is likely broken
+    NearExpiry,
+    /// A secondary has passed the SOA expire interval without being refreshed, and should no
+    ///  longer be considered authoritative
+    Expired,
+    /// The zone failed to reload, the version it had before is still served
+    ReloadFailed,
+    /// The zone failed to load, or has no SOA
+    Failed,
+}
+
+/// The health of a single zone, as determined from its SOA and when it was last refreshed
+#[derive(Clone, Debug)]
+pub struct ZoneHealth {
+    zone: Name,
+    status: ZoneHealthStatus,
+    serial: Option<u32>,
+    since_refresh: Option<Duration>,
+    error: Option<String>,
+}
+
+impl ZoneHealth {
+    pub(crate) fn failed(zone: Name) -> Self {
+        ZoneHealth {
+            zone: zone,
+            status: ZoneHealthStatus::Failed,
+            serial: None,
+            since_refresh: None,
+            error: None,
+        }
+    }
+
+    /// Adds the reason the zone last failed to load, a zone which is served is then
+    ///  `ReloadFailed`, unless it is worse off already
+    pub(crate) fn with_error(mut self, error: String) -> Self {
+        if self.serial.is_some() {
+            self.status = match self.status {
+                ZoneHealthStatus::Healthy |
+                ZoneHealthStatus::RefreshOverdue => ZoneHealthStatus::ReloadFailed,
+                status => status,
+            };
+        }
+
+        self.error = Some(error);
+        self
+    }
+
+    /// Checks the zone against the refresh and expire intervals of its SOA, only secondaries can
+    ///  become stale, all other zones are healthy if they have an SOA
+    pub(crate) fn check(authority: &Authority, stats: &ZoneStats) -> Self {
+        let soa = authority.soa().and_then(|soa| if let RData::SOA(ref soa) = *soa.rdata() {
+            Some(soa.clone())
+        } else {
+            None
+        });
+        let soa = match soa {
+            Some(soa) => soa,
+            None => return Self::failed(authority.origin().clone()),
+        };
+
+        let since_refresh = stats.since_refresh();
+        let status = if authority.zone_type() != ZoneType::Slave {
+            ZoneHealthStatus::Healthy
+        } else {
+            // negative intervals are nonsensical, treat them as already elapsed
+            let refresh = Duration::from_secs(soa.refresh().max(0) as u64);
+            let expire = Duration::from_secs(soa.expire().max(0) as u64);
+            let warning = Duration::from_secs(expire.as_secs() * EXPIRY_WARNING_PERCENT / 100);
+
+            if since_refresh >= expire {
+                ZoneHealthStatus::Expired
+            } else if since_refresh >= warning {
+                ZoneHealthStatus::NearExpiry
+            } else if since_refresh >= refresh {
+                ZoneHealthStatus::RefreshOverdue
+            } else {
+                ZoneHealthStatus::Healthy
+            }
+        };
+
+        ZoneHealth {
+            zone: authority.origin().clone(),
+            status: status,
+            serial: Some(soa.serial()),
+            since_refresh: Some(since_refresh),
+            error: None,
+        }
+    }
+
+    /// The name of the zone
+    pub fn zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// The health of the zone
+    pub fn status(&self) -> ZoneHealthStatus {
+        self.status
+    }
+
+    /// True if the zone needs the attention of an operator, i.e. it is not `Healthy` and not only
+    ///  `RefreshOverdue`
+    pub fn is_critical(&self) -> bool {
+        match self.status {
+            ZoneHealthStatus::Healthy |
+            ZoneHealthStatus::RefreshOverdue => false,
+            ZoneHealthStatus::NearExpiry |
+            ZoneHealthStatus::Expired |
+            ZoneHealthStatus::ReloadFailed |
+            ZoneHealthStatus::Failed => true,
+        }
+    }
+
+    /// The serial of the zone being served, None if the zone failed to load
+    pub fn serial(&self) -> Option<u32> {
+        self.serial
+    }
+
+    /// Time since the zone was last refreshed, None if the zone failed to load
+    pub fn since_refresh(&self) -> Option<Duration> {
+        self.since_refresh
+    }
+
+    /// The reason the zone last failed to load, or to reload, None if it has not failed since it
+    ///  was last loaded
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(|error| error.as_str())
+    }
+
+    /// The error in the words of `Display`, `reload failed: ...` for a zone which is still served,
+    ///  otherwise `load failed: ...`
+    pub(crate) fn error_text(&self) -> Option<String> {
+        self.error.as_ref().map(|error| if self.serial.is_some() {
+            format!("reload failed: {}", error)
+        } else {
+            format!("load failed: {}", error)
+        })
+    }
+}
+
+impl fmt::Display for ZoneHealth {
+    /// The zone, its status, serial and seconds since the last refresh, and why it failed to
+    ///  load, e.g. `example.com. Healthy serial 2017010101 refreshed 42s ago`, or
+    ///  `example.com. ReloadFailed serial 2017010101 refreshed 42s ago, reload failed: ...`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} {:?}", self.zone, self.status));
+        if let Some(serial) = self.serial {
+            try!(write!(f, " serial {}", serial));
+        }
+        if let Some(since_refresh) = self.since_refresh {
+            try!(write!(f, " refreshed {}s ago", since_refresh.as_secs()));
+        }
+        if let Some(error) = self.error_text() {
+            try!(write!(f, ", {}", error));
+        }
+        Ok(())
+    }
+}
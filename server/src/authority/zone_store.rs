@@ -0,0 +1,127 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Storage of the RRsets of a zone, see `Authority::with_store`
+
+use std::collections::BTreeMap;
+
+use trust_dns::rr::{RecordSet, RrKey};
+
+use error::PersistenceResult;
+
+/// The storage of the RRsets of a zone
+///
+/// The `Authority` reads the RRsets through `get_rrset` and `iter`, and writes each change
+///  through `upsert` or `delete`, so that a store may keep them elsewhere, e.g. in a database,
+///  as long as it can hand out references to them, e.g. from a cache in memory. The default store
+///  is a `BTreeMap` in memory.
+///
+/// The changes of one dynamic update are made between `begin` and `commit`, so that a store which
+///  can fail to write them can make them all or none, see `rollback`.
+pub trait ZoneStore: Send + Sync {
+    /// Returns the RRset of the name and type, if it is in the zone
+    fn get_rrset(&self, key: &RrKey) -> Option<&RecordSet>;
+
+    /// Inserts the RRset, or replaces the one of its name and type, which is returned
+    ///
+    /// An error is returned if the change could not be written, outside of `begin` and `commit`
+    ///  the RRset is then left as it was.
+    fn upsert(&mut self, key: RrKey, rrset: RecordSet) -> PersistenceResult<Option<RecordSet>>;
+
+    /// Removes the RRset of the name and type, and returns it
+    ///
+    /// An error is returned if the change could not be written, see `upsert`.
+    fn delete(&mut self, key: &RrKey) -> PersistenceResult<Option<RecordSet>>;
+
+    /// Starts a set of changes, which are kept together until `commit` or `rollback`
+    fn begin(&mut self) -> PersistenceResult<()> {
+        Ok(())
+    }
+
+    /// Keeps the changes since `begin`, if any of them could not be written, or they can not be
+    ///  committed, an error is returned and they are all undone, as by `rollback`
+    fn commit(&mut self) -> PersistenceResult<()> {
+        Ok(())
+    }
+
+    /// Undoes the changes since `begin`
+    ///
+    /// The default does nothing, which is only correct for stores whose writes can not fail.
+    fn rollback(&mut self) {}
+
+    /// All the RRsets of the zone, in the order of their keys, i.e. the canonical order of their
+    ///  names and then their types
+    fn iter<'a>(&'a self) -> Box<Iterator<Item = (&'a RrKey, &'a RecordSet)> + 'a>;
+
+    /// The RRsets of the zone from the key on, as by `iter`, e.g. to resume a zone transfer where
+    ///  its last message ended
+    ///
+    /// The default skips the RRsets before the key, stores which keep them ordered should look up
+    ///  the key instead.
+    fn iter_from<'a>(
+        &'a self,
+        key: &RrKey,
+    ) -> Box<Iterator<Item = (&'a RrKey, &'a RecordSet)> + 'a> {
+        let key = key.clone();
+        Box::new(self.iter().skip_while(move |&(other, _)| *other < key))
+    }
+
+    /// The number of RRsets in the zone
+    fn len(&self) -> usize;
+
+    /// Removes all the RRsets of the zone
+    fn clear(&mut self);
+
+    /// Returns true if the zone has no RRsets
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a copy of all the RRsets of the zone
+    fn to_map(&self) -> BTreeMap<RrKey, RecordSet> {
+        self.iter()
+            .map(|(key, rrset)| (key.clone(), rrset.clone()))
+            .collect()
+    }
+}
+
+impl ZoneStore for BTreeMap<RrKey, RecordSet> {
+    fn get_rrset(&self, key: &RrKey) -> Option<&RecordSet> {
+        self.get(key)
+    }
+
+    fn upsert(&mut self, key: RrKey, rrset: RecordSet) -> PersistenceResult<Option<RecordSet>> {
+        Ok(self.insert(key, rrset))
+    }
+
+    fn delete(&mut self, key: &RrKey) -> PersistenceResult<Option<RecordSet>> {
+        Ok(self.remove(key))
+    }
+
+    fn iter<'a>(&'a self) -> Box<Iterator<Item = (&'a RrKey, &'a RecordSet)> + 'a> {
+        Box::new(BTreeMap::iter(self))
+    }
+
+    fn iter_from<'a>(
+        &'a self,
+        key: &RrKey,
+    ) -> Box<Iterator<Item = (&'a RrKey, &'a RecordSet)> + 'a> {
+        Box::new(self.range(key.clone()..))
+    }
+
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+
+    fn clear(&mut self) {
+        BTreeMap::clear(self)
+    }
+
+    fn to_map(&self) -> BTreeMap<RrKey, RecordSet> {
+        self.clone()
+    }
+}
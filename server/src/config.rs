@@ -18,27 +18,35 @@
 
 use std::fs::File;
 use std::io::Read;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{AddrParseError, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
 
 use log::LogLevel;
+use num_cpus;
+use regex;
 use rustc_serialize::Decodable;
-use toml::{Decoder, Value};
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::hex::FromHex;
+use toml::{Decoder, Parser, Value};
 
 use trust_dns::error::*;
 use trust_dns::rr::Name;
-use trust_dns::rr::dnssec::{Algorithm, KeyFormat};
+use trust_dns::rr::dnssec::{Algorithm, KeyFormat, TsigAlgorithm, DEFAULT_FUDGE};
+use trust_dns::rr::rdata::opt::RESPONSE_PADDING_BLOCK_SIZE;
 use trust_dns_proto::error::ProtoResult;
 
-use authority::ZoneType;
+use authority::{Denial, IllegalDataPolicy, ResponsePolicy, ZoneType, MAX_NSEC3_ITERATIONS};
 use error::{ConfigErrorKind, ConfigResult, ConfigError};
+use server::DEFAULT_POLL_BUDGET;
 
 static DEFAULT_PATH: &'static str = "/var/named"; // TODO what about windows (do I care? ;)
 static DEFAULT_PORT: u16 = 53;
 static DEFAULT_TLS_PORT: u16 = 853;
 static DEFAULT_TCP_REQUEST_TIMEOUT: u64 = 5;
+static DEFAULT_HEALTH_CHECK_INTERVAL: u64 = 60;
+static DEFAULT_WORKER_QUEUE_DEPTH: usize = 128;
 
 /// Server configuration
 #[derive(RustcDecodable, Debug)]
@@ -61,6 +69,26 @@ pub struct Config {
     zones: Vec<ZoneConfig>,
     /// Certificate to associate to TLS connections
     tls_cert: Option<TlsCertConfig>,
+    /// Block size to which responses to padded queries over TLS are padded, 0 disables padding
+    tls_padding_block_size: Option<u16>,
+    /// Number of threads for handling CPU intensive requests, e.g. signed updates
+    worker_threads: Option<usize>,
+    /// Number of CPU intensive requests which can wait for a worker before being rejected
+    worker_queue_depth: Option<usize>,
+    /// Number of requests, or connections, taken from a socket before it yields to the others
+    poll_budget: Option<usize>,
+    /// Number of assembled answers to cache, 0 disables the cache
+    response_cache_size: Option<usize>,
+    /// Seconds between checks of the health of each zone, 0 disables the checks
+    health_check_interval: Option<u64>,
+    /// Answer the CHAOS TXT query for health.server. with the health of each zone, false by default
+    health_query: Option<bool>,
+    /// Address on which to listen for the commands which add and remove zones, none by default
+    control_addr: Option<String>,
+    /// Answer queries without a valid server cookie with BADCOOKIE, false by default
+    require_cookies: Option<bool>,
+    /// Shared keys with which requests are verified and their responses signed, TSIG
+    tsig_keys: Option<Vec<TsigKeyConfig>>,
 }
 
 impl Config {
@@ -132,22 +160,110 @@ impl Config {
     pub fn get_tls_cert(&self) -> Option<&TlsCertConfig> {
         self.tls_cert.as_ref()
     }
+    /// the block size to which the responses to queries over TLS with the Padding option are
+    ///  padded, RFC 7830, None if 0 is configured. Defaults to 468, as recommended by RFC 8467
+    pub fn get_tls_padding_block_size(&self) -> Option<u16> {
+        match self.tls_padding_block_size {
+            Some(0) => None,
+            Some(block_size) => Some(block_size),
+            None => Some(RESPONSE_PADDING_BLOCK_SIZE),
+        }
+    }
+    /// number of worker threads for CPU intensive requests, the number of CPUs by default, 0
+    ///  disables the pool and all requests are handled on the IO loop
+    pub fn get_worker_threads(&self) -> usize {
+        self.worker_threads.unwrap_or_else(num_cpus::get)
+    }
+    /// number of CPU intensive requests which may be queued for the worker pool, once full
+    ///  additional requests are answered with ServFail
+    pub fn get_worker_queue_depth(&self) -> usize {
+        self.worker_queue_depth.unwrap_or(DEFAULT_WORKER_QUEUE_DEPTH)
+    }
+    /// number of requests, or connections, taken from a socket or connection each time it is
+    ///  polled, before it yields to the others, so that one busy client can not hold up the
+    ///  rest, see `ServerFuture::set_poll_budget`. Defaults to 32
+    pub fn get_poll_budget(&self) -> usize {
+        self.poll_budget.unwrap_or(DEFAULT_POLL_BUDGET)
+    }
+    /// number of assembled answers the catalog will cache, 0 (the default) disables the cache
+    pub fn get_response_cache_size(&self) -> usize {
+        self.response_cache_size.unwrap_or(0)
+    }
+    /// interval at which the health of each zone is checked and any problems logged, a zero
+    ///  duration disables the checks
+    pub fn get_health_check_interval(&self) -> Duration {
+        Duration::from_secs(self.health_check_interval.unwrap_or(
+            DEFAULT_HEALTH_CHECK_INTERVAL,
+        ))
+    }
+    /// true if a TXT query for `health.server.` in the CHAOS class is answered with the health of
+    ///  each zone, which lists the names of the zones to anyone who can query the server, see
+    ///  `Catalog::set_health_query`. Defaults to false
+    pub fn get_health_query(&self) -> bool {
+        self.health_query.unwrap_or(false)
+    }
+    /// address of the control channel, on which zones can be added, reloaded and removed while
+    ///  the server is running, e.g. `127.0.0.1:9953`. The commands are not authenticated, so this
+    ///  must be a loopback address, only reachable from the host of the server. None (the
+    ///  default) disables it
+    pub fn get_control_addr(&self) -> ConfigResult<Option<SocketAddr>> {
+        let control_addr: SocketAddr = match self.control_addr {
+            Some(ref control_addr) => {
+                try!(control_addr.parse().map_err(|e: AddrParseError| {
+                    ConfigError::from(ConfigErrorKind::Msg(format!("{}: {}", e, control_addr)))
+                }))
+            }
+            None => return Ok(None),
+        };
+
+        if !control_addr.ip().is_loopback() {
+            return Err(
+                ConfigErrorKind::Msg(format!("not a loopback address: {}", control_addr)).into(),
+            );
+        }
+        Ok(Some(control_addr))
+    }
+    /// true if queries with a client cookie but without a valid server cookie are answered with
+    ///  BADCOOKIE and a fresh server cookie, RFC 7873 section 5.2.3; queries without any cookie
+    ///  are always answered. Defaults to false
+    pub fn get_require_cookies(&self) -> bool {
+        self.require_cookies.unwrap_or(false)
+    }
+    /// the shared keys, TSIG, with which requests are verified and their responses signed,
+    ///  updates must also be allowed for the key by the zone, see `update_tsig_keys`
+    pub fn get_tsig_keys(&self) -> &[TsigKeyConfig] {
+        self.tsig_keys.as_ref().map_or(&[], |keys| &keys[..])
+    }
 }
 
 impl FromStr for Config {
     type Err = ConfigError;
 
     fn from_str(toml: &str) -> ConfigResult<Config> {
-        let value: Value = try!(toml.parse().map_err(
-            |vec| ConfigErrorKind::VecParserError(vec),
-        ));
+        let mut parser = Parser::new(toml);
+        let value: Value = match parser.parse() {
+            Some(table) => Value::Table(table),
+            None => {
+                // report the line and column of each error, these are 0 based in the parser
+                let errors = parser
+                    .errors
+                    .iter()
+                    .map(|e| {
+                        let (line, col) = parser.to_linecol(e.lo);
+                        format!("line {} column {}: {}", line + 1, col + 1, e.desc)
+                    })
+                    .collect();
+
+                return Err(ConfigErrorKind::ParserErrors(errors).into());
+            }
+        };
         let mut decoder: Decoder = Decoder::new(value);
         Ok(try!(Self::decode(&mut decoder)))
     }
 }
 
 /// Configuration for a zone
-#[derive(RustcDecodable, PartialEq, Debug)]
+#[derive(RustcDecodable, PartialEq, Debug, Clone)]
 pub struct ZoneConfig {
     zone: String, // TODO: make Domain::Name decodable
     zone_type: ZoneType,
@@ -155,6 +271,24 @@ pub struct ZoneConfig {
     allow_update: Option<bool>,
     enable_dnssec: Option<bool>,
     keys: Vec<KeyConfig>,
+    primary: Option<String>,
+    also_notify: Option<Vec<String>>,
+    illegal_data: Option<IllegalDataPolicy>,
+    reload_interval: Option<u64>,
+    auto_serial: Option<bool>,
+    min_ttl: Option<u32>,
+    max_ttl: Option<u32>,
+    hide_txt: Option<Vec<String>>,
+    refuse_any: Option<bool>,
+    update_tsig_keys: Option<Vec<String>>,
+    ixfr_history_size: Option<usize>,
+    reverse_of: Option<Vec<String>>,
+    sqlite_store: Option<bool>,
+    nsec3: Option<bool>,
+    nsec3_iterations: Option<u16>,
+    nsec3_salt: Option<String>,
+    nsec3_opt_out: Option<bool>,
+    inline_signing: Option<bool>,
 }
 
 impl ZoneConfig {
@@ -183,6 +317,24 @@ impl ZoneConfig {
             allow_update: allow_update,
             enable_dnssec: enable_dnssec,
             keys: keys,
+            primary: None,
+            also_notify: None,
+            illegal_data: None,
+            reload_interval: None,
+            auto_serial: None,
+            min_ttl: None,
+            max_ttl: None,
+            hide_txt: None,
+            refuse_any: None,
+            update_tsig_keys: None,
+            ixfr_history_size: None,
+            reverse_of: None,
+            sqlite_store: None,
+            nsec3: None,
+            nsec3_iterations: None,
+            nsec3_salt: None,
+            nsec3_opt_out: None,
+            inline_signing: None,
         }
     }
 
@@ -219,10 +371,159 @@ impl ZoneConfig {
     pub fn get_keys(&self) -> &[KeyConfig] {
         &self.keys
     }
+
+    /// address of the primary from which a Slave zone is transferred, and to which its updates are
+    /// forwarded, e.g. `10.0.0.1:53`, by default the MNAME of the zone's SOA, which a Slave zone
+    /// without a zone file or journal does not have yet
+    pub fn get_primary(&self) -> Result<Option<SocketAddr>, AddrParseError> {
+        match self.primary {
+            Some(ref primary) => primary.parse().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// addresses which are sent a NOTIFY when this Master zone changes, in addition to the name
+    /// servers of the zone, e.g. `["10.0.0.2:53"]`
+    pub fn get_also_notify(&self) -> Result<Vec<SocketAddr>, AddrParseError> {
+        match self.also_notify {
+            Some(ref also_notify) => also_notify.iter().map(|addr| addr.parse()).collect(),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// how a CNAME at the apex, a CNAME with other data, or an SOA not at the apex in the zone is
+    /// handled on load, by default it is logged, see `IllegalDataPolicy`
+    pub fn get_illegal_data(&self) -> IllegalDataPolicy {
+        self.illegal_data.unwrap_or_default()
+    }
+
+    /// interval at which the zone file is checked for changes, and reloaded if it was modified,
+    /// None (the default) or 0 seconds disables reloading. Zones which allow updates are not
+    /// reloaded, their journal is the source of truth
+    pub fn get_reload_interval(&self) -> Option<Duration> {
+        match self.reload_interval {
+            Some(0) | None => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// when a reloaded zone file does not have a later SOA serial than the zone being served, the
+    /// serial is advanced past it, so that secondaries see the change
+    pub fn is_auto_serial(&self) -> bool {
+        self.auto_serial.unwrap_or(false)
+    }
+
+    /// how the answers of the zone are altered as they are served, from `min_ttl` and `max_ttl`
+    /// which clamp the TTLs, `hide_txt`, regular expressions of the TXT records to hide, and
+    /// `refuse_any`, which refuses queries of type ANY, see `ResponsePolicy`. None if there is
+    /// nothing to alter
+    pub fn get_response_policy(&self) -> Result<Option<ResponsePolicy>, regex::Error> {
+        if self.min_ttl.is_none() && self.max_ttl.is_none() && self.hide_txt.is_none() &&
+            self.refuse_any.is_none()
+        {
+            return Ok(None);
+        }
+
+        let mut policy = ResponsePolicy::new();
+        policy.set_min_ttl(self.min_ttl);
+        policy.set_max_ttl(self.max_ttl);
+        policy.set_refuse_any(self.refuse_any.unwrap_or(false));
+        for pattern in self.hide_txt.as_ref().map_or(&[][..], |hide_txt| &hide_txt[..]) {
+            try!(policy.hide_txt(pattern));
+        }
+
+        Ok(Some(policy))
+    }
+
+    /// names of the TSIG keys, see `Config::get_tsig_keys`, which are allowed to update the zone,
+    /// e.g. `["update-key.example.com."]`
+    pub fn get_update_tsig_keys(&self) -> ParseResult<Vec<Name>> {
+        match self.update_tsig_keys {
+            Some(ref keys) => {
+                keys.iter()
+                    .map(|key| Name::parse(key, None).map_err(|e| e.into()))
+                    .collect()
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    /// number of the latest changes to the zone which are kept to answer incremental zone
+    /// transfers, IXFR, None for the default of the `Authority`, 0 keeps none
+    pub fn get_ixfr_history_size(&self) -> Option<usize> {
+        self.ixfr_history_size
+    }
+
+    /// names of the forward zones from whose A and AAAA records the PTR records of this reverse
+    /// zone are generated, e.g. `["example.com."]`, the zone file then only needs the SOA and NS
+    /// records, see `Catalog::set_reverse_zone`
+    pub fn get_reverse_of(&self) -> ParseResult<Vec<Name>> {
+        match self.reverse_of {
+            Some(ref zones) => {
+                zones
+                    .iter()
+                    .map(|zone| Name::parse(zone, Some(&Name::new())).map_err(|e| e.into()))
+                    .collect()
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    /// the records of a zone which allows updates, or of a secondary zone, are kept in SQLite
+    /// next to the zone file, with the extension `sqlite`, instead of the journal, so that the
+    /// zone is started with the serial it was left at, see `SqliteStore`
+    pub fn is_sqlite_store(&self) -> bool {
+        self.sqlite_store.unwrap_or(false)
+    }
+
+    /// the zone is signed again as it changes, after each update, on reloads, and before its
+    /// signatures expire, rather than once when it is loaded, see `SigningAuthority`. requires
+    /// `enable_dnssec`, the zone is signed with its zone signing keys
+    pub fn is_inline_signing(&self) -> bool {
+        self.inline_signing.unwrap_or(false)
+    }
+
+    /// how the signed zone proves that names and types do not exist, NSEC records by default, or
+    /// NSEC3 records if `nsec3` is true, with `nsec3_iterations` additional iterations of the hash,
+    /// 0 by default and at most `MAX_NSEC3_ITERATIONS`, the hex encoded `nsec3_salt`, none by
+    /// default, and `nsec3_opt_out`, which leaves the delegations without DS records out of the
+    /// chain, see `Denial`
+    pub fn get_denial(&self) -> ParseResult<Denial> {
+        if !self.nsec3.unwrap_or(false) {
+            return Ok(Denial::Nsec);
+        }
+
+        let salt = match self.nsec3_salt {
+            Some(ref salt) => {
+                try!(salt.from_hex().map_err(|e| {
+                    ParseError::from(ParseErrorKind::Msg(
+                        format!("bad nsec3_salt of zone {}: {}", self.zone, e),
+                    ))
+                }))
+            }
+            None => vec![],
+        };
+
+        let iterations = self.nsec3_iterations.unwrap_or(0);
+        if iterations > MAX_NSEC3_ITERATIONS {
+            return Err(ParseErrorKind::Msg(format!(
+                "nsec3_iterations of zone {} is more than {}: {}",
+                self.zone,
+                MAX_NSEC3_ITERATIONS,
+                iterations
+            )).into());
+        }
+
+        Ok(Denial::Nsec3 {
+            iterations: iterations,
+            salt: salt,
+            opt_out: self.nsec3_opt_out.unwrap_or(false),
+        })
+    }
 }
 
 /// Key pair configuration for DNSSec keys for signing a zone
-#[derive(RustcDecodable, PartialEq, Debug)]
+#[derive(RustcDecodable, PartialEq, Debug, Clone)]
 pub struct KeyConfig {
     key_path: String,
     password: Option<String>,
@@ -325,6 +626,40 @@ impl KeyConfig {
     }
 }
 
+/// Shared key for signing requests and responses, TSIG, RFC 8945
+#[derive(RustcDecodable, PartialEq, Debug, Clone)]
+pub struct TsigKeyConfig {
+    name: String,
+    algorithm: String,
+    secret: String,
+    fudge: Option<u16>,
+}
+
+impl TsigKeyConfig {
+    /// the name of the key, as the client names it, e.g. `update-key.example.com.`
+    pub fn name(&self) -> ParseResult<Name> {
+        Name::parse(&self.name, None).map_err(|e| e.into())
+    }
+
+    /// the MAC algorithm of the key, e.g. `hmac-sha256`
+    pub fn algorithm(&self) -> ParseResult<TsigAlgorithm> {
+        TsigAlgorithm::from_str(&self.algorithm).map_err(|e| e.into())
+    }
+
+    /// the shared secret, base64 encoded in the configuration, as in the key files of BIND
+    pub fn secret(&self) -> ParseResult<Vec<u8>> {
+        self.secret.from_base64().map_err(|e| {
+            ParseErrorKind::Msg(format!("bad secret of TSIG key {}: {}", self.name, e)).into()
+        })
+    }
+
+    /// seconds of allowed difference between the clocks of the client and server, defaults to
+    /// 300
+    pub fn fudge(&self) -> u16 {
+        self.fudge.unwrap_or(DEFAULT_FUDGE)
+    }
+}
+
 /// Configuration for a TLS certificate
 #[derive(RustcDecodable, PartialEq, Debug)]
 pub struct TlsCertConfig {
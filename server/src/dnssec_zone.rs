@@ -0,0 +1,350 @@
+/*
+ * Copyright (C) 2015 Benjamin Fry <benjaminfry@me.com>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `dnssec-zone` binary for signing zones offline, and verifying signed zones
+//!
+//! The signed zone is written as a zone file, with the RDATA of every record in the generic form
+//!  of RFC 3597, which any zone file parser reads, including the one of `named`.
+//!
+//! ```text
+//! Usage: dnssec-zone sign [options] --origin=NAME (--key=KEY)... <zone-file>
+//!        dnssec-zone verify [options] --origin=NAME <zone-file>
+//!        dnssec-zone (-h | --help | --version)
+//!
+//! Options:
+//!     -q, --quiet             Disable INFO messages, WARN and ERROR will remain
+//!     -d, --debug             Turn on DEBUG messages (default is only INFO)
+//!     -h, --help              Show this message
+//!     -v, --version           Show the version of trust-dns
+//!     -o NAME, --origin=NAME  Name of the zone
+//!     -k KEY, --key=KEY       Private key to sign the zone with, as PATH:ALGORITHM, e.g.
+//!                             zsk.pk8:ED25519, the format is taken from the extension of the path
+//!     --validity=DAYS         Number of days the signatures are valid for [default: 30]
+//!     --nsec3                 Prove the non-existence of names with NSEC3 rather than NSEC
+//!     --iterations=COUNT      Additional iterations of the NSEC3 hash [default: 0]
+//!     --salt=HEX              Salt of the NSEC3 hash, in hex, by default there is none
+//!     --opt-out               Leave delegations without DS records out of the NSEC3 chain
+//!     --output=FILE           Path of the signed zone, default is the zone file with .signed
+//!     --ds=FILE               Path of the DS records for the parent zone, default is the zone
+//!                             file with .ds
+//!     --digest=TYPE           Digest of the DS records, SHA1, SHA256 or SHA384 [default: SHA256]
+//! ```
+
+extern crate chrono;
+extern crate data_encoding;
+extern crate docopt;
+#[macro_use]
+extern crate log;
+extern crate rustc_serialize;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use chrono::{Duration, Utc};
+use data_encoding::hex;
+use docopt::Docopt;
+use log::LogLevel;
+
+use trust_dns::logger;
+use trust_dns::version;
+use trust_dns::rr::{Name, Record, RecordType};
+use trust_dns::rr::dnssec::{Algorithm, DigestType, KeyPair, Signer};
+use trust_dns::serialize::binary::{BinEncoder, NameCompression};
+use trust_dns::serialize::txt::{Lexer, Parser};
+
+use trust_dns_server::authority::{Denial, ZoneSigner};
+use trust_dns_server::authority::zone_signer;
+use trust_dns_server::config::KeyConfig;
+
+// the Docopt usage string.
+//  http://docopt.org
+const USAGE: &'static str = "
+Usage: dnssec-zone sign [options] --origin=NAME (--key=KEY)... <zone-file>
+       dnssec-zone verify [options] --origin=NAME <zone-file>
+       dnssec-zone (-h | --help | --version)
+
+Options:
+    -q, --quiet             Disable INFO messages, WARN and ERROR will remain
+    -d, --debug             Turn on DEBUG messages (default is only INFO)
+    -h, --help              Show this message
+    -v, --version           Show the version of trust-dns
+    -o NAME, --origin=NAME  Name of the zone
+    -k KEY, --key=KEY       Private key to sign the zone with, as PATH:ALGORITHM, e.g.
+                            zsk.pk8:ED25519, the format is taken from the extension of the path
+    --validity=DAYS         Number of days the signatures are valid for [default: 30]
+    --nsec3                 Prove the non-existence of names with NSEC3 rather than NSEC
+    --iterations=COUNT      Additional iterations of the NSEC3 hash [default: 0]
+    --salt=HEX              Salt of the NSEC3 hash, in hex, by default there is none
+    --opt-out               Leave delegations without DS records out of the NSEC3 chain
+    --output=FILE           Path of the signed zone, default is the zone file with .signed
+    --ds=FILE               Path of the DS records for the parent zone, default is the zone
+                            file with .ds
+    --digest=TYPE           Digest of the DS records, SHA1, SHA256 or SHA384 [default: SHA256]
+";
+
+#[derive(RustcDecodable)]
+struct Args {
+    pub cmd_sign: bool,
+    pub cmd_verify: bool,
+    pub arg_zone_file: String,
+    pub flag_quiet: bool,
+    pub flag_debug: bool,
+    pub flag_help: bool,
+    pub flag_version: bool,
+    pub flag_origin: String,
+    pub flag_key: Vec<String>,
+    pub flag_validity: i64,
+    pub flag_nsec3: bool,
+    pub flag_iterations: u16,
+    pub flag_salt: Option<String>,
+    pub flag_opt_out: bool,
+    pub flag_output: Option<String>,
+    pub flag_ds: Option<String>,
+    pub flag_digest: String,
+}
+
+/// Reads all the records of the zone file, including any RRSIGs
+fn read_zone(zone_path: &Path, origin: &Name) -> Result<Vec<Record>, String> {
+    info!("reading zone file: {:?}", zone_path);
+
+    let mut file = try!(File::open(zone_path).map_err(|e| {
+        format!("error opening zone file: {:?}: {}", zone_path, e)
+    }));
+    let mut buf = String::new();
+    try!(file.read_to_string(&mut buf).map_err(|e| {
+        format!("error reading zone file: {:?}: {}", zone_path, e)
+    }));
+
+    let lexer = Lexer::new(&buf);
+    let mut parser = match zone_path.parent() {
+        Some(zone_dir) => Parser::with_include_dir(zone_dir.to_owned()),
+        None => Parser::new(),
+    };
+    let (_, rrsets) = try!(parser.parse(lexer, Some(origin.clone())).map_err(|e| {
+        format!("error parsing zone file: {:?}: {}", zone_path, e)
+    }));
+
+    Ok(
+        rrsets
+            .values()
+            .flat_map(|rrset| rrset.iter().chain(rrset.rrsigs()))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Writes the records with their RDATA in the generic form, RFC 3597 section 5
+fn write_records(path: &Path, records: &[Record]) -> Result<(), String> {
+    info!("writing {} records to: {:?}", records.len(), path);
+
+    let file = try!(File::create(path).map_err(|e| {
+        format!("error creating: {:?}: {}", path, e)
+    }));
+    let mut writer = BufWriter::new(file);
+
+    for record in records {
+        let mut rdata = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut rdata);
+            encoder.set_name_compression(NameCompression::Disabled);
+            try!(record.rdata().emit(&mut encoder).map_err(|e| {
+                format!("error encoding {} {}: {}", record.name(), record.rr_type(), e)
+            }));
+        }
+
+        try!(
+            writeln!(
+                writer,
+                "{} {} {} {} \\# {} {}",
+                record.name(),
+                record.ttl(),
+                record.dns_class(),
+                record.rr_type(),
+                rdata.len(),
+                hex::encode(&rdata)
+            ).map_err(|e| format!("error writing: {:?}: {}", path, e))
+        );
+    }
+
+    writer.flush().map_err(
+        |e| format!("error writing: {:?}: {}", path, e),
+    )
+}
+
+/// Loads a key given as PATH:ALGORITHM, see `load_key` of `named`
+fn load_key(origin: &Name, key: &str, validity: Duration) -> Result<Signer, String> {
+    let mut parts = key.rsplitn(2, ':');
+    let (algorithm, key_path) = match (parts.next(), parts.next()) {
+        (Some(algorithm), Some(key_path)) => (algorithm, key_path),
+        _ => return Err(format!("expected PATH:ALGORITHM for the key: {}", key)),
+    };
+    let algorithm: Algorithm = try!(algorithm.parse().map_err(|e| {
+        format!("bad algorithm of key {}: {}", key, e)
+    }));
+
+    let key_config = KeyConfig::new(
+        key_path.to_string(),
+        None,
+        algorithm,
+        origin.to_string(),
+        true,
+        false,
+    );
+    let format = try!(key_config.format().map_err(
+        |e| format!("bad key format: {}", e),
+    ));
+
+    info!("reading key: {:?}", key_config.key_path());
+    let mut file = try!(File::open(key_config.key_path()).map_err(|e| {
+        format!("error opening private key file: {:?}: {}", key_path, e)
+    }));
+    let mut key_bytes = Vec::with_capacity(256);
+    try!(file.read_to_end(&mut key_bytes).map_err(|e| {
+        format!("could not read key from: {:?}: {}", key_path, e)
+    }));
+
+    let key: KeyPair = try!(format.decode_key(&key_bytes, None, algorithm).map_err(|e| {
+        format!("could not decode key: {:?}: {}", key_path, e)
+    }));
+    let dnskey = try!(key.to_dnskey(algorithm).map_err(|e| {
+        format!("error converting to dnskey: {}", e)
+    }));
+
+    Ok(Signer::dnssec(dnskey, key, origin.clone(), validity))
+}
+
+fn sign(args: &Args, origin: Name, zone_path: &Path) -> Result<(), String> {
+    let records = try!(read_zone(zone_path, &origin));
+
+    let denial = if args.flag_nsec3 {
+        let salt = match args.flag_salt {
+            Some(ref salt) => {
+                try!(hex::decode(salt.to_uppercase().as_bytes()).map_err(|e| {
+                    format!("bad salt: {}: {:?}", salt, e)
+                }))
+            }
+            None => vec![],
+        };
+
+        Denial::Nsec3 {
+            iterations: args.flag_iterations,
+            salt: salt,
+            opt_out: args.flag_opt_out,
+        }
+    } else {
+        Denial::Nsec
+    };
+
+    let digest_type = match args.flag_digest.to_uppercase().as_str() {
+        "SHA1" => DigestType::SHA1,
+        "SHA256" => DigestType::SHA256,
+        "SHA384" => DigestType::SHA384,
+        digest => return Err(format!("unsupported digest for DS records: {}", digest)),
+    };
+
+    let validity = Duration::days(args.flag_validity);
+    let mut signers = Vec::with_capacity(args.flag_key.len());
+    for key in &args.flag_key {
+        signers.push(try!(load_key(&origin, key, validity)));
+    }
+
+    let zone_signer = ZoneSigner::new(origin.clone(), signers, denial);
+    info!("signing zone: {} with {:?}", origin, zone_signer.denial());
+    let signed = try!(zone_signer.sign(&records, Utc::now()).map_err(|e| {
+        format!("failed to sign zone: {}: {}", origin, e)
+    }));
+
+    // the DS records have the TTL of the DNSKEYs they are for
+    let ttl = signed
+        .iter()
+        .find(|record| record.rr_type() == RecordType::DNSKEY)
+        .map_or(0, |record| record.ttl());
+    let ds_records = try!(zone_signer.ds_records(digest_type, ttl).map_err(|e| {
+        format!("failed to generate DS records: {}", e)
+    }));
+
+    let output_path = args.flag_output.as_ref().map_or_else(
+        || with_added_extension(zone_path, "signed"),
+        PathBuf::from,
+    );
+    let ds_path = args.flag_ds.as_ref().map_or_else(
+        || with_added_extension(zone_path, "ds"),
+        PathBuf::from,
+    );
+
+    try!(write_records(&output_path, &signed));
+    try!(write_records(&ds_path, &ds_records));
+    info!("signed zone: {} to: {:?}", origin, output_path);
+    Ok(())
+}
+
+fn verify(origin: Name, zone_path: &Path) -> Result<(), String> {
+    let records = try!(read_zone(zone_path, &origin));
+
+    info!("verifying zone: {}", origin);
+    let problems = zone_signer::verify_zone(&origin, &records, Utc::now().timestamp() as u32);
+    for problem in &problems {
+        error!("{}", problem);
+    }
+
+    if problems.is_empty() {
+        info!("zone is valid: {}", origin);
+        Ok(())
+    } else {
+        Err(format!("{} problem(s) found in: {:?}", problems.len(), zone_path))
+    }
+}
+
+/// e.g. `example.com.zone` to `example.com.zone.signed`
+fn with_added_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// Main method for the dnssec-zone binary, the exit status is non-zero on any failure
+pub fn main() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.help(true).version(Some(version().into())).decode())
+        .unwrap_or_else(|e| e.exit());
+
+    if args.flag_quiet {
+        logger::TrustDnsLogger::enable_logging(LogLevel::Warn);
+    } else if args.flag_debug {
+        logger::TrustDnsLogger::enable_logging(LogLevel::Debug);
+    } else {
+        logger::TrustDnsLogger::enable_logging(LogLevel::Info);
+    }
+
+    let zone_path = Path::new(&args.arg_zone_file);
+    let result = Name::parse(&args.flag_origin, Some(&Name::root()))
+        .map_err(|e| format!("bad origin: {}: {}", args.flag_origin, e))
+        .and_then(|origin| if args.cmd_sign {
+            sign(&args, origin, zone_path)
+        } else {
+            verify(origin, zone_path)
+        });
+
+    if let Err(e) = result {
+        error!("{}", e);
+        process::exit(1);
+    }
+}
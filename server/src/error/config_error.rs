@@ -53,9 +53,9 @@ error_chain! {
     // the same as `quick_error!`, but the `from()` and `cause()`
     // syntax is not supported.
     errors {
-      VecParserError(vec: Vec<ParserError>) {
+      ParserErrors(errors: Vec<String>) {
         description("parser errors")
-        display("parser errors: {:?}", vec)
+        display("parser errors: {}", errors.join(", "))
       }
     }
 }
@@ -21,15 +21,20 @@
 
 mod config_error;
 mod persistence_error;
+mod transfer_error;
 
 pub use self::config_error::Error as ConfigError;
 pub use self::persistence_error::Error as PersistenceError;
+pub use self::transfer_error::Error as TransferError;
 
 pub use self::config_error::ErrorKind as ConfigErrorKind;
 pub use self::persistence_error::ErrorKind as PersistenceErrorKind;
+pub use self::transfer_error::ErrorKind as TransferErrorKind;
 
 pub use self::config_error::ChainErr as ConfigChainErr;
 pub use self::persistence_error::ChainErr as PersistenceChainErr;
+pub use self::transfer_error::ChainErr as TransferChainErr;
 
 pub use self::config_error::Result as ConfigResult;
 pub use self::persistence_error::Result as PersistenceResult;
+pub use self::transfer_error::Result as TransferResult;
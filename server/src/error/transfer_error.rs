@@ -0,0 +1,66 @@
+// Copyright 2015-2017 Benjamin Fry
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+use trust_dns::rr::{Name, RecordType};
+use trust_dns_proto::error::*;
+
+error_chain! {
+  // The type defined for this error. These are the conventional
+  // and recommended names, but they can be arbitrarily chosen.
+  types {
+    Error, ErrorKind, ChainErr, Result;
+  }
+
+  // Automatic conversions between this error chain and other
+  // error chains. In this case, it will e.g. generate an
+  // `ErrorKind` variant called `Dist` which in turn contains
+  // the `rustup_dist::ErrorKind`, with conversions from
+  // `rustup_dist::Error`.
+  //
+  // This section can be empty.
+  links {
+    ProtoError, ProtoErrorKind, ProtoError;
+  }
+
+  // Automatic conversions between this error chain and other
+  // error types not defined by the `error_chain!`. These will be
+  // boxed as the error cause and wrapped in a new error with,
+  // in this case, the `ErrorKind::Temp` variant.
+  //
+  // This section can be empty.
+  foreign_links {
+  }
+
+  // Define additional `ErrorKind` variants. The syntax here is
+  // the same as `quick_error!`, but the `from()` and `cause()`
+  // syntax is not supported.
+  errors {
+    MissingSoa(origin: Name) {
+      description("transfer is missing the SOA")
+      display("transfer is missing the SOA for: {}", origin)
+    }
+
+    OutOfZone(name: Name, origin: Name) {
+      description("transfer contains records outside of the zone")
+      display("transfer contains record {} outside of the zone: {}", name, origin)
+    }
+
+    MissingZonemd(origin: Name) {
+      description("transfer is missing a usable ZONEMD")
+      display("transfer is missing a supported ZONEMD for serial of: {}", origin)
+    }
+
+    ZonemdMismatch(origin: Name, serial: u32) {
+      description("zone digest does not match ZONEMD")
+      display("zone digest does not match ZONEMD for: {} serial: {}", origin, serial)
+    }
+
+    MissingSignature(name: Name, record_type: RecordType) {
+      description("no valid RRSIG for record set")
+      display("no valid RRSIG for record set: {} {}", name, record_type)
+    }
+  }
+}
@@ -28,12 +28,18 @@
 //! * New features for securing public information
 
 extern crate chrono;
+#[cfg(feature = "dnssec")]
+extern crate data_encoding;
 #[macro_use]
 extern crate error_chain;
 #[macro_use]
 extern crate futures;
 #[macro_use]
 extern crate log;
+extern crate lru_cache;
+extern crate num_cpus;
+extern crate rand;
+extern crate regex;
 extern crate rusqlite;
 extern crate rustc_serialize;
 extern crate time;
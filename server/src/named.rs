@@ -29,38 +29,58 @@
 //!    -z DIR, --zonedir=DIR   Path to the root directory for all zone files, see also config toml
 //!    -p PORT, --port=PORT    Override the listening port
 //!    --tls-port=PORT         Override the listening port for TLS connections
+//!    --check                 Validate the configuration, zones, keys and certificates then exit,
+//!                            the exit status is non-zero if there were any problems
 //! ```
 
 extern crate chrono;
 extern crate docopt;
+extern crate futures;
 #[macro_use]
 extern crate log;
 extern crate rustc_serialize;
+extern crate tokio_core;
 extern crate trust_dns;
 extern crate trust_dns_server;
 
 #[cfg(feature = "tls")]
 extern crate trust_dns_openssl;
 
-use std::fs::File;
-use std::collections::BTreeMap;
-use std::net::{Ipv4Addr, IpAddr, SocketAddr, TcpListener, ToSocketAddrs, UdpSocket};
-use std::path::{Path, PathBuf};
-use std::io::Read;
+use std::cmp;
+use std::fs::{self, File};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::{Ipv4Addr, IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::{Component, Path, PathBuf};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time;
 
 use chrono::Duration;
+#[cfg(feature = "dnssec")]
+use chrono::Utc;
 use docopt::Docopt;
+use futures::{Future, Stream};
 use log::LogLevel;
+use tokio_core::reactor::{Handle, Interval};
 
 use trust_dns::error::ParseResult;
 use trust_dns::logger;
 use trust_dns::version;
 use trust_dns::serialize::txt::{Lexer, Parser};
 use trust_dns::rr::Name;
-use trust_dns::rr::dnssec::{Algorithm, KeyPair, Signer};
+use trust_dns::rr::dnssec::{Algorithm, KeyPair, Signer, TSigner};
 
-use trust_dns_server::authority::{Authority, Catalog, Journal, ZoneType};
-use trust_dns_server::config::{Config, KeyConfig, TlsCertConfig, ZoneConfig};
+use trust_dns_server::authority::{Authority, Catalog, Journal, Zone, ZoneHealthStatus, ZoneType};
+#[cfg(feature = "dnssec")]
+use trust_dns_server::authority::{SigningAuthority, ZoneSigner};
+#[cfg(feature = "sqlite")]
+use trust_dns_server::authority::{SqliteStore, ZoneStore};
+use trust_dns_server::config::{Config, KeyConfig, TlsCertConfig, TsigKeyConfig, ZoneConfig};
+#[cfg(feature = "sqlite")]
+use trust_dns_server::error::PersistenceError;
 use trust_dns_server::server::ServerFuture;
 
 #[cfg(feature = "tls")]
@@ -82,8 +102,23 @@ Options:
     -z DIR, --zonedir=DIR   Path to the root directory for all zone files, see also config toml
     -p PORT, --port=PORT    Override the listening port
     --tls-port=PORT         Override the listening port for TLS connections
+    --check                 Validate the configuration, zones, keys and certificates then exit,
+                            the exit status is non-zero if there were any problems
 ";
 
+/// Number of threads on which the zones are loaded at startup
+const ZONE_LOAD_THREADS: usize = 4;
+
+/// Seconds a connection to the control channel may be idle before it is closed
+const CONTROL_TIMEOUT_SECS: u64 = 60;
+
+/// Seconds between the checks of which secondary zones are due to be refreshed
+const SECONDARY_REFRESH_CHECK_SECS: u64 = 1;
+
+/// Seconds between the checks of which inline signed zones are due to be signed again
+#[cfg(feature = "dnssec")]
+const RESIGN_CHECK_SECS: u64 = 60;
+
 #[derive(RustcDecodable)]
 struct Args {
     pub flag_quiet: bool,
@@ -94,10 +129,12 @@ struct Args {
     pub flag_zonedir: Option<String>,
     pub flag_port: Option<u16>,
     pub flag_tls_port: Option<u16>,
+    pub flag_check: bool,
 }
 
 fn parse_file(
     file: File,
+    include_dir: &Path,
     origin: Option<Name>,
     zone_type: ZoneType,
     allow_update: bool,
@@ -110,7 +147,9 @@ fn parse_file(
     //  keep the usage down. and be a custom lexer...
     try!(file.read_to_string(&mut buf));
     let lexer = Lexer::new(&buf);
-    let (origin, records) = try!(Parser::new().parse(lexer, origin));
+    let (origin, records) = try!(
+        Parser::with_include_dir(include_dir.to_owned()).parse(lexer, origin)
+    );
 
     Ok(Authority::new(
         origin,
@@ -121,16 +160,57 @@ fn parse_file(
     ))
 }
 
-fn load_zone(zone_dir: &Path, zone_config: &ZoneConfig) -> Result<Authority, String> {
+/// Loads the zone, see `load_authority`, a zone which is `inline_signing` is then signed as it
+///  is served, see `SigningAuthority`
+fn load_zone(
+    zone_dir: &Path,
+    zone_config: &ZoneConfig,
+    dry_run: bool,
+    served_serial: Option<u32>,
+) -> Result<Zone, String> {
+    let authority = try!(load_authority(zone_dir, zone_config, dry_run, served_serial));
+    if !zone_config.is_inline_signing() {
+        return Ok(authority.into());
+    }
+
+    signing_zone(zone_dir, zone_config, authority)
+}
+
+/// Loads the zone, its journal and keys
+///
+/// When `dry_run` is true nothing is written to disk, i.e. a new journal is not created for zones
+///  which allow updates. `served_serial` is the serial of the version of the zone which is being
+///  served, when the zone is reloaded, see `ZoneConfig::is_auto_serial`.
+///
+/// A secondary zone is journaled as well, its journal holds the zone as it was last transferred
+///  from the primary. Without a journal or zone file it starts out empty, and is served once it
+///  has been transferred, see `Catalog::refresh_secondaries`.
+///
+/// A zone which is `inline_signing` is not signed here, see `load_zone`.
+fn load_authority(
+    zone_dir: &Path,
+    zone_config: &ZoneConfig,
+    dry_run: bool,
+    served_serial: Option<u32>,
+) -> Result<Authority, String> {
     debug!("loading zone with config: {:#?}", zone_config);
 
-    let zone_name: Name = zone_config.get_zone().expect("bad zone name");
+    let zone_name: Name = try!(zone_config.get_zone().map_err(
+        |e| format!("bad zone name: {}", e),
+    ));
     let zone_path: PathBuf = zone_dir.to_owned().join(zone_config.get_file());
     let journal_path: PathBuf = zone_path.with_extension("jrnl");
-    let original_key_path: PathBuf = zone_path.with_extension("key");
 
     // load the zone
-    let mut authority = if zone_config.is_update_allowed() && journal_path.exists() {
+    let is_secondary = zone_config.get_zone_type() == ZoneType::Slave;
+    let is_journaled = zone_config.is_update_allowed() || is_secondary;
+    if zone_config.is_sqlite_store() && !is_journaled {
+        warn!("zone {} is not updated, its sqlite_store is ignored", zone_name);
+    }
+
+    let mut authority = if is_journaled && zone_config.is_sqlite_store() {
+        try!(load_sqlite_zone(zone_dir, &zone_path, &zone_name, zone_config, dry_run))
+    } else if is_journaled && journal_path.exists() {
         info!("recovering zone from journal: {:?}", journal_path);
         let journal = try!(Journal::from_file(&journal_path).map_err(|e| {
             format!("error opening journal: {:?}: {}", journal_path, e)
@@ -152,24 +232,10 @@ fn load_zone(zone_dir: &Path, zone_config: &ZoneConfig) -> Result<Authority, Str
 
         authority
     } else if zone_path.exists() {
-        info!("loading zone file: {:?}", zone_path);
-
-        let zone_file = try!(File::open(&zone_path).map_err(|e| {
-            format!("error opening zone file: {:?}: {}", zone_path, e)
-        }));
+        let mut authority = try!(read_zone_file(zone_dir, &zone_path, &zone_name, zone_config));
 
-        let mut authority = try!(
-            parse_file(
-                zone_file,
-                Some(zone_name.clone()),
-                zone_config.get_zone_type(),
-                zone_config.is_update_allowed(),
-                zone_config.is_dnssec_enabled(),
-            ).map_err(|e| format!("error reading zone: {:?}: {}", zone_path, e))
-        );
-
-        // if dynamic update is enabled, enable the journal
-        if zone_config.is_update_allowed() {
+        // if dynamic update is enabled, or this is a secondary, enable the journal
+        if is_journaled && !dry_run {
             info!("enabling journal: {:?}", journal_path);
             let journal = try!(Journal::from_file(&journal_path).map_err(|e| {
                 format!("error creating journal {:?}: {}", journal_path, e)
@@ -184,62 +250,601 @@ fn load_zone(zone_dir: &Path, zone_config: &ZoneConfig) -> Result<Authority, Str
         }
 
         info!("zone file loaded: {}", zone_name);
+        authority
+    } else if is_secondary {
+        info!("zone {} will be transferred from its primary", zone_name);
+        let mut authority = Authority::new(
+            zone_name.clone(),
+            BTreeMap::new(),
+            zone_config.get_zone_type(),
+            zone_config.is_update_allowed(),
+            zone_config.is_dnssec_enabled(),
+        );
+
+        if !dry_run {
+            info!("enabling journal: {:?}", journal_path);
+            let journal = try!(Journal::from_file(&journal_path).map_err(|e| {
+                format!("error creating journal {:?}: {}", journal_path, e)
+            }));
+            authority.set_journal(journal);
+        }
+
         authority
     } else {
         return Err(format!("no zone file defined at: {:?}", zone_path));
     };
 
+    try!(authority.check_illegal_data(zone_config.get_illegal_data()).map_err(|e| {
+        format!("illegal data in zone: {}: {}", zone_name, e)
+    }));
+
+    // a reloaded zone needs a later serial than the version being served, for secondaries to
+    //  notice the change
+    if let Some(served_serial) = served_serial {
+        if zone_config.is_auto_serial() {
+            authority.advance_serial(served_serial);
+        } else if (authority.serial().wrapping_sub(served_serial) as i32) <= 0 {
+            warn!(
+                "zone {} reloaded with a serial not after {}, secondaries will miss the change",
+                zone_name,
+                served_serial
+            );
+        }
+    }
+
+    if let Some(ixfr_history_size) = zone_config.get_ixfr_history_size() {
+        authority.set_ixfr_history_size(ixfr_history_size);
+    }
+
+    let update_tsig_keys = try!(zone_config.get_update_tsig_keys().map_err(|e| {
+        format!("bad TSIG key name for updates of zone {}: {}", zone_name, e)
+    }));
+    for key_name in update_tsig_keys {
+        info!("allowing updates of zone {} with TSIG key: {}", zone_name, key_name);
+        authority.add_update_tsig_key(key_name);
+    }
+
     // load any keys for the Zone, if it is a dynamic update zone, then keys are required
-    if zone_config.is_dnssec_enabled() {
-        // old backward compatible logic, TODO: deprecated
-        if zone_config.get_keys().is_empty() {
-            // original RSA key construction
-            let key_config = KeyConfig::new(
-                original_key_path.to_string_lossy().to_string(),
+    if zone_config.is_dnssec_enabled() && !zone_config.is_inline_signing() {
+        for (key_config, signer) in try!(load_zone_keys(&zone_path, &zone_name, zone_config)) {
+            info!(
+                "adding key to zone: {:?}, is_zsk: {}, is_auth: {}",
+                key_config.key_path(),
+                key_config.is_zone_signing_key(),
+                key_config.is_zone_update_auth()
+            );
+            try!(authority.add_secure_key(signer).map_err(|e| {
+                format!("failed to add key to authority: {}", e)
+            }));
+        }
+
+        let denial = try!(zone_config.get_denial().map_err(|e| {
+            format!("bad denial of existence for zone {}: {}", zone_name, e)
+        }));
+        authority.set_denial(denial);
+
+        info!("signing zone: {}", zone_name);
+        try!(authority.secure_zone().map_err(
+            |e| format!("failed to sign zone: {}", e),
+        ));
+    }
+
+    info!("zone successfully loaded: {}", zone_name);
+    Ok(authority)
+}
+
+/// Loads the keys of the zone, those of `ZoneConfig::get_keys`, or the RSA key at the zone file
+///  with the extension `key` if there are none
+fn load_zone_keys(
+    zone_path: &Path,
+    zone_name: &Name,
+    zone_config: &ZoneConfig,
+) -> Result<Vec<(KeyConfig, Signer)>, String> {
+    // old backward compatible logic, TODO: deprecated
+    let key_configs = if zone_config.get_keys().is_empty() {
+        // original RSA key construction
+        vec![
+            KeyConfig::new(
+                zone_path.with_extension("key").to_string_lossy().to_string(),
                 None,
                 Algorithm::RSASHA256,
-                zone_name.clone().to_string(),
+                zone_name.to_string(),
                 true,
                 true,
-            );
-            let signer = try!(load_key(zone_name, &key_config).map_err(|e| {
+            ),
+        ]
+    } else {
+        zone_config.get_keys().to_vec()
+    };
+
+    key_configs
+        .into_iter()
+        .map(|key_config| -> Result<(KeyConfig, Signer), String> {
+            let signer = try!(load_key(zone_name.clone(), &key_config).map_err(|e| {
                 format!("failed to load key: {:?} msg: {}", key_config.key_path(), e)
             }));
+            Ok((key_config, signer))
+        })
+        .collect()
+}
+
+/// Signs the zone with its zone signing keys, to be signed again as it changes, see
+///  `SigningAuthority`
+#[cfg(feature = "dnssec")]
+fn signing_zone(
+    zone_dir: &Path,
+    zone_config: &ZoneConfig,
+    authority: Authority,
+) -> Result<Zone, String> {
+    let zone_name = authority.origin().clone();
+    if !zone_config.is_dnssec_enabled() {
+        return Err(format!("inline_signing of zone {} requires enable_dnssec", zone_name));
+    }
+    if zone_config.get_zone_type() == ZoneType::Slave {
+        return Err(format!("secondary zone {} can not be inline_signing", zone_name));
+    }
+
+    let zone_path: PathBuf = zone_dir.to_owned().join(zone_config.get_file());
+    let mut signers = Vec::new();
+    for (key_config, signer) in try!(load_zone_keys(&zone_path, &zone_name, zone_config)) {
+        if key_config.is_zone_signing_key() {
+            info!("signing zone with key: {:?}", key_config.key_path());
+            signers.push(signer);
+        }
+    }
+    if signers.is_empty() {
+        return Err(format!("zone {} has no zone signing keys", zone_name));
+    }
+
+    let denial = try!(zone_config.get_denial().map_err(|e| {
+        format!("bad denial of existence for zone {}: {}", zone_name, e)
+    }));
+
+    info!("signing zone inline: {}", zone_name);
+    let signer = ZoneSigner::new(zone_name, signers, denial);
+    let signing = try!(SigningAuthority::new(authority, signer).map_err(
+        |e| format!("failed to sign zone: {}", e),
+    ));
+    Ok(signing.into())
+}
+
+#[cfg(not(feature = "dnssec"))]
+fn signing_zone(
+    _zone_dir: &Path,
+    _zone_config: &ZoneConfig,
+    _authority: Authority,
+) -> Result<Zone, String> {
+    Err("inline_signing requires the dnssec feature".to_string())
+}
+
+/// Reads the zone from the zone file
+fn read_zone_file(
+    zone_dir: &Path,
+    zone_path: &Path,
+    zone_name: &Name,
+    zone_config: &ZoneConfig,
+) -> Result<Authority, String> {
+    info!("loading zone file: {:?}", zone_path);
+
+    let zone_file = try!(File::open(zone_path).map_err(|e| {
+        format!("error opening zone file: {:?}: {}", zone_path, e)
+    }));
+
+    parse_file(
+        zone_file,
+        zone_dir,
+        Some(zone_name.clone()),
+        zone_config.get_zone_type(),
+        zone_config.is_update_allowed(),
+        zone_config.is_dnssec_enabled(),
+    ).map_err(|e| format!("error reading zone: {:?}: {}", zone_path, e))
+}
+
+/// Loads the zone from its SQLite store, see `ZoneConfig::is_sqlite_store`, a new store is filled
+///  from the zone file
+///
+/// The store is left as the zone was, it is not reloaded from the zone file once it holds the zone.
+///  When `dry_run` is true and there is no store yet, none is created.
+#[cfg(feature = "sqlite")]
+fn load_sqlite_zone(
+    zone_dir: &Path,
+    zone_path: &Path,
+    zone_name: &Name,
+    zone_config: &ZoneConfig,
+    dry_run: bool,
+) -> Result<Authority, String> {
+    let is_secondary = zone_config.get_zone_type() == ZoneType::Slave;
+    let store_path: PathBuf = zone_path.with_extension("sqlite");
+
+    if dry_run && !store_path.exists() {
+        return if zone_path.exists() {
+            read_zone_file(zone_dir, zone_path, zone_name, zone_config)
+        } else if is_secondary {
+            Ok(Authority::new(
+                zone_name.clone(),
+                BTreeMap::new(),
+                zone_config.get_zone_type(),
+                zone_config.is_update_allowed(),
+                zone_config.is_dnssec_enabled(),
+            ))
+        } else {
+            Err(format!("no zone file defined at: {:?}", zone_path))
+        };
+    }
+
+    info!("opening store: {:?}", store_path);
+    let mut store = try!(SqliteStore::from_file(&store_path).map_err(|e| {
+        format!("error opening store: {:?}: {}", store_path, e)
+    }));
+
+    if !store.is_empty() {
+        info!("recovered zone {} from store: {:?}", zone_name, store_path);
+    } else if zone_path.exists() {
+        let authority = try!(read_zone_file(zone_dir, zone_path, zone_name, zone_config));
+        let write_error =
+            |e: PersistenceError| format!("error writing store: {:?}: {}", store_path, e);
+        try!(store.begin().map_err(&write_error));
+        for (key, rrset) in authority.records().to_map() {
+            try!(store.upsert(key, rrset).map_err(&write_error));
+        }
+        try!(store.commit().map_err(&write_error));
+        info!("zone {} stored in: {:?}", zone_name, store_path);
+    } else if is_secondary {
+        info!("zone {} will be transferred from its primary", zone_name);
+    } else {
+        return Err(format!("no zone file defined at: {:?}", zone_path));
+    }
+
+    Ok(Authority::with_store(
+        zone_name.clone(),
+        Box::new(store),
+        zone_config.get_zone_type(),
+        zone_config.is_update_allowed(),
+        zone_config.is_dnssec_enabled(),
+    ))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn load_sqlite_zone(
+    _zone_dir: &Path,
+    _zone_path: &Path,
+    _zone_name: &Name,
+    _zone_config: &ZoneConfig,
+    _dry_run: bool,
+) -> Result<Authority, String> {
+    Err("sqlite_store requires the sqlite feature".to_string())
+}
+
+/// Loads the zones on a few threads at once, so that a large zone, or one which is slow to sign,
+///  does not hold up the others, the results are in the order of the zones
+fn load_zones(zone_dir: &Path, zones: &[ZoneConfig]) -> Vec<Result<Zone, String>> {
+    let queue = Arc::new(Mutex::new(
+        zones.iter().cloned().enumerate().collect::<VecDeque<_>>(),
+    ));
+    let (sender, receiver) = mpsc::channel();
+
+    let threads = (0..cmp::min(ZONE_LOAD_THREADS, zones.len()))
+        .map(|_| {
+            let queue = queue.clone();
+            let sender = sender.clone();
+            let zone_dir = zone_dir.to_owned();
+
+            thread::spawn(move || loop {
+                let next = queue.lock().expect("zone queue poisoned").pop_front();
+                match next {
+                    Some((idx, zone)) => {
+                        let _ = sender.send((idx, load_zone(&zone_dir, &zone, false, None)));
+                    }
+                    None => return,
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(sender);
+
+    let mut loaded: Vec<Option<Result<Zone, String>>> = zones.iter().map(|_| None).collect();
+    for (idx, result) in receiver.iter() {
+        loaded[idx] = Some(result);
+    }
+    for thread in threads {
+        if thread.join().is_err() {
+            error!("a thread loading zones panicked");
+        }
+    }
+
+    loaded
+        .into_iter()
+        .map(|result| {
+            result.unwrap_or_else(|| Err("loading of the zone panicked".to_string()))
+        })
+        .collect()
+}
+
+/// Checks the zone file for modifications every `interval`, and reloads the zone when it was
+///  modified
+///
+/// The zone is loaded on a thread of its own, until it replaces the zone in the catalog the
+///  previous version continues to be served. A version which fails to load is logged, and
+///  marked failed in the catalog, see `reload_zone`, and not retried until the file is modified
+///  again.
+fn watch_zone(
+    zone_dir: &Path,
+    zone_config: &ZoneConfig,
+    interval: time::Duration,
+    catalog: Arc<Catalog>,
+    handle: &Handle,
+) -> Result<(), String> {
+    let zone_name = try!(zone_config.get_zone().map_err(
+        |e| format!("bad zone name: {}", e),
+    ));
+    let zone_path = zone_dir.join(zone_config.get_file());
+    let interval = try!(Interval::new(interval, handle).map_err(|e| {
+        format!("could not create reload interval: {}", e)
+    }));
+
+    let zone_dir = zone_dir.to_owned();
+    let zone_config = zone_config.clone();
+    let mut modified = modified_time(&zone_path);
+    let reloading = Arc::new(AtomicBool::new(false));
+    let stopped_name = zone_name.clone();
+
+    handle.spawn(
+        interval
+            .for_each(move |_| {
+                let current = modified_time(&zone_path);
+                // while a reload is in progress the modification is picked up on the next tick
+                if current == modified || reloading.swap(true, Ordering::SeqCst) {
+                    return Ok(());
+                }
+                modified = current;
+
+                info!("zone file modified, reloading: {:?}", zone_path);
+                let zone_name = zone_name.clone();
+                let zone_dir = zone_dir.clone();
+                let zone_config = zone_config.clone();
+                let catalog = catalog.clone();
+                let reloading = reloading.clone();
+                thread::spawn(move || {
+                    if let Err(e) = reload_zone(&zone_dir, &zone_config, &catalog) {
+                        error!("could not reload zone {}: {}", zone_name, e);
+                    }
+                    reloading.store(false, Ordering::SeqCst);
+                });
+
+                Ok(())
+            })
+            .map_err(move |e| error!("reloading of zone {} stopped: {}", stopped_name, e)),
+    );
+
+    Ok(())
+}
+
+/// Loads the zone file again, and replaces the version of the zone which is being served
+///
+/// A zone which fails to reload is marked failed in the catalog, see `Catalog::mark_failed`, so
+///  that the failure shows in its health and statistics while the previous version is served.
+fn reload_zone(zone_dir: &Path, zone_config: &ZoneConfig, catalog: &Catalog) -> Result<(), String> {
+    let zone_name = try!(zone_config.get_zone().map_err(
+        |e| format!("bad zone name: {}", e),
+    ));
+    let served_serial = try!(catalog.serial(&zone_name).ok_or_else(
+        || "zone is not being served".to_string(),
+    ));
+
+    // zones which allow updates are not reloaded, there is no journal to write
+    let reloaded = load_authority(zone_dir, zone_config, true, Some(served_serial))
+        .and_then(|authority| {
             info!(
-                "adding key to zone: {:?}, is_zsk: {}, is_auth: {}",
-                key_config.key_path(),
-                key_config.is_zone_signing_key(),
-                key_config.is_zone_update_auth()
-            );
-            authority.add_secure_key(signer).expect(
-                "failed to add key to authority",
+                "reloaded zone {}, serial {} replaces {}",
+                zone_name,
+                authority.serial(),
+                served_serial
             );
-        } else {
-            for key_config in zone_config.get_keys() {
-                let signer = try!(load_key(zone_name.clone(), &key_config).map_err(|e| {
-                    format!("failed to load key: {:?} msg: {}", key_config.key_path(), e)
-                }));
-                info!(
-                    "adding key to zone: {:?}, is_zsk: {}, is_auth: {}",
-                    key_config.key_path(),
-                    key_config.is_zone_signing_key(),
-                    key_config.is_zone_update_auth()
-                );
-                authority.add_secure_key(signer).expect(
-                    "failed to add key to authority",
-                );
+
+            if !catalog.replace(&zone_name, authority) {
+                return Err("the reloaded zone could not replace the one being served".to_string());
             }
+            Ok(())
+        });
+
+    if let Err(ref error) = reloaded {
+        catalog.mark_failed(zone_name.clone(), error.clone());
+    }
+    reloaded
+}
+
+/// Returns true if the path is relative, and does not leave the directory it is relative to
+fn is_within_dir(path: &Path) -> bool {
+    path.components().all(|component| match component {
+        Component::Normal(..) |
+        Component::CurDir => true,
+        _ => false,
+    })
+}
+
+fn modified_time(path: &Path) -> Option<time::SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Accepts connections to the control channel, on a thread of its own, each connection is then
+///  handled on a thread of its own, see `control_command`
+///
+/// `zones` are the configurations of the zones being served, by name, zones added over the
+///  channel are added to them.
+fn serve_control(
+    listener: TcpListener,
+    zone_dir: PathBuf,
+    zones: HashMap<Name, ZoneConfig>,
+    catalog: Arc<Catalog>,
+) {
+    let zone_dir = Arc::new(zone_dir);
+    let zones = Arc::new(Mutex::new(zones));
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("control connection failed: {}", e);
+                    continue;
+                }
+            };
+
+            let zone_dir = zone_dir.clone();
+            let zones = zones.clone();
+            let catalog = catalog.clone();
+            thread::spawn(move || {
+                if let Err(e) = handle_control(stream, &zone_dir, &zones, &catalog) {
+                    warn!("control connection failed: {}", e);
+                }
+            });
         }
+    });
+}
 
-        info!("signing zone: {}", zone_config.get_zone().unwrap());
-        authority.secure_zone().expect("failed to sign zone");
+/// Answers the commands of a connection to the control channel, until it is closed, or no command
+///  is received within `CONTROL_TIMEOUT_SECS`
+///
+/// The commands of all the connections are performed one at a time, so they are applied in order.
+fn handle_control(
+    stream: TcpStream,
+    zone_dir: &Path,
+    zones: &Mutex<HashMap<Name, ZoneConfig>>,
+    catalog: &Catalog,
+) -> io::Result<()> {
+    let peer = try!(stream.peer_addr());
+    try!(stream.set_read_timeout(
+        Some(time::Duration::from_secs(CONTROL_TIMEOUT_SECS)),
+    ));
+    let mut writer = try!(stream.try_clone());
+
+    for line in BufReader::new(stream).lines() {
+        let line = try!(line);
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        info!("control command from {}: {}", peer, line);
+        let answer = match control_command(
+            &line,
+            zone_dir,
+            &mut *zones.lock().unwrap(), // poison errors should panic
+            catalog,
+        ) {
+            Ok(answer) => format!("ok {}", answer),
+            Err(e) => {
+                warn!("control command failed: {}: {}", line, e);
+                format!("error {}", e)
+            }
+        };
+        try!(writeln!(writer, "{}", answer.trim_right()));
     }
 
-    info!(
-        "zone successfully loaded: {}",
-        zone_config.get_zone().unwrap()
-    );
-    Ok(authority)
+    Ok(())
+}
+
+/// Performs a command of the control channel, one of
+///
+/// ```text
+/// zones                      lists the zones being served
+/// health                     the health of each zone, see `Catalog::health`
+/// add ZONE FILE [ZONE_TYPE]  loads the zone file, which must be within the zone directory,
+///                            and serves the zone, the type is Master by default
+/// reload ZONE                loads the zone file again, and replaces the zone being served
+/// remove ZONE                stops serving the zone
+/// ```
+///
+/// Queries which are being answered are answered from the zone as it was when they arrived.
+fn control_command(
+    line: &str,
+    zone_dir: &Path,
+    zones: &mut HashMap<Name, ZoneConfig>,
+    catalog: &Catalog,
+) -> Result<String, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or("");
+    let mut next = |what: &str| {
+        words.next().ok_or_else(
+            || format!("{} requires a {}", command, what),
+        )
+    };
+
+    match command {
+        "zones" => {
+            let mut names = catalog.zone_names();
+            names.sort();
+            Ok(names.iter().map(|name| name.to_string()).collect::<Vec<_>>().join(" "))
+        }
+        "health" => {
+            let mut health = catalog.health();
+            health.sort_by(|a, b| a.zone().cmp(b.zone()));
+            Ok(health.iter().map(|health| health.to_string()).collect::<Vec<_>>().join(", "))
+        }
+        "add" => {
+            let zone = try!(next("zone"));
+            let file = try!(next("file"));
+            if !is_within_dir(Path::new(file)) {
+                return Err(format!("not within the zone directory: {}", file));
+            }
+            let zone_type = match next("zone type") {
+                Ok("Master") | Err(_) => ZoneType::Master,
+                Ok("Slave") => ZoneType::Slave,
+                Ok(other) => return Err(format!("unsupported zone type: {}", other)),
+            };
+
+            let zone_config = ZoneConfig::new(
+                zone.to_string(),
+                zone_type,
+                file.to_string(),
+                None,
+                None,
+                vec![],
+            );
+            let zone_name = try!(zone_config.get_zone().map_err(
+                |e| format!("bad zone name: {}", e),
+            ));
+            if catalog.serial(&zone_name).is_some() {
+                return Err(format!("zone is already served: {}", zone_name));
+            }
+
+            let authority = try!(load_zone(zone_dir, &zone_config, false, None));
+            let serial = authority.serial();
+            if !catalog.add_zone(zone_name.clone(), authority) {
+                return Err(format!("zone is already served: {}", zone_name));
+            }
+
+            zones.insert(zone_name.clone(), zone_config);
+            Ok(format!("added {} serial {}", zone_name, serial))
+        }
+        "reload" => {
+            let zone_name = try!(Name::parse(try!(next("zone")), Some(&Name::root())).map_err(
+                |e| format!("bad zone name: {}", e),
+            ));
+            let zone_config = try!(zones.get(&zone_name).cloned().ok_or_else(|| {
+                format!("zone is not served: {}", zone_name)
+            }));
+            if zone_config.is_update_allowed() {
+                return Err(format!(
+                    "zone allows updates, its journal is not replaced: {}",
+                    zone_name
+                ));
+            }
+
+            try!(reload_zone(zone_dir, &zone_config, catalog));
+            Ok(format!("reloaded {}", zone_name))
+        }
+        "remove" => {
+            let zone_name = try!(Name::parse(try!(next("zone")), Some(&Name::root())).map_err(
+                |e| format!("bad zone name: {}", e),
+            ));
+            if !catalog.remove_zone(&zone_name) {
+                return Err(format!("zone is not served: {}", zone_name));
+            }
+
+            zones.remove(&zone_name);
+            Ok(format!("removed {}", zone_name))
+        }
+        _ => Err(format!("unknown command: {}", command)),
+    }
 }
 
 /// set of DNSSEC algorithms to use to sign the zone. enable_dnssec must be true.
@@ -302,6 +907,18 @@ fn load_key(zone_name: Name, key_config: &KeyConfig) -> Result<Signer, String> {
     ))
 }
 
+fn load_tsig_key(tsig_key_config: &TsigKeyConfig) -> Result<TSigner, String> {
+    let name = try!(tsig_key_config.name().map_err(
+        |e| format!("bad TSIG key name: {}", e),
+    ));
+    let algorithm = try!(tsig_key_config.algorithm().map_err(|e| {
+        format!("bad algorithm of TSIG key {}: {}", name, e)
+    }));
+    let secret = try!(tsig_key_config.secret().map_err(|e| e.to_string()));
+
+    Ok(TSigner::new(name, algorithm, secret, tsig_key_config.fudge()))
+}
+
 #[cfg(feature = "tls")]
 fn load_cert(zone_dir: &Path, tls_cert_config: &TlsCertConfig) -> Result<ParsedPkcs12, String> {
     let path = zone_dir.to_owned().join(tls_cert_config.get_path());
@@ -336,6 +953,22 @@ pub fn main() {
     let config_path = Path::new(args.flag_config.as_ref().map(|s| s as &str).unwrap_or(
         "/etc/named.toml",
     ));
+
+    if args.flag_check {
+        let problems = check_config(&args, config_path);
+        for problem in &problems {
+            error!("{}", problem);
+        }
+
+        if problems.is_empty() {
+            info!("configuration is valid: {:?}", config_path);
+            process::exit(0);
+        } else {
+            error!("{} problem(s) found in: {:?}", problems.len(), config_path);
+            process::exit(1);
+        }
+    }
+
     info!("loading configuration from: {:?}", config_path);
     let config = Config::read_config(config_path).expect(&format!(
         "could not read config: {:?}",
@@ -346,19 +979,103 @@ pub fn main() {
     );
 
     let mut catalog: Catalog = Catalog::new();
-    // configure our server based on the config_path
+    catalog.set_response_cache_size(config.get_response_cache_size());
+    catalog.set_require_cookies(config.get_require_cookies());
+    catalog.set_health_query(config.get_health_query());
+    for tsig_key_config in config.get_tsig_keys() {
+        match load_tsig_key(tsig_key_config) {
+            Ok(tsigner) => {
+                info!("adding TSIG key: {}", tsigner.key_name());
+                catalog.add_tsig_key(tsigner);
+            }
+            Err(e) => error!("{}, skipping TSIG key", e),
+        }
+    }
+    // configure our server based on the config_path, a zone which fails to load does not prevent
+    //  the others from being served
+    let mut zones: Vec<(Name, ZoneConfig)> = Vec::new();
+    let mut reverse_zones: Vec<(Name, Vec<Name>)> = Vec::new();
     for zone in config.get_zones() {
-        let zone_name = zone.get_zone().expect(&format!(
-            "bad zone name in {:?}",
-            config_path
-        ));
+        let zone_name = match zone.get_zone() {
+            Ok(zone_name) => zone_name,
+            Err(e) => {
+                error!("bad zone name in {:?}: {}, skipping zone", config_path, e);
+                continue;
+            }
+        };
+
+        match zone.get_primary() {
+            Ok(Some(primary)) => catalog.set_primary(zone_name.clone(), primary),
+            Ok(None) => (),
+            Err(e) => error!("bad primary for zone {}: {}, using the SOA MNAME", zone_name, e),
+        }
+
+        match zone.get_also_notify() {
+            Ok(ref also_notify) if also_notify.is_empty() => (),
+            Ok(also_notify) => catalog.set_also_notify(zone_name.clone(), also_notify),
+            Err(e) => error!("bad also_notify for zone {}: {}, ignoring", zone_name, e),
+        }
+
+        match zone.get_response_policy() {
+            Ok(Some(policy)) => catalog.set_response_policy(zone_name.clone(), policy),
+            Ok(None) => (),
+            Err(e) => error!("bad hide_txt for zone {}: {}, ignoring the policy", zone_name, e),
+        }
+
+        match zone.get_reverse_of() {
+            Ok(ref forward_zones) if forward_zones.is_empty() => (),
+            Ok(forward_zones) => reverse_zones.push((zone_name.clone(), forward_zones)),
+            Err(e) => error!("bad reverse_of for zone {}: {}, no PTRs generated", zone_name, e),
+        }
 
-        match load_zone(zone_dir, zone) {
-            Ok(authority) => catalog.upsert(zone_name, authority),
-            Err(error) => error!("could not load zone {}: {}", zone_name, error),
+        zones.push((zone_name, zone.clone()));
+    }
+
+    let zone_configs: Vec<ZoneConfig> = zones.iter().map(|&(_, ref zone)| zone.clone()).collect();
+    let control_zones: HashMap<Name, ZoneConfig> = zones.iter().cloned().collect();
+    let mut reloaded_zones: Vec<ZoneConfig> = Vec::new();
+    for ((zone_name, zone), loaded) in zones.into_iter().zip(load_zones(zone_dir, &zone_configs)) {
+        match loaded {
+            Ok(authority) => {
+                catalog.upsert(zone_name.clone(), authority);
+
+                if zone.get_reload_interval().is_some() {
+                    if zone.is_update_allowed() {
+                        warn!(
+                            "zone {} allows updates, its journal is not replaced by reloads",
+                            zone_name
+                        );
+                    } else {
+                        reloaded_zones.push(zone);
+                    }
+                }
+            }
+            Err(error) => {
+                error!("could not load zone {}: {}", zone_name, error);
+                catalog.mark_failed(zone_name, error);
+            }
         }
     }
 
+    // the PTRs are generated once all the forward zones are loaded
+    for (zone_name, forward_zones) in reverse_zones {
+        info!("generating the PTRs of {} from: {:?}", zone_name, forward_zones);
+        catalog.set_reverse_zone(zone_name, forward_zones);
+    }
+
+    let failed_zones = catalog.failed_zones();
+    if !failed_zones.is_empty() {
+        warn!(
+            "{} zone(s) failed to load and will be answered with ServFail: {}",
+            failed_zones.len(),
+            failed_zones
+                .keys()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
     // TODO: support all the IPs asked to listen on...
     // TODO:, there should be the option to listen on any port, IP and protocol option...
     let v4addr = config.get_listen_addrs_ipv4();
@@ -393,7 +1110,18 @@ pub fn main() {
 
 
     // now, run the server, based on the config
-    let mut server = ServerFuture::new(catalog).expect("error creating ServerFuture");
+    let worker_threads = config.get_worker_threads();
+    let mut server = if worker_threads > 0 {
+        info!(
+            "starting {} worker threads, queue depth: {}",
+            worker_threads,
+            config.get_worker_queue_depth()
+        );
+        ServerFuture::with_worker_pool(catalog, worker_threads, config.get_worker_queue_depth())
+    } else {
+        ServerFuture::new(catalog)
+    }.expect("error creating ServerFuture");
+    server.set_poll_budget(config.get_poll_budget());
 
     // load all the listeners
     for udp_socket in udp_sockets {
@@ -421,6 +1149,79 @@ pub fn main() {
         );
     }
 
+    // periodically report on the zones, so that problems are noticed before they are outages
+    let health_check_interval = config.get_health_check_interval();
+    if health_check_interval > time::Duration::from_secs(0) {
+        let catalog = server.handler();
+        let budget_yields = server.budget_yields();
+        let handle = server.tokio_core().handle();
+        let interval = Interval::new(health_check_interval, &handle).expect(
+            "could not create health check interval",
+        );
+
+        handle.spawn(
+            interval
+                .for_each(move |_| {
+                    log_zone_health(&catalog);
+                    info!(
+                        "sockets yielded to others {} times, having spent their poll budget",
+                        budget_yields.load(Ordering::Relaxed)
+                    );
+                    Ok(())
+                })
+                .map_err(|e| error!("zone health checks stopped: {}", e)),
+        );
+    }
+
+    // refresh the secondary zones from their primaries, on the timers of their SOA
+    {
+        let catalog = server.handler();
+        let handle = server.tokio_core().handle();
+        let interval = Interval::new(
+            time::Duration::from_secs(SECONDARY_REFRESH_CHECK_SECS),
+            &handle,
+        ).expect("could not create secondary refresh interval");
+
+        handle.spawn(
+            interval
+                .for_each(move |_| {
+                    for zone in catalog.refresh_secondaries() {
+                        info!("refreshing secondary zone: {}", zone);
+                    }
+                    Ok(())
+                })
+                .map_err(|e| error!("refreshes of secondary zones stopped: {}", e)),
+        );
+    }
+
+    // sign the inline signed zones again before their signatures expire
+    resign_periodically(&mut server);
+
+    // reload the zones whose files are modified, without a restart
+    for zone in &reloaded_zones {
+        let catalog = server.handler();
+        let handle = server.tokio_core().handle();
+        let interval = zone.get_reload_interval().expect("only zones with an interval reload");
+
+        if let Err(e) = watch_zone(zone_dir, zone, interval, catalog, &handle) {
+            error!("zone {:?} will not be reloaded: {}", zone.get_file(), e);
+        }
+    }
+
+    // add, reload and remove zones on the commands of the control channel
+    match config.get_control_addr() {
+        Ok(Some(control_addr)) => {
+            let listener = TcpListener::bind(control_addr).expect(&format!(
+                "could not bind to control address: {}",
+                control_addr
+            ));
+            info!("listening for control commands on {:?}", listener);
+            serve_control(listener, zone_dir.to_owned(), control_zones, server.handler());
+        }
+        Ok(None) => (),
+        Err(e) => error!("bad control_addr: {}, the control channel is disabled", e),
+    }
+
     // config complete, starting!
     banner();
     info!("awaiting connections...");
@@ -432,6 +1233,176 @@ pub fn main() {
     info!("Trust-DNS {} stopping", trust_dns::version());
 }
 
+/// Checks every `RESIGN_CHECK_SECS` which inline signed zones are due to be signed again, and signs
+///  them on the worker pool of the server, or on a thread of their own if it has none, see
+///  `Catalog::resign_due`
+///
+/// The zones are not checked again until those due are signed. When the pool is full they are
+///  signed on the next check.
+#[cfg(feature = "dnssec")]
+fn resign_periodically(server: &mut ServerFuture<Catalog>) {
+    let catalog = server.handler();
+    let worker_pool = server.worker_pool();
+    let handle = server.tokio_core().handle();
+    let interval = Interval::new(time::Duration::from_secs(RESIGN_CHECK_SECS), &handle).expect(
+        "could not create re-signing interval",
+    );
+    let resigning = Arc::new(AtomicBool::new(false));
+
+    handle.spawn(
+        interval
+            .for_each(move |_| {
+                if resigning.swap(true, Ordering::SeqCst) {
+                    return Ok(());
+                }
+
+                let catalog = catalog.clone();
+                let done = resigning.clone();
+                let resign = move || {
+                    catalog.resign_due(Utc::now());
+                    done.store(false, Ordering::SeqCst);
+                };
+
+                let started = match worker_pool {
+                    Some(ref worker_pool) => worker_pool.try_execute(resign),
+                    None => {
+                        thread::spawn(resign);
+                        true
+                    }
+                };
+                if !started {
+                    debug!("worker pool is full, checking the zones to re-sign later");
+                    resigning.store(false, Ordering::SeqCst);
+                }
+
+                Ok(())
+            })
+            .map_err(|e| error!("re-signing of zones stopped: {}", e)),
+    );
+}
+
+#[cfg(not(feature = "dnssec"))]
+fn resign_periodically(_server: &mut ServerFuture<Catalog>) {}
+
+/// Logs any zones which are not healthy, and the statistics of each zone
+fn log_zone_health(catalog: &Catalog) {
+    for health in catalog.health() {
+        match health.status() {
+            ZoneHealthStatus::Healthy => (),
+            ZoneHealthStatus::RefreshOverdue => {
+                warn!(
+                    "zone {} serial: {:?} has not been refreshed for: {:?}",
+                    health.zone(),
+                    health.serial(),
+                    health.since_refresh()
+                )
+            }
+            status => {
+                error!(
+                    "zone {} serial: {:?} is {:?}, last refreshed: {:?} ago, error: {:?}",
+                    health.zone(),
+                    health.serial(),
+                    status,
+                    health.since_refresh(),
+                    health.error()
+                )
+            }
+        }
+    }
+
+    for (zone, stats) in catalog.zone_stats() {
+        debug!(
+            "zone {} queries: {} response_codes: {:?} transfer: {:?} load_failures: {}",
+            zone,
+            stats.queries(),
+            stats.response_codes(),
+            stats.transfer_status(),
+            stats.load_failures()
+        );
+    }
+}
+
+/// Loads everything the server would at startup, without writing to disk or binding any sockets
+///
+/// # Returns
+///
+/// every problem found, prefixed with the file and config entry it relates to
+fn check_config(args: &Args, config_path: &Path) -> Vec<String> {
+    info!("checking configuration: {:?}", config_path);
+    let mut problems = Vec::new();
+
+    let config = match Config::read_config(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            problems.push(format!("{:?}: {}", config_path, e));
+            return problems;
+        }
+    };
+    let zone_dir: &Path = args.flag_zonedir.as_ref().map(|s| Path::new(s)).unwrap_or(
+        config.get_directory(),
+    );
+
+    if let Err(e) = config.get_control_addr() {
+        problems.push(format!("{:?}: bad control_addr: {}", config_path, e));
+    }
+
+    for (idx, tsig_key_config) in config.get_tsig_keys().iter().enumerate() {
+        if let Err(e) = load_tsig_key(tsig_key_config) {
+            problems.push(format!("{:?}: tsig_keys[{}]: {}", config_path, idx, e));
+        }
+    }
+
+    let mut zone_names: Vec<Name> = Vec::new();
+    for (idx, zone) in config.get_zones().iter().enumerate() {
+        let location = format!("{:?}: zones[{}] ({:?})", config_path, idx, zone.get_file());
+
+        if let Ok(zone_name) = zone.get_zone() {
+            if zone_names.contains(&zone_name) {
+                problems.push(format!("{}: duplicate zone: {}", location, zone_name));
+            }
+            zone_names.push(zone_name);
+        }
+
+        if let Err(e) = zone.get_primary() {
+            problems.push(format!("{}: bad primary: {}", location, e));
+        }
+
+        if let Err(e) = zone.get_also_notify() {
+            problems.push(format!("{}: bad also_notify: {}", location, e));
+        }
+
+        if let Err(e) = zone.get_response_policy() {
+            problems.push(format!("{}: bad hide_txt: {}", location, e));
+        }
+
+        if let Err(e) = zone.get_reverse_of() {
+            problems.push(format!("{}: bad reverse_of: {}", location, e));
+        }
+
+        if let Err(e) = load_zone(zone_dir, zone, true, None) {
+            problems.push(format!("{}: {}", location, e));
+        }
+    }
+
+    if let Some(tls_cert_config) = config.get_tls_cert() {
+        if let Err(e) = check_tls(zone_dir, tls_cert_config) {
+            problems.push(format!("{:?}: tls_cert: {}", config_path, e));
+        }
+    }
+
+    problems
+}
+
+#[cfg(not(feature = "tls"))]
+fn check_tls(_zone_dir: &Path, _tls_cert_config: &TlsCertConfig) -> Result<(), String> {
+    Err("TLS not enabled".to_string())
+}
+
+#[cfg(feature = "tls")]
+fn check_tls(zone_dir: &Path, tls_cert_config: &TlsCertConfig) -> Result<(), String> {
+    load_cert(zone_dir, tls_cert_config).map(|_| ())
+}
+
 #[cfg(not(feature = "tls"))]
 fn config_tls(
     _args: &Args,
@@ -478,6 +1449,7 @@ fn config_tls(
             load_cert(zone_dir, tls_cert_config).expect("error loading tls certificate file");
 
         info!("listening for TLS on {:?}", tls_listener);
+        server.set_padding_block_size(config.get_tls_padding_block_size());
         server
             .register_tls_listener(tls_listener, config.get_tcp_request_timeout(), tls_cert)
             .expect("could not register TLS listener");
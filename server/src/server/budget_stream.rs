@@ -0,0 +1,97 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Cooperative yielding of the tasks of the IO loop, see `BudgetStream`
+
+use std::cmp;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::{task, Async, Poll, Stream};
+
+/// The number of items a `BudgetStream` takes from its stream by default before it yields
+pub const DEFAULT_POLL_BUDGET: usize = 32;
+
+/// Wraps a stream so that at most `budget` of its items are taken each time its task is polled.
+///
+/// Once the budget is spent, the task is notified and `NotReady` is returned, which puts the task
+///  at the back of the queue of the reactor, behind the other sockets and connections which are
+///  ready, and the budget is renewed for when the task is polled again. Without this, a client
+///  which pipelines requests on one TCP connection, or a burst on a UDP socket, is served for as
+///  long as it has requests ready, while every other socket waits.
+///
+/// The number of times the budget was spent is counted in `yields`, which may be shared by the
+///  streams of a server, e.g. to see how often its sockets compete for the IO loop.
+pub struct BudgetStream<S> {
+    stream: S,
+    budget: usize,
+    remaining: usize,
+    yields: Arc<AtomicUsize>,
+}
+
+impl<S> BudgetStream<S> {
+    /// Returns a new BudgetStream
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - stream to wrap
+    /// * `budget` - number of items taken per poll of the task, at least 1
+    pub fn new(stream: S, budget: usize) -> Self {
+        Self::with_yields(stream, budget, Arc::new(AtomicUsize::new(0)))
+    }
+
+    /// Returns a new BudgetStream, which counts the times it yields in `yields`
+    pub fn with_yields(stream: S, budget: usize, yields: Arc<AtomicUsize>) -> Self {
+        let budget = cmp::max(budget, 1);
+
+        BudgetStream {
+            stream: stream,
+            budget: budget,
+            remaining: budget,
+            yields: yields,
+        }
+    }
+
+    /// The number of items taken per poll of the task
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// The number of times the budget was spent, and the task yielded
+    pub fn yields(&self) -> usize {
+        self.yields.load(Ordering::Relaxed)
+    }
+}
+
+impl<S: Stream> Stream for BudgetStream<S> {
+    type Item = S::Item;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.remaining == 0 {
+            self.remaining = self.budget;
+            self.yields.fetch_add(1, Ordering::Relaxed);
+
+            // there may be more ready, poll again after the others had their turn
+            task::current().notify();
+            return Ok(Async::NotReady);
+        }
+
+        match self.stream.poll() {
+            Ok(Async::Ready(Some(item))) => {
+                self.remaining -= 1;
+                Ok(Async::Ready(Some(item)))
+            }
+            Ok(Async::NotReady) => {
+                // nothing more is ready, the next poll is a new turn
+                self.remaining = self.budget;
+                Ok(Async::NotReady)
+            }
+            other => other,
+        }
+    }
+}
@@ -16,14 +16,22 @@
 
 //! `Server` component for hosting a domain name servers operations.
 
+mod budget_stream;
+mod pooled_stream;
 mod request_stream;
+mod response_queue;
 mod server_future;
 mod timeout_stream;
 mod request_handler;
+mod worker_pool;
 
+pub use self::budget_stream::{BudgetStream, DEFAULT_POLL_BUDGET};
+pub use self::pooled_stream::PooledStream;
 pub use self::request_stream::Request;
 pub use self::request_stream::RequestStream;
 pub use self::request_stream::ResponseHandle;
+pub use self::response_queue::{QueuedResponse, ResponseQueue, ResponseQueueHandle};
 pub use self::server_future::ServerFuture;
 pub use self::timeout_stream::TimeoutStream;
 pub use self::request_handler::RequestHandler;
+pub use self::worker_pool::WorkerPool;
@@ -0,0 +1,122 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The responses to a request which are produced on the worker pool, see `PooledStream`
+
+use std::io;
+use std::mem;
+use std::sync::Arc;
+
+use futures::{Async, Future, Poll, Stream};
+use futures::sync::oneshot;
+use trust_dns::op::{Message, OpCode, ResponseCode};
+
+use server::WorkerPool;
+
+/// The responses to a request, which are produced one at a time
+pub type Responses = Box<Stream<Item = Message, Error = io::Error> + Send>;
+
+/// The next response, and the responses after it, as a worker hands them back
+type Produced = (Option<io::Result<Message>>, Responses);
+
+/// Where the responses of a `PooledStream` are
+enum State {
+    /// Waiting to be polled for the next response
+    Idle(Responses),
+    /// A worker is producing the next response
+    Producing(oneshot::Receiver<Produced>),
+    /// All the responses were produced
+    Done,
+}
+
+/// Wraps the responses to a request so that each one is produced by a job on the `WorkerPool`,
+///  rather than on the IO loop which polls them, e.g. the messages of a zone transfer which are
+///  each signed with TSIG
+///
+/// The job for the next response is only queued once it is polled for, so that, as for the
+///  other streamed responses, the messages are produced no faster than they are written. If the
+///  pool's queue is full, the responses end with a `ServFail`, as do the requests which are
+///  rejected by the pool, see `ServerFuture::with_worker_pool`.
+pub struct PooledStream {
+    pool: Arc<WorkerPool>,
+    id: u16,
+    op_code: OpCode,
+    state: State,
+}
+
+impl PooledStream {
+    /// Returns the responses, which are produced on the pool
+    ///
+    /// # Arguments
+    ///
+    /// * `responses` - the responses, which are only polled on the workers of the pool
+    /// * `pool` - the pool which produces them
+    /// * `id` - the id of the request, and of the `ServFail`
+    /// * `op_code` - the op code of the request
+    pub fn new(responses: Responses, pool: Arc<WorkerPool>, id: u16, op_code: OpCode) -> Self {
+        PooledStream {
+            pool: pool,
+            id: id,
+            op_code: op_code,
+            state: State::Idle(responses),
+        }
+    }
+}
+
+impl Stream for PooledStream {
+    type Item = Message;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            match mem::replace(&mut self.state, State::Done) {
+                State::Idle(responses) => {
+                    let (sender, receiver) = oneshot::channel();
+                    let produce = move || {
+                        let mut responses = responses.wait();
+                        let next = responses.next();
+                        // the receiver is gone if the connection was closed
+                        let _ = sender.send((next, responses.into_inner()));
+                    };
+
+                    if !self.pool.try_execute(produce) {
+                        warn!("worker pool is full, failing responses to request: {}", self.id);
+                        return Ok(Async::Ready(Some(
+                            Message::error_msg(self.id, self.op_code, ResponseCode::ServFail),
+                        )));
+                    }
+                    self.state = State::Producing(receiver);
+                }
+                State::Producing(mut receiver) => {
+                    let (next, responses) = match receiver.poll() {
+                        Ok(Async::Ready(produced)) => produced,
+                        Ok(Async::NotReady) => {
+                            self.state = State::Producing(receiver);
+                            return Ok(Async::NotReady);
+                        }
+                        Err(oneshot::Canceled) => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::Other,
+                                "worker stopped producing responses",
+                            ))
+                        }
+                    };
+
+                    return match next {
+                        Some(Ok(response)) => {
+                            self.state = State::Idle(responses);
+                            Ok(Async::Ready(Some(response)))
+                        }
+                        Some(Err(e)) => Err(e),
+                        None => Ok(Async::Ready(None)),
+                    };
+                }
+                State::Done => return Ok(Async::Ready(None)),
+            }
+        }
+    }
+}
@@ -7,6 +7,11 @@
 
 //! Request Handler for incoming requests
 
+use std::io;
+
+use futures::{Future, Stream};
+use tokio_core::reactor::Handle;
+
 use server::Request;
 use trust_dns::op::Message;
 
@@ -22,4 +27,85 @@ pub trait RequestHandler {
     ///
     /// The derived response to the the request
     fn handle_request(&self, request: &Request) -> Message;
+
+    /// Determine's what needs to happen given the type of request, and sends the responses to it,
+    ///  more than one where they do not fit in one message, e.g. a zone transfer, RFC 5936
+    ///  section 2.2. The `ServerFuture` handles the requests which are not streamed, see
+    ///  `response_stream`, this way.
+    ///
+    /// The default sends the responses of `response_stream`, as they are produced, or else the
+    ///  response of `handle_request`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the requested action to perform.
+    /// * `send` - sends a response to the client, an error ends the responses to the request
+    fn handle_request_stream(
+        &self,
+        request: &Request,
+        send: &mut FnMut(Message) -> io::Result<()>,
+    ) -> io::Result<()> {
+        match self.response_stream(request) {
+            Some(responses) => {
+                for response in responses.wait() {
+                    try!(send(try!(response)));
+                }
+                Ok(())
+            }
+            None => send(self.handle_request(request)),
+        }
+    }
+
+    /// Returns the responses to a request which are produced one at a time, as they are sent,
+    ///  e.g. the messages of a zone transfer, RFC 5936 section 2.2, or None for the requests which
+    ///  are answered by `handle_request`.
+    ///
+    /// The `ServerFuture` asks for the stream on its IO loop, or on its worker pool for the
+    ///  requests which are cpu intensive, see `is_cpu_intensive`, which then also produces each
+    ///  message, and polls it for the next message only once the one before it was written to the
+    ///  connection, so that the messages are neither all held in memory, nor produced faster than
+    ///  the client reads them. The default streams no requests.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the request which is about to be handled
+    fn response_stream(
+        &self,
+        _request: &Request,
+    ) -> Option<Box<Stream<Item = Message, Error = io::Error> + Send>> {
+        None
+    }
+
+    /// Returns the future of the response to a request which is answered by another server, e.g.
+    ///  an update to a secondary zone, which is forwarded to the primary, or None for the requests
+    ///  which are answered by `handle_request_stream`.
+    ///
+    /// The `ServerFuture` runs the future on its IO loop and sends the response once it is ready,
+    ///  so that waiting on the other server holds up neither the loop nor a worker. The default
+    ///  answers all requests with `handle_request_stream`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the request which is about to be handled
+    /// * `handle` - the IO loop on which the future is run
+    fn forward_request(
+        &self,
+        _request: &Request,
+        _handle: &Handle,
+    ) -> Option<Box<Future<Item = Message, Error = io::Error>>> {
+        None
+    }
+
+    /// Returns true if the request is expected to be expensive to process, e.g. it requires
+    ///  signature verification or signing.
+    ///
+    /// If the `ServerFuture` was constructed with a worker pool, these requests will be handled
+    ///  off of the IO loop. The default is to handle all requests inline.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the request which is about to be handled
+    fn is_cpu_intensive(&self, _request: &Request) -> bool {
+        false
+    }
 }
@@ -1,11 +1,18 @@
 use std::io;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
 
-use futures::{Async, Poll, Stream};
+use futures::{Async, Future, Poll, Stream};
+use tokio_core::reactor::Handle;
 
 use trust_dns::BufStreamHandle;
 use trust_dns::op::Message;
-use trust_dns::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+use trust_dns::serialize::binary::{BinDecoder, BinEncoder, BinSerializable, DecodeMode,
+                                   NameCompression};
+
+use server::{BudgetStream, DEFAULT_POLL_BUDGET};
+use server::response_queue::{QueuedResponse, ResponseQueueHandle};
 
 /// An incoming request to the DNS catalog
 pub struct Request {
@@ -23,7 +30,11 @@ pub struct Request {
 ///  can be returned.
 pub struct RequestStream<S> {
     stream: S,
-    stream_handle: BufStreamHandle,
+    sender: ResponseSender,
+    name_compression: NameCompression,
+    padding_block_size: Option<u16>,
+    response_budget: usize,
+    budget_yields: Arc<AtomicUsize>,
 }
 
 impl<S> RequestStream<S> {
@@ -33,11 +44,53 @@ impl<S> RequestStream<S> {
     /// * `stream` - Stream from which requests will be read
     /// * `stream_handle` - Handle to which responses will be posted
     pub fn new(stream: S, stream_handle: BufStreamHandle) -> Self {
+        Self::with_name_compression(stream, stream_handle, NameCompression::All)
+    }
+
+    /// Creates a new RequestStream, whose responses compress only the names allowed by
+    ///  `name_compression`
+    pub fn with_name_compression(
+        stream: S,
+        stream_handle: BufStreamHandle,
+        name_compression: NameCompression,
+    ) -> Self {
+        Self::with_sender(stream, ResponseSender::Buffer(stream_handle), name_compression)
+    }
+
+    /// Creates a new RequestStream for a connection, whose responses are queued to be written one
+    ///  message at a time, see `ResponseQueue`
+    pub fn with_response_queue(
+        stream: S,
+        response_queue: ResponseQueueHandle,
+        name_compression: NameCompression,
+    ) -> Self {
+        Self::with_sender(stream, ResponseSender::Queue(response_queue), name_compression)
+    }
+
+    fn with_sender(stream: S, sender: ResponseSender, name_compression: NameCompression) -> Self {
         RequestStream {
             stream: stream,
-            stream_handle: stream_handle,
+            sender: sender,
+            name_compression: name_compression,
+            padding_block_size: None,
+            response_budget: DEFAULT_POLL_BUDGET,
+            budget_yields: Arc::new(AtomicUsize::new(0)),
         }
     }
+
+    /// Sets the block size to which the responses to padded requests are padded, RFC 7830; the
+    ///  responses to requests without the Padding option are never padded
+    pub fn set_padding_block_size(&mut self, padding_block_size: Option<u16>) {
+        self.padding_block_size = padding_block_size;
+    }
+
+    /// Sets the number of messages of a streamed response, e.g. a zone transfer, which are
+    ///  produced each time its task is polled, see `ResponseHandle::send_stream`, and counts the
+    ///  times it yields in `yields`, see `BudgetStream`
+    pub fn set_response_budget(&mut self, response_budget: usize, yields: Arc<AtomicUsize>) {
+        self.response_budget = response_budget;
+        self.budget_yields = yields;
+    }
 }
 
 impl<S> Stream for RequestStream<S>
@@ -61,17 +114,28 @@ where
                     //       forward the request to another sender such that we could pull serialization off
                     //       the IO thread.
                     // decode any messages that are ready
-                    let mut decoder = BinDecoder::new(&buffer);
+                    //  strictly, e.g. bytes after the last record are an error, RFC 1035 section 4.1
+                    let mut decoder = BinDecoder::with_mode(&buffer, DecodeMode::Strict);
                     match Message::read(&mut decoder) {
                         Ok(message) => {
                             debug!("received message: {}", message.id());
+                            let is_padded =
+                                message.edns().and_then(|edns| edns.padding()).is_some();
                             let request = Request {
                                 message: message,
                                 src: addr,
                             };
                             let response_handle = ResponseHandle {
                                 dst: addr,
-                                stream_handle: self.stream_handle.clone(),
+                                sender: self.sender.clone(),
+                                name_compression: self.name_compression,
+                                padding_block_size: if is_padded {
+                                    self.padding_block_size
+                                } else {
+                                    None
+                                },
+                                response_budget: self.response_budget,
+                                budget_yields: self.budget_yields.clone(),
                             };
                             return Ok(Async::Ready(Some((request, response_handle))));
                         }
@@ -87,32 +151,131 @@ where
     }
 }
 
+/// Where the responses of a `RequestStream` are sent
+#[derive(Clone)]
+enum ResponseSender {
+    /// Each response is sent as soon as it is encoded, e.g. over UDP
+    Buffer(BufStreamHandle),
+    /// The responses are queued for the connection, which writes them one at a time
+    Queue(ResponseQueueHandle),
+}
+
 /// A handler for wraping a BufStreamHandle, which will properly serialize the message and add the
 ///  associated destination.
+#[derive(Clone)]
 pub struct ResponseHandle {
     dst: SocketAddr,
-    stream_handle: BufStreamHandle,
+    sender: ResponseSender,
+    name_compression: NameCompression,
+    padding_block_size: Option<u16>,
+    response_budget: usize,
+    budget_yields: Arc<AtomicUsize>,
 }
 
 impl ResponseHandle {
     /// Serializes and sends a message to to the wrapped handle
     pub fn send(&mut self, response: Message) -> io::Result<()> {
-        debug!("sending message: {}", response.id());
-        let mut buffer = Vec::with_capacity(512);
-        let encode_result = {
-            let mut encoder: BinEncoder = BinEncoder::new(&mut buffer);
-            response.emit(&mut encoder)
+        let (buffer, dst) = try!(encode(
+            response,
+            self.dst,
+            self.name_compression,
+            self.padding_block_size,
+        ));
+
+        let sent = match self.sender {
+            ResponseSender::Buffer(ref stream_handle) => {
+                stream_handle.unbounded_send((buffer, dst)).map_err(|_| ())
+            }
+            ResponseSender::Queue(ref response_queue) => {
+                response_queue
+                    .unbounded_send(QueuedResponse::Message(buffer, dst))
+                    .map_err(|_| ())
+            }
         };
+        sent.map_err(|()| io::Error::new(io::ErrorKind::Other, "unknown"))
+    }
 
-        try!(encode_result.map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("error encoding message: {}", e),
-            )
-        }));
+    /// Serializes and sends the messages of a response which are produced as they are sent, e.g.
+    ///  the messages of a zone transfer, see `RequestHandler::response_stream`
+    ///
+    /// Over a connection, the stream is queued behind the responses before it, and each message
+    ///  is only produced and encoded once the one before it was written, see `ResponseQueue`.
+    ///  Otherwise the messages are sent as they are produced, by a task spawned on the IO loop.
+    ///  Either way, at most the response budget of messages is produced each time the task is
+    ///  polled, see `RequestStream::set_response_budget`, before it yields to the other sockets.
+    pub fn send_stream(
+        &mut self,
+        responses: Box<Stream<Item = Message, Error = io::Error> + Send>,
+        handle: &Handle,
+    ) -> io::Result<()> {
+        let dst = self.dst;
+        let name_compression = self.name_compression;
+        let padding_block_size = self.padding_block_size;
+        let encoded = responses.and_then(move |response| {
+            encode(response, dst, name_compression, padding_block_size)
+        });
+        let encoded =
+            BudgetStream::with_yields(encoded, self.response_budget, self.budget_yields.clone());
 
-        self.stream_handle
-            .unbounded_send((buffer, self.dst))
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "unknown"))
+        match self.sender {
+            ResponseSender::Buffer(ref stream_handle) => {
+                let stream_handle = stream_handle.clone();
+                handle.spawn(
+                    encoded
+                        .for_each(move |message| {
+                            stream_handle.unbounded_send(message).map_err(|_| {
+                                io::Error::new(io::ErrorKind::Other, "unknown")
+                            })
+                        })
+                        .map_err(move |e| debug!("error sending responses to {}: {}", dst, e)),
+                );
+                Ok(())
+            }
+            ResponseSender::Queue(ref response_queue) => {
+                response_queue
+                    .unbounded_send(QueuedResponse::Stream(Box::new(encoded)))
+                    .map_err(|_| io::Error::new(io::ErrorKind::Other, "unknown"))
+            }
+        }
     }
 }
+
+/// Serializes the response to the destination, padded to the block size, if there is one
+fn encode(
+    mut response: Message,
+    dst: SocketAddr,
+    name_compression: NameCompression,
+    padding_block_size: Option<u16>,
+) -> io::Result<(Vec<u8>, SocketAddr)> {
+    debug!("sending message: {}", response.id());
+    // the MAC of a TSIG covers the response as it was signed, with all names compressed
+    let name_compression = if response.tsig().is_some() {
+        NameCompression::All
+    } else {
+        name_compression
+    };
+
+    if let Some(block_size) = padding_block_size {
+        if response.tsig().is_some() {
+            debug!("not padding signed response: {}", response.id());
+        } else if let Err(e) = response.pad(block_size, name_compression) {
+            warn!("could not pad response, sending it unpadded: {}", e);
+        }
+    }
+
+    let mut buffer = Vec::with_capacity(512);
+    let encode_result = {
+        let mut encoder: BinEncoder = BinEncoder::new(&mut buffer);
+        encoder.set_name_compression(name_compression);
+        response.emit(&mut encoder)
+    };
+
+    try!(encode_result.map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("error encoding message: {}", e),
+        )
+    }));
+
+    Ok((buffer, dst))
+}
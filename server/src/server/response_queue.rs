@@ -0,0 +1,89 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The responses waiting to be written to a connection, see `ResponseQueue`
+
+use std::io;
+use std::net::SocketAddr;
+
+use futures::{Async, Poll, Stream};
+use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+
+/// The encoded messages of a response, which are produced as they are written, see
+///  `ResponseHandle::send_stream`
+pub type EncodedStream = Box<Stream<Item = (Vec<u8>, SocketAddr), Error = io::Error> + Send>;
+
+/// Handle to which the responses of a connection are queued, see `ResponseQueue`
+pub type ResponseQueueHandle = UnboundedSender<QueuedResponse>;
+
+/// A response which waits to be written to a connection
+pub enum QueuedResponse {
+    /// An encoded message, and the address to which it is sent
+    Message(Vec<u8>, SocketAddr),
+    /// The messages of a response, e.g. a zone transfer, which are encoded as they are written
+    Stream(EncodedStream),
+}
+
+/// The responses to the requests of a connection, in the order in which they were queued, from
+///  which the connection pulls the next message once it has written the one before it, see
+///  `TcpStream::from_stream_with_outbound`
+///
+/// The next message of a queued stream is only produced when it is pulled, so that a zone
+///  transfer is not encoded faster than the client reads it, the responses queued after a stream
+///  wait until it ends. A stream which fails is dropped, which ends that response.
+pub struct ResponseQueue {
+    responses: UnboundedReceiver<QueuedResponse>,
+    current: Option<EncodedStream>,
+}
+
+impl ResponseQueue {
+    /// Returns a new, empty, queue, and the handle to which the responses are queued
+    pub fn new() -> (Self, ResponseQueueHandle) {
+        let (sender, receiver) = unbounded();
+
+        let queue = ResponseQueue {
+            responses: receiver,
+            current: None,
+        };
+        (queue, sender)
+    }
+}
+
+impl Stream for ResponseQueue {
+    type Item = (Vec<u8>, SocketAddr);
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            let ended = match self.current {
+                Some(ref mut current) => {
+                    match current.poll() {
+                        Ok(Async::Ready(Some(message))) => return Ok(Async::Ready(Some(message))),
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Ok(Async::Ready(None)) => true,
+                        Err(e) => {
+                            warn!("error in streamed response, ending it: {}", e);
+                            true
+                        }
+                    }
+                }
+                None => false,
+            };
+            if ended {
+                self.current = None;
+            }
+
+            match try_ready!(self.responses.poll()) {
+                Some(QueuedResponse::Message(buffer, dst)) => {
+                    return Ok(Async::Ready(Some((buffer, dst))))
+                }
+                Some(QueuedResponse::Stream(stream)) => self.current = Some(stream),
+                None => return Ok(Async::Ready(None)),
+            }
+        }
+    }
+}
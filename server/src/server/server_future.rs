@@ -7,39 +7,149 @@
 use std;
 use std::io;
 use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
 use std::time::Duration;
 
-use futures::{Async, Future, Poll, Stream};
+use futures::{future, stream, Async, Future, Poll, Stream};
 
 use tokio_core;
-use tokio_core::reactor::Core;
+use tokio_core::reactor::{Core, Handle};
 
+use trust_dns::serialize::binary::NameCompression;
 use trust_dns::udp::UdpStream;
 use trust_dns::tcp::TcpStream;
 
 #[cfg(feature = "tls")]
-use trust_dns_openssl::{TlsStream, tls_server};
+use trust_dns_openssl::tls_server;
 
 #[cfg(feature = "tls")]
 use trust_dns_openssl::tls_server::*;
 
-use server::{Request, RequestHandler, RequestStream, ResponseHandle, TimeoutStream};
+use server::{BudgetStream, PooledStream, Request, RequestHandler, RequestStream, ResponseHandle,
+             ResponseQueue, TimeoutStream, WorkerPool, DEFAULT_POLL_BUDGET};
+use server::pooled_stream::Responses;
 
 // TODO, would be nice to have a Slab for buffers here...
 
+/// Hands a request off to the worker pool, returns its responses, which are produced there
+type Offload = Arc<Fn(Request) -> Responses>;
+
 /// A Futures based implementation of a DNS server
 pub struct ServerFuture<T: RequestHandler + 'static> {
     io_loop: Core,
-    handler: Arc<T>
+    handler: Arc<T>,
+    offload: Option<Offload>,
+    worker_pool: Option<Arc<WorkerPool>>,
+    name_compression: NameCompression,
+    padding_block_size: Option<u16>,
+    poll_budget: usize,
+    budget_yields: Arc<AtomicUsize>,
 }
 
-impl <T: RequestHandler> ServerFuture <T> {
+impl<T: RequestHandler + Send + Sync> ServerFuture<T> {
+    /// Creates a new ServerFuture with the specified Handler, and a pool of worker threads.
+    ///
+    /// Any request for which `RequestHandler::is_cpu_intensive` returns true will be handled on
+    ///  the worker pool rather than the IO loop, as is each message of its streamed responses,
+    ///  e.g. of a zone transfer signed with TSIG, see `PooledStream`. When the pool's queue is
+    ///  full, those requests are answered with `ServFail`, so that a burst of expensive requests
+    ///  can not stall all other traffic.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - the handler for all requests
+    /// * `threads` - number of worker threads to spawn
+    /// * `queue_depth` - number of requests which may wait for a free worker
+    pub fn with_worker_pool(
+        handler: T,
+        threads: usize,
+        queue_depth: usize,
+    ) -> io::Result<ServerFuture<T>> {
+        let handler = Arc::new(handler);
+        let worker_pool = Arc::new(try!(WorkerPool::new(threads, queue_depth)));
+
+        let pool = worker_pool.clone();
+        let pool_handler = handler.clone();
+        let offload = move |request: Request| -> Responses {
+            let handler = pool_handler.clone();
+            let id = request.message.id();
+            let op_code = request.message.op_code();
+            // the responses are only asked for once the first of them is produced, on the pool
+            let responses = future::lazy(move || {
+                Self::pooled_responses(&*handler, &request)
+            }).flatten_stream();
+
+            Box::new(PooledStream::new(Box::new(responses), pool.clone(), id, op_code))
+        };
+
+        Ok(ServerFuture {
+            io_loop: try!(Core::new()),
+            handler: handler,
+            offload: Some(Arc::new(offload)),
+            worker_pool: Some(worker_pool),
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            poll_budget: DEFAULT_POLL_BUDGET,
+            budget_yields: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+}
+
+impl<T: RequestHandler> ServerFuture<T> {
     /// Creates a new ServerFuture with the specified Handler.
     pub fn new(handler: T) -> io::Result<ServerFuture<T>> {
         Ok(ServerFuture {
-               io_loop: try!(Core::new()),
-               handler: Arc::new(handler),
-           })
+            io_loop: try!(Core::new()),
+            handler: Arc::new(handler),
+            offload: None,
+            worker_pool: None,
+            name_compression: NameCompression::All,
+            padding_block_size: None,
+            poll_budget: DEFAULT_POLL_BUDGET,
+            budget_yields: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// The pool of worker threads, if there is one, see `with_worker_pool`, for other work which
+    ///  should not hold up the IO loop, e.g. signing zones again, see `Catalog::resign_due`
+    pub fn worker_pool(&self) -> Option<Arc<WorkerPool>> {
+        self.worker_pool.clone()
+    }
+
+    /// Sets which names of the responses are compressed, for the sockets and listeners registered
+    ///  after this, e.g. uncompressed for a listener of embedded clients which fail to decode
+    ///  pointers
+    pub fn set_name_compression(&mut self, name_compression: NameCompression) {
+        self.name_compression = name_compression;
+    }
+
+    /// Sets the block size to which the responses to padded requests are padded, RFC 7830, for
+    ///  the sockets and listeners registered after this, e.g. only for the TLS listeners
+    pub fn set_padding_block_size(&mut self, padding_block_size: Option<u16>) {
+        self.padding_block_size = padding_block_size;
+    }
+
+    /// Sets the number of requests, or connections, taken from a socket each time it is polled,
+    ///  for the sockets and listeners registered after this, see `BudgetStream`
+    ///
+    /// Each socket, and each TCP or TLS connection, then yields to the others once it has taken
+    ///  its budget, so that a client which pipelines requests, or a burst of connections, can not
+    ///  hold up the queries on the other sockets. The same budget bounds the messages of a
+    ///  streamed response, e.g. a zone transfer, which are produced each time, see
+    ///  `RequestHandler::response_stream`.
+    pub fn set_poll_budget(&mut self, poll_budget: usize) {
+        self.poll_budget = poll_budget;
+    }
+
+    /// The number of requests, or connections, taken from a socket each time it is polled
+    pub fn poll_budget(&self) -> usize {
+        self.poll_budget
+    }
+
+    /// Returns the number of times a socket or connection spent its budget and yielded to the
+    ///  others, see `set_poll_budget`, shared so that it can be read while the server runs
+    pub fn budget_yields(&self) -> Arc<AtomicUsize> {
+        self.budget_yields.clone()
     }
 
     /// Register a UDP socket. Should be bound before calling this function.
@@ -48,8 +158,15 @@ impl <T: RequestHandler> ServerFuture <T> {
 
         // create the new UdpStream
         let (buf_stream, stream_handle) = UdpStream::with_bound(socket, &self.io_loop.handle());
-        let request_stream = RequestStream::new(buf_stream, stream_handle);
+        let mut request_stream =
+            RequestStream::with_name_compression(buf_stream, stream_handle, self.name_compression);
+        request_stream.set_padding_block_size(self.padding_block_size);
+        request_stream.set_response_budget(self.poll_budget, self.budget_yields.clone());
+        let request_stream =
+            BudgetStream::with_yields(request_stream, self.poll_budget, self.budget_yields.clone());
         let handler = self.handler.clone();
+        let offload = self.offload.clone();
+        let handle = self.io_loop.handle();
 
         // this spawns a ForEach future which handles all the requests into a Handler.
         self.io_loop
@@ -57,7 +174,11 @@ impl <T: RequestHandler> ServerFuture <T> {
             .spawn(// TODO dedup with below into generic func
                    request_stream
                        .for_each(move |(request, response_handle)| {
-                                     Self::handle_request(request, response_handle, handler.clone())
+                                     Self::handle_request(request,
+                                                          response_handle,
+                                                          handler.clone(),
+                                                          offload.clone(),
+                                                          &handle)
                                  })
                        .map_err(|e| debug!("error in UDP request_stream handler: {}", e)));
     }
@@ -80,28 +201,50 @@ impl <T: RequestHandler> ServerFuture <T> {
                              -> io::Result<()> {
         let handle = self.io_loop.handle();
         let handler = self.handler.clone();
+        let offload = self.offload.clone();
+        let name_compression = self.name_compression;
+        let padding_block_size = self.padding_block_size;
+        let poll_budget = self.poll_budget;
+        let budget_yields = self.budget_yields.clone();
         // TODO: this is an awkward interface with socketaddr...
         let addr = try!(listener.local_addr());
         let listener = tokio_core::net::TcpListener::from_listener(listener, &addr, &handle)
             .expect("could not register listener");
         debug!("registered tcp: {:?}", listener);
+        let incoming =
+            BudgetStream::with_yields(listener.incoming(), poll_budget, budget_yields.clone());
 
         // for each incoming request...
         self.io_loop
             .handle()
-            .spawn(listener
-                       .incoming()
+            .spawn(incoming
                        .for_each(move |(tcp_stream, src_addr)| {
                 debug!("accepted request from: {}", src_addr);
                 // take the created stream...
-                let (buf_stream, stream_handle) = TcpStream::from_stream(tcp_stream, src_addr);
+                let (response_queue, queue_handle) = ResponseQueue::new();
+                let buf_stream =
+                    TcpStream::from_stream_with_outbound(tcp_stream, src_addr, response_queue);
                 let timeout_stream = try!(TimeoutStream::new(buf_stream, timeout, &handle));
-                let request_stream = RequestStream::new(timeout_stream, stream_handle);
+                let mut request_stream = RequestStream::with_response_queue(
+                    timeout_stream,
+                    queue_handle,
+                    name_compression,
+                );
+                request_stream.set_padding_block_size(padding_block_size);
+                request_stream.set_response_budget(poll_budget, budget_yields.clone());
+                let request_stream =
+                    BudgetStream::with_yields(request_stream, poll_budget, budget_yields.clone());
                 let handler = handler.clone();
+                let offload = offload.clone();
+                let request_handle = handle.clone();
 
                 // and spawn to the io_loop
                 handle.spawn(request_stream.for_each(move |(request, response_handle)| {
-                        Self::handle_request(request, response_handle, handler.clone())
+                        Self::handle_request(request,
+                                             response_handle,
+                                             handler.clone(),
+                                             offload.clone(),
+                                             &request_handle)
                     })
                     .map_err(move |e| {
                         debug!("error in TCP request_stream src: {:?} error: {}",
@@ -137,37 +280,59 @@ impl <T: RequestHandler> ServerFuture <T> {
                                  -> io::Result<()> {
         let handle = self.io_loop.handle();
         let handler = self.handler.clone();
+        let offload = self.offload.clone();
+        let name_compression = self.name_compression;
+        let padding_block_size = self.padding_block_size;
+        let poll_budget = self.poll_budget;
+        let budget_yields = self.budget_yields.clone();
         // TODO: this is an awkward interface with socketaddr...
         let addr = listener.local_addr().expect("listener is not bound?");
         let listener = tokio_core::net::TcpListener::from_listener(listener, &addr, &handle)
             .expect("could not register listener");
         debug!("registered tcp: {:?}", listener);
+        let incoming =
+            BudgetStream::with_yields(listener.incoming(), poll_budget, budget_yields.clone());
 
         let tls_acceptor = tls_server::new_acceptor(&pkcs12)?;
 
         // for each incoming request...
         self.io_loop.handle().spawn(
-        listener.incoming()
+        incoming
                 .for_each(move |(tcp_stream, src_addr)| {
                   debug!("accepted request from: {}", src_addr);
                   let timeout = timeout.clone();
                   let handle = handle.clone();
                   let handler = handler.clone();
+                  let offload = offload.clone();
+                  let budget_yields = budget_yields.clone();
 
                   // take the created stream...
                   tls_acceptor.accept_async(tcp_stream)
                               .map_err(|e| io::Error::new(io::ErrorKind::ConnectionRefused, format!("tls error: {}", e)))
                               .and_then(move |tls_stream| {
-                                  let (buf_stream, stream_handle) =
-                                      TlsStream::from_stream(tls_stream, src_addr.clone());
+                                  let (response_queue, queue_handle) = ResponseQueue::new();
+                                  let buf_stream = TcpStream::from_stream_with_outbound(
+                                      tls_stream,
+                                      src_addr,
+                                      response_queue,
+                                  );
                                   let timeout_stream = try!(TimeoutStream::new(buf_stream, timeout, &handle));
-                                  let request_stream = RequestStream::new(timeout_stream, stream_handle);
+                                  let mut request_stream = RequestStream::with_response_queue(
+                                      timeout_stream,
+                                      queue_handle,
+                                      name_compression,
+                                  );
+                                  request_stream.set_padding_block_size(padding_block_size);
+                                  request_stream.set_response_budget(poll_budget, budget_yields.clone());
+                                  let request_stream = BudgetStream::with_yields(request_stream, poll_budget, budget_yields);
                                   let handler = handler.clone();
+                                  let offload = offload.clone();
+                                  let request_handle = handle.clone();
 
                                   // and spawn to the io_loop
                                   handle.spawn(
                                   request_stream.for_each(move |(request, response_handle)| {
-                                      Self::handle_request(request, response_handle, handler.clone())
+                                      Self::handle_request(request, response_handle, handler.clone(), offload.clone(), &request_handle)
                                   })
                               .map_err(move |e| debug!("error in TCP request_stream src: {:?} error: {}", src_addr, e))
                               );
@@ -198,12 +363,54 @@ impl <T: RequestHandler> ServerFuture <T> {
         &mut self.io_loop
     }
 
+    /// Returns the handler for all requests, e.g. to inspect the `Catalog` while the server runs
+    pub fn handler(&self) -> Arc<T> {
+        self.handler.clone()
+    }
+
+    /// The responses to a request on the worker pool, those of `RequestHandler::response_stream`,
+    ///  or else those sent by `RequestHandler::handle_request_stream`
+    fn pooled_responses(handler: &T, request: &Request) -> io::Result<Responses> {
+        if let Some(responses) = handler.response_stream(request) {
+            return Ok(responses);
+        }
+
+        let mut responses = Vec::new();
+        try!(handler.handle_request_stream(request, &mut |response| {
+            responses.push(response);
+            Ok(())
+        }));
+        Ok(Box::new(stream::iter_ok(responses)))
+    }
+
     fn handle_request(request: Request,
                       mut response_handle: ResponseHandle,
-                      handler: Arc<T>)
+                      handler: Arc<T>,
+                      offload: Option<Offload>,
+                      handle: &Handle)
                       -> io::Result<()> {
-        let response = handler.handle_request(&request);
-        response_handle.send(response)
+        // e.g. an update forwarded to the primary, answered once the primary has answered
+        if let Some(response) = handler.forward_request(&request, handle) {
+            let id = request.message.id();
+            handle.spawn(response
+                             .and_then(move |response| response_handle.send(response))
+                             .map_err(move |e| debug!("error forwarding request {}: {}", id, e)));
+            return Ok(());
+        }
+
+        // the request is verified, and each of its responses produced, on the pool
+        if let Some(offload) = offload {
+            if handler.is_cpu_intensive(&request) {
+                return response_handle.send_stream(offload(request), handle);
+            }
+        }
+
+        // e.g. a zone transfer, whose messages are produced as the connection writes them
+        if let Some(responses) = handler.response_stream(&request) {
+            return response_handle.send_stream(responses, handle);
+        }
+
+        handler.handle_request_stream(&request, &mut |response| response_handle.send(response))
     }
 }
 
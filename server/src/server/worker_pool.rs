@@ -0,0 +1,151 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Bounded pool of worker threads for offloading CPU intensive work from the IO loop
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+/// A unit of work to be run on the pool, `Box<FnOnce>` can not be called directly
+trait Job: Send {
+    fn run(self: Box<Self>);
+}
+
+impl<F: FnOnce() + Send> Job for F {
+    fn run(self: Box<Self>) {
+        (*self)()
+    }
+}
+
+/// A fixed size set of threads, fed from a bounded queue.
+///
+/// The queue is what provides backpressure, once `queue_depth` jobs are waiting for a worker,
+///  `try_execute` will refuse any more work. This is intended for things like DNSSEC signing and
+///  signature verification, where a burst of requests should not be allowed to stall the IO loop
+///  that is responsible for reading and writing packets.
+pub struct WorkerPool {
+    sender: Option<SyncSender<Box<Job>>>,
+    workers: Vec<JoinHandle<()>>,
+    queue_depth: usize,
+}
+
+impl WorkerPool {
+    /// Spawns a new pool of worker threads
+    ///
+    /// # Arguments
+    ///
+    /// * `threads` - number of worker threads to spawn, must be greater than 0
+    /// * `queue_depth` - number of jobs which can be waiting for a free worker before new jobs are
+    ///                   rejected
+    pub fn new(threads: usize, queue_depth: usize) -> io::Result<Self> {
+        if threads == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "worker pool requires at least one thread",
+            ));
+        }
+
+        let (sender, receiver) = mpsc::sync_channel::<Box<Job>>(queue_depth);
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let mut workers = Vec::with_capacity(threads);
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            let worker = try!(
+                thread::Builder::new()
+                    .name(format!("trust-dns-worker-{}", i))
+                    .spawn(move || Self::work(receiver))
+            );
+
+            workers.push(worker);
+        }
+
+        debug!(
+            "started worker pool, threads: {} queue_depth: {}",
+            threads,
+            queue_depth
+        );
+        Ok(WorkerPool {
+            sender: Some(sender),
+            workers: workers,
+            queue_depth: queue_depth,
+        })
+    }
+
+    /// Number of threads in the pool
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Number of jobs which may wait for a worker before work is rejected
+    pub fn queue_depth(&self) -> usize {
+        self.queue_depth
+    }
+
+    /// Attempt to run the job on one of the workers
+    ///
+    /// # Returns
+    ///
+    /// false if the queue is full (or the pool is shutting down), in which case the job was not
+    ///  accepted and will never be run. It's up to the caller to decide what to do in this case,
+    ///  e.g. perform the work inline or reject the request.
+    pub fn try_execute<F>(&self, job: F) -> bool
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let sender = match self.sender {
+            Some(ref sender) => sender,
+            None => return false,
+        };
+
+        match sender.try_send(Box::new(job)) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                debug!("worker pool queue is full, rejecting job");
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                warn!("all workers in the pool have stopped, rejecting job");
+                false
+            }
+        }
+    }
+
+    fn work(receiver: Arc<Mutex<Receiver<Box<Job>>>>) {
+        loop {
+            // the lock is only held while waiting for the next job, not while running it
+            let job = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(_) => {
+                    error!("worker pool receiver poisoned, stopping worker");
+                    return;
+                }
+            };
+
+            match job {
+                Ok(job) => job.run(),
+                // the sender was dropped, the pool is shutting down
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // dropping the sender will cause all the workers to exit once the queue is drained
+        self.sender.take();
+
+        for worker in self.workers.drain(..) {
+            if let Err(_) = worker.join() {
+                warn!("worker thread panicked");
+            }
+        }
+    }
+}
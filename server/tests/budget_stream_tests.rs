@@ -0,0 +1,78 @@
+extern crate futures;
+extern crate tokio_core;
+extern crate trust_dns_server;
+
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::Future;
+#[allow(deprecated)]
+use futures::stream::{iter, Stream};
+use tokio_core::reactor::Core;
+
+use trust_dns_server::server::BudgetStream;
+
+#[test]
+fn test_all_items() {
+    #[allow(deprecated)]
+    let sequence = iter((0..10).map(Ok::<usize, io::Error>));
+    let mut core = Core::new().expect("could not get core");
+
+    let budget_stream = BudgetStream::new(sequence, 3);
+    assert_eq!(budget_stream.budget(), 3);
+
+    let items = core.run(budget_stream.collect()).expect("collect failed");
+    assert_eq!(items, (0..10).collect::<Vec<usize>>());
+}
+
+#[test]
+fn test_zero_budget() {
+    #[allow(deprecated)]
+    let sequence = iter((0..2).map(Ok::<usize, io::Error>));
+    let mut core = Core::new().expect("could not get core");
+
+    let budget_stream = BudgetStream::new(sequence, 0);
+    assert_eq!(budget_stream.budget(), 1);
+
+    let items = core.run(budget_stream.collect()).expect("collect failed");
+    assert_eq!(items, vec![0, 1]);
+}
+
+#[test]
+fn test_fairness() {
+    let mut core = Core::new().expect("could not get core");
+    let order = Rc::new(RefCell::new(Vec::new()));
+    let yields = Arc::new(AtomicUsize::new(0));
+
+    // two streams which are always ready, polled in the same task, take turns of 3 items
+    let streams = (0..2)
+        .map(|id| {
+            let order = order.clone();
+            #[allow(deprecated)]
+            let sequence = iter((0..10).map(Ok::<usize, io::Error>));
+            BudgetStream::with_yields(sequence, 3, yields.clone()).for_each(move |item| {
+                order.borrow_mut().push((id, item));
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut streams = streams.into_iter();
+    let first = streams.next().unwrap();
+    let second = streams.next().unwrap();
+    core.run(first.join(second)).expect("streams failed");
+
+    let mut expected = Vec::new();
+    for turn in &[0..3, 3..6, 6..9, 9..10] {
+        for id in 0..2 {
+            expected.extend(turn.clone().map(|item| (id, item)));
+        }
+    }
+    assert_eq!(*order.borrow(), expected);
+
+    // the last turn ends with the streams, not the budget
+    assert_eq!(yields.load(Ordering::Relaxed), 6);
+}
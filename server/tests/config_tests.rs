@@ -20,15 +20,15 @@ extern crate trust_dns_server;
 
 use std::env;
 use std::path::{Path, PathBuf};
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::Duration;
 
 use log::LogLevel;
 
-use trust_dns::rr::Name;
-use trust_dns::rr::dnssec::Algorithm;
+use trust_dns::rr::{Name, RecordType};
+use trust_dns::rr::dnssec::{Algorithm, TsigAlgorithm};
 
-use trust_dns_server::authority::ZoneType;
+use trust_dns_server::authority::{Denial, IllegalDataPolicy, ZoneType};
 use trust_dns_server::config::*;
 
 #[test]
@@ -53,6 +53,13 @@ fn test_read_config() {
     assert_eq!(config.get_tcp_request_timeout(), Duration::from_secs(5));
     assert_eq!(config.get_log_level(), LogLevel::Info);
     assert_eq!(config.get_directory(), Path::new("/var/named"));
+    assert!(config.get_worker_threads() > 0);
+    assert_eq!(config.get_worker_queue_depth(), 128);
+    assert_eq!(config.get_poll_budget(), 32);
+    assert_eq!(config.get_response_cache_size(), 0);
+    assert_eq!(config.get_health_check_interval(), Duration::from_secs(60));
+    assert!(!config.get_health_query());
+    assert!(!config.get_require_cookies());
     assert_eq!(
         config.get_zones(),
         [
@@ -154,6 +161,13 @@ fn test_parse_toml() {
     assert_eq!(config.get_directory(), Path::new("/dev/null"));
 }
 
+#[test]
+fn test_parse_toml_error_location() {
+    let result = "listen_port = 2053\nlisten_addrs_ipv4 = [\"0.0.0.0\"".parse::<Config>();
+    let error = result.err().expect("config should not parse");
+    assert!(format!("{}", error).contains("line 2"), "{}", error);
+}
+
 #[test]
 fn test_parse_zone_keys() {
     let config: Config = "
@@ -229,6 +243,311 @@ signer_name = \"ns.example.com.\"
     );
 }
 
+#[test]
+fn test_parse_zone_primary() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Slave\"
+file = \"example.com.zone\"
+primary = \"10.0.0.1:53\"
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Slave\"
+file = \"example.net.zone\"
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(
+        config.get_zones()[0].get_primary().unwrap(),
+        Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 53))
+    );
+    assert_eq!(config.get_zones()[1].get_primary().unwrap(), None);
+
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Slave\"
+file = \"example.com.zone\"
+primary = \"not an address\"
+"
+        .parse()
+        .unwrap();
+    assert!(config.get_zones()[0].get_primary().is_err());
+}
+
+#[test]
+fn test_parse_zone_also_notify() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+also_notify = [\"10.0.0.2:53\", \"[2001:db8::2]:5353\"]
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Master\"
+file = \"example.net.zone\"
+also_notify = [\"10.0.0.2\"]
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(
+        config.get_zones()[0].get_also_notify().unwrap(),
+        vec![
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 53),
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2)), 5353),
+        ]
+    );
+    // the port is required
+    assert!(config.get_zones()[1].get_also_notify().is_err());
+}
+
+#[test]
+fn test_parse_zone_illegal_data() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+illegal_data = \"Repair\"
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Master\"
+file = \"example.net.zone\"
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(
+        config.get_zones()[0].get_illegal_data(),
+        IllegalDataPolicy::Repair
+    );
+    assert_eq!(
+        config.get_zones()[1].get_illegal_data(),
+        IllegalDataPolicy::Warn
+    );
+}
+
+#[test]
+fn test_parse_zone_reload() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+reload_interval = 30
+auto_serial = true
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Master\"
+file = \"example.net.zone\"
+reload_interval = 0
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(
+        config.get_zones()[0].get_reload_interval(),
+        Some(Duration::from_secs(30))
+    );
+    assert!(config.get_zones()[0].is_auto_serial());
+    assert_eq!(config.get_zones()[1].get_reload_interval(), None);
+    assert!(!config.get_zones()[1].is_auto_serial());
+}
+
+#[test]
+fn test_parse_zone_sqlite_store() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+allow_update = true
+sqlite_store = true
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Master\"
+file = \"example.net.zone\"
+"
+        .parse()
+        .unwrap();
+
+    assert!(config.get_zones()[0].is_sqlite_store());
+    assert!(!config.get_zones()[1].is_sqlite_store());
+}
+
+#[test]
+fn test_parse_zone_inline_signing() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+enable_dnssec = true
+inline_signing = true
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Master\"
+file = \"example.net.zone\"
+"
+        .parse()
+        .unwrap();
+
+    assert!(config.get_zones()[0].is_inline_signing());
+    assert!(!config.get_zones()[1].is_inline_signing());
+}
+
+#[test]
+fn test_parse_zone_response_policy() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Slave\"
+file = \"example.com.zone\"
+min_ttl = 60
+max_ttl = 3600
+hide_txt = [\"^google-site-verification=\", \"^MS=\"]
+refuse_any = true
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Master\"
+file = \"example.net.zone\"
+
+[[zones]]
+zone = \"example.org\"
+zone_type = \"Master\"
+file = \"example.org.zone\"
+hide_txt = [\"(\"]
+"
+        .parse()
+        .unwrap();
+
+    let policy = config.get_zones()[0].get_response_policy().unwrap().unwrap();
+    assert_eq!(policy.clamp_ttl(5), 60);
+    assert_eq!(policy.clamp_ttl(300), 300);
+    assert_eq!(policy.clamp_ttl(86400), 3600);
+    assert!(policy.is_refused(RecordType::ANY));
+    assert!(!policy.is_refused(RecordType::A));
+
+    assert!(config.get_zones()[1].get_response_policy().unwrap().is_none());
+    assert!(config.get_zones()[2].get_response_policy().is_err());
+}
+
+#[test]
+fn test_parse_zone_nsec3() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+enable_dnssec = true
+nsec3 = true
+nsec3_iterations = 5
+nsec3_salt = \"AABBCCDD\"
+nsec3_opt_out = true
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Master\"
+file = \"example.net.zone\"
+enable_dnssec = true
+nsec3 = true
+
+[[zones]]
+zone = \"example.org\"
+zone_type = \"Master\"
+file = \"example.org.zone\"
+enable_dnssec = true
+
+[[zones]]
+zone = \"example.info\"
+zone_type = \"Master\"
+file = \"example.info.zone\"
+enable_dnssec = true
+nsec3 = true
+nsec3_salt = \"not hex\"
+
+[[zones]]
+zone = \"example.biz\"
+zone_type = \"Master\"
+file = \"example.biz.zone\"
+enable_dnssec = true
+nsec3 = true
+nsec3_iterations = 101
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(
+        config.get_zones()[0].get_denial().unwrap(),
+        Denial::Nsec3 {
+            iterations: 5,
+            salt: vec![0xAA, 0xBB, 0xCC, 0xDD],
+            opt_out: true,
+        }
+    );
+    assert_eq!(
+        config.get_zones()[1].get_denial().unwrap(),
+        Denial::Nsec3 {
+            iterations: 0,
+            salt: vec![],
+            opt_out: false,
+        }
+    );
+    assert_eq!(config.get_zones()[2].get_denial().unwrap(), Denial::Nsec);
+    assert!(config.get_zones()[3].get_denial().is_err());
+    assert!(config.get_zones()[4].get_denial().is_err());
+}
+
+#[test]
+fn test_parse_control_addr() {
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config.get_control_addr().unwrap(), None);
+
+    let config: Config = "control_addr = \"127.0.0.1:9953\"".parse().unwrap();
+    assert_eq!(
+        config.get_control_addr().unwrap(),
+        Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9953))
+    );
+
+    let config: Config = "control_addr = \"[::1]:9953\"".parse().unwrap();
+    assert_eq!(
+        config.get_control_addr().unwrap(),
+        Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), 9953))
+    );
+
+    let config: Config = "control_addr = \"localhost\"".parse().unwrap();
+    assert!(config.get_control_addr().is_err());
+
+    // the commands are not authenticated, so they may only come from the host of the server
+    let config: Config = "control_addr = \"0.0.0.0:9953\"".parse().unwrap();
+    assert!(config.get_control_addr().is_err());
+    let config: Config = "control_addr = \"192.0.2.1:9953\"".parse().unwrap();
+    assert!(config.get_control_addr().is_err());
+}
+
+#[test]
+fn test_parse_health_query() {
+    let config: Config = "health_query = true".parse().unwrap();
+    assert!(config.get_health_query());
+}
+
+#[test]
+fn test_parse_poll_budget() {
+    let config: Config = "poll_budget = 4".parse().unwrap();
+    assert_eq!(config.get_poll_budget(), 4);
+}
+
 #[test]
 fn test_parse_tls() {
     // defaults
@@ -236,17 +555,75 @@ fn test_parse_tls() {
 
     assert_eq!(config.get_tls_listen_port(), 853);
     assert_eq!(config.get_tls_cert(), None);
+    assert_eq!(config.get_tls_padding_block_size(), Some(468));
 
     let config: Config = "
 tls_cert = { path = \"path/to/some.pkcs12\" }
 tls_listen_port = 8853
+tls_padding_block_size = 0
   "
         .parse()
         .unwrap();
 
     assert_eq!(config.get_tls_listen_port(), 8853);
+    assert_eq!(config.get_tls_padding_block_size(), None);
     assert_eq!(
         config.get_tls_cert().unwrap().get_path(),
         Path::new("path/to/some.pkcs12")
     );
 }
+
+#[test]
+fn test_parse_tsig_keys() {
+    let config: Config = "".parse().unwrap();
+    assert!(config.get_tsig_keys().is_empty());
+
+    let config: Config = "
+[[tsig_keys]]
+name = \"update-key.example.com.\"
+algorithm = \"hmac-sha256\"
+secret = \"c2VjcmV0\"
+
+[[tsig_keys]]
+name = \"transfer-key.\"
+algorithm = \"hmac-sha512.\"
+secret = \"not base64!\"
+fudge = 60
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+allow_update = true
+update_tsig_keys = [\"update-key.example.com.\"]
+keys = []
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Master\"
+file = \"example.net.zone\"
+keys = []
+  "
+        .parse()
+        .unwrap();
+
+    let keys = config.get_tsig_keys();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(
+        keys[0].name().unwrap(),
+        Name::parse("update-key.example.com.", None).unwrap()
+    );
+    assert_eq!(keys[0].algorithm().unwrap(), TsigAlgorithm::HmacSha256);
+    assert_eq!(keys[0].secret().unwrap(), b"secret".to_vec());
+    assert_eq!(keys[0].fudge(), 300);
+
+    assert_eq!(keys[1].algorithm().unwrap(), TsigAlgorithm::HmacSha512);
+    assert!(keys[1].secret().is_err());
+    assert_eq!(keys[1].fudge(), 60);
+
+    assert_eq!(
+        config.get_zones()[0].get_update_tsig_keys().unwrap(),
+        vec![Name::parse("update-key.example.com.", None).unwrap()]
+    );
+    assert!(config.get_zones()[1].get_update_tsig_keys().unwrap().is_empty());
+}
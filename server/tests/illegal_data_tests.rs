@@ -0,0 +1,132 @@
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+use trust_dns::rr::*;
+use trust_dns::rr::rdata::*;
+use trust_dns_server::authority::*;
+
+fn name(name: &str) -> Name {
+    Name::parse(name, None).unwrap()
+}
+
+fn soa(origin: Name) -> Record {
+    Record::from_rdata(
+        origin,
+        3600,
+        RecordType::SOA,
+        RData::SOA(SOA::new(
+            name("sns.dns.icann.org."),
+            name("noc.dns.icann.org."),
+            2017092201,
+            7200,
+            3600,
+            1209600,
+            3600,
+        )),
+    )
+}
+
+fn cname(owner: Name) -> Record {
+    Record::from_rdata(
+        owner,
+        86400,
+        RecordType::CNAME,
+        RData::CNAME(name("www.example.net.")),
+    )
+}
+
+fn a(owner: Name) -> Record {
+    Record::from_rdata(
+        owner,
+        86400,
+        RecordType::A,
+        RData::A(Ipv4Addr::new(93, 184, 216, 34)),
+    )
+}
+
+/// A zone with a CNAME at the apex, a CNAME with an A at www, and an SOA at sub
+fn create_authority() -> Authority {
+    let mut authority = Authority::new(
+        name("example.com."),
+        BTreeMap::new(),
+        ZoneType::Master,
+        false,
+        false,
+    );
+
+    for record in vec![
+        soa(name("example.com.")),
+        cname(name("example.com.")),
+        cname(name("www.example.com.")),
+        a(name("www.example.com.")),
+        cname(name("alias.example.com.")),
+        soa(name("sub.example.com.")),
+    ]
+    {
+        authority.upsert(record, 0);
+    }
+
+    authority
+}
+
+#[test]
+fn test_warn() {
+    let mut authority = create_authority();
+    let illegal = authority.check_illegal_data(IllegalDataPolicy::Warn).unwrap();
+
+    assert_eq!(illegal.len(), 3);
+    assert!(illegal.contains(&IllegalData::CnameAtApex(name("example.com."))));
+    assert!(illegal.contains(
+        &IllegalData::CnameAndOtherData(name("www.example.com.")),
+    ));
+    assert!(illegal.contains(&IllegalData::DuplicateSoa(name("sub.example.com."))));
+
+    // nothing is removed
+    assert_eq!(authority.records().len(), 6);
+}
+
+#[test]
+fn test_reject() {
+    let mut authority = create_authority();
+
+    assert_eq!(
+        authority.check_illegal_data(IllegalDataPolicy::Reject),
+        Err(IllegalData::CnameAtApex(name("example.com.")))
+    );
+}
+
+#[test]
+fn test_repair() {
+    let mut authority = create_authority();
+    assert_eq!(
+        authority
+            .check_illegal_data(IllegalDataPolicy::Repair)
+            .unwrap()
+            .len(),
+        3
+    );
+
+    {
+        let records = authority.records();
+        let contains = |owner: &str, record_type: RecordType| {
+            records
+                .get_rrset(&RrKey::new(&name(owner), record_type))
+                .is_some()
+        };
+        assert_eq!(records.len(), 3);
+        assert!(contains("example.com.", RecordType::SOA));
+        assert!(contains("www.example.com.", RecordType::A));
+        assert!(contains("alias.example.com.", RecordType::CNAME));
+    }
+
+    // nothing illegal is left
+    assert!(
+        authority
+            .check_illegal_data(IllegalDataPolicy::Reject)
+            .unwrap()
+            .is_empty()
+    );
+}
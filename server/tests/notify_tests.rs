@@ -0,0 +1,239 @@
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::collections::BTreeMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use trust_dns::op::{Message, MessageType, OpCode};
+use trust_dns::rr::*;
+use trust_dns::rr::rdata::*;
+use trust_dns_server::authority::*;
+
+fn name(name: &str) -> Name {
+    Name::parse(name, None).unwrap()
+}
+
+/// A zone with only an SOA, so that only the also notify addresses are notified
+fn create_authority(zone_type: ZoneType, serial: u32) -> Authority {
+    let origin = name("example.com.");
+    let mut authority = Authority::new(origin.clone(), BTreeMap::new(), zone_type, false, false);
+    authority.upsert(
+        Record::from_rdata(
+            origin,
+            3600,
+            RecordType::SOA,
+            RData::SOA(SOA::new(
+                name("sns.dns.icann.org."),
+                name("noc.dns.icann.org."),
+                serial,
+                7200,
+                3600,
+                1209600,
+                3600,
+            )),
+        ),
+        serial,
+    );
+
+    authority
+}
+
+fn create_catalog(zone_type: ZoneType, secondary: SocketAddr) -> Catalog {
+    let mut catalog = Catalog::new();
+    catalog.upsert(name("example.com."), create_authority(zone_type, 2017092201));
+    catalog.set_also_notify(name("example.com."), vec![secondary]);
+    catalog.set_notify_retry(NotifyRetry {
+        attempts: 3,
+        timeout: Duration::from_millis(200),
+        max_jitter: Duration::from_millis(0),
+    });
+
+    catalog
+}
+
+/// A secondary which ignores the first `ignore` NOTIFYs and responds to the next, returning
+///  that NOTIFY
+fn spawn_secondary(ignore: usize) -> (SocketAddr, thread::JoinHandle<Message>) {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let addr = socket.local_addr().unwrap();
+
+    let secondary = thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        for _ in 0..ignore {
+            socket.recv_from(&mut buffer).unwrap();
+        }
+
+        let (len, from) = socket.recv_from(&mut buffer).unwrap();
+        let notify = Message::from_vec(&buffer[..len]).unwrap();
+
+        let mut response = Message::new();
+        response
+            .set_id(notify.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(OpCode::Notify);
+        socket.send_to(&response.to_vec().unwrap(), from).unwrap();
+
+        notify
+    });
+
+    (addr, secondary)
+}
+
+fn wait_for_notify(catalog: &Catalog, secondary: SocketAddr) -> NotifyStatus {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let status = catalog
+            .zone_stats()
+            .get(&name("example.com."))
+            .and_then(|stats| stats.notify_status().get(&secondary).cloned());
+
+        match status {
+            Some(NotifyStatus::Pending { .. }) | None => (),
+            Some(status) => return status,
+        }
+
+        assert!(Instant::now() < deadline, "notify did not complete");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn test_notify() {
+    let (addr, secondary) = spawn_secondary(0);
+    let catalog = create_catalog(ZoneType::Master, addr);
+
+    assert!(catalog.notify(&name("example.com.")));
+    let notify = secondary.join().unwrap();
+
+    assert_eq!(notify.op_code(), OpCode::Notify);
+    assert!(notify.authoritative());
+    assert_eq!(notify.queries()[0].name(), &name("example.com."));
+    assert_eq!(notify.queries()[0].query_type(), RecordType::SOA);
+    assert_eq!(notify.answers()[0].rr_type(), RecordType::SOA);
+
+    assert_eq!(
+        wait_for_notify(&catalog, addr),
+        NotifyStatus::Succeeded {
+            serial: 2017092201,
+            attempts: 1,
+        }
+    );
+}
+
+#[test]
+fn test_notify_retry() {
+    let (addr, secondary) = spawn_secondary(1);
+    let catalog = create_catalog(ZoneType::Master, addr);
+
+    assert!(catalog.notify(&name("example.com.")));
+    secondary.join().unwrap();
+
+    assert_eq!(
+        wait_for_notify(&catalog, addr),
+        NotifyStatus::Succeeded {
+            serial: 2017092201,
+            attempts: 2,
+        }
+    );
+}
+
+#[test]
+fn test_notify_failed() {
+    // bound, so nothing else gets the port, but never responds
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    let catalog = create_catalog(ZoneType::Master, addr);
+
+    assert!(catalog.notify(&name("example.com.")));
+    match wait_for_notify(&catalog, addr) {
+        NotifyStatus::Failed { serial, .. } => assert_eq!(serial, 2017092201),
+        status => panic!("expected failure: {:?}", status),
+    }
+}
+
+#[test]
+fn test_notify_on_replace() {
+    let (addr, secondary) = spawn_secondary(0);
+    let catalog = create_catalog(ZoneType::Master, addr);
+
+    assert!(catalog.replace(
+        &name("example.com."),
+        create_authority(ZoneType::Master, 2017092202),
+    ));
+    let notify = secondary.join().unwrap();
+
+    match *notify.answers()[0].rdata() {
+        RData::SOA(ref soa) => assert_eq!(soa.serial(), 2017092202),
+        ref rdata => panic!("expected SOA: {:?}", rdata),
+    }
+}
+
+#[test]
+fn test_notify_latest_serial() {
+    // a secondary which only responds to the NOTIFYs of the second serial, and returns the
+    //  serials of all the NOTIFYs it received
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+    let addr = socket.local_addr().unwrap();
+    let secondary = thread::spawn(move || {
+        let mut serials = Vec::new();
+        let mut buffer = [0u8; 4096];
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while Instant::now() < deadline {
+            let (len, from) = match socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(_) => continue,
+            };
+            let notify = Message::from_vec(&buffer[..len]).unwrap();
+            let serial = match *notify.answers()[0].rdata() {
+                RData::SOA(ref soa) => soa.serial(),
+                ref rdata => panic!("expected SOA: {:?}", rdata),
+            };
+            serials.push(serial);
+
+            if serial == 2017092202 {
+                let mut response = Message::new();
+                response
+                    .set_id(notify.id())
+                    .set_message_type(MessageType::Response)
+                    .set_op_code(OpCode::Notify);
+                socket.send_to(&response.to_vec().unwrap(), from).unwrap();
+            }
+        }
+
+        serials
+    });
+
+    let catalog = create_catalog(ZoneType::Master, addr);
+    assert!(catalog.notify(&name("example.com.")));
+    assert!(catalog.replace(
+        &name("example.com."),
+        create_authority(ZoneType::Master, 2017092202),
+    ));
+
+    assert_eq!(
+        wait_for_notify(&catalog, addr),
+        NotifyStatus::Succeeded {
+            serial: 2017092202,
+            attempts: 1,
+        }
+    );
+
+    // the earlier serial is not retried once the later one is being sent
+    let serials = secondary.join().unwrap();
+    let first_later = serials.iter().position(|serial| *serial == 2017092202).unwrap();
+    assert!(serials[first_later..].iter().all(|serial| *serial == 2017092202));
+    assert_eq!(serials.len(), first_later + 1);
+}
+
+#[test]
+fn test_notify_only_primaries() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let catalog = create_catalog(ZoneType::Slave, socket.local_addr().unwrap());
+
+    assert!(!catalog.notify(&name("example.com.")));
+    assert!(!catalog.notify(&name("example.net.")));
+}
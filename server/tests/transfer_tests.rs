@@ -0,0 +1,160 @@
+extern crate trust_dns;
+extern crate trust_dns_proto;
+extern crate trust_dns_server;
+
+use std::net::Ipv4Addr;
+
+use trust_dns::rr::*;
+use trust_dns::rr::rdata::*;
+use trust_dns_server::authority::*;
+use trust_dns_server::authority::transfer::load_transfer;
+use trust_dns_server::error::TransferErrorKind;
+
+fn origin() -> Name {
+    Name::parse("example.com.", None).unwrap()
+}
+
+fn create_transfer() -> Vec<Record> {
+    let soa = Record::new()
+        .set_name(origin())
+        .set_ttl(3600)
+        .set_rr_type(RecordType::SOA)
+        .set_dns_class(DNSClass::IN)
+        .set_rdata(RData::SOA(SOA::new(
+            Name::parse("sns.dns.icann.org.", None).unwrap(),
+            Name::parse("noc.dns.icann.org.", None).unwrap(),
+            2017092201,
+            7200,
+            3600,
+            1209600,
+            3600,
+        )))
+        .clone();
+
+    let www = Record::new()
+        .set_name(Name::parse("www.example.com.", None).unwrap())
+        .set_ttl(86400)
+        .set_rr_type(RecordType::A)
+        .set_dns_class(DNSClass::IN)
+        .set_rdata(RData::A(Ipv4Addr::new(93, 184, 216, 34)))
+        .clone();
+
+    // an AXFR starts and ends with the SOA
+    vec![soa.clone(), www, soa]
+}
+
+#[test]
+fn test_load_transfer() {
+    let authority = load_transfer(
+        origin(),
+        create_transfer(),
+        ZoneType::Slave,
+        &TransferValidation::new(),
+    ).expect("transfer should load");
+
+    assert_eq!(authority.serial(), 2017092201);
+    assert_eq!(authority.zone_type(), ZoneType::Slave);
+}
+
+#[test]
+fn test_missing_soa() {
+    let records = create_transfer()
+        .into_iter()
+        .filter(|r| r.rr_type() != RecordType::SOA)
+        .collect::<Vec<_>>();
+
+    match *TransferValidation::new()
+        .validate(&origin(), &records)
+        .unwrap_err()
+        .kind() {
+        TransferErrorKind::MissingSoa(..) => (),
+        ref other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn test_out_of_zone() {
+    let mut records = create_transfer();
+    records.push(
+        Record::new()
+            .set_name(Name::parse("www.example.net.", None).unwrap())
+            .set_ttl(86400)
+            .set_rr_type(RecordType::A)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::A(Ipv4Addr::new(10, 0, 0, 1)))
+            .clone(),
+    );
+
+    match *TransferValidation::new()
+        .validate(&origin(), &records)
+        .unwrap_err()
+        .kind() {
+        TransferErrorKind::OutOfZone(..) => (),
+        ref other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn test_missing_zonemd() {
+    let mut validation = TransferValidation::new();
+    validation.set_verify_zonemd(true);
+
+    match *validation
+        .validate(&origin(), &create_transfer())
+        .unwrap_err()
+        .kind() {
+        TransferErrorKind::MissingZonemd(..) => (),
+        ref other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+#[cfg(feature = "dnssec")]
+fn test_zonemd() {
+    use trust_dns::rr::dnssec::DigestType;
+    use trust_dns_proto::rr::rdata::zonemd;
+
+    let mut records = create_transfer();
+    let digest = zonemd::digest(&origin(), &records, DigestType::SHA384).unwrap();
+    records.push(
+        Record::new()
+            .set_name(origin())
+            .set_ttl(3600)
+            .set_rr_type(RecordType::ZONEMD)
+            .set_dns_class(DNSClass::IN)
+            .set_rdata(RData::ZONEMD(ZONEMD::new(
+                2017092201,
+                zonemd::SCHEME_SIMPLE,
+                zonemd::HASH_SHA384,
+                digest.as_ref().to_vec(),
+            )))
+            .clone(),
+    );
+
+    let mut validation = TransferValidation::new();
+    validation.set_verify_zonemd(true);
+    validation.validate(&origin(), &records).expect(
+        "ZONEMD should match",
+    );
+
+    // tamper with the zone
+    records[1].set_rdata(RData::A(Ipv4Addr::new(10, 0, 0, 1)));
+    match *validation.validate(&origin(), &records).unwrap_err().kind() {
+        TransferErrorKind::ZonemdMismatch(..) => (),
+        ref other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn test_unsigned_zone() {
+    let mut validation = TransferValidation::new();
+    validation.set_verify_dnssec(true);
+
+    match *validation
+        .validate(&origin(), &create_transfer())
+        .unwrap_err()
+        .kind() {
+        TransferErrorKind::MissingSignature(_, RecordType::DNSKEY) => (),
+        ref other => panic!("unexpected error: {}", other),
+    }
+}
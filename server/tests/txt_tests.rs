@@ -299,3 +299,696 @@ venera  A       10.1.0.52
         panic!("Not an SRV record!!!") // valid panic, test code
     }
 }
+
+#[test]
+fn test_cds_cdnskey_csync() {
+    let lexer = Lexer::new(
+        "@   IN  SOA     ns      hostmaster (
+                            2017101601 ; SERIAL
+                            7200       ; REFRESH
+                            600        ; RETRY
+                            3600000    ; EXPIRE
+                            60)        ; MINIMUM
+        NS      ns
+        CDS     3613 15 2 ( 3AA5AB37EFCE57F737FC1627013FEE07
+                            BDF241BD10F3B1964AB55C78E79A304B )
+        CDNSKEY 257 3 ED25519 l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=
+        CSYNC   66 3 A NS AAAA
+ns      A       192.0.2.1
+removed CDS     0 0 0 00
+        CDNSKEY 0 3 0 AA==",
+    );
+
+    let records = Parser::new().parse(lexer, Some(Name::from_labels(vec!["example", "com"])));
+    if records.is_err() {
+        panic!("failed to parse: {:?}", records.err())
+    }
+
+    let (origin, records) = records.unwrap();
+    let authority = Authority::new(origin, records, ZoneType::Master, false, false);
+
+    let lookup = |name: Vec<&str>, record_type: RecordType| -> RData {
+        authority
+            .lookup(
+                &Name::from_labels(name),
+                record_type,
+                false,
+                SupportedAlgorithms::new(),
+            )
+            .first()
+            .expect("record not found")
+            .rdata()
+            .clone()
+    };
+
+    match lookup(vec!["example", "com"], RecordType::CDS) {
+        RData::CDS(ref cds) => {
+            let ds = cds.ds().expect("expected a DS");
+            assert_eq!(ds.key_tag(), 3613);
+            assert_eq!(*ds.algorithm(), Algorithm::ED25519);
+            assert_eq!(ds.digest_type(), DigestType::SHA256);
+            assert_eq!(ds.digest().len(), 32);
+            assert_eq!(ds.digest()[0], 0x3A);
+        }
+        rdata => panic!("Not a CDS record!!! {:?}", rdata), // valid panic, test code
+    }
+
+    match lookup(vec!["example", "com"], RecordType::CDNSKEY) {
+        RData::CDNSKEY(ref cdnskey) => {
+            let dnskey = cdnskey.dnskey().expect("expected a DNSKEY");
+            assert!(dnskey.zone_key());
+            assert!(dnskey.secure_entry_point());
+            assert!(!dnskey.revoke());
+            assert_eq!(dnskey.algorithm(), Algorithm::ED25519);
+            assert_eq!(dnskey.public_key().len(), 32);
+        }
+        rdata => panic!("Not a CDNSKEY record!!! {:?}", rdata), // valid panic, test code
+    }
+
+    match lookup(vec!["example", "com"], RecordType::CSYNC) {
+        RData::CSYNC(ref csync) => {
+            assert_eq!(csync.soa_serial(), 66);
+            assert!(csync.immediate());
+            assert!(csync.soa_minimum());
+            assert_eq!(
+                csync.type_bit_maps(),
+                &[RecordType::A, RecordType::NS, RecordType::AAAA]
+            );
+        }
+        rdata => panic!("Not a CSYNC record!!! {:?}", rdata), // valid panic, test code
+    }
+
+    match lookup(vec!["removed", "example", "com"], RecordType::CDS) {
+        RData::CDS(ref cds) => assert!(cds.is_delete()),
+        rdata => panic!("Not a CDS record!!! {:?}", rdata), // valid panic, test code
+    }
+
+    match lookup(vec!["removed", "example", "com"], RecordType::CDNSKEY) {
+        RData::CDNSKEY(ref cdnskey) => assert!(cdnskey.is_delete()),
+        rdata => panic!("Not a CDNSKEY record!!! {:?}", rdata), // valid panic, test code
+    }
+}
+
+#[test]
+fn test_tlsa() {
+    use trust_dns::rr::rdata::tlsa::{CertUsage, Matching, Selector};
+
+    let lexer = Lexer::new(
+        "@   IN  SOA     ns      hostmaster (
+                            2017101601 ; SERIAL
+                            7200       ; REFRESH
+                            600        ; RETRY
+                            3600000    ; EXPIRE
+                            60)        ; MINIMUM
+        NS      ns
+_443._tcp.www TLSA 3 1 1 ( 0c72ac70b745ac19998811b131d662c9
+                           ac69dbdbe7cb23e5b514b56664c5d3d6 )",
+    );
+
+    let records = Parser::new().parse(lexer, Some(Name::from_labels(vec!["example", "com"])));
+    if records.is_err() {
+        panic!("failed to parse: {:?}", records.err())
+    }
+
+    let (origin, records) = records.unwrap();
+    let authority = Authority::new(origin, records, ZoneType::Master, false, false);
+
+    let tlsa_record: &Record = authority
+        .lookup(
+            &Name::from_labels(vec!["_443", "_tcp", "www", "example", "com"]),
+            RecordType::TLSA,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .first()
+        .cloned()
+        .unwrap();
+    if let RData::TLSA(ref tlsa) = *tlsa_record.rdata() {
+        assert_eq!(tlsa.cert_usage(), CertUsage::DaneEe);
+        assert_eq!(tlsa.selector(), Selector::Spki);
+        assert_eq!(tlsa.matching(), Matching::Sha256);
+        assert_eq!(tlsa.cert_data().len(), 32);
+        assert_eq!(tlsa.cert_data()[0], 0x0C);
+    } else {
+        panic!("Not a TLSA record!!!") // valid panic, test code
+    }
+}
+
+#[test]
+fn test_sshfp() {
+    use trust_dns::rr::rdata::sshfp::{Algorithm, FingerprintType};
+
+    let lexer = Lexer::new(
+        "@   IN  SOA     ns      hostmaster (
+                            2017101601 ; SERIAL
+                            7200       ; REFRESH
+                            600        ; RETRY
+                            3600000    ; EXPIRE
+                            60)        ; MINIMUM
+        NS      ns
+host    SSHFP   4 2 ( 2a3b4c6e9c2ef8b9fa1e0a0d1f3a6e5c
+                      9d8c7b6a5f4e3d2c1b0a99887766554a )",
+    );
+
+    let records = Parser::new().parse(lexer, Some(Name::from_labels(vec!["example", "com"])));
+    if records.is_err() {
+        panic!("failed to parse: {:?}", records.err())
+    }
+
+    let (origin, records) = records.unwrap();
+    let authority = Authority::new(origin, records, ZoneType::Master, false, false);
+
+    let sshfp_record: &Record = authority
+        .lookup(
+            &Name::from_labels(vec!["host", "example", "com"]),
+            RecordType::SSHFP,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .first()
+        .cloned()
+        .unwrap();
+    if let RData::SSHFP(ref sshfp) = *sshfp_record.rdata() {
+        assert_eq!(sshfp.algorithm(), Algorithm::Ed25519);
+        assert_eq!(sshfp.fingerprint_type(), FingerprintType::SHA256);
+        assert_eq!(sshfp.fingerprint().len(), 32);
+        assert_eq!(sshfp.fingerprint()[0], 0x2A);
+    } else {
+        panic!("Not an SSHFP record!!!") // valid panic, test code
+    }
+}
+
+#[test]
+fn test_svcb() {
+    use trust_dns::rr::rdata::svcb::SvcParamKey;
+
+    let lexer = Lexer::new(
+        "@   IN  SOA     ns      hostmaster (
+                            2017101601 ; SERIAL
+                            7200       ; REFRESH
+                            600        ; RETRY
+                            3600000    ; EXPIRE
+                            60)        ; MINIMUM
+        NS      ns
+        HTTPS   0 svc
+svc     HTTPS   1 . alpn=\"h3,h2\" port=8443 ipv4hint=192.0.2.1,192.0.2.2 no-default-alpn
+_dns    SVCB    1 dns.example.net. alpn=dot ipv6hint=2001:db8::53 ech=AQID key65333=hello",
+    );
+
+    let records = Parser::new().parse(lexer, Some(Name::from_labels(vec!["example", "com"])));
+    if records.is_err() {
+        panic!("failed to parse: {:?}", records.err())
+    }
+
+    let (origin, records) = records.unwrap();
+    let authority = Authority::new(origin, records, ZoneType::Master, false, false);
+
+    let alias: &Record = authority
+        .lookup(
+            &Name::from_labels(vec!["example", "com"]),
+            RecordType::HTTPS,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .first()
+        .cloned()
+        .unwrap();
+    if let RData::HTTPS(ref https) = *alias.rdata() {
+        assert!(https.is_alias());
+        assert_eq!(https.target_name(), &Name::from_labels(vec!["svc", "example", "com"]));
+    } else {
+        panic!("Not an HTTPS record!!!") // valid panic, test code
+    }
+
+    let service: &Record = authority
+        .lookup(
+            &Name::from_labels(vec!["svc", "example", "com"]),
+            RecordType::HTTPS,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .first()
+        .cloned()
+        .unwrap();
+    if let RData::HTTPS(ref https) = *service.rdata() {
+        assert_eq!(https.svc_priority(), 1);
+        assert!(https.target_name().is_root());
+        assert_eq!(https.alpn(), &["h3".to_string(), "h2".to_string()]);
+        assert_eq!(https.port(), Some(8443));
+        assert_eq!(
+            https.ipv4_hint(),
+            &[Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2)]
+        );
+        assert_eq!(https.svc_params().len(), 4);
+    } else {
+        panic!("Not an HTTPS record!!!") // valid panic, test code
+    }
+
+    let dns: &Record = authority
+        .lookup(
+            &Name::from_labels(vec!["_dns", "example", "com"]),
+            RecordType::SVCB,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .first()
+        .cloned()
+        .unwrap();
+    if let RData::SVCB(ref svcb) = *dns.rdata() {
+        assert_eq!(svcb.target_name(), &Name::from_labels(vec!["dns", "example", "net"]));
+        assert_eq!(svcb.alpn(), &["dot".to_string()]);
+        assert_eq!(svcb.ipv6_hint(), &[Ipv6Addr::from_str("2001:db8::53").unwrap()]);
+        assert_eq!(svcb.ech(), Some(&[1u8, 2, 3][..]));
+        assert_eq!(svcb.svc_params().last().unwrap().key(), SvcParamKey::Key(65333));
+    } else {
+        panic!("Not an SVCB record!!!") // valid panic, test code
+    }
+}
+
+#[test]
+fn test_openpgpkey_smimea_uri() {
+    use trust_dns::rr::rdata::tlsa::{CertUsage, Matching, Selector};
+
+    let lexer = Lexer::new(
+        "@   IN  SOA     ns      hostmaster (
+                            2017101601 ; SERIAL
+                            7200       ; REFRESH
+                            600        ; RETRY
+                            3600000    ; EXPIRE
+                            60)        ; MINIMUM
+        NS      ns
+c93f1e400f26708f98cb19d936620da35eec8f72e57f9eec01c1afd6._openpgpkey OPENPGPKEY ( mQENBFVH
+                                                                                  ERIc )
+c93f1e400f26708f98cb19d936620da35eec8f72e57f9eec01c1afd6._smimecert SMIMEA 3 0 1 (
+    0c72ac70b745ac19998811b131d662c9ac69dbdbe7cb23e5b514b56664c5d3d6 )
+_ftp._tcp   URI     10 1 \"ftp://ftp1.example.com/public\"",
+    );
+
+    let records = Parser::new().parse(lexer, Some(Name::from_labels(vec!["example", "com"])));
+    if records.is_err() {
+        panic!("failed to parse: {:?}", records.err())
+    }
+
+    let (origin, records) = records.unwrap();
+    let authority = Authority::new(origin, records, ZoneType::Master, false, false);
+    let hash = "c93f1e400f26708f98cb19d936620da35eec8f72e57f9eec01c1afd6";
+
+    let openpgpkey: &Record = authority
+        .lookup(
+            &Name::from_labels(vec![hash, "_openpgpkey", "example", "com"]),
+            RecordType::OPENPGPKEY,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .first()
+        .cloned()
+        .unwrap();
+    if let RData::OPENPGPKEY(ref openpgpkey) = *openpgpkey.rdata() {
+        assert_eq!(
+            openpgpkey.public_key(),
+            &[0x99, 0x01, 0x0d, 0x04, 0x55, 0x47, 0x11, 0x12, 0x1c]
+        );
+    } else {
+        panic!("Not an OPENPGPKEY record!!!") // valid panic, test code
+    }
+
+    let smimea: &Record = authority
+        .lookup(
+            &Name::from_labels(vec![hash, "_smimecert", "example", "com"]),
+            RecordType::SMIMEA,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .first()
+        .cloned()
+        .unwrap();
+    if let RData::SMIMEA(ref smimea) = *smimea.rdata() {
+        assert_eq!(smimea.cert_usage(), CertUsage::DaneEe);
+        assert_eq!(smimea.selector(), Selector::Full);
+        assert_eq!(smimea.matching(), Matching::Sha256);
+        assert_eq!(smimea.cert_data().len(), 32);
+    } else {
+        panic!("Not an SMIMEA record!!!") // valid panic, test code
+    }
+
+    let uri: &Record = authority
+        .lookup(
+            &Name::from_labels(vec!["_ftp", "_tcp", "example", "com"]),
+            RecordType::URI,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .first()
+        .cloned()
+        .unwrap();
+    if let RData::URI(ref uri) = *uri.rdata() {
+        assert_eq!(uri.priority(), 10);
+        assert_eq!(uri.weight(), 1);
+        assert_eq!(uri.target(), "ftp://ftp1.example.com/public");
+    } else {
+        panic!("Not a URI record!!!") // valid panic, test code
+    }
+}
+
+#[test]
+fn test_naptr() {
+    let lexer = Lexer::new(
+        "@   IN  SOA     ns      hostmaster (
+                            2017101601 ; SERIAL
+                            7200       ; REFRESH
+                            600        ; RETRY
+                            3600000    ; EXPIRE
+                            60)        ; MINIMUM
+        NS      ns
+4.3.2.1.6.7.9.8.6.4 NAPTR 100 10 \"u\" \"E2U+sip\" \"!^\\\\+46(.*)$!sip:\\\\1@example.com!\" .
+                    NAPTR 10 100 \"\" \"\" \"\" enum.example.com.",
+    );
+
+    let records = Parser::new().parse(lexer, Some(Name::from_labels(vec!["example", "com"])));
+    if records.is_err() {
+        panic!("failed to parse: {:?}", records.err())
+    }
+
+    let (origin, records) = records.unwrap();
+    let authority = Authority::new(origin, records, ZoneType::Master, false, false);
+
+    let mut naptrs = authority
+        .lookup(
+            &Name::parse("4.3.2.1.6.7.9.8.6.4.example.com.", None).unwrap(),
+            RecordType::NAPTR,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .iter()
+        .map(|record| if let RData::NAPTR(ref naptr) = *record.rdata() {
+            naptr.clone()
+        } else {
+            panic!("Not a NAPTR record!!!") // valid panic, test code
+        })
+        .collect::<Vec<_>>();
+    naptrs.sort_by_key(|naptr| naptr.order());
+    assert_eq!(naptrs.len(), 2);
+
+    assert!(!naptrs[0].is_terminal());
+    assert_eq!(naptrs[0].regexp(), "");
+    assert_eq!(
+        *naptrs[0].replacement(),
+        Name::parse("enum.example.com.", None).unwrap()
+    );
+
+    assert!(naptrs[1].is_terminal());
+    assert_eq!(naptrs[1].order(), 100);
+    assert_eq!(naptrs[1].preference(), 10);
+    assert_eq!(naptrs[1].services(), "E2U+sip");
+    assert_eq!(naptrs[1].regexp(), "!^\\+46(.*)$!sip:\\1@example.com!");
+    assert!(naptrs[1].replacement().is_root());
+}
+
+#[test]
+fn test_unknown() {
+    let lexer = Lexer::new(
+        "@   IN  SOA     ns      hostmaster (
+                            2017101601 ; SERIAL
+                            7200       ; REFRESH
+                            600        ; RETRY
+                            3600000    ; EXPIRE
+                            60)        ; MINIMUM
+        NS      ns
+opaque  TYPE65534 \\# 6 ( 0a00 0001 beef )
+empty   TYPE65534 \\# 0
+known   A       \\# 4 C0000201",
+    );
+
+    let records = Parser::new().parse(lexer, Some(Name::from_labels(vec!["example", "com"])));
+    if records.is_err() {
+        panic!("failed to parse: {:?}", records.err())
+    }
+
+    let (origin, records) = records.unwrap();
+    let authority = Authority::new(origin, records, ZoneType::Master, false, false);
+
+    let opaque: &Record = authority
+        .lookup(
+            &Name::parse("opaque.example.com.", None).unwrap(),
+            RecordType::Unknown(65534),
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .first()
+        .cloned()
+        .unwrap();
+    assert_eq!(opaque.rr_type(), RecordType::Unknown(65534));
+    assert_eq!(
+        *opaque.rdata(),
+        RData::Unknown {
+            code: 65534,
+            rdata: vec![0x0a, 0x00, 0x00, 0x01, 0xbe, 0xef],
+        }
+    );
+
+    let empty: &Record = authority
+        .lookup(
+            &Name::parse("empty.example.com.", None).unwrap(),
+            RecordType::Unknown(65534),
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .first()
+        .cloned()
+        .unwrap();
+    assert_eq!(
+        *empty.rdata(),
+        RData::Unknown {
+            code: 65534,
+            rdata: vec![],
+        }
+    );
+
+    // the generic form of a known type is decoded as that type
+    let known: &Record = authority
+        .lookup(
+            &Name::parse("known.example.com.", None).unwrap(),
+            RecordType::A,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .first()
+        .cloned()
+        .unwrap();
+    assert_eq!(*known.rdata(), RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+}
+
+fn lookup_rdatas(authority: &Authority, name: &str, record_type: RecordType) -> Vec<RData> {
+    let mut rdatas = authority
+        .lookup(
+            &Name::parse(name, None).unwrap(),
+            record_type,
+            false,
+            SupportedAlgorithms::new(),
+        )
+        .iter()
+        .map(|record| record.rdata().clone())
+        .collect::<Vec<_>>();
+    rdatas.sort();
+    rdatas
+}
+
+#[test]
+fn test_generate() {
+    let lexer = Lexer::new(
+        "$TTL 3600
+@   IN  SOA ns hostmaster 2017101601 7200 600 3600000 60
+        NS  ns
+$GENERATE 1-3 host-$ A 10.0.0.$
+$GENERATE 8-12/2 ${0,2,x}.rev 60 IN PTR host-${-7}.example.com.",
+    );
+
+    let records = Parser::new().parse(lexer, Some(Name::from_labels(vec!["example", "com"])));
+    if records.is_err() {
+        panic!("failed to parse: {:?}", records.err())
+    }
+
+    let (origin, records) = records.unwrap();
+    let authority = Authority::new(origin, records, ZoneType::Master, false, false);
+
+    for i in 1..4 {
+        assert_eq!(
+            lookup_rdatas(&authority, &format!("host-{}.example.com.", i), RecordType::A),
+            vec![RData::A(Ipv4Addr::new(10, 0, 0, i))]
+        );
+    }
+    assert!(lookup_rdatas(&authority, "host-4.example.com.", RecordType::A).is_empty());
+
+    for &(rev, host) in &[("08", "host-1"), ("0a", "host-3"), ("0c", "host-5")] {
+        let ptr = authority
+            .lookup(
+                &Name::parse(&format!("{}.rev.example.com.", rev), None).unwrap(),
+                RecordType::PTR,
+                false,
+                SupportedAlgorithms::new(),
+            )
+            .first()
+            .cloned()
+            .unwrap()
+            .clone();
+        assert_eq!(ptr.ttl(), 60);
+        assert_eq!(
+            *ptr.rdata(),
+            RData::PTR(Name::parse(&format!("{}.example.com.", host), None).unwrap())
+        );
+    }
+    assert!(lookup_rdatas(&authority, "09.rev.example.com.", RecordType::PTR).is_empty());
+
+    // ranges which add too many records, and values which are too wide, are rejected
+    for generate in &[
+        "$TTL 3600\n$GENERATE 0-4294967295 host-$ IN A 10.0.0.1",
+        "$TTL 3600\n$GENERATE 0-65536 host-$ IN A 10.0.0.1",
+        "$TTL 3600\n$GENERATE 1-3 host-$ IN TXT ${0,256}",
+    ] {
+        let lexer = Lexer::new(generate);
+        assert!(
+            Parser::new()
+                .parse(lexer, Some(Name::from_labels(vec!["example", "com"])))
+                .is_err(),
+            "{}",
+            generate
+        );
+    }
+    let lexer = Lexer::new("$TTL 3600\n$GENERATE 0-65535/256 host-$ IN TXT ${0,255}");
+    assert!(
+        Parser::new()
+            .parse(lexer, Some(Name::from_labels(vec!["example", "com"])))
+            .is_ok()
+    );
+}
+
+#[test]
+fn test_include() {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    let include_dir = env::temp_dir().join("trust-dns-test_include");
+    fs::create_dir_all(include_dir.join("sub")).unwrap();
+    File::create(include_dir.join("hosts.zone"))
+        .unwrap()
+        .write_all(
+            b"www A 10.0.0.1
+$ORIGIN mail
+@   A 10.0.0.2
+$INCLUDE sub/more.zone deeper",
+        )
+        .unwrap();
+    File::create(include_dir.join("sub").join("more.zone"))
+        .unwrap()
+        .write_all(b"host A 10.0.0.3\n")
+        .unwrap();
+
+    let lexer = Lexer::new(
+        "$TTL 3600
+@   IN  SOA ns hostmaster 2017101601 7200 600 3600000 60
+$INCLUDE hosts.zone
+after   A 10.0.0.4
+$INCLUDE hosts.zone other
+",
+    );
+
+    let records = Parser::with_include_dir(include_dir.clone())
+        .parse(lexer, Some(Name::from_labels(vec!["example", "com"])));
+    fs::remove_dir_all(&include_dir).unwrap();
+    if records.is_err() {
+        panic!("failed to parse: {:?}", records.err())
+    }
+
+    let (origin, records) = records.unwrap();
+    let authority = Authority::new(origin, records, ZoneType::Master, false, false);
+
+    let a = |name: &str, octet: u8| {
+        assert_eq!(
+            lookup_rdatas(&authority, name, RecordType::A),
+            vec![RData::A(Ipv4Addr::new(10, 0, 0, octet))],
+            "{}",
+            name
+        );
+    };
+    a("www.example.com.", 1);
+    a("mail.example.com.", 2);
+    a("host.deeper.mail.example.com.", 3);
+    // the origin of the including file is not changed by the included one
+    a("after.example.com.", 4);
+    a("www.other.example.com.", 1);
+    a("mail.other.example.com.", 2);
+    a("host.deeper.mail.other.example.com.", 3);
+
+    // a missing file is an error
+    let lexer = Lexer::new("$INCLUDE does-not-exist.zone\n");
+    assert!(
+        Parser::with_include_dir(include_dir)
+            .parse(lexer, Some(Name::from_labels(vec!["example", "com"])))
+            .is_err()
+    );
+}
+
+#[test]
+fn test_escapes_and_lists() {
+    let lexer = Lexer::new(
+        "@   IN  SOA ( ns hostmaster
+                  2017101601 7200 600 3600000 60 )
+        NS  ns
+semi    TXT     a\\059b \"with spaces; and \\\"quotes\\\"\" \\065\\ B
+list    TXT     ( \"first string\"
+                  \"second string\" )
+list    HINFO   \"Some CPU\" ( \"Some OS\" )
+a\\.b   A       10.0.0.1",
+    );
+
+    let records = Parser::new().parse(lexer, Some(Name::from_labels(vec!["example", "com"])));
+    if records.is_err() {
+        panic!("failed to parse: {:?}", records.err())
+    }
+
+    let (origin, records) = records.unwrap();
+    let authority = Authority::new(origin, records, ZoneType::Master, false, false);
+
+    match lookup_rdatas(&authority, "example.com.", RecordType::SOA)[0] {
+        RData::SOA(ref soa) => {
+            assert_eq!(soa.serial(), 2017101601);
+            assert_eq!(soa.minimum(), 60);
+        }
+        ref rdata => panic!("Not an SOA record: {:?}", rdata), // valid panic, test code
+    }
+
+    assert_eq!(
+        lookup_rdatas(&authority, "semi.example.com.", RecordType::TXT),
+        vec![
+            RData::TXT(rdata::TXT::new(vec![
+                "a;b".to_string(),
+                "with spaces; and \"quotes\"".to_string(),
+                "A B".to_string(),
+            ])),
+        ]
+    );
+    assert_eq!(
+        lookup_rdatas(&authority, "list.example.com.", RecordType::TXT),
+        vec![
+            RData::TXT(rdata::TXT::new(
+                vec!["first string".to_string(), "second string".to_string()],
+            )),
+        ]
+    );
+    assert_eq!(
+        lookup_rdatas(&authority, "list.example.com.", RecordType::HINFO),
+        vec![
+            RData::HINFO(rdata::HINFO::new("Some CPU".to_string(), "Some OS".to_string())),
+        ]
+    );
+
+    // an escaped dot is part of the label
+    let name = Name::from_labels(vec!["a.b", "example", "com"]);
+    assert_eq!(
+        authority
+            .lookup(&name, RecordType::A, false, SupportedAlgorithms::new())
+            .len(),
+        1
+    );
+}
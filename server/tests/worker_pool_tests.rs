@@ -0,0 +1,49 @@
+extern crate trust_dns_server;
+
+use std::sync::{Arc, Barrier};
+use std::sync::mpsc::channel;
+
+use trust_dns_server::server::WorkerPool;
+
+#[test]
+fn test_no_threads() {
+    assert!(WorkerPool::new(0, 1).is_err());
+}
+
+#[test]
+fn test_execute() {
+    let pool = WorkerPool::new(2, 4).expect("could not create pool");
+    assert_eq!(pool.threads(), 2);
+    assert_eq!(pool.queue_depth(), 4);
+
+    let (sender, receiver) = channel();
+    for i in 0..4 {
+        let sender = sender.clone();
+        assert!(pool.try_execute(move || sender.send(i).unwrap()));
+    }
+
+    let mut results = receiver.iter().take(4).collect::<Vec<usize>>();
+    results.sort();
+    assert_eq!(results, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn test_backpressure() {
+    let pool = WorkerPool::new(1, 1).expect("could not create pool");
+
+    // block the only worker until the queue has been filled
+    let barrier = Arc::new(Barrier::new(2));
+    let worker_barrier = barrier.clone();
+    let (started, wait_started) = channel();
+    assert!(pool.try_execute(move || {
+        started.send(()).unwrap();
+        worker_barrier.wait();
+    }));
+    wait_started.recv().unwrap();
+
+    // one job fits in the queue, the next is rejected
+    assert!(pool.try_execute(|| ()));
+    assert!(!pool.try_execute(|| ()));
+
+    barrier.wait();
+}